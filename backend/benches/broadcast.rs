@@ -0,0 +1,51 @@
+/*
+ * File: benches/broadcast.rs
+ * Purpose: Benchmarks for relaying a message to a document's subscribers
+ *
+ * Broadcasts an operation to one document through the server, with ten
+ * subscribers per document at 1k and at 10k connections. Broadcasting
+ * looks up only the document's subscribers, so both take about as long.
+ */
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use crdt_editor_backend::{
+    crdt::{Operation, Position},
+    websocket::{message::OperationMessage, EditorServer, Message, MessageType, ServerConfig},
+};
+
+const SUBSCRIBERS_PER_DOCUMENT: usize = 10;
+
+fn bench_broadcast(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let operation = OperationMessage::new(Operation::insert("writer".to_string(), 'x', Position::new(vec![1])), "doc-0".to_string());
+    let message = Message::new(MessageType::Operation, "writer".to_string(), serde_json::to_value(&operation).unwrap());
+
+    let mut group = c.benchmark_group("broadcast_to_10_subscribers");
+    for connections in [1_000, 10_000] {
+        let documents = connections / SUBSCRIBERS_PER_DOCUMENT;
+        let server = EditorServer::new(ServerConfig::default());
+        let mut subscribers = Vec::new();
+        runtime.block_on(async {
+            for i in 0..connections {
+                let document_id = format!("doc-{}", i % documents);
+                let frames = server.attach_subscriber("default", &format!("client-{}", i), &document_id, 16).await;
+                if i % documents == 0 {
+                    subscribers.push(frames);
+                }
+            }
+        });
+
+        group.bench_function(format!("{}_connections_{}_documents", connections, documents), |b| {
+            b.iter(|| {
+                runtime.block_on(server.broadcast_to_subscribers("default", "doc-0", &message));
+                for frames in &mut subscribers {
+                    frames.try_recv().unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_broadcast);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+/*
+ * File: benches/group_commit.rs
+ * Purpose: Benchmarks for appending operations to the SQLite store
+ *
+ * Appends 1000 operations one call at a time to a database file and
+ * flushes them, committing every write on its own and in groups. Each
+ * commit syncs the write-ahead log, so grouped commits take a fraction
+ * of the time; the throughput is reported in appends per second.
+ */
+
+use std::time::Duration;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use crdt_editor_backend::{
+    fixtures::DocumentBuilder,
+    storage::{DocumentStore, GroupCommit, SqliteStore},
+};
+
+const APPENDS: usize = 1000;
+
+fn bench_group_commit(c: &mut Criterion) {
+    let operations = DocumentBuilder::with_text("x".repeat(APPENDS)).operations();
+    let mut group = c.benchmark_group("append_1000_operations");
+    group.throughput(Throughput::Elements(APPENDS as u64));
+    group.sample_size(10);
+    let configs = [
+        ("unbatched", GroupCommit { max_batch: 1, window: Duration::ZERO, ..Default::default() }),
+        ("batched", GroupCommit { window: Duration::ZERO, ..Default::default() }),
+        ("batched_2ms_window", GroupCommit::default()),
+    ];
+    for (name, commit) in configs {
+        let path = std::env::temp_dir().join(format!("coedit-bench-{}.db", uuid::Uuid::new_v4()));
+        let store = SqliteStore::open_with(&path, commit, None).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for operation in &operations {
+                    store.append_ops("default/bench", std::slice::from_ref(operation)).unwrap();
+                }
+                store.flush().unwrap();
+            })
+        });
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_group_commit);
+criterion_main!(benches);
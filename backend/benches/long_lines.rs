@@ -0,0 +1,58 @@
+/*
+ * File: benches/long_lines.rs
+ * Purpose: Stress benchmarks for documents made of one very long line
+ *
+ * Edits a 200k-character single-line document, like minified JSON, in the
+ * middle of the line, and maps offsets to lines and columns. Compares the
+ * line index with scanning the characters the way documents used to.
+ */
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use crdt_editor_backend::crdt::{Document, LineIndex, Operation, Position};
+
+const LENGTH: usize = 200_000;
+
+fn bench_long_lines(c: &mut Criterion) {
+    let positions = Position::spread(LENGTH);
+    let mut doc = Document::new("minified".to_string());
+    for (i, position) in positions.iter().enumerate() {
+        let character = if i % 50 == 0 { ',' } else { 'x' };
+        doc.apply(Operation::insert("seed".to_string(), character, position.clone()));
+    }
+    let flags: Vec<(bool, bool)> = (0..LENGTH).map(|i| (true, i == LENGTH - 1)).collect();
+    let index = LineIndex::build(flags.iter().copied());
+
+    let mut group = c.benchmark_group("long_line_200k_characters");
+    // A deep position mid-line, as many concurrent edits at one spot leave
+    let mut deep = positions[LENGTH / 2 - 1].clone();
+    for _ in 0..64 {
+        deep = Position::between(&deep, &positions[LENGTH / 2]);
+    }
+    group.bench_function("insert_and_delete_mid_line", |b| {
+        b.iter_batched(
+            || doc.clone(),
+            |mut doc| {
+                let applied = doc.apply_operation(Operation::insert("bench".to_string(), 'y', deep.clone())).unwrap();
+                doc.apply_operation(Operation::delete("bench".to_string(), deep.clone())).unwrap();
+                // Returned, so dropping the copy isn't timed
+                (doc, applied.index)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("line_column_index", |b| {
+        b.iter(|| index.line_column(black_box(LENGTH * 3 / 4)))
+    });
+    group.bench_function("line_column_scan", |b| {
+        b.iter(|| {
+            let offset = black_box(LENGTH * 3 / 4);
+            let line = flags[..offset].iter().filter(|(_, newline)| *newline).count();
+            let start = flags[..offset].iter().rposition(|(_, newline)| *newline).map_or(0, |i| i + 1);
+            (line, offset - start)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_long_lines);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+/*
+ * File: benches/subscriptions.rs
+ * Purpose: Benchmarks for finding a document's subscribers
+ *
+ * Compares the subscriber index against scanning every connected client,
+ * at 10k connections spread over 1k documents.
+ */
+
+use std::collections::{HashMap, HashSet};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use crdt_editor_backend::websocket::SubscriptionIndex;
+
+const CONNECTIONS: usize = 10_000;
+const DOCUMENTS: usize = 1_000;
+
+fn client_id(i: usize) -> String {
+    format!("client-{}", i)
+}
+
+fn document_id(i: usize) -> String {
+    format!("doc-{}", i % DOCUMENTS)
+}
+
+fn bench_subscribers(c: &mut Criterion) {
+    let mut index = SubscriptionIndex::new();
+    let mut scan: HashMap<String, HashSet<String>> = HashMap::new();
+    for i in 0..CONNECTIONS {
+        index.join("default", &document_id(i), &client_id(i));
+        scan.entry(client_id(i)).or_default().insert(document_id(i));
+    }
+    let target = document_id(DOCUMENTS / 2);
+
+    let mut group = c.benchmark_group("subscribers_10k_connections_1k_documents");
+    group.bench_function("index", |b| {
+        b.iter(|| index.subscribers("default", black_box(&target)).count())
+    });
+    group.bench_function("full_scan", |b| {
+        b.iter(|| scan.values().filter(|documents| documents.contains(black_box(&target))).count())
+    });
+    group.finish();
+
+    c.bench_function("join_and_remove_client", |b| {
+        let mut i = CONNECTIONS;
+        b.iter(|| {
+            let client = client_id(i);
+            index.join("default", &document_id(i), &client);
+            index.remove_client(&client);
+            i += 1;
+        })
+    });
+}
+
+criterion_group!(benches, bench_subscribers);
+criterion_main!(benches);
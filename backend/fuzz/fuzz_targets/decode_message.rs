@@ -0,0 +1,58 @@
+/*
+ * File: fuzz/fuzz_targets/decode_message.rs
+ * Purpose: Fuzz target for decoding client frames
+ *
+ * Feeds arbitrary bytes through the path a client frame takes on the
+ * server: decoding the envelope, decoding and checking the payload for its
+ * message type, masking it for the logs, and replying with an error that
+ * must pass outbound validation. Asserts nothing panics and decoded
+ * messages stay proportional to the frame they came from.
+ *
+ *   cargo +nightly fuzz run decode_message -- -max_len=65536 -rss_limit_mb=512
+ */
+
+#![no_main]
+
+use std::sync::OnceLock;
+use libfuzzer_sys::fuzz_target;
+use crdt_editor_backend::{
+    security::Redactor,
+    websocket::{validation::validate_outbound, Message},
+};
+
+/// Slack for fields a decoded message gains, such as defaults
+const SIZE_SLACK: usize = 256;
+
+fn redactor() -> &'static Redactor {
+    static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+    REDACTOR.get_or_init(Redactor::default)
+}
+
+fuzz_target!(|data: &[u8]| {
+    // Transports only deliver text frames
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let message = match Message::decode(text) {
+        Ok(message) => message,
+        Err(_) => {
+            let _ = redactor().redact_raw(text);
+            return;
+        }
+    };
+    let _ = message.check_request();
+    let _ = redactor().redact_message(&message);
+
+    let encoded = serde_json::to_string(&message).expect("decoded messages encode");
+    assert!(
+        encoded.len() <= 2 * text.len() + SIZE_SLACK,
+        "{} byte frame decoded into a {} byte message",
+        text.len(),
+        encoded.len()
+    );
+    assert!(Message::decode(&encoded).is_ok(), "re-encoded message fails to decode");
+
+    let reply = message.error_reply("server".to_string(), "Invalid request".to_string());
+    let reply_encoded = serde_json::to_string(&reply).expect("replies encode");
+    assert_eq!(validate_outbound(&reply, &reply_encoded), Ok(()));
+});
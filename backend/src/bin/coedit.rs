@@ -0,0 +1,276 @@
+/*
+ * File: src/bin/coedit.rs
+ * Purpose: Self-contained collaborative editor server
+ *
+ * `coedit serve` runs the WebSocket server and the editor frontend on one
+ * port. Built with the `embed-assets` feature, the frontend is compiled
+ * into the binary, so a single file is all a deployment needs:
+ *
+ *   cd frontend && npm run build
+ *   cargo build --release --features embed-assets --bin coedit
+ *
+ * Usage: coedit serve|doctor [--host ADDR] [--port N] [--assets DIR] [--no-spa-fallback] [--welcome] [--database FILE]
+ *        coedit tail <document> [--host ADDR] [--port N] [--url URL]
+ *        coedit sync <dir> [--host ADDR] [--port N] [--url URL]
+ *
+ * `--assets` serves a frontend build from disk instead of the embedded one.
+ * `--welcome` seeds every workspace with the welcome tour and keyboard
+ * shortcuts when the server starts. `--database` keeps documents in a
+ * SQLite database file, created if needed, and loads them on start;
+ * without it, documents live in memory only. With `COEDIT_MASTER_KEYS`
+ * set, the database is encrypted with those master keys, which also seal
+ * session and share tokens.
+ * `coedit doctor` runs the self-check `serve` runs before binding and
+ * prints every result; it exits with 1 if the server would refuse to start.
+ * `coedit tail` follows a document on a running server through the client
+ * SDK and prints each change as a JSON line, for debugging sync issues
+ * and for shell tooling. `coedit sync` keeps the `.md` and `.txt` files of
+ * a directory in step with the documents named after them, in both
+ * directions, so they can be edited in any local editor. `--url` names the
+ * WebSocket URL, with tenant path and key, instead of the host and port.
+ */
+
+use std::{path::{Path, PathBuf}, process::ExitCode, sync::Arc};
+use tokio_util::sync::CancellationToken;
+use crdt_editor_backend::{
+    client::{sync_directory, websocket_connector, ContentChanges, EditorClient, ReconnectConfig, Replica, SyncConfig},
+    security::{KeyProvider, StaticKeyProvider, TokenConfig, TokenSealer, MASTER_KEYS_ENV},
+    storage::{SqliteStore, StorageConfig},
+    websocket::{diagnose, AssetSource, EditorServer, SeedConfig, ServerConfig, StaticConfig},
+};
+
+const USAGE: &str = "Usage: coedit serve|doctor [--host ADDR] [--port N] [--assets DIR] [--no-spa-fallback] [--welcome] [--database FILE]
+       coedit tail <document> [--host ADDR] [--port N] [--url URL]
+       coedit sync <dir> [--host ADDR] [--port N] [--url URL]";
+
+/// Author of the replica `coedit tail` follows a document with; it never
+/// edits
+const TAIL_AUTHOR: &str = "coedit-tail";
+
+/// Prefix of the authors `coedit sync` edits documents as; every run
+/// appends its own ID, since it starts from fresh replicas
+const SYNC_AUTHOR: &str = "coedit-sync";
+
+/// What to do with the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Serve,
+    Doctor,
+    /// Follow a document on a running server
+    Tail(String),
+    /// Keep the files of a directory in step with documents on a running
+    /// server
+    Sync(PathBuf),
+}
+
+impl Command {
+    /// Check whether the command talks to a running server as a client
+    fn is_client(&self) -> bool {
+        matches!(self, Self::Tail(_) | Self::Sync(_))
+    }
+}
+
+/// Command line options of `coedit`
+struct Options {
+    command: Command,
+    host: String,
+    port: u16,
+    assets: Option<AssetSource>,
+    spa_fallback: bool,
+    welcome: bool,
+    database: Option<PathBuf>,
+    url: Option<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let command = match args.next().as_deref() {
+            Some("serve") => Command::Serve,
+            Some("doctor") => Command::Doctor,
+            Some("tail") => Command::Tail(args.next().ok_or("tail needs a document")?),
+            Some("sync") => Command::Sync(args.next().ok_or("sync needs a directory")?.into()),
+            Some(other) => return Err(format!("Unknown command: {}", other)),
+            None => return Err("Missing command".to_string()),
+        };
+
+        let defaults = ServerConfig::default();
+        let mut options = Self {
+            command,
+            host: defaults.host,
+            port: defaults.port,
+            assets: default_assets(),
+            spa_fallback: true,
+            welcome: false,
+            database: None,
+            url: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--host" => options.host = args.next().ok_or("--host needs a value")?,
+                "--port" => {
+                    let value = args.next().ok_or("--port needs a value")?;
+                    options.port = value.parse().map_err(|_| format!("Invalid port: {}", value))?;
+                }
+                "--assets" => {
+                    let value = args.next().ok_or("--assets needs a value")?;
+                    options.assets = Some(AssetSource::Directory(value.into()));
+                }
+                "--no-spa-fallback" => options.spa_fallback = false,
+                "--welcome" if !options.command.is_client() => options.welcome = true,
+                "--database" if !options.command.is_client() => {
+                    options.database = Some(args.next().ok_or("--database needs a file")?.into());
+                }
+                "--url" if options.command.is_client() => {
+                    options.url = Some(args.next().ok_or("--url needs a value")?);
+                }
+                other => return Err(format!("Unexpected argument: {}", other)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Build the server configuration the options describe, opening the
+    /// database if one is named
+    fn config(&self) -> Result<ServerConfig, String> {
+        // Master keys seal tokens too, so they outlive restarts
+        let provider: Option<Arc<dyn KeyProvider>> = match std::env::var_os(MASTER_KEYS_ENV) {
+            Some(_) => Some(Arc::new(StaticKeyProvider::from_env().map_err(|e| e.to_string())?)),
+            None => None,
+        };
+        let tokens = match &provider {
+            Some(provider) => TokenConfig { sealer: Arc::new(TokenSealer::new(provider.clone())), ..Default::default() },
+            None => TokenConfig::default(),
+        };
+        let storage = match &self.database {
+            Some(path) => {
+                let store = match &provider {
+                    Some(provider) => SqliteStore::open_encrypted(path, provider.clone()),
+                    None => SqliteStore::open(path),
+                };
+                let store = store.map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+                StorageConfig { store: Arc::new(store), ..Default::default() }
+            }
+            None => StorageConfig::default(),
+        };
+        Ok(ServerConfig {
+            host: self.host.clone(),
+            port: self.port,
+            assets: StaticConfig {
+                source: self.assets.clone(),
+                spa_fallback: self.spa_fallback,
+                ..Default::default()
+            },
+            seed: if self.welcome { SeedConfig::system() } else { SeedConfig::default() },
+            storage,
+            tokens,
+            ..Default::default()
+        })
+    }
+
+    /// WebSocket URL of the server to follow or sync documents on
+    fn url(&self) -> String {
+        self.url.clone().unwrap_or_else(|| format!("ws://{}:{}/ws", self.host, self.port))
+    }
+}
+
+/// Print every change to a document, reconnecting whenever the connection
+/// drops, until the process is stopped
+async fn tail(document_id: &str, url: String) -> ExitCode {
+    let replica = Replica::new(document_id, TAIL_AUTHOR);
+    let client = EditorClient::start(websocket_connector(url), replica, ReconnectConfig::default());
+    let mut changes = ContentChanges::new(&client);
+    while let Some(change) = changes.next().await {
+        match serde_json::to_string(&change) {
+            Ok(line) => println!("{}", line),
+            Err(e) => {
+                eprintln!("Change could not be printed: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Sync the files of a directory until the process is interrupted
+async fn sync(dir: &Path, url: String) -> ExitCode {
+    if !dir.is_dir() {
+        eprintln!("Not a directory: {}", dir.display());
+        return ExitCode::FAILURE;
+    }
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let _ = tokio::signal::ctrl_c().await;
+            shutdown.cancel();
+        }
+    });
+    let author = format!("{}-{}", SYNC_AUTHOR, uuid::Uuid::new_v4());
+    println!("Syncing {} with {}", dir.display(), url);
+    match sync_directory(websocket_connector(url), dir, &author, SyncConfig::default(), shutdown).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Sync failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The embedded frontend when the binary carries one
+#[cfg(feature = "embed-assets")]
+fn default_assets() -> Option<AssetSource> {
+    Some(AssetSource::Embedded)
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn default_assets() -> Option<AssetSource> {
+    None
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match &options.command {
+        Command::Tail(document_id) => return tail(document_id, options.url()).await,
+        Command::Sync(dir) => return sync(dir, options.url()).await,
+        Command::Serve | Command::Doctor => {}
+    }
+
+    let config = match options.config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if options.command == Command::Doctor {
+        let report = diagnose(&config);
+        print!("{}", report);
+        return if report.has_errors() { ExitCode::FAILURE } else { ExitCode::SUCCESS };
+    }
+
+    if options.assets.is_none() {
+        log::warn!("No frontend assets configured; serving the WebSocket API only");
+    }
+    let server = EditorServer::new(config);
+
+    println!("CoEdit serving on http://{}:{}", options.host, options.port);
+    match server.run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Server failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,347 @@
+/*
+ * File: src/bin/conformance.rs
+ * Purpose: Protocol conformance suite for collaborative editing servers
+ *
+ * Connects to a running server over real WebSockets and exercises the
+ * documented protocol, printing a pass/fail report:
+ * - Handshake and welcome status
+ * - Edit relay between two clients
+ * - Request ID echo on replies
+ * - Error replies to malformed requests
+ * - Document state sync
+ *
+ * The suite only speaks the wire format, so it can check any server
+ * implementation, not just this crate's.
+ *
+ * Usage: conformance [--strict] [--json] [--timeout-ms N] [ws://host:port/ws]
+ *
+ * Exits non-zero when a required check fails, or when any check fails
+ * with `--strict`.
+ */
+
+use std::{process::ExitCode, time::Duration};
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message as Frame, MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:8080/ws";
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// Whether a failing check fails the run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    Required,
+    Optional,
+}
+
+/// Outcome of a single check
+#[derive(Debug, Serialize)]
+struct CheckResult {
+    name: &'static str,
+    level: Level,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// Full report of a conformance run
+#[derive(Debug, Serialize)]
+struct Report {
+    url: String,
+    passed: bool,
+    checks: Vec<CheckResult>,
+}
+
+/// Command line options
+struct Options {
+    url: String,
+    strict: bool,
+    json: bool,
+    timeout: Duration,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            url: DEFAULT_URL.to_string(),
+            strict: false,
+            json: false,
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--strict" => options.strict = true,
+                "--json" => options.json = true,
+                "--timeout-ms" => {
+                    let value = args.next().ok_or("--timeout-ms needs a value")?;
+                    let millis = value.parse().map_err(|_| format!("Invalid timeout: {}", value))?;
+                    options.timeout = Duration::from_millis(millis);
+                }
+                url if url.starts_with("ws://") || url.starts_with("wss://") => options.url = url.to_string(),
+                other => return Err(format!("Unexpected argument: {}", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// A protocol client connected to the server under test
+struct Client {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    client_id: String,
+    timeout: Duration,
+}
+
+impl Client {
+    /// Connect and wait for the welcome status
+    async fn connect(url: &str, timeout: Duration) -> Result<Self, String> {
+        let (socket, _) = tokio::time::timeout(timeout, connect_async(url))
+            .await
+            .map_err(|_| "Timed out connecting".to_string())?
+            .map_err(|e| format!("Failed to connect: {}", e))?;
+
+        let mut client = Self {
+            socket,
+            client_id: String::new(),
+            timeout,
+        };
+
+        let welcome = client.recv_until(|_| true).await?;
+        if welcome["type"] != "status" || welcome["payload"]["status"] != "connected" {
+            return Err(format!("Expected a connected status, got {}", welcome));
+        }
+        client.client_id = welcome["payload"]["client_id"]
+            .as_str()
+            .filter(|id| !id.is_empty())
+            .ok_or("Welcome status is missing client_id")?
+            .to_string();
+        Ok(client)
+    }
+
+    /// Send a message envelope
+    async fn send(&mut self, message_type: &str, payload: Value, request_id: Option<&str>) -> Result<(), String> {
+        let mut envelope = json!({
+            "type": message_type,
+            "client_id": &self.client_id,
+            "payload": payload,
+        });
+        if let Some(request_id) = request_id {
+            envelope["request_id"] = json!(request_id);
+        }
+        self.send_raw(envelope.to_string()).await
+    }
+
+    /// Send a raw text frame
+    async fn send_raw(&mut self, text: String) -> Result<(), String> {
+        self.socket
+            .send(Frame::Text(text))
+            .await
+            .map_err(|e| format!("Failed to send: {}", e))
+    }
+
+    /// Wait for the first message matching the predicate, skipping others
+    async fn recv_until(&mut self, matches: impl Fn(&Value) -> bool) -> Result<Value, String> {
+        let wait = async {
+            while let Some(frame) = self.socket.next().await {
+                match frame.map_err(|e| format!("Connection error: {}", e))? {
+                    Frame::Text(text) => {
+                        let message: Value = serde_json::from_str(&text)
+                            .map_err(|e| format!("Server sent invalid JSON: {}", e))?;
+                        if matches(&message) {
+                            return Ok(message);
+                        }
+                    }
+                    Frame::Close(_) => return Err("Server closed the connection".to_string()),
+                    _ => {}
+                }
+            }
+            Err("Server closed the connection".to_string())
+        };
+
+        tokio::time::timeout(self.timeout, wait)
+            .await
+            .map_err(|_| "Timed out waiting for a reply".to_string())?
+    }
+
+    /// Wait for a reply carrying the given request ID
+    async fn reply_to(&mut self, request_id: &str) -> Result<Value, String> {
+        self.recv_until(|message| message["request_id"] == request_id).await
+    }
+}
+
+/// Build an insert operation in the documented wire format
+fn insert_operation(client_id: &str, character: char, path: u32) -> Value {
+    json!({
+        "Insert": {
+            "client_id": client_id,
+            "character": character,
+            "position": { "path": [path], "is_end": false },
+            "timestamp": { "logical_clock": 1, "client_id": client_id },
+        }
+    })
+}
+
+/// Expect an `error` message
+fn expect_error(reply: &Value) -> Result<(), String> {
+    match reply["type"].as_str() {
+        Some("error") if reply["payload"].is_string() => Ok(()),
+        _ => Err(format!("Expected an error, got {}", reply)),
+    }
+}
+
+async fn check_handshake(options: &Options) -> Result<(), String> {
+    Client::connect(&options.url, options.timeout).await.map(|_| ())
+}
+
+async fn check_edit_relay(options: &Options) -> Result<(), String> {
+    let document_id = format!("conformance-{}", Uuid::new_v4());
+    let mut author = Client::connect(&options.url, options.timeout).await?;
+    let mut peer = Client::connect(&options.url, options.timeout).await?;
+
+    // Operations are relayed to the clients that opened the document
+    let created = insert_operation(&author.client_id, 'a', 1);
+    author
+        .send("operation", json!({ "document_id": &document_id, "operation": &created }), Some("conformance-create"))
+        .await?;
+    author.reply_to("conformance-create").await?;
+    peer.send("getDocument", json!({ "document_id": &document_id }), Some("conformance-open")).await?;
+    peer.reply_to("conformance-open").await?;
+
+    let operation = insert_operation(&author.client_id, 'b', 2);
+    author
+        .send("operation", json!({ "document_id": &document_id, "operation": &operation }), None)
+        .await?;
+
+    let relayed = peer
+        .recv_until(|message| message["type"] == "operation" && message["payload"]["document_id"] == document_id.as_str())
+        .await?;
+    if relayed["payload"]["operation"] != operation {
+        return Err(format!("Relayed operation differs: {}", relayed["payload"]["operation"]));
+    }
+    Ok(())
+}
+
+async fn check_request_id_echo(options: &Options) -> Result<(), String> {
+    let mut client = Client::connect(&options.url, options.timeout).await?;
+    client
+        .send("operation", json!({ "document_id": "conformance" }), Some("conformance-echo"))
+        .await?;
+    expect_error(&client.reply_to("conformance-echo").await?)
+}
+
+async fn check_invalid_playback(options: &Options) -> Result<(), String> {
+    let mut client = Client::connect(&options.url, options.timeout).await?;
+    client
+        .send(
+            "playbackRequest",
+            json!({ "document_id": "conformance", "ops_per_second": -1.0 }),
+            Some("conformance-playback"),
+        )
+        .await?;
+    expect_error(&client.reply_to("conformance-playback").await?)
+}
+
+async fn check_malformed_frame(options: &Options) -> Result<(), String> {
+    let mut client = Client::connect(&options.url, options.timeout).await?;
+    client.send_raw("not json".to_string()).await?;
+
+    // The connection must survive and keep answering
+    client
+        .send("operation", json!({ "document_id": "conformance" }), Some("conformance-after-malformed"))
+        .await?;
+    expect_error(&client.reply_to("conformance-after-malformed").await?)
+}
+
+async fn check_sync(options: &Options) -> Result<(), String> {
+    let mut client = Client::connect(&options.url, options.timeout).await?;
+    let document_id = format!("conformance-{}", Uuid::new_v4());
+    client
+        .send("getDocument", json!({ "document_id": &document_id }), Some("conformance-sync"))
+        .await?;
+
+    let reply = client.reply_to("conformance-sync").await?;
+    match reply["type"].as_str() {
+        Some("documentState") if reply["payload"]["content"].is_string() => Ok(()),
+        Some("error") => Ok(()),
+        _ => Err(format!("Expected documentState or error, got {}", reply)),
+    }
+}
+
+/// Run every check against the server
+async fn run(options: &Options) -> Report {
+    let outcomes = vec![
+        ("handshake", Level::Required, check_handshake(options).await),
+        ("edit_relay", Level::Required, check_edit_relay(options).await),
+        ("request_id_echo", Level::Required, check_request_id_echo(options).await),
+        ("invalid_playback", Level::Required, check_invalid_playback(options).await),
+        ("malformed_frame", Level::Required, check_malformed_frame(options).await),
+        ("sync", Level::Optional, check_sync(options).await),
+    ];
+
+    let checks: Vec<CheckResult> = outcomes
+        .into_iter()
+        .map(|(name, level, outcome)| CheckResult {
+            name,
+            level,
+            passed: outcome.is_ok(),
+            detail: outcome.err(),
+        })
+        .collect();
+    let passed = checks
+        .iter()
+        .all(|check| check.passed || (check.level == Level::Optional && !options.strict));
+
+    Report {
+        url: options.url.clone(),
+        passed,
+        checks,
+    }
+}
+
+fn print_report(report: &Report) {
+    println!("Conformance report for {}", report.url);
+    for check in &report.checks {
+        let status = if check.passed { "PASS" } else { "FAIL" };
+        let level = if check.level == Level::Optional { " (optional)" } else { "" };
+        match &check.detail {
+            Some(detail) => println!("{}  {}{}: {}", status, check.name, level, detail),
+            None => println!("{}  {}{}", status, check.name, level),
+        }
+    }
+
+    let passed = report.checks.iter().filter(|check| check.passed).count();
+    println!("{}/{} checks passed", passed, report.checks.len());
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: conformance [--strict] [--json] [--timeout-ms N] [ws://host:port/ws]");
+            return ExitCode::from(2);
+        }
+    };
+
+    let report = run(&options).await;
+    if options.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize report: {}", e),
+        }
+    } else {
+        print_report(&report);
+    }
+
+    if report.passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ * File: src/bin/usage_preview.rs
+ * Purpose: Show exactly what an anonymous usage report contains
+ *
+ * Prints the JSON body a server would post to its telemetry endpoint,
+ * for the given counts, without sending anything. Usage reporting is off
+ * unless `TelemetryConfig::enabled` is set.
+ *
+ * Usage: usage_preview [--documents N] [--peak-clients N]
+ */
+
+use std::process::ExitCode;
+use crdt_editor_backend::telemetry::UsageReport;
+
+/// Command line options
+struct Options {
+    documents: usize,
+    peak_clients: usize,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            documents: 0,
+            peak_clients: 0,
+        };
+
+        while let Some(arg) = args.next() {
+            let target = match arg.as_str() {
+                "--documents" => &mut options.documents,
+                "--peak-clients" => &mut options.peak_clients,
+                other => return Err(format!("Unexpected argument: {}", other)),
+            };
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            *target = value.parse().map_err(|_| format!("Invalid count: {}", value))?;
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: usage_preview [--documents N] [--peak-clients N]");
+            return ExitCode::from(2);
+        }
+    };
+
+    let report = UsageReport::new(options.documents, options.peak_clients);
+    match serde_json::to_string_pretty(&report) {
+        Ok(body) => {
+            println!("{}", body);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to serialize usage report: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,220 @@
+/*
+ * File: src/blocks/checklist.rs
+ * Purpose: Checklists, kept as conflict-free ordered lists
+ *
+ * A checklist block marks where a named checklist of the document goes:
+ *
+ *   ```checklist groceries
+ *   ```
+ *
+ * Lines between the fences are ignored. The items live next to the text,
+ * in an `OrderedList` of item texts plus a last-writer-wins check mark per
+ * item, so concurrent reorders, renames and toggles all survive. Clients
+ * change a checklist with `ChecklistOperation`s, which the server applies
+ * and relays like text operations.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::crdt::{list::write_register, ElementId, ListOperation, OrderedList, Timestamp};
+
+/// Language tag of the fence opening a checklist block
+pub const CHECKLIST_TAG: &str = "checklist";
+
+/// Most items, removed ones included, a checklist may hold
+pub const MAX_CHECKLIST_ITEMS: usize = 1000;
+
+/// An item of a checklist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub id: ElementId,
+    pub text: String,
+    pub checked: bool,
+}
+
+/// An operation on a checklist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChecklistOperation {
+    /// Add, move, rename or remove an item
+    Item { operation: ListOperation<String> },
+    /// Check or uncheck an item
+    Check { id: ElementId, checked: bool, stamp: Timestamp },
+}
+
+impl ChecklistOperation {
+    /// Get the text the operation gives an item, if any
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            ChecklistOperation::Item { operation: ListOperation::Insert { value, .. } }
+            | ChecklistOperation::Item { operation: ListOperation::Update { value, .. } } => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A checklist that merges operations from any replica
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Checklist {
+    items: OrderedList<String>,
+    checks: BTreeMap<ElementId, Option<(bool, Timestamp)>>,
+    /// Newest check stamp seen, to stamp local checks after it
+    latest: Option<Timestamp>,
+}
+
+impl Checklist {
+    /// Create an empty checklist
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an operation from any replica. Returns whether the checklist
+    /// changed.
+    pub fn apply(&mut self, operation: ChecklistOperation) -> bool {
+        match operation {
+            ChecklistOperation::Item { operation } => self.items.apply(operation),
+            ChecklistOperation::Check { id, checked, stamp } => {
+                if self.latest.as_ref().is_none_or(|latest| stamp > *latest) {
+                    self.latest = Some(stamp.clone());
+                }
+                write_register(self.checks.entry(id).or_default(), checked, &stamp)
+            }
+        }
+    }
+
+    /// Add an unchecked item at an index, on behalf of a client
+    pub fn add(&mut self, client_id: &str, index: usize, text: &str) -> ChecklistOperation {
+        ChecklistOperation::Item { operation: self.items.insert(client_id, index, text.to_string()) }
+    }
+
+    /// Move an item to an index of the checklist without it
+    pub fn move_item(&mut self, client_id: &str, id: &ElementId, index: usize) -> Option<ChecklistOperation> {
+        let operation = self.items.move_to(client_id, id, index)?;
+        Some(ChecklistOperation::Item { operation })
+    }
+
+    /// Change the text of an item
+    pub fn rename(&mut self, client_id: &str, id: &ElementId, text: &str) -> Option<ChecklistOperation> {
+        let operation = self.items.update(client_id, id, text.to_string())?;
+        Some(ChecklistOperation::Item { operation })
+    }
+
+    /// Check or uncheck an item
+    pub fn set_checked(&mut self, client_id: &str, id: &ElementId, checked: bool) -> Option<ChecklistOperation> {
+        self.items.get(id)?;
+        let mut stamp = Timestamp::new(client_id.to_string());
+        if let Some(latest) = &self.latest {
+            stamp.update(latest);
+        }
+        stamp.increment();
+        let operation = ChecklistOperation::Check { id: id.clone(), checked, stamp };
+        self.apply(operation.clone());
+        Some(operation)
+    }
+
+    /// Remove an item
+    pub fn remove(&mut self, id: &ElementId) -> Option<ChecklistOperation> {
+        let operation = self.items.remove(id)?;
+        Some(ChecklistOperation::Item { operation })
+    }
+
+    /// Get the items, in order
+    pub fn items(&self) -> Vec<ChecklistItem> {
+        self.items
+            .items()
+            .into_iter()
+            .map(|(id, text)| ChecklistItem {
+                id: id.clone(),
+                text: text.clone(),
+                checked: self.checks.get(id).and_then(|check| check.as_ref()).is_some_and(|(checked, _)| *checked),
+            })
+            .collect()
+    }
+
+    /// Get the number of items
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check whether the checklist has no items
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Get operations that rebuild this checklist in an empty replica
+    pub fn operations(&self) -> Vec<ChecklistOperation> {
+        let items = self.items.operations().into_iter().map(|operation| ChecklistOperation::Item { operation });
+        let checks = self.checks.iter().filter_map(|(id, check)| {
+            check.as_ref().map(|(checked, stamp)| ChecklistOperation::Check {
+                id: id.clone(),
+                checked: *checked,
+                stamp: stamp.clone(),
+            })
+        });
+        items.chain(checks).collect()
+    }
+}
+
+/// Checklists of a tenant's documents, by document and checklist name
+#[derive(Debug, Default)]
+pub struct ChecklistTable {
+    checklists: RwLock<HashMap<(String, String), Checklist>>,
+}
+
+impl ChecklistTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an operation to a document's checklist, creating the
+    /// checklist if needed. Returns whether it changed, or None if the
+    /// checklist is full.
+    pub fn apply(&self, document_id: &str, name: &str, operation: ChecklistOperation) -> Option<bool> {
+        let mut checklists = self.checklists.write();
+        let checklist = checklists.entry((document_id.to_string(), name.to_string())).or_default();
+        let adds = matches!(operation, ChecklistOperation::Item { operation: ListOperation::Insert { .. } });
+        if adds && checklist.items.footprint() >= MAX_CHECKLIST_ITEMS {
+            return None;
+        }
+        Some(checklist.apply(operation))
+    }
+
+    /// Get a copy of a document's checklist; empty if it has none
+    pub fn checklist(&self, document_id: &str, name: &str) -> Checklist {
+        let key = (document_id.to_string(), name.to_string());
+        self.checklists.read().get(&key).cloned().unwrap_or_default()
+    }
+
+    /// Get copies of a document's checklists, by name
+    pub fn for_document(&self, document_id: &str) -> HashMap<String, Checklist> {
+        self.checklists
+            .read()
+            .iter()
+            .filter(|((document, _), _)| document == document_id)
+            .map(|((_, name), checklist)| (name.clone(), checklist.clone()))
+            .collect()
+    }
+
+    /// Forget the checklists of a removed document
+    pub fn remove_document(&self, document_id: &str) {
+        self.checklists.write().retain(|(document, _), _| document != document_id);
+    }
+
+    /// Remove a document's checklists to move them to another tenant
+    pub fn take_document(&self, document_id: &str) -> HashMap<String, Checklist> {
+        let checklists = self.for_document(document_id);
+        self.remove_document(document_id);
+        checklists
+    }
+
+    /// Add the checklists of a document moved in from another tenant
+    pub fn insert_document(&self, document_id: &str, checklists: HashMap<String, Checklist>) {
+        let mut table = self.checklists.write();
+        for (name, checklist) in checklists {
+            table.insert((document_id.to_string(), name), checklist);
+        }
+    }
+}
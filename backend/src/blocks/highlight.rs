@@ -0,0 +1,81 @@
+/*
+ * File: src/blocks/highlight.rs
+ * Purpose: Syntax highlighting of code blocks in HTML exports
+ *
+ * Code blocks tagged with a language are highlighted with syntect, using
+ * its bundled syntaxes and themes. Tags are matched against syntax names
+ * and file extensions, so both "rust" and "rs" work; blocks without a tag,
+ * or with one syntect doesn't know, are left plain.
+ *
+ * The highlighted code keeps the text byte for byte: every token becomes a
+ * `<span>` with inline colors, so exports need no stylesheet. The theme's
+ * background goes on the enclosing `<pre>`.
+ *
+ * Syntaxes and themes are loaded on first use, which takes a moment.
+ */
+
+use std::sync::OnceLock;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Color, Theme, ThemeSet},
+    html::{append_highlighted_html_for_styled_line, IncludeBackground},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use thiserror::Error;
+
+/// Theme used when a caller asks for highlighting without naming one
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Highlighting errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum HighlightError {
+    #[error("Unknown highlighting theme: {0}")]
+    UnknownTheme(String),
+}
+
+/// A highlighted code block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlighted {
+    /// Code as `<span>` elements, to place inside `<code>`
+    pub html: String,
+    /// CSS color of the theme's background
+    pub background: String,
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Get the names of the available themes, sorted
+pub fn theme_names() -> Vec<&'static str> {
+    themes().themes.keys().map(String::as_str).collect()
+}
+
+/// Look up a theme by name
+pub fn theme(name: &str) -> Result<&'static Theme, HighlightError> {
+    themes().themes.get(name).ok_or_else(|| HighlightError::UnknownTheme(name.to_string()))
+}
+
+/// Highlight code in a language, or get None when the language is unknown
+pub fn highlight(code: &str, language: &str, theme: &Theme) -> Option<Highlighted> {
+    let syntaxes = syntaxes();
+    let syntax = syntaxes.find_syntax_by_token(language)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut html = String::with_capacity(code.len() * 4);
+    for line in LinesWithEndings::from(code) {
+        let regions = highlighter.highlight_line(line, syntaxes).ok()?;
+        append_highlighted_html_for_styled_line(&regions, IncludeBackground::No, &mut html).ok()?;
+    }
+    let background = theme.settings.background.unwrap_or(Color::WHITE);
+    Some(Highlighted {
+        html,
+        background: format!("#{:02x}{:02x}{:02x}", background.r, background.g, background.b),
+    })
+}
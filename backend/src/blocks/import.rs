@@ -0,0 +1,525 @@
+/*
+ * File: src/blocks/import.rs
+ * Purpose: Import of outside text into the block structure of documents
+ *
+ * Formats:
+ * - text: the source, byte for byte
+ * - markdown: the source mapped onto document blocks
+ *
+ * Markdown maps like this:
+ * - Headings, paragraphs, quotes and lists stay text; underlined (setext)
+ *   headings become `#` headings
+ * - Fenced code blocks stay code blocks, `~~~` and longer fences become
+ *   "```" fences, and indented code blocks are fenced with their
+ *   indentation removed
+ * - Runs of task list items ("- [ ] item", "- [x] item") become checklist
+ *   blocks, named `tasks-1`, `tasks-2` and so on, with the items checked
+ *   as in the source. Nested task items are flattened into the run.
+ * - A task list right after a `<!-- checklist <name> -->` comment, as
+ *   Markdown export writes them, becomes the checklist of that name, if
+ *   the document doesn't use the name yet
+ *
+ * Tables, HTML, images, footnotes and front matter have no block of their
+ * own. They are kept as text and reported as `Unmapped`, so nothing of the
+ * source is lost and callers can tell users what needs a look.
+ */
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+use serde::{Deserialize, Serialize};
+use crate::{
+    blocks::{
+        checklist::{CHECKLIST_TAG, MAX_CHECKLIST_ITEMS},
+        export::{CHECKLIST_MARKER_END, CHECKLIST_MARKER_START},
+        parse, Block, Checklist, ChecklistOperation, FENCE,
+    },
+    crdt::{Document, Operation, Position},
+};
+
+/// Prefix of the names of checklists made from task lists
+const TASKS_PREFIX: &str = "tasks-";
+
+/// Import format of a source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ImportFormat {
+    #[default]
+    Text,
+    Markdown,
+}
+
+impl ImportFormat {
+    /// Get the name of the format, as used in `format=` parameters
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportFormat::Text => "text",
+            ImportFormat::Markdown => "markdown",
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(ImportFormat::Text),
+            "markdown" => Ok(ImportFormat::Markdown),
+            other => Err(format!("Unknown import format: {}", other)),
+        }
+    }
+}
+
+/// A Markdown construct without a block of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Construct {
+    Table,
+    Html,
+    Image,
+    Footnote,
+    FrontMatter,
+    /// A task item inside another list item, flattened into its checklist
+    NestedTask,
+}
+
+impl Construct {
+    /// Get the name of the construct, as reported
+    pub fn name(self) -> &'static str {
+        match self {
+            Construct::Table => "table",
+            Construct::Html => "html",
+            Construct::Image => "image",
+            Construct::Footnote => "footnote",
+            Construct::FrontMatter => "front_matter",
+            Construct::NestedTask => "nested_task",
+        }
+    }
+}
+
+impl fmt::Display for Construct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A construct of the source that was kept as text or flattened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unmapped {
+    /// Line of the source the construct starts on, from 1
+    pub line: usize,
+    pub construct: Construct,
+}
+
+/// A checklist made from a run of task list items
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedChecklist {
+    /// Name of the checklist, as in its block's fence
+    pub name: String,
+    /// Text of each item and whether it is checked, in order
+    pub items: Vec<(String, bool)>,
+}
+
+impl ImportedChecklist {
+    /// Get operations that fill an empty checklist with the items, on
+    /// behalf of a client
+    pub fn operations(&self, client_id: &str) -> Vec<ChecklistOperation> {
+        let mut checklist = Checklist::new();
+        let mut operations = Vec::new();
+        for (index, (text, checked)) in self.items.iter().enumerate() {
+            operations.push(checklist.add(client_id, index, text));
+            if *checked {
+                let id = checklist.items()[index].id.clone();
+                operations.extend(checklist.set_checked(client_id, &id, true));
+            }
+        }
+        operations
+    }
+}
+
+/// A source mapped onto document blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Import {
+    /// Document text of the source, checklist blocks included
+    pub content: String,
+    /// Checklists whose blocks the content holds
+    pub checklists: Vec<ImportedChecklist>,
+    /// Constructs that were kept as text or flattened, in source order
+    pub unmapped: Vec<Unmapped>,
+}
+
+impl Import {
+    /// Get the text operations appending the content to a document, after
+    /// its last visible character, on behalf of a client. A line break
+    /// goes first if the document doesn't end with one, so blocks start on
+    /// a line of their own.
+    pub fn operations(&self, document: &Document, client_id: &str) -> Vec<Operation> {
+        let mut text = String::new();
+        let last = document.visible_characters().last();
+        if !self.content.is_empty() && last.is_some_and(|(character, _)| character != '\n') {
+            text.push('\n');
+        }
+        text.push_str(&self.content);
+
+        let after = last.map(|(_, position)| position.clone()).unwrap_or_else(Position::start);
+        let positions = document.positions_after(&after, text.chars().count());
+        text.chars()
+            .zip(positions)
+            .map(|(character, position)| Operation::insert(client_id.to_string(), character, position))
+            .collect()
+    }
+}
+
+/// Import a source in a format
+pub fn import(source: &str, format: ImportFormat) -> Import {
+    import_with_names(source, format, BTreeSet::new())
+}
+
+/// Import a source in a format, to append to a document. Checklists get
+/// names the document's blocks don't use yet.
+pub fn import_into(document: &Document, source: &str, format: ImportFormat) -> Import {
+    import_with_names(source, format, checklist_names(&document.content()))
+}
+
+fn import_with_names(source: &str, format: ImportFormat, taken: BTreeSet<String>) -> Import {
+    match format {
+        ImportFormat::Text => Import { content: source.to_string(), ..Default::default() },
+        ImportFormat::Markdown => {
+            let source = source.replace("\r\n", "\n");
+            let mut taken = taken;
+            taken.extend(checklist_names(&source));
+            MarkdownImporter::new(&source, taken).run()
+        }
+    }
+}
+
+/// Names of the checklist blocks of document text
+fn checklist_names(content: &str) -> BTreeSet<String> {
+    parse(content)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Checklist { name } => Some(name),
+            Block::Text { .. } | Block::Code(_) => None,
+        })
+        .collect()
+}
+
+/// A fence opening a code block of the source
+struct OpenFence {
+    marker: char,
+    length: usize,
+}
+
+/// Maps Markdown lines onto document lines
+struct MarkdownImporter<'a> {
+    lines: Vec<&'a str>,
+    output: Vec<String>,
+    checklists: Vec<ImportedChecklist>,
+    unmapped: Vec<Unmapped>,
+    /// Checklist names in use
+    taken: BTreeSet<String>,
+    /// Names of the source's checklist markers, which new names avoid
+    reserved: BTreeSet<String>,
+    /// Whether the last line of text belonged to a list
+    in_list: bool,
+}
+
+impl<'a> MarkdownImporter<'a> {
+    fn new(source: &'a str, taken: BTreeSet<String>) -> Self {
+        let lines: Vec<&str> = source.split('\n').collect();
+        let reserved = lines.iter().filter_map(|line| checklist_marker(line)).map(str::to_string).collect();
+        Self {
+            lines,
+            output: Vec::new(),
+            checklists: Vec::new(),
+            unmapped: Vec::new(),
+            taken,
+            reserved,
+            in_list: false,
+        }
+    }
+
+    fn run(mut self) -> Import {
+        let mut index = self.front_matter();
+        while index < self.lines.len() {
+            index = self.block(index);
+        }
+        Import { content: self.output.join("\n"), checklists: self.checklists, unmapped: self.unmapped }
+    }
+
+    fn report(&mut self, index: usize, construct: Construct) {
+        self.unmapped.push(Unmapped { line: index + 1, construct });
+    }
+
+    /// Keep front matter as text. Returns the index of the first line
+    /// after it.
+    fn front_matter(&mut self) -> usize {
+        if self.lines.first() != Some(&"---") {
+            return 0;
+        }
+        let Some(end) = self.lines.iter().skip(1).position(|line| *line == "---" || *line == "...") else {
+            return 0;
+        };
+        self.report(0, Construct::FrontMatter);
+        let lines = self.lines[..end + 2].iter().map(|line| line.to_string());
+        self.output.extend(lines);
+        end + 2
+    }
+
+    /// Map the block starting at a line. Returns the index of the first
+    /// line after it.
+    fn block(&mut self, index: usize) -> usize {
+        let line = self.lines[index];
+        if let Some(fence) = opening_fence(line) {
+            self.in_list = false;
+            return self.fenced_code(index, fence);
+        }
+        if is_blank(line) {
+            self.output.push(line.to_string());
+            return index + 1;
+        }
+        if indentation(line) >= 4 && !self.in_list && self.follows_blank_line() {
+            return self.indented_code(index);
+        }
+        if let Some(name) = checklist_marker(line) {
+            self.in_list = true;
+            return self.tasks(index + 1, Some(name));
+        }
+        if task_item(line).is_some() {
+            self.in_list = true;
+            return self.tasks(index, None);
+        }
+        if is_table_row(line) && self.lines.get(index + 1).is_some_and(|next| is_table_separator(next)) {
+            self.in_list = false;
+            return self.table(index);
+        }
+        if let Some(level) = self.lines.get(index + 1).and_then(|next| setext_level(next)) {
+            if !self.in_list && list_item(line).is_none() && self.follows_blank_line() {
+                self.output.push(format!("{} {}", "#".repeat(level), line.trim()));
+                return index + 2;
+            }
+        }
+
+        let trimmed = line.trim_start();
+        if list_item(line).is_some() {
+            self.in_list = true;
+        } else if indentation(line) == 0 {
+            self.in_list = false;
+        }
+        if is_html(trimmed) {
+            self.report(index, Construct::Html);
+        } else if trimmed.starts_with("[^") && trimmed.contains("]:") {
+            self.report(index, Construct::Footnote);
+        }
+        if has_image(line) {
+            self.report(index, Construct::Image);
+        }
+        self.output.push(line.to_string());
+        index + 1
+    }
+
+    /// Whether the document so far ends with a blank line, or is empty
+    fn follows_blank_line(&self) -> bool {
+        self.output.last().is_none_or(|line| is_blank(line))
+    }
+
+    fn fenced_code(&mut self, index: usize, fence: OpenFence) -> usize {
+        let info = self.lines[index].trim_start()[fence.length..].trim();
+        self.output.push(if info.is_empty() { FENCE.to_string() } else { format!("{}{}", FENCE, info) });
+        let mut next = index + 1;
+        while next < self.lines.len() {
+            let line = self.lines[next];
+            next += 1;
+            if closes(line, &fence) {
+                self.output.push(FENCE.to_string());
+                return next;
+            }
+            self.output.push(line.to_string());
+        }
+        // Unclosed fences run to the end, as in documents
+        next
+    }
+
+    fn indented_code(&mut self, index: usize) -> usize {
+        let mut end = index;
+        let mut last_code = index;
+        while end < self.lines.len() && (is_blank(self.lines[end]) || indentation(self.lines[end]) >= 4) {
+            if !is_blank(self.lines[end]) {
+                last_code = end;
+            }
+            end += 1;
+        }
+        self.output.push(FENCE.to_string());
+        for line in &self.lines[index..=last_code] {
+            self.output.push(strip_indentation(line, 4).to_string());
+        }
+        self.output.push(FENCE.to_string());
+        last_code + 1
+    }
+
+    /// Map a run of task items, possibly empty, onto a checklist, named
+    /// by a marker if there was one
+    fn tasks(&mut self, index: usize, name: Option<&str>) -> usize {
+        let mut name = name;
+        let mut next = index;
+        let mut items = Vec::new();
+        while let Some((text, checked)) = self.lines.get(next).and_then(|line| task_item(line)) {
+            if indentation(self.lines[next]) > 0 {
+                self.report(next, Construct::NestedTask);
+            }
+            if items.len() == MAX_CHECKLIST_ITEMS {
+                self.checklist(name.take(), std::mem::take(&mut items));
+            }
+            items.push((text.to_string(), checked));
+            next += 1;
+        }
+        self.checklist(name, items);
+        next
+    }
+
+    /// Add a checklist block with the given name if it is free, or a new
+    /// one
+    fn checklist(&mut self, name: Option<&str>, items: Vec<(String, bool)>) {
+        let name = match name.filter(|name| !self.taken.contains(*name)) {
+            Some(name) => name.to_string(),
+            None => (1..)
+                .map(|number| format!("{}{}", TASKS_PREFIX, number))
+                .find(|name| !self.taken.contains(name) && !self.reserved.contains(name))
+                .unwrap_or_default(),
+        };
+        self.taken.insert(name.clone());
+        self.output.push(format!("{}{} {}", FENCE, CHECKLIST_TAG, name));
+        self.output.push(FENCE.to_string());
+        self.checklists.push(ImportedChecklist { name, items });
+    }
+
+    fn table(&mut self, index: usize) -> usize {
+        self.report(index, Construct::Table);
+        let mut next = index;
+        while next < self.lines.len() && is_table_row(self.lines[next]) {
+            self.output.push(self.lines[next].to_string());
+            next += 1;
+        }
+        next
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Columns of leading whitespace, with tabs to the next multiple of 4
+fn indentation(line: &str) -> usize {
+    let mut columns = 0;
+    for character in line.chars() {
+        match character {
+            ' ' => columns += 1,
+            '\t' => columns += 4 - columns % 4,
+            _ => break,
+        }
+    }
+    columns
+}
+
+/// Remove up to a number of columns of leading whitespace
+fn strip_indentation(line: &str, columns: usize) -> &str {
+    let mut removed = 0;
+    for (offset, character) in line.char_indices() {
+        if removed >= columns {
+            return &line[offset..];
+        }
+        match character {
+            ' ' => removed += 1,
+            '\t' => removed += 4 - removed % 4,
+            _ => return &line[offset..],
+        }
+    }
+    ""
+}
+
+fn opening_fence(line: &str) -> Option<OpenFence> {
+    if indentation(line) >= 4 {
+        return None;
+    }
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next().filter(|marker| *marker == '`' || *marker == '~')?;
+    let length = trimmed.chars().take_while(|character| *character == marker).count();
+    // Backtick fences can't have backticks in their info string
+    let info_ok = marker == '~' || !trimmed[length..].contains('`');
+    (length >= 3 && info_ok).then_some(OpenFence { marker, length })
+}
+
+fn closes(line: &str, fence: &OpenFence) -> bool {
+    let trimmed = line.trim();
+    indentation(line) < 4
+        && trimmed.chars().count() >= fence.length
+        && trimmed.chars().all(|character| character == fence.marker)
+}
+
+/// The text after a list marker, if the line is a list item
+fn list_item(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if let Some(rest) = trimmed.strip_prefix(['-', '*', '+']) {
+        return rest.strip_prefix(' ');
+    }
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 || digits > 9 {
+        return None;
+    }
+    trimmed[digits..].strip_prefix(['.', ')'])?.strip_prefix(' ')
+}
+
+/// The checklist a marker comment names
+fn checklist_marker(line: &str) -> Option<&str> {
+    let name = line.strip_prefix(CHECKLIST_MARKER_START)?.strip_suffix(CHECKLIST_MARKER_END)?;
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then_some(name)
+}
+
+/// The text of a task list item and whether it is checked
+fn task_item(line: &str) -> Option<(&str, bool)> {
+    let rest = list_item(line)?;
+    let (checked, text) = if let Some(text) = rest.strip_prefix("[ ]") {
+        (false, text)
+    } else if let Some(text) = rest.strip_prefix("[x]").or_else(|| rest.strip_prefix("[X]")) {
+        (true, text)
+    } else {
+        return None;
+    };
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((text.trim(), checked))
+}
+
+/// Heading level of a setext underline
+fn setext_level(line: &str) -> Option<usize> {
+    if indentation(line) >= 4 {
+        return None;
+    }
+    let trimmed = line.trim();
+    if !trimmed.is_empty() && trimmed.chars().all(|character| character == '=') {
+        Some(1)
+    } else if !trimmed.is_empty() && trimmed.chars().all(|character| character == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.contains('-') && trimmed.chars().all(|character| matches!(character, '|' | '-' | ':' | ' ' | '\t'))
+}
+
+fn is_html(trimmed: &str) -> bool {
+    trimmed.strip_prefix('<')
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|next| next.is_ascii_alphabetic() || next == '/' || next == '!')
+}
+
+fn has_image(line: &str) -> bool {
+    line.match_indices("![")
+        .any(|(offset, _)| line[offset..].find("](").is_some_and(|close| line[offset + close..].contains(')')))
+}
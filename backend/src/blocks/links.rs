@@ -0,0 +1,155 @@
+/*
+ * File: src/blocks/links.rs
+ * Purpose: Links between documents and the index of backlinks
+ *
+ * Text blocks link to other documents of the tenant wiki-style, by ID or
+ * slug, optionally with a label:
+ *
+ *   See [[meeting-notes]] and [[3f2a9c|the budget]].
+ *
+ * Links don't span lines, and fences turn them into plain code. Targets
+ * need not exist yet: a document created later already has its backlinks.
+ *
+ * The index keeps each document's outgoing links and, reversed, the
+ * documents linking to each target. It is updated incrementally: a change
+ * only marks its document, and the next lookup reads the links of the
+ * marked documents again and adjusts the index by the difference, so
+ * typing costs no more than a set insert.
+ */
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::blocks::{parse, Block};
+
+/// Marker opening a link
+pub const LINK_OPEN: &str = "[[";
+
+/// Marker closing a link
+pub const LINK_CLOSE: &str = "]]";
+
+/// A link from a document to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentLink {
+    /// ID or slug of the linked document
+    pub target: String,
+    /// Text shown instead of the target
+    pub label: Option<String>,
+}
+
+/// Find the links in document text, in order
+pub fn links(content: &str) -> Vec<DocumentLink> {
+    let mut found = Vec::new();
+    for block in parse(content) {
+        let Block::Text { text } = block else {
+            continue;
+        };
+        for line in text.lines() {
+            let mut rest = line;
+            while let Some(start) = rest.find(LINK_OPEN) {
+                let after = &rest[start + LINK_OPEN.len()..];
+                let Some(end) = after.find(LINK_CLOSE) else {
+                    break;
+                };
+                let (target, label) = match after[..end].split_once('|') {
+                    Some((target, label)) => (target.trim(), Some(label.trim().to_string())),
+                    None => (after[..end].trim(), None),
+                };
+                if !target.is_empty() && !target.contains(char::is_whitespace) {
+                    found.push(DocumentLink { target: target.to_string(), label });
+                }
+                rest = &after[end + LINK_CLOSE.len()..];
+            }
+        }
+    }
+    found
+}
+
+/// Outgoing links and backlinks of a document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentLinks {
+    pub document_id: String,
+    /// Documents this one links to, sorted
+    pub links: Vec<String>,
+    /// Documents linking to this one, sorted
+    pub backlinks: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    outgoing: HashMap<String, BTreeSet<String>>,
+    incoming: HashMap<String, BTreeSet<String>>,
+    /// Documents changed since their links were last read
+    stale: HashSet<String>,
+}
+
+/// Links between a tenant's documents, both ways
+#[derive(Debug, Default)]
+pub struct LinkIndex {
+    index: RwLock<Index>,
+}
+
+impl LinkIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a document changed, so its links are read again
+    pub fn touch(&self, document_id: &str) {
+        let mut index = self.index.write();
+        if !index.stale.contains(document_id) {
+            index.stale.insert(document_id.to_string());
+        }
+    }
+
+    /// Get the documents whose links are out of date
+    pub fn stale(&self) -> Vec<String> {
+        self.index.read().stale.iter().cloned().collect()
+    }
+
+    /// Set the documents a document links to, updating backlinks by the
+    /// difference. Links to itself are left out.
+    pub fn update(&self, document_id: &str, targets: BTreeSet<String>) {
+        let mut index = self.index.write();
+        index.stale.remove(document_id);
+        let mut targets = targets;
+        targets.remove(document_id);
+        let previous = index.outgoing.remove(document_id).unwrap_or_default();
+
+        for removed in previous.difference(&targets) {
+            if let Some(sources) = index.incoming.get_mut(removed) {
+                sources.remove(document_id);
+                if sources.is_empty() {
+                    index.incoming.remove(removed);
+                }
+            }
+        }
+        for added in targets.difference(&previous) {
+            index.incoming.entry(added.clone()).or_default().insert(document_id.to_string());
+        }
+        if !targets.is_empty() {
+            index.outgoing.insert(document_id.to_string(), targets);
+        }
+    }
+
+    /// Get a document's links and backlinks as last read
+    pub fn links(&self, document_id: &str) -> DocumentLinks {
+        let index = self.index.read();
+        let sorted = |sets: &HashMap<String, BTreeSet<String>>| {
+            sets.get(document_id).map(|set| set.iter().cloned().collect()).unwrap_or_default()
+        };
+        DocumentLinks {
+            document_id: document_id.to_string(),
+            links: sorted(&index.outgoing),
+            backlinks: sorted(&index.incoming),
+        }
+    }
+
+    /// Forget the links of a removed document. Links to it stay, as they
+    /// do for documents that don't exist yet.
+    pub fn remove_document(&self, document_id: &str) {
+        self.update(document_id, BTreeSet::new());
+    }
+}
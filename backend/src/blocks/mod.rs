@@ -0,0 +1,133 @@
+/*
+ * File: src/blocks/mod.rs
+ * Purpose: Block structure of document text, for code pads
+ *
+ * Documents are plain text. Code blocks are marked the Markdown way, with
+ * fences carrying an optional language tag:
+ *
+ *   ```rust
+ *   fn main() {}
+ *   ```
+ *
+ * Everything outside fences is a text block. An unclosed fence runs to
+ * the end of the document. A fence tagged `checklist` and a name, as in
+ * "```checklist todo", is a checklist block instead; see `checklist`.
+ *
+ * This module contains:
+ * - checklist: Checklists kept as conflict-free ordered lists
+ * - export: Whitespace-preserving plain text and HTML export, with watermarks
+ * - highlight: Syntax highlighting of code blocks in HTML exports
+ * - import: Plain text and Markdown import, mapped onto blocks
+ * - links: Links between documents and the index of backlinks
+ * - syntax: Pluggable syntax checks of code blocks
+ */
+
+pub mod checklist;
+pub mod export;
+pub mod highlight;
+pub mod import;
+pub mod links;
+pub mod syntax;
+
+pub use checklist::{Checklist, ChecklistItem, ChecklistOperation, ChecklistTable};
+pub use export::{export, export_highlighted, export_with_checklists, ExportFormat, Watermark};
+pub use highlight::{HighlightError, DEFAULT_THEME};
+pub use import::{import, import_into, Construct, Import, ImportFormat, ImportedChecklist, Unmapped};
+pub use links::{links, DocumentLink, DocumentLinks, LinkIndex};
+pub use syntax::{BlockDiagnostic, DelimiterChecker, Diagnostic, SyntaxChecker};
+
+use serde::{Deserialize, Serialize};
+
+/// Marker opening and closing a code block
+pub const FENCE: &str = "```";
+
+/// A fenced code block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeBlock {
+    /// Language tag of the opening fence, e.g. `rust`
+    pub language: Option<String>,
+    /// The code, without fences, whitespace untouched
+    pub code: String,
+    /// Line of the document the code starts on, from 1
+    pub first_line: usize,
+    /// Whether a closing fence ends the block
+    pub closed: bool,
+}
+
+/// A section of a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Block {
+    Text { text: String },
+    Code(CodeBlock),
+    /// Place of a named checklist of the document
+    Checklist { name: String },
+}
+
+/// Split document text into text and code blocks
+pub fn parse(content: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut text: Vec<&str> = Vec::new();
+    let mut code: Option<(CodeBlock, Vec<&str>)> = None;
+    // Name of the checklist whose block is open, if it is one
+    let mut checklist: Option<String> = None;
+
+    for (index, line) in content.split('\n').enumerate() {
+        let fence = line.trim_start().strip_prefix(FENCE);
+        match (&mut code, fence) {
+            (Some(_), Some(rest)) if rest.trim().is_empty() => {
+                if let Some((mut block, lines)) = code.take() {
+                    block.code = lines.join("\n");
+                    block.closed = true;
+                    blocks.push(match checklist.take() {
+                        Some(name) => Block::Checklist { name },
+                        None => Block::Code(block),
+                    });
+                }
+            }
+            (Some((_, lines)), _) => lines.push(line),
+            (None, Some(rest)) => {
+                if !text.is_empty() {
+                    blocks.push(Block::Text { text: text.join("\n") });
+                    text.clear();
+                }
+                let mut words = rest.split_whitespace();
+                let language = words.next().map(str::to_string);
+                if language.as_deref() == Some(checklist::CHECKLIST_TAG) {
+                    checklist = words.next().map(str::to_string);
+                }
+                let block = CodeBlock {
+                    language,
+                    code: String::new(),
+                    first_line: index + 2,
+                    closed: false,
+                };
+                code = Some((block, Vec::new()));
+            }
+            (None, None) => text.push(line),
+        }
+    }
+
+    if let Some((mut block, lines)) = code {
+        block.code = lines.join("\n");
+        blocks.push(match checklist {
+            Some(name) => Block::Checklist { name },
+            None => Block::Code(block),
+        });
+    }
+    if text.iter().any(|line| !line.is_empty()) || text.len() > 1 {
+        blocks.push(Block::Text { text: text.join("\n") });
+    }
+    blocks
+}
+
+/// Get the code blocks of document text
+pub fn code_blocks(content: &str) -> Vec<CodeBlock> {
+    parse(content)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Code(code) => Some(code),
+            Block::Text { .. } | Block::Checklist { .. } => None,
+        })
+        .collect()
+}
@@ -0,0 +1,131 @@
+/*
+ * File: src/blocks/syntax.rs
+ * Purpose: Pluggable syntax checks of code blocks
+ *
+ * A `SyntaxChecker` receives one code block at a time, with its language
+ * tag, and returns diagnostics with lines counted from the start of the
+ * block. The server translates them to document lines. Checkers are async
+ * so they can call compilers or language servers out of process.
+ *
+ * `DelimiterChecker` is a language-agnostic default: it reports
+ * unbalanced brackets and double-quoted strings left open at the end of a
+ * line. Single quotes only matter around a single character, as in '}',
+ * since they double as apostrophes and lifetimes.
+ */
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+/// A problem found in a code block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Line within the checked code, from 1
+    pub line: usize,
+    /// Column in characters, from 1
+    pub column: usize,
+    pub message: String,
+}
+
+/// A diagnostic placed in its document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockDiagnostic {
+    /// Index of the code block among the document's code blocks
+    pub block: usize,
+    pub language: Option<String>,
+    /// Line of the document, from 1
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Checks the syntax of code blocks
+pub trait SyntaxChecker: std::fmt::Debug + Send + Sync {
+    /// Check a block of code written in `language`, if it has a tag
+    fn check<'a>(&'a self, language: Option<&'a str>, code: &'a str) -> BoxFuture<'a, Vec<Diagnostic>>;
+}
+
+/// Checks that brackets are balanced and string literals are terminated
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DelimiterChecker;
+
+impl DelimiterChecker {
+    /// Check code synchronously
+    pub fn diagnostics(code: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut open: Vec<(char, usize, usize)> = Vec::new();
+
+        for (index, line) in code.split('\n').enumerate() {
+            let line_number = index + 1;
+            let mut quote: Option<usize> = None;
+            let mut escaped = false;
+            let chars: Vec<char> = line.chars().collect();
+            let mut skip = 0;
+            for (index, &c) in chars.iter().enumerate() {
+                let column = index + 1;
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                if quote.is_some() {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        quote = None;
+                    }
+                    continue;
+                }
+                match c {
+                    '"' => quote = Some(column),
+                    // A character literal such as '}'
+                    '\'' if chars.get(index + 2) == Some(&'\'') => skip = 2,
+                    '(' | '[' | '{' => open.push((c, line_number, column)),
+                    ')' | ']' | '}' => {
+                        let expected = match c {
+                            ')' => '(',
+                            ']' => '[',
+                            _ => '{',
+                        };
+                        match open.pop() {
+                            Some((opening, _, _)) if opening == expected => {}
+                            Some((opening, line, column_opened)) => diagnostics.push(Diagnostic {
+                                line: line_number,
+                                column,
+                                message: format!("`{}` does not close `{}` from {}:{}", c, opening, line, column_opened),
+                            }),
+                            None => diagnostics.push(Diagnostic {
+                                line: line_number,
+                                column,
+                                message: format!("Unmatched `{}`", c),
+                            }),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(column) = quote {
+                diagnostics.push(Diagnostic {
+                    line: line_number,
+                    column,
+                    message: "Unterminated string".to_string(),
+                });
+            }
+        }
+
+        for (c, line, column) in open {
+            diagnostics.push(Diagnostic {
+                line,
+                column,
+                message: format!("Unclosed `{}`", c),
+            });
+        }
+        diagnostics
+    }
+}
+
+impl SyntaxChecker for DelimiterChecker {
+    fn check<'a>(&'a self, _language: Option<&'a str>, code: &'a str) -> BoxFuture<'a, Vec<Diagnostic>> {
+        Box::pin(async move { Self::diagnostics(code) })
+    }
+}
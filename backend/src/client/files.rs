@@ -0,0 +1,304 @@
+/*
+ * File: src/client/files.rs
+ * Purpose: Keeping local files in step with documents
+ *
+ * `FileSync` pairs a file with an `EditorClient`, so documents can be
+ * edited in any local editor. Each round it:
+ * - reads the file, and turns what changed since the round before into
+ *   operations: deleted characters are deleted at their positions, and
+ *   inserted text is placed right after the character it follows
+ * - writes the merged content back, unless the file changed again since
+ *   it was read; that change is picked up by the next round instead
+ *
+ * Local and remote edits are merged by the CRDT, so neither side's edits
+ * are lost when both change the same spot: remote text inserted where
+ * local text was typed ends up after it. Files are replaced through a
+ * temporary file, so editors never see half-written content.
+ *
+ * When syncing starts, the file and the document are compared once they
+ * are synced with the server. A missing file is created, and an empty
+ * document takes the file's content. If both have differing content, the
+ * document wins and the file is kept next to it as `<name>.conflict`.
+ *
+ * `sync_directory` does this for every `.md` and `.txt` file of a
+ * directory, named after its document, picking up new files as they
+ * appear and dropping removed ones; documents are never deleted. Files
+ * are polled rather than watched, every `poll_interval`.
+ */
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    client::{ContentChange, Connector, EditorClient, ReconnectConfig, Replica},
+    crdt::{DocumentError, Operation, Position},
+};
+
+/// Extensions of the files `sync_directory` maps to documents
+pub const SYNCED_EXTENSIONS: [&str; 2] = ["md", "txt"];
+
+/// File sync errors
+#[derive(Debug, Error)]
+pub enum FileSyncError {
+    #[error("File {0} was removed")]
+    Removed(PathBuf),
+    #[error("File {path} could not be synced: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Document(#[from] DocumentError),
+}
+
+/// Polling and reconnecting of synced files
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncConfig {
+    /// Time between rounds
+    pub poll_interval: Duration,
+    /// Delays between connection attempts of each document
+    pub reconnect: ReconnectConfig,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(500),
+            reconnect: ReconnectConfig::default(),
+        }
+    }
+}
+
+/// A file kept in step with a document
+pub struct FileSync {
+    path: PathBuf,
+    client: EditorClient,
+    /// Content of the file as of the last round
+    base: String,
+    /// Positions of the characters of `base` in the document
+    base_positions: Vec<Position>,
+}
+
+impl FileSync {
+    /// Start syncing a file with a document, editing as `author`. Waits
+    /// until the document is synced with the server, then reconciles the
+    /// two. Use an author no other replica uses, since every start begins
+    /// with a fresh replica.
+    pub async fn start(
+        connector: impl Connector,
+        path: impl Into<PathBuf>,
+        document_id: &str,
+        author: &str,
+        reconnect: ReconnectConfig,
+    ) -> Result<Self, FileSyncError> {
+        let path = path.into();
+        let client = EditorClient::start(connector, Replica::new(document_id, author), reconnect);
+        client.synced().await;
+
+        let mut sync = Self { path, client, base: String::new(), base_positions: Vec::new() };
+        let (content, positions) = sync.document();
+        let file = match fs::read_to_string(&sync.path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(sync.io_error(e)),
+        };
+        match file {
+            Some(file) if file == content => (sync.base, sync.base_positions) = (content, positions),
+            Some(file) if content.is_empty() => {
+                // The whole file is new to the document
+                let change = ContentChange { offset: 0, removed: String::new(), inserted: file.clone() };
+                sync.push(&file, change)?;
+            }
+            file => {
+                if let Some(file) = file {
+                    let conflict = sync.sibling("conflict");
+                    fs::write(&conflict, file).map_err(|e| sync.io_error(e))?;
+                    log::warn!("{} differs from document {}; kept it as {}", sync.path.display(), document_id, conflict.display());
+                }
+                sync.replace(&content)?;
+                (sync.base, sync.base_positions) = (content, positions);
+            }
+        }
+        Ok(sync)
+    }
+
+    /// Get the synced file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the client keeping the document in step with the server
+    pub fn client(&self) -> &EditorClient {
+        &self.client
+    }
+
+    /// Send the file's changes since the last round, and write the merged
+    /// content back. Returns whether the file or the document changed.
+    pub fn sync(&mut self) -> Result<bool, FileSyncError> {
+        let file = match fs::read_to_string(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(FileSyncError::Removed(self.path.clone())),
+            Err(e) => return Err(self.io_error(e)),
+        };
+        let local = ContentChange::between(&self.base, &file);
+        let pushed = local.is_some();
+        if let Some(change) = local {
+            self.push(&file, change)?;
+        }
+
+        let (content, positions) = self.document();
+        if content == self.base {
+            return Ok(pushed);
+        }
+        // Leave a file edited meanwhile to the next round
+        match fs::read_to_string(&self.path) {
+            Ok(current) if current == file => {
+                self.replace(&content)?;
+                (self.base, self.base_positions) = (content, positions);
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(FileSyncError::Removed(self.path.clone())),
+            Err(e) => return Err(self.io_error(e)),
+        }
+        Ok(true)
+    }
+
+    /// Stop syncing and close the connection
+    pub async fn close(self) {
+        self.client.close().await;
+    }
+
+    /// Apply a change of the file to the document, and take the file as
+    /// the new base
+    fn push(&mut self, file: &str, change: ContentChange) -> Result<(), FileSyncError> {
+        let replica = self.client.replica();
+        let document = replica.document();
+        let author = replica.author().to_string();
+        let removed = change.removed.chars().count();
+        let end = change.offset + removed;
+
+        for position in &self.base_positions[change.offset..end] {
+            // Characters deleted remotely meanwhile stay deleted
+            if document.character_at(position).is_some() {
+                self.client.edit(Operation::delete(author.clone(), position.clone()))?;
+            }
+        }
+        let anchor = match change.offset {
+            0 => Position::start(),
+            offset => self.base_positions[offset - 1].clone(),
+        };
+        let inserted = document.positions_after(&anchor, change.inserted.chars().count());
+        for (character, position) in change.inserted.chars().zip(&inserted) {
+            self.client.edit(Operation::insert(author.clone(), character, position.clone()))?;
+        }
+
+        self.base_positions.splice(change.offset..end, inserted);
+        self.base = file.to_string();
+        Ok(())
+    }
+
+    /// Get the document's content and the positions of its characters
+    fn document(&self) -> (String, Vec<Position>) {
+        let replica = self.client.replica();
+        let (content, positions) = replica.document().visible_characters()
+            .map(|(character, position)| (character, position.clone()))
+            .unzip();
+        (content, positions)
+    }
+
+    /// Replace the file's content through a temporary file
+    fn replace(&self, content: &str) -> Result<(), FileSyncError> {
+        let temporary = self.sibling("coedit-tmp");
+        fs::write(&temporary, content)
+            .and_then(|()| fs::rename(&temporary, &self.path))
+            .map_err(|e| self.io_error(e))
+    }
+
+    /// Path next to the file, with an extra extension
+    fn sibling(&self, extension: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(extension);
+        self.path.with_file_name(name)
+    }
+
+    fn io_error(&self, source: io::Error) -> FileSyncError {
+        FileSyncError::Io { path: self.path.clone(), source }
+    }
+}
+
+/// Keep every `.md` and `.txt` file of a directory in step with the
+/// document named after it, until cancelled
+pub async fn sync_directory(
+    connector: impl Connector + Clone,
+    dir: &Path,
+    author: &str,
+    config: SyncConfig,
+    shutdown: CancellationToken,
+) -> Result<(), FileSyncError> {
+    let mut files: HashMap<PathBuf, FileSync> = HashMap::new();
+    while !shutdown.is_cancelled() {
+        for (path, document_id) in synced_files(dir)? {
+            if files.contains_key(&path) {
+                continue;
+            }
+            let author = format!("{}-{}", author, document_id);
+            let start = FileSync::start(connector.clone(), path.clone(), &document_id, &author, config.reconnect.clone());
+            let sync = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                sync = start => sync,
+            };
+            match sync {
+                Ok(sync) => {
+                    log::info!("Syncing {} with document {}", path.display(), document_id);
+                    files.insert(path, sync);
+                }
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+
+        let mut removed = Vec::new();
+        for (path, sync) in files.iter_mut() {
+            match sync.sync() {
+                Ok(_) => {}
+                Err(FileSyncError::Removed(_)) => removed.push(path.clone()),
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+        for path in removed {
+            if let Some(sync) = files.remove(&path) {
+                log::info!("Stopped syncing {}, which was removed", path.display());
+                sync.close().await;
+            }
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(config.poll_interval) => {}
+        }
+    }
+    for (_, sync) in files {
+        sync.close().await;
+    }
+    Ok(())
+}
+
+/// List the files of a directory to sync, with their document IDs
+fn synced_files(dir: &Path) -> Result<Vec<(PathBuf, String)>, FileSyncError> {
+    let entries = fs::read_dir(dir).map_err(|source| FileSyncError::Io { path: dir.to_path_buf(), source })?;
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let synced = path.extension().and_then(OsStr::to_str).is_some_and(|extension| SYNCED_EXTENSIONS.contains(&extension));
+        let document_id = path.file_stem().and_then(OsStr::to_str).map(str::to_string);
+        if let (true, Some(document_id)) = (synced && path.is_file(), document_id) {
+            files.push((path, document_id));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,21 @@
+/*
+ * File: src/client/mod.rs
+ * Purpose: Client SDK for embedding CoEdit documents in Rust programs
+ *
+ * This module contains:
+ * - files: Keeping local files in step with documents, for `coedit sync`
+ * - replica: A client's copy of a document and its pending edits
+ * - reconnect: Syncing a replica over connections that come and go, with
+ *   backoff, session resumption and resending of pending edits
+ * - tail: Following the changes merged into a client's document
+ */
+
+pub mod files;
+pub mod replica;
+pub mod reconnect;
+pub mod tail;
+
+pub use files::{sync_directory, FileSync, FileSyncError, SyncConfig, SYNCED_EXTENSIONS};
+pub use replica::Replica;
+pub use reconnect::{websocket_connector, ClientError, ClientStatus, Connector, EditorClient, ReconnectConfig};
+pub use tail::{ContentChange, ContentChanges};
@@ -0,0 +1,452 @@
+/*
+ * File: src/client/reconnect.rs
+ * Purpose: Editing a document through connections that come and go
+ *
+ * `EditorClient` keeps a `Replica` in step with the server in the
+ * background. Every connection resumes the session the same way: once the
+ * server welcomes it, the client sends a document update with the edits
+ * the server lacks, and merges the server's reply, which holds what the
+ * client missed. Afterwards, local edits go out as updates, one in flight
+ * at a time, and operations the server relays are merged as they arrive.
+ *
+ * Edits made while disconnected stay pending in the replica and go out
+ * with the first update of the next connection. Failed connections are
+ * retried with exponential backoff, from `min_backoff` up to
+ * `max_backoff`; a connection that was welcomed starts the delays over.
+ *
+ * Every merge that changes the local copy bumps a revision counter, which
+ * `revisions` watches and `ContentChanges` follows.
+ *
+ * The client also follows who is present in the document, from the
+ * presence snapshot and diffs the server sends; when the sequence numbers
+ * show it missed a diff, it asks for a fresh snapshot with `getPresence`.
+ * Each connection starts over from the snapshot it gets on joining.
+ *
+ * Protocol features the server announces as deprecated in its welcome are
+ * logged as warnings and kept for `deprecations`, so embedders learn of
+ * them before the server drops them.
+ */
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    sync::{watch, Notify},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    client::Replica,
+    crdt::{AppliedOp, DocumentError, Operation},
+    presence::{Presence, PresenceUpdate, PresenceView},
+    websocket::{
+        compat::Deprecation,
+        message::{DocumentUpdateMessage, OperationMessage},
+        transport::{FrameSink, FrameStream, Transport, TransportError},
+        Message, MessageType,
+    },
+};
+
+/// Client connection errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ClientError {
+    #[error("Connection failed: {0}")]
+    Transport(#[from] TransportError),
+    #[error("No reply from the server within {0:?}")]
+    Timeout(Duration),
+    #[error("Unexpected message from the server: {0}")]
+    Protocol(String),
+}
+
+/// Delays between connection attempts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectConfig {
+    /// Delay after the first failed attempt
+    pub min_backoff: Duration,
+    /// Longest delay between attempts
+    pub max_backoff: Duration,
+    /// How long to wait for the server's welcome
+    pub welcome_timeout: Duration,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            welcome_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// Get the delay before retrying after a number of failed attempts in
+    /// a row, from 1
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let doublings = failures.saturating_sub(1).min(31);
+        self.min_backoff.saturating_mul(1 << doublings).min(self.max_backoff)
+    }
+}
+
+/// State of a client's connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientStatus {
+    /// Opening a connection
+    Connecting,
+    /// Connected, with edits on their way or a reply awaited
+    Syncing,
+    /// Connected, and the server has every local edit
+    Synced,
+    /// Waiting to retry after failed attempts in a row
+    Disconnected { failures: u32 },
+    /// Closed for good
+    Closed,
+}
+
+/// A connection to the server: its outgoing and incoming frames
+pub type Connection = (FrameSink, FrameStream);
+
+/// Opens connections to the server
+pub trait Connector: Send + Sync + 'static {
+    /// Open a new connection
+    fn connect(&self) -> Pin<Box<dyn Future<Output = Result<Connection, TransportError>> + Send>>;
+}
+
+impl<F, Fut, T> Connector for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T, TransportError>> + Send + 'static,
+    T: Transport,
+{
+    fn connect(&self) -> Pin<Box<dyn Future<Output = Result<Connection, TransportError>> + Send>> {
+        let connecting = self();
+        Box::pin(async move { connecting.await.map(Transport::split) })
+    }
+}
+
+/// Connect over WebSockets, to a URL such as `ws://host/t/acme/ws?key=...`
+pub fn websocket_connector(url: impl Into<String>) -> impl Connector + Clone {
+    let url: String = url.into();
+    move || {
+        let url = url.clone();
+        async move {
+            let (socket, _) = tokio_tungstenite::connect_async(url.as_str())
+                .await
+                .map_err(|e| TransportError::Failed(e.to_string()))?;
+            Ok(socket)
+        }
+    }
+}
+
+/// State shared between a client and its background task
+struct Shared {
+    replica: Mutex<Replica>,
+    status: watch::Sender<ClientStatus>,
+    /// Count of merges that changed the local copy
+    revisions: watch::Sender<u64>,
+    /// Woken by local edits
+    edited: Notify,
+    /// Deprecations the server announced in its latest welcome
+    deprecations: Mutex<Vec<Deprecation>>,
+    /// Who is present in the document, as of the latest connection
+    presence: Mutex<PresenceView>,
+}
+
+impl Shared {
+    /// Report whether the server has every local edit
+    fn update_sync_status(&self) {
+        let pending = self.replica.lock().pending();
+        self.status.send_if_modified(|status| {
+            let next = match *status {
+                ClientStatus::Syncing | ClientStatus::Synced if pending == 0 => ClientStatus::Synced,
+                ClientStatus::Syncing | ClientStatus::Synced => ClientStatus::Syncing,
+                other => other,
+            };
+            std::mem::replace(status, next) != next
+        });
+    }
+
+    /// Report a merge that changed the local copy
+    fn merged(&self) {
+        self.revisions.send_modify(|revision| *revision += 1);
+    }
+}
+
+/// A document kept in step with the server over connections that come
+/// and go
+pub struct EditorClient {
+    shared: Arc<Shared>,
+    status: watch::Receiver<ClientStatus>,
+    shutdown: CancellationToken,
+    task: Option<JoinHandle<()>>,
+}
+
+impl EditorClient {
+    /// Start keeping a replica in step with the server, connecting in the
+    /// background
+    pub fn start(connector: impl Connector, replica: Replica, config: ReconnectConfig) -> Self {
+        let (status_sender, status) = watch::channel(ClientStatus::Connecting);
+        let shared = Arc::new(Shared {
+            replica: Mutex::new(replica),
+            status: status_sender,
+            revisions: watch::channel(0).0,
+            edited: Notify::new(),
+            deprecations: Mutex::new(Vec::new()),
+            presence: Mutex::new(PresenceView::new()),
+        });
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(run(connector, shared.clone(), config, shutdown.clone()));
+        Self { shared, status, shutdown, task: Some(task) }
+    }
+
+    /// Apply a local edit, authored by the replica's author. It is sent
+    /// now if connected, or on the next connection.
+    pub fn edit(&self, operation: Operation) -> Result<AppliedOp, DocumentError> {
+        let applied = self.shared.replica.lock().edit(operation)?;
+        self.shared.update_sync_status();
+        self.shared.edited.notify_one();
+        Ok(applied)
+    }
+
+    /// Get the text of the local copy
+    pub fn content(&self) -> String {
+        self.shared.replica.lock().document().content()
+    }
+
+    /// Count the local edits the server doesn't have yet
+    pub fn pending(&self) -> u64 {
+        self.shared.replica.lock().pending()
+    }
+
+    /// Get a copy of the replica, to keep it across restarts
+    pub fn replica(&self) -> Replica {
+        self.shared.replica.lock().clone()
+    }
+
+    /// Get the state of the connection
+    pub fn status(&self) -> ClientStatus {
+        *self.status.borrow()
+    }
+
+    /// Watch the count of merges from the server that changed the local
+    /// copy
+    pub fn revisions(&self) -> watch::Receiver<u64> {
+        self.shared.revisions.subscribe()
+    }
+
+    /// Get the protocol features the server announced as deprecated
+    pub fn deprecations(&self) -> Vec<Deprecation> {
+        self.shared.deprecations.lock().clone()
+    }
+
+    /// Get the clients present in the document, this one included, as of
+    /// the latest presence update; empty until the server sends one
+    pub fn presence(&self) -> Vec<Presence> {
+        self.shared.presence.lock().present()
+    }
+
+    /// Wait until connected and the server has every local edit
+    pub async fn synced(&self) {
+        let mut status = self.status.clone();
+        let _ = status.wait_for(|status| *status == ClientStatus::Synced).await;
+    }
+
+    /// Close the connection for good
+    pub async fn close(mut self) {
+        self.shutdown.cancel();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for EditorClient {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Connect, sync and reconnect until shut down
+async fn run(connector: impl Connector, shared: Arc<Shared>, config: ReconnectConfig, shutdown: CancellationToken) {
+    let mut failures = 0;
+    loop {
+        shared.status.send_replace(ClientStatus::Connecting);
+        let result = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            result = connect(&connector, &shared, &config, &shutdown, &mut failures) => result,
+        };
+        if let Err(e) = result {
+            log::warn!("Connection for document {} lost: {}", shared.replica.lock().document().id(), e);
+        }
+        if shutdown.is_cancelled() {
+            break;
+        }
+
+        failures += 1;
+        shared.status.send_replace(ClientStatus::Disconnected { failures });
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(config.backoff(failures)) => {}
+        }
+    }
+    shared.status.send_replace(ClientStatus::Closed);
+}
+
+/// Open a connection and sync over it until it drops
+async fn connect(
+    connector: &impl Connector,
+    shared: &Shared,
+    config: &ReconnectConfig,
+    shutdown: &CancellationToken,
+    failures: &mut u32,
+) -> Result<(), ClientError> {
+    let (sink, mut stream) = connector.connect().await?;
+    let welcome = tokio::time::timeout(config.welcome_timeout, receive(&mut stream))
+        .await
+        .map_err(|_| ClientError::Timeout(config.welcome_timeout))??;
+    let client_id = match welcome.payload()["client_id"].as_str() {
+        Some(client_id) if welcome.message_type() == &MessageType::Status => client_id.to_string(),
+        _ => return Err(ClientError::Protocol(welcome.payload().to_string())),
+    };
+    let deprecations: Vec<Deprecation> = welcome.payload().get("deprecations")
+        .and_then(|deprecations| serde_json::from_value(deprecations.clone()).ok())
+        .unwrap_or_default();
+    // Warn once per server announcement, not on every reconnect
+    {
+        let mut announced = shared.deprecations.lock();
+        for deprecation in deprecations.iter().filter(|deprecation| !announced.contains(deprecation)) {
+            log::warn!(
+                "The server deprecates {}, to be removed in {}{}",
+                deprecation.feature,
+                deprecation.sunset,
+                deprecation.replacement.as_ref().map(|replacement| format!("; use {} instead", replacement)).unwrap_or_default(),
+            );
+        }
+        *announced = deprecations;
+    }
+    *failures = 0;
+    shared.status.send_replace(ClientStatus::Syncing);
+    *shared.presence.lock() = PresenceView::new();
+
+    let mut session = Session {
+        shared,
+        client_id,
+        sink,
+        document_id: shared.replica.lock().document().id().to_string(),
+        next_request: 0,
+        in_flight: None,
+    };
+    session.run(&mut stream, shutdown).await
+}
+
+/// Receive the next protocol message
+async fn receive(stream: &mut FrameStream) -> Result<Message, ClientError> {
+    let frame = stream.next().await.ok_or(TransportError::Closed)??;
+    serde_json::from_str(&frame).map_err(|e| ClientError::Protocol(e.to_string()))
+}
+
+/// One connection's sync with the server
+struct Session<'a> {
+    shared: &'a Shared,
+    client_id: String,
+    sink: FrameSink,
+    /// Document ID, with a slug resolved once the server replies
+    document_id: String,
+    next_request: u64,
+    /// Request ID of the update awaiting a reply
+    in_flight: Option<String>,
+}
+
+impl Session<'_> {
+    async fn run(&mut self, stream: &mut FrameStream, shutdown: &CancellationToken) -> Result<(), ClientError> {
+        let shared = self.shared;
+        // Resume: send what the server lacks and learn what it has
+        self.send_update().await?;
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => return Ok(()),
+                _ = shared.edited.notified(), if self.in_flight.is_none() => {
+                    if shared.replica.lock().pending() > 0 {
+                        self.send_update().await?;
+                    }
+                }
+                message = receive(stream) => self.handle(message?).await?,
+            }
+        }
+    }
+
+    async fn handle(&mut self, message: Message) -> Result<(), ClientError> {
+        let reply = message.request_id().is_some() && message.request_id() == self.in_flight.as_deref();
+        match message.message_type() {
+            MessageType::DocumentUpdate if reply => {
+                let reply: DocumentUpdateMessage = serde_json::from_value(message.payload().clone())
+                    .map_err(|e| ClientError::Protocol(e.to_string()))?;
+                self.in_flight = None;
+                self.document_id = reply.document_id;
+                let reconciled = self.shared.replica.lock().reconcile(&reply.update);
+                match reconciled {
+                    Ok(applied) if !applied.is_empty() => self.shared.merged(),
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Update for document {} could not be merged: {}", self.document_id, e),
+                }
+                self.shared.update_sync_status();
+                // Edits made while the update was in flight
+                if self.shared.replica.lock().pending() > 0 {
+                    self.send_update().await?;
+                }
+            }
+            MessageType::Operation => {
+                let Ok(relay) = serde_json::from_value::<OperationMessage>(message.payload().clone()) else {
+                    return Ok(());
+                };
+                if relay.document_id == self.document_id {
+                    let received = self.shared.replica.lock().receive(relay.operation);
+                    match received {
+                        Ok(true) => self.shared.merged(),
+                        Ok(false) => {}
+                        Err(e) => log::warn!("Relayed operation on document {} could not be merged: {}", self.document_id, e),
+                    }
+                }
+            }
+            MessageType::PresenceUpdate => {
+                let Ok(update) = serde_json::from_value::<PresenceUpdate>(message.payload().clone()) else {
+                    return Ok(());
+                };
+                if update.document_id == self.document_id && !self.shared.presence.lock().apply(&update) {
+                    self.send(MessageType::GetPresence, json!({ "document_id": &self.document_id }), None).await?;
+                }
+            }
+            MessageType::Error if reply => {
+                // Pending edits stay pending and go out with the next one
+                self.in_flight = None;
+                log::warn!("Update of document {} rejected: {}", self.document_id, message.payload());
+                self.shared.update_sync_status();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn send_update(&mut self) -> Result<(), ClientError> {
+        self.next_request += 1;
+        let request_id = format!("{}-update-{}", self.client_id, self.next_request);
+        let update = DocumentUpdateMessage {
+            document_id: self.document_id.clone(),
+            update: self.shared.replica.lock().outgoing(),
+        };
+        let payload = serde_json::to_value(&update).map_err(|e| ClientError::Protocol(e.to_string()))?;
+        self.send(MessageType::DocumentUpdate, payload, Some(request_id.clone())).await?;
+        self.in_flight = Some(request_id);
+        Ok(())
+    }
+
+    async fn send(&mut self, message_type: MessageType, payload: Value, request_id: Option<String>) -> Result<(), ClientError> {
+        let message = Message::new(message_type, self.client_id.clone(), payload).with_request_id(request_id);
+        let text = serde_json::to_string(&message).map_err(|e| ClientError::Protocol(e.to_string()))?;
+        self.sink.send(Arc::from(text)).await?;
+        Ok(())
+    }
+}
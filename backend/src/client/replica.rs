@@ -0,0 +1,95 @@
+/*
+ * File: src/client/replica.rs
+ * Purpose: A client's copy of a document, with the edits the server lacks
+ *
+ * The replica applies local edits at once and remembers the server's state
+ * vector from its latest update. Whatever the replica holds beyond that
+ * state vector is pending: it is sent as a document update, and stays
+ * pending until an update from the server counts it. Operations relayed by
+ * the server are merged and counted as the server's too.
+ *
+ * Local edits are authored by the replica's author. Choose an ID that
+ * outlives connections, such as a device ID: the server assigns a new
+ * client ID to every connection, but state vectors count operations per
+ * author, so a stable author keeps pending edits recognizable across
+ * reconnects.
+ */
+
+use crate::crdt::{AppliedOp, Document, DocumentError, DocumentUpdate, Operation, StateVector};
+
+/// A client's copy of a document
+#[derive(Debug, Clone)]
+pub struct Replica {
+    author: String,
+    document: Document,
+    /// Operations the server is known to have
+    server_state: StateVector,
+}
+
+impl Replica {
+    /// Create an empty replica of a document, editing as `author`
+    pub fn new(document_id: &str, author: &str) -> Self {
+        Self::restore(Document::new(document_id.to_string()), StateVector::new(), author)
+    }
+
+    /// Resume a replica kept by the client, with the server's state vector
+    /// from the last update it received
+    pub fn restore(document: Document, server_state: StateVector, author: &str) -> Self {
+        Self {
+            author: author.to_string(),
+            document,
+            server_state,
+        }
+    }
+
+    /// Get the author of local edits
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// Get the local copy of the document
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Get the operations the server is known to have
+    pub fn server_state(&self) -> &StateVector {
+        &self.server_state
+    }
+
+    /// Apply a local edit. It stays pending until the server has it.
+    pub fn edit(&mut self, operation: Operation) -> Result<AppliedOp, DocumentError> {
+        self.document.apply_operation(operation)
+    }
+
+    /// Count the local edits the server doesn't have yet
+    pub fn pending(&self) -> u64 {
+        let written = StateVector::of(&self.document).get(&self.author);
+        written.saturating_sub(self.server_state.get(&self.author))
+    }
+
+    /// Build the update to send the server: the operations beyond its
+    /// state vector, with the replica's own
+    pub fn outgoing(&self) -> DocumentUpdate {
+        DocumentUpdate::since(&self.document, &self.server_state)
+    }
+
+    /// Merge an update from the server, returning the operations the
+    /// replica lacked
+    pub fn reconcile(&mut self, update: &DocumentUpdate) -> Result<Vec<Operation>, DocumentError> {
+        let applied = update.apply_to(&mut self.document)?;
+        self.server_state = update.state_vector.clone();
+        Ok(applied)
+    }
+
+    /// Merge an operation the server relayed. Returns whether it was new.
+    pub fn receive(&mut self, operation: Operation) -> Result<bool, DocumentError> {
+        let applied = self.document.merge_operation(operation.clone())?.is_some();
+        // Operations the replica already had came with an update, which
+        // counted them
+        if applied {
+            self.server_state.observe(&operation);
+        }
+        Ok(applied)
+    }
+}
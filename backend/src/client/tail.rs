@@ -0,0 +1,76 @@
+/*
+ * File: src/client/tail.rs
+ * Purpose: Following the changes to a client's document as they arrive
+ *
+ * `ContentChanges` turns the edits an `EditorClient` merges from the
+ * server into a stream of text changes, each the smallest replaced span
+ * between the content before and after. Edits merged while no one was
+ * waiting are folded into one change. `coedit tail` prints the stream as
+ * JSON lines.
+ */
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use crate::client::EditorClient;
+
+/// A span of the content replaced by other text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentChange {
+    /// Character offset of the span in the content before the change
+    pub offset: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl ContentChange {
+    /// Find the span that differs between two versions of a text, or
+    /// `None` if they are equal
+    pub fn between(before: &str, after: &str) -> Option<Self> {
+        let before: Vec<char> = before.chars().collect();
+        let after: Vec<char> = after.chars().collect();
+        let prefix = before.iter().zip(&after).take_while(|(a, b)| a == b).count();
+        let suffix = before[prefix..].iter().rev()
+            .zip(after[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let removed: String = before[prefix..before.len() - suffix].iter().collect();
+        let inserted: String = after[prefix..after.len() - suffix].iter().collect();
+        if removed.is_empty() && inserted.is_empty() {
+            return None;
+        }
+        Some(Self { offset: prefix, removed, inserted })
+    }
+}
+
+/// The changes to a client's document, starting from its content when
+/// created
+pub struct ContentChanges<'a> {
+    client: &'a EditorClient,
+    revisions: watch::Receiver<u64>,
+    content: String,
+}
+
+impl<'a> ContentChanges<'a> {
+    /// Follow the changes a client merges from the server
+    pub fn new(client: &'a EditorClient) -> Self {
+        Self { client, revisions: client.revisions(), content: client.content() }
+    }
+
+    /// Get the content as of the last change returned
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Wait for the next change to the content. Returns `None` if the
+    /// client can no longer report changes.
+    pub async fn next(&mut self) -> Option<ContentChange> {
+        loop {
+            self.revisions.changed().await.ok()?;
+            let content = self.client.content();
+            if let Some(change) = ContentChange::between(&self.content, &content) {
+                self.content = content;
+                return Some(change);
+            }
+        }
+    }
+}
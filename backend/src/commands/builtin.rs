@@ -0,0 +1,114 @@
+/*
+ * File: src/commands/builtin.rs
+ * Purpose: Commands every server knows
+ *
+ * - `/insert-template <name>` appends a named template to the document
+ * - `/format` removes whitespace at the end of lines
+ * - `/export [text|html|markdown]` renders the document, as the HTTP export does,
+ *   and returns it to the sender
+ *
+ * The template command starts without templates; deployments register
+ * it again with theirs.
+ */
+
+use std::collections::BTreeMap;
+use futures::future::BoxFuture;
+use serde_json::json;
+use crate::{
+    blocks::{export, ExportFormat},
+    commands::{CommandError, CommandHandler, CommandInput, CommandOutput, COMMAND_CLIENT_ID},
+    crdt::{Operation, Position},
+};
+
+/// Appends a named template to the document
+#[derive(Debug, Clone, Default)]
+pub struct InsertTemplateCommand {
+    templates: BTreeMap<String, String>,
+}
+
+impl InsertTemplateCommand {
+    /// Serve these templates, by name
+    pub fn new(templates: BTreeMap<String, String>) -> Self {
+        Self { templates }
+    }
+
+    /// Add a template
+    pub fn with_template(mut self, name: &str, text: &str) -> Self {
+        self.templates.insert(name.to_string(), text.to_string());
+        self
+    }
+}
+
+impl CommandHandler for InsertTemplateCommand {
+    fn run<'a>(&'a self, input: &'a CommandInput) -> BoxFuture<'a, Result<CommandOutput, CommandError>> {
+        Box::pin(async move {
+            let available = || self.templates.keys().cloned().collect::<Vec<_>>().join(", ");
+            if input.args.is_empty() {
+                return Err(input.invalid(format!("name a template: {}", available())));
+            }
+            let Some(text) = self.templates.get(&input.args) else {
+                return Err(input.invalid(format!("unknown template {}; available: {}", input.args, available())));
+            };
+
+            let document = &input.document;
+            let last = document.visible_characters().last().map(|(_, position)| position.clone());
+            let positions = document.positions_after(&last.unwrap_or_else(Position::start), text.chars().count());
+            let operations = text.chars()
+                .zip(positions)
+                .map(|(character, position)| Operation::insert(COMMAND_CLIENT_ID.to_string(), character, position))
+                .collect();
+            Ok(CommandOutput::Operations(operations))
+        })
+    }
+}
+
+/// Removes spaces and tabs at the end of lines
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatCommand;
+
+impl CommandHandler for FormatCommand {
+    fn run<'a>(&'a self, input: &'a CommandInput) -> BoxFuture<'a, Result<CommandOutput, CommandError>> {
+        Box::pin(async move {
+            if !input.args.is_empty() {
+                return Err(input.invalid("takes no arguments"));
+            }
+            let mut operations = Vec::new();
+            let mut trailing = Vec::new();
+            for (character, position) in input.document.visible_characters() {
+                match character {
+                    ' ' | '\t' => trailing.push(position),
+                    '\n' => operations.extend(trailing.drain(..).map(|position| {
+                        Operation::delete(COMMAND_CLIENT_ID.to_string(), position.clone())
+                    })),
+                    _ => trailing.clear(),
+                }
+            }
+            operations.extend(trailing.into_iter().map(|position| Operation::delete(COMMAND_CLIENT_ID.to_string(), position.clone())));
+            Ok(CommandOutput::Operations(operations))
+        })
+    }
+}
+
+/// Renders the document for the sender
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportCommand;
+
+impl CommandHandler for ExportCommand {
+    fn run<'a>(&'a self, input: &'a CommandInput) -> BoxFuture<'a, Result<CommandOutput, CommandError>> {
+        Box::pin(async move {
+            let format = match input.args.as_str() {
+                "" => ExportFormat::default(),
+                format => format.parse::<ExportFormat>().map_err(|e| input.invalid(e))?,
+            };
+            let content = export(&input.document.content(), format);
+            Ok(CommandOutput::Status {
+                message: format!("Exported {} as {}", input.document.id(), format.name()),
+                data: json!({
+                    "format": format.name(),
+                    "content_type": format.content_type(),
+                    "content": content,
+                }),
+            })
+        })
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * File: src/commands/handler.rs
+ * Purpose: Command handlers and the registry routing commands to them
+ *
+ * A command line is a name and its arguments, `/insert-template meeting`
+ * for instance. The registry looks the name up and runs its
+ * `CommandHandler`, which sees who sent the command and a snapshot of the
+ * document, and answers with either:
+ * - Operations, which the server applies as its own and relays to
+ *   everyone editing the document
+ * - A status, returned to the sender alone
+ *
+ * Handlers are async so they can call out to external services.
+ */
+
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use crate::{
+    commands::{CommandError, ExportCommand, FormatCommand, InsertTemplateCommand},
+    crdt::{Document, Operation},
+};
+
+/// Client ID under which command operations are applied
+pub const COMMAND_CLIENT_ID: &str = "commands";
+
+/// Everything a handler sees about a command
+#[derive(Debug, Clone)]
+pub struct CommandInput {
+    pub tenant_id: String,
+    /// Connection the command arrived on
+    pub client_id: String,
+    /// Name of the command, without the slash
+    pub name: String,
+    /// Rest of the command line, trimmed
+    pub args: String,
+    /// The document as the command found it
+    pub document: Document,
+}
+
+impl CommandInput {
+    /// Reject the arguments of the command
+    pub fn invalid(&self, message: impl Into<String>) -> CommandError {
+        CommandError::InvalidArguments { command: self.name.clone(), message: message.into() }
+    }
+}
+
+/// What a command did
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutput {
+    /// Edits to apply to the document, in order
+    Operations(Vec<Operation>),
+    /// A result for the sender alone
+    Status { message: String, data: Value },
+}
+
+impl CommandOutput {
+    /// A status without data
+    pub fn status(message: impl Into<String>) -> Self {
+        CommandOutput::Status { message: message.into(), data: Value::Null }
+    }
+}
+
+/// Runs one command
+pub trait CommandHandler: Debug + Send + Sync {
+    /// Run the command against a snapshot of the document
+    fn run<'a>(&'a self, input: &'a CommandInput) -> BoxFuture<'a, Result<CommandOutput, CommandError>>;
+}
+
+/// Split a command line into its name and arguments
+pub fn parse_command(line: &str) -> Result<(String, String), CommandError> {
+    let Some(command) = line.trim().strip_prefix('/') else {
+        return Err(CommandError::NotACommand(line.to_string()));
+    };
+    let (name, args) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    check_name(name)?;
+    Ok((name.to_string(), args.trim().to_string()))
+}
+
+/// Command names are lowercase words joined by hyphens
+fn check_name(name: &str) -> Result<(), CommandError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(CommandError::InvalidName(name.to_string()))
+    }
+}
+
+/// Handlers by command name. The default registry has the built-in
+/// commands; registering a name again replaces its handler.
+#[derive(Debug, Clone)]
+pub struct CommandRegistry {
+    handlers: BTreeMap<String, Arc<dyn CommandHandler>>,
+}
+
+impl CommandRegistry {
+    /// A registry without any command
+    pub fn empty() -> Self {
+        Self { handlers: BTreeMap::new() }
+    }
+
+    /// Register a handler under a command name
+    pub fn register(&mut self, name: &str, handler: Arc<dyn CommandHandler>) -> Result<(), CommandError> {
+        check_name(name)?;
+        self.handlers.insert(name.to_string(), handler);
+        Ok(())
+    }
+
+    /// Get the handler of a command
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn CommandHandler>> {
+        self.handlers.get(name)
+    }
+
+    /// Names of the registered commands, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.handlers.insert("insert-template".to_string(), Arc::new(InsertTemplateCommand::default()));
+        registry.handlers.insert("format".to_string(), Arc::new(FormatCommand));
+        registry.handlers.insert("export".to_string(), Arc::new(ExportCommand));
+        registry
+    }
+}
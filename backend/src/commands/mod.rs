@@ -0,0 +1,39 @@
+/*
+ * File: src/commands/mod.rs
+ * Purpose: Module organization for server-side commands
+ *
+ * This module contains:
+ * - handler: The CommandHandler trait, its inputs and outputs, and the
+ *   registry routing commands to handlers
+ * - builtin: The commands every server knows: `/insert-template`,
+ *   `/format` and `/export`
+ *
+ * Clients send a `command` message with a slash command line for a
+ * document; the server runs the handler registered under its name and
+ * applies the operations it returns, or replies with its status.
+ * Deployments add their own actions by registering handlers in
+ * `ServerConfig::commands`.
+ */
+
+pub mod builtin;
+pub mod handler;
+
+pub use builtin::{ExportCommand, FormatCommand, InsertTemplateCommand};
+pub use handler::{parse_command, CommandHandler, CommandInput, CommandOutput, CommandRegistry, COMMAND_CLIENT_ID};
+
+use thiserror::Error;
+
+/// Command errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    #[error("Not a command: {0:?}; commands start with '/'")]
+    NotACommand(String),
+    #[error("Invalid command name: {0}")]
+    InvalidName(String),
+    #[error("Unknown command /{0}")]
+    Unknown(String),
+    #[error("Invalid arguments for /{command}: {message}")]
+    InvalidArguments { command: String, message: String },
+    #[error("Command /{command} failed: {message}")]
+    Failed { command: String, message: String },
+}
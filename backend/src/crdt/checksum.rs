@@ -0,0 +1,132 @@
+/*
+ * File: crdt/checksum.rs
+ * Purpose: Incrementally maintained content hash
+ *
+ * The hash of a document is the wrapping sum of a hash of every visible
+ * character (its position, value and insert timestamp). Sums can be updated
+ * in O(1): an insert adds the character's hash and a delete subtracts it,
+ * so the checksum never needs a pass over the content. Tombstones don't
+ * contribute, so garbage collection leaves it unchanged.
+ *
+ * Sums are also kept per region of the position space, keyed by the top
+ * byte of a position's first path component. Comparing region sums tells
+ * two replicas which part of a document diverged.
+ *
+ * Character hashes use FNV-1a with a final mix, which is stable across
+ * processes and platforms, unlike the standard library's hasher.
+ */
+
+use serde::{Deserialize, Serialize};
+use crate::crdt::{Position, Timestamp};
+
+/// Number of regions the position space is split into
+pub const CHECKSUM_REGIONS: usize = 256;
+
+/// Per-region and whole-document hash of visible content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentHash {
+    total: u64,
+    regions: Vec<u64>,
+}
+
+impl Default for ContentHash {
+    fn default() -> Self {
+        Self {
+            total: 0,
+            regions: vec![0; CHECKSUM_REGIONS],
+        }
+    }
+}
+
+impl ContentHash {
+    /// Create the hash of an empty document
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the hash of the whole document
+    pub fn checksum(&self) -> u64 {
+        self.total
+    }
+
+    /// Get the hash of each region, in position order
+    pub fn regions(&self) -> &[u64] {
+        &self.regions
+    }
+
+    /// Get the indices of regions whose hashes differ from another replica's
+    pub fn diverged_regions(&self, other: &ContentHash) -> Vec<usize> {
+        self.diverged_from(&other.regions)
+    }
+
+    /// Get the indices of regions whose hashes differ from a list of region
+    /// hashes received from another replica. Regions missing from the list
+    /// count as diverged.
+    pub fn diverged_from(&self, regions: &[u64]) -> Vec<usize> {
+        self.regions
+            .iter()
+            .enumerate()
+            .filter(|(index, ours)| regions.get(*index) != Some(ours))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Get the region a position belongs to
+    pub fn region_of(position: &Position) -> usize {
+        position.path().first().map_or(0, |first| (first >> 24) as usize)
+    }
+
+    /// Account for a character becoming visible
+    pub(crate) fn add(&mut self, position: &Position, value: char, timestamp: &Timestamp) {
+        let hash = character_hash(position, value, timestamp);
+        let region = &mut self.regions[Self::region_of(position)];
+        *region = region.wrapping_add(hash);
+        self.total = self.total.wrapping_add(hash);
+    }
+
+    /// Account for a visible character being deleted
+    pub(crate) fn remove(&mut self, position: &Position, value: char, timestamp: &Timestamp) {
+        let hash = character_hash(position, value, timestamp);
+        let region = &mut self.regions[Self::region_of(position)];
+        *region = region.wrapping_sub(hash);
+        self.total = self.total.wrapping_sub(hash);
+    }
+}
+
+/// Hash a single character
+fn character_hash(position: &Position, value: char, timestamp: &Timestamp) -> u64 {
+    let mut hash = Fnv::new();
+    for component in position.path() {
+        hash.write(&component.to_le_bytes());
+    }
+    hash.write(&[0xff]);
+    hash.write(&u32::from(value).to_le_bytes());
+    hash.write(&timestamp.logical_clock().to_le_bytes());
+    hash.write(timestamp.client_id().as_bytes());
+    mix(hash.0)
+}
+
+/// 64-bit FNV-1a
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Finalizer spreading FNV's output so sums of hashes don't cancel out
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
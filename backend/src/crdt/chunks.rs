@@ -0,0 +1,197 @@
+/*
+ * File: crdt/chunks.rs
+ * Purpose: Chunked list with Fenwick-indexed chunk lengths
+ *
+ * A document's characters, and the flags the `LineIndex` keeps for them,
+ * are long lists that take inserts anywhere. Keeping either in a single
+ * vector moves the rest of the list on every insert.
+ *
+ * `Chunks` splits a list into chunks of at most `CHUNK_CAPACITY` items and
+ * keeps the prefix sums of their lengths in a Fenwick tree. Finding an
+ * index descends the tree, O(log chunks), and an insert moves items within
+ * one chunk only. A chunk that fills up is split in two, which rebuilds
+ * the tree; that happens once every `CHUNK_CAPACITY / 2` inserts into it.
+ * Callers keeping their own sums per chunk rebuild them on a split too.
+ */
+
+use std::mem;
+
+/// Most items in a chunk before it is split
+pub const CHUNK_CAPACITY: usize = 512;
+
+/// Prefix sums over chunks that can change in place
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Fenwick {
+    tree: Vec<usize>,
+}
+
+impl Fenwick {
+    pub(crate) fn build(values: impl Iterator<Item = usize>) -> Self {
+        let mut tree: Vec<usize> = values.collect();
+        for i in 0..tree.len() {
+            let parent = i | (i + 1);
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+        Self { tree }
+    }
+
+    pub(crate) fn add(&mut self, index: usize, delta: isize) {
+        let mut i = index;
+        while i < self.tree.len() {
+            self.tree[i] = self.tree[i].wrapping_add_signed(delta);
+            i |= i + 1;
+        }
+    }
+
+    /// Sum of the first `count` values
+    pub(crate) fn prefix(&self, count: usize) -> usize {
+        let mut sum = 0;
+        let mut i = count;
+        while i > 0 {
+            sum += self.tree[i - 1];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Find the value holding the `target`-th unit, counting from 0: the
+    /// index with `prefix(index) <= target < prefix(index + 1)`, and that
+    /// prefix. Past the last unit, gives the number of values.
+    pub(crate) fn find(&self, target: usize) -> (usize, usize) {
+        let mut index = 0;
+        let mut before = 0;
+        let mut step = self.tree.len().next_power_of_two();
+        while step > 0 {
+            let next = index + step;
+            if next <= self.tree.len() && before + self.tree[next - 1] <= target {
+                index = next;
+                before += self.tree[next - 1];
+            }
+            step /= 2;
+        }
+        (index, before)
+    }
+
+    /// Estimate the bytes the tree takes up
+    pub(crate) fn bytes(&self) -> usize {
+        self.tree.len() * mem::size_of::<usize>()
+    }
+}
+
+/// A list split into never empty chunks, in order
+#[derive(Debug, Clone)]
+pub(crate) struct Chunks<T> {
+    chunks: Vec<Vec<T>>,
+    lengths: Fenwick,
+}
+
+impl<T> Default for Chunks<T> {
+    fn default() -> Self {
+        Self { chunks: Vec::new(), lengths: Fenwick::default() }
+    }
+}
+
+impl<T> Chunks<T> {
+    /// Split a list into half-full chunks, leaving room for inserts
+    pub(crate) fn build(items: impl IntoIterator<Item = T>) -> Self {
+        let mut chunks = Vec::new();
+        let mut items = items.into_iter().peekable();
+        while items.peek().is_some() {
+            let mut chunk = Vec::with_capacity(CHUNK_CAPACITY);
+            chunk.extend(items.by_ref().take(CHUNK_CAPACITY / 2));
+            chunks.push(chunk);
+        }
+        let lengths = Fenwick::build(chunks.iter().map(Vec::len));
+        Self { chunks, lengths }
+    }
+
+    /// Get the chunks, in order
+    pub(crate) fn chunks(&self) -> &[Vec<T>] {
+        &self.chunks
+    }
+
+    /// Count the items
+    pub(crate) fn len(&self) -> usize {
+        self.lengths.prefix(self.chunks.len())
+    }
+
+    /// Find the chunk of an index and the index's place in it; the end of
+    /// the list is placed after the last item
+    pub(crate) fn locate(&self, index: usize) -> (usize, usize) {
+        let (chunk, before) = self.lengths.find(index);
+        if chunk == self.chunks.len() && chunk > 0 {
+            return (chunk - 1, index - self.lengths.prefix(chunk - 1));
+        }
+        (chunk, index - before)
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&T> {
+        let (chunk, offset) = self.locate(index);
+        self.chunks.get(chunk)?.get(offset)
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let (chunk, offset) = self.locate(index);
+        self.chunks.get_mut(chunk)?.get_mut(offset)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks.iter().flatten()
+    }
+
+    /// Iterate over the items from `index` on
+    pub(crate) fn iter_from(&self, index: usize) -> impl Iterator<Item = &T> {
+        let (chunk, offset) = self.locate(index);
+        self.chunks[chunk.min(self.chunks.len())..].iter().flatten().skip(offset)
+    }
+
+    /// Find the index of the first item `before` is false for, where it is
+    /// true for every item before that one, as `slice::partition_point`
+    /// does
+    pub(crate) fn partition_point(&self, mut before: impl FnMut(&T) -> bool) -> usize {
+        let chunk = self.chunks.partition_point(|chunk| chunk.last().is_some_and(&mut before));
+        match self.chunks.get(chunk) {
+            Some(items) => self.lengths.prefix(chunk) + items.partition_point(before),
+            None => self.len(),
+        }
+    }
+
+    /// Insert an item at an index, moving the following ones up. Returns
+    /// the chunk it went into, or `None` if that chunk was split, which
+    /// moves the chunks after it.
+    pub(crate) fn insert(&mut self, index: usize, item: T) -> Option<usize> {
+        if self.chunks.is_empty() {
+            self.chunks.push(Vec::with_capacity(CHUNK_CAPACITY));
+            self.rebuild_lengths();
+        }
+        let (chunk, offset) = self.locate(index);
+        self.chunks[chunk].insert(offset, item);
+        if self.chunks[chunk].len() > CHUNK_CAPACITY {
+            let upper = self.chunks[chunk].split_off(CHUNK_CAPACITY / 2);
+            self.chunks.insert(chunk + 1, upper);
+            self.rebuild_lengths();
+            return None;
+        }
+        self.lengths.add(chunk, 1);
+        Some(chunk)
+    }
+
+    /// Take the items out, leaving the list empty
+    pub(crate) fn take(&mut self) -> Vec<T> {
+        let items = mem::take(&mut self.chunks).into_iter().flatten().collect();
+        self.lengths = Fenwick::default();
+        items
+    }
+
+    /// Estimate the bytes the chunks and their tree take up, beside the
+    /// list itself
+    pub(crate) fn bytes(&self) -> usize {
+        self.chunks.len() * mem::size_of::<Vec<T>>() + self.len() * mem::size_of::<T>() + self.lengths.bytes()
+    }
+
+    fn rebuild_lengths(&mut self) {
+        self.lengths = Fenwick::build(self.chunks.iter().map(Vec::len));
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * File: crdt/diff.rs
+ * Purpose: Changes of a document relative to an earlier revision
+ *
+ * Responsibilities:
+ * - Rebuild a document as it was at a version from its history
+ * - Match characters of both revisions by identity (position and
+ *   timestamp), not by text, so moved-looking edits aren't misreported
+ * - Group inserted and removed characters into ranges of the current
+ *   content
+ *
+ * This powers review overlays: the server sends the ranges to clients
+ * that asked for a diff against a revision, and again after every edit.
+ */
+
+use serde::{Deserialize, Serialize};
+use crate::crdt::{Document, DocumentError, Position, Timestamp};
+
+/// How a range differs from the base revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    /// Text in the current content that the base revision lacks
+    Inserted,
+    /// Text of the base revision that is gone from the current content
+    Removed,
+}
+
+/// A run of inserted or removed text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffRange {
+    pub kind: DiffKind,
+    /// Character offset in the current content where inserted text starts,
+    /// or before which removed text stood
+    pub offset: usize,
+    pub text: String,
+}
+
+/// A character of either revision
+struct Entry<'a> {
+    value: char,
+    position: &'a Position,
+    timestamp: &'a Timestamp,
+    visible: bool,
+}
+
+fn entries(document: &Document) -> Vec<Entry<'_>> {
+    document.all_characters()
+        .map(|(value, position, timestamp, deleted)| Entry { value, position, timestamp, visible: !deleted })
+        .collect()
+}
+
+/// Rebuild a document as it was after its first `version` operations
+pub fn revision(document: &Document, version: u64) -> Result<Document, DocumentError> {
+    let history = document.operations();
+    let count = usize::try_from(version).ok()
+        .filter(|count| *count <= history.len())
+        .ok_or_else(|| DocumentError::UnknownRevision {
+            document_id: document.id().to_string(),
+            version,
+            length: history.len(),
+        })?;
+    let mut base = Document::new(document.id().to_string());
+    base.set_tie_break(document.tie_break());
+    for operation in &history[..count] {
+        base.apply(operation.clone());
+    }
+    Ok(base)
+}
+
+/// Find the text inserted and removed since the document's revision at
+/// `version`, in content order
+pub fn diff_since(document: &Document, version: u64) -> Result<Vec<DiffRange>, DocumentError> {
+    Ok(diff(&revision(document, version)?, document))
+}
+
+/// Find the text inserted and removed between an earlier revision of a
+/// document and the document now, in content order. Removed characters
+/// whose tombstones were collected are placed by their position.
+pub fn diff(base: &Document, document: &Document) -> Vec<DiffRange> {
+    let current = entries(document);
+    let before = entries(base);
+
+    let mut keys: Vec<(&Position, &Timestamp, usize)> = current.iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.position, entry.timestamp, i))
+        .collect();
+    keys.sort_unstable();
+    let find = |entry: &Entry| {
+        keys.binary_search_by(|(position, timestamp, _)| (*position, *timestamp).cmp(&(entry.position, entry.timestamp)))
+            .ok()
+            .map(|found| keys[found].2)
+    };
+
+    // Visibility in the base revision of each current character, and the
+    // base's visible characters that no longer exist at all
+    let mut was_visible = vec![false; current.len()];
+    let mut collected = Vec::new();
+    for entry in &before {
+        match find(entry) {
+            Some(index) => was_visible[index] = entry.visible,
+            None if entry.visible => collected.push(entry),
+            None => {}
+        }
+    }
+
+    let mut ranges: Vec<DiffRange> = Vec::new();
+    let mut push = |kind: DiffKind, offset: usize, value: char| {
+        match ranges.last_mut() {
+            Some(last) if last.kind == kind && last.offset + extent(last) == offset => last.text.push(value),
+            _ => ranges.push(DiffRange { kind, offset, text: value.to_string() }),
+        }
+    };
+    let mut collected = collected.into_iter().peekable();
+    let mut offset = 0;
+    for (entry, was_visible) in current.iter().zip(was_visible) {
+        while let Some(gone) = collected.next_if(|gone| gone.position < entry.position) {
+            push(DiffKind::Removed, offset, gone.value);
+        }
+        match (was_visible, entry.visible) {
+            (false, true) => push(DiffKind::Inserted, offset, entry.value),
+            (true, false) => push(DiffKind::Removed, offset, entry.value),
+            _ => {}
+        }
+        if entry.visible {
+            offset += 1;
+        }
+    }
+    for gone in collected {
+        push(DiffKind::Removed, offset, gone.value);
+    }
+    ranges
+}
+
+/// Offsets an inserted range takes up; removed text takes up none
+fn extent(range: &DiffRange) -> usize {
+    match range.kind {
+        DiffKind::Inserted => range.text.chars().count(),
+        DiffKind::Removed => 0,
+    }
+}
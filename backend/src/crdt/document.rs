@@ -12,10 +12,126 @@
  * This file implements the core document logic for the CRDT,
  * managing the state of text content and handling operations
  * in a way that ensures eventual consistency across all clients.
+ *
+ * Garbage collection removes the tombstones of deleted characters. Clients
+ * that sync with state vectors acknowledge what they have seen, and their
+ * acknowledgement is a barrier: a tombstone is only collected once every
+ * barrier covers an operation that deleted it. Without barriers, every
+ * tombstone is collectible.
+ *
+ * The operation log keeps a state vector of what it holds, counted per
+ * author, so replicas that reconnect get `operations_since` their own
+ * vector without the document counting its history for every request.
+ *
+ * A range delete tombstones every character between two positions in one
+ * operation, such as a selected paragraph. It carries the state vector of
+ * the deleting replica and only deletes the characters whose inserts that
+ * vector covers, so text other clients typed into the range concurrently
+ * survives on every replica, whichever order they apply the two in.
+ *
+ * `memory_report` breaks a document's memory down into its live
+ * characters, tombstones, operation log and indexes, so the effect of
+ * garbage collection and compaction can be measured.
+ *
+ * The characters are kept in chunks rather than one vector, so an insert
+ * into a document of hundreds of thousands of characters moves the rest of
+ * its chunk, not the rest of the document. Finding a position or an index
+ * takes O(log n).
  */
 
-use serde::{Deserialize, Serialize};
-use crate::crdt::{Position, Timestamp};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    mem,
+    ops::{AddAssign, Bound, Deref, Index, RangeInclusive},
+};
+use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use crate::crdt::{
+    chunks::Chunks, ConcurrencyStats, ContentHash, LineIndex, Position, PositionBounds, StateVector, TieBreak, Timestamp,
+};
+
+/// Document-specific errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DocumentError {
+    #[error("Document not found: {0}")]
+    NotFound(String),
+    #[error("Invalid position: {0}")]
+    InvalidPosition(String),
+    #[error("Document already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Document {document_id} is at version {version}, older than the requested {required}")]
+    Stale { document_id: String, version: u64, required: u64 },
+    #[error("Document {document_id} has no revision {version}; its history has {length} operations")]
+    UnknownRevision { document_id: String, version: u64, length: usize },
+}
+
+/// What an applied operation changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppliedOp {
+    /// Document version after the operation
+    pub version: u64,
+    /// Visible index of the inserted or deleted character; None when the
+    /// operation changed nothing visible (e.g. deleting a deleted character)
+    pub index: Option<usize>,
+}
+
+/// What garbage collection would remove from a document now
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    /// Tombstones of deleted characters the document holds
+    pub tombstones: usize,
+    /// Tombstones every barrier lets go of
+    pub collectible: usize,
+    /// Estimated bytes collecting them would free
+    pub estimated_savings: usize,
+    /// Clients whose barriers hold back at least one tombstone, sorted
+    pub blocking_clients: Vec<String>,
+}
+
+/// Entries of one part of a document, and the bytes they take up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    pub count: usize,
+    /// Estimated bytes, heap data included
+    pub bytes: usize,
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.count += other.count;
+        self.bytes += other.bytes;
+    }
+}
+
+/// What a document holds in memory, by part
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryReport {
+    /// Visible characters
+    pub live: MemoryUsage,
+    /// Deleted characters kept until garbage collection
+    pub tombstones: MemoryUsage,
+    /// Applied operations, kept as the document's history
+    pub operations: MemoryUsage,
+    /// Chunks of the index of visible characters and lines
+    pub indexes: MemoryUsage,
+}
+
+impl MemoryReport {
+    /// Get the estimated bytes of every part together
+    pub fn total_bytes(&self) -> usize {
+        self.live.bytes + self.tombstones.bytes + self.operations.bytes + self.indexes.bytes
+    }
+}
+
+impl AddAssign for MemoryReport {
+    fn add_assign(&mut self, other: Self) {
+        self.live += other.live;
+        self.tombstones += other.tombstones;
+        self.operations += other.operations;
+        self.indexes += other.indexes;
+    }
+}
 
 /// A character in the CRDT document
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -26,10 +142,192 @@ struct Character {
     position: Position,
     /// Whether this character has been deleted
     deleted: bool,
+    /// ID of the client that inserted this character
+    author: String,
+    /// Timestamp of the insert, used to order characters at equal positions
+    timestamp: Timestamp,
+}
+
+/// The characters of a document in position order, tombstones included,
+/// with the index of their visible characters and lines. Serialized as the
+/// list alone; the index is rebuilt when deserializing.
+///
+/// The list is kept in `Chunks`, like the flags of the `LineIndex`.
+/// Finding a position searches the chunks by their last character, then a
+/// single chunk; finding an index descends the tree of chunk lengths. An
+/// insert or delete moves characters within one chunk only.
+#[derive(Debug, Clone, Default)]
+struct Characters {
+    list: Chunks<Character>,
+    /// Boxed to keep documents small where they are moved around by value
+    lines: Box<LineIndex>,
+}
+
+impl Characters {
+    /// Count the characters, tombstones included
+    fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&Character> {
+        self.list.get(index)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Character> {
+        self.list.iter()
+    }
+
+    /// Iterate over the characters from `index` on
+    fn iter_from(&self, index: usize) -> impl Iterator<Item = &Character> {
+        self.list.iter_from(index)
+    }
+
+    /// Find the index of the first character `before` is false for, where
+    /// it is true for every character before that one, as
+    /// `slice::partition_point` does
+    fn partition_point(&self, before: impl FnMut(&Character) -> bool) -> usize {
+        self.list.partition_point(before)
+    }
+
+    fn insert(&mut self, index: usize, character: Character) {
+        self.lines.insert(index, !character.deleted, character.value == '\n');
+        self.list.insert(index, character);
+    }
+
+    /// Mark the character at `index` deleted
+    fn delete(&mut self, index: usize) -> &Character {
+        self.lines.hide(index);
+        let character = self.list.get_mut(index).expect("character index in bounds");
+        character.deleted = true;
+        character
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&Character) -> bool) {
+        let list: Vec<Character> = self.take().into_iter().filter(|c| keep(c)).collect();
+        *self = Self::from(list);
+    }
+
+    fn take(&mut self) -> Vec<Character> {
+        let list = self.list.take();
+        *self = Self::default();
+        list
+    }
+}
+
+impl Index<usize> for Characters {
+    type Output = Character;
+
+    fn index(&self, index: usize) -> &Character {
+        self.get(index).expect("character index in bounds")
+    }
+}
+
+impl From<Vec<Character>> for Characters {
+    fn from(list: Vec<Character>) -> Self {
+        let lines = Box::new(LineIndex::build(list.iter().map(|c| (!c.deleted, c.value == '\n'))));
+        Self { list: Chunks::build(list), lines }
+    }
+}
+
+impl Serialize for Characters {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut list = serializer.serialize_seq(Some(self.len()))?;
+        for character in self.iter() {
+            list.serialize_element(character)?;
+        }
+        list.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Characters {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Character>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// The operations applied to a document, in order, with the state vector
+/// counting them and an index of the inserts by position. Serialized as
+/// the list alone; the rest is rebuilt when deserializing.
+#[derive(Debug, Clone, Default)]
+struct Operations {
+    list: Vec<Operation>,
+    seen: StateVector,
+    /// For each inserted position, the index of each insert in `list` and
+    /// how many of its author's operations came before it
+    inserts: BTreeMap<Position, Vec<(usize, u64)>>,
+}
+
+impl Operations {
+    fn push(&mut self, operation: Operation) {
+        if let Operation::Insert { client_id, position, .. } = &operation {
+            let sequence = self.seen.get(client_id);
+            self.inserts.entry(position.clone()).or_default().push((self.list.len(), sequence));
+        }
+        self.seen.observe(&operation);
+        self.list.push(operation);
+    }
+
+    fn retain(&mut self, keep: impl FnMut(&Operation) -> bool) {
+        self.list.retain(keep);
+        *self = Self::from(mem::take(&mut self.list));
+    }
+
+    /// Get the newest insert at a position, by `author` if given
+    fn inserted_at(&self, position: &Position, author: Option<&str>) -> Option<&Operation> {
+        self.inserts
+            .get(position)?
+            .iter()
+            .rev()
+            .map(|&(index, _)| &self.list[index])
+            .find(|operation| author.is_none_or(|author| operation.client_id() == author))
+    }
+
+    /// Get the inserts between `start` and `end` made by operations counted
+    /// in `seen`, in position order
+    fn inserted<'a>(&'a self, start: &Position, end: &Position, seen: &'a StateVector) -> impl Iterator<Item = &'a Operation> {
+        let entries = (start <= end).then(|| self.inserts.range::<Position, _>((Bound::Included(start), Bound::Included(end))));
+        entries
+            .into_iter()
+            .flatten()
+            .flat_map(|(_, entries)| entries)
+            .map(|&(index, sequence)| (&self.list[index], sequence))
+            .filter(move |(operation, sequence)| *sequence < seen.get(operation.client_id()))
+            .map(|(operation, _)| operation)
+    }
+}
+
+impl From<Vec<Operation>> for Operations {
+    fn from(list: Vec<Operation>) -> Self {
+        let mut operations = Self { list: Vec::with_capacity(list.len()), ..Self::default() };
+        for operation in list {
+            operations.push(operation);
+        }
+        operations
+    }
+}
+
+impl Deref for Operations {
+    type Target = [Operation];
+
+    fn deref(&self) -> &[Operation] {
+        &self.list
+    }
+}
+
+impl Serialize for Operations {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.list.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Operations {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Operation>::deserialize(deserializer).map(Self::from)
+    }
 }
 
 /// An operation that can be applied to the document
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Operation {
     /// Insert a character at a position
     Insert {
@@ -48,6 +346,26 @@ pub enum Operation {
         client_id: String,
         /// Position of the character to delete
         position: Position,
+        /// ID of the client that inserted the character, telling apart
+        /// characters inserted at the same position; without it, the first
+        /// character at the position is deleted
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        author: Option<String>,
+        /// Timestamp of the operation
+        timestamp: Timestamp,
+    },
+    /// Delete every character from `start` to `end`, both included, that
+    /// the client had seen
+    DeleteRange {
+        /// ID of the client that created this operation
+        client_id: String,
+        /// Position of the first character to delete
+        start: Position,
+        /// Position of the last character to delete
+        end: Position,
+        /// Operations the client had applied; characters inserted by
+        /// operations beyond them were concurrent and are kept
+        seen: StateVector,
         /// Timestamp of the operation
         timestamp: Timestamp,
     },
@@ -71,6 +389,31 @@ impl Operation {
         Self::Delete {
             client_id,
             position,
+            author: None,
+            timestamp,
+        }
+    }
+
+    /// Create a delete of the character `author` inserted at a position
+    pub fn delete_of(client_id: String, position: Position, author: String) -> Self {
+        let timestamp = Timestamp::new(client_id.clone());
+        Self::Delete {
+            client_id,
+            position,
+            author: Some(author),
+            timestamp,
+        }
+    }
+
+    /// Create a range delete of the characters from `start` to `end`, both
+    /// included, that `document` holds; pass the replica the client edits
+    pub fn delete_range(client_id: String, document: &Document, start: Position, end: Position) -> Self {
+        let timestamp = Timestamp::new(client_id.clone());
+        Self::DeleteRange {
+            client_id,
+            start,
+            end,
+            seen: StateVector::of(document),
             timestamp,
         }
     }
@@ -80,14 +423,17 @@ impl Operation {
         match self {
             Operation::Insert { client_id, .. } => client_id,
             Operation::Delete { client_id, .. } => client_id,
+            Operation::DeleteRange { client_id, .. } => client_id,
         }
     }
 
-    /// Returns the position associated with this operation
+    /// Returns the position associated with this operation; the start of a
+    /// range delete
     pub fn position(&self) -> &Position {
         match self {
             Operation::Insert { position, .. } => position,
             Operation::Delete { position, .. } => position,
+            Operation::DeleteRange { start, .. } => start,
         }
     }
 
@@ -96,6 +442,23 @@ impl Operation {
         match self {
             Operation::Insert { timestamp, .. } => timestamp,
             Operation::Delete { timestamp, .. } => timestamp,
+            Operation::DeleteRange { timestamp, .. } => timestamp,
+        }
+    }
+
+    /// Check whether the operation deletes characters
+    pub fn is_delete(&self) -> bool {
+        !matches!(self, Operation::Insert { .. })
+    }
+
+    /// Get the checksum regions the operation touches
+    fn regions(&self) -> RangeInclusive<usize> {
+        match self {
+            Operation::DeleteRange { start, end, .. } => ContentHash::region_of(start)..=ContentHash::region_of(end),
+            operation => {
+                let region = ContentHash::region_of(operation.position());
+                region..=region
+            }
         }
     }
 }
@@ -106,13 +469,36 @@ pub struct Document {
     /// Unique identifier for this document
     id: String,
     /// List of characters in the document
-    characters: Vec<Character>,
+    characters: Characters,
     /// List of operations that have been applied
-    operations: Vec<Operation>,
+    operations: Operations,
     /// Number of deleted characters before triggering garbage collection
     garbage_collection_threshold: Option<usize>,
     /// Count of deleted characters since last garbage collection
     deleted_count: usize,
+    /// Concurrency metrics for operations applied to this document
+    #[serde(default)]
+    stats: ConcurrencyStats,
+    /// Number of operations applied; increases by one on every apply
+    #[serde(default)]
+    version: u64,
+    /// Ordering of inserts at equal positions
+    #[serde(default)]
+    tie_break: TieBreak,
+    /// Hash of the visible content, updated on every edit
+    #[serde(default)]
+    hash: ContentHash,
+    /// Operations each client has acknowledged; tombstones stay until every
+    /// barrier covers their deletion
+    #[serde(default)]
+    gc_barriers: BTreeMap<String, StateVector>,
+    /// Tombstones the barriers kept at the last garbage collection
+    #[serde(default)]
+    kept_tombstones: usize,
+    /// Number of repairs applied; they rewrite the history without
+    /// changing the version
+    #[serde(default)]
+    repairs: u64,
 }
 
 impl Document {
@@ -120,10 +506,17 @@ impl Document {
     pub fn new(id: String) -> Self {
         Self {
             id,
-            characters: Vec::new(),
-            operations: Vec::new(),
+            characters: Characters::default(),
+            operations: Operations::default(),
             garbage_collection_threshold: None,
             deleted_count: 0,
+            stats: ConcurrencyStats::default(),
+            version: 0,
+            tie_break: TieBreak::default(),
+            hash: ContentHash::new(),
+            gc_barriers: BTreeMap::new(),
+            kept_tombstones: 0,
+            repairs: 0,
         }
     }
 
@@ -132,47 +525,284 @@ impl Document {
         &self.id
     }
 
+    /// Copy the document under a new ID, with its history and settings
+    pub fn fork(&self, id: String) -> Self {
+        Self {
+            id,
+            ..self.clone()
+        }
+    }
+
+    /// Get the document version, the number of operations applied so far.
+    /// Clients use it to anchor delta sync and cache validation.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Get the number of repairs applied to the document. A repair
+    /// replaces part of the history, so copies kept as a history, such as
+    /// a store's operation log, have to be written again as a whole.
+    pub fn repairs(&self) -> u64 {
+        self.repairs
+    }
+
     /// Get the content of the document as a string
     pub fn get_content(&self) -> String {
         self.characters.iter().map(|c| c.value).collect()
     }
 
     /// Apply a CRDT operation to the document and record it.
-    pub fn apply_operation(&mut self, op: Operation) -> Result<(), &'static str> {
+    ///
+    /// Inserts at an equal position are ordered by the document's
+    /// tie-break strategy; see `TieBreak`.
+    /// Returns the new version and the visible index that changed.
+    pub fn apply_operation(&mut self, op: Operation) -> Result<AppliedOp, DocumentError> {
         match &op {
-            Operation::Insert { character, position, .. } => {
-                let new_char = Character {
+            Operation::Insert { position, .. } if position.is_end() => {
+                return Err(DocumentError::InvalidPosition("cannot insert at the end sentinel".to_string()));
+            }
+            Operation::DeleteRange { start, end, .. } if start > end => {
+                return Err(DocumentError::InvalidPosition("range delete ends before it starts".to_string()));
+            }
+            _ => {}
+        }
+
+        let concurrent = self.stats.observe(&op);
+        let index = match &op {
+            Operation::Insert { client_id, character, position, timestamp } => {
+                self.hash.add(position, *character, timestamp);
+                if self.anchor_missing(position) {
+                    self.stats.record_unknown_anchor();
+                    log::debug!("Insert at {:?} in {} has an unknown anchor", position.path(), self.id);
+                }
+                let index = self.insert_character_in_doc(Character {
                     value: *character,
                     position: position.clone(),
                     deleted: false,
-                };
-                self.insert_character_in_doc(new_char);
-            }
-            Operation::Delete { position, .. } => {
-                self.delete_character_in_doc(position);
+                    author: client_id.clone(),
+                    timestamp: timestamp.clone(),
+                });
+                if concurrent {
+                    self.record_interleaving(index);
+                }
+                Some(self.visible_index(index))
             }
+            Operation::Delete { position, author, .. } => self.delete_character_in_doc(position, author.as_deref()),
+            Operation::DeleteRange { start, end, seen, .. } => self.delete_range_in_doc(start, end, seen),
         };
+
         self.operations.push(op);
-        Ok(())
+        self.version += 1;
+
+        // Collect garbage once enough characters have been deleted
+        if let Some(threshold) = self.garbage_collection_threshold {
+            if self.deleted_count.saturating_sub(self.kept_tombstones) >= threshold {
+                self.collect_garbage();
+            }
+        }
+
+        Ok(AppliedOp {
+            version: self.version,
+            index,
+        })
     }
 
-    /// Inserts a character into the document at the correct sorted position.
-    fn insert_character_in_doc(&mut self, new_char: Character) {
-        let pos = self.characters.binary_search_by(|c| c.position.cmp(&new_char.position));
-        let index = pos.unwrap_or_else(|e| e);
+    /// Check whether an insert's anchor is missing from the document.
+    ///
+    /// `Position::between` extends the left neighbour's path when there is
+    /// no room between two siblings, so a position's parent path names the
+    /// character it was anchored to. The anchor may be gone because it was a
+    /// tombstone that garbage collection removed. Placement doesn't depend on
+    /// it: positions are totally ordered, so the insert still lands right
+    /// after its nearest surviving predecessor, exactly where a replica that
+    /// kept the tombstone shows it.
+    fn anchor_missing(&self, position: &Position) -> bool {
+        let path = position.path();
+        if path.len() < 2 {
+            return false;
+        }
+        let anchor = Position::new(path[..path.len() - 1].to_vec());
+        self.find_character_index(&anchor).is_none()
+    }
+
+    /// Inserts a character in position order, breaking ties between equal
+    /// positions with the tie-break strategy. Returns its index in the
+    /// character list.
+    fn insert_character_in_doc(&mut self, new_char: Character) -> usize {
+        let tie_break = self.tie_break;
+        let index = self.characters.partition_point(|c| match c.position.cmp(&new_char.position) {
+            Ordering::Equal => tie_break.compare(&c.timestamp, &new_char.timestamp) != Ordering::Greater,
+            ordering => ordering == Ordering::Less,
+        });
+        let tied = |c: &Character| c.position == new_char.position;
+        if (index > 0 && tied(&self.characters[index - 1])) || self.characters.get(index).is_some_and(tied) {
+            self.stats.record_tie_break();
+        }
         self.characters.insert(index, new_char);
+        index
+    }
+
+    /// Count the visible characters before `index` in the character list
+    fn visible_index(&self, index: usize) -> usize {
+        self.characters.lines.visible_before(index)
+    }
+
+    /// Count a concurrent insert at `index` that split another client's run
+    fn record_interleaving(&mut self, index: usize) {
+        if index == 0 || index + 1 >= self.characters.len() {
+            return;
+        }
+        let before = &self.characters[index - 1].author;
+        let after = &self.characters[index + 1].author;
+        if before == after && *before != self.characters[index].author {
+            self.stats.record_interleaving();
+        }
+    }
+
+    /// Get the checksum of the visible content. It is maintained on every
+    /// edit, so this is O(1); replicas with equal content have equal checksums.
+    pub fn checksum(&self) -> u64 {
+        self.hash.checksum()
+    }
+
+    /// Get the per-region content hash, to find where replicas diverged
+    pub fn content_hash(&self) -> &ContentHash {
+        &self.hash
     }
 
-    /// Marks a character as deleted in the document.
-    fn delete_character_in_doc(&mut self, position: &Position) {
-        if let Some(char) = self.characters.iter_mut().find(|c| c.position == *position) {
-            if !char.deleted {
-                char.deleted = true;
-                self.deleted_count += 1;
+    /// Get the recorded operations that touch the given checksum regions,
+    /// in the order they were applied
+    pub fn region_operations(&self, regions: &[usize]) -> Vec<Operation> {
+        self.operations
+            .iter()
+            .filter(|op| op.regions().any(|region| regions.contains(&region)))
+            .cloned()
+            .collect()
+    }
+
+    /// Rebuild the given checksum regions from another replica's operations
+    /// for them, e.g. from a repair response. Characters and history in those
+    /// regions are replaced; the rest of the document is untouched. The
+    /// version is not changed, as it counts operations this replica applied;
+    /// `repairs()` counts the repair instead.
+    pub fn repair_regions(&mut self, regions: &[usize], operations: Vec<Operation>) {
+        self.repairs += 1;
+        let in_repair = |position: &Position| regions.contains(&ContentHash::region_of(position));
+
+        let mut kept = Vec::with_capacity(self.characters.len());
+        for character in self.characters.take() {
+            if !in_repair(&character.position) {
+                kept.push(character);
+            } else if character.deleted {
+                self.deleted_count -= 1;
+            } else {
+                self.hash.remove(&character.position, character.value, &character.timestamp);
+            }
+        }
+        self.characters = Characters::from(kept);
+        // Range deletes across regions are replaced with the rest
+        let touches_repair = |op: &Operation| op.regions().any(|region| regions.contains(&region));
+        self.operations.retain(|op| !touches_repair(op));
+
+        for op in operations {
+            if !touches_repair(&op) {
+                log::warn!("Ignored repair operation outside the repaired regions of {}", self.id);
+                continue;
+            }
+            match &op {
+                Operation::Insert { client_id, character, position, timestamp } => {
+                    self.hash.add(position, *character, timestamp);
+                    self.insert_character_in_doc(Character {
+                        value: *character,
+                        position: position.clone(),
+                        deleted: false,
+                        author: client_id.clone(),
+                        timestamp: timestamp.clone(),
+                    });
+                }
+                Operation::Delete { position, author, .. } => {
+                    self.delete_character_in_doc(position, author.as_deref());
+                }
+                Operation::DeleteRange { start, end, seen, .. } => {
+                    self.delete_range_in_doc(start, end, seen);
+                }
+            }
+            self.operations.push(op);
+        }
+    }
+
+    /// Get the concurrency statistics of this document
+    pub fn concurrency_stats(&self) -> &ConcurrencyStats {
+        &self.stats
+    }
+
+    /// Marks the character `author` inserted at a position as deleted, or
+    /// the first character there without an author, returning its visible
+    /// index before the deletion. Returns `None` if it was already deleted.
+    fn delete_character_in_doc(&mut self, position: &Position, author: Option<&str>) -> Option<usize> {
+        let index = self.find_authored_index(position, author)?;
+        if self.characters[index].deleted {
+            return None;
+        }
+
+        let visible = self.visible_index(index);
+        let character = self.characters.delete(index);
+        self.hash.remove(&character.position, character.value, &character.timestamp);
+        self.deleted_count += 1;
+        Some(visible)
+    }
+
+    /// Marks the live characters of a range delete as deleted, returning
+    /// the visible index of the first before the deletion
+    fn delete_range_in_doc(&mut self, start: &Position, end: &Position, seen: &StateVector) -> Option<usize> {
+        let mut first = None;
+        for (position, timestamp, _) in self.range_targets(start, end, seen) {
+            let Some(index) = self.find_inserted_index(&position, &timestamp) else {
+                continue;
+            };
+            if self.characters[index].deleted {
+                continue;
             }
+            let visible = self.visible_index(index);
+            first = Some(first.map_or(visible, |first: usize| first.min(visible)));
+            let character = self.characters.delete(index);
+            self.hash.remove(&character.position, character.value, &character.timestamp);
+            self.deleted_count += 1;
+        }
+        first
+    }
+
+    /// Get the characters a range delete covers, tombstoned or not, with
+    /// the positions they were inserted at, in position order
+    pub fn range_characters(&self, start: &Position, end: &Position, seen: &StateVector) -> Vec<(char, Position)> {
+        self.range_targets(start, end, seen)
+            .into_iter()
+            .map(|(position, _, character)| (character, position))
+            .collect()
+    }
+
+    /// Get the character last inserted at a position, by `author` if
+    /// given, tombstoned or not. Read from the operation log, so it
+    /// outlives garbage collection.
+    pub fn inserted_character(&self, position: &Position, author: Option<&str>) -> Option<char> {
+        match self.operations.inserted_at(position, author)? {
+            Operation::Insert { character, .. } => Some(*character),
+            _ => None,
         }
     }
 
+    /// Find the characters a range delete covers: those inserted between
+    /// `start` and `end` by operations counted in `seen`
+    fn range_targets(&self, start: &Position, end: &Position, seen: &StateVector) -> Vec<(Position, Timestamp, char)> {
+        self.operations
+            .inserted(start, end, seen)
+            .filter_map(|operation| match operation {
+                Operation::Insert { position, timestamp, character, .. } => Some((position.clone(), timestamp.clone(), *character)),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Get the current content of the document as a string
     pub fn content(&self) -> String {
         self.characters
@@ -182,16 +812,100 @@ impl Document {
             .collect()
     }
 
+    /// Get the first `count` characters of the document's content
+    pub fn prefix(&self, count: usize) -> String {
+        self.characters
+            .iter()
+            .filter(|c| !c.deleted)
+            .take(count)
+            .map(|c| c.value)
+            .collect()
+    }
+
+    /// Get the clients whose characters are visible, in order of their
+    /// first character
+    pub fn authors(&self) -> Vec<String> {
+        let mut authors: Vec<String> = Vec::new();
+        for c in self.characters.iter().filter(|c| !c.deleted) {
+            if !authors.contains(&c.author) {
+                authors.push(c.author.clone());
+            }
+        }
+        authors
+    }
+
     /// Get a reference to the list of operations
     pub fn operations(&self) -> &[Operation] {
         &self.operations
     }
 
+    /// Get the state vector of the document: the operations applied,
+    /// counted per author
+    pub fn state_vector(&self) -> &StateVector {
+        &self.operations.seen
+    }
+
+    /// Get the operations a replica with the given state vector lacks, in
+    /// the order they were applied. An empty state vector gets the whole
+    /// history.
+    pub fn operations_since(&self, known: &StateVector) -> Vec<Operation> {
+        if known.covers(self.state_vector()) {
+            return Vec::new();
+        }
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        let mut missing = Vec::new();
+        for operation in self.operations.iter() {
+            let count = counts.entry(operation.client_id()).or_insert(0);
+            if *count >= known.get(operation.client_id()) {
+                missing.push(operation.clone());
+            }
+            *count += 1;
+        }
+        missing
+    }
+
+    /// Get the operations applied after a version, in order. `None` when
+    /// the version is ahead of the document, or when a repair reordered
+    /// the history so that versions no longer index it.
+    pub fn operations_after(&self, version: u64) -> Option<&[Operation]> {
+        if version > self.version || self.operations.len() as u64 != self.version {
+            return None;
+        }
+        Some(&self.operations[version as usize..])
+    }
+
     /// Get the total number of characters (including deleted ones)
     pub fn character_count(&self) -> usize {
         self.characters.len()
     }
 
+    /// Get the number of visible (non-deleted) characters
+    pub fn len(&self) -> usize {
+        self.characters.len() - self.deleted_count
+    }
+
+    /// Check whether the document has no visible characters
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count the lines of the content; an empty document has one
+    pub fn line_count(&self) -> usize {
+        self.characters.lines.line_count()
+    }
+
+    /// Get the line and column, both from 0, of a character offset into
+    /// the content; the offset may be the end of the content
+    pub fn line_column(&self, offset: usize) -> Option<(usize, usize)> {
+        self.characters.lines.line_column(offset)
+    }
+
+    /// Get the character offset into the content of a line and column,
+    /// which may be the end of the line but not past it
+    pub fn offset_of(&self, line: usize, column: usize) -> Option<usize> {
+        self.characters.lines.offset_of(line, column)
+    }
+
     /// Set the threshold for automatic garbage collection
     /// When the number of deleted characters reaches this threshold,
     /// garbage collection will be triggered automatically.
@@ -200,58 +914,328 @@ impl Document {
         self.garbage_collection_threshold = Some(threshold);
     }
 
-    /// Remove all deleted characters from the document
+    /// Set how inserts at equal positions are ordered. Set this before
+    /// applying operations; characters already in the document keep their
+    /// order.
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+    }
+
+    /// Get the tie-break strategy of this document
+    pub fn tie_break(&self) -> TieBreak {
+        self.tie_break
+    }
+
+    /// Remove the deleted characters no barrier holds back
     pub fn collect_garbage(&mut self) {
-        self.characters.retain(|c| !c.deleted);
-        self.deleted_count = 0;
+        if self.gc_barriers.is_empty() {
+            self.characters.retain(|c| !c.deleted);
+            self.deleted_count = 0;
+            self.kept_tombstones = 0;
+            return;
+        }
+        let (collectible, _) = self.collectible_tombstones();
+        let mut index = 0;
+        self.characters.retain(|_| {
+            index += 1;
+            !collectible[index - 1]
+        });
+        self.deleted_count = self.characters.iter().filter(|c| c.deleted).count();
+        self.kept_tombstones = self.deleted_count;
     }
 
-    /// Apply an operation to the document
-    pub fn apply(&mut self, operation: Operation) {
-        match &operation {
-            Operation::Insert { character, position, .. } => {
-                // Find the insertion index
-                let index = self.find_insert_index(position);
-                
-                // Insert the character
-                self.characters.insert(index, Character {
-                    value: *character,
-                    position: position.clone(),
-                    deleted: false,
-                });
-            }
-            Operation::Delete { position, .. } => {
-                // Find and mark the character as deleted
-                if let Some(index) = self.find_character_index(position) {
-                    self.characters[index].deleted = true;
-                    self.deleted_count += 1;
-
-                    // Check if we need to run garbage collection
-                    if let Some(threshold) = self.garbage_collection_threshold {
-                        if self.deleted_count >= threshold {
-                            self.collect_garbage();
+    /// Record the operations a client has acknowledged, holding back the
+    /// collection of tombstones whose deletion it hasn't seen
+    pub fn set_gc_barrier(&mut self, client_id: &str, seen: StateVector) {
+        self.gc_barriers.insert(client_id.to_string(), seen);
+    }
+
+    /// Stop holding garbage collection back for a client. Returns whether
+    /// it had a barrier.
+    pub fn release_gc_barrier(&mut self, client_id: &str) -> bool {
+        let released = self.gc_barriers.remove(client_id).is_some();
+        if released {
+            self.kept_tombstones = 0;
+        }
+        released
+    }
+
+    /// Get the barriers holding garbage collection back, by client
+    pub fn gc_barriers(&self) -> &BTreeMap<String, StateVector> {
+        &self.gc_barriers
+    }
+
+    /// Report what garbage collection would remove now, without removing
+    /// anything
+    pub fn gc_report(&self) -> GcReport {
+        let (collectible, blocking) = self.collectible_tombstones();
+        let freed = self.characters.iter().zip(&collectible).filter(|(_, collectible)| **collectible);
+        GcReport {
+            tombstones: self.deleted_count,
+            collectible: collectible.iter().filter(|collectible| **collectible).count(),
+            estimated_savings: freed.map(|(c, _)| footprint(c)).sum(),
+            blocking_clients: blocking.into_iter().collect(),
+        }
+    }
+
+    /// Break down what the document holds in memory: its live characters,
+    /// tombstones, operation log and indexes, counted and estimated in bytes
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        for character in self.characters.iter() {
+            let part = if character.deleted { &mut report.tombstones } else { &mut report.live };
+            *part += MemoryUsage { count: 1, bytes: footprint(character) };
+        }
+        for operation in self.operations.iter() {
+            report.operations += MemoryUsage { count: 1, bytes: operation_footprint(operation) };
+        }
+        let (chunks, bytes) = self.characters.lines.memory();
+        report.indexes = MemoryUsage { count: chunks, bytes };
+        report
+    }
+
+    /// Mark the characters garbage collection may remove, and find the
+    /// clients whose barriers keep any of the others
+    fn collectible_tombstones(&self) -> (Vec<bool>, BTreeSet<String>) {
+        // Where each delete falls in its author's history
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        let mut deletes: BTreeMap<&Position, Vec<(&str, u64)>> = BTreeMap::new();
+        // Where each insert falls in its author's history, to find what
+        // range deletes covered
+        let mut inserts: BTreeMap<&Position, Vec<(&str, u64)>> = BTreeMap::new();
+        for operation in self.operations.iter() {
+            let count = counts.entry(operation.client_id()).or_insert(0);
+            match operation {
+                Operation::Insert { client_id, position, .. } => {
+                    inserts.entry(position).or_default().push((client_id, *count));
+                }
+                Operation::Delete { client_id, position, .. } => {
+                    deletes.entry(position).or_default().push((client_id, *count));
+                }
+                Operation::DeleteRange { client_id, start, end, seen, .. } => {
+                    for (position, inserted) in inserts.range::<&Position, _>(start..=end) {
+                        if inserted.iter().any(|(author, index)| seen.get(author) > *index) {
+                            deletes.entry(position).or_default().push((client_id, *count));
                         }
                     }
                 }
             }
+            *count += 1;
+        }
+
+        let mut blocking = BTreeSet::new();
+        let collectible = self.characters
+            .iter()
+            .map(|c| {
+                if !c.deleted {
+                    return false;
+                }
+                let known = deletes.get(&c.position).map(Vec::as_slice).unwrap_or_default();
+                let mut collectible = true;
+                for (client_id, seen) in &self.gc_barriers {
+                    if !known.iter().any(|(author, index)| seen.get(author) > *index) {
+                        blocking.insert(client_id.clone());
+                        collectible = false;
+                    }
+                }
+                collectible
+            })
+            .collect();
+        (collectible, blocking)
+    }
+
+    /// Apply an operation to the document, logging and skipping it if it
+    /// is rejected. Use `apply_operation` to learn what changed.
+    pub fn apply(&mut self, operation: Operation) {
+        if let Err(e) = self.apply_operation(operation) {
+            log::warn!("Skipped operation on document {}: {}", self.id, e);
+        }
+    }
+
+    /// Apply an operation unless the document already reflects it.
+    ///
+    /// Replicas that exchange histories, after a reconnect or between
+    /// federated servers, see some operations twice. An insert is known when
+    /// its character (position and timestamp) is present, tombstoned or not;
+    /// a delete is known when its character is already deleted. Returns
+    /// `None` for known operations, which leave the document untouched.
+    pub fn merge_operation(&mut self, op: Operation) -> Result<Option<AppliedOp>, DocumentError> {
+        if self.knows(&op) {
+            return Ok(None);
+        }
+        self.apply_operation(op).map(Some)
+    }
+
+    /// Check whether the document already reflects an operation
+    pub fn knows(&self, op: &Operation) -> bool {
+        match op {
+            Operation::Insert { position, timestamp, .. } => {
+                let start = self.characters.partition_point(|c| c.position < *position);
+                self.characters
+                    .iter_from(start)
+                    .take_while(|c| c.position == *position)
+                    .any(|c| c.timestamp == *timestamp)
+            }
+            Operation::Delete { position, author, .. } => self
+                .find_authored_index(position, author.as_deref())
+                .is_some_and(|index| self.characters[index].deleted),
+            Operation::DeleteRange { start, end, seen, .. } => {
+                let targets = self.range_targets(start, end, seen);
+                !targets.is_empty() && targets.iter().all(|(position, timestamp, _)| {
+                    self.find_inserted_index(position, timestamp).is_none_or(|index| self.characters[index].deleted)
+                })
+            }
         }
-        
-        // Record the operation
-        self.operations.push(operation);
     }
 
-    /// Find the index where a character should be inserted
-    fn find_insert_index(&self, position: &Position) -> usize {
+    /// Get the visible character at a position, if there is one
+    pub fn character_at(&self, position: &Position) -> Option<char> {
+        self.find_character_index(position)
+            .map(|index| &self.characters[index])
+            .filter(|c| !c.deleted)
+            .map(|c| c.value)
+    }
+
+    /// Get the visible character `author` inserted at a position, if there
+    /// is one; characters tied at a position have different authors
+    pub fn character_by(&self, position: &Position, author: &str) -> Option<char> {
+        self.find_authored_index(position, Some(author))
+            .map(|index| &self.characters[index])
+            .filter(|c| !c.deleted)
+            .map(|c| c.value)
+    }
+
+    /// Generate a position right after the character at `position` and
+    /// before whatever follows it, to place a replacement next to it
+    pub fn position_after(&self, position: &Position) -> Position {
+        match self.room_after(position) {
+            (anchor, Some(next)) => Position::between(&anchor, next),
+            (anchor, None) => {
+                let mut path = anchor.path().clone();
+                path.push(1);
+                Position::new(path)
+            }
+        }
+    }
+
+    /// Generate `count` ascending positions after `position` and before
+    /// whatever follows it, to place a run of text there. Past the last
+    /// character the positions stay at the same depth.
+    pub fn positions_after(&self, position: &Position, count: usize) -> Vec<Position> {
+        let (anchor, next) = self.room_after(position);
+        if let Some(next) = next {
+            let mut previous = anchor;
+            return (0..count)
+                .map(|_| {
+                    previous = Position::between(&previous, next);
+                    previous.clone()
+                })
+                .collect();
+        }
+        let mut path = anchor.path().clone();
+        let Some(last) = path.pop() else {
+            return Position::spread(count);
+        };
+        if u32::try_from(count).ok().and_then(|count| last.checked_add(count)).is_some() {
+            return (1..=count as u32)
+                .map(|i| {
+                    let mut path = path.clone();
+                    path.push(last + i);
+                    Position::new(path)
+                })
+                .collect();
+        }
+        // No room left at this depth: spread the run one level deeper
+        path.push(last);
+        Position::spread(count)
+            .into_iter()
+            .map(|spread| {
+                let mut path = path.clone();
+                path.extend(spread.path());
+                Position::new(path)
+            })
+            .collect()
+    }
+
+    /// Find the room after the character at `position`: the position to
+    /// follow, and that of the next character, if any. Characters nothing
+    /// sorts before (see `Position::has_room`) are followed instead.
+    fn room_after(&self, position: &Position) -> (Position, Option<&Position>) {
+        let mut anchor = position.clone();
+        loop {
+            let next = self.characters.partition_point(|c| c.position <= anchor);
+            match self.characters.get(next) {
+                Some(c) if !Position::has_room(&anchor, &c.position) => anchor = c.position.clone(),
+                Some(c) => return (anchor, Some(&c.position)),
+                None => return (anchor, None),
+            }
+        }
+    }
+
+    /// Iterate over the visible characters with their positions, in order
+    pub fn visible_characters(&self) -> impl Iterator<Item = (char, &Position)> {
+        self.characters.iter().filter(|c| !c.deleted).map(|c| (c.value, &c.position))
+    }
+
+    /// Iterate over every character, tombstones included, in order
+    pub(crate) fn all_characters(&self) -> impl Iterator<Item = (char, &Position, &Timestamp, bool)> {
+        self.characters.iter().map(|c| (c.value, &c.position, &c.timestamp, c.deleted))
+    }
+
+    /// Find the index of the character an insert with the given position
+    /// and timestamp placed
+    fn find_inserted_index(&self, position: &Position, timestamp: &Timestamp) -> Option<usize> {
+        let start = self.characters.partition_point(|c| c.position < *position);
         self.characters
-            .iter()
-            .position(|c| c.position > *position)
-            .unwrap_or(self.characters.len())
+            .iter_from(start)
+            .take_while(|c| c.position == *position)
+            .position(|c| c.timestamp == *timestamp)
+            .map(|offset| start + offset)
     }
 
-    /// Find the index of a character with the given position
+    /// Find the index of the character `author` inserted at a position, or
+    /// of the first character there without an author
+    fn find_authored_index(&self, position: &Position, author: Option<&str>) -> Option<usize> {
+        let Some(author) = author else {
+            return self.find_character_index(position);
+        };
+        let start = self.characters.partition_point(|c| c.position < *position);
+        self.characters
+            .iter_from(start)
+            .take_while(|c| c.position == *position)
+            .position(|c| c.author == author)
+            .map(|offset| start + offset)
+    }
+
+    /// Find the index of the first character at the given position
     fn find_character_index(&self, position: &Position) -> Option<usize> {
+        let index = self.characters.partition_point(|c| c.position < *position);
         self.characters
-            .iter()
-            .position(|c| c.position == *position)
+            .get(index)
+            .filter(|c| c.position == *position)
+            .map(|_| index)
     }
 }
+
+/// Estimate the bytes a character takes up, its heap data included
+fn footprint(character: &Character) -> usize {
+    mem::size_of::<Character>()
+        + character.position.path().len() * mem::size_of::<u32>()
+        + character.author.len()
+        + character.timestamp.client_id().len()
+}
+
+/// Estimate the bytes an operation takes up, its heap data included
+fn operation_footprint(operation: &Operation) -> usize {
+    let range = match operation {
+        Operation::DeleteRange { end, seen, .. } => {
+            end.path().len() * mem::size_of::<u32>() + seen.len() * (mem::size_of::<String>() + mem::size_of::<u64>())
+        }
+        _ => 0,
+    };
+    mem::size_of::<Operation>()
+        + operation.position().path().len() * mem::size_of::<u32>()
+        + operation.client_id().len()
+        + operation.timestamp().client_id().len()
+        + range
+}
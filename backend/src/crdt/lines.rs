@@ -0,0 +1,201 @@
+/*
+ * File: crdt/lines.rs
+ * Purpose: Index of visible characters and lines over a document's characters
+ *
+ * A document keeps its characters, tombstones included, in position
+ * order. Counting the visible characters before one, or finding where a
+ * line starts, would scan the list from the start; on documents made of
+ * one very long line, such as minified JSON or a log, every edit would
+ * pay for the whole line.
+ *
+ * `LineIndex` keeps a flag per slot in `Chunks`, split independently of
+ * line boundaries, so a long line spans many chunks and is indexed by
+ * them. Beside the chunk lengths it counts visible characters and visible
+ * newlines per chunk, and keeps prefix sums of those counts in Fenwick
+ * trees too. Every lookup descends a tree, O(log chunks), then scans a
+ * single chunk. A chunk that splits rebuilds the trees.
+ *
+ * Offsets count visible characters; lines and columns start at 0.
+ */
+
+use std::mem;
+use crate::crdt::chunks::{Chunks, Fenwick};
+
+const VISIBLE: u8 = 1;
+const NEWLINE: u8 = 2;
+
+/// Visible characters and lines of a list of characters, tombstones included
+#[derive(Debug, Clone, Default)]
+pub struct LineIndex {
+    /// Flags of the slots
+    slots: Chunks<u8>,
+    visible: Fenwick,
+    newlines: Fenwick,
+}
+
+fn flags(visible: bool, newline: bool) -> u8 {
+    (if visible { VISIBLE } else { 0 }) | (if newline { NEWLINE } else { 0 })
+}
+
+fn is_visible(flags: u8) -> bool {
+    flags & VISIBLE != 0
+}
+
+fn is_visible_newline(flags: u8) -> bool {
+    flags == VISIBLE | NEWLINE
+}
+
+impl LineIndex {
+    /// Create an index of an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index a list from whether each character is visible and a newline
+    pub fn build(characters: impl IntoIterator<Item = (bool, bool)>) -> Self {
+        let slots = Chunks::build(characters.into_iter().map(|(visible, newline)| flags(visible, newline)));
+        let mut index = Self { slots, ..Self::default() };
+        index.rebuild_sums();
+        index
+    }
+
+    fn rebuild_sums(&mut self) {
+        let chunks = self.slots.chunks();
+        self.visible = Fenwick::build(chunks.iter().map(|chunk| chunk.iter().filter(|f| is_visible(**f)).count()));
+        self.newlines = Fenwick::build(chunks.iter().map(|chunk| chunk.iter().filter(|f| is_visible_newline(**f)).count()));
+    }
+
+    /// Add a character at a slot, moving the following ones up
+    pub fn insert(&mut self, slot: usize, visible: bool, newline: bool) {
+        let flags = flags(visible, newline);
+        let Some(chunk) = self.slots.insert(slot, flags) else {
+            self.rebuild_sums();
+            return;
+        };
+        if is_visible(flags) {
+            self.visible.add(chunk, 1);
+        }
+        if is_visible_newline(flags) {
+            self.newlines.add(chunk, 1);
+        }
+    }
+
+    /// Mark the character at a slot deleted
+    pub fn hide(&mut self, slot: usize) {
+        let (chunk, _) = self.slots.locate(slot);
+        let Some(flags) = self.slots.get_mut(slot) else {
+            return;
+        };
+        if !is_visible(*flags) {
+            return;
+        }
+        let newline = is_visible_newline(*flags);
+        *flags &= !VISIBLE;
+        self.visible.add(chunk, -1);
+        if newline {
+            self.newlines.add(chunk, -1);
+        }
+    }
+
+    /// Count the chunks, and estimate the bytes they and the trees take up
+    pub fn memory(&self) -> (usize, usize) {
+        let bytes = self.slots.bytes() + self.visible.bytes() + self.newlines.bytes();
+        (self.slots.chunks().len(), mem::size_of::<Self>() + bytes)
+    }
+
+    /// Count the slots, tombstones included
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Check whether the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count the visible characters
+    pub fn visible_len(&self) -> usize {
+        self.visible.prefix(self.slots.chunks().len())
+    }
+
+    /// Count the visible characters before a slot
+    pub fn visible_before(&self, slot: usize) -> usize {
+        let (chunk, offset) = self.slots.locate(slot);
+        let Some(flags) = self.slots.chunks().get(chunk) else {
+            return 0;
+        };
+        self.visible.prefix(chunk) + flags[..offset].iter().filter(|f| is_visible(**f)).count()
+    }
+
+    /// Count the lines; an empty list has one, empty line
+    pub fn line_count(&self) -> usize {
+        self.newlines.prefix(self.slots.chunks().len()) + 1
+    }
+
+    /// Get the offset where a line starts
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        if line == 0 {
+            return Some(0);
+        }
+        if line >= self.line_count() {
+            return None;
+        }
+        // The line starts after the `line`-th newline
+        let (chunk, before) = self.newlines.find(line - 1);
+        let mut newlines = before;
+        let mut offset = self.visible.prefix(chunk);
+        for flags in &self.slots.chunks()[chunk] {
+            if is_visible(*flags) {
+                offset += 1;
+            }
+            if is_visible_newline(*flags) {
+                newlines += 1;
+                if newlines == line {
+                    return Some(offset);
+                }
+            }
+        }
+        None
+    }
+
+    /// Get the line and column of an offset, which may be the end of the
+    /// text
+    pub fn line_column(&self, offset: usize) -> Option<(usize, usize)> {
+        let total = self.visible_len();
+        if offset > total {
+            return None;
+        }
+        let line = if offset == total {
+            self.line_count() - 1
+        } else {
+            // Newlines among the visible characters before the offset
+            let (chunk, before) = self.visible.find(offset);
+            let mut newlines = self.newlines.prefix(chunk);
+            let mut remaining = offset - before;
+            for flags in &self.slots.chunks()[chunk] {
+                if remaining == 0 {
+                    break;
+                }
+                if is_visible(*flags) {
+                    remaining -= 1;
+                    if is_visible_newline(*flags) {
+                        newlines += 1;
+                    }
+                }
+            }
+            newlines
+        };
+        self.line_start(line).map(|start| (line, offset - start))
+    }
+
+    /// Get the offset of a line and column; the column may be the end of
+    /// the line, but not past it
+    pub fn offset_of(&self, line: usize, column: usize) -> Option<usize> {
+        let start = self.line_start(line)?;
+        let end = match self.line_start(line + 1) {
+            Some(next) => next - 1,
+            None => self.visible_len(),
+        };
+        (start + column <= end).then_some(start + column)
+    }
+}
@@ -0,0 +1,269 @@
+/*
+ * File: crdt/list.rs
+ * Purpose: Conflict-free ordered list of elements that can be moved
+ *
+ * Responsibilities:
+ * - Insert, move, update and remove elements of a list
+ * - Merge operations from any replica, in any order, any number of times
+ * - Keep each element exactly once, wherever concurrent moves put it
+ *
+ * Each element is identified by the timestamp of the insert that created
+ * it. Its place and its value are last-writer-wins registers, stamped with
+ * Lamport timestamps: an insert writes both, a move writes the place, an
+ * update the value. The element with the newest stamp wins, so concurrent
+ * moves of one element leave it where the later move put it, never in two
+ * places, and a concurrent move and update both take effect. Removal is
+ * final. Elements are ordered by position, then by ID.
+ *
+ * Lists are meant to be short, like the items of a checklist: the order
+ * is worked out each time the list is read.
+ */
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::crdt::{Position, Timestamp};
+
+/// Identity of a list element: the timestamp of the insert that created it
+pub type ElementId = Timestamp;
+
+/// An operation on an ordered list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ListOperation<T> {
+    /// Add an element
+    Insert { id: ElementId, position: Position, value: T },
+    /// Give an element a new place
+    Move { id: ElementId, position: Position, stamp: Timestamp },
+    /// Give an element a new value
+    Update { id: ElementId, value: T, stamp: Timestamp },
+    /// Remove an element for good
+    Remove { id: ElementId },
+}
+
+impl<T> ListOperation<T> {
+    /// Get the ID of the element the operation applies to
+    pub fn id(&self) -> &ElementId {
+        match self {
+            ListOperation::Insert { id, .. }
+            | ListOperation::Move { id, .. }
+            | ListOperation::Update { id, .. }
+            | ListOperation::Remove { id } => id,
+        }
+    }
+}
+
+/// State of an element. Parts are missing while the operations that set
+/// them have not arrived yet.
+#[derive(Debug, Clone, PartialEq)]
+struct Element<T> {
+    position: Option<(Position, Timestamp)>,
+    value: Option<(T, Timestamp)>,
+    removed: bool,
+}
+
+impl<T> Default for Element<T> {
+    fn default() -> Self {
+        Self { position: None, value: None, removed: false }
+    }
+}
+
+/// A conflict-free ordered list
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderedList<T> {
+    elements: BTreeMap<ElementId, Element<T>>,
+    /// Newest stamp seen, to stamp local operations after it
+    latest: Option<Timestamp>,
+}
+
+impl<T> Default for OrderedList<T> {
+    fn default() -> Self {
+        Self { elements: BTreeMap::new(), latest: None }
+    }
+}
+
+impl<T: Clone> OrderedList<T> {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an operation from any replica. Returns whether the list
+    /// changed; operations the list already reflects change nothing.
+    pub fn apply(&mut self, operation: ListOperation<T>) -> bool {
+        match operation {
+            ListOperation::Insert { id, position, value } => {
+                self.observe(&id);
+                let element = self.elements.entry(id.clone()).or_default();
+                let placed = write_register(&mut element.position, position, &id);
+                let valued = write_register(&mut element.value, value, &id);
+                placed || valued
+            }
+            ListOperation::Move { id, position, stamp } => {
+                self.observe(&stamp);
+                write_register(&mut self.elements.entry(id).or_default().position, position, &stamp)
+            }
+            ListOperation::Update { id, value, stamp } => {
+                self.observe(&stamp);
+                write_register(&mut self.elements.entry(id).or_default().value, value, &stamp)
+            }
+            ListOperation::Remove { id } => {
+                let element = self.elements.entry(id).or_default();
+                !std::mem::replace(&mut element.removed, true)
+            }
+        }
+    }
+
+    /// Insert a value at an index of the list, on behalf of a client
+    pub fn insert(&mut self, client_id: &str, index: usize, value: T) -> ListOperation<T> {
+        let order = self.order();
+        let index = index.min(order.len());
+        let position = place_between(
+            index.checked_sub(1).map(|before| self.position_of(order[before])),
+            order.get(index).map(|after| self.position_of(after)),
+        );
+        let operation = ListOperation::Insert { id: self.stamp(client_id), position, value };
+        self.apply(operation.clone());
+        operation
+    }
+
+    /// Move an element to an index of the list without it, on behalf of a
+    /// client. Returns None for elements not in the list.
+    pub fn move_to(&mut self, client_id: &str, id: &ElementId, index: usize) -> Option<ListOperation<T>> {
+        let order = self.order();
+        if !order.contains(&id) {
+            return None;
+        }
+        let others: Vec<&ElementId> = order.into_iter().filter(|other| *other != id).collect();
+        let index = index.min(others.len());
+        let position = place_between(
+            index.checked_sub(1).map(|before| self.position_of(others[before])),
+            others.get(index).map(|after| self.position_of(after)),
+        );
+        let operation = ListOperation::Move { id: id.clone(), position, stamp: self.stamp(client_id) };
+        self.apply(operation.clone());
+        Some(operation)
+    }
+
+    /// Replace the value of an element, on behalf of a client. Returns
+    /// None for elements not in the list.
+    pub fn update(&mut self, client_id: &str, id: &ElementId, value: T) -> Option<ListOperation<T>> {
+        self.get(id)?;
+        let operation = ListOperation::Update { id: id.clone(), value, stamp: self.stamp(client_id) };
+        self.apply(operation.clone());
+        Some(operation)
+    }
+
+    /// Remove an element. Returns None for elements not in the list.
+    pub fn remove(&mut self, id: &ElementId) -> Option<ListOperation<T>> {
+        self.get(id)?;
+        let operation = ListOperation::Remove { id: id.clone() };
+        self.apply(operation.clone());
+        Some(operation)
+    }
+
+    /// Get the value of an element in the list
+    pub fn get(&self, id: &ElementId) -> Option<&T> {
+        let element = self.elements.get(id).filter(|element| !element.removed && element.position.is_some())?;
+        element.value.as_ref().map(|(value, _)| value)
+    }
+
+    /// Get the elements of the list, in order
+    pub fn items(&self) -> Vec<(&ElementId, &T)> {
+        self.order().into_iter().filter_map(|id| self.get(id).map(|value| (id, value))).collect()
+    }
+
+    /// Get the number of elements in the list
+    pub fn len(&self) -> usize {
+        self.order().len()
+    }
+
+    /// Check whether the list has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count the elements known, removed ones included
+    pub fn footprint(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Get operations that rebuild this list's state in an empty replica,
+    /// for clients that join late
+    pub fn operations(&self) -> Vec<ListOperation<T>> {
+        let mut operations = Vec::new();
+        for (id, element) in &self.elements {
+            if element.removed {
+                operations.push(ListOperation::Remove { id: id.clone() });
+                continue;
+            }
+            if let Some((position, stamp)) = &element.position {
+                operations.push(ListOperation::Move { id: id.clone(), position: position.clone(), stamp: stamp.clone() });
+            }
+            if let Some((value, stamp)) = &element.value {
+                operations.push(ListOperation::Update { id: id.clone(), value: value.clone(), stamp: stamp.clone() });
+            }
+        }
+        operations
+    }
+
+    /// Get the IDs of the elements in the list, in order
+    fn order(&self) -> Vec<&ElementId> {
+        let mut order: Vec<(&Position, &ElementId)> = self.elements
+            .iter()
+            .filter(|(_, element)| !element.removed && element.value.is_some())
+            .filter_map(|(id, element)| element.position.as_ref().map(|(position, _)| (position, id)))
+            .collect();
+        order.sort();
+        order.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn position_of(&self, id: &ElementId) -> &Position {
+        let position = self.elements.get(id).and_then(|element| element.position.as_ref());
+        &position.expect("ordered elements have a position").0
+    }
+
+    /// Stamp a local operation after every operation seen
+    fn stamp(&mut self, client_id: &str) -> Timestamp {
+        let mut stamp = Timestamp::new(client_id.to_string());
+        if let Some(latest) = &self.latest {
+            stamp.update(latest);
+        }
+        stamp.increment();
+        stamp
+    }
+
+    fn observe(&mut self, stamp: &Timestamp) {
+        if self.latest.as_ref().is_none_or(|latest| stamp > latest) {
+            self.latest = Some(stamp.clone());
+        }
+    }
+}
+
+/// Write a last-writer-wins register if the stamp is newer than its own.
+/// Returns whether it was written.
+pub(crate) fn write_register<V>(register: &mut Option<(V, Timestamp)>, value: V, stamp: &Timestamp) -> bool {
+    if register.as_ref().is_some_and(|(_, current)| current >= stamp) {
+        return false;
+    }
+    *register = Some((value, stamp.clone()));
+    true
+}
+
+/// Generate a position between two neighbours; None stands for either end
+/// of the list
+fn place_between(before: Option<&Position>, after: Option<&Position>) -> Position {
+    let lowest = Position::new(vec![0]);
+    let highest = Position::new(vec![u32::MAX]);
+    match (before, after) {
+        (Some(before), Some(after)) => Position::between(before, after),
+        (Some(before), None) if *before < highest => Position::between(before, &highest),
+        (Some(before), None) => {
+            let mut path = before.path().clone();
+            path.push(1);
+            Position::new(path)
+        }
+        (None, Some(after)) if lowest < *after => Position::between(&lowest, after),
+        (None, Some(_)) => Position::start(),
+        (None, None) => Position::spread(1).remove(0),
+    }
+}
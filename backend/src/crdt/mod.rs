@@ -7,12 +7,42 @@
  * - Position: Fractional indexing for character positions
  * - Operation: Document operations (insert/delete)
  * - Timestamp: Lamport timestamps for causality tracking
+ * - Playback: Step-wise replay of a document's history
+ * - Diff: Changes of a document since an earlier revision
+ * - ConcurrencyStats: Conflict and concurrency metrics
+ * - ContentHash: Incrementally maintained content checksum
+ * - TieBreak: Ordering of inserts at equal positions
+ * - OperationSource: Kind of actor an operation comes from
+ * - DocumentUpdate: Operations a replica lacks, for offline-first clients
+ * - OrderedList: Ordered list of movable elements, for checklists
+ * - LineIndex: Visible characters and lines of long documents
+ * - Chunks: Chunked lists with Fenwick-indexed lengths, behind both
  */
 
+pub mod checksum;
+mod chunks;
+pub mod diff;
 pub mod document;
+pub mod lines;
+pub mod list;
+pub mod playback;
 pub mod position;
+pub mod source;
+pub mod stats;
+pub mod tiebreak;
 pub mod timestamp;
+pub mod update;
 
-pub use document::{Document, Operation};
+pub use checksum::{ContentHash, CHECKSUM_REGIONS};
+pub use diff::{diff, diff_since, revision, DiffKind, DiffRange};
+pub use document::{AppliedOp, Document, DocumentError, GcReport, MemoryReport, MemoryUsage, Operation};
+pub use chunks::CHUNK_CAPACITY;
+pub use lines::LineIndex;
+pub use list::{ElementId, ListOperation, OrderedList};
+pub use playback::{Playback, PlaybackFrame};
 pub use position::{Position, PositionBounds};
+pub use source::OperationSource;
+pub use stats::ConcurrencyStats;
+pub use tiebreak::TieBreak;
 pub use timestamp::Timestamp;
+pub use update::{DocumentUpdate, StateVector, VersionVector};
@@ -0,0 +1,121 @@
+/*
+ * File: crdt/playback.rs
+ * Purpose: Step-wise reconstruction of a document from its history
+ *
+ * Responsibilities:
+ * - Replay a document's operation log into a fresh replica
+ * - Produce the intermediate content after each operation
+ * - Support seeking to an arbitrary point in the history
+ *
+ * This powers "watch this document being written" playback, where the
+ * server streams intermediate states to a client at a chosen pace.
+ */
+
+use serde::{Deserialize, Serialize};
+use crate::crdt::{Document, Operation};
+
+/// The state of a document after one replayed operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackFrame {
+    /// Index of the operation in the history (0-based)
+    pub index: usize,
+    /// Total number of operations in the history
+    pub total: usize,
+    /// The operation that produced this frame
+    pub operation: Operation,
+    /// Document content after applying the operation
+    pub content: String,
+}
+
+/// Replays a document history one operation at a time
+#[derive(Debug, Clone)]
+pub struct Playback {
+    /// Replica the history is replayed into
+    replica: Document,
+    /// Operations to replay, in their original order
+    history: Vec<Operation>,
+    /// Index of the next operation to apply
+    cursor: usize,
+}
+
+impl Playback {
+    /// Create a playback over the given operation history
+    pub fn new(document_id: String, history: Vec<Operation>) -> Self {
+        Self {
+            replica: Document::new(document_id),
+            history,
+            cursor: 0,
+        }
+    }
+
+    /// Create a playback over a document's recorded operations
+    pub fn from_document(document: &Document) -> Self {
+        let mut playback = Self::new(document.id().to_string(), document.operations().to_vec());
+        playback.replica.set_tie_break(document.tie_break());
+        playback
+    }
+
+    /// Total number of operations in the history
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Check whether the history is empty
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Index of the next operation to be replayed
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    /// Check whether every operation has been replayed
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.history.len()
+    }
+
+    /// Content of the replica at the current position
+    pub fn content(&self) -> String {
+        self.replica.content()
+    }
+
+    /// Apply the next operation and return the resulting frame
+    pub fn step(&mut self) -> Option<PlaybackFrame> {
+        let operation = self.history.get(self.cursor)?.clone();
+        self.replica.apply(operation.clone());
+
+        let frame = PlaybackFrame {
+            index: self.cursor,
+            total: self.history.len(),
+            operation,
+            content: self.replica.content(),
+        };
+        self.cursor += 1;
+        Some(frame)
+    }
+
+    /// Move to the state right before operation `index`.
+    /// Seeking backwards rebuilds the replica from the start.
+    pub fn seek(&mut self, index: usize) {
+        let index = index.min(self.history.len());
+        if index < self.cursor {
+            let tie_break = self.replica.tie_break();
+            self.replica = Document::new(self.replica.id().to_string());
+            self.replica.set_tie_break(tie_break);
+            self.cursor = 0;
+        }
+        while self.cursor < index {
+            self.replica.apply(self.history[self.cursor].clone());
+            self.cursor += 1;
+        }
+    }
+}
+
+impl Iterator for Playback {
+    type Item = PlaybackFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step()
+    }
+}
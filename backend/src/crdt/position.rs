@@ -69,6 +69,12 @@ impl Position {
     /// 1. Greater than the left position
     /// 2. Less than the right position
     /// 3. Unique from both positions
+    ///
+    /// When there is no room at a level, it descends one below the left
+    /// position, so generated paths never end in 0. Nothing sorts between
+    /// equal positions, or before a position that is another followed only
+    /// by zeros; then the right position itself is returned, tied with it
+    /// (see `Position::has_room`). Arguments in the wrong order are swapped.
     /// 
     /// # Panics
     /// Panics if either position is an end position.
@@ -83,60 +89,95 @@ impl Position {
     /// 
     /// assert!(pos1 < between);
     /// assert!(between < pos2);
+    ///
+    /// // No room between siblings descends a level
+    /// let pos3 = Position::new(vec![1, 3]);
+    /// assert_eq!(Position::between(&pos3, &pos2), Position::new(vec![1, 3, 1]));
     /// ```
     pub fn between(left: &Position, right: &Position) -> Self {
         // Handle special cases involving end positions
         if left.is_end || right.is_end {
             panic!("Cannot generate position involving end position");
         }
+        let (left, right) = if left > right { (right, left) } else { (left, right) };
+
+        // Walk down the levels. `bounded` holds while the new path is a
+        // prefix of the right position, which limits the next component;
+        // past the end of the left path any component sorts after it.
+        let mut path = Vec::new();
+        let mut bounded = true;
+        loop {
+            let depth = path.len();
+            let low = left.path.get(depth).copied();
+            let high = if bounded {
+                match right.path.get(depth) {
+                    Some(&high) => Some(high),
+                    // The new path would be the right position or sort
+                    // after it: nothing lies between them
+                    None => return right.clone(),
+                }
+            } else {
+                None
+            };
+
+            let component = match (low, high) {
+                // Room between the two components
+                (Some(l), Some(r)) if r - l > 1 => Some(l + (r - l) / 2),
+                // Room before the right component, leaving 0 free
+                (None, Some(r)) if r > 1 => Some(r / 2),
+                // Room after the left component
+                (Some(l), None) if l < u32::MAX => Some(l + 1),
+                (None, None) => Some(1),
+                _ => None,
+            };
+            if let Some(component) = component {
+                path.push(component);
+                return Self::new(path);
+            }
 
-        // Ensure left is less than right
-        if left >= right {
-            return Self::between(right, left);
+            // No room at this level: follow the left path, or 0 past its
+            // end, and descend
+            let next = low.unwrap_or(0);
+            bounded = bounded && high == Some(next);
+            path.push(next);
         }
+    }
 
-        // Find the common prefix length
-        let common_len = left.path.iter()
-            .zip(right.path.iter())
-            .take_while(|(a, b)| a == b)
-            .count();
-
-        // Get the differing components or next available components
-        let left_next = left.path.get(common_len).copied();
-        let right_next = right.path.get(common_len).copied();
-
-        let mut new_path = left.path[..common_len].to_vec();
-
-        match (left_next, right_next) {
-            // Case 1: Both positions have a differing component
-            (Some(l), Some(r)) => {
-                // If the numbers are too close, extend the path
-                if r - l <= 1 {
-                    new_path.extend_from_slice(&left.path[common_len..]);
-                    new_path.push(1);
-                } else {
-                    // Generate a number between l and r
-                    new_path.push(l + ((r - l) / 2));
-                }
-            },
-            // Case 2: Left position is a prefix of right
-            (None, Some(r)) => {
-                // Generate a number before r
-                new_path.push(r / 2);
-            },
-            // Case 3: Right position is a prefix of left
-            (Some(l), None) => {
-                // Generate a number after l
-                new_path.push(l + 1);
-            },
-            // Case 4: Both positions are identical
-            (None, None) => {
-                // Append a new component
-                new_path.push(1);
-            },
+    /// Check whether any position sorts strictly between two positions.
+    /// Only equal positions, and a position followed by another that
+    /// extends it with zeros, leave no room.
+    pub fn has_room(left: &Position, right: &Position) -> bool {
+        let (left, right) = if left > right { (right, left) } else { (left, right) };
+        if left.is_end || right.is_end {
+            return left != right;
         }
+        !(right.path.starts_with(&left.path) && right.path[left.path.len()..].iter().all(|&component| component == 0))
+    }
 
-        Self::new(new_path)
+    /// Create `count` ascending positions spread evenly over the whole
+    /// range, leaving equal room before, between and after them for later
+    /// inserts. Used to seed documents with existing text.
+    ///
+    /// # Examples
+    /// ```
+    /// use crdt_editor_backend::Position;
+    ///
+    /// let positions = Position::spread(3);
+    /// assert_eq!(positions.len(), 3);
+    /// assert!(positions[0] < positions[1] && positions[1] < positions[2]);
+    /// ```
+    pub fn spread(count: usize) -> Vec<Position> {
+        let step = (u32::MAX as u64 / (count as u64 + 1)).max(1);
+        (1..=count as u64)
+            .map(|i| {
+                // Past 2^32 characters, continue one level deeper
+                let index = i * step;
+                match u32::try_from(index) {
+                    Ok(component) => Self::new(vec![component]),
+                    Err(_) => Self::new(vec![u32::MAX, (index - u32::MAX as u64) as u32]),
+                }
+            })
+            .collect()
     }
 
     /// Create a position representing the start of the document.
@@ -0,0 +1,70 @@
+/*
+ * File: crdt/source.rs
+ * Purpose: Kind of actor an operation comes from
+ *
+ * Operations carry a source next to their client ID:
+ * - user: typed by a person, the default
+ * - server: generated by the server itself, e.g. moderation
+ * - bot: sent by an automated client
+ * - import: brought in from outside, e.g. a file import or a sync link
+ *
+ * Undo stacks hold a user's own edits; operations of other sources are
+ * left out of them by default.
+ */
+
+use std::{fmt, str::FromStr};
+use serde::{Deserialize, Serialize};
+
+/// Kind of actor that generated an operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationSource {
+    #[default]
+    User,
+    Server,
+    Bot,
+    Import,
+}
+
+impl OperationSource {
+    /// Get the name of the source, as used on the wire
+    pub fn name(self) -> &'static str {
+        match self {
+            OperationSource::User => "user",
+            OperationSource::Server => "server",
+            OperationSource::Bot => "bot",
+            OperationSource::Import => "import",
+        }
+    }
+
+    /// Check whether the source is a person typing
+    pub fn is_user(&self) -> bool {
+        *self == OperationSource::User
+    }
+
+    /// Check whether operations of this source belong on undo stacks
+    /// unless configured otherwise
+    pub fn undoable_by_default(self) -> bool {
+        self.is_user()
+    }
+}
+
+impl fmt::Display for OperationSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OperationSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "user" => Ok(OperationSource::User),
+            "server" => Ok(OperationSource::Server),
+            "bot" => Ok(OperationSource::Bot),
+            "import" => Ok(OperationSource::Import),
+            other => Err(format!("Unknown operation source: {}", other)),
+        }
+    }
+}
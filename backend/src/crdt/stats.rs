@@ -0,0 +1,92 @@
+/*
+ * File: crdt/stats.rs
+ * Purpose: Concurrency statistics for CRDT documents
+ *
+ * Responsibilities:
+ * - Detect operations that are concurrent with already applied ones
+ * - Count tie-breaks between inserts at identical positions
+ * - Count concurrent inserts that split another client's run of text
+ * - Count inserts whose anchor is unknown, e.g. collected by GC
+ *
+ * Concurrency is derived from Lamport clocks: an operation whose clock is
+ * not greater than a clock already seen from another client cannot have
+ * observed that client's operation, so the two are concurrent.
+ */
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::crdt::Operation;
+
+/// Per-document concurrency metrics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConcurrencyStats {
+    /// Number of operations observed
+    pub total_operations: u64,
+    /// Operations concurrent with at least one operation from another client
+    pub concurrent_operations: u64,
+    /// Inserts whose position matched an existing character's position
+    pub tie_breaks: u64,
+    /// Concurrent inserts that landed inside another client's run of text.
+    /// Ordering changes meant to prevent interleaving should drive this down.
+    pub interleaved_inserts: u64,
+    /// Inserts whose anchor position was not in the document, typically
+    /// because it was a tombstone removed by garbage collection
+    #[serde(default)]
+    pub unknown_anchors: u64,
+    /// Highest logical clock observed per client
+    #[serde(skip)]
+    clocks: HashMap<String, u64>,
+}
+
+impl ConcurrencyStats {
+    /// Fraction of operations that were concurrent with another client's
+    pub fn concurrency_ratio(&self) -> f64 {
+        if self.total_operations == 0 {
+            0.0
+        } else {
+            self.concurrent_operations as f64 / self.total_operations as f64
+        }
+    }
+
+    /// Get the highest logical clock observed from any client
+    pub fn latest_clock(&self) -> u64 {
+        self.clocks.values().copied().max().unwrap_or(0)
+    }
+
+    /// Record an operation and return whether it was concurrent
+    pub(crate) fn observe(&mut self, operation: &Operation) -> bool {
+        let client_id = operation.client_id();
+        let clock = operation.timestamp().logical_clock();
+
+        let latest_other = self.clocks
+            .iter()
+            .filter(|(id, _)| id.as_str() != client_id)
+            .map(|(_, clock)| *clock)
+            .max();
+        let concurrent = matches!(latest_other, Some(other) if clock <= other);
+
+        self.total_operations += 1;
+        if concurrent {
+            self.concurrent_operations += 1;
+        }
+
+        let seen = self.clocks.entry(client_id.to_string()).or_insert(clock);
+        *seen = (*seen).max(clock);
+        concurrent
+    }
+
+    /// Record an insert that collided with an existing position
+    pub(crate) fn record_tie_break(&mut self) {
+        self.tie_breaks += 1;
+    }
+
+    /// Record a concurrent insert that split another client's run
+    pub(crate) fn record_interleaving(&mut self) {
+        self.interleaved_inserts += 1;
+    }
+
+    /// Record an insert whose anchor was not found
+    pub(crate) fn record_unknown_anchor(&mut self) {
+        self.unknown_anchors += 1;
+    }
+}
@@ -0,0 +1,38 @@
+/*
+ * File: crdt/tiebreak.rs
+ * Purpose: Ordering of inserts that share a position
+ *
+ * Positions are not guaranteed to be unique: two clients can generate the
+ * same position concurrently. The tie-break strategy decides how such
+ * characters are ordered relative to each other:
+ * - TimestampThenClient: order by Lamport clock, then by client ID. Every
+ *   replica reaches the same order regardless of arrival order.
+ * - ArrivalOrder: place the later arrival after the earlier one. Replicas
+ *   that receive concurrent inserts in different orders diverge; this is
+ *   kept only for experimentation and comparison.
+ */
+
+use std::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+use crate::crdt::Timestamp;
+
+/// How characters at an equal position are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TieBreak {
+    /// Lower Lamport clock first, then lower client ID
+    #[default]
+    TimestampThenClient,
+    /// Later arrivals after earlier ones; not convergent
+    ArrivalOrder,
+}
+
+impl TieBreak {
+    /// Order two characters at an equal position by their insert timestamps.
+    /// `Equal` means the new character goes after the existing one.
+    pub fn compare(&self, existing: &Timestamp, new: &Timestamp) -> Ordering {
+        match self {
+            TieBreak::TimestampThenClient => existing.cmp(new),
+            TieBreak::ArrivalOrder => Ordering::Equal,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * File: crdt/update.rs
+ * Purpose: Compact document updates for offline-first clients
+ *
+ * Responsibilities:
+ * - Summarize the operations a replica has seen in a state vector
+ * - Collect the operations another replica lacks into an update
+ * - Merge an update into a replica, skipping operations it already has
+ *
+ * A state vector counts the operations of each author a replica has
+ * applied. Every replica applies an author's operations in the order the
+ * author made them, so a count names a prefix of that author's history:
+ * whatever lies beyond it is what the replica lacks. Clients persist their
+ * pending operations as an update, together with the server's state vector
+ * and version they last saw, and submit it on reconnect.
+ *
+ * Repairing checksum regions replaces a replica's history in those
+ * regions, which reorders it; clients of a repaired document should start
+ * over from an empty state vector.
+ */
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::crdt::{Document, DocumentError, Operation, PositionBounds};
+
+/// Operations a replica has applied, counted per author
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StateVector(BTreeMap<String, u64>);
+
+/// A version vector, as delta sync calls it: `Document::operations_since`
+/// takes one. Counting operations per author is what a state vector does,
+/// so the two are the same type.
+pub type VersionVector = StateVector;
+
+impl StateVector {
+    /// Create an empty state vector, for a replica that has seen nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the state vector of a document
+    pub fn of(document: &Document) -> Self {
+        document.state_vector().clone()
+    }
+
+    /// Get the number of an author's operations seen
+    pub fn get(&self, author: &str) -> u64 {
+        self.0.get(author).copied().unwrap_or(0)
+    }
+
+    /// Count an applied operation
+    pub fn observe(&mut self, operation: &Operation) {
+        *self.0.entry(operation.client_id().to_string()).or_insert(0) += 1;
+    }
+
+    /// Check whether every operation counted in another state vector is
+    /// counted in this one too
+    pub fn covers(&self, other: &StateVector) -> bool {
+        other.0.iter().all(|(author, count)| self.get(author) >= *count)
+    }
+
+    /// Check whether no operations have been seen
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Count the authors with operations seen
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Operations one replica of a document lacks, from another replica
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentUpdate {
+    /// Version of the replica the update was taken from
+    pub version: u64,
+    /// Operations the replica the update was taken from has applied
+    pub state_vector: StateVector,
+    /// Operations beyond the state vector it was taken for, in the order
+    /// they were applied
+    #[serde(default)]
+    pub operations: Vec<Operation>,
+}
+
+impl DocumentUpdate {
+    /// Collect the operations of a document that a replica with the given
+    /// state vector lacks. An empty state vector collects the whole history.
+    pub fn since(document: &Document, known: &StateVector) -> Self {
+        Self {
+            version: document.version(),
+            state_vector: document.state_vector().clone(),
+            operations: document.operations_since(known),
+        }
+    }
+
+    /// Merge the update's operations into a document, returning those it
+    /// lacked in the order they were applied. Nothing is applied if any
+    /// operation is invalid.
+    pub fn apply_to(&self, document: &mut Document) -> Result<Vec<Operation>, DocumentError> {
+        let invalid = self.operations.iter().any(|operation| {
+            matches!(operation, Operation::Insert { position, .. } if position.is_end())
+        });
+        if invalid {
+            return Err(DocumentError::InvalidPosition("cannot insert at the end sentinel".to_string()));
+        }
+
+        let mut applied = Vec::new();
+        for operation in &self.operations {
+            if document.merge_operation(operation.clone())?.is_some() {
+                applied.push(operation.clone());
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Encode the update for storage
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a stored update
+    pub fn decode(encoded: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(encoded)
+    }
+
+    /// Check whether the update carries no operations
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
@@ -0,0 +1,85 @@
+/*
+ * File: src/fixtures/document.rs
+ * Purpose: Builder for documents with existing text and authors
+ *
+ * `DocumentBuilder` seeds a document the way `createDocument` does, with
+ * positions spread evenly so later inserts have room anywhere. With
+ * several clients, the characters are authored by `client1`, `client2`, ...
+ * in turn, as if they had typed the text together.
+ */
+
+use crate::crdt::{Document, Operation, Position};
+
+/// Builder for documents in tests
+#[derive(Debug, Clone)]
+pub struct DocumentBuilder {
+    document_id: String,
+    text: String,
+    clients: usize,
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        Self::new("doc1")
+    }
+}
+
+impl DocumentBuilder {
+    /// Start building an empty document authored by one client
+    pub fn new(document_id: impl Into<String>) -> Self {
+        Self {
+            document_id: document_id.into(),
+            text: String::new(),
+            clients: 1,
+        }
+    }
+
+    /// Start building `doc1` with some text
+    pub fn with_text(text: impl Into<String>) -> Self {
+        Self::default().text(text)
+    }
+
+    /// Set the ID of the document
+    pub fn id(mut self, document_id: impl Into<String>) -> Self {
+        self.document_id = document_id.into();
+        self
+    }
+
+    /// Set the text of the document
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    /// Share the authorship of the text between `clients` clients
+    pub fn with_clients(mut self, clients: usize) -> Self {
+        self.clients = clients.max(1);
+        self
+    }
+
+    /// Get the IDs of the authoring clients
+    pub fn client_ids(&self) -> Vec<String> {
+        (1..=self.clients).map(|i| format!("client{}", i)).collect()
+    }
+
+    /// Get the operations inserting the text, in order
+    pub fn operations(&self) -> Vec<Operation> {
+        let clients = self.client_ids();
+        let positions = Position::spread(self.text.chars().count());
+        self.text
+            .chars()
+            .zip(positions)
+            .enumerate()
+            .map(|(i, (character, position))| Operation::insert(clients[i % clients.len()].clone(), character, position))
+            .collect()
+    }
+
+    /// Build the document
+    pub fn build(&self) -> Document {
+        let mut document = Document::new(self.document_id.clone());
+        for operation in self.operations() {
+            document.apply(operation);
+        }
+        document
+    }
+}
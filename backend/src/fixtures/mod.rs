@@ -0,0 +1,29 @@
+/*
+ * File: src/fixtures/mod.rs
+ * Purpose: Module organization for test fixtures
+ *
+ * This module contains:
+ * - document: Builder for documents with existing text and authors
+ * - network: A network to an in-process server whose links can be cut
+ * - server: A server on a free port and typed clients connected to it
+ * - store: Conformance suite every DocumentStore must pass
+ *
+ * Compiled with the `test-util` feature, for the crate's own integration
+ * tests and for downstream crates testing against a real server:
+ *
+ *   let server = TestServer::spawn().await;
+ *   let mut alice = server.connect().await;
+ *   alice.type_text("doc1", "Hello").await;
+ *
+ * Fixtures panic instead of returning errors, as a failing test would.
+ */
+
+pub mod document;
+pub mod network;
+pub mod server;
+pub mod store;
+
+pub use document::DocumentBuilder;
+pub use network::NetworkSimulator;
+pub use server::{TestClient, TestServer, RECEIVE_TIMEOUT};
+pub use store::store_conformance;
@@ -0,0 +1,134 @@
+/*
+ * File: src/fixtures/network.rs
+ * Purpose: A network between clients and an in-process server that can fail
+ *
+ * `NetworkSimulator` hands out connections to a server through links it
+ * controls, to test how clients cope with an unreliable network:
+ * - `go_offline` cuts every open link and refuses new connections, as a
+ *   dropped network would
+ * - `go_online` accepts connections again
+ * - `fail_next` refuses a number of connection attempts, as an
+ *   unreachable server would
+ *
+ * Frames cross a link in order and are never lost while it is up.
+ */
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::{
+    client::Connector,
+    tenant::DEFAULT_TENANT,
+    websocket::{
+        transport::{duplex, MemoryTransport, TransportError},
+        EditorServer, Transport,
+    },
+};
+
+/// A network whose links to a server can be cut
+pub struct NetworkSimulator {
+    server: Arc<EditorServer>,
+    online: AtomicBool,
+    /// Connection attempts still to refuse
+    failures: AtomicUsize,
+    /// Connection attempts made, refused ones included
+    attempts: AtomicUsize,
+    links: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl NetworkSimulator {
+    /// Create an online network to a server's default tenant
+    pub fn new(server: Arc<EditorServer>) -> Arc<Self> {
+        Arc::new(Self {
+            server,
+            online: AtomicBool::new(true),
+            failures: AtomicUsize::new(0),
+            attempts: AtomicUsize::new(0),
+            links: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Open a connection to the server through a new link
+    pub fn connect(&self) -> Result<MemoryTransport, TransportError> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        let refused = self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1)).is_ok();
+        if refused || !self.online.load(Ordering::SeqCst) {
+            return Err(TransportError::Failed("network unreachable".to_string()));
+        }
+        let server_end = self.server
+            .connect_in_process(DEFAULT_TENANT, None)
+            .map_err(|e| TransportError::Failed(e.to_string()))?;
+        let (client_end, link_end) = duplex();
+        self.links.lock().push(tokio::spawn(relay(link_end, server_end)));
+        Ok(client_end)
+    }
+
+    /// Get a connector opening connections through this network
+    pub fn connector(self: &Arc<Self>) -> impl Connector + Clone {
+        let network = self.clone();
+        move || {
+            let connection = network.connect();
+            async move { connection }
+        }
+    }
+
+    /// Cut every open link and refuse new connections
+    pub fn go_offline(&self) {
+        self.online.store(false, Ordering::SeqCst);
+        for link in self.links.lock().drain(..) {
+            link.abort();
+        }
+    }
+
+    /// Accept connections again
+    pub fn go_online(&self) {
+        self.online.store(true, Ordering::SeqCst);
+    }
+
+    /// Refuse the next connection attempts
+    pub fn fail_next(&self, attempts: usize) {
+        self.failures.store(attempts, Ordering::SeqCst);
+    }
+
+    /// Count the connection attempts made so far
+    pub fn attempts(&self) -> usize {
+        self.attempts.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for NetworkSimulator {
+    fn drop(&mut self) {
+        for link in self.links.lock().drain(..) {
+            link.abort();
+        }
+    }
+}
+
+/// Carry frames both ways until either end closes
+async fn relay(client: MemoryTransport, server: MemoryTransport) {
+    let (mut client_sink, mut client_stream) = client.split();
+    let (mut server_sink, mut server_stream) = server.split();
+    let upstream = async {
+        while let Some(Ok(frame)) = client_stream.next().await {
+            if server_sink.send(Arc::from(frame)).await.is_err() {
+                break;
+            }
+        }
+    };
+    let downstream = async {
+        while let Some(Ok(frame)) = server_stream.next().await {
+            if client_sink.send(Arc::from(frame)).await.is_err() {
+                break;
+            }
+        }
+    };
+    tokio::select! {
+        _ = upstream => {}
+        _ = downstream => {}
+    }
+}
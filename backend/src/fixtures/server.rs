@@ -0,0 +1,327 @@
+/*
+ * File: src/fixtures/server.rs
+ * Purpose: A server on a free port and typed clients connected to it
+ *
+ * `TestServer::spawn` starts an `EditorServer` on 127.0.0.1 and a free
+ * port, and returns once it accepts connections; dropping it stops the
+ * server, though connections already open are served until they close.
+ * `TestServer::in_process` connects its clients over in-memory transports
+ * instead, without binding a port; most tests should use it and keep real
+ * sockets for a smoke suite.
+ *
+ * `TestClient` speaks the protocol over any transport:
+ * - connecting waits for the welcome message and keeps the assigned ID
+ * - `recv` returns the next message, failing after `RECEIVE_TIMEOUT`
+ * - `expect` skips messages of other types, such as relays of other
+ *   clients' operations, and fails on errors unless it expects one
+ * - `create_document`, `insert`, `type_text` and `get_document` send a
+ *   request and wait for its reply
+ */
+
+use std::{net::TcpListener, sync::Arc, time::Duration};
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use tokio::{net::TcpStream, task::JoinHandle};
+
+use crate::{
+    crdt::{Operation, Position},
+    tenant::DEFAULT_TENANT,
+    websocket::{
+        message::{DocumentCreatedMessage, DocumentStateMessage, OperationMessage},
+        transport::{FrameSink, FrameStream},
+        EditorServer, Message, MessageType, ServerConfig, Transport,
+    },
+};
+
+/// Longest wait for a message before a test fails
+pub const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for a spawned server to accept connections
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A server running in the background for the length of a test
+pub struct TestServer {
+    server: Arc<EditorServer>,
+    /// Port and task of a server listening on a socket
+    listener: Option<(u16, JoinHandle<anyhow::Result<()>>)>,
+}
+
+impl TestServer {
+    /// Start a server with the default configuration
+    pub async fn spawn() -> Self {
+        Self::with_config(ServerConfig::default()).await
+    }
+
+    /// Create a server whose clients connect in process
+    pub fn in_process() -> Self {
+        Self::in_process_with_config(ServerConfig::default())
+    }
+
+    /// Create a server with a configuration whose clients connect in
+    /// process. Tasks started by `EditorServer::run`, such as moderation,
+    /// don't run.
+    pub fn in_process_with_config(config: ServerConfig) -> Self {
+        Self {
+            server: Arc::new(EditorServer::new(config)),
+            listener: None,
+        }
+    }
+
+    /// Start a server with a configuration; its host and port are replaced
+    /// by 127.0.0.1 and a free port
+    pub async fn with_config(config: ServerConfig) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("free port")
+            .port();
+        let server = Arc::new(EditorServer::new(ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            ..config
+        }));
+        let task = tokio::spawn({
+            let server = server.clone();
+            async move { server.run().await }
+        });
+
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+            if task.is_finished() {
+                match task.await {
+                    Ok(Err(e)) => panic!("Test server failed to start: {}", e),
+                    _ => panic!("Test server stopped before accepting connections"),
+                }
+            }
+            assert!(tokio::time::Instant::now() < deadline, "Test server did not start on port {}", port);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Self { server, listener: Some((port, task)) }
+    }
+
+    /// Get the running server
+    pub fn server(&self) -> &Arc<EditorServer> {
+        &self.server
+    }
+
+    /// Get the port the server listens on; None in process
+    pub fn port(&self) -> Option<u16> {
+        self.listener.as_ref().map(|(port, _)| *port)
+    }
+
+    /// Get the WebSocket URL of the default tenant
+    ///
+    /// # Panics
+    /// Panics if the server runs in process
+    pub fn url(&self) -> String {
+        format!("ws://127.0.0.1:{}/ws", self.listening_port())
+    }
+
+    /// Get the WebSocket URL of a tenant
+    ///
+    /// # Panics
+    /// Panics if the server runs in process
+    pub fn tenant_url(&self, tenant_id: &str) -> String {
+        format!("ws://127.0.0.1:{}/t/{}/ws", self.listening_port(), tenant_id)
+    }
+
+    fn listening_port(&self) -> u16 {
+        self.port().expect("an in-process test server has no URL")
+    }
+
+    /// Connect a client to the default tenant
+    pub async fn connect(&self) -> TestClient {
+        self.connect_to(DEFAULT_TENANT, None).await
+    }
+
+    /// Connect a client to a tenant with an access key
+    pub async fn connect_to(&self, tenant_id: &str, key: Option<&str>) -> TestClient {
+        if self.listener.is_some() {
+            let mut url = self.tenant_url(tenant_id);
+            if let Some(key) = key {
+                url = format!("{}?key={}", url, key);
+            }
+            return TestClient::connect(&url).await;
+        }
+        let transport = self.server
+            .connect_in_process(tenant_id, key)
+            .unwrap_or_else(|e| panic!("Failed to connect to tenant {}: {}", tenant_id, e));
+        TestClient::over(transport).await
+    }
+
+    /// Resume a session of the default tenant with a session token
+    pub async fn resume(&self, token: &str) -> TestClient {
+        if self.listener.is_some() {
+            return TestClient::connect(&format!("{}?session={}", self.url(), token)).await;
+        }
+        let transport = self.server
+            .resume_in_process(DEFAULT_TENANT, token)
+            .unwrap_or_else(|e| panic!("Failed to resume a session: {}", e));
+        TestClient::over(transport).await
+    }
+
+    /// Stop the server and wait until it no longer accepts connections
+    pub async fn shutdown(self) {
+        if let Some((port, task)) = &self.listener {
+            task.abort();
+            while TcpStream::connect(("127.0.0.1", *port)).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+
+    /// Connect several clients to the default tenant
+    pub async fn connect_many(&self, count: usize) -> Vec<TestClient> {
+        let mut clients = Vec::with_capacity(count);
+        for _ in 0..count {
+            clients.push(self.connect().await);
+        }
+        clients
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some((_, task)) = &self.listener {
+            task.abort();
+        }
+    }
+}
+
+/// A client connected to a server
+pub struct TestClient {
+    sink: FrameSink,
+    stream: FrameStream,
+    client_id: String,
+    /// Token resuming the session, from the welcome message
+    session_token: Option<String>,
+    next_request: u64,
+}
+
+impl TestClient {
+    /// Connect to a WebSocket URL and wait for the welcome message
+    pub async fn connect(url: &str) -> Self {
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to connect to {}: {}", url, e));
+        Self::over(socket).await
+    }
+
+    /// Speak the protocol over a connected transport, and wait for the
+    /// welcome message
+    pub async fn over(transport: impl Transport) -> Self {
+        let (sink, stream) = transport.split();
+        let mut client = Self {
+            sink,
+            stream,
+            client_id: String::new(),
+            session_token: None,
+            next_request: 0,
+        };
+        let welcome = client.expect(MessageType::Status).await;
+        client.client_id = welcome.payload()["client_id"]
+            .as_str()
+            .expect("client ID in welcome message")
+            .to_string();
+        client.session_token = welcome.payload()["session_token"].as_str().map(str::to_string);
+        client
+    }
+
+    /// Get the client ID the server assigned
+    pub fn id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Get the token resuming the client's session
+    pub fn session_token(&self) -> Option<&str> {
+        self.session_token.as_deref()
+    }
+
+    /// Send a message as is
+    pub async fn send(&mut self, message: &Message) {
+        let text = serde_json::to_string(message).expect("serializable message");
+        self.sink.send(Arc::from(text)).await.expect("open connection");
+    }
+
+    /// Send a request with a fresh request ID, and return the ID
+    pub async fn request(&mut self, message_type: MessageType, payload: impl Serialize) -> String {
+        self.next_request += 1;
+        let request_id = format!("{}-{}", self.client_id, self.next_request);
+        let message = Message::new(
+            message_type,
+            self.client_id.clone(),
+            serde_json::to_value(payload).expect("serializable payload"),
+        )
+        .with_request_id(Some(request_id.clone()));
+        self.send(&message).await;
+        request_id
+    }
+
+    /// Receive the next message
+    pub async fn recv(&mut self) -> Message {
+        match self.recv_within(RECEIVE_TIMEOUT).await {
+            Some(message) => message,
+            None => panic!("{} received no message within {:?}", self.client_id, RECEIVE_TIMEOUT),
+        }
+    }
+
+    /// Receive the next message if one arrives in time and the connection
+    /// stays open
+    pub async fn recv_within(&mut self, timeout: Duration) -> Option<Message> {
+        let text = tokio::time::timeout(timeout, self.stream.next()).await.ok()??.ok()?;
+        Some(serde_json::from_str(&text).expect("protocol message"))
+    }
+
+    /// Receive messages until one of a type arrives, and return it. Fails
+    /// on an error message unless errors are expected.
+    pub async fn expect(&mut self, message_type: MessageType) -> Message {
+        loop {
+            let message = self.recv().await;
+            if message.message_type() == &message_type {
+                return message;
+            }
+            if message.message_type() == &MessageType::Error {
+                panic!("{} expected {:?}, got error: {}", self.client_id, message_type, message.payload());
+            }
+        }
+    }
+
+    /// Create a document with initial content
+    pub async fn create_document(&mut self, document_id: &str, content: &str) -> DocumentCreatedMessage {
+        self.request(MessageType::CreateDocument, json!({ "document_id": document_id, "initial_content": content }))
+            .await;
+        let created = self.expect(MessageType::DocumentCreated).await;
+        serde_json::from_value(created.payload().clone()).expect("documentCreated payload")
+    }
+
+    /// Insert a character and return the document version after it
+    pub async fn insert(&mut self, document_id: &str, character: char, position: Position) -> u64 {
+        let operation = Operation::insert(self.client_id.clone(), character, position);
+        self.request(MessageType::Operation, OperationMessage::new(operation, document_id.to_string()))
+            .await;
+        let ack = self.expect(MessageType::Ack).await;
+        ack.payload()["version"].as_u64().expect("version in ack")
+    }
+
+    /// Type text into an empty document, at evenly spread positions, and
+    /// return the document version after it
+    pub async fn type_text(&mut self, document_id: &str, text: &str) -> u64 {
+        let mut version = 0;
+        for (character, position) in text.chars().zip(Position::spread(text.chars().count())) {
+            version = self.insert(document_id, character, position).await;
+        }
+        version
+    }
+
+    /// Get the state of a document
+    pub async fn get_document(&mut self, document_id: &str) -> DocumentStateMessage {
+        self.request(MessageType::GetDocument, json!({ "document_id": document_id })).await;
+        let state = self.expect(MessageType::DocumentState).await;
+        serde_json::from_value(state.payload().clone()).expect("documentState payload")
+    }
+
+    /// Close the connection
+    pub async fn close(mut self) {
+        let _ = self.sink.close().await;
+    }
+}
@@ -0,0 +1,142 @@
+/*
+ * File: src/fixtures/store.rs
+ * Purpose: Conformance suite every DocumentStore must pass
+ *
+ * Every store must behave the same, so each check takes the store as a
+ * trait object, and `store_conformance` runs all of them on a fresh one.
+ * The crate runs it against its own stores; a crate adding a backend runs
+ * it against theirs, so it can't silently diverge.
+ *
+ * Checks:
+ * - Loading missing and stored documents
+ * - Appending operations and restoring from them
+ * - Compacting logs on save, snapshot after snapshot
+ * - Replacing documents written as a whole, log included
+ * - Listing and deleting documents
+ * - Storing password hashes, removed with their documents
+ * - Flushing writes
+ */
+
+use crate::{
+    crdt::{Document, Operation},
+    fixtures::DocumentBuilder,
+    storage::DocumentStore,
+};
+
+fn document_of(document_id: &str, operations: &[Operation]) -> Document {
+    let mut document = Document::new(document_id.to_string());
+    for operation in operations {
+        document.apply(operation.clone());
+    }
+    document
+}
+
+fn check_load_missing(store: &dyn DocumentStore) {
+    assert!(store.load("default/missing").unwrap().is_none());
+}
+
+fn check_append_and_restore(store: &dyn DocumentStore) {
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    store.append_ops("default/appended", &operations[..2]).unwrap();
+    store.append_ops("default/appended", &operations[2..]).unwrap();
+
+    let stored = store.load("default/appended").unwrap().unwrap();
+    assert!(stored.snapshot.is_none());
+    assert_eq!(stored.operations, operations);
+    let document = stored.restore("appended").unwrap();
+    assert_eq!(document.id(), "appended");
+    assert_eq!(document.content(), "Hello");
+}
+
+fn check_save_compacts_log(store: &dyn DocumentStore) {
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    store.append_ops("default/compacted", &operations[..3]).unwrap();
+    store.save("default/compacted", &document_of("compacted", &operations[..3])).unwrap();
+    store.append_ops("default/compacted", &operations[3..]).unwrap();
+
+    let stored = store.load("default/compacted").unwrap().unwrap();
+    assert_eq!(stored.snapshot.as_ref().unwrap().content(), "Hel");
+    assert_eq!(stored.operations, operations[3..]);
+    assert_eq!(stored.restore("compacted").unwrap().content(), "Hello");
+}
+
+fn check_successive_saves(store: &dyn DocumentStore) {
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    store.append_ops("default/saved", &operations[..2]).unwrap();
+    store.save("default/saved", &document_of("saved", &operations[..2])).unwrap();
+    store.append_ops("default/saved", &operations[2..]).unwrap();
+    // The second snapshot holds one of the three operations logged after
+    // the first
+    store.save("default/saved", &document_of("saved", &operations[..3])).unwrap();
+
+    let stored = store.load("default/saved").unwrap().unwrap();
+    assert_eq!(stored.operations, operations[3..]);
+    assert_eq!(stored.restore("saved").unwrap().content(), "Hello");
+
+    store.save("default/saved", &document_of("saved", &operations)).unwrap();
+    assert!(store.load("default/saved").unwrap().unwrap().operations.is_empty());
+}
+
+fn check_replace_drops_log(store: &dyn DocumentStore) {
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    store.append_ops("default/replaced", &operations).unwrap();
+    store.replace("default/replaced", &document_of("replaced", &operations[..2])).unwrap();
+
+    let stored = store.load("default/replaced").unwrap().unwrap();
+    assert!(stored.operations.is_empty());
+    assert_eq!(stored.restore("replaced").unwrap().content(), "He");
+}
+
+fn check_list_and_delete(store: &dyn DocumentStore) {
+    let document = DocumentBuilder::with_text("Hi").id("listed").build();
+    store.save("default/listed", &document).unwrap();
+    store.append_ops("other/listed", &DocumentBuilder::with_text("Yo").operations()).unwrap();
+
+    let keys = store.list().unwrap();
+    assert!(keys.contains(&"default/listed".to_string()));
+    assert!(keys.contains(&"other/listed".to_string()));
+    let mut sorted = keys.clone();
+    sorted.sort();
+    assert_eq!(keys, sorted);
+
+    store.delete("default/listed").unwrap();
+    assert!(store.load("default/listed").unwrap().is_none());
+    assert!(!store.list().unwrap().contains(&"default/listed".to_string()));
+    assert!(store.load("other/listed").unwrap().is_some());
+    // Deleting a missing document is not an error
+    store.delete("default/listed").unwrap();
+}
+
+fn check_passwords(store: &dyn DocumentStore) {
+    store.append_ops("default/locked", &DocumentBuilder::with_text("Hi").operations()).unwrap();
+    store.save_password("default/locked", Some("first")).unwrap();
+    store.save_password("default/locked", Some("second")).unwrap();
+    store.save_password("other/opened", Some("hash")).unwrap();
+    store.save_password("other/opened", None).unwrap();
+    assert_eq!(store.load_passwords().unwrap(), [("default/locked".to_string(), "second".to_string())]);
+
+    store.delete("default/locked").unwrap();
+    assert!(store.load_passwords().unwrap().is_empty());
+}
+
+fn check_flush(store: &dyn DocumentStore) {
+    let operations = DocumentBuilder::with_text("Hi").operations();
+    store.append_ops("default/flushed", &operations).unwrap();
+    store.flush().unwrap();
+    assert_eq!(store.load("default/flushed").unwrap().unwrap().operations, operations);
+    // Flushing with nothing written succeeds too
+    store.flush().unwrap();
+}
+
+/// Run every check of the suite against a store, which must start empty.
+/// Panics on the first way it diverges.
+pub fn store_conformance(store: &dyn DocumentStore) {
+    check_load_missing(store);
+    check_append_and_restore(store);
+    check_save_compacts_log(store);
+    check_successive_saves(store);
+    check_replace_drops_log(store);
+    check_list_and_delete(store);
+    check_passwords(store);
+    check_flush(store);
+}
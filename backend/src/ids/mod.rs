@@ -0,0 +1,11 @@
+/*
+ * File: src/ids/mod.rs
+ * Purpose: Module organization for identifier generation
+ *
+ * This module contains:
+ * - generator: The IdGenerator trait and its UUID and slug implementations
+ */
+
+pub mod generator;
+
+pub use generator::{IdGenerator, SlugIds, UuidV4Ids, UuidV7Ids};
@@ -0,0 +1,80 @@
+/*
+ * File: src/jobs/mod.rs
+ * Purpose: Module organization for long-running server tasks
+ *
+ * This module contains:
+ * - queue: The job queue running tasks with progress and cancellation
+ *
+ * Tasks that outlive the request starting them, such as bulk exports or
+ * compactions, run as jobs instead of bare `tokio::spawn`s. A job is
+ * queued, runs once one of `MAX_RUNNING_JOBS` slots is free, reports how
+ * many of its items are done, and can be cancelled. Its status stays
+ * queryable after it finishes, through the admin API.
+ */
+
+pub mod queue;
+
+pub use queue::{JobContext, JobQueue, JOB_HISTORY, MAX_RUNNING_JOBS};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Job errors
+#[derive(Error, Debug, PartialEq)]
+pub enum JobError {
+    #[error("Job not found: {0}")]
+    NotFound(u64),
+    #[error("Job {0} has already finished")]
+    Finished(u64),
+    #[error("Job {0} was cancelled")]
+    Cancelled(u64),
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Where a job is in its life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for a free slot
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Check whether the job has stopped for good
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// How far a job has come, in items such as documents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+/// State of a job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    /// What the job does, e.g. `export`
+    pub kind: String,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    /// Outcome of a completed job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// Reason a job failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub queued_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
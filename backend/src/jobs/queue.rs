@@ -0,0 +1,210 @@
+/*
+ * File: src/jobs/queue.rs
+ * Purpose: Job queue with progress reporting and cancellation
+ *
+ * `enqueue` registers a job and spawns its task, which waits for a free
+ * slot before it starts. The task gets a `JobContext` to report progress
+ * and to notice cancellation; it should check `is_cancelled` between
+ * items, or return `JobError::Cancelled`. Cancelling a queued job keeps it
+ * from ever starting.
+ *
+ * The newest `JOB_HISTORY` jobs are kept; beyond that, the oldest finished
+ * job is dropped when a new one is queued.
+ */
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::jobs::{Job, JobError, JobProgress, JobStatus};
+
+/// Jobs running at once; more wait in the queue
+pub const MAX_RUNNING_JOBS: usize = 4;
+
+/// Most jobs kept for status queries
+pub const JOB_HISTORY: usize = 100;
+
+struct JobEntry {
+    job: Job,
+    cancel: CancellationToken,
+}
+
+/// Queue of long-running server tasks
+pub struct JobQueue {
+    jobs: Mutex<BTreeMap<u64, JobEntry>>,
+    next_id: AtomicU64,
+    slots: Arc<Semaphore>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(MAX_RUNNING_JOBS)
+    }
+}
+
+impl JobQueue {
+    /// Create a queue running up to `running` jobs at once
+    pub fn new(running: usize) -> Self {
+        Self {
+            jobs: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+            slots: Arc::new(Semaphore::new(running.max(1))),
+        }
+    }
+
+    /// Queue a job and return its ID. The task runs once a slot is free;
+    /// its result becomes the job's result.
+    pub fn enqueue<F, Fut>(self: &Arc<Self>, kind: &str, task: F) -> u64
+    where
+        F: FnOnce(JobContext) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Value, JobError>> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancel = CancellationToken::new();
+        {
+            let mut jobs = self.jobs.lock();
+            if jobs.len() >= JOB_HISTORY {
+                let oldest = jobs.values().find(|entry| entry.job.status.is_finished()).map(|entry| entry.job.id);
+                if let Some(oldest) = oldest {
+                    jobs.remove(&oldest);
+                }
+            }
+            jobs.insert(id, JobEntry {
+                job: Job {
+                    id,
+                    kind: kind.to_string(),
+                    status: JobStatus::Queued,
+                    progress: JobProgress::default(),
+                    result: None,
+                    error: None,
+                    queued_at: Utc::now(),
+                    finished_at: None,
+                },
+                cancel: cancel.clone(),
+            });
+        }
+        log::info!("Queued {} job {}", kind, id);
+
+        let queue = self.clone();
+        tokio::spawn(async move {
+            let permit = tokio::select! {
+                _ = cancel.cancelled() => return,
+                permit = queue.slots.clone().acquire_owned() => permit,
+            };
+            let Ok(_permit) = permit else {
+                return;
+            };
+            if !queue.update(id, |job| job.status = JobStatus::Running) {
+                return;
+            }
+            let outcome = task(JobContext { id, queue: queue.clone(), cancel: cancel.clone() }).await;
+            queue.finish(id, outcome);
+        });
+        id
+    }
+
+    /// Get the state of a job
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().get(&id).map(|entry| entry.job.clone())
+    }
+
+    /// Get the state of every kept job, oldest first
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs.lock().values().map(|entry| entry.job.clone()).collect()
+    }
+
+    /// Cancel a queued or running job. A running job stops at its next
+    /// cancellation check.
+    pub fn cancel(&self, id: u64) -> Result<(), JobError> {
+        let mut jobs = self.jobs.lock();
+        let entry = jobs.get_mut(&id).ok_or(JobError::NotFound(id))?;
+        if entry.job.status.is_finished() {
+            return Err(JobError::Finished(id));
+        }
+        entry.cancel.cancel();
+        if entry.job.status == JobStatus::Queued {
+            entry.job.status = JobStatus::Cancelled;
+            entry.job.finished_at = Some(Utc::now());
+        }
+        log::info!("Cancelled {} job {}", entry.job.kind, id);
+        Ok(())
+    }
+
+    /// Change a job that hasn't finished, returning whether it was changed
+    fn update(&self, id: u64, change: impl FnOnce(&mut Job)) -> bool {
+        match self.jobs.lock().get_mut(&id) {
+            Some(entry) if !entry.job.status.is_finished() => {
+                change(&mut entry.job);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn finish(&self, id: u64, outcome: Result<Value, JobError>) {
+        let cancelled = self.jobs.lock().get(&id).is_some_and(|entry| entry.cancel.is_cancelled());
+        self.update(id, |job| {
+            match outcome {
+                Ok(result) if !cancelled => {
+                    job.status = JobStatus::Completed;
+                    job.result = Some(result);
+                }
+                Ok(_) | Err(JobError::Cancelled(_)) => job.status = JobStatus::Cancelled,
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+            job.finished_at = Some(Utc::now());
+            log::info!("{} job {} is {:?}", job.kind, id, job.status);
+        });
+    }
+}
+
+/// What a running job sees of the queue
+pub struct JobContext {
+    id: u64,
+    queue: Arc<JobQueue>,
+    cancel: CancellationToken,
+}
+
+impl JobContext {
+    /// Get the ID of the job
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Set the number of items the job will process
+    pub fn set_total(&self, total: usize) {
+        self.queue.update(self.id, |job| job.progress.total = total);
+    }
+
+    /// Record that one more item is done
+    pub fn advance(&self) {
+        self.queue.update(self.id, |job| job.progress.done += 1);
+    }
+
+    /// Check whether the job has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Fail with `JobError::Cancelled` if the job has been cancelled
+    pub fn check_cancelled(&self) -> Result<(), JobError> {
+        if self.is_cancelled() {
+            Err(JobError::Cancelled(self.id))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -5,10 +5,41 @@
  * This is the root of the backend library, organizing and
  * re-exporting the main components:
  * - CRDT implementation
+ * - Code blocks, export, and syntax checks
  * - WebSocket server
+ * - Client SDK with automatic reconnects
+ * - Security (encryption at rest, log redaction)
+ * - Multi-tenancy
+ * - Server metrics
+ * - Content moderation
+ * - Operation policies
+ * - Server-side undo
+ * - Document persistence
+ * - Presence of clients in documents
+ * - Server-side slash commands
+ * - Job queue for long-running tasks
+ * - Identifier generation
+ * - Opt-in usage statistics
+ * - Test fixtures (with the `test-util` feature)
  */
 
+pub mod blocks;
+pub mod client;
+pub mod commands;
 pub mod crdt;
+#[cfg(feature = "test-util")]
+pub mod fixtures;
+pub mod ids;
+pub mod jobs;
+pub mod metrics;
+pub mod moderation;
+pub mod policy;
+pub mod presence;
+pub mod security;
+pub mod storage;
+pub mod telemetry;
+pub mod tenant;
+pub mod undo;
 pub mod websocket;
 
 // Re-export commonly used types
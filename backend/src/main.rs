@@ -22,6 +22,7 @@ use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use futures::StreamExt;
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use crdt_editor_backend::ids::IdGenerator;
 
 // Types for our CRDT implementation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,7 +85,7 @@ async fn handle_client(ws: WebSocket, clients: Clients, _documents: Documents) {
     let (client_sender, client_rcv) = mpsc::unbounded_channel();
 
     // Generate a client ID based on connection details
-    let client_id = uuid::Uuid::new_v4().to_string();
+    let client_id = crdt_editor_backend::ids::UuidV7Ids.client_id();
 
     // Store the sender in our clients list
     clients.write().insert(client_id.clone(), client_sender);
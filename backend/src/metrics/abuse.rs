@@ -0,0 +1,294 @@
+/*
+ * File: src/metrics/abuse.rs
+ * Purpose: Detection of abusive editing rates per client
+ *
+ * Every operation a client sends counts toward its rate, and every delete
+ * also toward its delete rate, both averaged over the latest `window`. A
+ * client whose rate goes over `max_operations_per_second`, or whose
+ * delete rate goes over `max_deletes_per_second`, raises an alert: it is
+ * logged, counted and kept for the admin API, newest `ALERT_CAPACITY`
+ * first. A client alerts once per kind until its rate falls back under
+ * the limit. Since rates are averaged, a burst trips a limit once it holds
+ * more than `limit × window` operations.
+ *
+ * With `restrict_for` set, an alert also restricts the client's writes
+ * for that long: its operations are refused with an error until the
+ * restriction ends or an operator lifts it. Detection is off by default.
+ *
+ *   GET    /admin/abuse
+ *   DELETE /admin/abuse/<tenant>/<client_id>
+ *
+ * Clients are forgotten when they disconnect, unless restricted; then
+ * they are kept until the restriction ends, so it is still reported.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crdt::Operation;
+
+/// Most alerts kept for the admin API
+pub const ALERT_CAPACITY: usize = 256;
+
+/// Resolution at which operations are counted
+const BUCKET: Duration = Duration::from_millis(100);
+
+/// Limits of editing rates; detection is off by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbuseConfig {
+    /// Operations per second of one client that raise an alert
+    pub max_operations_per_second: Option<u64>,
+    /// Deletes per second of one client that raise an alert
+    pub max_deletes_per_second: Option<u64>,
+    /// Time rates are averaged over
+    pub window: Duration,
+    /// How long an alert restricts the client's writes; alerts restrict
+    /// nothing when None
+    pub restrict_for: Option<Duration>,
+}
+
+impl Default for AbuseConfig {
+    fn default() -> Self {
+        Self {
+            max_operations_per_second: None,
+            max_deletes_per_second: None,
+            window: Duration::from_secs(5),
+            restrict_for: None,
+        }
+    }
+}
+
+impl AbuseConfig {
+    /// Check whether any limit is set
+    pub fn is_enabled(&self) -> bool {
+        self.max_operations_per_second.is_some() || self.max_deletes_per_second.is_some()
+    }
+}
+
+/// Which limit a client went over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbuseKind {
+    /// Too many operations per second
+    OperationRate,
+    /// Too many deletes per second
+    DeleteStorm,
+}
+
+/// A client going over a limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbuseAlert {
+    pub tenant_id: String,
+    pub client_id: String,
+    pub kind: AbuseKind,
+    /// Rate when the alert was raised, per second
+    pub rate: f64,
+    /// Limit the rate went over, per second
+    pub limit: u64,
+    pub at: DateTime<Utc>,
+    /// End of the write restriction the alert imposed, if any
+    pub restricted_until: Option<DateTime<Utc>>,
+}
+
+/// A client whose writes are restricted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Restriction {
+    pub tenant_id: String,
+    pub client_id: String,
+    pub until: DateTime<Utc>,
+}
+
+/// Alerts, newest first, and the restrictions in force
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AbuseReport {
+    pub alerts: Vec<AbuseAlert>,
+    pub restricted: Vec<Restriction>,
+}
+
+/// Abuse errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AbuseError {
+    #[error("Writes of client {client_id} are restricted until {until} for editing too fast")]
+    Restricted { client_id: String, until: DateTime<Utc> },
+    #[error("Client {0} is not restricted")]
+    NotRestricted(String),
+}
+
+#[derive(Debug, Default)]
+struct ClientRate {
+    /// Start of each bucket with its operations and deletes, oldest first
+    buckets: VecDeque<(Instant, u64, u64)>,
+    /// Kinds the client has alerted for and not yet fallen back under
+    alerted: Vec<AbuseKind>,
+    /// End of the write restriction, for checks and for reports
+    restricted: Option<(Instant, DateTime<Utc>)>,
+    /// Whether the client disconnected while restricted
+    disconnected: bool,
+}
+
+impl ClientRate {
+    /// Count an operation and get the operation and delete rates
+    fn record(&mut self, delete: bool, window: Duration, now: Instant) -> (f64, f64) {
+        while self.buckets.front().is_some_and(|(start, _, _)| now.saturating_duration_since(*start) >= window) {
+            self.buckets.pop_front();
+        }
+        match self.buckets.back_mut() {
+            Some((start, operations, deletes)) if now.saturating_duration_since(*start) < BUCKET => {
+                *operations += 1;
+                *deletes += u64::from(delete);
+            }
+            _ => self.buckets.push_back((now, 1, u64::from(delete))),
+        }
+        let seconds = window.as_secs_f64().max(BUCKET.as_secs_f64());
+        let (operations, deletes) = self.buckets.iter().fold((0, 0), |(o, d), (_, operations, deletes)| (o + operations, d + deletes));
+        (operations as f64 / seconds, deletes as f64 / seconds)
+    }
+
+    fn restricted_until(&self, now: Instant) -> Option<DateTime<Utc>> {
+        self.restricted.filter(|(until, _)| *until > now).map(|(_, until)| until)
+    }
+}
+
+/// Editing rates of the clients of every tenant
+#[derive(Debug, Default)]
+pub struct AbuseDetector {
+    config: AbuseConfig,
+    /// Clients by tenant ID and client ID
+    clients: Mutex<HashMap<(String, String), ClientRate>>,
+    alerts: Mutex<VecDeque<AbuseAlert>>,
+    raised: AtomicU64,
+}
+
+impl AbuseDetector {
+    /// Create a detector with the given limits
+    pub fn new(config: AbuseConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Count an operation a client is about to apply. Fails when the
+    /// client's writes are restricted; otherwise returns the alerts it
+    /// raised, if any.
+    pub fn observe(&self, tenant_id: &str, client_id: &str, operation: &Operation, now: Instant) -> Result<Vec<AbuseAlert>, AbuseError> {
+        if !self.config.is_enabled() {
+            return Ok(Vec::new());
+        }
+        let mut clients = self.clients.lock();
+        let client = clients.entry((tenant_id.to_string(), client_id.to_string())).or_default();
+        if let Some(until) = client.restricted_until(now) {
+            return Err(AbuseError::Restricted { client_id: client_id.to_string(), until });
+        }
+        client.restricted = None;
+        client.disconnected = false;
+
+        let delete = operation.is_delete();
+        let (operation_rate, delete_rate) = client.record(delete, self.config.window, now);
+        let mut alerts = Vec::new();
+        for (kind, rate, limit) in [
+            (AbuseKind::OperationRate, operation_rate, self.config.max_operations_per_second),
+            (AbuseKind::DeleteStorm, delete_rate, self.config.max_deletes_per_second),
+        ] {
+            let Some(limit) = limit else { continue };
+            let over = rate > limit as f64;
+            let alerted = client.alerted.contains(&kind);
+            if !over {
+                client.alerted.retain(|alerted| *alerted != kind);
+                continue;
+            }
+            if alerted {
+                continue;
+            }
+            client.alerted.push(kind);
+            let at = Utc::now();
+            let restricted_until = self.config.restrict_for.map(|restrict_for| {
+                let until = at + chrono::Duration::from_std(restrict_for).unwrap_or(chrono::Duration::MAX);
+                client.restricted = Some((now + restrict_for, until));
+                until
+            });
+            alerts.push(AbuseAlert {
+                tenant_id: tenant_id.to_string(),
+                client_id: client_id.to_string(),
+                kind,
+                rate,
+                limit,
+                at,
+                restricted_until,
+            });
+        }
+        drop(clients);
+
+        for alert in &alerts {
+            log::warn!(
+                "Client {} of tenant {} edits abusively: {:?} at {:.1}/s over the limit of {}/s{}",
+                alert.client_id,
+                alert.tenant_id,
+                alert.kind,
+                alert.rate,
+                alert.limit,
+                alert.restricted_until.map(|until| format!("; writes restricted until {}", until)).unwrap_or_default(),
+            );
+            self.raised.fetch_add(1, Ordering::Relaxed);
+            let mut kept = self.alerts.lock();
+            if kept.len() >= ALERT_CAPACITY {
+                kept.pop_back();
+            }
+            kept.push_front(alert.clone());
+        }
+        Ok(alerts)
+    }
+
+    /// Lift a client's write restriction
+    pub fn lift(&self, tenant_id: &str, client_id: &str, now: Instant) -> Result<(), AbuseError> {
+        let mut clients = self.clients.lock();
+        let client = clients.get_mut(&(tenant_id.to_string(), client_id.to_string()))
+            .filter(|client| client.restricted_until(now).is_some())
+            .ok_or_else(|| AbuseError::NotRestricted(client_id.to_string()))?;
+        client.restricted = None;
+        Ok(())
+    }
+
+    /// Forget a client that disconnected, unless its writes are restricted
+    pub fn forget(&self, tenant_id: &str, client_id: &str, now: Instant) {
+        let key = (tenant_id.to_string(), client_id.to_string());
+        let mut clients = self.clients.lock();
+        match clients.get_mut(&key) {
+            Some(client) if client.restricted_until(now).is_some() => client.disconnected = true,
+            Some(_) => {
+                clients.remove(&key);
+            }
+            None => {}
+        }
+    }
+
+    /// Count the alerts raised since the server started
+    pub fn alerts_raised(&self) -> u64 {
+        self.raised.load(Ordering::Relaxed)
+    }
+
+    /// Get the kept alerts and the restrictions in force
+    pub fn report(&self, now: Instant) -> AbuseReport {
+        let mut clients = self.clients.lock();
+        // Restricted clients that disconnected are dropped once it ends
+        clients.retain(|_, client| !client.disconnected || client.restricted_until(now).is_some());
+        let mut restricted: Vec<Restriction> = clients.iter()
+            .filter_map(|((tenant_id, client_id), client)| {
+                client.restricted_until(now).map(|until| Restriction {
+                    tenant_id: tenant_id.clone(),
+                    client_id: client_id.clone(),
+                    until,
+                })
+            })
+            .collect();
+        restricted.sort_by(|a, b| (&a.tenant_id, &a.client_id).cmp(&(&b.tenant_id, &b.client_id)));
+        AbuseReport {
+            alerts: self.alerts.lock().iter().cloned().collect(),
+            restricted,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+/*
+ * File: src/metrics/bandwidth.rs
+ * Purpose: Bytes sent and received per client and per document, with caps
+ *
+ * Every frame a client sends or receives counts toward the client's
+ * session and, when the message names a `document_id`, toward that
+ * document: a broadcast counts once per recipient. Operators on metered
+ * links use the totals to find heavy rooms.
+ *
+ * `BandwidthConfig` can cap the bytes per second, in and out together, of
+ * each client and of each document. Usage drains from a bucket at the cap
+ * and may burst up to a second's worth; beyond that the server throttles,
+ * reading nothing more from the client until the bucket has drained back
+ * under the cap, for at most `MAX_THROTTLE_DELAY` per message. Throttling
+ * slows a heavy client or room down without dropping anything.
+ *
+ * Sessions are forgotten when the client disconnects; documents are kept.
+ */
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Longest a single message is held back
+pub const MAX_THROTTLE_DELAY: Duration = Duration::from_secs(10);
+
+/// Bandwidth caps, in bytes per second of traffic in both directions; no
+/// caps by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BandwidthConfig {
+    /// Cap of each client session
+    pub client_bytes_per_second: Option<u64>,
+    /// Cap of each document, across its clients
+    pub document_bytes_per_second: Option<u64>,
+}
+
+/// Which way a frame went, seen from the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// Bytes a client or document sent and received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthUsage {
+    /// Bytes received from clients
+    pub bytes_in: u64,
+    /// Bytes sent to clients
+    pub bytes_out: u64,
+}
+
+impl BandwidthUsage {
+    /// Get the bytes in both directions
+    pub fn total(&self) -> u64 {
+        self.bytes_in + self.bytes_out
+    }
+}
+
+/// Usage of a client session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientBandwidth {
+    pub tenant_id: String,
+    pub client_id: String,
+    #[serde(flatten)]
+    pub usage: BandwidthUsage,
+}
+
+/// Usage of a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentBandwidth {
+    pub tenant_id: String,
+    pub document_id: String,
+    #[serde(flatten)]
+    pub usage: BandwidthUsage,
+}
+
+/// The heaviest clients and documents, for the admin API
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthReport {
+    /// Clients by bytes in both directions, heaviest first
+    pub clients: Vec<ClientBandwidth>,
+    /// Documents by bytes in both directions, heaviest first
+    pub documents: Vec<DocumentBandwidth>,
+    /// Messages held back by a cap
+    pub throttled: u64,
+}
+
+#[derive(Debug)]
+struct Meter {
+    usage: BandwidthUsage,
+    /// Bytes not yet drained at the cap
+    level: f64,
+    updated: Instant,
+}
+
+impl Meter {
+    fn new(now: Instant) -> Self {
+        Self { usage: BandwidthUsage::default(), level: 0.0, updated: now }
+    }
+
+    fn add(&mut self, direction: Direction, bytes: u64, cap: Option<u64>, now: Instant) {
+        match direction {
+            Direction::In => self.usage.bytes_in += bytes,
+            Direction::Out => self.usage.bytes_out += bytes,
+        }
+        if let Some(cap) = cap {
+            self.drain(cap, now);
+            self.level += bytes as f64;
+        }
+    }
+
+    fn drain(&mut self, cap: u64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.level = (self.level - elapsed * cap as f64).max(0.0);
+        self.updated = now;
+    }
+
+    /// Time until the bucket is back under a second's worth of the cap
+    fn delay(&mut self, cap: Option<u64>, now: Instant) -> Duration {
+        let Some(cap) = cap.filter(|cap| *cap > 0) else {
+            return Duration::ZERO;
+        };
+        self.drain(cap, now);
+        let excess = self.level - cap as f64;
+        if excess <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(excess / cap as f64).min(MAX_THROTTLE_DELAY)
+    }
+}
+
+/// Bandwidth of client sessions and documents
+#[derive(Debug, Default)]
+pub struct BandwidthMeter {
+    config: BandwidthConfig,
+    /// Sessions by client ID, with their tenant
+    clients: Mutex<HashMap<String, (String, Meter)>>,
+    /// Documents by tenant and document ID
+    documents: Mutex<HashMap<(String, String), Meter>>,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    throttled: AtomicU64,
+}
+
+impl BandwidthMeter {
+    /// Create a meter enforcing caps
+    pub fn new(config: BandwidthConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Count a frame of a client, and of the document its message names
+    pub fn record(&self, tenant_id: &str, client_id: &str, document_id: Option<&str>, direction: Direction, bytes: usize) {
+        let bytes = bytes as u64;
+        let now = Instant::now();
+        match direction {
+            Direction::In => self.bytes_in.fetch_add(bytes, Ordering::Relaxed),
+            Direction::Out => self.bytes_out.fetch_add(bytes, Ordering::Relaxed),
+        };
+        {
+            let mut clients = self.clients.lock();
+            let (_, meter) = match clients.get_mut(client_id) {
+                Some(entry) => entry,
+                None => clients.entry(client_id.to_string()).or_insert_with(|| (tenant_id.to_string(), Meter::new(now))),
+            };
+            meter.add(direction, bytes, self.config.client_bytes_per_second, now);
+        }
+        if let Some(document_id) = document_id {
+            let mut documents = self.documents.lock();
+            let meter = documents.entry((tenant_id.to_string(), document_id.to_string())).or_insert_with(|| Meter::new(now));
+            meter.add(direction, bytes, self.config.document_bytes_per_second, now);
+        }
+    }
+
+    /// Get how long to hold back a client's next message, for its session
+    /// and the document the message names to get back under their caps
+    pub fn throttle_delay(&self, tenant_id: &str, client_id: &str, document_id: Option<&str>) -> Duration {
+        let now = Instant::now();
+        let client = self.clients.lock()
+            .get_mut(client_id)
+            .map_or(Duration::ZERO, |(_, meter)| meter.delay(self.config.client_bytes_per_second, now));
+        let document = document_id
+            .and_then(|document_id| {
+                self.documents.lock()
+                    .get_mut(&(tenant_id.to_string(), document_id.to_string()))
+                    .map(|meter| meter.delay(self.config.document_bytes_per_second, now))
+            })
+            .unwrap_or_default();
+        let delay = client.max(document);
+        if !delay.is_zero() {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+        }
+        delay
+    }
+
+    /// Forget a disconnected client's session
+    pub fn forget_client(&self, client_id: &str) {
+        self.clients.lock().remove(client_id);
+    }
+
+    /// Get the usage of a client's session
+    pub fn client(&self, client_id: &str) -> Option<BandwidthUsage> {
+        self.clients.lock().get(client_id).map(|(_, meter)| meter.usage)
+    }
+
+    /// Get the usage of a document
+    pub fn document(&self, tenant_id: &str, document_id: &str) -> Option<BandwidthUsage> {
+        self.documents.lock().get(&(tenant_id.to_string(), document_id.to_string())).map(|meter| meter.usage)
+    }
+
+    /// Get the bytes received and sent since the server started
+    pub fn totals(&self) -> BandwidthUsage {
+        BandwidthUsage {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Count the messages held back by a cap
+    pub fn throttled(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Get the heaviest connected clients and documents, at most `limit` of
+    /// each
+    pub fn report(&self, limit: usize) -> BandwidthReport {
+        let mut clients: Vec<ClientBandwidth> = self.clients.lock()
+            .iter()
+            .map(|(client_id, (tenant_id, meter))| ClientBandwidth {
+                tenant_id: tenant_id.clone(),
+                client_id: client_id.clone(),
+                usage: meter.usage,
+            })
+            .collect();
+        clients.sort_by(|a, b| b.usage.total().cmp(&a.usage.total()).then_with(|| a.client_id.cmp(&b.client_id)));
+        clients.truncate(limit);
+
+        let mut documents: Vec<DocumentBandwidth> = self.documents.lock()
+            .iter()
+            .map(|((tenant_id, document_id), meter)| DocumentBandwidth {
+                tenant_id: tenant_id.clone(),
+                document_id: document_id.clone(),
+                usage: meter.usage,
+            })
+            .collect();
+        documents.sort_by(|a, b| {
+            b.usage.total().cmp(&a.usage.total())
+                .then_with(|| (&a.tenant_id, &a.document_id).cmp(&(&b.tenant_id, &b.document_id)))
+        });
+        documents.truncate(limit);
+
+        BandwidthReport { clients, documents, throttled: self.throttled() }
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * File: src/metrics/counters.rs
+ * Purpose: Monotonic counters for server events
+ *
+ * Counters are lock-free and shared between connection tasks. Callers
+ * read them through `MetricsSnapshot`, a plain serializable copy, in which
+ * counters per tenant and document are bounded by the `LabelConfig`.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::{
+    abuse::{AbuseConfig, AbuseDetector},
+    bandwidth::{BandwidthConfig, BandwidthMeter},
+    deprecation::{DeprecatedUsage, DeprecationUsage},
+    labels::{LabelConfig, LabeledCounter, LabeledValue},
+    memory::{MemoryConfig, MemorySample, MemoryTrend},
+};
+
+/// A monotonically increasing event counter
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    /// Increment the counter by one
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the current value
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters for server-wide events
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    /// Messages that could not be handed to a client's outbound channel
+    pub send_failures: Counter,
+    /// Clients removed after repeated send failures
+    pub client_evictions: Counter,
+    /// Non-essential requests refused while overloaded
+    pub shed_requests: Counter,
+    /// Ephemeral messages, such as cursors, dropped as stale or because
+    /// their recipient's queue was full
+    pub stale_messages: Counter,
+    /// Operations applied, per tenant and document
+    pub operations: LabeledCounter,
+    /// Bytes per client and document, with their caps
+    pub bandwidth: BandwidthMeter,
+    /// Editing rates of clients, with their alerts and restrictions
+    pub abuse: AbuseDetector,
+    /// Messages and clients using deprecated protocol features
+    pub deprecations: DeprecationUsage,
+    /// Memory of documents, sampled over time
+    pub memory: MemoryTrend,
+    /// Limits of the tenant and document labels in snapshots
+    labels: LabelConfig,
+}
+
+impl ServerMetrics {
+    /// Create counters whose snapshots keep labels within limits
+    pub fn with_labels(labels: LabelConfig) -> Self {
+        Self {
+            labels,
+            ..Self::default()
+        }
+    }
+
+    /// Cap the bandwidth of clients and documents
+    pub fn with_bandwidth(mut self, caps: BandwidthConfig) -> Self {
+        self.bandwidth = BandwidthMeter::new(caps);
+        self
+    }
+
+    /// Alert on, and optionally restrict, clients editing abusively fast
+    pub fn with_abuse(mut self, limits: AbuseConfig) -> Self {
+        self.abuse = AbuseDetector::new(limits);
+        self
+    }
+
+    /// Keep as many memory samples as configured
+    pub fn with_memory(mut self, config: MemoryConfig) -> Self {
+        self.memory = MemoryTrend::new(config.samples);
+        self
+    }
+
+    /// Take a point-in-time copy of all counters
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            send_failures: self.send_failures.get(),
+            client_evictions: self.client_evictions.get(),
+            shed_requests: self.shed_requests.get(),
+            stale_messages: self.stale_messages.get(),
+            operations: self.operations.export(self.labels),
+            bytes_in: self.bandwidth.totals().bytes_in,
+            bytes_out: self.bandwidth.totals().bytes_out,
+            throttled_messages: self.bandwidth.throttled(),
+            abuse_alerts: self.abuse.alerts_raised(),
+            deprecated_usage: self.deprecations.usage(),
+            memory: self.memory.latest(),
+        }
+    }
+}
+
+/// Point-in-time copy of the server counters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub send_failures: u64,
+    pub client_evictions: u64,
+    pub shed_requests: u64,
+    /// Ephemeral messages dropped rather than delivered
+    #[serde(default)]
+    pub stale_messages: u64,
+    /// Operations applied per tenant and document, the least busy summed
+    /// under `other`
+    pub operations: Vec<LabeledValue>,
+    /// Bytes received from clients
+    #[serde(default)]
+    pub bytes_in: u64,
+    /// Bytes sent to clients
+    #[serde(default)]
+    pub bytes_out: u64,
+    /// Messages held back by a bandwidth cap
+    #[serde(default)]
+    pub throttled_messages: u64,
+    /// Alerts raised for clients editing abusively fast
+    #[serde(default)]
+    pub abuse_alerts: u64,
+    /// Use of deprecated protocol features, by feature
+    #[serde(default)]
+    pub deprecated_usage: Vec<DeprecatedUsage>,
+    /// Memory of every document at the latest sample
+    #[serde(default)]
+    pub memory: Option<MemorySample>,
+}
@@ -0,0 +1,67 @@
+/*
+ * File: src/metrics/deprecation.rs
+ * Purpose: Use of deprecated protocol features by clients
+ *
+ * Every message using a deprecated message type or feature bit is counted
+ * per feature, along with the clients that used it. A client counts once
+ * per connection, so `clients` tells how many old clients are still
+ * around, and `messages` how much they lean on the feature. Connected
+ * clients are forgotten when they disconnect; their counts stay.
+ */
+
+use std::collections::{BTreeMap, HashSet};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Use of one deprecated feature since the server started
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeprecatedUsage {
+    pub feature: String,
+    /// Messages that used it
+    pub messages: u64,
+    /// Connections that used it at least once
+    pub clients: u64,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    /// Messages and connections per feature
+    counts: BTreeMap<String, (u64, u64)>,
+    /// Connected clients already counted, by feature and client ID
+    seen: HashSet<(String, String)>,
+}
+
+/// Counters of deprecated features in use
+#[derive(Debug, Default)]
+pub struct DeprecationUsage {
+    usage: Mutex<Usage>,
+}
+
+impl DeprecationUsage {
+    /// Count a message of a client using a deprecated feature. Returns
+    /// whether it is the client's first use on this connection.
+    pub fn record(&self, feature: &str, client_id: &str) -> bool {
+        let mut usage = self.usage.lock();
+        let first = usage.seen.insert((feature.to_string(), client_id.to_string()));
+        let (messages, clients) = usage.counts.entry(feature.to_string()).or_default();
+        *messages += 1;
+        *clients += u64::from(first);
+        first
+    }
+
+    /// Forget a client that disconnected
+    pub fn forget(&self, client_id: &str) {
+        self.usage.lock().seen.retain(|(_, seen)| seen != client_id);
+    }
+
+    /// Get the use of every deprecated feature used so far, by feature
+    pub fn usage(&self) -> Vec<DeprecatedUsage> {
+        self.usage.lock().counts.iter()
+            .map(|(feature, (messages, clients))| DeprecatedUsage {
+                feature: feature.clone(),
+                messages: *messages,
+                clients: *clients,
+            })
+            .collect()
+    }
+}
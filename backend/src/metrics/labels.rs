@@ -0,0 +1,126 @@
+/*
+ * File: src/metrics/labels.rs
+ * Purpose: Per-tenant and per-document counters with bounded label sets
+ *
+ * Counters labeled by tenant and document would grow a series for every
+ * document ever touched, more than a scrape can carry. `LabeledCounter`
+ * counts exactly, and `LabelConfig` bounds what is exported:
+ * - the tenants with the highest counts keep their own series, and the
+ *   rest are summed under tenant `other`, document `other`
+ * - within a kept tenant, its busiest documents keep their own series,
+ *   and the rest are summed under document `other`
+ *
+ * Totals are preserved: the exported values add up to the exact count.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Label value of the series summing the values over a limit
+pub const OTHER_LABEL: &str = "other";
+
+/// How many values of a label keep their own series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelLimit {
+    /// Every value keeps its own series
+    Unlimited,
+    /// The values with the highest counts keep their own series; the rest
+    /// are summed under `other`
+    Top(usize),
+}
+
+impl LabelLimit {
+    fn keeps(self) -> usize {
+        match self {
+            LabelLimit::Unlimited => usize::MAX,
+            LabelLimit::Top(count) => count,
+        }
+    }
+}
+
+/// Limits of the tenant and document labels of exported metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabelConfig {
+    /// Tenants with their own series
+    pub tenants: LabelLimit,
+    /// Documents with their own series, per tenant
+    pub documents: LabelLimit,
+}
+
+impl Default for LabelConfig {
+    fn default() -> Self {
+        Self {
+            tenants: LabelLimit::Top(50),
+            documents: LabelLimit::Top(20),
+        }
+    }
+}
+
+/// The value of one exported series
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabeledValue {
+    pub tenant: String,
+    pub document: String,
+    pub value: u64,
+}
+
+/// A counter per tenant and document
+#[derive(Debug, Default)]
+pub struct LabeledCounter {
+    /// Counts by tenant, then document
+    counts: Mutex<HashMap<String, HashMap<String, u64>>>,
+}
+
+impl LabeledCounter {
+    /// Increment the count of a tenant's document by one
+    pub fn increment(&self, tenant_id: &str, document_id: &str) {
+        let mut counts = self.counts.lock();
+        let documents = match counts.get_mut(tenant_id) {
+            Some(documents) => documents,
+            None => counts.entry(tenant_id.to_string()).or_default(),
+        };
+        match documents.get_mut(document_id) {
+            Some(count) => *count += 1,
+            None => {
+                documents.insert(document_id.to_string(), 1);
+            }
+        }
+    }
+
+    /// Get the exact count of a tenant's document
+    pub fn get(&self, tenant_id: &str, document_id: &str) -> u64 {
+        let counts = self.counts.lock();
+        counts.get(tenant_id).and_then(|documents| documents.get(document_id)).copied().unwrap_or(0)
+    }
+
+    /// Get the series to export within label limits, sorted by tenant and
+    /// document
+    pub fn export(&self, config: LabelConfig) -> Vec<LabeledValue> {
+        let counts = self.counts.lock();
+        let mut tenants: Vec<(&String, &HashMap<String, u64>, u64)> = counts
+            .iter()
+            .map(|(tenant, documents)| (tenant, documents, documents.values().sum()))
+            .collect();
+        tenants.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+
+        let mut series: BTreeMap<(String, String), u64> = BTreeMap::new();
+        let kept = config.tenants.keeps();
+        for (rank, (tenant, documents, total)) in tenants.into_iter().enumerate() {
+            if rank >= kept {
+                *series.entry((OTHER_LABEL.to_string(), OTHER_LABEL.to_string())).or_default() += total;
+                continue;
+            }
+            let mut ranked: Vec<(&String, &u64)> = documents.iter().collect();
+            ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            for (rank, (document, count)) in ranked.into_iter().enumerate() {
+                let document = if rank < config.documents.keeps() { document.as_str() } else { OTHER_LABEL };
+                *series.entry((tenant.clone(), document.to_string())).or_default() += count;
+            }
+        }
+        series
+            .into_iter()
+            .map(|((tenant, document), value)| LabeledValue { tenant, document, value })
+            .collect()
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * File: src/metrics/memory.rs
+ * Purpose: What documents hold in memory, sampled over time
+ *
+ * Every `interval`, the server adds up the `MemoryReport` of each of its
+ * documents: live characters, tombstones, operation log and indexes, in
+ * entries and estimated bytes. The latest `samples` are kept, oldest
+ * first, so a change to garbage collection or compaction shows up as a
+ * change in the trend rather than being guessed at.
+ *
+ * Byte counts are estimates from the sizes of the structures and their
+ * heap data; allocator overhead and spare capacity aren't included.
+ */
+
+use std::{collections::VecDeque, time::Duration};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::crdt::MemoryReport;
+
+/// How often memory is sampled, and how many samples are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryConfig {
+    /// Time between samples
+    pub interval: Duration,
+    /// Samples kept; the default keeps a day of them
+    pub samples: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            samples: 24 * 60,
+        }
+    }
+}
+
+/// Memory of every document together at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemorySample {
+    pub at: DateTime<Utc>,
+    /// Documents added up
+    pub documents: usize,
+    #[serde(flatten)]
+    pub memory: MemoryReport,
+}
+
+/// Memory of a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentMemory {
+    pub tenant_id: String,
+    pub document_id: String,
+    #[serde(flatten)]
+    pub memory: MemoryReport,
+}
+
+/// The current memory of documents and its trend, for the admin API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryOverview {
+    /// Every document together, now
+    pub current: MemorySample,
+    /// Documents by estimated bytes, heaviest first
+    pub documents: Vec<DocumentMemory>,
+    /// Samples taken, oldest first
+    pub trend: Vec<MemorySample>,
+}
+
+/// The latest memory samples
+#[derive(Debug)]
+pub struct MemoryTrend {
+    samples: Mutex<VecDeque<MemorySample>>,
+    capacity: usize,
+}
+
+impl Default for MemoryTrend {
+    fn default() -> Self {
+        Self::new(MemoryConfig::default().samples)
+    }
+}
+
+impl MemoryTrend {
+    /// Create a trend keeping the latest `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        Self { samples: Mutex::new(VecDeque::new()), capacity }
+    }
+
+    /// Keep a sample, dropping the oldest beyond capacity
+    pub fn record(&self, sample: MemorySample) {
+        let mut samples = self.samples.lock();
+        samples.push_back(sample);
+        while samples.len() > self.capacity {
+            samples.pop_front();
+        }
+    }
+
+    /// Get the newest sample
+    pub fn latest(&self) -> Option<MemorySample> {
+        self.samples.lock().back().cloned()
+    }
+
+    /// Get the samples kept, oldest first
+    pub fn samples(&self) -> Vec<MemorySample> {
+        self.samples.lock().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,29 @@
+/*
+ * File: src/metrics/mod.rs
+ * Purpose: Module organization for server metrics
+ *
+ * This module contains:
+ * - abuse: Alerts on, and restrictions of, abusive editing rates
+ * - bandwidth: Bytes per client and document, with caps that throttle
+ * - counters: Monotonic counters for server events and their snapshots
+ * - deprecation: Use of deprecated protocol features by clients
+ * - labels: Counters per tenant and document with bounded label sets
+ * - memory: What documents hold in memory, sampled over time
+ * - overload: Overload detection from operation latency
+ */
+
+pub mod abuse;
+pub mod bandwidth;
+pub mod counters;
+pub mod deprecation;
+pub mod labels;
+pub mod memory;
+pub mod overload;
+
+pub use abuse::{AbuseAlert, AbuseConfig, AbuseDetector, AbuseError, AbuseKind, AbuseReport, Restriction, ALERT_CAPACITY};
+pub use bandwidth::{BandwidthConfig, BandwidthMeter, BandwidthReport, BandwidthUsage, Direction};
+pub use counters::{Counter, MetricsSnapshot, ServerMetrics};
+pub use deprecation::{DeprecatedUsage, DeprecationUsage};
+pub use labels::{LabelConfig, LabelLimit, LabeledCounter, LabeledValue, OTHER_LABEL};
+pub use memory::{DocumentMemory, MemoryConfig, MemoryOverview, MemorySample, MemoryTrend};
+pub use overload::{OverloadChange, OverloadConfig, OverloadDetector, OverloadStatus};
@@ -0,0 +1,167 @@
+/*
+ * File: src/metrics/overload.rs
+ * Purpose: Overload detection from operation latency
+ *
+ * The detector keeps the latencies of the newest `LATENCY_WINDOW`
+ * operations, each measured from the moment the server starts applying it
+ * until it has been broadcast. Every `EVALUATE_EVERY` operations it
+ * compares their p99 with the configured budget: above it, the server
+ * sheds non-essential work so that editing stays fast. Shedding stops once
+ * p99 falls below `recovery_ratio` of the budget, or when no operation
+ * arrives for `quiet_period`; the gap between the two thresholds keeps the
+ * server from flapping.
+ */
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Operations whose latency is kept
+pub const LATENCY_WINDOW: usize = 1024;
+
+/// Operations between two evaluations of the latency
+pub const EVALUATE_EVERY: usize = 64;
+
+/// Latency budget of operations; detection is off by default
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverloadConfig {
+    /// p99 latency above which the server sheds work; no shedding when None
+    pub budget: Option<Duration>,
+    /// Fraction of the budget p99 must fall below to stop shedding
+    pub recovery_ratio: f64,
+    /// Time without operations after which shedding stops
+    pub quiet_period: Duration,
+}
+
+impl Default for OverloadConfig {
+    fn default() -> Self {
+        Self {
+            budget: None,
+            recovery_ratio: 0.8,
+            quiet_period: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A change of the overload state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadChange {
+    Started { p99: Duration },
+    Ended { p99: Duration },
+}
+
+/// Overload state, as reported to admins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverloadStatus {
+    pub shedding: bool,
+    /// p99 latency at the last evaluation, in milliseconds
+    pub p99_ms: Option<f64>,
+    pub budget_ms: Option<f64>,
+    /// When shedding started
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    latencies: VecDeque<Duration>,
+    since_evaluation: usize,
+    last_sample: Option<Instant>,
+    p99: Option<Duration>,
+    since: Option<DateTime<Utc>>,
+}
+
+/// Detector of operation latency above the budget
+#[derive(Debug, Default)]
+pub struct OverloadDetector {
+    config: OverloadConfig,
+    shedding: AtomicBool,
+    samples: Mutex<Samples>,
+}
+
+impl OverloadDetector {
+    /// Create a detector for a budget
+    pub fn new(config: OverloadConfig) -> Self {
+        Self {
+            config,
+            shedding: AtomicBool::new(false),
+            samples: Mutex::new(Samples::default()),
+        }
+    }
+
+    /// Record the latency of an operation, returning the overload change it
+    /// caused, if any
+    pub fn record(&self, latency: Duration) -> Option<OverloadChange> {
+        let budget = self.config.budget?;
+        let mut samples = self.samples.lock();
+        if samples.latencies.len() >= LATENCY_WINDOW {
+            samples.latencies.pop_front();
+        }
+        samples.latencies.push_back(latency);
+        samples.last_sample = Some(Instant::now());
+        samples.since_evaluation += 1;
+        if samples.since_evaluation < EVALUATE_EVERY {
+            return None;
+        }
+        samples.since_evaluation = 0;
+
+        let p99 = percentile(&samples.latencies, 0.99);
+        samples.p99 = Some(p99);
+        let shedding = self.shedding.load(Ordering::Relaxed);
+        if !shedding && p99 > budget {
+            self.shedding.store(true, Ordering::Relaxed);
+            samples.since = Some(Utc::now());
+            log::warn!("Overloaded: p99 operation latency {:?} exceeds the budget of {:?}; shedding non-essential work", p99, budget);
+            Some(OverloadChange::Started { p99 })
+        } else if shedding && p99.as_secs_f64() < budget.as_secs_f64() * self.config.recovery_ratio {
+            self.shedding.store(false, Ordering::Relaxed);
+            samples.since = None;
+            log::info!("Recovered from overload: p99 operation latency {:?}", p99);
+            Some(OverloadChange::Ended { p99 })
+        } else {
+            None
+        }
+    }
+
+    /// Check whether non-essential work should be shed
+    pub fn is_shedding(&self) -> bool {
+        if !self.shedding.load(Ordering::Relaxed) {
+            return false;
+        }
+        // Without operations there is no latency to protect
+        let mut samples = self.samples.lock();
+        if samples.last_sample.is_some_and(|last| last.elapsed() >= self.config.quiet_period) {
+            self.shedding.store(false, Ordering::Relaxed);
+            samples.latencies.clear();
+            samples.since_evaluation = 0;
+            samples.since = None;
+            log::info!("Recovered from overload: no operations for {:?}", self.config.quiet_period);
+            return false;
+        }
+        true
+    }
+
+    /// Get the overload state
+    pub fn status(&self) -> OverloadStatus {
+        let shedding = self.is_shedding();
+        let samples = self.samples.lock();
+        OverloadStatus {
+            shedding,
+            p99_ms: samples.p99.map(|p99| p99.as_secs_f64() * 1000.0),
+            budget_ms: self.config.budget.map(|budget| budget.as_secs_f64() * 1000.0),
+            since: samples.since,
+        }
+    }
+}
+
+/// Get a percentile of latencies; `quantile` lies between 0 and 1
+fn percentile(latencies: &VecDeque<Duration>, quantile: f64) -> Duration {
+    let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+    sorted.sort_unstable();
+    let rank = (sorted.len() as f64 * quantile).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied().unwrap_or_default()
+}
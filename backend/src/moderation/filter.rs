@@ -0,0 +1,65 @@
+/*
+ * File: src/moderation/filter.rs
+ * Purpose: Pluggable content filters and the local wordlist filter
+ *
+ * A filter receives a batch of texts and returns, for each one, the terms
+ * it objects to; an empty list means the text is fine. The wordlist filter
+ * matches whole words case-insensitively, so `class` doesn't trip a filter
+ * listing `ass`.
+ */
+
+use std::{collections::HashSet, str::FromStr};
+use futures::future::BoxFuture;
+use crate::moderation::ModerationError;
+
+/// Checks batches of text
+pub trait ContentFilter: std::fmt::Debug + Send + Sync {
+    /// Return the flagged terms of each text, in the order of `texts`
+    fn check<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<String>>, ModerationError>>;
+}
+
+/// Filter flagging words from a fixed list
+#[derive(Debug, Clone, Default)]
+pub struct WordlistFilter {
+    words: HashSet<String>,
+}
+
+impl WordlistFilter {
+    /// Create a filter flagging the given words
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Get the flagged words of a text, in order of appearance
+    pub fn matches(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .map(str::to_lowercase)
+            .filter(|word| self.words.contains(word))
+            .collect()
+    }
+}
+
+impl FromStr for WordlistFilter {
+    type Err = ModerationError;
+
+    /// Parse a wordlist with one word per line; `#` starts a comment line
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(source.lines().filter(|line| !line.trim_start().starts_with('#'))))
+    }
+}
+
+impl ContentFilter for WordlistFilter {
+    fn check<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<String>>, ModerationError>> {
+        Box::pin(async move { Ok(texts.iter().map(|text| self.matches(text)).collect()) })
+    }
+}
@@ -0,0 +1,78 @@
+/*
+ * File: src/moderation/http.rs
+ * Purpose: Content filter backed by an external moderation service
+ *
+ * Each batch is posted as JSON to the moderation service, which answers
+ * with the flagged terms of every text, in order:
+ *
+ *   request:  {"texts": ["hello", "badword"]}
+ *   response: {"results": [[], ["badword"]]}
+ *
+ * A failed request or a response with the wrong number of results fails
+ * the batch; its words are logged and left alone.
+ */
+
+use futures::future::BoxFuture;
+use hyper::{client::HttpConnector, Body, Client, Method, Request, Uri};
+use serde::{Deserialize, Serialize};
+use crate::moderation::{filter::ContentFilter, ModerationError};
+
+#[derive(Serialize)]
+struct FilterRequest<'a> {
+    texts: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct FilterResponse {
+    results: Vec<Vec<String>>,
+}
+
+/// Content filter calling an HTTP endpoint
+#[derive(Debug, Clone)]
+pub struct HttpFilter {
+    url: Uri,
+    client: Client<HttpConnector>,
+}
+
+impl HttpFilter {
+    /// Create a filter calling the given `http://` URL
+    pub fn new(url: &str) -> Result<Self, ModerationError> {
+        let url: Uri = url.parse().map_err(|_| ModerationError::InvalidUrl(url.to_string()))?;
+        if url.scheme_str() != Some("http") {
+            return Err(ModerationError::InvalidUrl(format!("{} (only http:// is supported)", url)));
+        }
+
+        Ok(Self {
+            url,
+            client: Client::new(),
+        })
+    }
+
+    async fn request(&self, texts: &[String]) -> Result<Vec<Vec<String>>, ModerationError> {
+        let failed = |e: String| ModerationError::Request(e);
+        let body = serde_json::to_vec(&FilterRequest { texts }).map_err(|e| failed(e.to_string()))?;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .map_err(|e| failed(e.to_string()))?;
+
+        let response = self.client.request(request).await.map_err(|e| failed(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(failed(format!("status {}", response.status().as_u16())));
+        }
+        let body = hyper::body::to_bytes(response.into_body()).await.map_err(|e| failed(e.to_string()))?;
+        let response: FilterResponse = serde_json::from_slice(&body).map_err(|e| failed(format!("invalid response: {}", e)))?;
+        if response.results.len() != texts.len() {
+            return Err(failed(format!("expected {} results, got {}", texts.len(), response.results.len())));
+        }
+        Ok(response.results)
+    }
+}
+
+impl ContentFilter for HttpFilter {
+    fn check<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<String>>, ModerationError>> {
+        Box::pin(self.request(texts))
+    }
+}
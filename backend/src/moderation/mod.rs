@@ -0,0 +1,77 @@
+/*
+ * File: src/moderation/mod.rs
+ * Purpose: Module organization for content moderation
+ *
+ * This module contains:
+ * - filter: The ContentFilter trait and the local wordlist filter
+ * - http: External moderation service over HTTP
+ * - regions: Grouping of inserted characters into words to check
+ *
+ * Moderation runs after operations are applied: inserted words are
+ * collected as they are typed, checked in batches, and flagged words are
+ * handled according to the configured `ModerationAction`.
+ */
+
+pub mod filter;
+pub mod http;
+pub mod regions;
+
+pub use filter::{ContentFilter, WordlistFilter};
+pub use http::HttpFilter;
+pub use regions::{RegionTracker, TextRegion};
+
+use std::{sync::Arc, time::Duration};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Moderation errors
+#[derive(Error, Debug)]
+pub enum ModerationError {
+    #[error("Invalid moderation URL: {0}")]
+    InvalidUrl(String),
+    #[error("Moderation request failed: {0}")]
+    Request(String),
+}
+
+/// What happens to a word the filter flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Mask the word's characters with the replacement character
+    #[default]
+    Redact,
+    /// Leave the word in place and notify moderators
+    Flag,
+    /// Remove the word and send its author an error
+    Reject,
+}
+
+/// Configuration for content moderation
+#[derive(Debug, Clone)]
+pub struct ModerationConfig {
+    /// Filter inserted words are checked against; `None` disables moderation
+    pub filter: Option<Arc<dyn ContentFilter>>,
+    /// What to do with flagged words
+    pub action: ModerationAction,
+    /// Character that replaces each character of a redacted word
+    pub replacement: char,
+    /// Most words checked in one filter call
+    pub batch_size: usize,
+    /// How long a word may wait for its batch to fill up
+    pub batch_delay: Duration,
+    /// Endpoint notified of every flagged word, e.g. a moderator queue
+    pub webhook_url: Option<String>,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            action: ModerationAction::default(),
+            replacement: '*',
+            batch_size: 32,
+            batch_delay: Duration::from_millis(200),
+            webhook_url: None,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * File: src/moderation/regions.rs
+ * Purpose: Grouping of inserted characters into words for moderation
+ *
+ * Operations insert one character at a time, so filters would never see a
+ * whole word if they checked operations. The tracker keeps the word each
+ * client is typing in each document:
+ * - Letters and digits extend the word
+ * - Any other character ends it, and the word is ready to check
+ * - Deleting a character of the word removes it from the word
+ * - Words reaching `MAX_REGION_CHARS` are checked without waiting further
+ */
+
+use std::collections::HashMap;
+use crate::crdt::{Operation, Position};
+
+/// Longest word kept before it is checked anyway
+pub const MAX_REGION_CHARS: usize = 64;
+
+/// A word a client inserted into a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRegion {
+    pub tenant_id: String,
+    pub document_id: String,
+    pub client_id: String,
+    /// Inserted characters and their positions, in the order typed
+    pub characters: Vec<(Position, char)>,
+}
+
+impl TextRegion {
+    /// Get the region's text
+    pub fn text(&self) -> String {
+        self.characters.iter().map(|(_, c)| c).collect()
+    }
+}
+
+/// Key of a word being typed: tenant, document and client
+type RegionKey = (String, String, String);
+
+/// Collects the words clients are typing
+#[derive(Debug, Default)]
+pub struct RegionTracker {
+    pending: HashMap<RegionKey, Vec<(Position, char)>>,
+}
+
+impl RegionTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an applied operation, returning the word it completes, if any
+    pub fn observe(&mut self, tenant_id: &str, document_id: &str, client_id: &str, operation: &Operation) -> Option<TextRegion> {
+        let key = (tenant_id.to_string(), document_id.to_string(), client_id.to_string());
+        match operation {
+            Operation::Insert { character, position, .. } if character.is_alphanumeric() => {
+                let characters = self.pending.entry(key.clone()).or_default();
+                characters.push((position.clone(), *character));
+                if characters.len() >= MAX_REGION_CHARS {
+                    return self.take(key);
+                }
+                None
+            }
+            Operation::Insert { .. } => self.take(key),
+            Operation::Delete { position, .. } => {
+                if let Some(characters) = self.pending.get_mut(&key) {
+                    characters.retain(|(p, _)| p != position);
+                    if characters.is_empty() {
+                        self.pending.remove(&key);
+                    }
+                }
+                None
+            }
+            Operation::DeleteRange { start, end, .. } => {
+                if let Some(characters) = self.pending.get_mut(&key) {
+                    characters.retain(|(p, _)| p < start || p > end);
+                    if characters.is_empty() {
+                        self.pending.remove(&key);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    /// Finish every word a client was typing, e.g. when it disconnects
+    pub fn finish_client(&mut self, client_id: &str) -> Vec<TextRegion> {
+        let keys: Vec<RegionKey> = self.pending.keys().filter(|(_, _, client)| client == client_id).cloned().collect();
+        keys.into_iter().filter_map(|key| self.take(key)).collect()
+    }
+
+    /// Get the number of words being typed
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn take(&mut self, key: RegionKey) -> Option<TextRegion> {
+        let characters = self.pending.remove(&key)?;
+        let (tenant_id, document_id, client_id) = key;
+        Some(TextRegion {
+            tenant_id,
+            document_id,
+            client_id,
+            characters,
+        })
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * File: src/policy/engine.rs
+ * Purpose: Pre-apply policy hook for operations
+ *
+ * Before the server applies a client's operation it asks the configured
+ * `PolicyEngine`, which sees who sent it, the document it targets and what
+ * it does, and decides to:
+ * - Allow the operation
+ * - Deny it, with a reason returned to the client
+ * - Transform it into a different operation, which is applied and relayed
+ *   in its place
+ *
+ * Engines are async so they can call out to external services.
+ * `CachedPolicy` remembers allow and deny decisions per identity and
+ * document for engines whose decisions don't depend on the operation.
+ */
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::crdt::{Operation, OperationSource};
+
+/// What an operation does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    Insert,
+    Delete,
+}
+
+/// Document an operation targets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentInfo {
+    pub id: String,
+    /// Whether the document exists yet
+    pub exists: bool,
+    /// Number of visible characters
+    pub length: usize,
+    pub version: u64,
+}
+
+/// Everything a policy sees about an operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyInput {
+    pub tenant_id: String,
+    /// Connection the operation arrived on
+    pub client_id: String,
+    pub document: DocumentInfo,
+    pub kind: OperationKind,
+    /// Character being inserted
+    pub character: Option<char>,
+    /// The operation itself, for policies that transform it
+    pub operation: Operation,
+    /// Kind of actor the operation comes from
+    #[serde(default)]
+    pub source: OperationSource,
+}
+
+impl PolicyInput {
+    /// Describe an operation sent by a client
+    pub fn new(tenant_id: &str, client_id: &str, document: DocumentInfo, operation: &Operation) -> Self {
+        let (kind, character) = match operation {
+            Operation::Insert { character, .. } => (OperationKind::Insert, Some(*character)),
+            Operation::Delete { .. } | Operation::DeleteRange { .. } => (OperationKind::Delete, None),
+        };
+        Self {
+            tenant_id: tenant_id.to_string(),
+            client_id: client_id.to_string(),
+            document,
+            kind,
+            character,
+            operation: operation.clone(),
+            source: OperationSource::User,
+        }
+    }
+
+    /// Describe the operation as coming from a kind of actor
+    pub fn with_source(mut self, source: OperationSource) -> Self {
+        self.source = source;
+        self
+    }
+}
+
+/// Outcome of a policy evaluation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Deny {
+        #[serde(default)]
+        reason: String,
+    },
+    /// Apply this operation instead
+    Transform { operation: Operation },
+}
+
+impl Decision {
+    /// Deny with a reason
+    pub fn deny(reason: impl Into<String>) -> Self {
+        Decision::Deny { reason: reason.into() }
+    }
+}
+
+/// Decides whether operations may be applied
+pub trait PolicyEngine: Debug + Send + Sync {
+    /// Evaluate an operation before it is applied
+    fn evaluate<'a>(&'a self, input: &'a PolicyInput) -> BoxFuture<'a, Decision>;
+}
+
+/// Allows every operation; the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAll;
+
+impl PolicyEngine for AllowAll {
+    fn evaluate<'a>(&'a self, _input: &'a PolicyInput) -> BoxFuture<'a, Decision> {
+        Box::pin(async { Decision::Allow })
+    }
+}
+
+/// Cache key: tenant, client and document
+type CacheKey = (String, String, String);
+
+/// Caches another engine's allow and deny decisions per (identity, document)
+/// for a fixed time. Transforms depend on the operation and are never cached.
+#[derive(Debug)]
+pub struct CachedPolicy {
+    inner: Arc<dyn PolicyEngine>,
+    ttl: Duration,
+    decisions: Mutex<HashMap<CacheKey, (Decision, Instant)>>,
+}
+
+impl CachedPolicy {
+    /// Cache the decisions of `inner` for `ttl`
+    pub fn new(inner: Arc<dyn PolicyEngine>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            decisions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forget every cached decision, e.g. after a policy change
+    pub fn clear(&self) {
+        self.decisions.lock().clear();
+    }
+}
+
+impl PolicyEngine for CachedPolicy {
+    fn evaluate<'a>(&'a self, input: &'a PolicyInput) -> BoxFuture<'a, Decision> {
+        Box::pin(async move {
+            let key = (input.tenant_id.clone(), input.client_id.clone(), input.document.id.clone());
+            if let Some((decision, at)) = self.decisions.lock().get(&key) {
+                if at.elapsed() < self.ttl {
+                    return decision.clone();
+                }
+            }
+
+            let decision = self.inner.evaluate(input).await;
+            let mut decisions = self.decisions.lock();
+            decisions.retain(|_, (_, at)| at.elapsed() < self.ttl);
+            if !matches!(decision, Decision::Transform { .. }) {
+                decisions.insert(key, (decision.clone(), Instant::now()));
+            }
+            decision
+        })
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * File: src/policy/http.rs
+ * Purpose: Operation policies decided by an external HTTP service
+ *
+ * Each operation's `PolicyInput` is posted as JSON to the policy service,
+ * which answers with a decision:
+ *
+ *   {"decision": "allow"}
+ *   {"decision": "deny", "reason": "Outside of class hours"}
+ *   {"decision": "transform", "operation": {...}}
+ *
+ * When the service is unreachable or answers with anything else, the
+ * operation is denied, unless the policy is configured to fail open.
+ * Wrap the policy in `CachedPolicy` to avoid a request per keystroke.
+ */
+
+use futures::future::BoxFuture;
+use hyper::{client::HttpConnector, Body, Client, Method, Request, Uri};
+use crate::policy::{
+    engine::{Decision, PolicyEngine, PolicyInput},
+    PolicyError,
+};
+
+/// Reason given when the policy service can't decide
+const UNAVAILABLE_REASON: &str = "Policy service unavailable";
+
+/// Policy engine backed by an HTTP endpoint
+#[derive(Debug, Clone)]
+pub struct HttpPolicy {
+    url: Uri,
+    client: Client<HttpConnector>,
+    fail_open: bool,
+}
+
+impl HttpPolicy {
+    /// Create a policy calling the given `http://` URL
+    pub fn new(url: &str) -> Result<Self, PolicyError> {
+        let url: Uri = url.parse().map_err(|_| PolicyError::InvalidUrl(url.to_string()))?;
+        if url.scheme_str() != Some("http") {
+            return Err(PolicyError::InvalidUrl(format!("{} (only http:// is supported)", url)));
+        }
+
+        Ok(Self {
+            url,
+            client: Client::new(),
+            fail_open: false,
+        })
+    }
+
+    /// Allow operations instead of denying them when the service fails
+    pub fn fail_open(mut self, fail_open: bool) -> Self {
+        self.fail_open = fail_open;
+        self
+    }
+
+    /// Ask the service for a decision
+    async fn request(&self, input: &PolicyInput) -> Result<Decision, String> {
+        let body = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .map_err(|e| e.to_string())?;
+
+        let response = self.client.request(request).await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("status {}", response.status().as_u16()));
+        }
+        let body = hyper::body::to_bytes(response.into_body()).await.map_err(|e| e.to_string())?;
+        serde_json::from_slice(&body).map_err(|e| format!("invalid decision: {}", e))
+    }
+}
+
+impl PolicyEngine for HttpPolicy {
+    fn evaluate<'a>(&'a self, input: &'a PolicyInput) -> BoxFuture<'a, Decision> {
+        Box::pin(async move {
+            match self.request(input).await {
+                Ok(decision) => decision,
+                Err(e) => {
+                    log::warn!("Policy service {} failed: {}", self.url, e);
+                    if self.fail_open {
+                        Decision::Allow
+                    } else {
+                        Decision::deny(UNAVAILABLE_REASON)
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,28 @@
+/*
+ * File: src/policy/mod.rs
+ * Purpose: Module organization for operation policies
+ *
+ * This module contains:
+ * - engine: The PolicyEngine trait, its inputs and decisions, and caching
+ * - rules: Embedded rule language
+ * - http: External policy service over HTTP
+ */
+
+pub mod engine;
+pub mod http;
+pub mod rules;
+
+pub use engine::{AllowAll, CachedPolicy, Decision, DocumentInfo, OperationKind, PolicyEngine, PolicyInput};
+pub use http::HttpPolicy;
+pub use rules::RulePolicy;
+
+use thiserror::Error;
+
+/// Policy configuration errors
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    #[error("Invalid rule on line {line}: {message}")]
+    InvalidRule { line: usize, message: String },
+    #[error("Invalid policy URL: {0}")]
+    InvalidUrl(String),
+}
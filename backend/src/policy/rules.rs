@@ -0,0 +1,301 @@
+/*
+ * File: src/policy/rules.rs
+ * Purpose: Embedded rule language for operation policies
+ *
+ * A rule set is plain text, one rule per line, evaluated top to bottom;
+ * the first matching rule decides and operations no rule matches are
+ * allowed:
+ *
+ *   # Announcements are read-only for everyone but the bot
+ *   allow * when document ^= "announce-" and client == "bot"
+ *   deny * when document ^= "announce-" reason "Announcements are read-only"
+ *   deny insert when length >= 50000 reason "Document is full"
+ *
+ * Syntax: `allow|deny insert|delete|* [when COND (and COND)*] [reason "TEXT"]`
+ *
+ * Conditions compare a field with a quoted string or a number:
+ * - String fields: tenant, client, document, character, exists, source,
+ *   with `==`, `!=` and `^=` (starts with)
+ * - Number fields: length, version, with `==`, `!=`, `<`, `<=`, `>`, `>=`
+ */
+
+use std::str::FromStr;
+use futures::future::BoxFuture;
+use crate::policy::{
+    engine::{Decision, OperationKind, PolicyEngine, PolicyInput},
+    PolicyError,
+};
+
+/// Reason given for denials without one
+const DEFAULT_REASON: &str = "Denied by policy";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Tenant,
+    Client,
+    Document,
+    Character,
+    Exists,
+    Source,
+    Length,
+    Version,
+}
+
+impl Field {
+    fn is_numeric(self) -> bool {
+        matches!(self, Field::Length | Field::Version)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    StartsWith,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Number(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: Field,
+    comparison: Comparison,
+    value: Value,
+}
+
+impl Condition {
+    fn matches(&self, input: &PolicyInput) -> bool {
+        match &self.value {
+            Value::Number(expected) => {
+                let actual = match self.field {
+                    Field::Length => input.document.length as u64,
+                    _ => input.document.version,
+                };
+                match self.comparison {
+                    Comparison::Equal => actual == *expected,
+                    Comparison::NotEqual => actual != *expected,
+                    Comparison::Less => actual < *expected,
+                    Comparison::LessOrEqual => actual <= *expected,
+                    Comparison::Greater => actual > *expected,
+                    Comparison::GreaterOrEqual => actual >= *expected,
+                    Comparison::StartsWith => false,
+                }
+            }
+            Value::Text(expected) => {
+                let actual = match self.field {
+                    Field::Tenant => input.tenant_id.clone(),
+                    Field::Client => input.client_id.clone(),
+                    Field::Document => input.document.id.clone(),
+                    Field::Character => input.character.map(String::from).unwrap_or_default(),
+                    Field::Source => input.source.name().to_string(),
+                    _ => input.document.exists.to_string(),
+                };
+                match self.comparison {
+                    Comparison::Equal => actual == *expected,
+                    Comparison::NotEqual => actual != *expected,
+                    Comparison::StartsWith => actual.starts_with(expected.as_str()),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    effect: Effect,
+    /// Operation kind the rule applies to; `None` for all
+    kind: Option<OperationKind>,
+    conditions: Vec<Condition>,
+    reason: Option<String>,
+}
+
+impl Rule {
+    fn matches(&self, input: &PolicyInput) -> bool {
+        self.kind.is_none_or(|kind| kind == input.kind)
+            && self.conditions.iter().all(|condition| condition.matches(input))
+    }
+}
+
+/// Policy defined by a list of allow and deny rules
+#[derive(Debug, Clone, Default)]
+pub struct RulePolicy {
+    rules: Vec<Rule>,
+}
+
+impl RulePolicy {
+    /// Parse a rule set
+    pub fn parse(source: &str) -> Result<Self, PolicyError> {
+        let rules = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(index, line)| {
+                parse_rule(line).map_err(|message| PolicyError::InvalidRule { line: index + 1, message })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Decide on an operation
+    pub fn decide(&self, input: &PolicyInput) -> Decision {
+        match self.rules.iter().find(|rule| rule.matches(input)) {
+            Some(Rule { effect: Effect::Deny, reason, .. }) => {
+                Decision::deny(reason.as_deref().unwrap_or(DEFAULT_REASON))
+            }
+            _ => Decision::Allow,
+        }
+    }
+}
+
+impl FromStr for RulePolicy {
+    type Err = PolicyError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl PolicyEngine for RulePolicy {
+    fn evaluate<'a>(&'a self, input: &'a PolicyInput) -> BoxFuture<'a, Decision> {
+        Box::pin(async move { self.decide(input) })
+    }
+}
+
+/// Parse a single rule line
+fn parse_rule(line: &str) -> Result<Rule, String> {
+    let tokens = tokenize(line)?;
+    let mut tokens = tokens.into_iter().peekable();
+
+    let effect = match tokens.next().as_deref() {
+        Some("allow") => Effect::Allow,
+        Some("deny") => Effect::Deny,
+        other => return Err(format!("expected allow or deny, found {:?}", other.unwrap_or(""))),
+    };
+    let kind = match tokens.next().as_deref() {
+        Some("insert") => Some(OperationKind::Insert),
+        Some("delete") => Some(OperationKind::Delete),
+        Some("*") | None => None,
+        Some(other) => return Err(format!("expected insert, delete or *, found {:?}", other)),
+    };
+
+    let mut rule = Rule {
+        effect,
+        kind,
+        conditions: Vec::new(),
+        reason: None,
+    };
+    if tokens.peek().map(String::as_str) == Some("when") {
+        tokens.next();
+        loop {
+            rule.conditions.push(parse_condition(&mut tokens)?);
+            if tokens.peek().map(String::as_str) != Some("and") {
+                break;
+            }
+            tokens.next();
+        }
+    }
+    if tokens.peek().map(String::as_str) == Some("reason") {
+        tokens.next();
+        let reason = tokens.next().ok_or("reason needs a text")?;
+        rule.reason = Some(unquote(&reason).ok_or("reason must be quoted")?);
+    }
+    match tokens.next() {
+        Some(extra) => Err(format!("unexpected {:?}", extra)),
+        None => Ok(rule),
+    }
+}
+
+/// Parse `FIELD OP VALUE`
+fn parse_condition(tokens: &mut impl Iterator<Item = String>) -> Result<Condition, String> {
+    let field = match tokens.next().as_deref() {
+        Some("tenant") => Field::Tenant,
+        Some("client") => Field::Client,
+        Some("document") => Field::Document,
+        Some("character") => Field::Character,
+        Some("exists") => Field::Exists,
+        Some("source") => Field::Source,
+        Some("length") => Field::Length,
+        Some("version") => Field::Version,
+        other => return Err(format!("unknown field {:?}", other.unwrap_or(""))),
+    };
+    let comparison = match tokens.next().as_deref() {
+        Some("==") => Comparison::Equal,
+        Some("!=") => Comparison::NotEqual,
+        Some("^=") => Comparison::StartsWith,
+        Some("<") => Comparison::Less,
+        Some("<=") => Comparison::LessOrEqual,
+        Some(">") => Comparison::Greater,
+        Some(">=") => Comparison::GreaterOrEqual,
+        other => return Err(format!("unknown comparison {:?}", other.unwrap_or(""))),
+    };
+    let value = tokens.next().ok_or("condition needs a value")?;
+
+    let value = if field.is_numeric() {
+        if comparison == Comparison::StartsWith {
+            return Err("^= only applies to text fields".to_string());
+        }
+        Value::Number(value.parse().map_err(|_| format!("expected a number, found {:?}", value))?)
+    } else {
+        if !matches!(comparison, Comparison::Equal | Comparison::NotEqual | Comparison::StartsWith) {
+            return Err("text fields only support ==, != and ^=".to_string());
+        }
+        Value::Text(unquote(&value).ok_or_else(|| format!("expected a quoted string, found {:?}", value))?)
+    };
+    Ok(Condition { field, comparison, value })
+}
+
+/// Split a line into words and quoted strings, which keep their quotes
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            let mut token = String::from(chars.next().unwrap_or('"'));
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            token.push('"');
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+/// Strip the quotes of a quoted token
+fn unquote(token: &str) -> Option<String> {
+    token
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .map(str::to_string)
+}
@@ -0,0 +1,99 @@
+/*
+ * File: src/presence/cursors.rs
+ * Purpose: Throttling of relayed cursor updates
+ *
+ * Editors send a cursor update on every keystroke and mouse drag, far more
+ * often than anyone can watch a remote caret move. The throttle relays a
+ * client's first update in a document at once, then at most one update per
+ * `CURSOR_INTERVAL`: updates arriving sooner are coalesced, the newest
+ * replacing the ones before it, and flushed when the interval is up. The
+ * latest position always arrives; the positions in between may not.
+ */
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Shortest time between two relayed cursor updates of a client in a
+/// document
+pub const CURSOR_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A client in a document within a tenant
+type CursorKey = (String, String, String);
+
+/// What to do with a cursor update
+#[derive(Debug, PartialEq)]
+pub enum Throttled<T> {
+    /// Relay the update now
+    Relay(T),
+    /// The update is held; flush it after the delay
+    Flush(Duration),
+    /// The update replaced a held one whose flush is already due
+    Coalesced,
+}
+
+/// Last relay and held update of a client in a document
+#[derive(Debug)]
+struct Slot<T> {
+    last_relayed: Instant,
+    held: Option<T>,
+}
+
+/// Coalesces the cursor updates of each client in each document
+#[derive(Debug)]
+pub struct CursorThrottle<T> {
+    interval: Duration,
+    slots: HashMap<CursorKey, Slot<T>>,
+}
+
+impl<T> Default for CursorThrottle<T> {
+    fn default() -> Self {
+        Self::new(CURSOR_INTERVAL)
+    }
+}
+
+impl<T> CursorThrottle<T> {
+    /// Create a throttle relaying at most one update per interval
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Decide whether a client's cursor update is relayed now or held
+    pub fn observe(&mut self, tenant_id: &str, document_id: &str, client_id: &str, update: T, now: Instant) -> Throttled<T> {
+        let key = (tenant_id.to_string(), document_id.to_string(), client_id.to_string());
+        let Some(slot) = self.slots.get_mut(&key) else {
+            self.slots.insert(key, Slot { last_relayed: now, held: None });
+            return Throttled::Relay(update);
+        };
+
+        let elapsed = now.saturating_duration_since(slot.last_relayed);
+        if slot.held.is_some() {
+            slot.held = Some(update);
+            return Throttled::Coalesced;
+        }
+        if elapsed >= self.interval {
+            slot.last_relayed = now;
+            return Throttled::Relay(update);
+        }
+        slot.held = Some(update);
+        Throttled::Flush(self.interval - elapsed)
+    }
+
+    /// Take a client's held update once its flush is due
+    pub fn flush(&mut self, tenant_id: &str, document_id: &str, client_id: &str, now: Instant) -> Option<T> {
+        let key = (tenant_id.to_string(), document_id.to_string(), client_id.to_string());
+        let slot = self.slots.get_mut(&key)?;
+        let held = slot.held.take()?;
+        slot.last_relayed = now;
+        Some(held)
+    }
+
+    /// Forget a disconnected client, dropping its held updates
+    pub fn forget_client(&mut self, client_id: &str) {
+        self.slots.retain(|(_, _, client), _| client != client_id);
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * File: src/presence/mod.rs
+ * Purpose: Module organization for presence
+ *
+ * This module contains:
+ * - tracker: Which clients are active in each document
+ * - cursors: Throttling of relayed cursor updates
+ * - view: What a client knows of a document's presence
+ *
+ * A client becomes present in a document when it joins it (by creating,
+ * editing or syncing it, or naming it in the connection URL) and stops
+ * being present when it disconnects or has sent nothing for
+ * `idle_timeout`; its next edit or sync brings it back.
+ *
+ * Presence is synced differentially: a client joining a document gets a
+ * full snapshot of who is there, then diffs of the joins, profile changes
+ * and departures. Every change of a document's presence increments its
+ * sequence number, so a client seeing a gap in the sequence missed a diff
+ * and asks for a fresh snapshot with `getPresence`.
+ *
+ * Cursors and selections are relayed, not tracked: the server passes each
+ * client's latest cursor on to the document's other clients, throttled,
+ * and clients drop the cursor of a client once it departs.
+ *
+ * Both are ephemeral: a cursor or presence update still queued for a slow
+ * connection once it is stale, or finding the connection's queue full, is
+ * dropped rather than delivered. Document operations never are. A dropped
+ * presence update shows as a gap, so the client asks for a snapshot.
+ */
+
+pub mod cursors;
+pub mod tracker;
+pub mod view;
+
+pub use cursors::{CursorThrottle, Throttled, CURSOR_INTERVAL};
+pub use tracker::PresenceTracker;
+pub use view::PresenceView;
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Longest display name, in characters
+pub const MAX_NAME_CHARS: usize = 64;
+
+/// Colors given to clients that did not pick one
+const PALETTE: [&str; 8] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324",
+];
+
+/// Presence settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceConfig {
+    /// How long a client may send nothing before it is no longer present;
+    /// zero keeps clients present until they disconnect
+    pub idle_timeout: Duration,
+    /// How long a relayed cursor update stays worth delivering, unless
+    /// its sender says otherwise; zero never drops them
+    pub cursor_staleness: Duration,
+    /// How long a presence update stays worth delivering; zero never
+    /// drops them
+    pub update_staleness: Duration,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(300),
+            cursor_staleness: Duration::from_secs(1),
+            update_staleness: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A client present in a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Presence {
+    pub client_id: String,
+    /// Display name; the client ID until the client sets one
+    pub name: String,
+    /// Display color, as `#rrggbb`
+    pub color: String,
+    /// When the client became present in the document
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Why a client stopped being present
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepartureReason {
+    /// The client disconnected
+    Left,
+    /// The client sent nothing for the idle timeout
+    Timeout,
+}
+
+/// A client that stopped being present
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Departure {
+    pub client_id: String,
+    pub reason: DepartureReason,
+}
+
+/// A change of who is present in a document, or with `full` all of it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresenceUpdate {
+    pub document_id: String,
+    /// Sequence number of the document's presence after the update
+    pub seq: u64,
+    /// Whether `present` lists every present client, replacing what the
+    /// recipient knew
+    #[serde(default)]
+    pub full: bool,
+    /// Clients that joined or changed their profile, or with `full` every
+    /// present client
+    #[serde(default)]
+    pub present: Vec<Presence>,
+    /// Clients no longer present
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub departed: Vec<Departure>,
+}
+
+/// Pick a client's default color from its ID
+pub fn default_color(client_id: &str) -> &'static str {
+    let hash = client_id.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    PALETTE[hash as usize % PALETTE.len()]
+}
+
+/// Check that a color is written as `#rrggbb`
+pub fn is_valid_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
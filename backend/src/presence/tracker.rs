@@ -0,0 +1,246 @@
+/*
+ * File: src/presence/tracker.rs
+ * Purpose: Which clients are active in each document
+ *
+ * The tracker keeps, per document:
+ * - the present clients, with their profile and last activity
+ * - a sequence number counting the changes of its presence
+ *
+ * and, per client, the documents it is present in and the name and color
+ * it set. Every change returns the `PresenceUpdate` diff to send to the
+ * document's other clients. Documents are keyed by tenant and ID; a
+ * document is dropped, and its sequence restarts, once nobody is present.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use chrono::{DateTime, Utc};
+
+use crate::presence::{default_color, Departure, DepartureReason, Presence, PresenceUpdate};
+
+/// A document within a tenant
+type DocumentKey = (String, String);
+
+/// A present client and when it was last active
+#[derive(Debug)]
+struct Member {
+    presence: Presence,
+    last_active: DateTime<Utc>,
+}
+
+/// Who is present in one document
+#[derive(Debug, Default)]
+struct DocumentPresence {
+    seq: u64,
+    members: HashMap<String, Member>,
+}
+
+/// Name and color a client set
+#[derive(Debug, Default)]
+struct Profile {
+    name: Option<String>,
+    color: Option<String>,
+}
+
+/// Tracks which clients are active in each document
+#[derive(Debug, Default)]
+pub struct PresenceTracker {
+    documents: HashMap<DocumentKey, DocumentPresence>,
+    /// Documents each client is present in
+    by_client: HashMap<String, HashSet<DocumentKey>>,
+    profiles: HashMap<String, Profile>,
+}
+
+impl PresenceTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark a client active in a document, returning the diff announcing
+    /// it if it was not present yet
+    pub fn join(&mut self, tenant_id: &str, document_id: &str, client_id: &str, now: DateTime<Utc>) -> Option<PresenceUpdate> {
+        let key = (tenant_id.to_string(), document_id.to_string());
+        let document = self.documents.entry(key.clone()).or_default();
+        if let Some(member) = document.members.get_mut(client_id) {
+            member.last_active = now;
+            return None;
+        }
+
+        let profile = self.profiles.get(client_id);
+        let presence = Presence {
+            client_id: client_id.to_string(),
+            name: profile.and_then(|profile| profile.name.clone()).unwrap_or_else(|| client_id.to_string()),
+            color: profile
+                .and_then(|profile| profile.color.clone())
+                .unwrap_or_else(|| default_color(client_id).to_string()),
+            joined_at: now,
+        };
+        document.members.insert(client_id.to_string(), Member { presence: presence.clone(), last_active: now });
+        document.seq += 1;
+        self.by_client.entry(client_id.to_string()).or_default().insert(key);
+        Some(PresenceUpdate {
+            document_id: document_id.to_string(),
+            seq: document.seq,
+            full: false,
+            present: vec![presence],
+            departed: Vec::new(),
+        })
+    }
+
+    /// Mark a client active in every document it is present in
+    pub fn touch(&mut self, client_id: &str, now: DateTime<Utc>) {
+        for key in self.by_client.get(client_id).into_iter().flatten() {
+            if let Some(member) = self.documents.get_mut(key).and_then(|document| document.members.get_mut(client_id)) {
+                member.last_active = now;
+            }
+        }
+    }
+
+    /// Set a client's name and color, keeping what is not given, and
+    /// return the diffs for the documents it is present in, by tenant
+    pub fn set_profile(&mut self, client_id: &str, name: Option<String>, color: Option<String>) -> Vec<(String, PresenceUpdate)> {
+        let profile = self.profiles.entry(client_id.to_string()).or_default();
+        if name.is_some() {
+            profile.name = name;
+        }
+        if color.is_some() {
+            profile.color = color;
+        }
+
+        let mut updates = Vec::new();
+        for key in self.by_client.get(client_id).into_iter().flatten() {
+            let Some(document) = self.documents.get_mut(key) else {
+                continue;
+            };
+            let Some(member) = document.members.get_mut(client_id) else {
+                continue;
+            };
+            if let Some(name) = &profile.name {
+                member.presence.name = name.clone();
+            }
+            if let Some(color) = &profile.color {
+                member.presence.color = color.clone();
+            }
+            document.seq += 1;
+            updates.push((key.0.clone(), PresenceUpdate {
+                document_id: key.1.clone(),
+                seq: document.seq,
+                full: false,
+                present: vec![member.presence.clone()],
+                departed: Vec::new(),
+            }));
+        }
+        updates
+    }
+
+    /// Forget a disconnected client, returning the diffs for the documents
+    /// it was present in, by tenant
+    pub fn remove_client(&mut self, client_id: &str) -> Vec<(String, PresenceUpdate)> {
+        self.profiles.remove(client_id);
+        let keys = self.by_client.remove(client_id).unwrap_or_default();
+        let mut updates = Vec::new();
+        for key in keys {
+            if let Some(update) = self.depart(&key, vec![client_id.to_string()], DepartureReason::Left) {
+                updates.push((key.0, update));
+            }
+        }
+        updates
+    }
+
+    /// Remove clients inactive for longer than the idle timeout, returning
+    /// one diff per document, by tenant. A zero timeout removes nobody.
+    pub fn expire(&mut self, idle_timeout: Duration, now: DateTime<Utc>) -> Vec<(String, PresenceUpdate)> {
+        let Ok(idle_timeout) = chrono::Duration::from_std(idle_timeout) else {
+            return Vec::new();
+        };
+        if idle_timeout.is_zero() {
+            return Vec::new();
+        }
+
+        let idle: Vec<(DocumentKey, Vec<String>)> = self.documents
+            .iter()
+            .filter_map(|(key, document)| {
+                let mut clients: Vec<String> = document.members
+                    .iter()
+                    .filter(|(_, member)| now - member.last_active > idle_timeout)
+                    .map(|(client_id, _)| client_id.clone())
+                    .collect();
+                clients.sort();
+                (!clients.is_empty()).then(|| (key.clone(), clients))
+            })
+            .collect();
+
+        let mut updates = Vec::new();
+        for (key, clients) in idle {
+            if let Some(update) = self.depart(&key, clients, DepartureReason::Timeout) {
+                updates.push((key.0, update));
+            }
+        }
+        updates
+    }
+
+    /// Remove clients from one document, such as those a new password
+    /// keeps out, returning the diff announcing it
+    pub fn leave(&mut self, tenant_id: &str, document_id: &str, clients: Vec<String>) -> Option<PresenceUpdate> {
+        let key = (tenant_id.to_string(), document_id.to_string());
+        self.depart(&key, clients, DepartureReason::Left)
+    }
+
+    /// Get everyone present in a document, as a full update
+    pub fn snapshot(&self, tenant_id: &str, document_id: &str) -> PresenceUpdate {
+        let document = self.documents.get(&(tenant_id.to_string(), document_id.to_string()));
+        PresenceUpdate {
+            document_id: document_id.to_string(),
+            seq: document.map_or(0, |document| document.seq),
+            full: true,
+            present: self.present(tenant_id, document_id),
+            departed: Vec::new(),
+        }
+    }
+
+    /// Get the clients present in a document, in the order they joined
+    pub fn present(&self, tenant_id: &str, document_id: &str) -> Vec<Presence> {
+        let Some(document) = self.documents.get(&(tenant_id.to_string(), document_id.to_string())) else {
+            return Vec::new();
+        };
+        let mut present: Vec<Presence> = document.members.values().map(|member| member.presence.clone()).collect();
+        present.sort_by(|a, b| a.joined_at.cmp(&b.joined_at).then_with(|| a.client_id.cmp(&b.client_id)));
+        present
+    }
+
+    /// Remove clients from a document, dropping it once it is empty
+    fn depart(&mut self, key: &DocumentKey, clients: Vec<String>, reason: DepartureReason) -> Option<PresenceUpdate> {
+        for client_id in &clients {
+            if let Some(keys) = self.by_client.get_mut(client_id) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.by_client.remove(client_id);
+                }
+            }
+        }
+        let document = self.documents.get_mut(key)?;
+        let departed: Vec<Departure> = clients
+            .into_iter()
+            .filter(|client_id| document.members.remove(client_id).is_some())
+            .map(|client_id| Departure { client_id, reason })
+            .collect();
+        if departed.is_empty() {
+            return None;
+        }
+        document.seq += 1;
+        let update = PresenceUpdate {
+            document_id: key.1.clone(),
+            seq: document.seq,
+            full: false,
+            present: Vec::new(),
+            departed,
+        };
+        if document.members.is_empty() {
+            self.documents.remove(key);
+        }
+        Some(update)
+    }
+}
@@ -0,0 +1,69 @@
+/*
+ * File: src/presence/view.rs
+ * Purpose: What a client knows of a document's presence
+ *
+ * Clients rebuild a document's presence from the updates the server sends
+ * them: a snapshot on joining, then diffs. Each update carries the
+ * sequence number of the document's presence after it, so a diff that
+ * doesn't follow the last update applied means one went missing. The
+ * view then stops applying diffs and reports that it needs a fresh
+ * snapshot, which the client asks for with `getPresence`.
+ */
+
+use std::collections::BTreeMap;
+
+use crate::presence::{Presence, PresenceUpdate};
+
+/// A client's copy of the presence of one document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresenceView {
+    /// Sequence number of the last update applied; None until a snapshot
+    /// arrives, and after a gap
+    seq: Option<u64>,
+    present: BTreeMap<String, Presence>,
+}
+
+impl PresenceView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an update, returning false if it shows the view missed one
+    /// and needs a fresh snapshot. Diffs older than the view are skipped.
+    pub fn apply(&mut self, update: &PresenceUpdate) -> bool {
+        if update.full {
+            self.present = update.present.iter().map(|presence| (presence.client_id.clone(), presence.clone())).collect();
+            self.seq = Some(update.seq);
+            return true;
+        }
+        match self.seq {
+            Some(seq) if update.seq <= seq => true,
+            Some(seq) if update.seq == seq + 1 => {
+                for departure in &update.departed {
+                    self.present.remove(&departure.client_id);
+                }
+                for presence in &update.present {
+                    self.present.insert(presence.client_id.clone(), presence.clone());
+                }
+                self.seq = Some(update.seq);
+                true
+            }
+            _ => {
+                self.seq = None;
+                false
+            }
+        }
+    }
+
+    /// Whether the view is waiting on a snapshot
+    pub fn is_stale(&self) -> bool {
+        self.seq.is_none()
+    }
+
+    /// Get the present clients, in the order they joined
+    pub fn present(&self) -> Vec<Presence> {
+        let mut present: Vec<Presence> = self.present.values().cloned().collect();
+        present.sort_by(|a, b| a.joined_at.cmp(&b.joined_at).then_with(|| a.client_id.cmp(&b.client_id)));
+        present
+    }
+}
@@ -0,0 +1,424 @@
+/*
+ * File: src/security/encryption.rs
+ * Purpose: Per-document encryption at rest with key rotation
+ *
+ * Responsibilities:
+ * - Encrypt and decrypt persisted records (snapshots, WAL entries) with AES-GCM
+ * - Generate a data key per document, wrapped by a master key
+ * - Load master keys from the environment or an external KMS via KeyProvider
+ * - Rewrap data keys when the master key is rotated
+ *
+ * Records are bound to their document through the AES-GCM associated data,
+ * so a ciphertext copied into another document's storage fails to decrypt.
+ *
+ * `SqliteStore::open_encrypted` encrypts what it stores with it and keeps
+ * the wrapped data keys in a table of its own; `spawn_key_rotation` asks
+ * the server's store to rewrap them every `StorageConfig::key_rotation`.
+ *
+ * Tenants whose clients encrypt content end to end declare it with
+ * `EncryptionMode::EndToEnd`; the server then never hands out their
+ * documents in plaintext form.
+ */
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use crate::storage::DocumentStore;
+
+/// Environment variable holding the master keys as `id:hex[,id:hex...]`.
+/// The first entry is the active key; the others are kept for unwrapping.
+pub const MASTER_KEYS_ENV: &str = "COEDIT_MASTER_KEYS";
+
+/// Encryption-specific errors
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("Master key {0} not found")]
+    MasterKeyNotFound(String),
+    #[error("Data key version {1} not found for document {0}")]
+    DataKeyNotFound(String, u32),
+    #[error("Invalid key material: {0}")]
+    InvalidKey(String),
+    #[error("Encryption failed")]
+    EncryptionFailed,
+    #[error("Decryption failed")]
+    DecryptionFailed,
+}
+
+/// Who can read a tenant's document content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionMode {
+    /// The server sees plaintext and may encrypt it at rest
+    #[default]
+    AtRest,
+    /// Clients encrypt content before sending it; the server only relays it
+    EndToEnd,
+}
+
+impl EncryptionMode {
+    /// Check whether content is encrypted end to end
+    pub fn is_end_to_end(self) -> bool {
+        self == EncryptionMode::EndToEnd
+    }
+}
+
+/// A 256-bit key-encryption key identified by an ID
+#[derive(Clone)]
+pub struct MasterKey {
+    id: String,
+    key: [u8; 32],
+}
+
+impl MasterKey {
+    /// Create a master key from raw key bytes
+    pub fn new(id: String, key: [u8; 32]) -> Self {
+        Self { id, key }
+    }
+
+    /// Create a master key from a hex-encoded 32-byte value
+    pub fn from_hex(id: String, encoded: &str) -> Result<Self, EncryptionError> {
+        let bytes = hex::decode(encoded.trim())
+            .map_err(|e| EncryptionError::InvalidKey(format!("{}: {}", id, e)))?;
+        let key: [u8; 32] = bytes.try_into()
+            .map_err(|_| EncryptionError::InvalidKey(format!("{}: expected 32 bytes", id)))?;
+        Ok(Self::new(id, key))
+    }
+
+    /// Generate a random master key, mainly useful for tests and local setups
+    pub fn generate(id: String) -> Self {
+        Self::new(id, Aes256Gcm::generate_key(OsRng).into())
+    }
+
+    /// Get the key identifier
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(crate) fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print key material
+        f.debug_struct("MasterKey").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+/// Source of master keys (environment, KMS, ...)
+pub trait KeyProvider: Send + Sync {
+    /// The key new data keys should be wrapped with
+    fn active_key(&self) -> Result<MasterKey, EncryptionError>;
+    /// Look up a (possibly retired) key by ID for unwrapping
+    fn key(&self, id: &str) -> Result<MasterKey, EncryptionError>;
+}
+
+/// Key provider backed by a fixed set of keys held in memory
+pub struct StaticKeyProvider {
+    /// Known keys, the first one being active
+    keys: RwLock<Vec<MasterKey>>,
+}
+
+impl StaticKeyProvider {
+    /// Create a provider with a single active key
+    pub fn new(active: MasterKey) -> Self {
+        Self {
+            keys: RwLock::new(vec![active]),
+        }
+    }
+
+    /// Load keys from `COEDIT_MASTER_KEYS`
+    pub fn from_env() -> Result<Self, EncryptionError> {
+        let value = std::env::var(MASTER_KEYS_ENV)
+            .map_err(|_| EncryptionError::InvalidKey(format!("{} is not set", MASTER_KEYS_ENV)))?;
+        Self::parse(&value)
+    }
+
+    /// Parse keys in the `id:hex[,id:hex...]` format
+    pub fn parse(value: &str) -> Result<Self, EncryptionError> {
+        let keys = value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (id, encoded) = entry.trim().split_once(':')
+                    .ok_or_else(|| EncryptionError::InvalidKey("expected id:hex".to_string()))?;
+                MasterKey::from_hex(id.to_string(), encoded)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if keys.is_empty() {
+            return Err(EncryptionError::InvalidKey("no master keys configured".to_string()));
+        }
+        Ok(Self {
+            keys: RwLock::new(keys),
+        })
+    }
+
+    /// Make `key` the active key; previous keys stay available for unwrapping
+    pub fn rotate(&self, key: MasterKey) {
+        let mut keys = self.keys.write();
+        keys.retain(|k| k.id != key.id);
+        keys.insert(0, key);
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn active_key(&self) -> Result<MasterKey, EncryptionError> {
+        self.keys.read()
+            .first()
+            .cloned()
+            .ok_or_else(|| EncryptionError::MasterKeyNotFound("<active>".to_string()))
+    }
+
+    fn key(&self, id: &str) -> Result<MasterKey, EncryptionError> {
+        self.keys.read()
+            .iter()
+            .find(|k| k.id == id)
+            .cloned()
+            .ok_or_else(|| EncryptionError::MasterKeyNotFound(id.to_string()))
+    }
+}
+
+/// A document data key encrypted under a master key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// Data key version, referenced by records encrypted with it
+    pub version: u32,
+    /// ID of the master key that wraps this data key
+    pub master_key_id: String,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// An encrypted snapshot or WAL record
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EncryptedRecord {
+    /// Version of the document data key used for this record
+    pub key_version: u32,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// Prefix of encoded records, telling them apart from plaintext
+const RECORD_PREFIX: &str = "aes-gcm:";
+
+impl EncryptedRecord {
+    /// Encode the record as text, `aes-gcm:<version>:<nonce>:<ciphertext>`
+    /// with hex-encoded bytes
+    pub fn encode(&self) -> String {
+        format!("{}{}:{}:{}", RECORD_PREFIX, self.key_version, hex::encode(&self.nonce), hex::encode(&self.ciphertext))
+    }
+
+    /// Decode a record encoded with `encode`, or None if the text isn't one
+    pub fn decode(text: &str) -> Option<Self> {
+        let mut parts = text.strip_prefix(RECORD_PREFIX)?.splitn(3, ':');
+        let key_version = parts.next()?.parse().ok()?;
+        let nonce = hex::decode(parts.next()?).ok()?;
+        let ciphertext = hex::decode(parts.next()?).ok()?;
+        Some(Self { key_version, nonce, ciphertext })
+    }
+}
+
+/// Manages per-document data keys and encrypts records with them
+pub struct DocumentEncryption {
+    provider: Arc<dyn KeyProvider>,
+    /// Wrapped data keys per document, oldest version first
+    keys: RwLock<HashMap<String, Vec<WrappedKey>>>,
+}
+
+impl DocumentEncryption {
+    /// Create a new encryption manager using the given master key source
+    pub fn new(provider: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            provider,
+            keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Encrypt a record for a document, creating its data key on first use
+    pub fn encrypt(&self, document_id: &str, plaintext: &[u8]) -> Result<EncryptedRecord, EncryptionError> {
+        let wrapped = match self.current_key(document_id) {
+            Some(wrapped) => wrapped,
+            None => self.create_data_key(document_id)?,
+        };
+        let cipher = self.unwrap_key(document_id, &wrapped)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = associated_data(document_id, wrapped.version);
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+        Ok(EncryptedRecord {
+            key_version: wrapped.version,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    /// Decrypt a record previously produced by `encrypt` for the same document
+    pub fn decrypt(&self, document_id: &str, record: &EncryptedRecord) -> Result<Vec<u8>, EncryptionError> {
+        let wrapped = self.keys.read()
+            .get(document_id)
+            .and_then(|versions| versions.iter().find(|k| k.version == record.key_version).cloned())
+            .ok_or_else(|| EncryptionError::DataKeyNotFound(document_id.to_string(), record.key_version))?;
+        if record.nonce.len() != 12 {
+            return Err(EncryptionError::DecryptionFailed);
+        }
+
+        let cipher = self.unwrap_key(document_id, &wrapped)?;
+        let aad = associated_data(document_id, record.key_version);
+        cipher
+            .decrypt(Nonce::from_slice(&record.nonce), Payload { msg: &record.ciphertext, aad: &aad })
+            .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+
+    /// Start a new data key version for a document.
+    /// Older versions are kept so existing records remain readable.
+    pub fn rotate_document_key(&self, document_id: &str) -> Result<u32, EncryptionError> {
+        Ok(self.create_data_key(document_id)?.version)
+    }
+
+    /// Rewrap every data key not wrapped by the active master key.
+    /// Returns the number of keys rewrapped.
+    pub fn rewrap_all(&self) -> Result<usize, EncryptionError> {
+        let active = self.provider.active_key()?;
+        let stale: Vec<(String, WrappedKey)> = self.keys.read()
+            .iter()
+            .flat_map(|(doc, versions)| {
+                versions.iter()
+                    .filter(|k| k.master_key_id != active.id)
+                    .map(move |k| (doc.clone(), k.clone()))
+            })
+            .collect();
+
+        let mut rewrapped = 0;
+        for (document_id, wrapped) in stale {
+            let old = self.provider.key(&wrapped.master_key_id)?;
+            let data_key = unwrap_with(&old, &document_id, &wrapped)?;
+            let new = wrap_with(&active, &document_id, wrapped.version, &data_key)?;
+
+            let mut keys = self.keys.write();
+            if let Some(slot) = keys.get_mut(&document_id)
+                .and_then(|versions| versions.iter_mut().find(|k| k.version == wrapped.version))
+            {
+                *slot = new;
+                rewrapped += 1;
+            }
+        }
+        Ok(rewrapped)
+    }
+
+    /// Get the wrapped data keys of a document so they can be persisted
+    pub fn wrapped_keys(&self, document_id: &str) -> Vec<WrappedKey> {
+        self.keys.read().get(document_id).cloned().unwrap_or_default()
+    }
+
+    /// Restore previously persisted wrapped data keys for a document
+    pub fn load_wrapped_keys(&self, document_id: &str, keys: Vec<WrappedKey>) {
+        self.keys.write().insert(document_id.to_string(), keys);
+    }
+
+    /// Get the IDs of the documents holding data keys
+    pub fn documents(&self) -> Vec<String> {
+        let mut documents: Vec<String> = self.keys.read().keys().cloned().collect();
+        documents.sort();
+        documents
+    }
+
+    /// Get the version of a document's current data key, if it has one
+    pub fn current_version(&self, document_id: &str) -> Option<u32> {
+        self.current_key(document_id).map(|wrapped| wrapped.version)
+    }
+
+    /// Drop all key material for a document (crypto-shredding its records)
+    pub fn forget_document(&self, document_id: &str) {
+        self.keys.write().remove(document_id);
+    }
+
+    fn current_key(&self, document_id: &str) -> Option<WrappedKey> {
+        self.keys.read().get(document_id).and_then(|versions| versions.last().cloned())
+    }
+
+    fn create_data_key(&self, document_id: &str) -> Result<WrappedKey, EncryptionError> {
+        let active = self.provider.active_key()?;
+        let data_key: [u8; 32] = Aes256Gcm::generate_key(OsRng).into();
+
+        let mut keys = self.keys.write();
+        let versions = keys.entry(document_id.to_string()).or_default();
+        let version = versions.last().map(|k| k.version + 1).unwrap_or(1);
+        let wrapped = wrap_with(&active, document_id, version, &data_key)?;
+        versions.push(wrapped.clone());
+        Ok(wrapped)
+    }
+
+    fn unwrap_key(&self, document_id: &str, wrapped: &WrappedKey) -> Result<Aes256Gcm, EncryptionError> {
+        let master = self.provider.key(&wrapped.master_key_id)?;
+        let data_key = unwrap_with(&master, document_id, wrapped)?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key)))
+    }
+}
+
+impl fmt::Debug for DocumentEncryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DocumentEncryption").field("documents", &self.keys.read().len()).finish_non_exhaustive()
+    }
+}
+
+/// Spawn the maintenance job that rewraps a store's data keys after a
+/// master key rotation, every interval until the server stops
+pub fn spawn_key_rotation(store: Arc<dyn DocumentStore>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match store.rotate_keys() {
+                Ok(0) => {}
+                Ok(count) => log::info!("Rewrapped {} document keys", count),
+                Err(e) => log::error!("Key rotation failed: {}", e),
+            }
+        }
+    })
+}
+
+fn associated_data(document_id: &str, version: u32) -> Vec<u8> {
+    format!("{}:{}", document_id, version).into_bytes()
+}
+
+fn wrap_with(master: &MasterKey, document_id: &str, version: u32, data_key: &[u8; 32]) -> Result<WrappedKey, EncryptionError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = associated_data(document_id, version);
+    let ciphertext = master.cipher()
+        .encrypt(&nonce, Payload { msg: data_key, aad: &aad })
+        .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+    Ok(WrappedKey {
+        version,
+        master_key_id: master.id.clone(),
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+fn unwrap_with(master: &MasterKey, document_id: &str, wrapped: &WrappedKey) -> Result<[u8; 32], EncryptionError> {
+    if wrapped.nonce.len() != 12 {
+        return Err(EncryptionError::DecryptionFailed);
+    }
+    let aad = associated_data(document_id, wrapped.version);
+    let plaintext = master.cipher()
+        .decrypt(Nonce::from_slice(&wrapped.nonce), Payload { msg: &wrapped.ciphertext, aad: &aad })
+        .map_err(|_| EncryptionError::DecryptionFailed)?;
+    plaintext.try_into().map_err(|_| EncryptionError::DecryptionFailed)
+}
@@ -0,0 +1,23 @@
+/*
+ * File: src/security/mod.rs
+ * Purpose: Module organization for security features
+ *
+ * This module contains:
+ * - encryption: Per-document encryption at rest with key rotation
+ * - passwords: Salted, slow hashes of document passwords
+ * - redaction: Redaction of sensitive content in logs
+ * - tokens: Self-contained encrypted tokens for sessions and sharing
+ */
+
+pub mod encryption;
+pub mod passwords;
+pub mod redaction;
+pub mod tokens;
+
+pub use encryption::{
+    spawn_key_rotation, DocumentEncryption, EncryptedRecord, EncryptionError, EncryptionMode, KeyProvider, MasterKey,
+    StaticKeyProvider, MASTER_KEYS_ENV,
+};
+pub use passwords::{check_cost, hash_password, secrets_match, verify_password, PasswordHashError};
+pub use redaction::{RedactionConfig, Redactor};
+pub use tokens::{SessionClaims, ShareClaims, TokenConfig, TokenError, TokenKind, TokenSealer};
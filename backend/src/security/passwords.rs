@@ -0,0 +1,85 @@
+/*
+ * File: src/security/passwords.rs
+ * Purpose: Salted, slow hashes of document passwords
+ *
+ * Document passwords are stored only as hashes, in the PHC string format:
+ *
+ *   $argon2id$v=19$m=19456,t=2,p=1$<salt base64>$<hash base64>
+ *
+ * Hashes are argon2id over a random salt, made and checked by the `argon2`
+ * crate. The algorithm and its costs travel with each hash, so raising the
+ * costs leaves existing hashes verifying. Hashing is deliberately slow;
+ * call it off the async runtime.
+ *
+ * Secrets compared as they are, such as the admin token and tenant access
+ * keys, are compared by their SHA-256 digests in constant time, so neither
+ * their content nor their length leaks through timing.
+ */
+
+use aes_gcm::aead::OsRng;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Memory of new hashes in KiB, as recommended for argon2id
+pub const DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+
+/// Passes over the memory of new hashes, as recommended for argon2id
+pub const DEFAULT_ITERATIONS: u32 = 2;
+
+/// Password hashing errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PasswordHashError {
+    #[error("Passwords can't be empty")]
+    Empty,
+    #[error("Invalid password hashing cost: {0}")]
+    Cost(String),
+    #[error("Unsupported password hash: {0}")]
+    Unsupported(String),
+}
+
+/// Check that argon2id accepts a memory cost in KiB and a number of passes
+pub fn check_cost(memory_kib: u32, iterations: u32) -> Result<(), PasswordHashError> {
+    hasher(memory_kib, iterations).map(drop)
+}
+
+/// Hash a password with a fresh salt
+pub fn hash_password(password: &str, memory_kib: u32, iterations: u32) -> Result<String, PasswordHashError> {
+    if password.is_empty() {
+        return Err(PasswordHashError::Empty);
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = hasher(memory_kib, iterations)?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| PasswordHashError::Cost(e.to_string()))?;
+    Ok(hash.to_string())
+}
+
+/// Check a password against a stored hash, with the costs it was made with
+pub fn verify_password(password: &str, stored: &str) -> Result<bool, PasswordHashError> {
+    let unsupported = || PasswordHashError::Unsupported(stored.split('$').nth(1).unwrap_or_default().to_string());
+    let hash = PasswordHash::new(stored).map_err(|_| unsupported())?;
+    if Algorithm::try_from(hash.algorithm).is_err() {
+        return Err(unsupported());
+    }
+    match Argon2::default().verify_password(password.as_bytes(), &hash) {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(_) => Err(unsupported()),
+    }
+}
+
+/// Check a presented secret against the expected one in constant time
+pub fn secrets_match(presented: &str, expected: &str) -> bool {
+    let (presented, expected) = (Sha256::digest(presented.as_bytes()), Sha256::digest(expected.as_bytes()));
+    presented.iter().zip(expected.iter()).fold(0u8, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// The argon2id hasher for a memory cost in KiB and a number of passes
+fn hasher(memory_kib: u32, iterations: u32) -> Result<Argon2<'static>, PasswordHashError> {
+    let params = Params::new(memory_kib, iterations, 1, None).map_err(|e| PasswordHashError::Cost(e.to_string()))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
@@ -0,0 +1,131 @@
+/*
+ * File: src/security/redaction.rs
+ * Purpose: Redaction of sensitive content before it reaches logs
+ *
+ * Responsibilities:
+ * - Mask configured payload fields (document text by default)
+ * - Mask configurable secret patterns in any string value
+ * - Provide a single hook for logs, audit records, and diagnostic dumps
+ *
+ * Every place that writes message content to an operational sink should
+ * go through a Redactor, so logging can be enabled in privacy-sensitive
+ * deployments without leaking document text.
+ */
+
+use std::collections::HashSet;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::websocket::Message;
+
+/// Configuration for the redaction layer
+#[derive(Debug, Clone)]
+pub struct RedactionConfig {
+    /// Payload field names whose values are always masked
+    pub fields: Vec<String>,
+    /// Regular expressions masked wherever they match inside a string
+    pub patterns: Vec<String>,
+    /// Text substituted for redacted content
+    pub replacement: String,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            // Document text, passwords and tokens travel in these fields
+            fields: ["character", "content", "initial_content", "password", "session_token", "share_token", "text"]
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+            patterns: Vec::new(),
+            replacement: "[REDACTED]".to_string(),
+        }
+    }
+}
+
+/// Applies redaction rules to values before they are logged
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    fields: HashSet<String>,
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl Redactor {
+    /// Create a redactor from configuration
+    pub fn new(config: &RedactionConfig) -> Result<Self, regex::Error> {
+        let patterns = config.patterns
+            .iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            fields: config.fields.iter().cloned().collect(),
+            patterns,
+            replacement: config.replacement.clone(),
+        })
+    }
+
+    /// Create a redactor that passes everything through unchanged
+    pub fn disabled() -> Self {
+        Self {
+            fields: HashSet::new(),
+            patterns: Vec::new(),
+            replacement: String::new(),
+        }
+    }
+
+    /// Mask secret patterns in a plain string
+    pub fn redact_str(&self, text: &str) -> String {
+        self.patterns.iter().fold(text.to_string(), |acc, pattern| {
+            pattern.replace_all(&acc, self.replacement.as_str()).into_owned()
+        })
+    }
+
+    /// Return a copy of a JSON value with redaction rules applied
+    pub fn redact_value(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| {
+                        if self.fields.contains(key) {
+                            (key.clone(), Value::String(self.replacement.clone()))
+                        } else {
+                            (key.clone(), self.redact_value(value))
+                        }
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.redact_value(v)).collect()),
+            Value::String(text) => Value::String(self.redact_str(text)),
+            other => other.clone(),
+        }
+    }
+
+    /// Redact a message for logging
+    pub fn redact_message(&self, message: &Message) -> Value {
+        match serde_json::to_value(message) {
+            Ok(value) => self.redact_value(&value),
+            Err(_) => Value::String(self.replacement.clone()),
+        }
+    }
+
+    /// Redact raw inbound text, which may or may not be valid JSON.
+    /// Unparseable text is fully masked whenever field redaction is active,
+    /// since there is no structure to tell document text apart.
+    pub fn redact_raw(&self, raw: &str) -> String {
+        match serde_json::from_str::<Value>(raw) {
+            Ok(value) => self.redact_value(&value).to_string(),
+            Err(_) if !self.fields.is_empty() => {
+                format!("{} ({} bytes)", self.replacement, raw.len())
+            }
+            Err(_) => self.redact_str(raw),
+        }
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&RedactionConfig::default()).expect("default redaction config is valid")
+    }
+}
@@ -0,0 +1,190 @@
+/*
+ * File: src/security/tokens.rs
+ * Purpose: Self-contained encrypted tokens for sessions and sharing
+ *
+ * Responsibilities:
+ * - Seal claims and their expiry into an opaque token with AES-GCM under
+ *   the active master key
+ * - Open tokens without any lookup beyond the master key, rejecting
+ *   expired, tampered or foreign tokens
+ * - Keep tokens sealed with retired master keys valid while the key
+ *   provider still has the key
+ *
+ * Tokens look like `v1.<master key id>.<hex nonce and ciphertext>`. The
+ * token kind and key ID are bound through the associated data, so a share
+ * token doesn't open as a session token and the key ID can't be swapped.
+ * Rotating the master key (`StaticKeyProvider::rotate`) seals new tokens
+ * with the new key; leaving a retired key out of the provider, such as
+ * out of `COEDIT_MASTER_KEYS`, revokes every token sealed with it.
+ *
+ * The server seals a session token into every connection's welcome,
+ * which resumes the session on reconnecting, and share tokens unlocking
+ * password-protected documents (`TokenConfig`).
+ */
+
+use std::{fmt, sync::Arc, time::Duration};
+use aes_gcm::{
+    aead::{Aead, AeadCore, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use crate::security::{EncryptionError, KeyProvider, MasterKey, StaticKeyProvider};
+
+/// Format version prefix of tokens
+const TOKEN_VERSION: &str = "v1";
+
+/// Bytes of the AES-GCM nonce at the start of a token's data
+const NONCE_LENGTH: usize = 12;
+
+/// Token errors
+#[derive(Error, Debug)]
+pub enum TokenError {
+    #[error("Malformed token")]
+    Malformed,
+    #[error("Token key {0} is no longer available")]
+    UnknownKey(String),
+    #[error("Invalid token")]
+    Invalid,
+    #[error("Token expired at {0}")]
+    Expired(DateTime<Utc>),
+    #[error("Claims can't be sealed: {0}")]
+    Claims(String),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
+/// What a token grants, so one kind can't stand in for another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    /// Resumption of a client's session
+    Session,
+    /// Access to a shared document
+    Share,
+}
+
+impl TokenKind {
+    /// Get the name of the kind
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::Session => "session",
+            TokenKind::Share => "share",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a session token carries: the session to resume
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionClaims {
+    pub tenant_id: String,
+    pub client_id: String,
+}
+
+/// What a share token carries: the document it unlocks, and a fingerprint
+/// of the password it stands in for, so changing the password revokes it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareClaims {
+    pub tenant_id: String,
+    pub document_id: String,
+    pub password: String,
+}
+
+/// Keys sealing the tokens the server issues, and how long they last
+#[derive(Debug, Clone)]
+pub struct TokenConfig {
+    /// Sealer of session and share tokens; by default one with a master key
+    /// generated at startup, so no token outlives the process
+    pub sealer: Arc<TokenSealer>,
+    /// How long a session token resumes its session
+    pub session_ttl: Duration,
+    /// How long a share token unlocks its document
+    pub share_ttl: Duration,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        let provider = StaticKeyProvider::new(MasterKey::generate("ephemeral".to_string()));
+        Self {
+            sealer: Arc::new(TokenSealer::new(Arc::new(provider))),
+            session_ttl: Duration::from_secs(24 * 60 * 60),
+            share_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// What a token carries
+#[derive(Serialize, Deserialize)]
+struct Sealed<T> {
+    /// Expiry, in seconds since the Unix epoch
+    exp: i64,
+    claims: T,
+}
+
+/// Seals and opens tokens with the master keys of a key provider
+pub struct TokenSealer {
+    provider: Arc<dyn KeyProvider>,
+}
+
+impl TokenSealer {
+    /// Create a sealer using the given master key source
+    pub fn new(provider: Arc<dyn KeyProvider>) -> Self {
+        Self { provider }
+    }
+
+    /// Seal claims into a token of a kind, valid until `expires`
+    pub fn seal<T: Serialize>(&self, kind: TokenKind, claims: &T, expires: DateTime<Utc>) -> Result<String, TokenError> {
+        let key = self.provider.active_key()?;
+        let plaintext = serde_json::to_vec(&Sealed { exp: expires.timestamp(), claims })
+            .map_err(|e| TokenError::Claims(e.to_string()))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = associated_data(kind, key.id());
+        let ciphertext = key.cipher()
+            .encrypt(&nonce, Payload { msg: &plaintext, aad: &aad })
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+        Ok(format!("{}.{}.{}{}", TOKEN_VERSION, key.id(), hex::encode(nonce), hex::encode(ciphertext)))
+    }
+
+    /// Open a token of a kind and return its claims, if it hasn't expired
+    /// by `now`
+    pub fn open<T: DeserializeOwned>(&self, kind: TokenKind, token: &str, now: DateTime<Utc>) -> Result<T, TokenError> {
+        let (key_id, data) = token.strip_prefix(TOKEN_VERSION)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.rsplit_once('.'))
+            .ok_or(TokenError::Malformed)?;
+        let data = hex::decode(data).map_err(|_| TokenError::Malformed)?;
+        if data.len() <= NONCE_LENGTH {
+            return Err(TokenError::Malformed);
+        }
+        let key = self.provider.key(key_id).map_err(|_| TokenError::UnknownKey(key_id.to_string()))?;
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        let aad = associated_data(kind, key_id);
+        let plaintext = key.cipher()
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| TokenError::Invalid)?;
+        let sealed: Sealed<T> = serde_json::from_slice(&plaintext).map_err(|_| TokenError::Invalid)?;
+        let expires = DateTime::from_timestamp(sealed.exp, 0).ok_or(TokenError::Invalid)?;
+        if expires <= now {
+            return Err(TokenError::Expired(expires));
+        }
+        Ok(sealed.claims)
+    }
+}
+
+impl fmt::Debug for TokenSealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenSealer").finish_non_exhaustive()
+    }
+}
+
+fn associated_data(kind: TokenKind, key_id: &str) -> Vec<u8> {
+    format!("coedit-token:{}:{}", kind, key_id).into_bytes()
+}
@@ -0,0 +1,189 @@
+/*
+ * File: src/storage/health.rs
+ * Purpose: Documents the store failed to write, and what recovers them
+ *
+ * Documents are served from memory, so a failing store loses nothing
+ * until the server stops. A document the store failed to write is
+ * degraded: its operations are held here, up to a bound, rather than
+ * appended, and the server retries until the store takes them. A
+ * document whose held operations overflowed the bound, or whose failure
+ * only surfaced when the store flushed, is recovered by writing it whole.
+ */
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::crdt::Operation;
+
+/// A document the store failed to write
+#[derive(Debug, Clone)]
+struct Degraded {
+    since: DateTime<Utc>,
+    error: String,
+    /// Operations to append once the store recovers, in order
+    held: Vec<Operation>,
+    /// Whether the document must be written whole, as what it lacks is
+    /// unknown or no longer held
+    whole: bool,
+    retries: u32,
+}
+
+/// What the admin API reports of a degraded document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DegradedDocument {
+    /// Tenant-scoped document key
+    pub key: String,
+    pub since: DateTime<Utc>,
+    /// Latest error of the store
+    pub error: String,
+    /// Operations held for the store
+    pub held: usize,
+    /// Whether the document will be written whole
+    pub whole: bool,
+    /// Failed attempts to recover it
+    pub retries: u32,
+}
+
+/// What to write to recover a degraded document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recovery {
+    /// Append the held operations
+    Append(Vec<Operation>),
+    /// Write the document as it is in memory, or delete it if it's gone
+    Whole,
+}
+
+/// Documents the store failed to write, and those written since the last
+/// flush
+#[derive(Debug)]
+pub struct StorageHealth {
+    degraded: BTreeMap<String, Degraded>,
+    /// Documents whose recovery waits on a flush, with when they became
+    /// degraded and how often their recovery failed
+    recovering: BTreeMap<String, (DateTime<Utc>, u32)>,
+    unflushed: HashSet<String>,
+    /// Most operations held per document
+    capacity: usize,
+}
+
+impl StorageHealth {
+    pub fn new(capacity: usize) -> Self {
+        Self { degraded: BTreeMap::new(), recovering: BTreeMap::new(), unflushed: HashSet::new(), capacity }
+    }
+
+    /// Whether a document is degraded
+    pub fn is_degraded(&self, key: &str) -> bool {
+        self.degraded.contains_key(key)
+    }
+
+    /// Whether anything is degraded or waits on a flush
+    pub fn is_idle(&self) -> bool {
+        self.degraded.is_empty() && self.recovering.is_empty() && self.unflushed.is_empty()
+    }
+
+    /// Record that the store failed to write a document, holding the
+    /// operations it failed to append, or writing it whole when
+    /// `operations` is None. Returns whether the document just became
+    /// degraded, rather than failing again or while recovering.
+    pub fn fail(&mut self, key: &str, error: &str, operations: Option<&[Operation]>, now: DateTime<Utc>) -> bool {
+        let recovering = self.recovering.remove(key);
+        let degraded = !self.degraded.contains_key(key) && recovering.is_none();
+        let (since, retries) = recovering.map_or((now, 0), |(since, retries)| (since, retries + 1));
+        let entry = self.degraded.entry(key.to_string()).or_insert_with(|| Degraded {
+            since,
+            error: String::new(),
+            held: Vec::new(),
+            whole: false,
+            retries,
+        });
+        entry.error = error.to_string();
+        match operations {
+            Some(operations) if !entry.whole && entry.held.len() + operations.len() <= self.capacity => {
+                entry.held.extend_from_slice(operations);
+            }
+            _ => {
+                entry.whole = true;
+                entry.held = Vec::new();
+            }
+        }
+        degraded
+    }
+
+    /// Hold an operation of a degraded document instead of appending it,
+    /// returning false if the document isn't degraded
+    pub fn hold(&mut self, key: &str, operation: &Operation) -> bool {
+        let Some(entry) = self.degraded.get_mut(key) else {
+            return false;
+        };
+        if !entry.whole {
+            if entry.held.len() < self.capacity {
+                entry.held.push(operation.clone());
+            } else {
+                entry.whole = true;
+                entry.held = Vec::new();
+            }
+        }
+        true
+    }
+
+    /// Record a write the store took, which a flush confirms
+    pub fn written(&mut self, key: &str) {
+        self.unflushed.insert(key.to_string());
+    }
+
+    /// Take the documents written since the last flush, before flushing
+    pub fn take_unflushed(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.unflushed)
+    }
+
+    /// Record that a flush failed: every document in `written`, and any
+    /// written since, must be written whole
+    pub fn flush_failed(&mut self, written: HashSet<String>, error: &str, now: DateTime<Utc>) -> Vec<String> {
+        let unflushed = self.take_unflushed();
+        let mut keys: Vec<String> = written.into_iter().chain(unflushed).collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter().filter(|key| self.fail(key, error, None, now)).collect()
+    }
+
+    /// Keys of the degraded documents
+    pub fn degraded_keys(&self) -> Vec<String> {
+        self.degraded.keys().cloned().collect()
+    }
+
+    /// Stop holding a degraded document's writes, taking what to write to
+    /// recover it. The caller writes it before anything else is written
+    /// to the document, failing it again if the store refuses.
+    pub fn begin_recovery(&mut self, key: &str) -> Option<Recovery> {
+        let entry = self.degraded.remove(key)?;
+        self.recovering.insert(key.to_string(), (entry.since, entry.retries));
+        Some(if entry.whole { Recovery::Whole } else { Recovery::Append(entry.held) })
+    }
+
+    /// Record that a flush of the documents in `written` succeeded,
+    /// returning those it recovered
+    pub fn flushed(&mut self, written: &HashSet<String>) -> Vec<String> {
+        let recovered: Vec<String> = self.recovering.keys().filter(|key| written.contains(*key)).cloned().collect();
+        for key in &recovered {
+            self.recovering.remove(key);
+        }
+        recovered
+    }
+
+    /// What to report of the degraded documents
+    pub fn report(&self) -> Vec<DegradedDocument> {
+        self.degraded
+            .iter()
+            .map(|(key, entry)| DegradedDocument {
+                key: key.clone(),
+                since: entry.since,
+                error: entry.error.clone(),
+                held: entry.held.len(),
+                whole: entry.whole,
+                retries: entry.retries,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * File: src/storage/memory.rs
+ * Purpose: In-memory document store
+ *
+ * Keeps snapshots, operation logs and password hashes in maps, for tests
+ * and for servers that don't persist documents. A store shared between two
+ * servers, one after the other, stands in for a restart.
+ */
+
+use std::collections::HashMap;
+use parking_lot::Mutex;
+
+use crate::{
+    crdt::{Document, Operation},
+    storage::{store::unsaved, DocumentStore, StorageError, StoredDocument},
+};
+
+/// Stores documents in memory
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    documents: Mutex<HashMap<String, StoredDocument>>,
+    passwords: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DocumentStore for MemoryStore {
+    fn load(&self, key: &str) -> Result<Option<StoredDocument>, StorageError> {
+        Ok(self.documents.lock().get(key).cloned())
+    }
+
+    fn save(&self, key: &str, document: &Document) -> Result<(), StorageError> {
+        let mut documents = self.documents.lock();
+        let stored = documents.entry(key.to_string()).or_default();
+        let previous = stored.snapshot.as_ref().map(|snapshot| snapshot.state_vector().clone()).unwrap_or_default();
+        let mut keep = unsaved(&previous, document.state_vector(), stored.operations.iter().map(Operation::client_id)).into_iter();
+        stored.operations.retain(|_| keep.next().unwrap_or(true));
+        stored.snapshot = Some(document.clone());
+        Ok(())
+    }
+
+    fn replace(&self, key: &str, document: &Document) -> Result<(), StorageError> {
+        self.documents.lock().insert(key.to_string(), StoredDocument { snapshot: Some(document.clone()), operations: Vec::new() });
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, StorageError> {
+        let mut keys: Vec<String> = self.documents.lock().keys().cloned().collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.documents.lock().remove(key);
+        self.passwords.lock().remove(key);
+        Ok(())
+    }
+
+    fn append_ops(&self, key: &str, operations: &[Operation]) -> Result<(), StorageError> {
+        self.documents.lock().entry(key.to_string()).or_default().operations.extend_from_slice(operations);
+        Ok(())
+    }
+
+    fn save_password(&self, key: &str, hash: Option<&str>) -> Result<(), StorageError> {
+        let mut passwords = self.passwords.lock();
+        match hash {
+            Some(hash) => passwords.insert(key.to_string(), hash.to_string()),
+            None => passwords.remove(key),
+        };
+        Ok(())
+    }
+
+    fn load_passwords(&self) -> Result<Vec<(String, String)>, StorageError> {
+        let mut passwords: Vec<(String, String)> = self.passwords.lock().clone().into_iter().collect();
+        passwords.sort();
+        Ok(passwords)
+    }
+}
@@ -0,0 +1,41 @@
+/*
+ * File: src/storage/mod.rs
+ * Purpose: Module organization for document persistence
+ *
+ * This module contains:
+ * - store: The DocumentStore trait, what it loads, and its configuration
+ * - memory: In-memory store, the default
+ * - sqlite: SQLite store, keeping documents in a database file
+ * - health: Documents the store failed to write, and what recovers them
+ *
+ * A store keeps each document as its latest snapshot plus the operations
+ * applied after it. The server appends every operation it applies, saves
+ * a snapshot of each changed document every `snapshot_interval`, and loads
+ * every stored document when it is created. Keys are tenant-scoped
+ * document IDs, `<tenant>/<document>`.
+ *
+ * Only documents and their password hashes are stored: slugs, classroom
+ * settings and other tenant state start over on restart. Temporary
+ * documents are never stored.
+ */
+
+pub mod health;
+pub mod memory;
+pub mod sqlite;
+pub mod store;
+
+pub use health::{DegradedDocument, StorageHealth};
+pub use memory::MemoryStore;
+pub use sqlite::{GroupCommit, SqliteStore};
+pub use store::{DocumentStore, StorageConfig, StoredDocument};
+
+use thiserror::Error;
+
+/// Storage errors
+#[derive(Error, Debug, PartialEq)]
+pub enum StorageError {
+    #[error("Storage failed: {0}")]
+    Backend(String),
+    #[error("Stored document {document_id} is invalid: {message}")]
+    Invalid { document_id: String, message: String },
+}
@@ -0,0 +1,535 @@
+/*
+ * File: src/storage/sqlite.rs
+ * Purpose: SQLite document store
+ *
+ * Keeps documents in one SQLite database file, so they outlive restarts:
+ * - documents: one row per document, with its latest snapshot and state
+ *   vector as JSON, and its version and time of the last snapshot
+ * - operations: the operations logged after each snapshot, in order, with
+ *   their authors
+ * - passwords: the password hash of each protected document
+ * - data_keys: the wrapped data keys of each encrypted document
+ *
+ * The schema is versioned with `PRAGMA user_version`; opening a database
+ * applies the migrations it lacks, in one transaction each, and refuses a
+ * database written by a newer schema. Append new migrations to
+ * `MIGRATIONS`, never edit applied ones.
+ *
+ * Writes go through a writer thread of the store's own, so the server's
+ * tasks don't wait on the disk: appends, snapshots and deletes are queued
+ * in the order they were called and return once queued. The queue is
+ * bounded (`GroupCommit::queue_capacity`); once it is full, writes block
+ * until the writer catches up, rather than queueing without limit. The
+ * writer commits writes in groups, one transaction each (group commit):
+ * after the first write of a group it waits up to `GroupCommit::window`
+ * for more, up to `max_batch`, and commits at once when a flush arrives.
+ * Each write runs in a savepoint, so one failing write is logged and
+ * skipped without losing the rest. `flush` waits until every queued write
+ * is committed; reads flush first, so they see every write made before
+ * them. Dropping the store commits what is queued.
+ *
+ * Opened with `open_encrypted`, the store encrypts snapshots, operations
+ * and password hashes before queueing them, under data keys of each
+ * document (`security::DocumentEncryption`), and queues a document's
+ * wrapped keys ahead of the first record sealed with a new one. Keys,
+ * state vectors and operation authors stay plaintext, so snapshots drop
+ * the operations they hold without decrypting them. Plaintext rows from
+ * before encryption was turned on are still read.
+ */
+
+use std::{
+    path::Path,
+    sync::{mpsc, Arc},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use chrono::Utc;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::{
+    crdt::{Document, Operation, StateVector},
+    security::{DocumentEncryption, EncryptedRecord, EncryptionError, KeyProvider},
+    storage::{store::unsaved, DocumentStore, StorageError, StoredDocument},
+};
+
+/// Schema migrations, in order; the database's `user_version` counts those
+/// applied
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE documents (
+        key TEXT PRIMARY KEY,
+        snapshot TEXT,
+        state_vector TEXT NOT NULL DEFAULT '{}',
+        version INTEGER NOT NULL DEFAULT 0,
+        saved_at TEXT
+    );
+    CREATE TABLE operations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        key TEXT NOT NULL REFERENCES documents (key) ON DELETE CASCADE,
+        operation TEXT NOT NULL
+    );
+    CREATE INDEX operations_by_key ON operations (key, id);",
+    "CREATE TABLE passwords (
+        key TEXT PRIMARY KEY,
+        hash TEXT NOT NULL
+    );",
+    "ALTER TABLE operations ADD COLUMN author TEXT;
+    CREATE TABLE data_keys (
+        key TEXT PRIMARY KEY,
+        wrapped TEXT NOT NULL
+    );",
+];
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Backend(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Backend(e.to_string())
+    }
+}
+
+impl From<EncryptionError> for StorageError {
+    fn from(e: EncryptionError) -> Self {
+        StorageError::Backend(e.to_string())
+    }
+}
+
+/// How the writer thread groups queued writes into transactions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupCommit {
+    /// Most writes committed in one transaction; one commits every write
+    /// on its own
+    pub max_batch: usize,
+    /// How long the writer waits for more writes after the first of a
+    /// transaction, adding that much latency for fewer commits; zero
+    /// commits what is queued at once
+    pub window: Duration,
+    /// Most writes queued for the writer; beyond them, writes block until
+    /// it catches up
+    pub queue_capacity: usize,
+}
+
+impl Default for GroupCommit {
+    fn default() -> Self {
+        Self { max_batch: 1024, window: Duration::from_millis(2), queue_capacity: 16 * 1024 }
+    }
+}
+
+/// A write queued for the writer thread
+#[derive(Debug)]
+enum Write {
+    /// Operations, each with its author
+    Append { key: String, operations: Vec<(String, String)> },
+    /// A snapshot, dropping the logged operations it holds, or all of
+    /// them with `replace`
+    Save { key: String, snapshot: String, state_vector: StateVector, version: u64, replace: bool },
+    Delete { key: String },
+    Password { key: String, hash: Option<String> },
+    /// A document's wrapped data keys, as JSON
+    Keys { key: String, wrapped: String },
+    /// Answer once every write queued before it is committed, with the
+    /// first failure since the previous flush
+    Flush(mpsc::Sender<Result<(), StorageError>>),
+}
+
+/// Stores documents in a SQLite database
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    writes: Option<mpsc::SyncSender<Write>>,
+    writer: Option<JoinHandle<()>>,
+    encryption: Option<Arc<DocumentEncryption>>,
+    /// Held while encrypting and queueing, so a document's keys are queued
+    /// before any record sealed with them
+    sealing: Mutex<()>,
+}
+
+impl SqliteStore {
+    /// Open a database file, creating it if needed, and migrate it
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::open_with(path, GroupCommit::default(), None)
+    }
+
+    /// Open a database file like `open`, encrypting what is stored with
+    /// data keys wrapped by the provider's master keys
+    pub fn open_encrypted(path: impl AsRef<Path>, provider: Arc<dyn KeyProvider>) -> Result<Self, StorageError> {
+        Self::open_with(path, GroupCommit::default(), Some(provider))
+    }
+
+    /// Open a database file like `open`, committing writes as configured,
+    /// and encrypting them if given a key provider
+    pub fn open_with(path: impl AsRef<Path>, commit: GroupCommit, provider: Option<Arc<dyn KeyProvider>>) -> Result<Self, StorageError> {
+        Self::with_connection(Self::connect(path)?, commit, provider)
+    }
+
+    /// Open a database that lives in memory, for tests
+    pub fn in_memory() -> Result<Self, StorageError> {
+        Self::with_connection(Connection::open_in_memory()?, GroupCommit::default(), None)
+    }
+
+    fn connect(path: impl AsRef<Path>) -> Result<Connection, StorageError> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Ok(connection)
+    }
+
+    fn with_connection(mut connection: Connection, commit: GroupCommit, provider: Option<Arc<dyn KeyProvider>>) -> Result<Self, StorageError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        let encryption = provider.map(|provider| load_keys(&connection, provider)).transpose()?.map(Arc::new);
+        let connection = Arc::new(Mutex::new(connection));
+        let (writes, queue) = mpsc::sync_channel(commit.queue_capacity.max(1));
+        let writer = std::thread::Builder::new()
+            .name("sqlite-writer".to_string())
+            .spawn({
+                let connection = connection.clone();
+                move || write_batches(&connection, &queue, commit)
+            })
+            .map_err(|e| StorageError::Backend(e.to_string()))?;
+        Ok(Self { connection, writes: Some(writes), writer: Some(writer), encryption, sealing: Mutex::new(()) })
+    }
+
+    /// Get the number of migrations applied to the database
+    pub fn schema_version(&self) -> Result<usize, StorageError> {
+        schema_version(&self.connection.lock())
+    }
+
+    fn queue(&self, write: Write) -> Result<(), StorageError> {
+        self.writes
+            .as_ref()
+            .and_then(|writes| writes.send(write).ok())
+            .ok_or_else(|| StorageError::Backend("The database writer has stopped".to_string()))
+    }
+
+    /// Queue a write of a document's texts, encrypted first if the store
+    /// encrypts, queueing the document's keys ahead of it when encrypting
+    /// created one
+    fn queue_sealed(&self, key: &str, texts: Vec<String>, write: impl FnOnce(Vec<String>) -> Write) -> Result<(), StorageError> {
+        let Some(encryption) = &self.encryption else {
+            return self.queue(write(texts));
+        };
+        let _sealing = self.sealing.lock();
+        let previous = encryption.current_version(key);
+        let sealed = texts
+            .iter()
+            .map(|text| Ok(encryption.encrypt(key, text.as_bytes())?.encode()))
+            .collect::<Result<Vec<_>, StorageError>>()?;
+        if encryption.current_version(key) != previous {
+            self.queue_keys(encryption, key)?;
+        }
+        self.queue(write(sealed))
+    }
+
+    fn queue_keys(&self, encryption: &DocumentEncryption, key: &str) -> Result<(), StorageError> {
+        let wrapped = serde_json::to_string(&encryption.wrapped_keys(key))?;
+        self.queue(Write::Keys { key: key.to_string(), wrapped })
+    }
+
+    /// Decrypt a stored text if it was encrypted
+    fn unseal(&self, key: &str, text: String) -> Result<String, StorageError> {
+        let Some(record) = EncryptedRecord::decode(&text) else {
+            return Ok(text);
+        };
+        let encryption = self.encryption.as_ref().ok_or_else(|| StorageError::Invalid {
+            document_id: key.to_string(),
+            message: "it is encrypted, and the store has no master keys".to_string(),
+        })?;
+        let plaintext = encryption.decrypt(key, &record)?;
+        String::from_utf8(plaintext).map_err(|e| StorageError::Invalid { document_id: key.to_string(), message: e.to_string() })
+    }
+
+    /// Read a document's logged operations, decrypted, in order
+    fn load_operations(&self, connection: &Connection, key: &str) -> Result<Vec<Operation>, StorageError> {
+        let mut statement = connection.prepare_cached("SELECT operation FROM operations WHERE key = ?1 ORDER BY id")?;
+        let rows = statement.query_map(params![key], |row| row.get::<_, String>(0))?;
+        rows.map(|row| Ok(serde_json::from_str(&self.unseal(key, row?)?)?)).collect()
+    }
+}
+
+/// Build the encryption of a database from its stored data keys
+fn load_keys(connection: &Connection, provider: Arc<dyn KeyProvider>) -> Result<DocumentEncryption, StorageError> {
+    let encryption = DocumentEncryption::new(provider);
+    let mut statement = connection.prepare("SELECT key, wrapped FROM data_keys")?;
+    let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (key, wrapped) = row?;
+        encryption.load_wrapped_keys(&key, serde_json::from_str(&wrapped)?);
+    }
+    Ok(encryption)
+}
+
+impl Drop for SqliteStore {
+    fn drop(&mut self) {
+        // The writer commits what is queued once the queue closes
+        self.writes.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Commit queued writes until the queue closes, in groups gathered as
+/// configured
+fn write_batches(connection: &Mutex<Connection>, queue: &mpsc::Receiver<Write>, commit: GroupCommit) {
+    let mut failure = None;
+    while let Ok(first) = queue.recv() {
+        let batch = gather(queue, first, commit);
+
+        let mut flushes = Vec::new();
+        let mut connection = connection.lock();
+        let result = connection.transaction().map_err(StorageError::from).and_then(|mut transaction| {
+            for write in batch {
+                if let Write::Flush(reply) = write {
+                    flushes.push(reply);
+                } else if let Err(e) = apply(&mut transaction, &write) {
+                    log::error!("Could not store a write to the document database: {}", e);
+                    failure.get_or_insert(e);
+                }
+            }
+            transaction.commit().map_err(StorageError::from)
+        });
+        drop(connection);
+        if let Err(e) = result {
+            log::error!("Could not commit writes to the document database: {}", e);
+            failure.get_or_insert(e);
+        }
+        for reply in flushes {
+            let _ = reply.send(failure.take().map_or(Ok(()), Err));
+        }
+    }
+}
+
+/// Gather a group of writes after its first: those queued within the
+/// window, up to the most per group, stopping at a flush, which is waiting
+fn gather(queue: &mpsc::Receiver<Write>, first: Write, commit: GroupCommit) -> Vec<Write> {
+    let deadline = Instant::now() + commit.window;
+    let mut batch = vec![first];
+    while batch.len() < commit.max_batch && !matches!(batch.last(), Some(Write::Flush(_))) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let next = if left.is_zero() { queue.try_recv().ok() } else { queue.recv_timeout(left).ok() };
+        match next {
+            Some(write) => batch.push(write),
+            None => break,
+        }
+    }
+    batch
+}
+
+/// Apply one write in a savepoint of the batch's transaction
+fn apply(transaction: &mut Transaction, write: &Write) -> Result<(), StorageError> {
+    let savepoint = transaction.savepoint()?;
+    match write {
+        Write::Append { key, operations } => {
+            savepoint.execute("INSERT OR IGNORE INTO documents (key) VALUES (?1)", params![key])?;
+            let mut statement = savepoint.prepare_cached("INSERT INTO operations (key, author, operation) VALUES (?1, ?2, ?3)")?;
+            for (author, operation) in operations {
+                statement.execute(params![key, author, operation])?;
+            }
+        }
+        Write::Save { key, snapshot, state_vector, version, replace: true } => {
+            savepoint.execute("DELETE FROM operations WHERE key = ?1", params![key])?;
+            store_snapshot(&savepoint, key, snapshot, state_vector, *version)?;
+        }
+        Write::Save { key, snapshot, state_vector, version, replace: false } => {
+            let previous: StateVector = savepoint
+                .query_row("SELECT state_vector FROM documents WHERE key = ?1", params![key], |row| row.get::<_, String>(0))
+                .optional()?
+                .map(|vector| serde_json::from_str(&vector))
+                .transpose()?
+                .unwrap_or_default();
+
+            let logged = logged_authors(&savepoint, key)?;
+            let keep = unsaved(&previous, state_vector, logged.iter().map(|(_, author)| author.as_str()));
+            for ((id, _), keep) in logged.iter().zip(keep) {
+                if !keep {
+                    savepoint.execute("DELETE FROM operations WHERE id = ?1", params![id])?;
+                }
+            }
+
+            store_snapshot(&savepoint, key, snapshot, state_vector, *version)?;
+        }
+        // Logged operations go with the document
+        Write::Delete { key } => {
+            savepoint.execute("DELETE FROM documents WHERE key = ?1", params![key])?;
+            savepoint.execute("DELETE FROM passwords WHERE key = ?1", params![key])?;
+            savepoint.execute("DELETE FROM data_keys WHERE key = ?1", params![key])?;
+        }
+        Write::Password { key, hash: Some(hash) } => {
+            savepoint.execute(
+                "INSERT INTO passwords (key, hash) VALUES (?1, ?2) ON CONFLICT (key) DO UPDATE SET hash = ?2",
+                params![key, hash],
+            )?;
+        }
+        Write::Password { key, hash: None } => {
+            savepoint.execute("DELETE FROM passwords WHERE key = ?1", params![key])?;
+        }
+        Write::Keys { key, wrapped } => {
+            savepoint.execute(
+                "INSERT INTO data_keys (key, wrapped) VALUES (?1, ?2) ON CONFLICT (key) DO UPDATE SET wrapped = ?2",
+                params![key, wrapped],
+            )?;
+        }
+        Write::Flush(_) => {}
+    }
+    savepoint.commit()?;
+    Ok(())
+}
+
+fn schema_version(connection: &Connection) -> Result<usize, StorageError> {
+    let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+/// Apply the migrations a database lacks
+fn migrate(connection: &mut Connection) -> Result<(), StorageError> {
+    let applied = schema_version(connection)?;
+    if applied > MIGRATIONS.len() {
+        return Err(StorageError::Backend(format!(
+            "Database schema version {} is newer than this server's {}",
+            applied,
+            MIGRATIONS.len()
+        )));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index as i64 + 1)?;
+        transaction.commit()?;
+        log::info!("Migrated document database to schema version {}", index + 1);
+    }
+    Ok(())
+}
+
+/// Read the authors of a document's logged operations, with their row
+/// IDs, in order. Operations logged before authors were stored name them
+/// only in their plaintext.
+fn logged_authors(connection: &Connection, key: &str) -> Result<Vec<(i64, String)>, StorageError> {
+    let mut statement = connection.prepare_cached(
+        "SELECT id, author, CASE WHEN author IS NULL THEN operation END FROM operations WHERE key = ?1 ORDER BY id",
+    )?;
+    let rows = statement.query_map(params![key], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+    })?;
+    rows.map(|row| {
+        let (id, author, operation) = row?;
+        let author = match (author, operation) {
+            (Some(author), _) => author,
+            (None, operation) => serde_json::from_str::<Operation>(&operation.unwrap_or_default())?.client_id().to_string(),
+        };
+        Ok((id, author))
+    })
+    .collect()
+}
+
+/// Write a document's snapshot row
+fn store_snapshot(connection: &Connection, key: &str, snapshot: &str, state_vector: &StateVector, version: u64) -> Result<(), StorageError> {
+    connection.execute(
+        "INSERT INTO documents (key, snapshot, state_vector, version, saved_at) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (key) DO UPDATE SET snapshot = ?2, state_vector = ?3, version = ?4, saved_at = ?5",
+        params![key, snapshot, serde_json::to_string(state_vector)?, version as i64, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+impl DocumentStore for SqliteStore {
+    fn load(&self, key: &str) -> Result<Option<StoredDocument>, StorageError> {
+        self.flush()?;
+        let connection = self.connection.lock();
+        let snapshot: Option<Option<String>> = connection
+            .query_row("SELECT snapshot FROM documents WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?;
+        let Some(snapshot) = snapshot else {
+            return Ok(None);
+        };
+        Ok(Some(StoredDocument {
+            snapshot: snapshot.map(|snapshot| Ok::<_, StorageError>(serde_json::from_str(&self.unseal(key, snapshot)?)?)).transpose()?,
+            operations: self.load_operations(&connection, key)?,
+        }))
+    }
+
+    fn save(&self, key: &str, document: &Document) -> Result<(), StorageError> {
+        self.queue_sealed(key, vec![serde_json::to_string(document)?], |snapshot| Write::Save {
+            key: key.to_string(),
+            snapshot: snapshot.concat(),
+            state_vector: document.state_vector().clone(),
+            version: document.version(),
+            replace: false,
+        })
+    }
+
+    fn replace(&self, key: &str, document: &Document) -> Result<(), StorageError> {
+        self.queue_sealed(key, vec![serde_json::to_string(document)?], |snapshot| Write::Save {
+            key: key.to_string(),
+            snapshot: snapshot.concat(),
+            state_vector: document.state_vector().clone(),
+            version: document.version(),
+            replace: true,
+        })
+    }
+
+    fn list(&self) -> Result<Vec<String>, StorageError> {
+        self.flush()?;
+        let connection = self.connection.lock();
+        let mut statement = connection.prepare_cached("SELECT key FROM documents ORDER BY key")?;
+        let keys = statement.query_map([], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let _sealing = self.sealing.lock();
+        self.queue(Write::Delete { key: key.to_string() })?;
+        if let Some(encryption) = &self.encryption {
+            encryption.forget_document(key);
+        }
+        Ok(())
+    }
+
+    fn append_ops(&self, key: &str, operations: &[Operation]) -> Result<(), StorageError> {
+        let texts = operations.iter().map(serde_json::to_string).collect::<Result<_, _>>()?;
+        self.queue_sealed(key, texts, |texts| Write::Append {
+            key: key.to_string(),
+            operations: operations.iter().map(|operation| operation.client_id().to_string()).zip(texts).collect(),
+        })
+    }
+
+    fn save_password(&self, key: &str, hash: Option<&str>) -> Result<(), StorageError> {
+        self.queue_sealed(key, hash.map(str::to_string).into_iter().collect(), |hash| Write::Password {
+            key: key.to_string(),
+            hash: hash.into_iter().next(),
+        })
+    }
+
+    fn load_passwords(&self) -> Result<Vec<(String, String)>, StorageError> {
+        self.flush()?;
+        let connection = self.connection.lock();
+        let mut statement = connection.prepare_cached("SELECT key, hash FROM passwords ORDER BY key")?;
+        let passwords = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<(String, String)>, _>>()?;
+        passwords.into_iter().map(|(key, hash)| Ok((key.clone(), self.unseal(&key, hash)?))).collect()
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        let (reply, committed) = mpsc::channel();
+        self.queue(Write::Flush(reply))?;
+        committed
+            .recv()
+            .map_err(|_| StorageError::Backend("The database writer has stopped".to_string()))?
+    }
+
+    fn rotate_keys(&self) -> Result<usize, StorageError> {
+        let Some(encryption) = &self.encryption else {
+            return Ok(0);
+        };
+        let _sealing = self.sealing.lock();
+        let rewrapped = encryption.rewrap_all()?;
+        if rewrapped > 0 {
+            for key in encryption.documents() {
+                self.queue_keys(encryption, &key)?;
+            }
+        }
+        Ok(rewrapped)
+    }
+}
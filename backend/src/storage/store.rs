@@ -0,0 +1,141 @@
+/*
+ * File: src/storage/store.rs
+ * Purpose: The DocumentStore trait, what it loads, and its configuration
+ *
+ * Stores are called from the server's tasks as operations are applied, so
+ * writes should be cheap; a store doing slow I/O should queue them, in
+ * order, and write them from a thread of its own, as `SqliteStore` does.
+ * Writes may then return before they are durable; `flush` waits for them.
+ *
+ * Saving a snapshot drops the logged operations it holds, which a store
+ * tells apart by state vectors: every replica applies an author's
+ * operations in order, and the log starts where the previous snapshot's
+ * vector ends, so an author's logged operations are covered while that
+ * count plus those before them in the log is below the new snapshot's.
+ * That holds only if every operation a snapshot holds was appended before
+ * it is saved, which the server ensures by tracking what it logged.
+ */
+
+use std::{collections::HashMap, fmt::Debug, sync::Arc, time::Duration};
+
+use crate::{
+    crdt::{Document, Operation, StateVector},
+    storage::{MemoryStore, StorageError},
+};
+
+/// Keeps documents as snapshots and the operations applied after them
+pub trait DocumentStore: Debug + Send + Sync {
+    /// Load a document's snapshot and the operations logged after it
+    fn load(&self, key: &str) -> Result<Option<StoredDocument>, StorageError>;
+
+    /// Store a snapshot of a document, replacing the previous one and
+    /// dropping the logged operations it holds. Every operation it holds
+    /// must have been appended first.
+    fn save(&self, key: &str, document: &Document) -> Result<(), StorageError>;
+
+    /// Store a document written as a whole, such as a copy or a repaired
+    /// document, dropping every logged operation
+    fn replace(&self, key: &str, document: &Document) -> Result<(), StorageError>;
+
+    /// Get the keys of every stored document
+    fn list(&self) -> Result<Vec<String>, StorageError>;
+
+    /// Remove a document's snapshot and operations
+    fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Log operations applied to a document, in the order they were applied
+    fn append_ops(&self, key: &str, operations: &[Operation]) -> Result<(), StorageError>;
+
+    /// Store the password hash of a document, or remove it with None, so
+    /// its protection outlives restarts. Deleting the document removes it
+    /// too.
+    fn save_password(&self, key: &str, hash: Option<&str>) -> Result<(), StorageError>;
+
+    /// Get the stored password hashes, by document key
+    fn load_passwords(&self) -> Result<Vec<(String, String)>, StorageError>;
+
+    /// Wait until every write made so far is durable, failing if any of
+    /// them failed since the previous flush. Blocks the calling thread.
+    fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    /// Rewrap the data keys of encrypted documents under the active master
+    /// key and store them, returning how many were rewrapped. Stores that
+    /// don't encrypt have none.
+    fn rotate_keys(&self) -> Result<usize, StorageError> {
+        Ok(0)
+    }
+}
+
+/// Tell the logged operations a new snapshot doesn't hold, in log order,
+/// from their authors. The log starts where `previous`, the state vector
+/// of the snapshot it follows, ends; `held` is the new snapshot's.
+pub(crate) fn unsaved<'a>(previous: &StateVector, held: &StateVector, authors: impl IntoIterator<Item = &'a str>) -> Vec<bool> {
+    let mut logged: HashMap<&str, u64> = HashMap::new();
+    authors
+        .into_iter()
+        .map(|author| {
+            let count = logged.entry(author).or_insert(0);
+            let before = previous.get(author) + *count;
+            *count += 1;
+            before >= held.get(author)
+        })
+        .collect()
+}
+
+/// A document as a store keeps it
+#[derive(Debug, Clone, Default)]
+pub struct StoredDocument {
+    /// Latest snapshot, if one was saved
+    pub snapshot: Option<Document>,
+    /// Operations logged after the snapshot, in order
+    pub operations: Vec<Operation>,
+}
+
+impl StoredDocument {
+    /// Rebuild the document: its snapshot, or an empty document with the
+    /// given ID, with the logged operations merged in order
+    pub fn restore(self, document_id: &str) -> Result<Document, StorageError> {
+        let mut document = self.snapshot.unwrap_or_else(|| Document::new(document_id.to_string()));
+        for operation in self.operations {
+            document.merge_operation(operation).map_err(|e| StorageError::Invalid {
+                document_id: document_id.to_string(),
+                message: e.to_string(),
+            })?;
+        }
+        Ok(document)
+    }
+}
+
+/// Where documents are stored, and how often they are snapshotted
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    /// Store of documents; in memory by default, so nothing outlives the
+    /// process
+    pub store: Arc<dyn DocumentStore>,
+    /// Time between snapshots of changed documents
+    pub snapshot_interval: Duration,
+    /// Time between rewraps of the store's data keys under the active
+    /// master key; zero turns key rotation off
+    pub key_rotation: Duration,
+    /// Time between attempts to write degraded documents, those the store
+    /// failed to write, and to confirm recent writes; zero turns recovery
+    /// off
+    pub retry_interval: Duration,
+    /// Most operations held per degraded document; beyond them the
+    /// document is written whole once the store recovers
+    pub degraded_capacity: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            store: Arc::new(MemoryStore::new()),
+            snapshot_interval: Duration::from_secs(60),
+            key_rotation: Duration::from_secs(3600),
+            retry_interval: Duration::from_secs(5),
+            degraded_capacity: 1024,
+        }
+    }
+}
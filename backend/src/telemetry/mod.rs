@@ -0,0 +1,11 @@
+/*
+ * File: src/telemetry/mod.rs
+ * Purpose: Module organization for opt-in usage statistics
+ *
+ * This module contains:
+ * - usage: Anonymous usage reports and their delivery
+ */
+
+pub mod usage;
+
+pub use usage::{bucket, TelemetryConfig, TelemetryError, UsageReport, UsageReporter};
@@ -0,0 +1,151 @@
+/*
+ * File: src/telemetry/usage.rs
+ * Purpose: Anonymous usage statistics, off unless an operator opts in
+ *
+ * A usage report holds coarse counters only:
+ * - The server version
+ * - The number of documents, as a bucket
+ * - The peak number of concurrent clients since the last report, as a bucket
+ *
+ * Reports carry no document or client IDs, tenant names, content, or
+ * host details, and no identifier linking one report to the next. The
+ * `usage_preview` binary prints a report exactly as it would be sent.
+ *
+ * Delivery is best-effort, like webhooks: failures are logged and never
+ * affect the server.
+ */
+
+use std::time::Duration;
+use hyper::{client::HttpConnector, Body, Client, Method, Request, Uri};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Upper bounds of the count buckets; larger counts fall into the last one
+const BUCKET_BOUNDS: [u64; 5] = [0, 9, 99, 999, 9_999];
+
+/// Telemetry errors
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("Invalid telemetry endpoint: {0}")]
+    InvalidEndpoint(String),
+    #[error("Failed to serialize usage report: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Usage report request failed: {0}")]
+    Request(#[from] hyper::Error),
+    #[error("Telemetry endpoint responded with status {0}")]
+    Status(u16),
+}
+
+/// Configuration for usage reporting
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// Send usage reports. Off by default.
+    pub enabled: bool,
+    /// `http://` URL reports are posted to; required when enabled
+    pub endpoint: Option<String>,
+    /// Time between reports
+    pub interval: Duration,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// Everything a usage report contains
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageReport {
+    /// Server version
+    pub version: String,
+    /// Number of documents, bucketed
+    pub documents: String,
+    /// Peak concurrent clients over the reporting period, bucketed
+    pub peak_clients: String,
+}
+
+impl UsageReport {
+    /// Build a report from exact counts, which are bucketed before they
+    /// leave this function
+    pub fn new(document_count: usize, peak_clients: usize) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            documents: bucket(document_count as u64),
+            peak_clients: bucket(peak_clients as u64),
+        }
+    }
+}
+
+/// Map a count to a coarse range such as `10-99`
+pub fn bucket(count: u64) -> String {
+    let mut lower = 0;
+    for upper in BUCKET_BOUNDS {
+        if count <= upper {
+            return if lower == upper {
+                upper.to_string()
+            } else {
+                format!("{}-{}", lower, upper)
+            };
+        }
+        lower = upper + 1;
+    }
+    format!("{}+", lower)
+}
+
+/// Posts usage reports to the configured endpoint
+#[derive(Clone)]
+pub struct UsageReporter {
+    url: Uri,
+    client: Client<HttpConnector>,
+}
+
+impl UsageReporter {
+    /// Create a reporter for the given `http://` endpoint
+    pub fn new(endpoint: &str) -> Result<Self, TelemetryError> {
+        let url: Uri = endpoint.parse().map_err(|_| TelemetryError::InvalidEndpoint(endpoint.to_string()))?;
+        if url.scheme_str() != Some("http") {
+            return Err(TelemetryError::InvalidEndpoint(format!("{} (only http:// is supported)", url)));
+        }
+
+        Ok(Self {
+            url,
+            client: Client::new(),
+        })
+    }
+
+    /// Create a reporter if telemetry is enabled and configured correctly
+    pub fn from_config(config: &TelemetryConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let Some(endpoint) = config.endpoint.as_deref() else {
+            log::error!("Telemetry is enabled but no endpoint is configured; not reporting");
+            return None;
+        };
+        Self::new(endpoint)
+            .map_err(|e| log::error!("Telemetry disabled: {}", e))
+            .ok()
+    }
+
+    /// Post a report and wait for the endpoint to accept it
+    pub async fn send(&self, report: &UsageReport) -> Result<(), TelemetryError> {
+        let body = serde_json::to_vec(report)?;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .map_err(|e| TelemetryError::InvalidEndpoint(e.to_string()))?;
+
+        let response = self.client.request(request).await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(TelemetryError::Status(response.status().as_u16()))
+        }
+    }
+}
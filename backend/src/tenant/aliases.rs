@@ -0,0 +1,102 @@
+/*
+ * File: src/tenant/aliases.rs
+ * Purpose: Human-friendly document slugs and alias resolution
+ *
+ * Each tenant keeps an alias table mapping slugs such as `meeting-notes-2024`
+ * to document IDs:
+ * - A document has at most one current slug
+ * - Renaming keeps the old slug as a redirect, so shared links keep working
+ * - A slug, current or retired, belongs to one document only; claiming a
+ *   slug held by another document fails
+ *
+ * Resolution is a lookup: anything that isn't a known slug is taken to be
+ * a document ID and returned unchanged.
+ */
+
+use std::collections::HashMap;
+use parking_lot::RwLock;
+use thiserror::Error;
+
+/// Slug-specific errors
+#[derive(Error, Debug, PartialEq)]
+pub enum AliasError {
+    #[error("Invalid slug: {0}")]
+    InvalidSlug(String),
+    #[error("Slug {0} is already taken")]
+    Taken(String),
+}
+
+#[derive(Debug, Default)]
+struct Aliases {
+    /// Current and retired slugs, mapped to their document ID
+    targets: HashMap<String, String>,
+    /// Current slug of each document
+    slugs: HashMap<String, String>,
+}
+
+/// Slugs of a tenant's documents
+#[derive(Debug, Default)]
+pub struct AliasTable {
+    inner: RwLock<Aliases>,
+}
+
+impl AliasTable {
+    /// Create an empty alias table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give a document a new slug. Its previous slug keeps redirecting to it.
+    pub fn set_slug(&self, document_id: &str, slug: &str) -> Result<(), AliasError> {
+        validate_slug(slug)?;
+
+        let mut aliases = self.inner.write();
+        match aliases.targets.get(slug) {
+            Some(target) if target != document_id => return Err(AliasError::Taken(slug.to_string())),
+            _ => {}
+        }
+        aliases.targets.insert(slug.to_string(), document_id.to_string());
+        aliases.slugs.insert(document_id.to_string(), slug.to_string());
+        Ok(())
+    }
+
+    /// Resolve a slug to its document ID; anything else is returned unchanged
+    pub fn resolve(&self, id_or_slug: &str) -> String {
+        self.inner.read()
+            .targets
+            .get(id_or_slug)
+            .cloned()
+            .unwrap_or_else(|| id_or_slug.to_string())
+    }
+
+    /// Check whether a slug, current or retired, is in use
+    pub fn contains(&self, slug: &str) -> bool {
+        self.inner.read().targets.contains_key(slug)
+    }
+
+    /// Get a document's current slug
+    pub fn slug(&self, document_id: &str) -> Option<String> {
+        self.inner.read().slugs.get(document_id).cloned()
+    }
+
+    /// Drop every slug of a removed document, freeing them for reuse
+    pub fn remove_document(&self, document_id: &str) {
+        let mut aliases = self.inner.write();
+        aliases.slugs.remove(document_id);
+        aliases.targets.retain(|_, target| target != document_id);
+    }
+}
+
+/// Check that a slug is 3 to 64 characters of `[a-z0-9-]`, starting and
+/// ending with a letter or digit
+pub fn validate_slug(slug: &str) -> Result<(), AliasError> {
+    let valid = (3..=64).contains(&slug.len())
+        && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-');
+    if valid {
+        Ok(())
+    } else {
+        Err(AliasError::InvalidSlug(slug.to_string()))
+    }
+}
@@ -0,0 +1,84 @@
+/*
+ * File: src/tenant/charset.rs
+ * Purpose: Characters a document accepts
+ *
+ * Some documents are meant for plain text only, such as log files or
+ * configuration under review. The owner of a document can restrict the
+ * characters inserted into it:
+ * - `any`: every character, the default
+ * - `ascii`: printable ASCII, newlines and tabs
+ * - `no_emoji`: every character but emoji, their modifiers and joiners
+ *
+ * Restrictions apply to characters inserted after they are set; text
+ * already in the document stays as it is.
+ */
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Characters allowed in a document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharacterSet {
+    /// Every character
+    #[default]
+    Any,
+    /// Printable ASCII, newlines and tabs
+    Ascii,
+    /// Every character but emoji
+    NoEmoji,
+}
+
+impl CharacterSet {
+    /// Get the name of the character set, as used in messages
+    pub fn name(self) -> &'static str {
+        match self {
+            CharacterSet::Any => "any",
+            CharacterSet::Ascii => "ascii",
+            CharacterSet::NoEmoji => "no_emoji",
+        }
+    }
+
+    /// Check whether a character may be inserted
+    pub fn allows(self, character: char) -> bool {
+        match self {
+            CharacterSet::Any => true,
+            CharacterSet::Ascii => matches!(character, ' '..='~' | '\n' | '\t'),
+            CharacterSet::NoEmoji => !is_emoji(character),
+        }
+    }
+
+    /// Find the first character of a text that may not be inserted
+    pub fn first_disallowed(self, text: &str) -> Option<char> {
+        text.chars().find(|&character| !self.allows(character))
+    }
+}
+
+impl fmt::Display for CharacterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Check whether a character is an emoji, or a modifier or joiner used to
+/// compose one
+fn is_emoji(character: char) -> bool {
+    matches!(
+        u32::from(character),
+        // Pictographs, emoticons, transport and map symbols, flags, skin
+        // tones and supplemental symbols
+        0x1F000..=0x1FAFF
+            // Miscellaneous symbols and dingbats
+            | 0x2600..=0x27BF
+            // Stars, arrows and squares used as emoji
+            | 0x2B00..=0x2BFF
+            // Watches, hourglasses and media controls
+            | 0x231A..=0x231B
+            | 0x23E9..=0x23FA
+            // Zero width joiner and emoji variation selector
+            | 0x200D
+            | 0xFE0F
+            // Tags of subdivision flags
+            | 0xE0020..=0xE007F
+    )
+}
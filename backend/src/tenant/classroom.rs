@@ -0,0 +1,158 @@
+/*
+ * File: src/tenant/classroom.rs
+ * Purpose: Document owners and instructor-controlled editing windows
+ *
+ * Each tenant keeps the classroom settings of its documents:
+ * - The client that creates a document owns it
+ * - The owner can freeze a document, after which only the owner can edit
+ *   it, and unfreeze it again
+ * - The owner can make breakout copies of a document, one per student,
+ *   named `<document>-<student>`; the owner owns the copies too
+ * - The owner can restrict the characters inserted into a document, see
+ *   `charset`
+ *
+ * Ownership belongs to a connection: a client ID is assigned per
+ * connection, so an owner that reconnects is an ordinary client.
+ */
+
+use std::collections::HashMap;
+use parking_lot::RwLock;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::tenant::{aliases::validate_slug, charset::CharacterSet};
+
+/// Classroom-specific errors
+#[derive(Error, Debug, PartialEq)]
+pub enum ClassroomError {
+    #[error("Only the owner of document {0} can change its settings")]
+    NotOwner(String),
+    #[error("Document {0} is frozen by its owner")]
+    Frozen(String),
+    #[error("Invalid breakout name: {0}")]
+    InvalidBreakout(String),
+    #[error("Character {character:?} (U+{code:04X}) is not allowed in document {document_id}, which accepts {charset} characters only", code = u32::from(*.character))]
+    DisallowedCharacter {
+        document_id: String,
+        character: char,
+        charset: CharacterSet,
+    },
+}
+
+/// Classroom settings of a document
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DocumentSettings {
+    /// Client that owns the document
+    pub owner: Option<String>,
+    /// Whether editing is limited to the owner
+    pub frozen: bool,
+    /// Characters that may be inserted
+    pub charset: CharacterSet,
+}
+
+/// Classroom settings of a tenant's documents
+#[derive(Debug, Default)]
+pub struct ClassroomTable {
+    settings: RwLock<HashMap<String, DocumentSettings>>,
+}
+
+impl ClassroomTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a client the owner of a document that has none.
+    /// Returns whether the client owns the document afterwards.
+    pub fn claim(&self, document_id: &str, client_id: &str) -> bool {
+        let mut settings = self.settings.write();
+        let owner = settings.entry(document_id.to_string()).or_default().owner.get_or_insert_with(|| client_id.to_string());
+        owner == client_id
+    }
+
+    /// Get a document's settings
+    pub fn settings(&self, document_id: &str) -> DocumentSettings {
+        self.settings.read().get(document_id).cloned().unwrap_or_default()
+    }
+
+    /// Check that a client may edit a document
+    pub fn check_edit(&self, document_id: &str, client_id: &str) -> Result<(), ClassroomError> {
+        match self.settings.read().get(document_id) {
+            Some(settings) if settings.frozen && settings.owner.as_deref() != Some(client_id) => {
+                Err(ClassroomError::Frozen(document_id.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Freeze or unfreeze a document on behalf of its owner
+    pub fn set_frozen(&self, document_id: &str, client_id: &str, frozen: bool) -> Result<DocumentSettings, ClassroomError> {
+        let mut settings = self.settings.write();
+        match settings.get_mut(document_id) {
+            Some(settings) if settings.owner.as_deref() == Some(client_id) => {
+                settings.frozen = frozen;
+                Ok(settings.clone())
+            }
+            _ => Err(ClassroomError::NotOwner(document_id.to_string())),
+        }
+    }
+
+    /// Restrict the characters of a document on behalf of its owner
+    pub fn set_charset(&self, document_id: &str, client_id: &str, charset: CharacterSet) -> Result<DocumentSettings, ClassroomError> {
+        let mut settings = self.settings.write();
+        match settings.get_mut(document_id) {
+            Some(settings) if settings.owner.as_deref() == Some(client_id) => {
+                settings.charset = charset;
+                Ok(settings.clone())
+            }
+            _ => Err(ClassroomError::NotOwner(document_id.to_string())),
+        }
+    }
+
+    /// Check that a document accepts every character of a text
+    pub fn check_text(&self, document_id: &str, text: &str) -> Result<(), ClassroomError> {
+        let charset = match self.settings.read().get(document_id) {
+            Some(settings) => settings.charset,
+            None => return Ok(()),
+        };
+        match charset.first_disallowed(text) {
+            Some(character) => Err(ClassroomError::DisallowedCharacter {
+                document_id: document_id.to_string(),
+                character,
+                charset,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that a client owns a document
+    pub fn check_owner(&self, document_id: &str, client_id: &str) -> Result<(), ClassroomError> {
+        if self.settings(document_id).owner.as_deref() == Some(client_id) {
+            Ok(())
+        } else {
+            Err(ClassroomError::NotOwner(document_id.to_string()))
+        }
+    }
+
+    /// Forget the settings of a removed document
+    pub fn remove_document(&self, document_id: &str) {
+        self.settings.write().remove(document_id);
+    }
+
+    /// Remove a document's settings to move them to another tenant
+    pub fn take_document(&self, document_id: &str) -> Option<DocumentSettings> {
+        self.settings.write().remove(document_id)
+    }
+
+    /// Set the settings of a document moved in from another tenant
+    pub fn insert_document(&self, document_id: &str, settings: DocumentSettings) {
+        self.settings.write().insert(document_id.to_string(), settings);
+    }
+}
+
+/// Get the ID of a student's breakout copy of a document. Student names
+/// follow the slug rules: 3 to 64 characters of `[a-z0-9-]`.
+pub fn breakout_id(document_id: &str, student: &str) -> Result<String, ClassroomError> {
+    validate_slug(student).map_err(|_| ClassroomError::InvalidBreakout(student.to_string()))?;
+    Ok(format!("{}-{}", document_id, student))
+}
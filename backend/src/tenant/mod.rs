@@ -0,0 +1,23 @@
+/*
+ * File: src/tenant/mod.rs
+ * Purpose: Module organization for multi-tenancy
+ *
+ * This module contains:
+ * - aliases: Human-friendly document slugs
+ * - charset: Characters a document accepts
+ * - classroom: Document owners, frozen documents, and breakout copies
+ * - passwords: Pre-shared passwords of documents, with lockout
+ * - registry: Tenant definitions, access keys, and document namespacing
+ */
+
+pub mod aliases;
+pub mod charset;
+pub mod classroom;
+pub mod passwords;
+pub mod registry;
+
+pub use aliases::{AliasError, AliasTable};
+pub use charset::CharacterSet;
+pub use classroom::{ClassroomError, ClassroomTable, DocumentSettings};
+pub use passwords::{PasswordConfig, PasswordError, PasswordTable};
+pub use registry::{Tenant, TenantConfig, TenantError, TenantRegistry, DEFAULT_TENANT};
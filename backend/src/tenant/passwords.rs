@@ -0,0 +1,231 @@
+/*
+ * File: src/tenant/passwords.rs
+ * Purpose: Pre-shared passwords of documents
+ *
+ * For small groups that don't want accounts, the owner of a document can
+ * protect it with a password. Clients of the tenant then have to unlock
+ * the document with the password before anything else addressing it,
+ * reads included; the unlock lasts for the connection.
+ *
+ * Only the hash of a password is kept (see `security::passwords`). After
+ * `max_failures` wrong passwords in a row, a document refuses unlock
+ * attempts for `lockout`, whoever makes them, since client IDs change with
+ * every connection. An operator can reset a document's password through
+ * the admin API, which also lifts its lockout.
+ *
+ * Clients with access can hand out share tokens (`security::tokens`)
+ * instead of the password. A share token names a fingerprint of the hash,
+ * so it stops unlocking the document once the password changes.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::security::passwords::{DEFAULT_ITERATIONS, DEFAULT_MEMORY_KIB};
+
+/// Hashing cost of document passwords and lockout after wrong ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordConfig {
+    /// Argon2id memory of new hashes, in KiB
+    pub memory_kib: u32,
+    /// Argon2id passes over the memory of new hashes
+    pub iterations: u32,
+    /// Wrong passwords in a row that lock a document
+    pub max_failures: u32,
+    /// How long a locked document refuses unlock attempts
+    pub lockout: Duration,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: DEFAULT_MEMORY_KIB,
+            iterations: DEFAULT_ITERATIONS,
+            max_failures: 5,
+            lockout: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// Document password errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PasswordError {
+    #[error("Document {0} is protected by a password; unlock it first")]
+    Locked(String),
+    #[error("Wrong password for document {0}")]
+    Wrong(String),
+    #[error("Document {document_id} refuses unlock attempts for {seconds} more seconds after too many wrong passwords")]
+    LockedOut { document_id: String, seconds: u64 },
+    #[error("Document {0} is checking other unlock attempts; try again shortly")]
+    Busy(String),
+    #[error("Document {0} has no password")]
+    NotProtected(String),
+    #[error("Share token for document {0} was revoked by a new password")]
+    Revoked(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct DocumentPassword {
+    hash: String,
+    /// Wrong passwords since the last right one
+    failures: u32,
+    /// Unlock attempts begun and not yet finished, which count toward the
+    /// lockout until they do
+    checking: u32,
+    locked_until: Option<Instant>,
+    /// Clients that unlocked the document
+    unlocked: HashSet<String>,
+}
+
+/// Passwords of a tenant's documents
+#[derive(Debug, Default)]
+pub struct PasswordTable {
+    documents: RwLock<HashMap<String, DocumentPassword>>,
+}
+
+impl PasswordTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether a document has a password
+    pub fn is_protected(&self, document_id: &str) -> bool {
+        self.documents.read().contains_key(document_id)
+    }
+
+    /// Check that a client may address a document: it has no password, or
+    /// the client unlocked it
+    pub fn check_access(&self, document_id: &str, client_id: &str) -> Result<(), PasswordError> {
+        match self.documents.read().get(document_id) {
+            Some(password) if !password.unlocked.contains(client_id) => Err(PasswordError::Locked(document_id.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get a fingerprint of a document's password hash, which changes with
+    /// the password; None if it has no password
+    pub fn fingerprint(&self, document_id: &str) -> Option<String> {
+        self.documents.read().get(document_id).map(|password| fingerprint(&password.hash))
+    }
+
+    /// Unlock a document for a client that presented a share token for
+    /// its current password. Lockouts don't apply, as tokens can't be
+    /// guessed.
+    pub fn grant(&self, document_id: &str, client_id: &str, presented: &str) -> Result<(), PasswordError> {
+        let mut documents = self.documents.write();
+        let password = documents.get_mut(document_id).ok_or_else(|| PasswordError::NotProtected(document_id.to_string()))?;
+        if fingerprint(&password.hash) != presented {
+            return Err(PasswordError::Revoked(document_id.to_string()));
+        }
+        password.unlocked.insert(client_id.to_string());
+        Ok(())
+    }
+
+    /// Protect a document with a password hash, or remove its password
+    /// with None. The client setting it keeps access.
+    pub fn set(&self, document_id: &str, client_id: &str, hash: Option<String>) {
+        let mut documents = self.documents.write();
+        match hash {
+            Some(hash) => {
+                let password = DocumentPassword {
+                    hash,
+                    unlocked: HashSet::from([client_id.to_string()]),
+                    ..Default::default()
+                };
+                documents.insert(document_id.to_string(), password);
+            }
+            None => {
+                documents.remove(document_id);
+            }
+        }
+    }
+
+    /// Start an unlock attempt: get the hash to check the password against,
+    /// unless the document is locked out. The attempt is reserved under
+    /// the same lock, so attempts checked at once can't get past
+    /// `max_failures` between them.
+    pub fn begin_unlock(&self, document_id: &str, config: &PasswordConfig, now: Instant) -> Result<String, PasswordError> {
+        let mut documents = self.documents.write();
+        let password = documents.get_mut(document_id).ok_or_else(|| PasswordError::NotProtected(document_id.to_string()))?;
+        if let Some(until) = password.locked_until.filter(|until| *until > now) {
+            return Err(PasswordError::LockedOut {
+                document_id: document_id.to_string(),
+                seconds: until.duration_since(now).as_secs().max(1),
+            });
+        }
+        if password.failures + password.checking >= config.max_failures {
+            return Err(PasswordError::Busy(document_id.to_string()));
+        }
+        password.checking += 1;
+        Ok(password.hash.clone())
+    }
+
+    /// Finish an unlock attempt with whether the password matched. A
+    /// right password unlocks the document for the client; a wrong one
+    /// counts toward the lockout.
+    pub fn finish_unlock(
+        &self,
+        document_id: &str,
+        client_id: &str,
+        matched: bool,
+        config: &PasswordConfig,
+        now: Instant,
+    ) -> Result<(), PasswordError> {
+        let mut documents = self.documents.write();
+        // The password may have been reset while it was checked
+        let password = documents.get_mut(document_id).ok_or_else(|| PasswordError::NotProtected(document_id.to_string()))?;
+        password.checking = password.checking.saturating_sub(1);
+        if matched {
+            password.failures = 0;
+            password.unlocked.insert(client_id.to_string());
+            return Ok(());
+        }
+        password.failures += 1;
+        if password.failures >= config.max_failures {
+            password.failures = 0;
+            password.locked_until = Some(now + config.lockout);
+            log::warn!("Document {} locked for {:?} after {} wrong passwords", document_id, config.lockout, config.max_failures);
+        }
+        Err(PasswordError::Wrong(document_id.to_string()))
+    }
+
+    /// Remove a document's password and lockout, on behalf of an operator
+    pub fn reset(&self, document_id: &str) -> Result<(), PasswordError> {
+        self.documents.write().remove(document_id).map(drop).ok_or_else(|| PasswordError::NotProtected(document_id.to_string()))
+    }
+
+    /// Forget the unlocks of a client that disconnected
+    pub fn forget_client(&self, client_id: &str) {
+        for password in self.documents.write().values_mut() {
+            password.unlocked.remove(client_id);
+        }
+    }
+
+    /// Forget the password of a removed document
+    pub fn remove_document(&self, document_id: &str) {
+        self.documents.write().remove(document_id);
+    }
+
+    /// Remove a document's password hash to move it to another tenant;
+    /// unlocks and lockouts stay behind
+    pub fn take_document(&self, document_id: &str) -> Option<String> {
+        self.documents.write().remove(document_id).map(|password| password.hash)
+    }
+
+    /// Protect a document moved in from another tenant with its hash
+    pub fn insert_document(&self, document_id: &str, hash: String) {
+        self.documents.write().insert(document_id.to_string(), DocumentPassword { hash, ..Default::default() });
+    }
+}
+
+/// Fingerprint of a password hash: the start of its SHA-256 digest, which
+/// tells passwords apart without revealing the hash
+fn fingerprint(hash: &str) -> String {
+    hex::encode(&Sha256::digest(hash.as_bytes())[..8])
+}
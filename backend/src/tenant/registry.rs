@@ -0,0 +1,225 @@
+/*
+ * File: src/tenant/registry.rs
+ * Purpose: Tenant registry and namespace isolation
+ *
+ * Responsibilities:
+ * - Define tenants with their access keys and quota settings
+ * - Namespace document keys per tenant
+ * - Authorize connections against a tenant's access keys
+ * - Hold each tenant's document slugs
+ *
+ * Every tenant-owned resource is keyed as `<tenant>/<id>`. Tenant IDs are
+ * restricted to `[A-Za-z0-9_-]`, so the first `/` always separates the
+ * tenant from the resource and keys from different tenants never collide.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use parking_lot::RwLock;
+use thiserror::Error;
+
+use crate::{
+    blocks::{ChecklistTable, LinkIndex},
+    security::{secrets_match, EncryptionMode},
+    tenant::{aliases::AliasTable, classroom::ClassroomTable, passwords::PasswordTable},
+    undo::UndoHistory,
+};
+use crate::websocket::quota::{QuotaConfig, QuotaTracker};
+
+/// Tenant used when a connection doesn't name one
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Tenant-specific errors
+#[derive(Error, Debug)]
+pub enum TenantError {
+    #[error("Invalid tenant ID: {0}")]
+    InvalidId(String),
+    #[error("Tenant {0} not found")]
+    NotFound(String),
+    #[error("Tenant {0} already exists")]
+    Exists(String),
+    #[error("Access to tenant {0} denied")]
+    Unauthorized(String),
+}
+
+/// Configuration of a single tenant
+#[derive(Debug, Clone, Default)]
+pub struct TenantConfig {
+    /// Tenant identifier
+    pub id: String,
+    /// Keys accepted for connections to this tenant; open when empty
+    pub api_keys: Vec<String>,
+    /// Quota overrides; the server-wide quota applies when None
+    pub quota: Option<QuotaConfig>,
+    /// Whether clients encrypt document content end to end
+    pub encryption: EncryptionMode,
+}
+
+/// An isolated namespace of documents and clients
+pub struct Tenant {
+    id: String,
+    api_keys: HashSet<String>,
+    quota: QuotaTracker,
+    aliases: AliasTable,
+    classroom: ClassroomTable,
+    passwords: PasswordTable,
+    checklists: ChecklistTable,
+    links: LinkIndex,
+    undo: UndoHistory,
+    encryption: EncryptionMode,
+}
+
+impl Tenant {
+    /// Get the tenant identifier
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the quota tracker for this tenant's documents
+    pub fn quota(&self) -> &QuotaTracker {
+        &self.quota
+    }
+
+    /// Get the slugs of this tenant's documents
+    pub fn aliases(&self) -> &AliasTable {
+        &self.aliases
+    }
+
+    /// Get the owners and classroom settings of this tenant's documents
+    pub fn classroom(&self) -> &ClassroomTable {
+        &self.classroom
+    }
+
+    /// Get the passwords of this tenant's documents
+    pub fn passwords(&self) -> &PasswordTable {
+        &self.passwords
+    }
+
+    /// Get the checklists of this tenant's documents
+    pub fn checklists(&self) -> &ChecklistTable {
+        &self.checklists
+    }
+
+    /// Get the links between this tenant's documents
+    pub fn links(&self) -> &LinkIndex {
+        &self.links
+    }
+
+    /// Get who can read this tenant's document content
+    pub fn encryption(&self) -> EncryptionMode {
+        self.encryption
+    }
+
+    /// Get the undo scopes and histories of this tenant's documents
+    pub fn undo(&self) -> &UndoHistory {
+        &self.undo
+    }
+
+    /// Check an access key presented by a client
+    pub fn authorize(&self, key: Option<&str>) -> Result<(), TenantError> {
+        if self.api_keys.is_empty() || key.is_some_and(|k| self.api_keys.iter().any(|api_key| secrets_match(k, api_key))) {
+            Ok(())
+        } else {
+            Err(TenantError::Unauthorized(self.id.clone()))
+        }
+    }
+
+    /// Build the namespaced key for a tenant resource
+    pub fn scoped(&self, id: &str) -> String {
+        format!("{}/{}", self.id, id)
+    }
+
+    /// Strip this tenant's namespace from a key, if the key belongs to it
+    pub fn unscoped<'a>(&self, key: &'a str) -> Option<&'a str> {
+        key.strip_prefix(self.id.as_str())?.strip_prefix('/')
+    }
+}
+
+/// Registry of all known tenants
+pub struct TenantRegistry {
+    tenants: RwLock<HashMap<String, Arc<Tenant>>>,
+    /// Quota applied to tenants without their own
+    default_quota: QuotaConfig,
+}
+
+impl TenantRegistry {
+    /// Create a registry containing the default tenant and the configured ones
+    pub fn new(default_quota: QuotaConfig, configs: Vec<TenantConfig>) -> Result<Self, TenantError> {
+        let registry = Self {
+            tenants: RwLock::new(HashMap::new()),
+            default_quota,
+        };
+
+        if !configs.iter().any(|c| c.id == DEFAULT_TENANT) {
+            registry.register(TenantConfig {
+                id: DEFAULT_TENANT.to_string(),
+                ..Default::default()
+            })?;
+        }
+        for config in configs {
+            registry.register(config)?;
+        }
+        Ok(registry)
+    }
+
+    /// Add a tenant
+    pub fn register(&self, config: TenantConfig) -> Result<Arc<Tenant>, TenantError> {
+        validate_tenant_id(&config.id)?;
+
+        let mut tenants = self.tenants.write();
+        if tenants.contains_key(&config.id) {
+            return Err(TenantError::Exists(config.id));
+        }
+
+        let tenant = Arc::new(Tenant {
+            id: config.id.clone(),
+            api_keys: config.api_keys.into_iter().collect(),
+            quota: QuotaTracker::new(config.quota.unwrap_or_else(|| self.default_quota.clone())),
+            aliases: AliasTable::new(),
+            classroom: ClassroomTable::new(),
+            passwords: PasswordTable::new(),
+            checklists: ChecklistTable::new(),
+            links: LinkIndex::new(),
+            undo: UndoHistory::new(),
+            encryption: config.encryption,
+        });
+        tenants.insert(config.id, tenant.clone());
+        Ok(tenant)
+    }
+
+    /// Look up a tenant
+    pub fn get(&self, id: &str) -> Result<Arc<Tenant>, TenantError> {
+        self.tenants.read()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| TenantError::NotFound(id.to_string()))
+    }
+
+    /// Remove a tenant from the registry
+    pub fn remove(&self, id: &str) -> Result<Arc<Tenant>, TenantError> {
+        self.tenants.write()
+            .remove(id)
+            .ok_or_else(|| TenantError::NotFound(id.to_string()))
+    }
+
+    /// Get the IDs of all registered tenants
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.tenants.read().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// Check that a tenant ID is non-empty and uses only `[A-Za-z0-9_-]`
+pub fn validate_tenant_id(id: &str) -> Result<(), TenantError> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(TenantError::InvalidId(id.to_string()))
+    }
+}
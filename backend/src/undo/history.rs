@@ -0,0 +1,210 @@
+/*
+ * File: src/undo/history.rs
+ * Purpose: Per-document history of undoable operations
+ *
+ * The history keeps the user operations applied to each document, newest
+ * last, with the client that sent them. Undoing takes the newest entry in
+ * scope and turns it into its inverse against the current document:
+ * - An insert becomes a delete of the inserted character
+ * - A delete becomes an insert of the same character right after its
+ *   tombstone; older entries for the old position follow it there
+ * - A range delete becomes an insert per character it removed, so one
+ *   undo step restores the whole selection. Each run of removed
+ *   characters gets its positions at once, after the run's first
+ *   tombstone, so it keeps its order whatever was inserted around it
+ *
+ * An entry is a group of operations undone together. Entries that no
+ * longer have an effect, such as an insert whose character another client
+ * already deleted, are dropped and the next one is tried. Inverses are
+ * applied as ordinary operations.
+ *
+ * Each inverse goes on a redo stack, and redoing inverts it the same way
+ * and puts the result back in the history, so it can be undone again. A
+ * new edit clears the redo entries in its scope. Deleted characters are
+ * read from the operation log, which garbage collection keeps, so a
+ * delete can be undone or redone after its tombstone is collected.
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+};
+use parking_lot::Mutex;
+
+use crate::{
+    crdt::{Document, Operation, Position},
+    undo::{UndoError, UndoScope},
+};
+
+/// Most undoable operations kept per document; the oldest are dropped
+pub const UNDO_HISTORY_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone)]
+struct UndoEntry {
+    client_id: String,
+    /// Operations undone in one step, in the order they were applied
+    operations: Vec<Operation>,
+}
+
+#[derive(Debug, Default)]
+struct DocumentHistory {
+    scope: UndoScope,
+    entries: Vec<UndoEntry>,
+    redo: Vec<UndoEntry>,
+}
+
+impl DocumentHistory {
+    /// Find the newest entry of a stack in a client's scope
+    fn newest(&self, stack: &[UndoEntry], client_id: &str) -> Option<usize> {
+        match self.scope {
+            UndoScope::Own => stack.iter().rposition(|entry| entry.client_id == client_id),
+            UndoScope::Global => stack.len().checked_sub(1),
+        }
+    }
+
+    /// Turn a group of operations into the ones reverting it against the
+    /// current document, newest first, sent on behalf of `client_id`.
+    /// Returns `None` when none of them has an effect any more.
+    fn invert(&mut self, operations: Vec<Operation>, document: &Document, client_id: &str) -> Option<Vec<Operation>> {
+        let mut inverses = Vec::new();
+        for operation in operations.into_iter().rev() {
+            match operation {
+                Operation::Insert { position, client_id: author, .. } => {
+                    if document.character_by(&position, &author).is_some() {
+                        inverses.push(Operation::delete_of(client_id.to_string(), position, author));
+                    }
+                }
+                Operation::Delete { position, author, .. } => {
+                    let visible = match &author {
+                        Some(author) => document.character_by(&position, author),
+                        None => document.character_at(&position),
+                    };
+                    let character = document.inserted_character(&position, author.as_deref());
+                    if let Some(character) = character.filter(|_| visible.is_none()) {
+                        inverses.extend(self.restore(vec![(character, position)], document, client_id));
+                    }
+                }
+                Operation::DeleteRange { start, end, seen, .. } => {
+                    let mut run = Vec::new();
+                    for (character, position) in document.range_characters(&start, &end, &seen) {
+                        if document.character_at(&position).is_some() {
+                            inverses.extend(self.restore(mem::take(&mut run), document, client_id));
+                        } else {
+                            run.push((character, position));
+                        }
+                    }
+                    inverses.extend(self.restore(run, document, client_id));
+                }
+            }
+        }
+        (!inverses.is_empty()).then_some(inverses)
+    }
+
+    /// Build the inserts restoring a run of deleted characters, in order,
+    /// right after the tombstone of the first, and move older entries for
+    /// their positions there. Characters that are visible again must not
+    /// be part of the run.
+    fn restore(&mut self, run: Vec<(char, Position)>, document: &Document, client_id: &str) -> Vec<Operation> {
+        let Some((_, first)) = run.first() else {
+            return Vec::new();
+        };
+        let restored = document.positions_after(first, run.len());
+        let moved: BTreeMap<&Position, &Position> = run.iter().map(|(_, position)| position).zip(&restored).collect();
+        let operations = self.entries.iter_mut().chain(self.redo.iter_mut()).flat_map(|entry| entry.operations.iter_mut());
+        for operation in operations {
+            if let Operation::Insert { position: old, .. } | Operation::Delete { position: old, .. } = operation {
+                if let Some(&new) = moved.get(old) {
+                    *old = new.clone();
+                }
+            }
+        }
+        run.into_iter()
+            .zip(restored)
+            .map(|((character, _), position)| Operation::insert(client_id.to_string(), character, position))
+            .collect()
+    }
+}
+
+/// Undo scopes and histories of a tenant's documents
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    documents: Mutex<HashMap<String, DocumentHistory>>,
+}
+
+impl UndoHistory {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a document's undo scope
+    pub fn scope(&self, document_id: &str) -> UndoScope {
+        self.documents.lock().get(document_id).map(|history| history.scope).unwrap_or_default()
+    }
+
+    /// Set a document's undo scope. Recorded operations are kept.
+    pub fn set_scope(&self, document_id: &str, scope: UndoScope) {
+        self.documents.lock().entry(document_id.to_string()).or_default().scope = scope;
+    }
+
+    /// Record an operation a client applied to a document
+    pub fn record(&self, document_id: &str, client_id: &str, operation: &Operation) {
+        let mut documents = self.documents.lock();
+        let history = documents.entry(document_id.to_string()).or_default();
+        match history.scope {
+            UndoScope::Own => history.redo.retain(|entry| entry.client_id != client_id),
+            UndoScope::Global => history.redo.clear(),
+        }
+        let entries = &mut history.entries;
+        if entries.len() >= UNDO_HISTORY_LIMIT {
+            entries.remove(0);
+        }
+        entries.push(UndoEntry {
+            client_id: client_id.to_string(),
+            operations: vec![operation.clone()],
+        });
+    }
+
+    /// Take the newest entry in the client's scope and return the
+    /// operations reverting it, sent on behalf of `client_id`. The caller
+    /// applies them to `document`, in order.
+    pub fn undo(&self, document_id: &str, document: &Document, client_id: &str) -> Result<Vec<Operation>, UndoError> {
+        let mut documents = self.documents.lock();
+        let history = documents
+            .get_mut(document_id)
+            .ok_or_else(|| UndoError::NothingToUndo(document_id.to_string()))?;
+
+        while let Some(index) = history.newest(&history.entries, client_id) {
+            let entry = history.entries.remove(index);
+            if let Some(inverse) = history.invert(entry.operations, document, client_id) {
+                history.redo.push(UndoEntry { client_id: entry.client_id, operations: inverse.clone() });
+                return Ok(inverse);
+            }
+        }
+        Err(UndoError::NothingToUndo(document_id.to_string()))
+    }
+
+    /// Take the newest undo in the client's scope and return the
+    /// operations reverting it, sent on behalf of `client_id`. The caller
+    /// applies them to `document`, in order; they can be undone again.
+    pub fn redo(&self, document_id: &str, document: &Document, client_id: &str) -> Result<Vec<Operation>, UndoError> {
+        let mut documents = self.documents.lock();
+        let history = documents
+            .get_mut(document_id)
+            .ok_or_else(|| UndoError::NothingToRedo(document_id.to_string()))?;
+
+        while let Some(index) = history.newest(&history.redo, client_id) {
+            let entry = history.redo.remove(index);
+            if let Some(inverse) = history.invert(entry.operations, document, client_id) {
+                history.entries.push(UndoEntry { client_id: entry.client_id, operations: inverse.clone() });
+                return Ok(inverse);
+            }
+        }
+        Err(UndoError::NothingToRedo(document_id.to_string()))
+    }
+
+    /// Forget the history of a removed document
+    pub fn remove_document(&self, document_id: &str) {
+        self.documents.lock().remove(document_id);
+    }
+}
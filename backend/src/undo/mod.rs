@@ -0,0 +1,75 @@
+/*
+ * File: src/undo/mod.rs
+ * Purpose: Module organization for server-side undo
+ *
+ * This module contains:
+ * - history: Per-document history of undoable operations
+ *
+ * Each document has an undo scope:
+ * - own (default): a client undoes its own most recent edit; edits of
+ *   other clients stay in place, even when they were made in between
+ * - global: any client undoes the most recent edit of the document,
+ *   whoever made it, as in a single-author document
+ *
+ * Redo follows the same scope: a client redoes its own newest undo, or
+ * the newest undo of the document.
+ *
+ * Only operations whose source is undoable by default (user edits) are
+ * recorded; server, bot and import operations are never undone.
+ */
+
+pub mod history;
+
+pub use history::{UndoHistory, UNDO_HISTORY_LIMIT};
+
+use std::{fmt, str::FromStr};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Undo errors
+#[derive(Error, Debug, PartialEq)]
+pub enum UndoError {
+    #[error("Nothing to undo in document {0}")]
+    NothingToUndo(String),
+    #[error("Nothing to redo in document {0}")]
+    NothingToRedo(String),
+}
+
+/// Whose edits an undo reverts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UndoScope {
+    /// Each client undoes its own edits
+    #[default]
+    Own,
+    /// Undo is linear across all clients
+    Global,
+}
+
+impl UndoScope {
+    /// Get the name of the scope, as used in messages
+    pub fn name(self) -> &'static str {
+        match self {
+            UndoScope::Own => "own",
+            UndoScope::Global => "global",
+        }
+    }
+}
+
+impl fmt::Display for UndoScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for UndoScope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "own" => Ok(UndoScope::Own),
+            "global" => Ok(UndoScope::Global),
+            other => Err(format!("Unknown undo scope: {}", other)),
+        }
+    }
+}
@@ -0,0 +1,302 @@
+/*
+ * File: src/websocket/activity.rs
+ * Purpose: Activity feed of each tenant's workspace
+ *
+ * Teams see what happened in their workspace today without following
+ * every edit. Document events are aggregated into activities:
+ * - created: a document came into existence
+ * - renamed: a client gave a document a slug
+ * - heavily_edited: a document received `heavy_edit_operations` changes
+ *   within `heavy_edit_window`; recorded once per window
+ *
+ * The feed is read newest first, a page at a time:
+ *
+ *   GET /activity?limit=50&before=<id>
+ *   GET /t/<tenant>/activity?key=<access key>
+ *
+ *   {"activities": [{"id": 7, "kind": "renamed", "slug": "roadmap",
+ *     "by": "alice", "document_id": "a1b2", "version": 42,
+ *     "timestamp": "..."}], "next": 6}
+ *
+ * `next` is the `before` of the following page, or null on the last one.
+ * Clients on a WebSocket send `subscribeActivity` to get the first page as
+ * `activityPage` and every later activity as `activity`, until
+ * `unsubscribeActivity`.
+ *
+ * Each tenant keeps its newest `capacity` activities, none older than
+ * `retention`. The feed lives in memory, like documents. Temporary
+ * documents never appear in it, and password-protected ones only to
+ * clients that unlocked them.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast;
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    Filter,
+};
+
+use crate::{tenant::DEFAULT_TENANT, websocket::server::ServerState};
+
+/// Activities returned per page unless `limit` asks for fewer
+pub const MAX_ACTIVITIES_PER_PAGE: usize = 200;
+
+/// Activities returned per page without `limit`
+pub(crate) const DEFAULT_ACTIVITY_LIMIT: usize = 50;
+
+/// Activities buffered for subscribers that fall behind
+const UPDATE_CAPACITY: usize = 256;
+
+/// Retention and aggregation of the activity feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActivityConfig {
+    /// Most activities kept per tenant
+    pub capacity: usize,
+    /// Age at which activities are dropped
+    pub retention: Duration,
+    /// Changes to a document within `heavy_edit_window` that make it
+    /// heavily edited
+    pub heavy_edit_operations: u64,
+    pub heavy_edit_window: Duration,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            retention: Duration::from_secs(7 * 24 * 60 * 60),
+            heavy_edit_operations: 500,
+            heavy_edit_window: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// What happened to a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ActivityKind {
+    Created,
+    Renamed {
+        slug: String,
+        /// Client that set the slug
+        by: String,
+    },
+    HeavilyEdited {
+        /// Changes within the window
+        operations: u64,
+    },
+}
+
+/// An entry of a workspace's activity feed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activity {
+    /// Position in the feed; increases with every activity of any tenant
+    pub id: u64,
+    #[serde(flatten)]
+    pub kind: ActivityKind,
+    pub document_id: String,
+    /// Document version when the activity was recorded
+    pub version: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Activities, newest first, and where the next page starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityPage {
+    pub activities: Vec<Activity>,
+    /// `before` of the next page; None on the last page
+    pub next: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Feeds {
+    /// Activities of each tenant, oldest first
+    tenants: HashMap<String, VecDeque<Activity>>,
+    /// Start of each document's edit window and its changes in it, and
+    /// whether it was reported heavily edited, by tenant and document ID
+    edits: HashMap<(String, String), (DateTime<Utc>, u64, bool)>,
+    last_id: u64,
+}
+
+/// Activity feeds of every tenant
+#[derive(Debug)]
+pub struct ActivityFeed {
+    config: ActivityConfig,
+    feeds: Mutex<Feeds>,
+    /// New activities with their tenant, for subscribers
+    updates: broadcast::Sender<(String, Activity)>,
+}
+
+impl Default for ActivityFeed {
+    fn default() -> Self {
+        Self::new(ActivityConfig::default())
+    }
+}
+
+impl ActivityFeed {
+    /// Create empty feeds with the given retention
+    pub fn new(config: ActivityConfig) -> Self {
+        Self {
+            config,
+            feeds: Mutex::new(Feeds::default()),
+            updates: broadcast::channel(UPDATE_CAPACITY).0,
+        }
+    }
+
+    /// Record that a document was created
+    pub fn created(&self, tenant_id: &str, document_id: &str, version: u64, now: DateTime<Utc>) {
+        self.record(tenant_id, document_id, ActivityKind::Created, version, now);
+    }
+
+    /// Record that a client gave a document a slug
+    pub fn renamed(&self, tenant_id: &str, document_id: &str, slug: &str, client_id: &str, version: u64, now: DateTime<Utc>) {
+        let kind = ActivityKind::Renamed { slug: slug.to_string(), by: client_id.to_string() };
+        self.record(tenant_id, document_id, kind, version, now);
+    }
+
+    /// Count a change to a document, recording it as heavily edited once
+    /// its changes in the window reach the threshold
+    pub fn changed(&self, tenant_id: &str, document_id: &str, version: u64, now: DateTime<Utc>) {
+        let window = chrono::Duration::from_std(self.config.heavy_edit_window).unwrap_or(chrono::Duration::MAX);
+        let operations = {
+            let mut feeds = self.feeds.lock();
+            let (start, operations, reported) = feeds.edits
+                .entry((tenant_id.to_string(), document_id.to_string()))
+                .or_insert((now, 0, false));
+            if now - *start >= window {
+                (*start, *operations, *reported) = (now, 0, false);
+            }
+            *operations += 1;
+            if *reported || *operations < self.config.heavy_edit_operations {
+                return;
+            }
+            *reported = true;
+            *operations
+        };
+        self.record(tenant_id, document_id, ActivityKind::HeavilyEdited { operations }, version, now);
+    }
+
+    /// Drop a document that left the tenant from edit counting; its
+    /// activities stay in the feed
+    pub fn forget(&self, tenant_id: &str, document_id: &str) {
+        self.feeds.lock().edits.remove(&(tenant_id.to_string(), document_id.to_string()));
+    }
+
+    /// Get up to `limit` of a tenant's activities of visible documents
+    /// before an ID, newest first. Without an ID, the page starts at the
+    /// newest activity.
+    pub fn page(
+        &self,
+        tenant_id: &str,
+        before: Option<u64>,
+        limit: usize,
+        now: DateTime<Utc>,
+        visible: impl Fn(&str) -> bool,
+    ) -> ActivityPage {
+        let mut feeds = self.feeds.lock();
+        let Some(activities) = feeds.tenants.get_mut(tenant_id) else {
+            return ActivityPage { activities: Vec::new(), next: None };
+        };
+        self.expire(activities, now);
+        let mut older = activities.iter().rev().filter(|activity| before.is_none_or(|before| activity.id < before) && visible(&activity.document_id));
+        let page: Vec<Activity> = older.by_ref().take(limit).cloned().collect();
+        let next = match (page.last(), older.next()) {
+            (Some(last), Some(_)) => Some(last.id),
+            _ => None,
+        };
+        ActivityPage { activities: page, next }
+    }
+
+    /// Follow new activities of every tenant
+    pub fn subscribe(&self) -> broadcast::Receiver<(String, Activity)> {
+        self.updates.subscribe()
+    }
+
+    fn record(&self, tenant_id: &str, document_id: &str, kind: ActivityKind, version: u64, now: DateTime<Utc>) {
+        let activity = {
+            let mut feeds = self.feeds.lock();
+            feeds.last_id += 1;
+            let activity = Activity {
+                id: feeds.last_id,
+                kind,
+                document_id: document_id.to_string(),
+                version,
+                timestamp: now,
+            };
+            let activities = feeds.tenants.entry(tenant_id.to_string()).or_default();
+            activities.push_back(activity.clone());
+            while activities.len() > self.config.capacity {
+                activities.pop_front();
+            }
+            self.expire(activities, now);
+            activity
+        };
+        let _ = self.updates.send((tenant_id.to_string(), activity));
+    }
+
+    /// Drop activities older than the retention
+    fn expire(&self, activities: &mut VecDeque<Activity>, now: DateTime<Utc>) {
+        let retention = chrono::Duration::from_std(self.config.retention).unwrap_or(chrono::Duration::MAX);
+        while activities.front().is_some_and(|activity| now - activity.timestamp > retention) {
+            activities.pop_front();
+        }
+    }
+}
+
+/// Build the filter serving activity feeds
+pub(crate) fn routes(state: ServerState) -> BoxedFilter<(Response<Body>,)> {
+    warp::get()
+        .and(
+            warp::path!("activity")
+                .map(|| DEFAULT_TENANT.to_string())
+                .or(warp::path!("t" / String / "activity"))
+                .unify(),
+        )
+        .and(warp::query::<HashMap<String, String>>())
+        .map(move |tenant_id: String, query: HashMap<String, String>| respond(&state, &tenant_id, &query))
+        .boxed()
+}
+
+/// Answer a request for a page of the feed
+fn respond(state: &ServerState, tenant_id: &str, query: &HashMap<String, String>) -> Response<Body> {
+    let tenant = match state.tenant(tenant_id) {
+        Ok(tenant) => tenant,
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    if let Err(e) = tenant.authorize(query.get("key").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e.to_string());
+    }
+    let before = match query.get("before").map(|before| before.parse::<u64>()) {
+        None => None,
+        Some(Ok(before)) => Some(before),
+        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "before must be an activity ID".to_string()),
+    };
+    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => DEFAULT_ACTIVITY_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(MAX_ACTIVITIES_PER_PAGE),
+        Some(_) => return plain(StatusCode::BAD_REQUEST, "limit must be a positive number".to_string()),
+    };
+
+    // Protected documents are left out, as they are from listings
+    let page = state.activity().page(tenant.id(), before, limit, Utc::now(), |document_id| !tenant.passwords().is_protected(document_id));
+    let mut response = Response::new(Body::from(json!(page).to_string()));
+    if let Ok(value) = "application/json".parse() {
+        response.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    response
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,574 @@
+/*
+ * File: src/websocket/admin.rs
+ * Purpose: HTTP admin API for bulk maintenance actions
+ *
+ * Operators start bulk actions as jobs and poll them until they finish:
+ *
+ *   POST   /admin/jobs        {"action": "compact", "tenant": "acme", "prefix": "notes-"}
+ *   GET    /admin/jobs
+ *   GET    /admin/jobs/<id>
+ *   DELETE /admin/jobs/<id>
+ *   GET    /admin/overload
+ *   GET    /admin/storage
+ *   GET    /admin/bandwidth?limit=20
+ *   GET    /admin/memory?limit=20
+ *   GET    /admin/abuse
+ *   DELETE /admin/abuse/<tenant>/<client_id>
+ *   DELETE /admin/passwords/<tenant>/<document_id>
+ *   GET    /admin/skew
+ *   POST   /admin/skew/<tenant>/<client_id>/release
+ *   DELETE /admin/skew/<tenant>/<client_id>
+ *   POST   /admin/documents/<tenant>/<document_id>/transfer
+ *
+ * Requests carry `Authorization: Bearer <token>` with the token of
+ * `AdminConfig`; without a configured token the API is disabled. Actions:
+ * - compact: drop the tombstones of deleted characters from every document
+ *   whose ID starts with `prefix`, in one tenant or all of them. Tombstones
+ *   whose deletion a connected client hasn't acknowledged yet are kept.
+ *   With `"dry_run": true` nothing is dropped; the job result holds each
+ *   document's `GcReport` instead, naming the clients holding tombstones
+ *   back
+ * - export: render every document of a tenant in `format` (`text` or
+ *   `html`); the job result maps document IDs to their exports
+ * - purge_trash, reindex: recognized but not supported, as the backend
+ *   keeps no trash and no search index
+ *
+ * Actions run on the server's job queue (see `jobs`), a document at a
+ * time, and report progress. `GET /admin/jobs` lists every job on the
+ * queue, whoever started it; `DELETE` cancels one. `GET /admin/overload`
+ * reports whether the server is shedding work, with its operation latency.
+ * `GET /admin/storage` lists the documents the store failed to write,
+ * which are served from memory until it recovers (see `storage::health`).
+ * `GET /admin/bandwidth` lists the connected clients and the documents
+ * that sent and received the most bytes, heaviest first.
+ * `GET /admin/memory` breaks down what documents hold in memory now, the
+ * heaviest first, with the samples taken over time (see `metrics::memory`).
+ * `GET /admin/abuse` lists the alerts raised for clients editing abusively
+ * fast and the clients whose writes are restricted (see `metrics::abuse`);
+ * deleting one lifts its restriction. Deleting a document's password
+ * resets it for an owner who lost it, and lifts its lockout (see
+ * `tenant::passwords`).
+ * `GET /admin/skew` lists the clients flagged for skewed timestamps (see
+ * `skew`); releasing one applies the operations it had quarantined, and
+ * deleting one discards them. Transferring a document moves it to another
+ * tenant (see `transfer`).
+ */
+
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response, StatusCode},
+    hyper::{body::Bytes, Body},
+    Filter,
+};
+
+use crate::{
+    blocks::{export, ExportFormat},
+    crdt::Document,
+    jobs::{JobContext, JobError},
+    security::secrets_match,
+    tenant::{Tenant, TenantError},
+    websocket::{
+        server::{EditorServer, ServerState},
+        transfer::{TransferError, TransferRequest},
+    },
+};
+
+/// Clients and documents listed by `GET /admin/bandwidth` without `limit`
+const DEFAULT_BANDWIDTH_LIMIT: usize = 20;
+
+/// Documents listed by `GET /admin/memory` without `limit`
+const DEFAULT_MEMORY_LIMIT: usize = 20;
+
+/// Access to the admin API
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdminConfig {
+    /// Bearer token admin requests must present; the API is disabled when None
+    pub token: Option<String>,
+}
+
+/// A bulk action requested through the admin API
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AdminAction {
+    /// Drop tombstones from matching documents
+    Compact {
+        /// Tenant to compact; every tenant when None
+        #[serde(default)]
+        tenant: Option<String>,
+        /// Prefix of the document IDs to compact
+        #[serde(default)]
+        prefix: String,
+        /// Report what would be dropped instead of dropping it
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Render every document of a tenant
+    Export {
+        tenant: String,
+        #[serde(default)]
+        format: Option<String>,
+    },
+    /// Delete trashed documents older than some days
+    PurgeTrash {
+        #[serde(default)]
+        older_than_days: Option<u32>,
+    },
+    /// Rebuild the search index
+    Reindex,
+}
+
+impl AdminAction {
+    /// Get the name of the action, as used in requests
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdminAction::Compact { .. } => "compact",
+            AdminAction::Export { .. } => "export",
+            AdminAction::PurgeTrash { .. } => "purge_trash",
+            AdminAction::Reindex => "reindex",
+        }
+    }
+}
+
+/// Build the filter serving the admin API
+pub(crate) fn routes(state: ServerState, config: &AdminConfig) -> BoxedFilter<(Response<Body>,)> {
+    let token = config.token.clone();
+    let authorized = warp::header::optional::<String>(header::AUTHORIZATION.as_str())
+        .map(move |authorization: Option<String>| authorize(token.as_deref(), authorization.as_deref()));
+
+    let start = warp::post()
+        .and(warp::path!("admin" / "jobs"))
+        .and(authorized.clone())
+        .and(warp::body::bytes())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>, body: Bytes| match authorized {
+                Ok(()) => start_job(&state, &body),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let list = warp::get()
+        .and(warp::path!("admin" / "jobs"))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => json_response(StatusCode::OK, &json!(state.jobs().list())),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let poll = warp::get()
+        .and(warp::path!("admin" / "jobs" / u64))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |id: u64, authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => match state.jobs().get(id) {
+                    Some(job) => json_response(StatusCode::OK, &json!(job)),
+                    None => plain(StatusCode::NOT_FOUND, JobError::NotFound(id).to_string()),
+                },
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let overload = warp::get()
+        .and(warp::path!("admin" / "overload"))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => json_response(StatusCode::OK, &json!(state.overload().status())),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let storage = warp::get()
+        .and(warp::path!("admin" / "storage"))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => json_response(StatusCode::OK, &json!({ "degraded": state.degraded_documents() })),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let bandwidth = warp::get()
+        .and(warp::path!("admin" / "bandwidth"))
+        .and(authorized.clone())
+        .and(warp::query::<BTreeMap<String, String>>())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>, query: BTreeMap<String, String>| match authorized {
+                Ok(()) => match query.get("limit").map(|limit| limit.parse::<usize>()) {
+                    None => json_response(StatusCode::OK, &json!(state.metrics().bandwidth.report(DEFAULT_BANDWIDTH_LIMIT))),
+                    Some(Ok(limit)) => json_response(StatusCode::OK, &json!(state.metrics().bandwidth.report(limit))),
+                    Some(Err(_)) => plain(StatusCode::BAD_REQUEST, "limit must be a number".to_string()),
+                },
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let memory = warp::get()
+        .and(warp::path!("admin" / "memory"))
+        .and(authorized.clone())
+        .and(warp::query::<BTreeMap<String, String>>())
+        .then({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>, query: BTreeMap<String, String>| {
+                let state = state.clone();
+                async move {
+                    if let Err((status, message)) = authorized {
+                        return plain(status, message);
+                    }
+                    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+                        None => DEFAULT_MEMORY_LIMIT,
+                        Some(Ok(limit)) => limit,
+                        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "limit must be a number".to_string()),
+                    };
+                    json_response(StatusCode::OK, &json!(EditorServer::memory_overview(&state, limit).await))
+                }
+            }
+        });
+
+    let abuse = warp::get()
+        .and(warp::path!("admin" / "abuse"))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => json_response(StatusCode::OK, &json!(state.metrics().abuse.report(Instant::now()))),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let lift = warp::delete()
+        .and(warp::path!("admin" / "abuse" / String / String))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |tenant_id: String, client_id: String, authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => match state.metrics().abuse.lift(&tenant_id, &client_id, Instant::now()) {
+                    Ok(()) => plain(StatusCode::NO_CONTENT, String::new()),
+                    Err(e) => plain(StatusCode::NOT_FOUND, e.to_string()),
+                },
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let reset_password = warp::delete()
+        .and(warp::path!("admin" / "passwords" / String / String))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |tenant_id: String, document_id: String, authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => {
+                    let reset = state.tenant(&tenant_id).map_err(|e| e.to_string()).and_then(|tenant| {
+                        let document_id = tenant.aliases().resolve(&document_id);
+                        tenant.passwords().reset(&document_id).map_err(|e| e.to_string())?;
+                        EditorServer::store_password(&state, &tenant.scoped(&document_id), None);
+                        Ok(())
+                    });
+                    match reset {
+                        Ok(()) => {
+                            log::info!("Password of document {} in tenant {} reset by an operator", document_id, tenant_id);
+                            plain(StatusCode::NO_CONTENT, String::new())
+                        }
+                        Err(message) => plain(StatusCode::NOT_FOUND, message),
+                    }
+                }
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let skewed = warp::get()
+        .and(warp::path!("admin" / "skew"))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => json_response(StatusCode::OK, &json!(state.skew().flagged())),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let release = warp::post()
+        .and(warp::path!("admin" / "skew" / String / String / "release"))
+        .and(authorized.clone())
+        .then({
+            let state = state.clone();
+            move |tenant_id: String, client_id: String, authorized: Result<(), (StatusCode, String)>| {
+                let state = state.clone();
+                async move {
+                    if let Err((status, message)) = authorized {
+                        return plain(status, message);
+                    }
+                    match EditorServer::release_quarantine(&state, &tenant_id, &client_id).await {
+                        Ok(applied) => json_response(StatusCode::OK, &json!({ "applied": applied })),
+                        Err(e) => plain(StatusCode::NOT_FOUND, e.to_string()),
+                    }
+                }
+            }
+        });
+
+    let discard = warp::delete()
+        .and(warp::path!("admin" / "skew" / String / String))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |tenant_id: String, client_id: String, authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => match state.skew().clear(&tenant_id, &client_id) {
+                    Ok(held) => json_response(StatusCode::OK, &json!({ "discarded": held.len() })),
+                    Err(e) => plain(StatusCode::NOT_FOUND, e.to_string()),
+                },
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let transfer = warp::post()
+        .and(warp::path!("admin" / "documents" / String / String / "transfer"))
+        .and(authorized.clone())
+        .and(warp::body::bytes())
+        .then({
+            let state = state.clone();
+            move |tenant_id: String, document_id: String, authorized: Result<(), (StatusCode, String)>, body: Bytes| {
+                let state = state.clone();
+                async move {
+                    if let Err((status, message)) = authorized {
+                        return plain(status, message);
+                    }
+                    let request = match serde_json::from_slice::<TransferRequest>(&body) {
+                        Ok(request) => request,
+                        Err(e) => return plain(StatusCode::BAD_REQUEST, format!("Invalid transfer request: {}", e)),
+                    };
+                    match EditorServer::move_document(&state, &tenant_id, &document_id, request).await {
+                        Ok(transfer) => json_response(StatusCode::OK, &json!(transfer)),
+                        Err(e) => plain(transfer_status(&e), e.to_string()),
+                    }
+                }
+            }
+        });
+
+    let cancel = warp::delete()
+        .and(warp::path!("admin" / "jobs" / u64))
+        .and(authorized)
+        .map(move |id: u64, authorized: Result<(), (StatusCode, String)>| match authorized {
+            Ok(()) => match state.jobs().cancel(id) {
+                Ok(()) => plain(StatusCode::NO_CONTENT, String::new()),
+                Err(e @ JobError::Finished(_)) => plain(StatusCode::CONFLICT, e.to_string()),
+                Err(e) => plain(StatusCode::NOT_FOUND, e.to_string()),
+            },
+            Err((status, message)) => plain(status, message),
+        });
+
+    start
+        .or(list)
+        .unify()
+        .or(poll)
+        .unify()
+        .or(cancel)
+        .unify()
+        .or(overload)
+        .unify()
+        .or(storage)
+        .unify()
+        .or(bandwidth)
+        .unify()
+        .or(memory)
+        .unify()
+        .or(abuse)
+        .unify()
+        .or(lift)
+        .unify()
+        .or(reset_password)
+        .unify()
+        .or(skewed)
+        .unify()
+        .or(release)
+        .unify()
+        .or(discard)
+        .unify()
+        .or(transfer)
+        .unify()
+        .boxed()
+}
+
+/// Check the bearer token of an admin request
+pub(crate) fn authorize(token: Option<&str>, authorization: Option<&str>) -> Result<(), (StatusCode, String)> {
+    let Some(token) = token else {
+        return Err((StatusCode::NOT_FOUND, "The admin API is disabled".to_string()));
+    };
+    match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(presented) if secrets_match(presented, token) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string())),
+    }
+}
+
+/// Get the HTTP status of a failed transfer
+fn transfer_status(error: &TransferError) -> StatusCode {
+    match error {
+        TransferError::Tenant(TenantError::NotFound(_)) | TransferError::NotFound(_) => StatusCode::NOT_FOUND,
+        TransferError::Tenant(_) | TransferError::SamePlace(_) => StatusCode::BAD_REQUEST,
+        TransferError::Temporary(_) | TransferError::Exists(..) | TransferError::Encryption(..) => StatusCode::CONFLICT,
+        TransferError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+    }
+}
+
+/// Validate a job request and start it in the background
+fn start_job(state: &ServerState, body: &[u8]) -> Response<Body> {
+    let action = match serde_json::from_slice::<AdminAction>(body) {
+        Ok(action) => action,
+        Err(e) => return plain(StatusCode::BAD_REQUEST, format!("Invalid admin request: {}", e)),
+    };
+
+    let targets = match &action {
+        AdminAction::Compact { tenant: Some(tenant_id), .. } | AdminAction::Export { tenant: tenant_id, .. } => {
+            match state.tenant(tenant_id) {
+                Ok(tenant) => vec![tenant],
+                Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+            }
+        }
+        AdminAction::Compact { tenant: None, .. } => {
+            state.tenant_ids().iter().filter_map(|id| state.tenant(id).ok()).collect()
+        }
+        AdminAction::PurgeTrash { .. } => {
+            return plain(StatusCode::NOT_IMPLEMENTED, "Documents are never trashed, so there is no trash to purge".to_string());
+        }
+        AdminAction::Reindex => {
+            return plain(StatusCode::NOT_IMPLEMENTED, "The server keeps no search index; follow GET /events to index documents".to_string());
+        }
+    };
+
+    let mut format = ExportFormat::default();
+    if let AdminAction::Export { tenant, format: requested } = &action {
+        if let Some(requested) = requested {
+            match requested.parse() {
+                Ok(requested) => format = requested,
+                Err(e) => return plain(StatusCode::BAD_REQUEST, e),
+            }
+        }
+        // As for single exports, the server only holds ciphertext
+        if targets.iter().any(|target| target.encryption().is_end_to_end()) {
+            return plain(
+                StatusCode::FORBIDDEN,
+                format!("Documents of tenant {} are end-to-end encrypted and can't be exported", tenant),
+            );
+        }
+    }
+
+    let kind = action.name();
+    let task_state = state.clone();
+    let id = state.jobs().enqueue(kind, move |job| async move {
+        match action {
+            AdminAction::Compact { prefix, dry_run: false, .. } => compact(&task_state, &job, &targets, &prefix).await,
+            AdminAction::Compact { prefix, dry_run: true, .. } => report_garbage(&task_state, &job, &targets, &prefix).await,
+            AdminAction::Export { tenant, .. } => export_tenant(&task_state, &job, &targets, &tenant, format).await,
+            AdminAction::PurgeTrash { .. } | AdminAction::Reindex => Err(JobError::Failed("Unsupported action".to_string())),
+        }
+    });
+
+    let mut response = json_response(StatusCode::ACCEPTED, &json!({ "id": id }));
+    if let Ok(location) = format!("/admin/jobs/{}", id).parse() {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    response
+}
+
+/// Get the IDs of the tenants' documents with a prefix
+async fn matching_documents(state: &ServerState, targets: &[Arc<Tenant>], prefix: &str) -> Vec<(Arc<Tenant>, String)> {
+    let mut documents = Vec::new();
+    for tenant in targets {
+        for document_id in EditorServer::document_ids(state, tenant).await {
+            if document_id.starts_with(prefix) {
+                documents.push((tenant.clone(), document_id));
+            }
+        }
+    }
+    documents
+}
+
+/// Drop tombstones from matching documents, one document at a time
+async fn compact(state: &ServerState, job: &JobContext, targets: &[Arc<Tenant>], prefix: &str) -> Result<Value, JobError> {
+    let documents = matching_documents(state, targets, prefix).await;
+    job.set_total(documents.len());
+
+    let mut compacted = 0;
+    let mut tombstones = 0;
+    for (tenant, document_id) in documents {
+        job.check_cancelled()?;
+        let removed = EditorServer::with_document_mut(state, &tenant, &document_id, |doc| {
+            let before = doc.character_count();
+            doc.collect_garbage();
+            before - doc.character_count()
+        })
+        .await;
+        if let Some(removed) = removed {
+            compacted += 1;
+            tombstones += removed;
+        }
+        job.advance();
+        tokio::task::yield_now().await;
+    }
+    Ok(json!({ "documents": compacted, "tombstones_removed": tombstones }))
+}
+
+/// Report what compacting matching documents would drop, one document at
+/// a time
+async fn report_garbage(state: &ServerState, job: &JobContext, targets: &[Arc<Tenant>], prefix: &str) -> Result<Value, JobError> {
+    let documents = matching_documents(state, targets, prefix).await;
+    job.set_total(documents.len());
+
+    let mut reports = BTreeMap::new();
+    for (tenant, document_id) in documents {
+        job.check_cancelled()?;
+        if let Some(report) = EditorServer::with_document(state, &tenant, &document_id, Document::gc_report).await {
+            reports.insert(tenant.scoped(&document_id), report);
+        }
+        job.advance();
+        tokio::task::yield_now().await;
+    }
+    Ok(json!({ "documents": reports.len(), "dry_run": true, "reports": reports }))
+}
+
+/// Render every document of a tenant, one document at a time
+async fn export_tenant(
+    state: &ServerState,
+    job: &JobContext,
+    targets: &[Arc<Tenant>],
+    tenant_id: &str,
+    format: ExportFormat,
+) -> Result<Value, JobError> {
+    let documents = matching_documents(state, targets, "").await;
+    job.set_total(documents.len());
+
+    let mut exports = BTreeMap::new();
+    for (tenant, document_id) in documents {
+        job.check_cancelled()?;
+        let rendered = EditorServer::with_document(state, &tenant, &document_id, |doc| export(&doc.content(), format)).await;
+        if let Some(rendered) = rendered {
+            exports.insert(document_id, rendered);
+        }
+        job.advance();
+        tokio::task::yield_now().await;
+    }
+    Ok(json!({ "tenant": tenant_id, "format": format.name(), "documents": exports }))
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    if let Ok(value) = "application/json".parse() {
+        response.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    response
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,181 @@
+/*
+ * File: src/websocket/assets.rs
+ * Purpose: Static asset serving for the editor frontend
+ *
+ * Serves the built frontend next to the WebSocket routes:
+ * - Assets come from a directory on disk, or from the binary itself when
+ *   built with the `embed-assets` feature
+ * - With SPA fallback, paths that don't name a file (client-side routes
+ *   such as `/docs/123`) get `index.html`
+ * - Assets get the configured `Cache-Control`; `index.html` is always
+ *   revalidated so new deployments are picked up
+ *
+ * Request paths are percent-decoded and refused unless every segment is a
+ * plain name: no empty, `.` or `..` segments, no backslashes or drive
+ * prefixes. An empty segment matters as much as `..`: `//etc/hostname`
+ * would otherwise name an absolute path, and joining it onto the asset
+ * directory replaces the directory. Files read from disk must also
+ * canonicalize to a path inside the canonicalized asset directory, so
+ * symlinks can't lead out of it either.
+ */
+
+use std::{
+    borrow::Cow,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response},
+    hyper::Body,
+    path::Tail,
+    Filter,
+};
+
+/// Document served for the site root and SPA fallbacks
+const INDEX: &str = "index.html";
+
+/// `Cache-Control` of `index.html`, which must never be served stale
+const INDEX_CACHE_CONTROL: &str = "no-cache";
+
+/// Frontend build compiled into the binary
+#[cfg(feature = "embed-assets")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "$CARGO_MANIFEST_DIR/../frontend/dist"]
+#[allow_missing = true]
+struct EmbeddedAssets;
+
+/// Where static assets are read from
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetSource {
+    /// Files under a directory on disk
+    Directory(PathBuf),
+    /// Files compiled into the binary from `frontend/dist`
+    #[cfg(feature = "embed-assets")]
+    Embedded,
+}
+
+impl AssetSource {
+    /// Check whether the source holds a frontend build, i.e. an `index.html`
+    pub fn has_index(&self) -> bool {
+        match self {
+            AssetSource::Directory(root) => root.join(INDEX).is_file(),
+            #[cfg(feature = "embed-assets")]
+            AssetSource::Embedded => EmbeddedAssets::get(INDEX).is_some(),
+        }
+    }
+}
+
+/// Configuration for static asset serving
+#[derive(Debug, Clone)]
+pub struct StaticConfig {
+    /// Where assets come from; `None` serves no assets
+    pub source: Option<AssetSource>,
+    /// Serve `index.html` for paths that don't name a file
+    pub spa_fallback: bool,
+    /// `Cache-Control` header of every asset except `index.html`
+    pub cache_control: String,
+}
+
+impl Default for StaticConfig {
+    fn default() -> Self {
+        Self {
+            source: None,
+            spa_fallback: true,
+            cache_control: "public, max-age=3600".to_string(),
+        }
+    }
+}
+
+impl StaticConfig {
+    /// Serve assets from a directory, with the default fallback and caching
+    pub fn directory(path: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Some(AssetSource::Directory(path.into())),
+            ..Default::default()
+        }
+    }
+}
+
+/// Build the filter serving `GET` and `HEAD` requests for static assets.
+/// Requests it can't serve are rejected as not found.
+pub fn routes(config: &StaticConfig) -> BoxedFilter<(Response<Body>,)> {
+    let config = Arc::new(config.clone());
+    warp::get()
+        .or(warp::head())
+        .unify()
+        .and(warp::path::tail())
+        .and_then(move |tail: Tail| {
+            let config = config.clone();
+            async move { serve(&config, tail.as_str()).await.ok_or_else(warp::reject::not_found) }
+        })
+        .boxed()
+}
+
+/// Resolve a request path to a response, if there is something to serve
+async fn serve(config: &StaticConfig, path: &str) -> Option<Response<Body>> {
+    let source = config.source.as_ref()?;
+    let path = percent_encoding::percent_decode_str(path).decode_utf8().ok()?;
+    let file = if path.is_empty() || path.ends_with('/') {
+        format!("{}{}", path, INDEX)
+    } else {
+        path.into_owned()
+    };
+    if !is_relative_file(&file) {
+        return None;
+    }
+    if let Some(body) = load(source, &file).await {
+        return Some(response(config, &file, body));
+    }
+
+    // Client-side routes have no file extension; missing assets do
+    let names_file = file.rsplit('/').next().is_some_and(|name| name.contains('.'));
+    if config.spa_fallback && !names_file {
+        let body = load(source, INDEX).await?;
+        return Some(response(config, INDEX, body));
+    }
+    None
+}
+
+/// Check that a decoded path names a file below the asset root: plain,
+/// non-empty segments only
+fn is_relative_file(path: &str) -> bool {
+    let plain = |segment: &str| !matches!(segment, "" | "." | "..") && !segment.contains(['\\', ':', '\0']);
+    path.split('/').all(plain) && Path::new(path).components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Read an asset from its source
+async fn load(source: &AssetSource, path: &str) -> Option<Cow<'static, [u8]>> {
+    match source {
+        AssetSource::Directory(root) => {
+            let root = tokio::fs::canonicalize(root).await.ok()?;
+            let file = tokio::fs::canonicalize(root.join(path)).await.ok()?;
+            if !file.starts_with(&root) {
+                return None;
+            }
+            tokio::fs::read(file).await.ok().map(Cow::Owned)
+        }
+        #[cfg(feature = "embed-assets")]
+        AssetSource::Embedded => EmbeddedAssets::get(path).map(|file| file.data),
+    }
+}
+
+/// Build the response for an asset
+fn response(config: &StaticConfig, path: &str, body: Cow<'static, [u8]>) -> Response<Body> {
+    let cache_control = if path.rsplit('/').next() == Some(INDEX) {
+        INDEX_CACHE_CONTROL
+    } else {
+        config.cache_control.as_str()
+    };
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+
+    let mut response = Response::new(Body::from(body));
+    let headers = response.headers_mut();
+    if let Ok(value) = content_type.as_ref().parse() {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    if let Ok(value) = cache_control.parse() {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
@@ -0,0 +1,86 @@
+/*
+ * File: src/websocket/backlinks.rs
+ * Purpose: Links and backlinks of documents over HTTP, for wiki navigation
+ *
+ *   GET /documents/<id>/links
+ *   GET /t/<tenant>/documents/<id>/links?key=<access key>
+ *
+ * The document is named by ID or slug. The response lists the documents it
+ * links to and the documents linking to it, by ID:
+ *
+ *   {"document_id": "...", "links": ["..."], "backlinks": ["..."]}
+ *
+ * Documents that don't exist yet answer too, with the backlinks of their
+ * future pages. Documents of end-to-end encrypted tenants can't be read by
+ * the server, so they have no links to answer with. Password-protected
+ * documents need a share token as `share`, and are left out of the
+ * backlinks of others.
+ */
+
+use std::collections::HashMap;
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    Filter,
+};
+
+use crate::{
+    tenant::DEFAULT_TENANT,
+    websocket::server::{EditorServer, ServerState},
+};
+
+/// Build the filter serving document links
+pub(crate) fn routes(state: ServerState) -> BoxedFilter<(Response<Body>,)> {
+    warp::get()
+        .and(
+            warp::path!("documents" / String / "links")
+                .map(|document_id: String| (DEFAULT_TENANT.to_string(), document_id))
+                .or(warp::path!("t" / String / "documents" / String / "links")
+                    .map(|tenant_id: String, document_id: String| (tenant_id, document_id)))
+                .unify(),
+        )
+        .and(warp::query::<HashMap<String, String>>())
+        .then(move |(tenant_id, document_id): (String, String), query: HashMap<String, String>| {
+            let state = state.clone();
+            async move { respond(&state, &tenant_id, &document_id, &query).await }
+        })
+        .boxed()
+}
+
+/// Answer a links request
+async fn respond(state: &ServerState, tenant_id: &str, document_id: &str, query: &HashMap<String, String>) -> Response<Body> {
+    let tenant = match state.tenant(tenant_id) {
+        Ok(tenant) => tenant,
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    if let Err(e) = tenant.authorize(query.get("key").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e.to_string());
+    }
+    if tenant.encryption().is_end_to_end() {
+        return plain(
+            StatusCode::FORBIDDEN,
+            format!("Documents of tenant {} are end-to-end encrypted and have no readable links", tenant.id()),
+        );
+    }
+
+    if let Err(e) = state.check_http_access(&tenant, document_id, query.get("share").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e);
+    }
+
+    // A protected document's links are part of its content
+    let mut links = EditorServer::collect_links(state, &tenant, document_id).await;
+    links.backlinks.retain(|backlink| !tenant.passwords().is_protected(backlink));
+    let body = serde_json::to_string(&links).unwrap_or_default();
+    let mut response = Response::new(Body::from(body));
+    if let Ok(value) = "application/json".parse() {
+        response.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    response
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,69 @@
+/*
+ * File: src/websocket/clock.rs
+ * Purpose: Time source abstraction for connection tracking
+ *
+ * Timeout and heartbeat logic reads the current time through `Clock`
+ * instead of calling `Utc::now()` directly:
+ * - SystemClock: wall-clock time, used in production
+ * - ManualClock: time that only moves when advanced, so tests can
+ *   exercise timeouts instantly and deterministically
+ */
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    /// Get the current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock backed by the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that only advances when told to
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// Create a clock stopped at the given time
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        let delta = chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        let mut now = self.now.lock();
+        *now = now.checked_add_signed(delta).unwrap_or(DateTime::<Utc>::MAX_UTC);
+    }
+
+    /// Set the clock to a specific time
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.now.lock() = time;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock()
+    }
+}
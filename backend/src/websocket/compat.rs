@@ -0,0 +1,152 @@
+/*
+ * File: src/websocket/compat.rs
+ * Purpose: Forward compatibility of messages and operations
+ *
+ * Clients and servers are upgraded separately, so a server has to cope
+ * with clients newer than itself. The rules:
+ * - Unknown fields are tolerated and preserved: fields of the message
+ *   envelope, of its payload and of an operation that this server doesn't
+ *   know are kept and relayed with the operation, so clients that know
+ *   them still see them. Documents store only the fields they know, so
+ *   late joiners and resyncs don't get them.
+ * - Feature bits announce what a message needs: a client sets the
+ *   `requires` bits of the capabilities a message can't be understood
+ *   without, such as a new kind of operation, and a server that lacks one
+ *   of them rejects the message instead of misreading it. The welcome
+ *   message lists the server's `features`.
+ * - Deprecations are announced before compatibility code is removed: the
+ *   welcome message lists the message types and feature bits the server
+ *   will stop supporting as `deprecations`, each with the `sunset` version
+ *   that drops it. Clients still using one are logged and counted in
+ *   metrics, so operators can tell when no one does anymore.
+ *
+ * Bits are never reused; a capability a server drops keeps its bit.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::websocket::message::{Message, OperationMessage};
+
+/// Operations tagged with the kind of actor they come from
+pub const FEATURE_OPERATION_SOURCE: u64 = 1 << 0;
+/// Document states answered with a snapshot and the operations after it
+pub const FEATURE_LATE_JOIN_TAIL: u64 = 1 << 1;
+/// Offline changes synced with `documentUpdate`
+pub const FEATURE_DOCUMENT_UPDATE: u64 = 1 << 2;
+/// Checklist blocks edited with `checklistOperation`
+pub const FEATURE_CHECKLISTS: u64 = 1 << 3;
+/// Undo of a client's own operations
+pub const FEATURE_UNDO: u64 = 1 << 4;
+/// `DeleteRange` operations
+pub const FEATURE_RANGE_DELETE: u64 = 1 << 5;
+/// Redo of undone operations
+pub const FEATURE_REDO: u64 = 1 << 6;
+/// Missed operations fetched with `syncRequest`
+pub const FEATURE_DELTA_SYNC: u64 = 1 << 7;
+/// Presence of clients in documents, with `presenceUpdate` diffs
+pub const FEATURE_PRESENCE: u64 = 1 << 8;
+/// Cursors and selections shared with `cursorUpdate`
+pub const FEATURE_CURSORS: u64 = 1 << 9;
+
+/// Every capability this server has
+pub const SUPPORTED_FEATURES: u64 = FEATURE_OPERATION_SOURCE
+    | FEATURE_LATE_JOIN_TAIL
+    | FEATURE_DOCUMENT_UPDATE
+    | FEATURE_CHECKLISTS
+    | FEATURE_UNDO
+    | FEATURE_RANGE_DELETE
+    | FEATURE_REDO
+    | FEATURE_DELTA_SYNC
+    | FEATURE_PRESENCE
+    | FEATURE_CURSORS;
+
+/// A message type or feature the server still supports but will drop
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Message type, such as `connect`, or feature bit, such as `0x4`
+    pub feature: String,
+    /// First server version without it
+    pub sunset: String,
+    /// What clients should use instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+impl Deprecation {
+    /// Deprecate a message type or feature bit
+    pub fn new(feature: impl Into<String>, sunset: impl Into<String>, replacement: Option<&str>) -> Self {
+        Self {
+            feature: feature.into(),
+            sunset: sunset.into(),
+            replacement: replacement.map(str::to_string),
+        }
+    }
+
+    /// Check whether a message uses what is deprecated, by its type or by
+    /// the bits it requires
+    pub fn applies_to(&self, message: &Message) -> bool {
+        match self.feature.strip_prefix("0x").map(|bits| u64::from_str_radix(bits, 16)) {
+            Some(Ok(bits)) => bits != 0 && message.requires() & bits == bits,
+            Some(Err(_)) => false,
+            None => serde_json::to_value(message.message_type()).is_ok_and(|name| name == self.feature.as_str()),
+        }
+    }
+}
+
+/// Message types and features this server announces as deprecated
+pub fn default_deprecations() -> Vec<Deprecation> {
+    vec![
+        Deprecation::new("connect", "0.2.0", Some("the welcome status sent when the WebSocket opens")),
+        Deprecation::new("disconnect", "0.2.0", Some("closing the WebSocket")),
+    ]
+}
+
+/// Compatibility errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CompatError {
+    #[error("Unsupported features required: {0:#x}")]
+    UnsupportedFeatures(u64),
+}
+
+/// Check that this server has every capability a message requires
+pub fn check_features(requires: u64) -> Result<(), CompatError> {
+    match requires & !SUPPORTED_FEATURES {
+        0 => Ok(()),
+        missing => Err(CompatError::UnsupportedFeatures(missing)),
+    }
+}
+
+/// Serialize an operation message the server changed, keeping the fields
+/// of the original payload that this server doesn't know. Unknown fields
+/// of the operation are kept only while it stays the same kind.
+pub fn operation_payload(original: &Value, op_msg: &OperationMessage) -> serde_json::Result<Value> {
+    let mut payload = serde_json::to_value(op_msg)?;
+    if let (Some(original), Some(updated)) = (original.as_object(), payload.as_object_mut()) {
+        copy_unknown(original, updated, &["operation", "document_id", "source"]);
+        let operations = (
+            original.get("operation").and_then(Value::as_object),
+            updated.get_mut("operation").and_then(Value::as_object_mut),
+        );
+        if let (Some(original), Some(updated)) = operations {
+            for (kind, body) in updated.iter_mut() {
+                let bodies = (original.get(kind).and_then(Value::as_object), body.as_object_mut());
+                if let (Some(original), Some(body)) = bodies {
+                    copy_unknown(original, body, &[]);
+                }
+            }
+        }
+    }
+    Ok(payload)
+}
+
+/// Copy the fields of `original` missing from `updated`, except `known`
+/// ones the server left out on purpose
+fn copy_unknown(original: &Map<String, Value>, updated: &mut Map<String, Value>, known: &[&str]) {
+    for (key, value) in original {
+        if !known.contains(&key.as_str()) && !updated.contains_key(key) {
+            updated.insert(key.clone(), value.clone());
+        }
+    }
+}
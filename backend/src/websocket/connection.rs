@@ -7,17 +7,28 @@
  * - Client tracking and identification
  * - Connection status monitoring
  * - Heartbeat mechanism
+ *
+ * Each client is a single `Session` holding both its info and status, and
+ * status changes go through validated state transitions: connected clients
+ * may time out, connected or timed-out clients may disconnect, and timed-out
+ * or disconnected clients may recover.
+ *
+ * All time comparisons go through an injected `Clock`, so timeouts can be
+ * tested without waiting in real time.
  */
 
 use std::{
     collections::HashMap,
     sync::Arc,
+    time::Duration,
 };
 use chrono::{DateTime, Utc};
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 use thiserror::Error;
-use tracing::{error, info, warn};
+use tracing::{info, warn};
+
+use crate::websocket::clock::{Clock, SystemClock};
 
 /// Connection-specific errors
 #[derive(Error, Debug)]
@@ -57,153 +68,187 @@ pub struct ConnectionStats {
     pub disconnected_clients: usize,
 }
 
+/// Timeout policy for tracked connections
+#[derive(Debug, Clone)]
+pub struct ConnectionConfig {
+    /// Inactivity after which a connected client is considered timed out
+    pub connection_timeout: Duration,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            connection_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A tracked client connection: its metadata and current status
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub info: ClientInfo,
+    pub status: ConnectionStatus,
+}
+
+impl Session {
+    /// Create a connected session
+    pub fn new(info: ClientInfo) -> Self {
+        Self {
+            info,
+            status: ConnectionStatus::Connected,
+        }
+    }
+
+    /// Connected or TimedOut -> Disconnected
+    pub fn disconnect(&mut self) -> Result<(), ConnectionError> {
+        match self.status {
+            ConnectionStatus::Connected | ConnectionStatus::TimedOut => {
+                self.status = ConnectionStatus::Disconnected;
+                Ok(())
+            }
+            ConnectionStatus::Disconnected => {
+                Err(ConnectionError::InvalidState("Client is already disconnected".to_string()))
+            }
+        }
+    }
+
+    /// Connected -> TimedOut
+    pub fn time_out(&mut self) -> Result<(), ConnectionError> {
+        match self.status {
+            ConnectionStatus::Connected => {
+                self.status = ConnectionStatus::TimedOut;
+                Ok(())
+            }
+            _ => Err(ConnectionError::InvalidState("Only connected clients can time out".to_string())),
+        }
+    }
+
+    /// Disconnected or TimedOut -> Connected
+    pub fn recover(&mut self) -> Result<(), ConnectionError> {
+        match self.status {
+            ConnectionStatus::Disconnected | ConnectionStatus::TimedOut => {
+                self.status = ConnectionStatus::Connected;
+                Ok(())
+            }
+            ConnectionStatus::Connected => {
+                Err(ConnectionError::InvalidState("Client is already connected".to_string()))
+            }
+        }
+    }
+}
+
 /// Manages WebSocket client connections
 #[derive(Clone)]
 pub struct ConnectionManager {
-    clients: Arc<RwLock<HashMap<String, ClientInfo>>>,
-    statuses: Arc<RwLock<HashMap<String, ConnectionStatus>>>,
+    /// Sessions keyed by client ID, behind a single lock so info and
+    /// status always change together
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+    clock: Arc<dyn Clock>,
+    timeout: chrono::Duration,
 }
 
 impl ConnectionManager {
-    /// Create a new connection manager
+    /// Create a new connection manager with the default timeout policy
     pub fn new() -> Self {
+        Self::with_config(ConnectionConfig::default())
+    }
+
+    /// Create a new connection manager with the given timeout policy
+    pub fn with_config(config: ConnectionConfig) -> Self {
         Self {
-            clients: Arc::new(RwLock::new(HashMap::new())),
-            statuses: Arc::new(RwLock::new(HashMap::new())),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            clock: Arc::new(SystemClock),
+            timeout: chrono::Duration::from_std(config.connection_timeout).unwrap_or(chrono::Duration::MAX),
         }
     }
 
+    /// Read time from the given clock instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Register a new client with the given ID
     pub async fn register_client(&mut self, client_id: String) -> Result<(), ConnectionError> {
+        let now = self.clock.now();
         let client_info = ClientInfo {
             id: client_id.clone(),
             ip: "127.0.0.1".to_string(), // Default IP for now
-            connected_at: chrono::Utc::now(),
-            last_activity: Some(chrono::Utc::now()),
+            connected_at: now,
+            last_activity: Some(now),
         };
         self.register_client_with_info(client_info).await
     }
 
     /// Register a new client with the given client info
     pub async fn register_client_with_info(&mut self, client_info: ClientInfo) -> Result<(), ConnectionError> {
-        let client_id = client_info.id.clone();
-        
-        // Update client info
-        let mut clients = self.clients.write().await;
-        clients.insert(client_id.clone(), client_info);
-        
-        // Update connection status
-        let mut statuses = self.statuses.write().await;
-        statuses.insert(client_id, ConnectionStatus::Connected);
-        
+        let mut sessions = self.sessions.write().await;
+        if sessions.contains_key(&client_info.id) {
+            return Err(ConnectionError::ClientExists(client_info.id));
+        }
+
+        sessions.insert(client_info.id.clone(), Session::new(client_info));
         Ok(())
     }
 
     /// Get the current status of a client
     pub async fn get_client_status(&self, client_id: &str) -> Option<ConnectionStatus> {
-        // First check if the client has timed out
-        if let Some(info) = self.get_client_info(client_id).await {
-            if let Some(last_activity) = info.last_activity {
-                let now = chrono::Utc::now();
-                let duration = now.signed_duration_since(last_activity);
-                
-                // If last activity was more than 3 seconds ago, mark as timed out
-                if duration.num_seconds() > 3 {
-                    let mut statuses = self.statuses.write().await;
-                    statuses.insert(client_id.to_string(), ConnectionStatus::TimedOut);
-                }
-            }
-        }
-        
-        let statuses = self.statuses.read().await;
-        statuses.get(client_id).cloned()
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(client_id)?;
+        self.expire_if_inactive(session);
+        Some(session.status.clone())
     }
 
     /// Get client information
     pub async fn get_client_info(&self, client_id: &str) -> Option<ClientInfo> {
-        let clients = self.clients.read().await;
-        clients.get(client_id).cloned()
+        self.sessions.read().await
+            .get(client_id)
+            .map(|session| session.info.clone())
+    }
+
+    /// Get a snapshot of a client's session
+    pub async fn get_session(&self, client_id: &str) -> Option<Session> {
+        self.sessions.read().await.get(client_id).cloned()
     }
 
     /// Update client heartbeat
     pub async fn update_heartbeat(&mut self, client_id: &str) -> Result<(), ConnectionError> {
-        let mut clients = self.clients.write().await;
-        
-        if let Some(client_info) = clients.get_mut(client_id) {
-            client_info.last_activity = Some(chrono::Utc::now());
+        let now = self.clock.now();
+        self.with_session(client_id, |session| {
+            session.info.last_activity = Some(now);
             Ok(())
-        } else {
-            Err(ConnectionError::ClientNotFound(client_id.to_string()))
-        }
+        }).await
     }
 
     /// Disconnect a client
     pub async fn disconnect_client(&mut self, client_id: &str) -> Result<(), ConnectionError> {
-        let clients = self.clients.read().await;
-        if !clients.contains_key(client_id) {
-            return Err(ConnectionError::ClientNotFound(client_id.to_string()));
-        }
-        
-        let mut statuses = self.statuses.write().await;
-        statuses.insert(client_id.to_string(), ConnectionStatus::Disconnected);
+        self.with_session(client_id, Session::disconnect).await?;
         info!("Client disconnected: {}", client_id);
         Ok(())
     }
 
     /// Check if client connection has timed out
     pub async fn check_connection_timeout(&mut self, client_id: &str) -> Result<bool, ConnectionError> {
-        let clients = self.clients.read().await;
-        let mut statuses = self.statuses.write().await;
-
-        let client = clients.get(client_id)
-            .ok_or_else(|| ConnectionError::ClientNotFound(client_id.to_string()))?;
-
-        if let Some(last_activity) = client.last_activity {
-            let timeout = Utc::now()
-                .signed_duration_since(last_activity)
-                .num_seconds() > 30; // 30 seconds timeout
-
-            if timeout {
-                statuses.insert(client_id.to_string(), ConnectionStatus::TimedOut);
-                warn!("Client connection timed out: {}", client_id);
-            }
-
-            Ok(timeout)
-        } else {
-            Ok(false)
-        }
+        self.with_session(client_id, |session| {
+            self.expire_if_inactive(session);
+            Ok(session.status == ConnectionStatus::TimedOut)
+        }).await
     }
 
     /// Attempt to recover a disconnected client
     pub async fn recover_connection(&mut self, client_id: &str) -> Result<(), ConnectionError> {
-        let clients = self.clients.read().await;
-        let mut statuses = self.statuses.write().await;
-
-        if !clients.contains_key(client_id) {
-            return Err(ConnectionError::ClientNotFound(client_id.to_string()));
-        }
-
-        match statuses.get(client_id) {
-            Some(ConnectionStatus::Disconnected) | Some(ConnectionStatus::TimedOut) => {
-                statuses.insert(client_id.to_string(), ConnectionStatus::Connected);
-                info!("Client connection recovered: {}", client_id);
-                Ok(())
-            }
-            Some(ConnectionStatus::Connected) => {
-                Err(ConnectionError::InvalidState("Client is already connected".to_string()))
-            }
-            None => Err(ConnectionError::ClientNotFound(client_id.to_string())),
-        }
+        self.with_session(client_id, Session::recover).await?;
+        info!("Client connection recovered: {}", client_id);
+        Ok(())
     }
 
     /// Get connection statistics
     pub async fn get_statistics(&self) -> ConnectionStats {
-        let clients = self.clients.read().await;
-        let statuses = self.statuses.read().await;
+        let sessions = self.sessions.read().await;
         
-        let total = clients.len();
-        let connected = statuses.values()
-            .filter(|&status| *status == ConnectionStatus::Connected)
+        let total = sessions.len();
+        let connected = sessions.values()
+            .filter(|session| session.status == ConnectionStatus::Connected)
             .count();
         let disconnected = total - connected;
 
@@ -213,6 +258,29 @@ impl ConnectionManager {
             disconnected_clients: disconnected,
         }
     }
+
+    /// Run a transition on a client's session under the sessions lock
+    async fn with_session<T>(
+        &self,
+        client_id: &str,
+        transition: impl FnOnce(&mut Session) -> Result<T, ConnectionError>,
+    ) -> Result<T, ConnectionError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(client_id)
+            .ok_or_else(|| ConnectionError::ClientNotFound(client_id.to_string()))?;
+        transition(session)
+    }
+
+    /// Mark a connected client as timed out once it has been inactive for
+    /// longer than the configured timeout
+    fn expire_if_inactive(&self, session: &mut Session) {
+        let Some(last_activity) = session.info.last_activity else {
+            return;
+        };
+        if self.clock.now().signed_duration_since(last_activity) > self.timeout && session.time_out().is_ok() {
+            warn!("Client connection timed out: {}", session.info.id);
+        }
+    }
 }
 
 impl Default for ConnectionManager {
@@ -0,0 +1,347 @@
+/*
+ * File: src/websocket/doctor.rs
+ * Purpose: Self-check of a server configuration before it starts
+ *
+ * `diagnose` checks what would make a server fail or misbehave once it is
+ * serving, and says how to fix it:
+ * - address: the host is an IP address and the port can be bound
+ * - timeouts: both are positive and heartbeats come more often than the
+ *   connection timeout, so idle clients aren't dropped between them
+ * - tenants: tenant IDs are valid and unique
+ * - quota: warning ratios lie between 0 and 1
+ * - assets: the configured frontend has an `index.html`
+ * - admin: the admin token isn't empty
+ * - overload: the latency budget is positive and the recovery ratio lies
+ *   between 0 and 1
+ * - abuse: rates are averaged over a positive window, and restrictions
+ *   have limits to trigger them
+ * - passwords: document password hashes have costs argon2id accepts, and
+ *   a wrong password can't lock a document by itself
+ * - memory: documents are sampled at a positive interval, and samples are
+ *   kept to show a trend
+ * - storage: snapshots are saved at a positive interval, so operation logs
+ *   are compacted
+ * - standby: the primary's URL is an HTTP URL, and the admin API is
+ *   enabled so the standby can be promoted
+ *
+ * `EditorServer::run` refuses to start while any check fails; `coedit
+ * doctor` prints every check. The server has no TLS or cluster backplane
+ * to check yet.
+ */
+
+use std::{fmt, net::{IpAddr, TcpListener}};
+use hyper::Uri;
+
+use crate::{
+    security::check_cost,
+    tenant::TenantRegistry,
+    websocket::server::ServerConfig,
+};
+
+/// Least argon2id memory of document password hashes without a warning, in KiB
+const MIN_PASSWORD_MEMORY_KIB: u32 = 19 * 1024;
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckLevel {
+    Ok,
+    /// The server runs, but probably not as intended
+    Warning,
+    /// The server must not start
+    Error,
+}
+
+impl CheckLevel {
+    /// Get the label of the level, as printed in reports
+    pub fn label(self) -> &'static str {
+        match self {
+            CheckLevel::Ok => "ok",
+            CheckLevel::Warning => "warning",
+            CheckLevel::Error => "error",
+        }
+    }
+}
+
+/// A single check and what it found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub level: CheckLevel,
+    /// What was found, and how to fix it unless the check passed
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &'static str, level: CheckLevel, message: impl Into<String>) -> Self {
+        Self { name, level, message: message.into() }
+    }
+}
+
+/// Results of every check of a configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Check whether any check failed
+    pub fn has_errors(&self) -> bool {
+        self.checks.iter().any(|check| check.level == CheckLevel::Error)
+    }
+
+    /// Get the checks at a level
+    pub fn at(&self, level: CheckLevel) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(move |check| check.level == level)
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.level.label(), check.name, check.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check a configuration, including whether its port is free
+pub fn diagnose(config: &ServerConfig) -> DoctorReport {
+    DoctorReport {
+        checks: vec![
+            check_address(config),
+            check_timeouts(config),
+            check_tenants(config),
+            check_quota(config),
+            check_assets(config),
+            check_admin(config),
+            check_overload(config),
+            check_abuse(config),
+            check_passwords(config),
+            check_memory(config),
+            check_storage(config),
+            check_standby(config),
+        ],
+    }
+}
+
+fn check_address(config: &ServerConfig) -> Check {
+    let ip = match config.host.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return Check::new(
+                "address",
+                CheckLevel::Error,
+                format!("Host {:?} is not an IP address; use 0.0.0.0 to listen everywhere or 127.0.0.1 for local use", config.host),
+            );
+        }
+    };
+    // Port 0 binds a free port at startup
+    if config.port == 0 {
+        return Check::new("address", CheckLevel::Ok, format!("{} on a free port", ip));
+    }
+    match TcpListener::bind((ip, config.port)) {
+        Ok(_) => Check::new("address", CheckLevel::Ok, format!("{}:{} is available", ip, config.port)),
+        Err(e) => Check::new(
+            "address",
+            CheckLevel::Error,
+            format!("Can't listen on {}:{} ({}); stop the process using the port or choose another with --port", ip, config.port, e),
+        ),
+    }
+}
+
+fn check_timeouts(config: &ServerConfig) -> Check {
+    let (heartbeat, timeout) = (config.heartbeat_interval, config.connection_timeout);
+    if heartbeat.is_zero() || timeout.is_zero() {
+        return Check::new("timeouts", CheckLevel::Error, "The heartbeat interval and connection timeout must be positive");
+    }
+    if heartbeat >= timeout {
+        return Check::new(
+            "timeouts",
+            CheckLevel::Error,
+            format!(
+                "The heartbeat interval ({:?}) must be shorter than the connection timeout ({:?}), or idle clients are dropped between heartbeats",
+                heartbeat, timeout,
+            ),
+        );
+    }
+    Check::new("timeouts", CheckLevel::Ok, format!("Heartbeat every {:?}, timeout after {:?}", heartbeat, timeout))
+}
+
+fn check_tenants(config: &ServerConfig) -> Check {
+    match TenantRegistry::new(config.quota.clone(), config.tenants.clone()) {
+        Ok(registry) => Check::new("tenants", CheckLevel::Ok, format!("{} tenants", registry.ids().len())),
+        Err(e) => Check::new(
+            "tenants",
+            CheckLevel::Error,
+            format!("{}; tenant IDs are 1–64 letters, digits, '-' or '_', and each ID is used once", e),
+        ),
+    }
+}
+
+fn check_quota(config: &ServerConfig) -> Check {
+    let ratios = std::iter::once(&config.quota)
+        .chain(config.tenants.iter().filter_map(|tenant| tenant.quota.as_ref()))
+        .flat_map(|quota| [quota.warning_ratio, quota.hysteresis_ratio]);
+    for ratio in ratios {
+        if !(0.0..=1.0).contains(&ratio) {
+            return Check::new(
+                "quota",
+                CheckLevel::Warning,
+                format!("Quota ratio {} is outside 0 to 1; warnings will never or always be sent", ratio),
+            );
+        }
+    }
+    Check::new("quota", CheckLevel::Ok, "Quota ratios are valid")
+}
+
+fn check_assets(config: &ServerConfig) -> Check {
+    match &config.assets.source {
+        None => Check::new("assets", CheckLevel::Ok, "No frontend assets; serving the API only"),
+        Some(source) if source.has_index() => Check::new("assets", CheckLevel::Ok, format!("Serving {:?}", source)),
+        Some(source) => Check::new(
+            "assets",
+            CheckLevel::Warning,
+            format!("No index.html in {:?}; build the frontend (npm run build) or fix --assets", source),
+        ),
+    }
+}
+
+fn check_admin(config: &ServerConfig) -> Check {
+    match config.admin.token.as_deref() {
+        None => Check::new("admin", CheckLevel::Ok, "Admin API disabled"),
+        Some(token) if token.trim().is_empty() => Check::new(
+            "admin",
+            CheckLevel::Error,
+            "The admin token is empty; set a long random token or leave it unset to disable the admin API",
+        ),
+        Some(_) => Check::new("admin", CheckLevel::Ok, "Admin API enabled"),
+    }
+}
+
+fn check_overload(config: &ServerConfig) -> Check {
+    let overload = &config.overload;
+    match overload.budget {
+        None => Check::new("overload", CheckLevel::Ok, "No latency budget; work is never shed"),
+        Some(budget) if budget.is_zero() => Check::new(
+            "overload",
+            CheckLevel::Error,
+            "The latency budget is zero, so the server would always shed work; set a budget such as 50ms or none",
+        ),
+        Some(_) if !(overload.recovery_ratio > 0.0 && overload.recovery_ratio <= 1.0) => Check::new(
+            "overload",
+            CheckLevel::Warning,
+            format!("Recovery ratio {} is outside 0 to 1; shedding will never or immediately stop", overload.recovery_ratio),
+        ),
+        Some(budget) => Check::new("overload", CheckLevel::Ok, format!("Shedding work above a p99 latency of {:?}", budget)),
+    }
+}
+
+fn check_abuse(config: &ServerConfig) -> Check {
+    let abuse = &config.abuse;
+    if !abuse.is_enabled() {
+        return match abuse.restrict_for {
+            Some(_) => Check::new(
+                "abuse",
+                CheckLevel::Warning,
+                "Abusive clients would be restricted, but no rate limit is set to detect them",
+            ),
+            None => Check::new("abuse", CheckLevel::Ok, "No editing rate limits"),
+        };
+    }
+    if abuse.window.is_zero() {
+        return Check::new(
+            "abuse",
+            CheckLevel::Error,
+            "The abuse window is zero, so editing rates can't be measured; set a window such as 5s",
+        );
+    }
+    match abuse.restrict_for {
+        Some(restrict_for) => Check::new("abuse", CheckLevel::Ok, format!("Alerting on abusive editing rates, restricting writes for {:?}", restrict_for)),
+        None => Check::new("abuse", CheckLevel::Ok, "Alerting on abusive editing rates"),
+    }
+}
+
+fn check_memory(config: &ServerConfig) -> Check {
+    let memory = &config.memory;
+    if memory.interval.is_zero() {
+        return Check::new(
+            "memory",
+            CheckLevel::Error,
+            "The memory sampling interval is zero; set an interval such as 60s",
+        );
+    }
+    if memory.samples == 0 {
+        return Check::new(
+            "memory",
+            CheckLevel::Warning,
+            "No memory samples are kept, so metrics show no memory trend; keep at least one",
+        );
+    }
+    Check::new("memory", CheckLevel::Ok, format!("Sampling the memory of documents every {:?}, keeping {} samples", memory.interval, memory.samples))
+}
+
+fn check_storage(config: &ServerConfig) -> Check {
+    let storage = &config.storage;
+    if storage.snapshot_interval.is_zero() {
+        return Check::new(
+            "storage",
+            CheckLevel::Warning,
+            "The snapshot interval is zero, so snapshots are only saved on demand and operation logs keep growing; set an interval such as 60s",
+        );
+    }
+    Check::new("storage", CheckLevel::Ok, format!("Snapshotting changed documents every {:?}", storage.snapshot_interval))
+}
+
+fn check_passwords(config: &ServerConfig) -> Check {
+    let passwords = &config.passwords;
+    if let Err(e) = check_cost(passwords.memory_kib, passwords.iterations) {
+        return Check::new(
+            "passwords",
+            CheckLevel::Error,
+            format!("{}; use the defaults of 19456 KiB and 2 iterations", e),
+        );
+    }
+    if passwords.max_failures <= 1 {
+        return Check::new(
+            "passwords",
+            CheckLevel::Error,
+            "A single wrong password would lock a document; allow at least 2 failures",
+        );
+    }
+    if passwords.memory_kib < MIN_PASSWORD_MEMORY_KIB {
+        return Check::new(
+            "passwords",
+            CheckLevel::Warning,
+            format!("Document passwords are hashed with only {} KiB, which makes them easy to guess from a leaked hash", passwords.memory_kib),
+        );
+    }
+    Check::new(
+        "passwords",
+        CheckLevel::Ok,
+        format!("Locking documents for {:?} after {} wrong passwords", passwords.lockout, passwords.max_failures),
+    )
+}
+
+fn check_standby(config: &ServerConfig) -> Check {
+    let Some(standby) = &config.standby else {
+        return Check::new("standby", CheckLevel::Ok, "Serving as the primary");
+    };
+    match standby.primary.parse::<Uri>() {
+        Ok(uri) if uri.scheme_str() == Some("http") && uri.host().is_some() => {}
+        _ => {
+            return Check::new(
+                "standby",
+                CheckLevel::Error,
+                format!("Primary {:?} is not an HTTP URL such as http://primary:8080", standby.primary),
+            );
+        }
+    }
+    if config.admin.token.is_none() {
+        return Check::new(
+            "standby",
+            CheckLevel::Warning,
+            "The admin API is disabled, so the standby can't be promoted over HTTP; set an admin token",
+        );
+    }
+    Check::new("standby", CheckLevel::Ok, format!("Following {} as a warm standby", standby.primary))
+}
@@ -0,0 +1,280 @@
+/*
+ * File: src/websocket/events.rs
+ * Purpose: Document lifecycle events over HTTP for external indexers
+ *
+ * Search indexers and backup agents follow document changes by long
+ * polling, without a WebSocket or a message queue:
+ *
+ *   GET /events?since=<cursor>&limit=100&timeout=30
+ *   GET /t/<tenant>/events?since=<cursor>&key=<access key>
+ *
+ * The response lists the tenant's events after `since` and the cursor to
+ * pass next time. When there are none yet, the request waits up to
+ * `timeout` seconds for one. Events are:
+ * - created: a document came into existence
+ * - changed: a document's content changed; consecutive changes of the
+ *   same document are coalesced into one event carrying the newest version
+ *
+ * Events of password-protected documents are left out. The log is kept
+ * in memory, holding the newest `EVENT_LOG_CAPACITY` events of all
+ * tenants. A cursor older than the log, or from before a restart, gets
+ * `410 Gone`: the follower has missed events and should reindex.
+ */
+
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::watch;
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    Filter,
+};
+
+use crate::{tenant::DEFAULT_TENANT, websocket::server::ServerState};
+
+/// Most events kept in memory, across tenants
+pub const EVENT_LOG_CAPACITY: usize = 10_000;
+
+/// Events returned per response unless `limit` asks for fewer
+pub const MAX_EVENTS_PER_RESPONSE: usize = 1000;
+
+/// Events returned per response without `limit`
+const DEFAULT_EVENT_LIMIT: usize = 100;
+
+/// How long a poll waits for events without `timeout`
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait a poll may ask for
+pub const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Event log errors
+#[derive(Error, Debug, PartialEq)]
+pub enum EventError {
+    #[error("Cursor {0} is no longer available; reindex and start from the current cursor")]
+    Expired(u64),
+}
+
+/// What happened to a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentEventKind {
+    Created,
+    Changed,
+}
+
+/// A lifecycle event of a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentEvent {
+    /// Position of the event in the log; increases with every event
+    pub cursor: u64,
+    pub kind: DocumentEventKind,
+    pub document_id: String,
+    /// Document version after the event
+    pub version: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Events after a cursor, and the cursor to continue from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventPage {
+    pub events: Vec<DocumentEvent>,
+    pub next: u64,
+}
+
+#[derive(Debug, Default)]
+struct EventBuffer {
+    /// Events with their tenant, oldest first
+    events: VecDeque<(String, DocumentEvent)>,
+    /// Cursor of the newest event; 0 before the first
+    head: u64,
+    /// Newest cursor dropped from the log
+    evicted: u64,
+}
+
+/// In-memory log of document lifecycle events
+#[derive(Debug)]
+pub struct EventLog {
+    buffer: Mutex<EventBuffer>,
+    /// Head cursor, for waking up polls
+    head: watch::Sender<u64>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            buffer: Mutex::new(EventBuffer::default()),
+            head: watch::channel(0).0,
+        }
+    }
+}
+
+impl EventLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the cursor of the newest event
+    pub fn cursor(&self) -> u64 {
+        self.buffer.lock().head
+    }
+
+    /// Record an event of a tenant's document
+    pub fn record(&self, tenant_id: &str, document_id: &str, kind: DocumentEventKind, version: u64) {
+        let mut buffer = self.buffer.lock();
+        buffer.head += 1;
+        let cursor = buffer.head;
+        let event = DocumentEvent {
+            cursor,
+            kind,
+            document_id: document_id.to_string(),
+            version,
+            timestamp: Utc::now(),
+        };
+
+        // Moving the newest event to the new cursor keeps it after every
+        // cursor already handed out
+        match buffer.events.back_mut() {
+            Some((tenant, last))
+                if kind == DocumentEventKind::Changed
+                    && last.kind == DocumentEventKind::Changed
+                    && tenant == tenant_id
+                    && last.document_id == document_id =>
+            {
+                *last = event;
+            }
+            _ => {
+                if buffer.events.len() >= EVENT_LOG_CAPACITY {
+                    if let Some((_, evicted)) = buffer.events.pop_front() {
+                        buffer.evicted = evicted.cursor;
+                    }
+                }
+                buffer.events.push_back((tenant_id.to_string(), event));
+            }
+        }
+        drop(buffer);
+        self.head.send_replace(cursor);
+    }
+
+    /// Get up to `limit` events of a tenant's visible documents after a
+    /// cursor. Without a cursor, every event still in the log is returned.
+    pub fn since(
+        &self,
+        tenant_id: &str,
+        since: Option<u64>,
+        limit: usize,
+        visible: impl Fn(&str) -> bool,
+    ) -> Result<EventPage, EventError> {
+        let buffer = self.buffer.lock();
+        if let Some(since) = since {
+            if since < buffer.evicted || since > buffer.head {
+                return Err(EventError::Expired(since));
+            }
+        }
+        let since = since.unwrap_or(0);
+        let events: Vec<DocumentEvent> = buffer.events
+            .iter()
+            .filter(|(tenant, event)| event.cursor > since && tenant == tenant_id && visible(&event.document_id))
+            .take(limit)
+            .map(|(_, event)| event.clone())
+            .collect();
+        // A full page continues after its last event; otherwise everything
+        // up to the head has been seen
+        let next = match events.last() {
+            Some(last) if events.len() == limit => last.cursor,
+            _ => buffer.head.max(since),
+        };
+        Ok(EventPage { events, next })
+    }
+
+    /// Like `since`, but wait up to `timeout` for events if there are none
+    pub async fn wait_since(
+        &self,
+        tenant_id: &str,
+        since: Option<u64>,
+        limit: usize,
+        timeout: Duration,
+        visible: impl Fn(&str) -> bool,
+    ) -> Result<EventPage, EventError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut head = self.head.subscribe();
+        loop {
+            let page = self.since(tenant_id, since, limit, &visible)?;
+            if !page.events.is_empty() {
+                return Ok(page);
+            }
+            match tokio::time::timeout_at(deadline, head.changed()).await {
+                Ok(Ok(())) => continue,
+                _ => return Ok(page),
+            }
+        }
+    }
+}
+
+/// Build the filter serving document events
+pub(crate) fn routes(state: ServerState) -> BoxedFilter<(Response<Body>,)> {
+    warp::get()
+        .and(
+            warp::path!("events")
+                .map(|| DEFAULT_TENANT.to_string())
+                .or(warp::path!("t" / String / "events"))
+                .unify(),
+        )
+        .and(warp::query::<HashMap<String, String>>())
+        .then(move |tenant_id: String, query: HashMap<String, String>| {
+            let state = state.clone();
+            async move { respond(&state, &tenant_id, &query).await }
+        })
+        .boxed()
+}
+
+/// Answer a poll for events
+async fn respond(state: &ServerState, tenant_id: &str, query: &HashMap<String, String>) -> Response<Body> {
+    let tenant = match state.tenant(tenant_id) {
+        Ok(tenant) => tenant,
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    if let Err(e) = tenant.authorize(query.get("key").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e.to_string());
+    }
+
+    let since = match query.get("since").map(|since| since.parse::<u64>()) {
+        None => None,
+        Some(Ok(since)) => Some(since),
+        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "since must be a cursor".to_string()),
+    };
+    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => DEFAULT_EVENT_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(MAX_EVENTS_PER_RESPONSE),
+        Some(_) => return plain(StatusCode::BAD_REQUEST, "limit must be a positive number".to_string()),
+    };
+    let timeout = match query.get("timeout").map(|timeout| timeout.parse::<u64>()) {
+        None => DEFAULT_POLL_TIMEOUT,
+        Some(Ok(seconds)) => Duration::from_secs(seconds).min(MAX_POLL_TIMEOUT),
+        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "timeout must be a number of seconds".to_string()),
+    };
+
+    // Protected documents are left out; their cursors are still passed
+    let visible = |document_id: &str| !tenant.passwords().is_protected(document_id);
+    match state.events().wait_since(tenant.id(), since, limit, timeout, visible).await {
+        Ok(page) => {
+            let mut response = Response::new(Body::from(json!(page).to_string()));
+            if let Ok(value) = "application/json".parse() {
+                response.headers_mut().insert(header::CONTENT_TYPE, value);
+            }
+            response
+        }
+        Err(e) => plain(StatusCode::GONE, e.to_string()),
+    }
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,283 @@
+/*
+ * File: src/websocket/export.rs
+ * Purpose: HTTP export of documents
+ *
+ * Serves a document's current content over plain HTTP:
+ *
+ *   GET /documents/<id>/export?format=html
+ *   GET /t/<tenant>/documents/<id>/export?format=text&key=<access key>
+ *
+ * `format` is `text` (the default) or `html`; see `blocks::export`.
+ * HTML exports with `theme=<name>` highlight code blocks tagged with a
+ * language in that theme, such as `InspiredGitHub`, for read-only
+ * sharing of code pads; see `blocks::highlight`.
+ * Documents can be addressed by slug. Tenants with access keys require
+ * `key`, as for WebSocket connections, and password-protected documents
+ * a share token as `share`. Documents of tenants whose clients
+ * encrypt content end to end are never exported: the server only holds
+ * ciphertext, and a plaintext export would be meaningless at best.
+ *
+ * `ExportConfig` can watermark exports with the document's authors and
+ * the export time.
+ *
+ * `min_version=<version>` asks for an export at least that new, such as
+ * the version acknowledged for the caller's last write. The request waits
+ * briefly for it and gets `503` if the document doesn't get there.
+ *
+ * Exports are cheap to revalidate, for documents embedded read-only in
+ * other sites:
+ * - The ETag is derived from the document version and checksum, and the
+ *   theme, so it changes with every edit and costs no rendering
+ * - `If-None-Match` requests for an unchanged document get `304`
+ * - Rendered exports are kept in an in-process cache until the document
+ *   changes; the least recently used entries are evicted beyond
+ *   `EXPORT_CACHE_CAPACITY`. Exports stamped with their time are rendered
+ *   every time instead
+ */
+
+use std::{collections::HashMap, sync::Arc};
+use chrono::Utc;
+use parking_lot::Mutex;
+use warp::{
+    filters::BoxedFilter,
+    http::{header, HeaderMap, Response, StatusCode},
+    hyper::{body::Bytes, Body},
+    Filter,
+};
+
+use crate::{
+    blocks::{export, export_highlighted, highlight, ExportFormat, Watermark},
+    crdt::{Document, DocumentError},
+    tenant::DEFAULT_TENANT,
+    websocket::server::{EditorServer, ServerState},
+};
+
+/// Most rendered exports kept in memory
+pub const EXPORT_CACHE_CAPACITY: usize = 256;
+
+/// Exports are always revalidated, which is cheap with ETags
+const EXPORT_CACHE_CONTROL: &str = "no-cache";
+
+/// Watermarks of HTTP exports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportConfig {
+    /// Append the clients whose text is in the document
+    pub watermark_authors: bool,
+    /// Append the time of the export
+    pub watermark_timestamp: bool,
+}
+
+impl ExportConfig {
+    fn watermark(&self, document: &Document) -> Watermark {
+        Watermark {
+            authors: if self.watermark_authors { document.authors() } else { Vec::new() },
+            exported_at: self.watermark_timestamp.then(Utc::now),
+        }
+    }
+}
+
+/// Tenant, canonical document ID, format and theme of a cached export
+type CacheKey = (String, String, ExportFormat, Option<String>);
+
+struct CachedExport {
+    etag: String,
+    body: Bytes,
+    /// Value of the cache clock when the entry was last served
+    used: u64,
+}
+
+/// Rendered exports, keyed by document and format
+#[derive(Default)]
+struct ExportCache {
+    entries: HashMap<CacheKey, CachedExport>,
+    clock: u64,
+}
+
+impl ExportCache {
+    /// Get the export for a key if it is still current
+    fn get(&mut self, key: &CacheKey, etag: &str) -> Option<Bytes> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key).filter(|entry| entry.etag == etag)?;
+        entry.used = self.clock;
+        Some(entry.body.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, etag: String, body: Bytes) {
+        self.clock += 1;
+        if self.entries.len() >= EXPORT_CACHE_CAPACITY && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.used).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, CachedExport { etag, body, used: self.clock });
+    }
+}
+
+/// Get the ETag of a document's export in a format
+pub fn etag(document: &Document, format: ExportFormat) -> String {
+    themed_etag(document, format, None)
+}
+
+/// Get the ETag of a document's export in a format and highlighting theme
+pub fn themed_etag(document: &Document, format: ExportFormat, theme: Option<&str>) -> String {
+    match theme {
+        None => format!("\"{}-{:x}-{}\"", document.version(), document.checksum(), format.name()),
+        Some(theme) => format!("\"{}-{:x}-{}-{}\"", document.version(), document.checksum(), format.name(), theme),
+    }
+}
+
+/// Build the filter serving document exports
+pub(crate) fn routes(state: ServerState) -> BoxedFilter<(Response<Body>,)> {
+    let cache = Arc::new(Mutex::new(ExportCache::default()));
+    warp::get()
+        .and(
+            warp::path!("documents" / String / "export")
+                .map(|document_id: String| (DEFAULT_TENANT.to_string(), document_id))
+                .or(warp::path!("t" / String / "documents" / String / "export")
+                    .map(|tenant_id: String, document_id: String| (tenant_id, document_id)))
+                .unify(),
+        )
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::headers_cloned())
+        .then(move |(tenant_id, document_id): (String, String), query: HashMap<String, String>, headers: HeaderMap| {
+            let state = state.clone();
+            let cache = cache.clone();
+            async move { respond(&state, &cache, &tenant_id, &document_id, &query, &headers).await }
+        })
+        .boxed()
+}
+
+/// Export a document, or explain why it can't be
+async fn respond(
+    state: &ServerState,
+    cache: &Mutex<ExportCache>,
+    tenant_id: &str,
+    document_id: &str,
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+) -> Response<Body> {
+    let tenant = match state.tenant(tenant_id) {
+        Ok(tenant) => tenant,
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    if let Err(e) = tenant.authorize(query.get("key").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e.to_string());
+    }
+    if tenant.encryption().is_end_to_end() {
+        return plain(
+            StatusCode::FORBIDDEN,
+            format!("Documents of tenant {} are end-to-end encrypted and can't be exported", tenant.id()),
+        );
+    }
+    if let Err(e) = state.check_http_access(&tenant, document_id, query.get("share").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e);
+    }
+    let format = match query.get("format").map(|format| format.parse::<ExportFormat>()) {
+        None => ExportFormat::default(),
+        Some(Ok(format)) => format,
+        Some(Err(e)) => return plain(StatusCode::BAD_REQUEST, e),
+    };
+    let theme = query.get("theme").map(String::as_str);
+    if let Some(theme) = theme {
+        if format != ExportFormat::Html {
+            return plain(StatusCode::BAD_REQUEST, "theme only applies to html exports".to_string());
+        }
+        if let Err(e) = highlight::theme(theme) {
+            return plain(StatusCode::BAD_REQUEST, e.to_string());
+        }
+    }
+
+    let min_version = match query.get("min_version").map(|version| version.parse::<u64>()) {
+        None => None,
+        Some(Ok(version)) => Some(version),
+        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "min_version must be a document version".to_string()),
+    };
+    match EditorServer::read_at_least(state, &tenant, document_id, min_version, |_| ()).await {
+        Ok(()) => {}
+        Err(e @ DocumentError::Stale { .. }) => {
+            let mut response = plain(StatusCode::SERVICE_UNAVAILABLE, e.to_string());
+            if let Ok(value) = "1".parse() {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+            return response;
+        }
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    }
+
+    let Some((canonical, current)) = EditorServer::with_document(state, &tenant, document_id, |doc| {
+        (doc.id().to_string(), themed_etag(doc, format, theme))
+    })
+    .await
+    else {
+        return plain(StatusCode::NOT_FOUND, format!("Document not found: {}", document_id));
+    };
+
+    if matches_etag(headers, &current) {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        set_headers(&mut response, &current, format);
+        return response;
+    }
+
+    let config = state.export_config();
+    let key = (tenant.id().to_string(), canonical, format, theme.map(str::to_string));
+    let cached = if config.watermark_timestamp { None } else { cache.lock().get(&key, &current) };
+    let (etag, body) = match cached {
+        Some(body) => (current, body),
+        None => {
+            // Render and tag the same snapshot; the document may have
+            // changed since the ETag above was computed
+            let rendered = EditorServer::with_document(state, &tenant, document_id, |doc| {
+                let mut body = match theme {
+                    Some(theme) => export_highlighted(&doc.content(), theme).unwrap_or_default(),
+                    None => export(&doc.content(), format),
+                };
+                body.push_str(&config.watermark(doc).render(format));
+                (themed_etag(doc, format, theme), Bytes::from(body))
+            })
+            .await;
+            let Some((etag, body)) = rendered else {
+                return plain(StatusCode::NOT_FOUND, format!("Document not found: {}", document_id));
+            };
+            if !config.watermark_timestamp {
+                cache.lock().insert(key, etag.clone(), body.clone());
+            }
+            (etag, body)
+        }
+    };
+
+    let mut response = Response::new(Body::from(body));
+    set_headers(&mut response, &etag, format);
+    response
+}
+
+/// Check `If-None-Match` against the current ETag
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+fn set_headers(response: &mut Response<Body>, etag: &str, format: ExportFormat) {
+    let headers = response.headers_mut();
+    for (name, value) in [
+        (header::CONTENT_TYPE, format.content_type()),
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, EXPORT_CACHE_CONTROL),
+    ] {
+        if let Ok(value) = value.parse() {
+            headers.insert(name, value);
+        }
+    }
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
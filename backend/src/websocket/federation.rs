@@ -0,0 +1,351 @@
+/*
+ * File: src/websocket/federation.rs
+ * Purpose: Mirroring documents between CoEdit servers
+ *
+ * A sync link connects to another CoEdit server as an ordinary WebSocket
+ * client and keeps a local document and a remote one in step, in both
+ * directions, using the same protocol as editors:
+ * - On connect, the two copies are reconciled with the anti-entropy repair
+ *   protocol: the remote server returns its operations for the regions
+ *   that differ, and the link sends the local operations for the same
+ *   regions back
+ * - Afterwards, operations relayed by the remote server are applied
+ *   locally, and local operations are sent to the remote server
+ * - Dropped connections are retried with backoff and reconciled again
+ *
+ * Relayed operations keep their source. Operations exchanged while
+ * reconciling arrive without one and are tagged `import`, as are the
+ * local server's own operations when sent on, since the remote server
+ * only accepts `server` operations from itself.
+ *
+ * Both servers skip operations they already have, so the exchange is
+ * idempotent. One side of a pair opens the link; links in both directions
+ * between the same documents are redundant.
+ */
+
+use std::{sync::Arc, time::Duration};
+use futures_util::{SinkExt, StreamExt};
+use thiserror::Error;
+use tokio::{net::TcpStream, sync::broadcast, task::JoinHandle};
+use tokio_tungstenite::{connect_async, tungstenite::Message as Frame, MaybeTlsStream, WebSocketStream};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    crdt::{Document, Operation, OperationSource, CHECKSUM_REGIONS},
+    tenant::{Tenant, DEFAULT_TENANT},
+    websocket::{
+        message::{Message, MessageType, OperationMessage, RepairRequestMessage, RepairResponseMessage},
+        server::{EditorServer, OperationEvent, ServerState},
+    },
+};
+
+/// Request ID of the reconciling repair request
+const REPAIR_REQUEST_ID: &str = "sync-repair";
+
+/// How long to wait for the remote server's welcome and repair replies
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// First and longest delay between reconnect attempts
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Sync link errors
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("Failed to connect: {0}")]
+    Connect(String),
+    #[error("Unexpected reply from remote server: {0}")]
+    Protocol(String),
+    #[error("Remote server did not reply in time")]
+    Timeout,
+    #[error("Connection closed")]
+    Closed,
+    #[error("Fell behind local operations")]
+    Lagged,
+}
+
+/// A local document mirroring a document on another server
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncLink {
+    /// WebSocket URL of the remote server, including tenant path and access
+    /// key if needed, e.g. `ws://team.example:8080/t/acme/ws?key=...`
+    pub url: String,
+    /// Document on the remote server, by ID or slug
+    pub remote_document: String,
+    /// Tenant of the local document
+    pub local_tenant: String,
+    /// Local document, by ID or slug
+    pub local_document: String,
+}
+
+impl SyncLink {
+    /// Mirror a remote document into the default tenant under the same ID
+    pub fn new(url: impl Into<String>, document_id: impl Into<String>) -> Self {
+        let document_id = document_id.into();
+        Self {
+            url: url.into(),
+            remote_document: document_id.clone(),
+            local_tenant: DEFAULT_TENANT.to_string(),
+            local_document: document_id,
+        }
+    }
+}
+
+/// Handle of a running sync link
+pub struct SyncHandle {
+    shutdown: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+impl SyncHandle {
+    /// Stop mirroring and close the connection
+    pub fn stop(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Check whether the link has stopped
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for SyncHandle {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Start a sync link in the background
+pub(crate) fn spawn(state: ServerState, tenant: Arc<Tenant>, link: SyncLink) -> SyncHandle {
+    let shutdown = CancellationToken::new();
+    let task = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let mut backoff = MIN_BACKOFF;
+            loop {
+                let mut session = Session::new(&state, &tenant, &link);
+                let result = tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    result = session.run(&shutdown) => result,
+                };
+                match result {
+                    Ok(()) => return,
+                    Err(e) if session.synced => {
+                        log::warn!("Sync of {} with {} interrupted: {}", link.local_document, link.url, e);
+                        backoff = MIN_BACKOFF;
+                    }
+                    Err(e) => log::warn!("Sync of {} with {} failed: {}", link.local_document, link.url, e),
+                }
+
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    });
+    SyncHandle { shutdown, task }
+}
+
+/// One connection of a sync link
+struct Session<'a> {
+    state: &'a ServerState,
+    tenant: &'a Tenant,
+    link: &'a SyncLink,
+    /// Local document ID, with slugs resolved
+    local_document: String,
+    /// Remote document ID, with slugs resolved once the remote replies
+    remote_document: String,
+    /// Client ID the remote server assigned to this connection
+    client_id: String,
+    /// Whether the initial reconciliation completed
+    synced: bool,
+}
+
+impl<'a> Session<'a> {
+    fn new(state: &'a ServerState, tenant: &'a Tenant, link: &'a SyncLink) -> Self {
+        Self {
+            state,
+            tenant,
+            link,
+            local_document: tenant.aliases().resolve(&link.local_document),
+            remote_document: link.remote_document.clone(),
+            client_id: String::new(),
+            synced: false,
+        }
+    }
+
+    /// Origin recorded on operations this session applies locally
+    fn origin(&self) -> String {
+        format!("sync:{}", self.client_id)
+    }
+
+    /// Connect, reconcile, and mirror until shutdown or a connection error
+    async fn run(&mut self, shutdown: &CancellationToken) -> Result<(), SyncError> {
+        let (mut socket, _) = connect_async(self.link.url.as_str())
+            .await
+            .map_err(|e| SyncError::Connect(e.to_string()))?;
+
+        let welcome = self.receive(&mut socket, |message| message.payload()["status"] == "connected").await?;
+        self.client_id = welcome.payload()["client_id"]
+            .as_str()
+            .ok_or_else(|| SyncError::Protocol("welcome status is missing client_id".to_string()))?
+            .to_string();
+
+        // Subscribe before taking the snapshot so no local edit falls between
+        let mut events = EditorServer::operation_events(self.state);
+        self.reconcile(&mut socket).await?;
+        self.synced = true;
+        log::info!("Syncing {} with {} on {}", self.local_document, self.remote_document, self.link.url);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => {
+                    let _ = socket.close(None).await;
+                    return Ok(());
+                }
+                frame = socket.next() => {
+                    if let Some(message) = parse(frame)? {
+                        self.apply_remote(message).await;
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) => self.forward_local(&mut socket, event).await?,
+                    Err(broadcast::error::RecvError::Lagged(_)) => return Err(SyncError::Lagged),
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+
+    /// Exchange the operations of the regions where the two copies differ
+    async fn reconcile(&mut self, socket: &mut Socket) -> Result<(), SyncError> {
+        let local = EditorServer::document_copy(self.state, self.tenant, &self.local_document)
+            .await
+            .unwrap_or_else(|| Document::new(self.local_document.clone()));
+
+        let request = RepairRequestMessage::new(self.link.remote_document.clone(), &local);
+        self.send(socket, MessageType::RepairRequest, &request, Some(REPAIR_REQUEST_ID)).await?;
+
+        // Operations relayed while waiting are applied after the repair
+        let mut relayed = Vec::new();
+        let reply = loop {
+            let message = self.receive(socket, |_| true).await?;
+            if message.request_id() == Some(REPAIR_REQUEST_ID) {
+                break message;
+            }
+            relayed.push(message);
+        };
+
+        let (regions, remote_operations) = match reply.message_type() {
+            MessageType::RepairResponse => {
+                let response: RepairResponseMessage = serde_json::from_value(reply.payload().clone())
+                    .map_err(|e| SyncError::Protocol(e.to_string()))?;
+                self.remote_document = response.document_id;
+                (response.regions, response.operations)
+            }
+            // The remote server doesn't have the document yet: send all of ours
+            MessageType::Error => ((0..CHECKSUM_REGIONS).collect(), Vec::new()),
+            other => return Err(SyncError::Protocol(format!("{:?} in reply to repairRequest", other))),
+        };
+
+        for operation in local.region_operations(&regions) {
+            self.send_operation(socket, operation, OperationSource::Import).await?;
+        }
+        let origin = self.origin();
+        for operation in remote_operations {
+            self.merge(operation, OperationSource::Import, &origin).await;
+        }
+        for message in relayed {
+            self.apply_remote(message).await;
+        }
+        Ok(())
+    }
+
+    /// Apply an operation relayed by the remote server, if it is for the
+    /// mirrored document
+    async fn apply_remote(&self, message: Message) {
+        match message.message_type() {
+            MessageType::Operation => {
+                let Ok(op_msg) = serde_json::from_value::<OperationMessage>(message.payload().clone()) else {
+                    return;
+                };
+                if op_msg.document_id == self.remote_document {
+                    self.merge(op_msg.operation, op_msg.source, &self.origin()).await;
+                }
+            }
+            MessageType::Error => {
+                let payload = self.state.redactor().redact_value(message.payload());
+                log::warn!("Remote server {} rejected a synced operation: {}", self.link.url, payload);
+            }
+            _ => {}
+        }
+    }
+
+    async fn merge(&self, operation: Operation, source: OperationSource, origin: &str) {
+        if let Err(e) = EditorServer::merge_remote(self.state, self.tenant, &self.local_document, operation, source, origin).await {
+            log::warn!("Failed to apply synced operation to {}: {}", self.local_document, e);
+        }
+    }
+
+    /// Send a local operation to the remote server, unless it came from there
+    async fn forward_local(&self, socket: &mut Socket, event: OperationEvent) -> Result<(), SyncError> {
+        if event.tenant_id != self.tenant.id() || event.document_id != self.local_document || event.origin == self.origin() {
+            return Ok(());
+        }
+        let source = match event.source {
+            OperationSource::Server => OperationSource::Import,
+            source => source,
+        };
+        self.send_operation(socket, event.operation, source).await
+    }
+
+    async fn send_operation(&self, socket: &mut Socket, operation: Operation, source: OperationSource) -> Result<(), SyncError> {
+        let payload = OperationMessage::new(operation, self.remote_document.clone()).with_source(source);
+        self.send(socket, MessageType::Operation, &payload, None).await
+    }
+
+    async fn send(
+        &self,
+        socket: &mut Socket,
+        message_type: MessageType,
+        payload: &impl serde::Serialize,
+        request_id: Option<&str>,
+    ) -> Result<(), SyncError> {
+        let payload = serde_json::to_value(payload).map_err(|e| SyncError::Protocol(e.to_string()))?;
+        let message = Message::new(message_type, self.client_id.clone(), payload)
+            .with_request_id(request_id.map(str::to_string));
+        let text = serde_json::to_string(&message).map_err(|e| SyncError::Protocol(e.to_string()))?;
+        socket.send(Frame::Text(text)).await.map_err(|_| SyncError::Closed)
+    }
+
+    /// Wait for the first message matching a predicate
+    async fn receive(&self, socket: &mut Socket, wanted: impl Fn(&Message) -> bool) -> Result<Message, SyncError> {
+        tokio::time::timeout(REPLY_TIMEOUT, async {
+            loop {
+                if let Some(message) = parse(socket.next().await)? {
+                    if wanted(&message) {
+                        return Ok(message);
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|_| SyncError::Timeout)?
+    }
+}
+
+/// Decode a frame from the remote server. Frames that don't carry protocol
+/// messages, such as pings, decode to `None`.
+fn parse(frame: Option<Result<Frame, tokio_tungstenite::tungstenite::Error>>) -> Result<Option<Message>, SyncError> {
+    match frame {
+        Some(Ok(Frame::Text(text))) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| SyncError::Protocol(e.to_string())),
+        Some(Ok(Frame::Close(_))) | Some(Err(_)) | None => Err(SyncError::Closed),
+        Some(Ok(_)) => Ok(None),
+    }
+}
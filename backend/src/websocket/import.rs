@@ -0,0 +1,148 @@
+/*
+ * File: src/websocket/import.rs
+ * Purpose: HTTP import of outside text into documents
+ *
+ * Appends a source to a document over plain HTTP, for migrating notes:
+ *
+ *   POST /documents/<id>/import?format=markdown
+ *   POST /t/<tenant>/documents/<id>/import?format=text&key=<access key>
+ *
+ * The request body is the source. `format` is `text` (the default) or
+ * `markdown`; see `blocks::import`. The document is created if needed,
+ * and the imported text and checklists reach connected clients like any
+ * other edit, tagged with the `import` source. The response lists the
+ * checklists made and the constructs that were kept as text:
+ *
+ *   {"document_id": "notes", "version": 42, "inserted": 40,
+ *    "checklists": ["tasks-1"], "unmapped": [{"line": 7, "construct": "table"}]}
+ *
+ * Sources must be UTF-8. Imports are subject to the tenant's document
+ * size limit, and documents of tenants whose clients encrypt content end
+ * to end can't be imported into, as with exports. Password-protected
+ * documents need a share token as `share`, as exports do.
+ */
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use warp::{
+    filters::BoxedFilter,
+    http::{Response, StatusCode},
+    hyper::{body::Bytes, Body},
+    Filter,
+};
+
+use crate::{
+    blocks::{ImportFormat, Unmapped},
+    crdt::DocumentError,
+    tenant::{TenantError, DEFAULT_TENANT},
+    websocket::server::{EditorServer, ServerState},
+};
+
+/// Client ID imported operations are made and relayed under
+pub const IMPORT_CLIENT_ID: &str = "import";
+
+/// Import errors
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error(transparent)]
+    Tenant(#[from] TenantError),
+    #[error("Documents of tenant {0} are end-to-end encrypted and can't be imported into")]
+    EndToEnd(String),
+    #[error("Importing into document {document_id} would exceed its size limit of {limit} characters")]
+    TooLarge { document_id: String, limit: usize },
+    #[error(transparent)]
+    Document(#[from] DocumentError),
+}
+
+/// What an import added to a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Canonical ID of the document
+    pub document_id: String,
+    /// Version of the document after the import
+    pub version: u64,
+    /// Characters appended
+    pub inserted: usize,
+    /// Names of the checklists made from task lists
+    pub checklists: Vec<String>,
+    /// Constructs of the source that were kept as text or flattened
+    pub unmapped: Vec<Unmapped>,
+}
+
+/// Build the filter serving document imports
+pub(crate) fn routes(state: ServerState) -> BoxedFilter<(Response<Body>,)> {
+    warp::post()
+        .and(
+            warp::path!("documents" / String / "import")
+                .map(|document_id: String| (DEFAULT_TENANT.to_string(), document_id))
+                .or(warp::path!("t" / String / "documents" / String / "import")
+                    .map(|tenant_id: String, document_id: String| (tenant_id, document_id)))
+                .unify(),
+        )
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::body::bytes())
+        .then(move |(tenant_id, document_id): (String, String), query: HashMap<String, String>, body: Bytes| {
+            let state = state.clone();
+            async move { respond(&state, &tenant_id, &document_id, &query, &body).await }
+        })
+        .boxed()
+}
+
+/// Import a source, or explain why it can't be
+async fn respond(
+    state: &ServerState,
+    tenant_id: &str,
+    document_id: &str,
+    query: &HashMap<String, String>,
+    body: &[u8],
+) -> Response<Body> {
+    let tenant = match state.tenant(tenant_id) {
+        Ok(tenant) => tenant,
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    if let Err(e) = tenant.authorize(query.get("key").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e.to_string());
+    }
+    if let Err(e) = state.check_http_access(&tenant, document_id, query.get("share").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e);
+    }
+    let format = match query.get("format").map(|format| format.parse::<ImportFormat>()) {
+        None => ImportFormat::default(),
+        Some(Ok(format)) => format,
+        Some(Err(e)) => return plain(StatusCode::BAD_REQUEST, e),
+    };
+    let Ok(source) = std::str::from_utf8(body) else {
+        return plain(StatusCode::BAD_REQUEST, "Sources must be UTF-8".to_string());
+    };
+
+    match EditorServer::import_source(state, tenant_id, document_id, source, format).await {
+        Ok(report) => match serde_json::to_string(&report) {
+            Ok(json) => {
+                let mut response = Response::new(Body::from(json));
+                if let Ok(value) = "application/json".parse() {
+                    response.headers_mut().insert(warp::http::header::CONTENT_TYPE, value);
+                }
+                response
+            }
+            Err(e) => plain(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        Err(e) => plain(status(&e), e.to_string()),
+    }
+}
+
+/// HTTP status of an import error
+fn status(error: &ImportError) -> StatusCode {
+    match error {
+        ImportError::Tenant(_) => StatusCode::NOT_FOUND,
+        ImportError::EndToEnd(_) => StatusCode::FORBIDDEN,
+        ImportError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        ImportError::Document(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
@@ -10,9 +10,17 @@
  * Messages are serialized using serde for WebSocket transmission
  */
 
+use std::time::Instant;
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use crate::crdt::{Operation, Document};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::{
+    blocks::{BlockDiagnostic, ChecklistItem, ChecklistOperation},
+    crdt::{DiffRange, Operation, OperationSource, Document, DocumentUpdate, PlaybackFrame, Position, StateVector, CHECKSUM_REGIONS},
+    presence::{is_valid_color, MAX_NAME_CHARS},
+    tenant::CharacterSet,
+    undo::UndoScope,
+    websocket::tail::DocumentTail,
+};
 
 /// Represents the type of WebSocket message
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -28,8 +36,49 @@ pub enum MessageType {
     Operation,
     Error,
     Status,
+    Ack,
+    PlaybackRequest,
+    PlaybackFrame,
+    PlaybackStop,
+    RepairRequest,
+    RepairResponse,
+    SetSlug,
+    SetFrozen,
+    SettingsChanged,
+    CreateBreakouts,
+    CheckSyntax,
+    SyntaxReport,
+    Undo,
+    Redo,
+    SetUndoScope,
+    SetCharset,
+    DocumentUpdate,
+    ChecklistOperation,
+    GetChecklist,
+    ChecklistState,
+    DocumentTransferred,
+    Command,
+    DiffRequest,
+    DiffAnnotations,
+    DiffStop,
+    SubscribeActivity,
+    ActivityPage,
+    Activity,
+    UnsubscribeActivity,
+    SetPassword,
+    UnlockDocument,
+    ShareDocument,
+    SyncRequest,
+    SyncResponse,
+    SetPresence,
+    GetPresence,
+    PresenceUpdate,
+    CursorUpdate,
 }
 
+/// Upper bound for playback speed, to keep a single playback from flooding a client
+pub const MAX_PLAYBACK_OPS_PER_SECOND: f64 = 1000.0;
+
 /// Base message structure for WebSocket communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -37,6 +86,24 @@ pub struct Message {
     message_type: MessageType,
     client_id: String,
     payload: serde_json::Value,
+    /// Client-chosen ID echoed in acks and errors for log correlation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    /// Feature bits of the capabilities needed to understand the message,
+    /// see `compat`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    requires: u64,
+    /// Fields of newer protocol versions, kept to relay them
+    #[serde(flatten)]
+    extensions: serde_json::Map<String, serde_json::Value>,
+    /// When an ephemeral message, such as a cursor, goes stale and is
+    /// dropped rather than delivered; None for messages never dropped
+    #[serde(skip)]
+    stale_at: Option<Instant>,
+}
+
+fn is_zero(bits: &u64) -> bool {
+    *bits == 0
 }
 
 /// Message for document operations (insert, delete)
@@ -44,6 +111,32 @@ pub struct Message {
 pub struct OperationMessage {
     pub operation: Operation,
     pub document_id: String,
+    /// Kind of actor the operation comes from; omitted for users
+    #[serde(default, skip_serializing_if = "OperationSource::is_user")]
+    pub source: OperationSource,
+}
+
+/// Message creating a document, optionally with existing text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateDocumentMessage {
+    pub document_id: String,
+    /// Text the document starts with
+    #[serde(default)]
+    pub initial_content: String,
+    /// Bind the document to the client's session: it is left out of
+    /// events and sync, and destroyed when the client disconnects
+    #[serde(default)]
+    pub temporary: bool,
+}
+
+/// Message confirming a document was created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentCreatedMessage {
+    pub document_id: String,
+    /// Document version after seeding; one per initial character
+    pub version: u64,
+    /// Number of characters the document starts with
+    pub length: usize,
 }
 
 /// Message for connection status updates
@@ -54,12 +147,337 @@ pub struct StatusMessage {
     pub timestamp: DateTime<Utc>,
 }
 
+/// Message requesting a document's current state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetDocumentMessage {
+    pub document_id: String,
+    /// Version the state must have reached, such as the version of an ack
+    /// the client received; the reply waits briefly for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<u64>,
+    /// The client applies a tail: the reply may hold an earlier snapshot
+    /// and the operations after it, see `tail`
+    #[serde(default)]
+    pub accept_tail: bool,
+}
+
 /// Message for document state synchronization
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentStateMessage {
     pub document_id: String,
     pub content: String,
+    /// Document version the content corresponds to
+    #[serde(default)]
+    pub version: u64,
+    /// Checksum of the content, see `Document::checksum`
+    #[serde(default)]
+    pub checksum: u64,
     pub timestamp: DateTime<Utc>,
+    /// Operations applied after `version`, in order, for requests accepting
+    /// a tail
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tail: Vec<Operation>,
+}
+
+/// Message requesting playback of a document's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackRequestMessage {
+    pub document_id: String,
+    /// Index of the first operation to stream
+    #[serde(default)]
+    pub from: usize,
+    /// Number of frames to stream; all remaining frames when absent
+    #[serde(default)]
+    pub count: Option<usize>,
+    /// Streaming speed; frames are sent back-to-back when absent
+    #[serde(default)]
+    pub ops_per_second: Option<f64>,
+}
+
+/// Message carrying one reconstructed playback state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackFrameMessage {
+    pub document_id: String,
+    #[serde(flatten)]
+    pub frame: PlaybackFrame,
+}
+
+/// Message asking the server which regions of a document diverged from
+/// the client's copy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairRequestMessage {
+    pub document_id: String,
+    /// The client's hash of every checksum region, in order
+    pub regions: Vec<u64>,
+}
+
+/// Message carrying the server's operations for diverged regions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairResponseMessage {
+    pub document_id: String,
+    /// Server document version the operations correspond to
+    pub version: u64,
+    /// Server checksum, to confirm the repair
+    pub checksum: u64,
+    /// Indices of the regions that diverged
+    pub regions: Vec<usize>,
+    /// The server's operations for those regions, in order
+    pub operations: Vec<Operation>,
+}
+
+/// Message giving a document a human-friendly slug
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetSlugMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    pub slug: String,
+}
+
+/// Message freezing or unfreezing a document for everyone but its owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetFrozenMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    pub frozen: bool,
+}
+
+/// Message protecting a document with a password, or removing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetPasswordMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    /// New password; None removes the password
+    pub password: Option<String>,
+}
+
+/// Message unlocking a password-protected document for the connection,
+/// with its password or a share token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockDocumentMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Token from `shareDocument`, instead of the password
+    #[serde(default)]
+    pub share_token: Option<String>,
+}
+
+/// Message asking for a share token unlocking a password-protected
+/// document without its password
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareDocumentMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+}
+
+/// Message announcing a document's new classroom settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsChangedMessage {
+    pub document_id: String,
+    pub owner: Option<String>,
+    pub frozen: bool,
+    #[serde(default)]
+    pub undo: UndoScope,
+    #[serde(default)]
+    pub charset: CharacterSet,
+}
+
+/// Message creating one breakout copy of a document per student
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateBreakoutsMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    /// Student names, each following the slug rules
+    pub students: Vec<String>,
+}
+
+/// Message undoing an edit of a document, or redoing an undo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+}
+
+/// Message choosing whose edits undo reverts in a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetUndoScopeMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    pub scope: UndoScope,
+}
+
+/// Message carrying a document update. Clients submit their pending
+/// operations with the state vector of their replica; the server merges
+/// them and replies with the operations the client lacks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentUpdateMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    #[serde(flatten)]
+    pub update: DocumentUpdate,
+}
+
+/// Message asking for the operations a reconnecting client missed, since
+/// the last version or state vector of the document it saw. A state vector
+/// is exact and preferred; with neither, the client gets the whole history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRequestMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    /// Last version of the document the client saw
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// Operations the client has applied, counted per author
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_vector: Option<StateVector>,
+}
+
+/// Message holding the operations a client missed, in the order they were
+/// applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResponseMessage {
+    pub document_id: String,
+    /// Document version the operations lead to
+    pub version: u64,
+    /// State vector the operations lead to, for the next request
+    pub state_vector: StateVector,
+    pub operations: Vec<Operation>,
+}
+
+/// Message setting the name and color a client is shown with to the other
+/// clients of its documents; omitted fields are kept
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SetPresenceMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Color as `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// Message asking for a full snapshot of who is present in a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPresenceMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+}
+
+/// Message carrying a client's cursor in a document, relayed to the
+/// document's other clients. Positions are those of the characters the
+/// caret and selection ends follow, or `Position::start()` before the first
+/// one, so they stay put when others edit concurrently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CursorUpdateMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    pub caret: Position,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selection: Option<Selection>,
+    /// Milliseconds after which the update is stale and dropped rather
+    /// than relayed; the server's default when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_after_ms: Option<u64>,
+}
+
+/// A selected range of a document, from `start` to `end`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Selection {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Message changing a checklist of a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecklistOperationMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    /// Name of the checklist, as in its block's fence
+    pub checklist: String,
+    pub operation: ChecklistOperation,
+}
+
+/// Message asking for a checklist of a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetChecklistMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    pub checklist: String,
+}
+
+/// Message carrying a checklist of a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecklistStateMessage {
+    pub document_id: String,
+    pub checklist: String,
+    /// Items in order
+    pub items: Vec<ChecklistItem>,
+    /// Operations rebuilding the checklist in an empty replica
+    pub operations: Vec<ChecklistOperation>,
+}
+
+/// Message restricting the characters inserted into a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetCharsetMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    pub charset: CharacterSet,
+}
+
+/// Message asking for the changes of a document since a revision, sent
+/// again after every edit until `diffStop`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffRequestMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    /// Version of the revision to compare with
+    pub version: u64,
+}
+
+/// Message carrying the changes of a document since a revision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffAnnotationsMessage {
+    pub document_id: String,
+    /// Version of the revision compared with
+    pub base_version: u64,
+    /// Document version the ranges correspond to
+    pub version: u64,
+    /// Inserted and removed text, in content order
+    pub ranges: Vec<DiffRange>,
+}
+
+/// Message subscribing to the activity feed of the client's tenant, until
+/// `unsubscribeActivity`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubscribeActivityMessage {
+    /// Activities in the first page; 50 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// Message running a server command on a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+    /// Command line, such as `/insert-template meeting`
+    pub command: String,
+}
+
+/// Message asking for a syntax check of a document's code blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSyntaxMessage {
+    /// Document ID, or one of its slugs
+    pub document_id: String,
+}
+
+/// Message carrying the diagnostics of a document's code blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntaxReportMessage {
+    pub document_id: String,
+    /// Document version the diagnostics correspond to
+    pub version: u64,
+    pub diagnostics: Vec<BlockDiagnostic>,
 }
 
 impl Message {
@@ -73,9 +491,44 @@ impl Message {
             message_type,
             client_id,
             payload,
+            request_id: None,
+            requires: 0,
+            extensions: serde_json::Map::new(),
+            stale_at: None,
         }
     }
 
+    /// Attach a request ID to the message
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Mark the message as needing capabilities, by their feature bits
+    pub fn with_requires(mut self, requires: u64) -> Self {
+        self.requires = requires;
+        self
+    }
+
+    /// Carry fields this server doesn't know, such as those of the message
+    /// being relayed
+    pub fn with_extensions(mut self, extensions: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Let the message be dropped instead of delivered from `stale_at` on,
+    /// or while its recipient's queue is full
+    pub fn with_stale_at(mut self, stale_at: Option<Instant>) -> Self {
+        self.stale_at = stale_at;
+        self
+    }
+
+    /// Get when the message goes stale, if it ever does
+    pub fn stale_at(&self) -> Option<Instant> {
+        self.stale_at
+    }
+
     /// Create an error message
     pub fn error(client_id: String, error: String) -> Self {
         Self::new(
@@ -99,6 +552,73 @@ impl Message {
     pub fn payload(&self) -> &serde_json::Value {
         &self.payload
     }
+
+    /// Get the request ID, if the client supplied one
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Get the feature bits of the capabilities the message needs
+    pub fn requires(&self) -> u64 {
+        self.requires
+    }
+
+    /// Get the fields of the message this server doesn't know
+    pub fn extensions(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.extensions
+    }
+
+    /// Create an error in response to this message, echoing its request ID
+    pub fn error_reply(&self, client_id: String, error: String) -> Self {
+        Self::error(client_id, error).with_request_id(self.request_id.clone())
+    }
+
+    /// Create an acknowledgement of this message, echoing its request ID
+    pub fn ack(&self, client_id: String, payload: serde_json::Value) -> Self {
+        Self::new(MessageType::Ack, client_id, payload).with_request_id(self.request_id.clone())
+    }
+
+    /// Decode a text frame received from a client
+    pub fn decode(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    /// Decode the payload of a client request into its type and check it,
+    /// as the server does before handling the request. Messages only
+    /// servers send have no payload to check.
+    pub fn check_request(&self) -> Result<(), String> {
+        fn parse<T: DeserializeOwned>(payload: &serde_json::Value) -> Result<T, String> {
+            T::deserialize(payload).map_err(|e| e.to_string())
+        }
+        let payload = &self.payload;
+        match self.message_type {
+            MessageType::Operation => parse::<OperationMessage>(payload)?.validate().map_err(str::to_string),
+            MessageType::PlaybackRequest => parse::<PlaybackRequestMessage>(payload)?.validate().map_err(str::to_string),
+            MessageType::RepairRequest => parse::<RepairRequestMessage>(payload)?.validate().map_err(str::to_string),
+            MessageType::CreateDocument => parse::<CreateDocumentMessage>(payload).map(drop),
+            MessageType::GetDocument => parse::<GetDocumentMessage>(payload).map(drop),
+            MessageType::SetSlug => parse::<SetSlugMessage>(payload).map(drop),
+            MessageType::SetFrozen => parse::<SetFrozenMessage>(payload).map(drop),
+            MessageType::SetCharset => parse::<SetCharsetMessage>(payload).map(drop),
+            MessageType::SetPassword => parse::<SetPasswordMessage>(payload).map(drop),
+            MessageType::UnlockDocument => parse::<UnlockDocumentMessage>(payload)?.validate().map_err(str::to_string),
+            MessageType::ShareDocument => parse::<ShareDocumentMessage>(payload).map(drop),
+            MessageType::CheckSyntax => parse::<CheckSyntaxMessage>(payload).map(drop),
+            MessageType::CreateBreakouts => parse::<CreateBreakoutsMessage>(payload).map(drop),
+            MessageType::DocumentUpdate => parse::<DocumentUpdateMessage>(payload).map(drop),
+            MessageType::SyncRequest => parse::<SyncRequestMessage>(payload).map(drop),
+            MessageType::SetPresence => parse::<SetPresenceMessage>(payload)?.validate().map_err(str::to_string),
+            MessageType::GetPresence => parse::<GetPresenceMessage>(payload).map(drop),
+            MessageType::CursorUpdate => parse::<CursorUpdateMessage>(payload)?.validate().map_err(str::to_string),
+            MessageType::ChecklistOperation => parse::<ChecklistOperationMessage>(payload).map(drop),
+            MessageType::GetChecklist => parse::<GetChecklistMessage>(payload).map(drop),
+            MessageType::Undo | MessageType::Redo => parse::<UndoMessage>(payload).map(drop),
+            MessageType::SetUndoScope => parse::<SetUndoScopeMessage>(payload).map(drop),
+            MessageType::Command => parse::<CommandMessage>(payload).map(drop),
+            MessageType::DiffRequest => parse::<DiffRequestMessage>(payload).map(drop),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl OperationMessage {
@@ -107,9 +627,16 @@ impl OperationMessage {
         Self {
             operation,
             document_id,
+            source: OperationSource::User,
         }
     }
 
+    /// Tag the operation with the kind of actor it comes from
+    pub fn with_source(mut self, source: OperationSource) -> Self {
+        self.source = source;
+        self
+    }
+
     /// Validate the operation message
     pub fn validate(&self) -> Result<(), &'static str> {
         if self.document_id.is_empty() {
@@ -119,6 +646,47 @@ impl OperationMessage {
     }
 }
 
+impl SetPresenceMessage {
+    /// Validate the name and color
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let Some(name) = &self.name {
+            if name.trim().is_empty() {
+                return Err("Name cannot be empty");
+            }
+            if name.chars().count() > MAX_NAME_CHARS {
+                return Err("Name is too long");
+            }
+        }
+        if self.color.as_deref().is_some_and(|color| !is_valid_color(color)) {
+            return Err("Color must be written as #rrggbb");
+        }
+        Ok(())
+    }
+}
+
+impl UnlockDocumentMessage {
+    /// Validate that exactly one of the password and share token is given
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.password.is_some() == self.share_token.is_some() {
+            return Err("Unlock with either a password or a share token");
+        }
+        Ok(())
+    }
+}
+
+impl CursorUpdateMessage {
+    /// Validate the cursor update
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.document_id.is_empty() {
+            return Err("Document ID cannot be empty");
+        }
+        if self.selection.as_ref().is_some_and(|selection| selection.start > selection.end) {
+            return Err("Selection cannot end before it starts");
+        }
+        Ok(())
+    }
+}
+
 impl StatusMessage {
     /// Create a new status message
     pub fn new(client_id: String, status: String) -> Self {
@@ -136,7 +704,92 @@ impl DocumentStateMessage {
         Self {
             document_id,
             content: document.content().to_string(),
+            version: document.version(),
+            checksum: document.checksum(),
+            timestamp: Utc::now(),
+            tail: Vec::new(),
+        }
+    }
+
+    /// Create a document state message from a snapshot and the operations
+    /// after it
+    pub fn from_tail(document_id: String, tail: DocumentTail) -> Self {
+        Self {
+            document_id,
+            content: tail.content.to_string(),
+            version: tail.version,
+            checksum: tail.checksum,
             timestamp: Utc::now(),
+            tail: tail.operations,
+        }
+    }
+}
+
+impl PlaybackRequestMessage {
+    /// Create a request streaming the whole history at the given speed
+    pub fn new(document_id: String, ops_per_second: Option<f64>) -> Self {
+        Self {
+            document_id,
+            from: 0,
+            count: None,
+            ops_per_second,
+        }
+    }
+
+    /// Create a request for a single frame, for step-by-step playback
+    pub fn step(document_id: String, index: usize) -> Self {
+        Self {
+            document_id,
+            from: index,
+            count: Some(1),
+            ops_per_second: None,
+        }
+    }
+
+    /// Validate the playback request
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.document_id.is_empty() {
+            return Err("Document ID cannot be empty");
+        }
+        if let Some(speed) = self.ops_per_second {
+            if !speed.is_finite() || speed <= 0.0 || speed > MAX_PLAYBACK_OPS_PER_SECOND {
+                return Err("Playback speed out of range");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RepairRequestMessage {
+    /// Create a repair request from a document's region hashes
+    pub fn new(document_id: String, document: &Document) -> Self {
+        Self {
+            document_id,
+            regions: document.content_hash().regions().to_vec(),
+        }
+    }
+
+    /// Validate the repair request
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.document_id.is_empty() {
+            return Err("Document ID cannot be empty");
+        }
+        if self.regions.len() != CHECKSUM_REGIONS {
+            return Err("Repair request must hash every checksum region");
+        }
+        Ok(())
+    }
+}
+
+impl RepairResponseMessage {
+    /// Create a response with a document's operations for diverged regions
+    pub fn new(document_id: String, document: &Document, regions: Vec<usize>) -> Self {
+        Self {
+            document_id,
+            version: document.version(),
+            checksum: document.checksum(),
+            operations: document.region_operations(&regions),
+            regions,
         }
     }
 }
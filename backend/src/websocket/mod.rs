@@ -5,14 +5,77 @@
  * This module provides WebSocket functionality for real-time collaboration:
  * - message: Message types and serialization
  * - connection: Client connection management
+ * - clock: Time source abstraction for timeouts
  * - server: WebSocket server implementation
+ * - subscriptions: Index of clients per document
+ * - quota: Document size quotas and soft-limit warnings
+ * - webhook: Outbound webhook notifications
+ * - validation: Outbound message schema validation
+ * - assets: Static asset serving for the frontend
+ * - federation: Mirroring documents between servers
+ * - export: HTTP export of documents
+ * - import: HTTP import of plain text and Markdown into documents
+ * - seed: System documents seeded into workspaces on first boot
+ * - events: Document lifecycle events for external indexers
+ * - previews: Previews of recently active documents
+ * - activity: Activity feed of each workspace, over HTTP and subscriptions
+ * - tail: Snapshots and recent operations for late joiners
+ * - skew: Detection of clients sending skewed timestamps
+ * - compat: Forward compatibility rules, feature bits and deprecations
+ * - transfer: Moving documents between tenants
+ * - backlinks: Links and backlinks of documents over HTTP
+ * - admin: Bulk maintenance jobs over HTTP, on the job queue
+ * - replication: Warm standby following a primary's changes
+ * - doctor: Self-check of a server configuration before it starts
+ * - transport: Connections over WebSockets or in memory
  */
 
 pub mod message;
 pub mod connection;
+pub mod clock;
 pub mod server;
+pub mod subscriptions;
+pub mod quota;
+pub mod webhook;
+pub mod validation;
+pub mod assets;
+pub mod federation;
+pub mod export;
+pub mod import;
+pub mod seed;
+pub mod events;
+pub mod previews;
+pub mod activity;
+pub mod tail;
+pub mod skew;
+pub mod compat;
+pub mod transfer;
+pub mod backlinks;
+pub mod admin;
+pub mod replication;
+pub mod doctor;
+pub mod transport;
 
 // Re-export commonly used types
 pub use message::{Message, MessageType};
-pub use connection::{ConnectionManager, ConnectionStatus};
-pub use server::{EditorServer, ServerConfig};
+pub use connection::{ConnectionConfig, ConnectionManager, ConnectionStatus, Session};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use server::{ClientSummary, DocumentPolicy, EditorServer, ServerConfig};
+pub use subscriptions::SubscriptionIndex;
+pub use quota::{QuotaConfig, QuotaTracker, QuotaWarning};
+pub use assets::{AssetSource, StaticConfig};
+pub use federation::{SyncError, SyncHandle, SyncLink};
+pub use export::ExportConfig;
+pub use import::{ImportError, ImportReport, IMPORT_CLIENT_ID};
+pub use seed::{SeedConfig, SeedDocument, SeededDocument};
+pub use events::{DocumentEvent, DocumentEventKind, EventLog, EventPage};
+pub use previews::{DocumentPreview, PreviewCache};
+pub use activity::{Activity, ActivityConfig, ActivityFeed, ActivityKind, ActivityPage};
+pub use tail::{DocumentTail, TailCache};
+pub use skew::{Skew, SkewConfig, SkewDetector, SkewError, SkewReport};
+pub use compat::{check_features, default_deprecations, CompatError, Deprecation, SUPPORTED_FEATURES};
+pub use transfer::{Transfer, TransferError, TransferRequest};
+pub use admin::{AdminAction, AdminConfig};
+pub use replication::{ReplicationError, ReplicationLog, ReplicationRole, ReplicationStatus, StandbyConfig};
+pub use doctor::{diagnose, Check, CheckLevel, DoctorReport};
+pub use transport::{duplex, MemoryTransport, Transport, TransportError};
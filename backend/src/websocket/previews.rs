@@ -0,0 +1,212 @@
+/*
+ * File: src/websocket/previews.rs
+ * Purpose: Previews of recently active documents for listings and rejoins
+ *
+ * Listings and rejoining clients show each document's latest version and
+ * the start of its text without loading whole documents:
+ *
+ *   GET /documents?limit=20
+ *   GET /t/<tenant>/documents?key=<access key>
+ *
+ * The response lists the tenant's recently changed documents, most recent
+ * first. The cache keeps the newest `PREVIEW_CACHE_CAPACITY` documents of
+ * all tenants. Every change records the document's version; the preview
+ * text, its first `PREVIEW_LENGTH` characters, is taken when a listing
+ * first asks for it after a change, so typing costs no more than a map
+ * update. Temporary and password-protected documents are never listed.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    Filter,
+};
+
+use crate::{
+    tenant::DEFAULT_TENANT,
+    websocket::server::{EditorServer, ServerState},
+};
+
+/// Most documents kept, across tenants
+pub const PREVIEW_CACHE_CAPACITY: usize = 1000;
+
+/// Characters of text in a preview
+pub const PREVIEW_LENGTH: usize = 120;
+
+/// Previews returned without `limit`
+const DEFAULT_PREVIEW_LIMIT: usize = 50;
+
+/// The latest state of a recently active document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentPreview {
+    pub document_id: String,
+    /// Version after the latest change
+    pub version: u64,
+    /// Start of the document's text
+    pub preview: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Position in the recency order
+    tick: u64,
+    version: u64,
+    updated_at: DateTime<Utc>,
+    /// Preview text and the version it was taken at
+    preview: Option<(u64, String)>,
+}
+
+#[derive(Debug, Default)]
+struct Previews {
+    entries: HashMap<(String, String), Entry>,
+    /// Keys by tick, oldest first
+    order: BTreeMap<u64, (String, String)>,
+    tick: u64,
+}
+
+/// Cache of recently active documents and their previews
+#[derive(Debug, Default)]
+pub struct PreviewCache {
+    previews: Mutex<Previews>,
+}
+
+impl PreviewCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a tenant's document changed to a version
+    pub fn touch(&self, tenant_id: &str, document_id: &str, version: u64) {
+        let mut previews = self.previews.lock();
+        previews.tick += 1;
+        let tick = previews.tick;
+        let key = (tenant_id.to_string(), document_id.to_string());
+
+        let previous = previews.entries.get(&key).map(|entry| (entry.tick, entry.preview.clone()));
+        let preview = match previous {
+            Some((old_tick, preview)) => {
+                previews.order.remove(&old_tick);
+                preview
+            }
+            None => None,
+        };
+        previews.order.insert(tick, key.clone());
+        previews.entries.insert(key, Entry { tick, version, updated_at: Utc::now(), preview });
+
+        while previews.entries.len() > PREVIEW_CACHE_CAPACITY {
+            let Some((_, oldest)) = previews.order.pop_first() else {
+                break;
+            };
+            previews.entries.remove(&oldest);
+        }
+    }
+
+    /// Drop a document that left the tenant
+    pub fn forget(&self, tenant_id: &str, document_id: &str) {
+        let mut previews = self.previews.lock();
+        if let Some(entry) = previews.entries.remove(&(tenant_id.to_string(), document_id.to_string())) {
+            previews.order.remove(&entry.tick);
+        }
+    }
+
+    /// Get the IDs of a tenant's `limit` most recent visible documents
+    /// whose preview is older than their version
+    pub fn stale(&self, tenant_id: &str, limit: usize, visible: impl Fn(&str) -> bool) -> Vec<String> {
+        let previews = self.previews.lock();
+        previews.order
+            .values()
+            .rev()
+            .filter(|(tenant, document_id)| tenant == tenant_id && visible(document_id))
+            .take(limit)
+            .filter(|key| {
+                let entry = &previews.entries[*key];
+                entry.preview.as_ref().map(|(version, _)| *version) != Some(entry.version)
+            })
+            .map(|(_, document_id)| document_id.clone())
+            .collect()
+    }
+
+    /// Store the preview of a document at a version
+    pub fn fill(&self, tenant_id: &str, document_id: &str, version: u64, preview: String) {
+        let key = (tenant_id.to_string(), document_id.to_string());
+        if let Some(entry) = self.previews.lock().entries.get_mut(&key) {
+            entry.preview = Some((version, preview));
+        }
+    }
+
+    /// Get a tenant's `limit` most recent visible documents, most recent
+    /// first. Documents without a preview yet are listed with an empty one.
+    pub fn recent(&self, tenant_id: &str, limit: usize, visible: impl Fn(&str) -> bool) -> Vec<DocumentPreview> {
+        let previews = self.previews.lock();
+        previews.order
+            .values()
+            .rev()
+            .filter(|(tenant, document_id)| tenant == tenant_id && visible(document_id))
+            .take(limit)
+            .map(|key| {
+                let entry = &previews.entries[key];
+                DocumentPreview {
+                    document_id: key.1.clone(),
+                    version: entry.version,
+                    preview: entry.preview.as_ref().map(|(_, text)| text.clone()).unwrap_or_default(),
+                    updated_at: entry.updated_at,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Build the filter serving document previews
+pub(crate) fn routes(state: ServerState) -> BoxedFilter<(Response<Body>,)> {
+    warp::get()
+        .and(
+            warp::path!("documents")
+                .map(|| DEFAULT_TENANT.to_string())
+                .or(warp::path!("t" / String / "documents"))
+                .unify(),
+        )
+        .and(warp::query::<HashMap<String, String>>())
+        .then(move |tenant_id: String, query: HashMap<String, String>| {
+            let state = state.clone();
+            async move { respond(&state, &tenant_id, &query).await }
+        })
+        .boxed()
+}
+
+/// Answer a listing request
+async fn respond(state: &ServerState, tenant_id: &str, query: &HashMap<String, String>) -> Response<Body> {
+    let tenant = match state.tenant(tenant_id) {
+        Ok(tenant) => tenant,
+        Err(e) => return plain(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    if let Err(e) = tenant.authorize(query.get("key").map(String::as_str)) {
+        return plain(StatusCode::FORBIDDEN, e.to_string());
+    }
+    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => DEFAULT_PREVIEW_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(PREVIEW_CACHE_CAPACITY),
+        Some(_) => return plain(StatusCode::BAD_REQUEST, "limit must be a positive number".to_string()),
+    };
+
+    // Protected documents are left out, as their previews are content
+    let previews = EditorServer::recent_previews(state, &tenant, limit, |document_id| !tenant.passwords().is_protected(document_id)).await;
+    let mut response = Response::new(Body::from(json!({ "documents": previews }).to_string()));
+    if let Ok(value) = "application/json".parse() {
+        response.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    response
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
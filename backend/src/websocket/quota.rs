@@ -0,0 +1,130 @@
+/*
+ * File: src/websocket/quota.rs
+ * Purpose: Document size quotas with soft-limit warnings
+ *
+ * This module enforces a hard limit on document size and warns editors
+ * before it is reached:
+ * - Inserts are rejected once a document is at its hard limit
+ * - Crossing the warning threshold emits a single warning
+ * - The warning re-arms only after the size drops below the threshold
+ *   by the hysteresis margin, so edits around the threshold don't spam
+ */
+
+use std::collections::HashSet;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::websocket::webhook::{WebhookEvent, WebhookNotifier};
+
+/// Quota configuration
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    /// Maximum number of visible characters per document; unlimited when None
+    pub max_document_characters: Option<usize>,
+    /// Fraction of the hard limit at which editors are warned
+    pub warning_ratio: f64,
+    /// Fraction of the hard limit the size must fall below the warning
+    /// threshold before another warning can be sent
+    pub hysteresis_ratio: f64,
+    /// HTTP endpoint notified of quota warnings
+    pub webhook_url: Option<String>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_document_characters: None,
+            warning_ratio: 0.8,
+            hysteresis_ratio: 0.05,
+            webhook_url: None,
+        }
+    }
+}
+
+/// Emitted when a document crosses its warning threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaWarning {
+    pub document_id: String,
+    /// Current document size in characters
+    pub size: usize,
+    /// Size at which warnings are triggered
+    pub warning_threshold: usize,
+    /// Hard limit at which inserts are rejected
+    pub limit: usize,
+}
+
+/// Tracks quota state per document
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    /// Documents that have been warned and not yet re-armed
+    warned: Mutex<HashSet<String>>,
+    webhook: Option<WebhookNotifier>,
+}
+
+impl QuotaTracker {
+    /// Create a quota tracker from configuration
+    pub fn new(config: QuotaConfig) -> Self {
+        let webhook = config.webhook_url.as_deref().and_then(|url| {
+            WebhookNotifier::new(url)
+                .map_err(|e| log::error!("Quota webhook disabled: {}", e))
+                .ok()
+        });
+
+        Self {
+            config,
+            warned: Mutex::new(HashSet::new()),
+            webhook,
+        }
+    }
+
+    /// Check whether a document of the given size may grow
+    pub fn allows_insert(&self, size: usize) -> bool {
+        match self.config.max_document_characters {
+            Some(limit) => size < limit,
+            None => true,
+        }
+    }
+
+    /// Get the hard limit, if any
+    pub fn limit(&self) -> Option<usize> {
+        self.config.max_document_characters
+    }
+
+    /// Record a document's new size, returning a warning if it just
+    /// crossed the warning threshold
+    pub fn observe(&self, document_id: &str, size: usize) -> Option<QuotaWarning> {
+        let limit = self.config.max_document_characters?;
+        let threshold = (limit as f64 * self.config.warning_ratio).ceil() as usize;
+        let rearm_below = threshold.saturating_sub((limit as f64 * self.config.hysteresis_ratio) as usize);
+
+        let mut warned = self.warned.lock();
+        if size >= threshold {
+            if warned.insert(document_id.to_string()) {
+                return Some(QuotaWarning {
+                    document_id: document_id.to_string(),
+                    size,
+                    warning_threshold: threshold,
+                    limit,
+                });
+            }
+        } else if size < rearm_below {
+            warned.remove(document_id);
+        }
+        None
+    }
+
+    /// Send a warning to the configured webhook, if any
+    pub fn notify(&self, warning: &QuotaWarning) {
+        if let Some(webhook) = &self.webhook {
+            match serde_json::to_value(warning) {
+                Ok(data) => webhook.notify(WebhookEvent::new("quota.warning", warning.document_id.clone(), data)),
+                Err(e) => log::error!("Failed to serialize quota warning: {}", e),
+            }
+        }
+    }
+
+    /// Drop quota state for a removed document
+    pub fn forget(&self, document_id: &str) {
+        self.warned.lock().remove(document_id);
+    }
+}
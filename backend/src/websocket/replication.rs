@@ -0,0 +1,610 @@
+/*
+ * File: src/websocket/replication.rs
+ * Purpose: Warm standby replication to a follower server
+ *
+ * A primary keeps an in-memory log of the changes applied to its
+ * documents, and a standby configured with `StandbyConfig` follows it over
+ * HTTP, asynchronously:
+ *
+ *   GET /replication/snapshot
+ *   GET /replication/log?since=<position>&limit=1000&timeout=30
+ *
+ * Both carry the primary's admin token. The standby starts from a snapshot
+ * of every document, taken at a position of the log, then long polls for
+ * the changes after it and applies them as sync links do. Changes are
+ * operations, and whole documents for breakouts, which are created as
+ * copies. A position older than the log, or from before the primary
+ * restarted, gets `410 Gone`, and the standby takes a new snapshot; so
+ * does a change the standby fails to apply.
+ *
+ * A standby refuses WebSocket connections until it is promoted, which is
+ * done by hand when the primary fails:
+ *
+ *   GET  /admin/replication            role and catch-up progress
+ *   POST /admin/replication/promote    stop following and accept clients
+ *
+ * Only documents are replicated: slugs, settings and other tenant state
+ * start over on a promoted standby, and compacting the primary doesn't
+ * compact the standby. Temporary documents are never replicated.
+ */
+
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+use chrono::{DateTime, Utc};
+use hyper::{client::HttpConnector, Body as ClientBody, Client, Method, Request, Uri};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::{sync::watch, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use warp::{
+    filters::BoxedFilter,
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    Filter,
+};
+
+use crate::{
+    crdt::{Document, Operation, OperationSource},
+    websocket::{
+        admin::{authorize, AdminConfig},
+        server::{EditorServer, ServerState},
+    },
+};
+
+/// Most changes kept in memory, across tenants
+pub const REPLICATION_LOG_CAPACITY: usize = 100_000;
+
+/// Changes returned per response unless `limit` asks for fewer
+pub const MAX_CHANGES_PER_RESPONSE: usize = 1000;
+
+/// How long a poll waits for changes without `timeout`
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait a poll may ask for
+pub const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Extra time a standby gives the primary to answer a poll
+const RESPONSE_GRACE: Duration = Duration::from_secs(10);
+
+/// First and longest delay between attempts to reach the primary
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Origin recorded on operations a standby applies
+const REPLICATION_ORIGIN: &str = "replication";
+
+/// Replication errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ReplicationError {
+    #[error("Position {0} is no longer available; take a snapshot and follow from its position")]
+    Expired(u64),
+    #[error("Request to the primary failed: {0}")]
+    Request(String),
+    #[error("Unexpected response from the primary: {0}")]
+    Protocol(String),
+    #[error("Failed to apply a change of document {0}; taking a new snapshot")]
+    Diverged(String),
+    #[error("The server is not a standby")]
+    NotStandby,
+}
+
+/// The primary a standby follows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandbyConfig {
+    /// HTTP URL of the primary, e.g. `http://primary.internal:8080`
+    pub primary: String,
+    /// Admin token of the primary
+    pub token: String,
+    /// How long each poll waits for changes
+    pub poll_timeout: Duration,
+}
+
+impl StandbyConfig {
+    /// Follow a primary with its admin token
+    pub fn new(primary: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            primary: primary.into(),
+            token: token.into(),
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+        }
+    }
+}
+
+/// A change applied to a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ReplicatedChange {
+    /// An operation, with the kind of actor it came from
+    Operation { operation: Operation, source: OperationSource },
+    /// A document created as a copy of another
+    Document { document: Box<Document> },
+}
+
+/// A change in the replication log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationEntry {
+    /// Position of the change in the log; increases with every change
+    pub position: u64,
+    pub tenant_id: String,
+    pub document_id: String,
+    #[serde(flatten)]
+    pub change: ReplicatedChange,
+}
+
+/// Changes after a position, and the position to continue from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationPage {
+    pub entries: Vec<ReplicationEntry>,
+    pub next: u64,
+    /// Position of the newest change in the log
+    pub head: u64,
+}
+
+/// A document of a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotDocument {
+    pub tenant_id: String,
+    pub document_id: String,
+    pub document: Document,
+}
+
+/// Every document of a primary, holding at least the changes up to
+/// `position`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationSnapshot {
+    pub position: u64,
+    pub documents: Vec<SnapshotDocument>,
+}
+
+#[derive(Debug, Default)]
+struct LogBuffer {
+    /// Changes, oldest first
+    entries: VecDeque<ReplicationEntry>,
+    /// Position of the newest change; 0 before the first
+    head: u64,
+    /// Newest position dropped from the log
+    evicted: u64,
+}
+
+/// In-memory log of the changes applied to documents
+#[derive(Debug)]
+pub struct ReplicationLog {
+    buffer: Mutex<LogBuffer>,
+    /// Head position, for waking up polls
+    head: watch::Sender<u64>,
+}
+
+impl Default for ReplicationLog {
+    fn default() -> Self {
+        Self {
+            buffer: Mutex::new(LogBuffer::default()),
+            head: watch::channel(0).0,
+        }
+    }
+}
+
+impl ReplicationLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the position of the newest change
+    pub fn head(&self) -> u64 {
+        self.buffer.lock().head
+    }
+
+    /// Record a change of a tenant's document, returning its position
+    pub fn record(&self, tenant_id: &str, document_id: &str, change: ReplicatedChange) -> u64 {
+        let mut buffer = self.buffer.lock();
+        buffer.head += 1;
+        let position = buffer.head;
+        if buffer.entries.len() >= REPLICATION_LOG_CAPACITY {
+            if let Some(evicted) = buffer.entries.pop_front() {
+                buffer.evicted = evicted.position;
+            }
+        }
+        buffer.entries.push_back(ReplicationEntry {
+            position,
+            tenant_id: tenant_id.to_string(),
+            document_id: document_id.to_string(),
+            change,
+        });
+        drop(buffer);
+        self.head.send_replace(position);
+        position
+    }
+
+    /// Get up to `limit` changes after a position
+    pub fn since(&self, since: u64, limit: usize) -> Result<ReplicationPage, ReplicationError> {
+        let buffer = self.buffer.lock();
+        if since < buffer.evicted || since > buffer.head {
+            return Err(ReplicationError::Expired(since));
+        }
+        // Positions are consecutive, so the first change after `since` is
+        // found by its offset
+        let skip = (since - buffer.evicted) as usize;
+        let entries: Vec<ReplicationEntry> = buffer.entries.iter().skip(skip).take(limit).cloned().collect();
+        let next = entries.last().map_or(since, |last| last.position);
+        Ok(ReplicationPage { entries, next, head: buffer.head })
+    }
+
+    /// Like `since`, but wait up to `timeout` for changes if there are none
+    pub async fn wait_since(&self, since: u64, limit: usize, timeout: Duration) -> Result<ReplicationPage, ReplicationError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut head = self.head.subscribe();
+        loop {
+            let page = self.since(since, limit)?;
+            if !page.entries.is_empty() {
+                return Ok(page);
+            }
+            match tokio::time::timeout_at(deadline, head.changed()).await {
+                Ok(Ok(())) => continue,
+                _ => return Ok(page),
+            }
+        }
+    }
+}
+
+/// Whether a server accepts clients or follows a primary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationRole {
+    Primary,
+    Standby,
+}
+
+/// Role of a server and, for a standby, how far it has caught up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationStatus {
+    pub role: ReplicationRole,
+    /// Position of the newest change in this server's own log
+    pub head: u64,
+    /// URL of the primary followed, or last followed before promotion
+    pub primary: Option<String>,
+    /// Position in the primary's log applied so far
+    pub applied: u64,
+    /// Newest position the primary reported
+    pub primary_head: u64,
+    /// Changes still to apply
+    pub lag: u64,
+    /// Whether the last request to the primary succeeded
+    pub connected: bool,
+    pub last_contact: Option<DateTime<Utc>>,
+    /// Snapshots taken, the first one included
+    pub snapshots: u64,
+    pub promoted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+struct Progress {
+    role: ReplicationRole,
+    primary: Option<String>,
+    applied: u64,
+    primary_head: u64,
+    /// Whether the next request must be for a snapshot
+    needs_snapshot: bool,
+    connected: bool,
+    last_contact: Option<DateTime<Utc>>,
+    snapshots: u64,
+    promoted_at: Option<DateTime<Utc>>,
+}
+
+/// Replication state of a server: its own log, and its progress while a
+/// standby
+#[derive(Debug)]
+pub(crate) struct Replication {
+    log: ReplicationLog,
+    progress: Mutex<Progress>,
+    /// Stops following the primary on promotion
+    following: CancellationToken,
+}
+
+impl Replication {
+    pub(crate) fn new(standby: Option<&StandbyConfig>) -> Self {
+        let role = if standby.is_some() { ReplicationRole::Standby } else { ReplicationRole::Primary };
+        Self {
+            log: ReplicationLog::new(),
+            progress: Mutex::new(Progress {
+                role,
+                primary: standby.map(|standby| standby.primary.clone()),
+                applied: 0,
+                primary_head: 0,
+                needs_snapshot: true,
+                connected: false,
+                last_contact: None,
+                snapshots: 0,
+                promoted_at: None,
+            }),
+            following: CancellationToken::new(),
+        }
+    }
+
+    /// Get the log of this server's changes
+    pub(crate) fn log(&self) -> &ReplicationLog {
+        &self.log
+    }
+
+    /// Check whether the server follows a primary
+    pub(crate) fn is_standby(&self) -> bool {
+        self.progress.lock().role == ReplicationRole::Standby
+    }
+
+    pub(crate) fn status(&self) -> ReplicationStatus {
+        let progress = self.progress.lock();
+        ReplicationStatus {
+            role: progress.role,
+            head: self.log.head(),
+            primary: progress.primary.clone(),
+            applied: progress.applied,
+            primary_head: progress.primary_head,
+            lag: progress.primary_head.saturating_sub(progress.applied),
+            connected: progress.connected,
+            last_contact: progress.last_contact,
+            snapshots: progress.snapshots,
+            promoted_at: progress.promoted_at,
+        }
+    }
+
+    /// Stop following the primary and accept clients
+    pub(crate) fn promote(&self) -> Result<ReplicationStatus, ReplicationError> {
+        {
+            let mut progress = self.progress.lock();
+            if progress.role != ReplicationRole::Standby {
+                return Err(ReplicationError::NotStandby);
+            }
+            progress.role = ReplicationRole::Primary;
+            progress.connected = false;
+            progress.promoted_at = Some(Utc::now());
+        }
+        self.following.cancel();
+        Ok(self.status())
+    }
+}
+
+/// Start following the primary in the background, until promoted
+pub(crate) fn spawn_follower(state: ServerState, config: StandbyConfig) -> JoinHandle<()> {
+    let following = state.replication().following.clone();
+    tokio::spawn(async move {
+        let follower = Follower { state: &state, config: &config, client: Client::new() };
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let result = tokio::select! {
+                _ = following.cancelled() => return,
+                result = follower.follow() => result,
+            };
+            let Err(e) = result;
+            if let ReplicationError::Expired(_) | ReplicationError::Diverged(_) = e {
+                // The primary is reachable, so snapshot right away
+                log::warn!("Standby lost track of {}: {}", config.primary, e);
+                state.replication().progress.lock().needs_snapshot = true;
+                backoff = MIN_BACKOFF;
+                continue;
+            }
+            log::warn!("Standby failed to reach {}: {}", config.primary, e);
+            state.replication().progress.lock().connected = false;
+
+            tokio::select! {
+                _ = following.cancelled() => return,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    })
+}
+
+/// A standby's requests to its primary
+struct Follower<'a> {
+    state: &'a ServerState,
+    config: &'a StandbyConfig,
+    client: Client<HttpConnector>,
+}
+
+impl Follower<'_> {
+    /// Apply the primary's changes until a request fails
+    async fn follow(&self) -> Result<std::convert::Infallible, ReplicationError> {
+        loop {
+            let (needs_snapshot, applied) = {
+                let progress = self.state.replication().progress.lock();
+                (progress.needs_snapshot, progress.applied)
+            };
+            if needs_snapshot {
+                let snapshot: ReplicationSnapshot = self.get("replication/snapshot", RESPONSE_GRACE).await?;
+                let position = snapshot.position;
+                let documents = snapshot.documents.len();
+                for SnapshotDocument { tenant_id, document_id, document } in snapshot.documents {
+                    match self.state.tenant(&tenant_id) {
+                        Ok(tenant) => EditorServer::restore_document(self.state, &tenant, &document_id, document, true).await,
+                        Err(e) => log::warn!("Skipping snapshot document {} of {}: {}", document_id, tenant_id, e),
+                    }
+                }
+                log::info!("Standby restored {} documents at position {} of {}", documents, position, self.config.primary);
+                self.advance(position, position, true);
+                continue;
+            }
+
+            let timeout = self.config.poll_timeout.min(MAX_POLL_TIMEOUT);
+            let path = format!(
+                "replication/log?since={}&limit={}&timeout={}",
+                applied,
+                MAX_CHANGES_PER_RESPONSE,
+                timeout.as_secs()
+            );
+            let page: ReplicationPage = self.get(&path, timeout + RESPONSE_GRACE).await?;
+            for entry in page.entries {
+                self.apply(entry).await?;
+            }
+            self.advance(page.next, page.head, false);
+        }
+    }
+
+    /// Apply a change of the primary
+    async fn apply(&self, entry: ReplicationEntry) -> Result<(), ReplicationError> {
+        let tenant = match self.state.tenant(&entry.tenant_id) {
+            Ok(tenant) => tenant,
+            Err(e) => {
+                log::warn!("Skipping change of document {} of {}: {}", entry.document_id, entry.tenant_id, e);
+                return Ok(());
+            }
+        };
+        match entry.change {
+            ReplicatedChange::Operation { operation, source } => {
+                EditorServer::merge_remote(self.state, &tenant, &entry.document_id, operation, source, REPLICATION_ORIGIN)
+                    .await
+                    .map_err(|_| ReplicationError::Diverged(entry.document_id.clone()))?;
+            }
+            ReplicatedChange::Document { document } => {
+                EditorServer::restore_document(self.state, &tenant, &entry.document_id, *document, false).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the position applied and the primary's newest one
+    fn advance(&self, applied: u64, primary_head: u64, snapshot: bool) {
+        let mut progress = self.state.replication().progress.lock();
+        progress.applied = applied;
+        progress.primary_head = primary_head;
+        progress.connected = true;
+        progress.last_contact = Some(Utc::now());
+        if snapshot {
+            progress.needs_snapshot = false;
+            progress.snapshots += 1;
+        }
+    }
+
+    /// Request a path of the primary and decode its JSON response
+    async fn get<T: DeserializeOwned>(&self, path: &str, timeout: Duration) -> Result<T, ReplicationError> {
+        let uri: Uri = format!("{}/{}", self.config.primary.trim_end_matches('/'), path)
+            .parse()
+            .map_err(|e: hyper::http::uri::InvalidUri| ReplicationError::Request(e.to_string()))?;
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.config.token))
+            .body(ClientBody::empty())
+            .map_err(|e| ReplicationError::Request(e.to_string()))?;
+
+        let response = tokio::time::timeout(timeout, self.client.request(request))
+            .await
+            .map_err(|_| ReplicationError::Request("the primary did not answer in time".to_string()))?
+            .map_err(|e| ReplicationError::Request(e.to_string()))?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| ReplicationError::Request(e.to_string()))?;
+        match status {
+            StatusCode::OK => serde_json::from_slice(&body).map_err(|e| ReplicationError::Protocol(e.to_string())),
+            StatusCode::GONE => Err(ReplicationError::Expired(self.state.replication().status().applied)),
+            status => Err(ReplicationError::Request(format!("{}: {}", status, String::from_utf8_lossy(&body)))),
+        }
+    }
+}
+
+/// Build the filter serving the replication log and the replication admin
+/// routes
+pub(crate) fn routes(state: ServerState, config: &AdminConfig) -> BoxedFilter<(Response<Body>,)> {
+    let token = config.token.clone();
+    let authorized = warp::header::optional::<String>(header::AUTHORIZATION.as_str())
+        .map(move |authorization: Option<String>| authorize(token.as_deref(), authorization.as_deref()));
+
+    let log = warp::get()
+        .and(warp::path!("replication" / "log"))
+        .and(authorized.clone())
+        .and(warp::query::<HashMap<String, String>>())
+        .then({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>, query: HashMap<String, String>| {
+                let state = state.clone();
+                async move {
+                    match authorized {
+                        Ok(()) => respond_log(&state, &query).await,
+                        Err((status, message)) => plain(status, message),
+                    }
+                }
+            }
+        });
+
+    let snapshot = warp::get()
+        .and(warp::path!("replication" / "snapshot"))
+        .and(authorized.clone())
+        .then({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| {
+                let state = state.clone();
+                async move {
+                    match authorized {
+                        Ok(()) => json_response(StatusCode::OK, &json!(EditorServer::replication_snapshot(&state).await)),
+                        Err((status, message)) => plain(status, message),
+                    }
+                }
+            }
+        });
+
+    let status = warp::get()
+        .and(warp::path!("admin" / "replication"))
+        .and(authorized.clone())
+        .map({
+            let state = state.clone();
+            move |authorized: Result<(), (StatusCode, String)>| match authorized {
+                Ok(()) => json_response(StatusCode::OK, &json!(state.replication().status())),
+                Err((status, message)) => plain(status, message),
+            }
+        });
+
+    let promote = warp::post()
+        .and(warp::path!("admin" / "replication" / "promote"))
+        .and(authorized)
+        .map(move |authorized: Result<(), (StatusCode, String)>| match authorized {
+            Ok(()) => match state.replication().promote() {
+                Ok(status) => {
+                    log::warn!("Promoted to primary after applying position {}", status.applied);
+                    json_response(StatusCode::OK, &json!(status))
+                }
+                Err(e) => plain(StatusCode::CONFLICT, e.to_string()),
+            },
+            Err((status, message)) => plain(status, message),
+        });
+
+    log.or(snapshot).unify().or(status).unify().or(promote).unify().boxed()
+}
+
+/// Answer a poll for changes
+async fn respond_log(state: &ServerState, query: &HashMap<String, String>) -> Response<Body> {
+    let since = match query.get("since").map(|since| since.parse::<u64>()) {
+        None => 0,
+        Some(Ok(since)) => since,
+        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "since must be a position".to_string()),
+    };
+    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => MAX_CHANGES_PER_RESPONSE,
+        Some(Ok(limit)) if limit > 0 => limit.min(MAX_CHANGES_PER_RESPONSE),
+        Some(_) => return plain(StatusCode::BAD_REQUEST, "limit must be a positive number".to_string()),
+    };
+    let timeout = match query.get("timeout").map(|timeout| timeout.parse::<u64>()) {
+        None => DEFAULT_POLL_TIMEOUT,
+        Some(Ok(seconds)) => Duration::from_secs(seconds).min(MAX_POLL_TIMEOUT),
+        Some(Err(_)) => return plain(StatusCode::BAD_REQUEST, "timeout must be a number of seconds".to_string()),
+    };
+
+    match state.replication().log().wait_since(since, limit, timeout).await {
+        Ok(page) => json_response(StatusCode::OK, &json!(page)),
+        Err(e) => plain(StatusCode::GONE, e.to_string()),
+    }
+}
+
+fn json_response(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    if let Ok(value) = "application/json".parse() {
+        response.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    response
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,75 @@
+/*
+ * File: src/websocket/seed.rs
+ * Purpose: System documents seeded into workspaces on first boot
+ *
+ * A deployment can start every workspace with documents such as a welcome
+ * tour, listed in `ServerConfig::seed`. `SeedConfig::system()` lists the
+ * embedded templates:
+ * - welcome: what the editor does and how to find your way around
+ * - keyboard-shortcuts: the editor's key bindings
+ *
+ * `EditorServer::seed_documents` imports each document into every tenant
+ * through the import pipeline, so seeded documents are ordinary documents:
+ * they can be edited, renamed and exported, and show up in events and the
+ * activity feed. Documents that already exist are left alone, and so are
+ * tenants whose content is end-to-end encrypted. A standby never seeds;
+ * it receives the primary's documents. Seeding runs once per server.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::blocks::ImportFormat;
+
+/// The welcome tour of `SeedConfig::system()`
+pub const WELCOME_TEMPLATE: &str = include_str!("../../templates/welcome.md");
+
+/// The shortcut reference of `SeedConfig::system()`
+pub const SHORTCUTS_TEMPLATE: &str = include_str!("../../templates/keyboard-shortcuts.md");
+
+/// A document to create in every workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedDocument {
+    pub document_id: String,
+    /// Text to import into the document
+    pub source: String,
+    pub format: ImportFormat,
+}
+
+impl SeedDocument {
+    /// Seed a document from Markdown, turning its task lists into
+    /// checklists
+    pub fn markdown(document_id: &str, source: &str) -> Self {
+        Self {
+            document_id: document_id.to_string(),
+            source: source.to_string(),
+            format: ImportFormat::Markdown,
+        }
+    }
+}
+
+/// Documents seeded on first boot; none by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeedConfig {
+    pub documents: Vec<SeedDocument>,
+}
+
+impl SeedConfig {
+    /// Seed the embedded welcome tour and keyboard shortcuts
+    pub fn system() -> Self {
+        Self {
+            documents: vec![
+                SeedDocument::markdown("welcome", WELCOME_TEMPLATE),
+                SeedDocument::markdown("keyboard-shortcuts", SHORTCUTS_TEMPLATE),
+            ],
+        }
+    }
+}
+
+/// A document created by seeding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeededDocument {
+    pub tenant_id: String,
+    pub document_id: String,
+    /// Version of the document after seeding
+    pub version: u64,
+}
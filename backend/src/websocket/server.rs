@@ -3,102 +3,692 @@
  * Purpose: WebSocket server implementation for real-time collaboration
  * 
  * This module implements the WebSocket server that handles:
- * - Client connections and disconnections
+ * - Client connections and disconnections, optionally joining a document
+ *   named in the URL
  * - Message routing between clients
  * - Document state management
  * - Heartbeat mechanism for connection health
+ * - Static frontend assets, when configured
+ * - Mirroring documents of other servers (see `federation`)
+ * - Opt-in anonymous usage reports
+ * - Content moderation of inserted words, when a filter is configured
+ * - Creating documents, seeded with initial content, and session-scoped
+ *   temporary documents destroyed on disconnect
+ * - Classroom controls: frozen documents and breakout copies
+ * - Syntax checks of code blocks, and HTTP export and import of documents
+ * - System documents seeded into every workspace on first boot (see `seed`)
+ * - Previews of recently active documents (see `previews`)
+ * - An activity feed of each workspace, over HTTP and subscriptions (see
+ *   `activity`)
+ * - Snapshots and recent operations for late joiners (see `tail`)
+ * - Detection of clients sending skewed timestamps (see `skew`)
+ * - Bandwidth accounting per client and document, with caps that throttle
+ * - Alerts on clients editing abusively fast, optionally restricting them
+ * - Bulk maintenance jobs through the admin API, when a token is configured
+ * - A self-check of the configuration before binding (see `doctor`)
+ * - Persisting documents through a `DocumentStore`: operations as they are
+ *   applied, and periodic snapshots (see `storage`)
+ * - Presence of clients in documents, announced on joins, departures and
+ *   idle timeouts, and their cursors, relayed throttled (see `presence`)
  */
 
 use std::{
     collections::HashMap,
     sync::{
         Arc,
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
     },
     time::Duration,
 };
 
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use futures_util::{SinkExt, StreamExt};
 use tokio::sync::RwLock;
+use serde::Serialize;
 use serde_json::json;
-use tokio::sync::mpsc;
+use tokio::{sync::{broadcast, mpsc}, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 use warp::{
-    ws::{Message as WsMessage, WebSocket},
+    http::StatusCode,
     Filter,
 };
-use uuid::Uuid;
 
-/// Represents a connected client
+/// Consecutive failed sends after which a client is evicted
+const MAX_SEND_FAILURES: u32 = 3;
 
+/// Maximum number of sends a single broadcast runs at once
+const BROADCAST_CONCURRENCY: usize = 32;
+
+/// How long a send may wait on a full client channel before it fails
+const SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Client ID of operations the server makes to moderate content
+const MODERATION_CLIENT_ID: &str = "moderation";
+
+/// Client ID of presence updates
+const PRESENCE_CLIENT_ID: &str = "presence";
+
+/// Most words waiting for the content filter; more are dropped unchecked
+/// Shortest time between two diff annotations of one view, so a burst of
+/// edits is diffed once
+const DIFF_VIEW_INTERVAL: Duration = Duration::from_millis(200);
+const MODERATION_QUEUE_CAPACITY: usize = 1024;
+
+/// An encoded outbound message. A broadcast encodes once and every
+/// recipient's channel holds a handle to the same buffer.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    text: Arc<str>,
+    /// When the message goes stale, if it is ephemeral
+    stale_at: Option<std::time::Instant>,
+}
+
+impl Frame {
+    fn new(text: Arc<str>) -> Self {
+        Self { text, stale_at: None }
+    }
+
+    /// The encoded message
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the frame is stale by `now`, to drop rather than send
+    fn is_stale(&self, now: std::time::Instant) -> bool {
+        self.stale_at.is_some_and(|stale_at| stale_at <= now)
+    }
+}
+
+/// A connected client's outbound channel and namespace
+struct ClientEntry {
+    sender: mpsc::Sender<Frame>,
+    tenant_id: String,
+    /// Shutdown signal of the client's connection tasks
+    shutdown: CancellationToken,
+    /// Consecutive failed sends
+    failures: Arc<AtomicU32>,
+}
+
+/// Snapshot of a connected client, for rosters and admin listings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClientSummary {
+    pub client_id: String,
+    pub tenant_id: String,
+    /// Documents the client has edited, sorted by ID
+    pub documents: Vec<String>,
+}
 
 /// Tracks all connected clients
 struct ClientManager {
-    clients: RwLock<HashMap<String, mpsc::Sender<WsMessage>>>,
+    clients: RwLock<HashMap<String, ClientEntry>>,
     client_count: AtomicUsize,
+    /// Most clients connected at once since the last usage report
+    peak_clients: AtomicUsize,
+    /// Documents each client has edited, indexed both ways
+    subscriptions: parking_lot::RwLock<SubscriptionIndex>,
+    /// Running playback stream per client
+    playbacks: RwLock<HashMap<String, JoinHandle<()>>>,
+    /// Running diff view per client
+    diff_views: RwLock<HashMap<String, JoinHandle<()>>>,
+    /// Running activity subscription per client
+    activity_feeds: RwLock<HashMap<String, JoinHandle<()>>>,
+    /// Owning client of each temporary document, by scoped document key
+    temporary: parking_lot::RwLock<HashMap<String, String>>,
+    /// Clients active in each document, with their names and colors
+    presence: parking_lot::Mutex<PresenceTracker>,
+    /// Whether outbound messages are checked against the protocol schema
+    validate_outbound: bool,
+    /// How long presence updates stay worth delivering; zero never drops
+    /// them
+    update_staleness: Duration,
+    metrics: Arc<ServerMetrics>,
 }
 
 impl ClientManager {
     /// Create a new client manager
-    fn new() -> Self {
+    fn new(validate_outbound: bool, metrics: Arc<ServerMetrics>) -> Self {
         Self {
             clients: RwLock::new(HashMap::new()),
             client_count: AtomicUsize::new(0),
+            peak_clients: AtomicUsize::new(0),
+            subscriptions: parking_lot::RwLock::new(SubscriptionIndex::new()),
+            playbacks: RwLock::new(HashMap::new()),
+            diff_views: RwLock::new(HashMap::new()),
+            activity_feeds: RwLock::new(HashMap::new()),
+            temporary: parking_lot::RwLock::new(HashMap::new()),
+            presence: parking_lot::Mutex::new(PresenceTracker::new()),
+            validate_outbound,
+            update_staleness: Duration::ZERO,
+            metrics,
+        }
+    }
+
+    /// Drop presence updates still undelivered after `staleness`
+    fn with_update_staleness(mut self, staleness: Duration) -> Self {
+        self.update_staleness = staleness;
+        self
+    }
+
+    /// Serialize an outbound message, validating it if enabled
+    fn encode(&self, message: &Message) -> Option<Frame> {
+        let encoded = match serde_json::to_string(message) {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::error!("Failed to serialize message: {}", e);
+                return None;
+            }
+        };
+
+        if self.validate_outbound {
+            if let Err(violation) = validate_outbound(message, &encoded) {
+                report_violation(&violation);
+            }
         }
+        Some(Frame { text: encoded.into(), stale_at: message.stale_at() })
     }
 
     /// Add a new client
-    async fn add_client(&self, id: String, sender: mpsc::Sender<WsMessage>) {
-        self.clients.write().await.insert(id, sender);
-        self.client_count.fetch_add(1, Ordering::SeqCst);
+    async fn add_client(
+        &self,
+        id: String,
+        tenant_id: String,
+        sender: mpsc::Sender<Frame>,
+        shutdown: CancellationToken,
+    ) {
+        let entry = ClientEntry {
+            sender,
+            tenant_id,
+            shutdown,
+            failures: Arc::new(AtomicU32::new(0)),
+        };
+        self.clients.write().await.insert(id, entry);
+        let count = self.client_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak_clients.fetch_max(count, Ordering::SeqCst);
     }
 
     /// Remove a client
-    async fn remove_client(&self, id: &str) -> Option<mpsc::Sender<WsMessage>> {
+    async fn remove_client(&self, id: &str) -> Option<mpsc::Sender<Frame>> {
         let mut clients = self.clients.write().await;
-        let sender = clients.remove(id);
+        let sender = clients.remove(id).map(|entry| entry.sender);
         if sender.is_some() {
             self.client_count.fetch_sub(1, Ordering::SeqCst);
         }
+        self.subscriptions.write().remove_client(id);
+        drop(clients);
+
+        self.stop_playback(id).await;
+        self.stop_diff_view(id).await;
+        self.stop_activity_feed(id).await;
         sender
     }
 
     /// Get the number of connected clients
+    fn client_count(&self) -> usize {
+        self.client_count.load(Ordering::SeqCst)
+    }
 
+    /// Get the most clients connected at once since the last reset
+    fn peak_clients(&self) -> usize {
+        self.peak_clients.load(Ordering::SeqCst)
+    }
 
-    /// Broadcast a message to all clients except the specified one
-    async fn broadcast(&self, message: &Message, exclude_id: Option<&str>) {
-        let message = match serde_json::to_string(message) {
-            Ok(msg) => msg,
-            Err(e) => {
-                log::error!("Failed to serialize message: {}", e);
-                return;
-            }
+    /// Start a new peak period from the current number of clients
+    fn reset_peak_clients(&self) {
+        self.peak_clients.store(self.client_count(), Ordering::SeqCst);
+    }
+
+    /// Subscribe a client to a document's operations without announcing
+    /// it, for joining under the document lock; `join_document` follows
+    fn subscribe(&self, tenant_id: &str, document_id: &str, client_id: &str) {
+        self.subscriptions.write().join(tenant_id, document_id, client_id);
+    }
+
+    /// Record that a client is working on a document. A client not
+    /// present in it yet is announced to the document's other clients and
+    /// sent a snapshot of who is there.
+    async fn join_document(&self, client_id: &str, document_id: &str) {
+        // Holding the client map keeps a concurrent disconnect from leaving
+        // a stale subscription behind
+        let clients = self.clients.read().await;
+        let Some(entry) = clients.get(client_id) else {
+            // Including one `subscribe` left before the client went
+            self.subscriptions.write().remove_client(client_id);
+            return;
         };
+        let tenant_id = entry.tenant_id.clone();
+        self.subscriptions.write().join(&tenant_id, document_id, client_id);
+        let joined = self.presence.lock().join(&tenant_id, document_id, client_id, Utc::now());
+        drop(clients);
+
+        if let Some(update) = joined {
+            self.send_presence(&tenant_id, &update, Some(client_id)).await;
+            let snapshot = self.presence.lock().snapshot(&tenant_id, document_id);
+            self.send_presence_to(client_id, &snapshot).await;
+        }
+    }
+
+    /// Mark a client active in the documents it is present in
+    fn touch_presence(&self, client_id: &str) {
+        self.presence.lock().touch(client_id, Utc::now());
+    }
+
+    /// Set a client's name and color and announce them in its documents
+    async fn set_profile(&self, tenant_id: &str, client_id: &str, name: Option<String>, color: Option<String>) {
+        let updates = self.presence.lock().set_profile(client_id, name, color);
+        for (_, update) in updates {
+            self.send_presence(tenant_id, &update, None).await;
+        }
+    }
+
+    /// Announce that a disconnected client left its documents
+    async fn leave_presence(&self, client_id: &str) {
+        let updates = self.presence.lock().remove_client(client_id);
+        for (tenant_id, update) in updates {
+            self.send_presence(&tenant_id, &update, None).await;
+        }
+    }
+
+    /// Remove clients idle for longer than the timeout and announce it,
+    /// returning how many documents changed
+    async fn expire_presence(&self, idle_timeout: Duration) -> usize {
+        let updates = self.presence.lock().expire(idle_timeout, Utc::now());
+        for (tenant_id, update) in &updates {
+            self.send_presence(tenant_id, update, None).await;
+        }
+        updates.len()
+    }
+
+    /// Get the clients present in a document
+    fn presence(&self, tenant_id: &str, document_id: &str) -> Vec<Presence> {
+        self.presence.lock().present(tenant_id, document_id)
+    }
+
+    /// Send a presence update to a document's clients
+    async fn send_presence(&self, tenant_id: &str, update: &PresenceUpdate, exclude_id: Option<&str>) {
+        match serde_json::to_value(update) {
+            Ok(payload) => {
+                let message = Message::new(MessageType::PresenceUpdate, PRESENCE_CLIENT_ID.to_string(), payload)
+                    .with_stale_at(stale_at(self.update_staleness));
+                self.broadcast_document(tenant_id, &update.document_id, &message, exclude_id).await;
+            }
+            Err(e) => log::error!("Failed to serialize presence update: {}", e),
+        }
+    }
+
+    /// Send a presence update to one client
+    async fn send_presence_to(&self, client_id: &str, update: &PresenceUpdate) {
+        match serde_json::to_value(update) {
+            Ok(payload) => {
+                let message = Message::new(MessageType::PresenceUpdate, PRESENCE_CLIENT_ID.to_string(), payload)
+                    .with_stale_at(stale_at(self.update_staleness));
+                self.send_to(client_id, &message).await;
+            }
+            Err(e) => log::error!("Failed to serialize presence update: {}", e),
+        }
+    }
+
+    /// Remove the subscribers `may_read` no longer lets read a document,
+    /// such as those a new password keeps out, announcing their departure
+    async fn lock_out(&self, tenant_id: &str, document_id: &str, may_read: impl Fn(&str) -> bool) {
+        let locked: Vec<String> = self.subscriptions.read()
+            .subscribers(tenant_id, document_id)
+            .filter(|client_id| !may_read(client_id))
+            .map(str::to_string)
+            .collect();
+        if locked.is_empty() {
+            return;
+        }
+        {
+            let mut subscriptions = self.subscriptions.write();
+            for client_id in &locked {
+                subscriptions.leave(tenant_id, document_id, client_id);
+            }
+        }
+        let departed = self.presence.lock().leave(tenant_id, document_id, locked);
+        if let Some(update) = departed {
+            self.send_presence(tenant_id, &update, None).await;
+        }
+    }
+
+    /// Get the IDs of the documents a client works on
+    fn documents_of(&self, client_id: &str) -> Vec<String> {
+        self.subscriptions.read().documents(client_id).map(str::to_string).collect()
+    }
 
+    /// Bind a temporary document to the client that created it
+    fn hold_temporary(&self, key: String, client_id: &str) {
+        self.temporary.write().insert(key, client_id.to_string());
+    }
+
+    /// Check whether a document is temporary, by scoped document key
+    fn is_temporary(&self, key: &str) -> bool {
+        self.temporary.read().contains_key(key)
+    }
+
+    /// Unbind and return the keys of a client's temporary documents
+    fn release_temporary(&self, client_id: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.temporary.write().retain(|key, owner| {
+            if owner == client_id {
+                keys.push(key.clone());
+            }
+            owner != client_id
+        });
+        keys
+    }
+
+    /// Get the IDs of a tenant's clients working on a document, sorted
+    fn clients_in_document(&self, tenant_id: &str, document_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self.subscriptions.read()
+            .subscribers(tenant_id, document_id)
+            .map(str::to_string)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Get a summary of every connected client of a tenant, sorted by ID
+    async fn clients(&self, tenant_id: &str) -> Vec<ClientSummary> {
         let clients = self.clients.read().await;
-        for (client_id, sender) in clients.iter() {
-            if let Some(exclude) = exclude_id {
-                if client_id == exclude {
-                    continue;
+        let subscriptions = self.subscriptions.read();
+        let mut summaries: Vec<ClientSummary> = clients
+            .iter()
+            .filter(|(_, entry)| entry.tenant_id == tenant_id)
+            .map(|(id, entry)| {
+                let mut documents: Vec<String> = subscriptions.documents(id).map(str::to_string).collect();
+                documents.sort();
+                ClientSummary {
+                    client_id: id.clone(),
+                    tenant_id: entry.tenant_id.clone(),
+                    documents,
                 }
-            }
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        summaries
+    }
+
+    /// Send a message to a single client, returning whether it was delivered
+    async fn send_to(&self, client_id: &str, message: &Message) -> bool {
+        let Some(encoded) = self.encode(message) else {
+            return false;
+        };
+
+        let entry = self.clients.read().await
+            .get(client_id)
+            .map(|entry| (entry.sender.clone(), entry.failures.clone(), entry.tenant_id.clone()));
+        let Some((sender, failures, tenant_id)) = entry else {
+            return false;
+        };
 
-            if let Err(e) = sender.send(WsMessage::text(message.clone())).await {
+        let bytes = encoded.text.len();
+        match deliver(&sender, encoded).await {
+            Ok(false) => {
+                self.metrics.stale_messages.increment();
+                false
+            }
+            Ok(true) => {
+                failures.store(0, Ordering::Relaxed);
+                self.metrics.bandwidth.record(&tenant_id, client_id, document_of(message), Direction::Out, bytes);
+                true
+            }
+            Err(e) => {
                 log::error!("Failed to send message to client {}: {}", client_id, e);
+                if self.record_failure(&failures) {
+                    self.evict(client_id).await;
+                }
+                false
+            }
+        }
+    }
+
+    /// Count a failed send, returning whether the client should be evicted
+    fn record_failure(&self, failures: &AtomicU32) -> bool {
+        self.metrics.send_failures.increment();
+        failures.fetch_add(1, Ordering::Relaxed) + 1 >= MAX_SEND_FAILURES
+    }
+
+    /// Remove a client whose channel keeps failing and shut its connection down,
+    /// which runs the normal disconnect cleanup
+    async fn evict(&self, client_id: &str) {
+        let shutdown = self.clients.read().await.get(client_id).map(|entry| entry.shutdown.clone());
+        if self.remove_client(client_id).await.is_some() {
+            log::warn!("Evicting client {} after {} failed sends", client_id, MAX_SEND_FAILURES);
+            self.metrics.client_evictions.increment();
+            if let Some(shutdown) = shutdown {
+                shutdown.cancel();
             }
         }
     }
+
+    /// Track a client's playback stream, replacing any previous one
+    async fn start_playback(&self, client_id: &str, task: JoinHandle<()>) {
+        if let Some(previous) = self.playbacks.write().await.insert(client_id.to_string(), task) {
+            previous.abort();
+        }
+    }
+
+    /// Stop a client's playback stream if one is running
+    async fn stop_playback(&self, client_id: &str) {
+        if let Some(task) = self.playbacks.write().await.remove(client_id) {
+            task.abort();
+        }
+    }
+
+    /// Track a client's diff view, replacing any previous one
+    async fn start_diff_view(&self, client_id: &str, task: JoinHandle<()>) {
+        if let Some(previous) = self.diff_views.write().await.insert(client_id.to_string(), task) {
+            previous.abort();
+        }
+    }
+
+    /// Stop a client's diff view if one is running
+    async fn stop_diff_view(&self, client_id: &str) {
+        if let Some(task) = self.diff_views.write().await.remove(client_id) {
+            task.abort();
+        }
+    }
+
+    /// Track a client's activity subscription, replacing any previous one
+    async fn start_activity_feed(&self, client_id: &str, task: JoinHandle<()>) {
+        if let Some(previous) = self.activity_feeds.write().await.insert(client_id.to_string(), task) {
+            previous.abort();
+        }
+    }
+
+    /// Stop a client's activity subscription if one is running
+    async fn stop_activity_feed(&self, client_id: &str) {
+        if let Some(task) = self.activity_feeds.write().await.remove(client_id) {
+            task.abort();
+        }
+    }
+
+    /// Broadcast a message to all clients of a tenant except the specified one.
+    ///
+    /// The recipients' senders are snapshotted and the client map is released
+    /// before sending, so a full channel never blocks registration or other
+    /// broadcasts. Sends run concurrently, each bounded by `SEND_TIMEOUT`.
+    async fn broadcast(&self, tenant_id: &str, message: &Message, exclude_id: Option<&str>) {
+        let recipients: Vec<(String, mpsc::Sender<Frame>, Arc<AtomicU32>)> = self.clients.read().await
+            .iter()
+            .filter(|(client_id, entry)| entry.tenant_id == tenant_id && exclude_id != Some(client_id.as_str()))
+            .map(|(client_id, entry)| (client_id.clone(), entry.sender.clone(), entry.failures.clone()))
+            .collect();
+        self.deliver_all(tenant_id, message, recipients).await;
+    }
+
+    /// Send a message to the clients working on a document, except the
+    /// excluded one
+    async fn broadcast_document(&self, tenant_id: &str, document_id: &str, message: &Message, exclude_id: Option<&str>) {
+        self.broadcast_document_to(tenant_id, document_id, message, exclude_id, |_| true).await;
+    }
+
+    /// Send a message to the clients working on a document that
+    /// `may_read` lets read it, except the excluded one
+    async fn broadcast_document_to(
+        &self,
+        tenant_id: &str,
+        document_id: &str,
+        message: &Message,
+        exclude_id: Option<&str>,
+        may_read: impl Fn(&str) -> bool,
+    ) {
+        let subscribers: Vec<String> = self.subscriptions.read()
+            .subscribers(tenant_id, document_id)
+            .filter(|client_id| exclude_id != Some(*client_id) && may_read(client_id))
+            .map(str::to_string)
+            .collect();
+        if subscribers.is_empty() {
+            return;
+        }
+        let clients = self.clients.read().await;
+        let recipients: Vec<(String, mpsc::Sender<Frame>, Arc<AtomicU32>)> = subscribers
+            .into_iter()
+            .filter_map(|client_id| {
+                let entry = clients.get(&client_id)?;
+                Some((client_id, entry.sender.clone(), entry.failures.clone()))
+            })
+            .collect();
+        drop(clients);
+        self.deliver_all(tenant_id, message, recipients).await;
+    }
+
+    /// Send a message to clients of a tenant, encoding it once, and evict
+    /// those whose channels keep failing
+    async fn deliver_all(&self, tenant_id: &str, message: &Message, recipients: Vec<(String, mpsc::Sender<Frame>, Arc<AtomicU32>)>) {
+        let Some(encoded) = self.encode(message) else {
+            return;
+        };
+        let document_id = document_of(message);
+        let bytes = encoded.text.len();
+
+        let dead: Vec<String> = futures::stream::iter(recipients)
+            .map(|(client_id, sender, failures)| {
+                let frame = Frame::clone(&encoded);
+                async move {
+                    match deliver(&sender, frame).await {
+                        Ok(false) => {
+                            self.metrics.stale_messages.increment();
+                            None
+                        }
+                        Ok(true) => {
+                            failures.store(0, Ordering::Relaxed);
+                            self.metrics.bandwidth.record(tenant_id, &client_id, document_id, Direction::Out, bytes);
+                            None
+                        }
+                        Err(e) => {
+                            log::error!("Failed to send message to client {}: {}", client_id, e);
+                            self.record_failure(&failures).then_some(client_id)
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(BROADCAST_CONCURRENCY)
+            .filter_map(|dead| async move { dead })
+            .collect()
+            .await;
+
+        for client_id in dead {
+            self.evict(&client_id).await;
+        }
+    }
+}
+
+/// Get when an ephemeral message sent now goes stale, if `staleness`
+/// isn't zero
+fn stale_at(staleness: Duration) -> Option<std::time::Instant> {
+    (!staleness.is_zero()).then(|| std::time::Instant::now() + staleness)
+}
+
+/// Get the document a message is about, for bandwidth accounting
+fn document_of(message: &Message) -> Option<&str> {
+    message.payload().get("document_id").and_then(|document_id| document_id.as_str())
+}
+
+/// Queue a frame on a client's channel, failing if the channel is closed or
+/// stays full for longer than `SEND_TIMEOUT`. Ephemeral frames don't wait
+/// on a full channel: they are dropped, returning false.
+async fn deliver(sender: &mpsc::Sender<Frame>, frame: Frame) -> Result<bool, String> {
+    if frame.stale_at.is_some() {
+        return match sender.try_send(frame) {
+            Ok(()) => Ok(true),
+            Err(mpsc::error::TrySendError::Full(_)) => Ok(false),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+    match tokio::time::timeout(SEND_TIMEOUT, sender.send(frame)).await {
+        Ok(Ok(())) => Ok(true),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("channel stayed full for {:?}", SEND_TIMEOUT)),
+    }
 }
 
 use crate::{
-    crdt::Document,
+    blocks::{checklist::MAX_CHECKLIST_ITEMS, code_blocks, import_into, links, BlockDiagnostic, DocumentLinks, ImportFormat, SyntaxChecker},
+    commands::{parse_command, CommandError, CommandInput, CommandOutput, CommandRegistry, COMMAND_CLIENT_ID},
+    crdt::{diff, revision, ConcurrencyStats, Document, DocumentError, DocumentUpdate, Operation, OperationSource, Playback, Position, StateVector},
+    ids::{IdGenerator, UuidV7Ids},
+    jobs::JobQueue,
+    metrics::{
+        AbuseConfig, AbuseError, AbuseReport, BandwidthConfig, BandwidthReport, Direction, DocumentMemory, LabelConfig, MemoryConfig, MemoryOverview, MemorySample,
+        MetricsSnapshot, OverloadConfig, OverloadDetector, OverloadStatus, ServerMetrics,
+    },
+    moderation::{ContentFilter, ModerationAction, ModerationConfig, RegionTracker, TextRegion},
+    policy::{AllowAll, Decision, DocumentInfo, PolicyEngine, PolicyInput},
+    presence::{CursorThrottle, Presence, PresenceConfig, PresenceTracker, PresenceUpdate, Throttled},
+    security::{
+        hash_password, spawn_key_rotation, verify_password, RedactionConfig, Redactor, SessionClaims, ShareClaims, TokenConfig, TokenError, TokenKind,
+    },
+    storage::{health::Recovery, DegradedDocument, DocumentStore, StorageConfig, StorageHealth},
+    telemetry::{TelemetryConfig, UsageReport, UsageReporter},
+    tenant::{classroom::breakout_id, AliasError, PasswordConfig, PasswordError, Tenant, TenantConfig, TenantError, TenantRegistry, DEFAULT_TENANT},
+    undo::{UndoError, UndoHistory},
     websocket::{
-        connection::ConnectionManager,
-        message::{Message, MessageType, OperationMessage},
+        admin::{self, AdminConfig},
+        doctor::{self, CheckLevel},
+        assets::{self, StaticConfig},
+        activity::{self, ActivityConfig, ActivityFeed, ActivityPage, DEFAULT_ACTIVITY_LIMIT, MAX_ACTIVITIES_PER_PAGE},
+        events::{self, DocumentEventKind, EventLog},
+        previews::{self, DocumentPreview, PreviewCache, PREVIEW_LENGTH},
+        tail::TailCache,
+        skew::{HeldOperation, SkewConfig, SkewDetector, SkewError, SkewReport},
+        compat::{check_features, default_deprecations, operation_payload, Deprecation, SUPPORTED_FEATURES},
+        transfer::{Transfer, TransferError, TransferRequest, AUDIT_TARGET},
+        backlinks,
+        replication::{self, Replication, ReplicationError, ReplicationSnapshot, ReplicationStatus, ReplicatedChange, SnapshotDocument, StandbyConfig},
+        export::{self, ExportConfig},
+        import::{self, ImportError, ImportReport, IMPORT_CLIENT_ID},
+        seed::{SeedConfig, SeededDocument},
+        federation::{self, SyncHandle, SyncLink},
+        connection::{ConnectionConfig, ConnectionManager, ConnectionStatus},
+        quota::{QuotaConfig, QuotaTracker, QuotaWarning},
+        subscriptions::SubscriptionIndex,
+        transport::{duplex, MemoryTransport, Transport},
+        validation::{report_violation, validate_outbound},
+        webhook::{WebhookEvent, WebhookNotifier},
+        message::{
+            ChecklistOperationMessage, ChecklistStateMessage, CheckSyntaxMessage, CommandMessage, CursorUpdateMessage, CreateBreakoutsMessage, CreateDocumentMessage, DiffAnnotationsMessage,
+            DiffRequestMessage, DocumentCreatedMessage, SubscribeActivityMessage,
+            DocumentStateMessage, DocumentUpdateMessage, GetChecklistMessage, GetDocumentMessage, Message, MessageType, OperationMessage, PlaybackFrameMessage,
+            PlaybackRequestMessage, RepairRequestMessage, RepairResponseMessage, SetCharsetMessage, SetFrozenMessage,
+            GetPresenceMessage, SetPasswordMessage, SetPresenceMessage, SetSlugMessage, SetUndoScopeMessage, SettingsChangedMessage, SyncRequestMessage, SyncResponseMessage,
+            ShareDocumentMessage, SyntaxReportMessage, UndoMessage, UnlockDocumentMessage,
+        },
     },
 };
 
+/// What to do with operations that target a document that doesn't exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentPolicy {
+    /// Create an empty document on its first operation
+    #[default]
+    AutoCreate,
+    /// Reject the operation with `DocumentNotFound`
+    Strict,
+}
+
 /// Configuration for the WebSocket server
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
@@ -110,6 +700,72 @@ pub struct ServerConfig {
     pub heartbeat_interval: Duration,
     /// Time before considering a connection as timed out
     pub connection_timeout: Duration,
+    /// Rules for masking message content in logs
+    pub redaction: RedactionConfig,
+    /// Document size limits and warning thresholds, per tenant unless overridden
+    pub quota: QuotaConfig,
+    /// Tenants served in addition to the default one
+    pub tenants: Vec<TenantConfig>,
+    /// Check outbound messages against the protocol schema (on in debug builds)
+    pub validate_outbound: bool,
+    /// Handling of operations on unknown documents
+    pub document_policy: DocumentPolicy,
+    /// Source of client and document IDs
+    pub ids: Arc<dyn IdGenerator>,
+    /// Anonymous usage reporting, off by default
+    pub telemetry: TelemetryConfig,
+    /// Decides whether client operations may be applied; allows all by default
+    pub policy: Arc<dyn PolicyEngine>,
+    /// Checking of inserted words against a content filter; off by default
+    pub moderation: ModerationConfig,
+    /// Checker answering `checkSyntax` requests; none by default
+    pub syntax_checker: Option<Arc<dyn SyntaxChecker>>,
+    /// Handlers of `command` messages; the built-in commands by default
+    pub commands: CommandRegistry,
+    /// Frontend assets served alongside the WebSocket routes; none by default
+    pub assets: StaticConfig,
+    /// Watermarks of HTTP exports; none by default
+    pub export: ExportConfig,
+    /// Access to the admin API; disabled by default
+    pub admin: AdminConfig,
+    /// Latency budget of operations, above which non-essential work is
+    /// shed; off by default
+    pub overload: OverloadConfig,
+    /// Primary to follow as a warm standby; none by default, serving as
+    /// the primary
+    pub standby: Option<StandbyConfig>,
+    /// Tenants and documents with their own series in metrics; the 50
+    /// busiest tenants and their 20 busiest documents by default
+    pub metric_labels: LabelConfig,
+    /// Thresholds at which clients are flagged for skewed timestamps, and
+    /// whether their operations are quarantined; off by default
+    pub skew: SkewConfig,
+    /// Bytes per second each client and each document may use before
+    /// being throttled; unlimited by default
+    pub bandwidth: BandwidthConfig,
+    /// Editing rates that raise alerts, and how long an alert restricts
+    /// the client's writes; off by default
+    pub abuse: AbuseConfig,
+    /// Size, retention and heavy-edit threshold of workspace activity feeds
+    pub activity: ActivityConfig,
+    /// Documents created in every workspace on first boot; none by default
+    pub seed: SeedConfig,
+    /// Message types and features announced as deprecated in the welcome
+    /// message; `connect` and `disconnect` by default
+    pub deprecations: Vec<Deprecation>,
+    /// Hashing cost of document passwords, and lockout after wrong ones
+    pub passwords: PasswordConfig,
+    /// How often the memory of documents is sampled, and how many samples
+    /// are kept
+    pub memory: MemoryConfig,
+    /// Where documents are stored, and how often they are snapshotted; in
+    /// memory by default
+    pub storage: StorageConfig,
+    /// How long clients stay present in documents without sending anything
+    pub presence: PresenceConfig,
+    /// Keys sealing session and share tokens, and how long the tokens last;
+    /// a key of the process by default
+    pub tokens: TokenConfig,
 }
 
 impl Default for ServerConfig {
@@ -119,236 +775,5020 @@ impl Default for ServerConfig {
             host: "127.0.0.1".to_string(),
             heartbeat_interval: Duration::from_secs(30),
             connection_timeout: Duration::from_secs(60),
+            redaction: RedactionConfig::default(),
+            quota: QuotaConfig::default(),
+            tenants: Vec::new(),
+            validate_outbound: cfg!(debug_assertions),
+            document_policy: DocumentPolicy::default(),
+            ids: Arc::new(UuidV7Ids),
+            telemetry: TelemetryConfig::default(),
+            policy: Arc::new(AllowAll),
+            moderation: ModerationConfig::default(),
+            syntax_checker: None,
+            commands: CommandRegistry::default(),
+            assets: StaticConfig::default(),
+            export: ExportConfig::default(),
+            admin: AdminConfig::default(),
+            overload: OverloadConfig::default(),
+            standby: None,
+            metric_labels: LabelConfig::default(),
+            skew: SkewConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            abuse: AbuseConfig::default(),
+            activity: ActivityConfig::default(),
+            seed: SeedConfig::default(),
+            deprecations: default_deprecations(),
+            passwords: PasswordConfig::default(),
+            memory: MemoryConfig::default(),
+            storage: StorageConfig::default(),
+            presence: PresenceConfig::default(),
+            tokens: TokenConfig::default(),
         }
     }
 }
 
-/// Main WebSocket server implementation
-pub struct EditorServer {
-    config: ServerConfig,
+/// An operation applied to a local document, as seen by sync links
+#[derive(Debug, Clone)]
+pub(crate) struct OperationEvent {
+    pub tenant_id: String,
+    pub document_id: String,
+    pub operation: Operation,
+    /// Client, or sync link, the operation came from
+    pub origin: String,
+    /// Kind of actor the operation came from
+    pub source: OperationSource,
+}
+
+/// Capacity of the operation event channel; a sync link that falls
+/// further behind reconnects and reconciles instead
+const OPERATION_EVENT_CAPACITY: usize = 1024;
+
+/// Characters of initial content applied per chunk; the server yields to
+/// other tasks between chunks so large seeds don't stall it
+const SEED_CHUNK_SIZE: usize = 4096;
+
+/// How long a read asking for a newer version than a document has waits
+/// for the writes still in flight
+pub(crate) const READ_WAIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shared state handed to every connection and message handler
+#[derive(Clone)]
+pub(crate) struct ServerState {
     connections: Arc<RwLock<ConnectionManager>>,
+    /// Documents keyed by their tenant-scoped ID
     documents: Arc<RwLock<HashMap<String, Document>>>,
     clients: Arc<ClientManager>,
+    redactor: Arc<Redactor>,
+    tenants: Arc<TenantRegistry>,
+    document_policy: DocumentPolicy,
+    metrics: Arc<ServerMetrics>,
+    ids: Arc<dyn IdGenerator>,
+    policy: Arc<dyn PolicyEngine>,
+    /// Applied operations, for sync links mirroring local documents
+    operations: broadcast::Sender<OperationEvent>,
+    /// Words being typed and the queue of words to check, when moderating
+    moderation: Option<ModerationQueue>,
+    syntax_checker: Option<Arc<dyn SyntaxChecker>>,
+    commands: Arc<CommandRegistry>,
+    export: ExportConfig,
+    /// Lifecycle events of documents, for `GET /events`
+    events: Arc<EventLog>,
+    /// Recently active documents, for `GET /documents`
+    previews: Arc<PreviewCache>,
+    /// What happened in each workspace, for `GET /activity`
+    activity: Arc<ActivityFeed>,
+    /// Snapshots and recent operations of joined documents
+    tails: Arc<TailCache>,
+    /// Clients sending skewed timestamps, and their quarantined operations
+    skew: Arc<SkewDetector>,
+    /// Latency of operations against their budget
+    overload: Arc<OverloadDetector>,
+    /// Long-running tasks such as bulk exports
+    jobs: Arc<JobQueue>,
+    /// Log of changes for standbys, and progress while following a primary
+    replication: Arc<Replication>,
+    /// Deprecated message types and features, announced to clients
+    deprecations: Arc<Vec<Deprecation>>,
+    passwords: PasswordConfig,
+    /// Store documents are persisted to
+    store: Arc<dyn DocumentStore>,
+    /// What the store holds of each document
+    persisted: Arc<parking_lot::Mutex<HashMap<String, Persisted>>>,
+    /// Documents the store failed to write. Taken before `persisted`,
+    /// never while holding it.
+    health: Arc<parking_lot::Mutex<StorageHealth>>,
+    /// Cursor updates held back to coalesce them
+    cursors: Arc<parking_lot::Mutex<CursorThrottle<Message>>>,
+    /// How long relayed cursor updates stay worth delivering by default
+    cursor_staleness: Duration,
+    /// Sealer and lifetimes of session and share tokens
+    tokens: TokenConfig,
+}
+
+/// What a store holds of a document
+#[derive(Debug, Clone, Default)]
+struct Persisted {
+    /// Version of the latest snapshot
+    saved: Option<u64>,
+    /// Operations logged, counted per author. A snapshot is saved only
+    /// once every operation it holds is logged, or the store would keep
+    /// operations logged after it that it already holds.
+    logged: StateVector,
+    /// Repairs of the document when it was last written as a whole; a
+    /// repaired document's history no longer matches the log
+    repairs: u64,
+}
+
+impl ServerState {
+    /// Look up a tenant
+    pub(crate) fn tenant(&self, tenant_id: &str) -> Result<Arc<Tenant>, TenantError> {
+        self.tenants.get(tenant_id)
+    }
+
+    /// Open a share token presented for a tenant's document, checking it
+    /// was issued for that document
+    pub(crate) fn open_share(&self, tenant: &Tenant, document_id: &str, token: &str) -> Result<ShareClaims, TokenError> {
+        let claims: ShareClaims = self.tokens.sealer.open(TokenKind::Share, token, Utc::now())?;
+        if claims.tenant_id != tenant.id() || claims.document_id != document_id {
+            return Err(TokenError::Invalid);
+        }
+        Ok(claims)
+    }
+
+    /// Check that an HTTP request may address a tenant's document, by ID
+    /// or slug: it has no password, or the request presents a share token
+    /// for its current one. HTTP requests have no connection to unlock.
+    pub(crate) fn check_http_access(&self, tenant: &Tenant, document_id: &str, share: Option<&str>) -> Result<(), String> {
+        let document_id = tenant.aliases().resolve(document_id);
+        let Some(fingerprint) = tenant.passwords().fingerprint(&document_id) else {
+            return Ok(());
+        };
+        let Some(token) = share else {
+            return Err(format!("Document {} is protected by a password; pass a share token as share", document_id));
+        };
+        let claims = self.open_share(tenant, &document_id, token).map_err(|e| e.to_string())?;
+        if claims.password != fingerprint {
+            return Err(PasswordError::Revoked(document_id).to_string());
+        }
+        Ok(())
+    }
+
+    /// Get the watermark settings of HTTP exports
+    pub(crate) fn export_config(&self) -> ExportConfig {
+        self.export
+    }
+
+    /// Get the log of document lifecycle events
+    pub(crate) fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    /// Get the activity feeds of the tenants
+    pub(crate) fn activity(&self) -> &ActivityFeed {
+        &self.activity
+    }
+
+    /// Get the redactor applied to logged message content
+    pub(crate) fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Get the overload detector
+    pub(crate) fn overload(&self) -> &OverloadDetector {
+        &self.overload
+    }
+
+    /// Get the server counters
+    pub(crate) fn metrics(&self) -> &ServerMetrics {
+        &self.metrics
+    }
+
+    /// Get the detector of clients sending skewed timestamps
+    pub(crate) fn skew(&self) -> &SkewDetector {
+        &self.skew
+    }
+
+    /// Get the queue of long-running tasks
+    pub(crate) fn jobs(&self) -> &Arc<JobQueue> {
+        &self.jobs
+    }
+
+    /// Get the replication log and role
+    pub(crate) fn replication(&self) -> &Replication {
+        &self.replication
+    }
+
+    /// Get the IDs of all tenants
+    pub(crate) fn tenant_ids(&self) -> Vec<String> {
+        self.tenants.ids()
+    }
+
+    /// Documents the store failed to write, for the admin API
+    pub(crate) fn degraded_documents(&self) -> Vec<DegradedDocument> {
+        self.health.lock().report()
+    }
+}
+
+/// Words on their way to the content filter
+#[derive(Clone)]
+struct ModerationQueue {
+    tracker: Arc<parking_lot::Mutex<RegionTracker>>,
+    regions: mpsc::Sender<TextRegion>,
+}
+
+impl ModerationQueue {
+    /// Queue a finished word, dropping it if the filter has fallen behind
+    fn push(&self, region: TextRegion) {
+        if self.regions.try_send(region).is_err() {
+            log::warn!("Moderation queue is full, a word was not checked");
+        }
+    }
+}
+
+/// Main WebSocket server implementation
+pub struct EditorServer {
+    config: ServerConfig,
+    state: ServerState,
+    /// Receiving end of the moderation queue, until checking starts
+    moderation_regions: parking_lot::Mutex<Option<mpsc::Receiver<TextRegion>>>,
+    /// Whether the seed documents were created
+    seeded: AtomicBool,
 }
 
 impl EditorServer {
     /// Create a new WebSocket server with the given configuration
     pub fn new(config: ServerConfig) -> Self {
+        let redactor = Redactor::new(&config.redaction).unwrap_or_else(|e| {
+            log::error!("Invalid redaction pattern, using defaults: {}", e);
+            Redactor::default()
+        });
+        let tenants = TenantRegistry::new(config.quota.clone(), config.tenants.clone()).unwrap_or_else(|e| {
+            log::error!("Invalid tenant configuration, serving the default tenant only: {}", e);
+            TenantRegistry::new(config.quota.clone(), Vec::new()).expect("default tenant is valid")
+        });
+        let metrics = Arc::new(ServerMetrics::with_labels(config.metric_labels).with_bandwidth(config.bandwidth).with_abuse(config.abuse).with_memory(config.memory));
+        let connection_config = if config.connection_timeout.is_zero() {
+            log::error!("Connection timeout must be positive, using the default");
+            ConnectionConfig::default()
+        } else {
+            ConnectionConfig {
+                connection_timeout: config.connection_timeout,
+            }
+        };
+
+        let (moderation, moderation_regions) = match config.moderation.filter {
+            Some(_) => {
+                let (regions, receiver) = mpsc::channel(MODERATION_QUEUE_CAPACITY);
+                let tracker = Arc::new(parking_lot::Mutex::new(RegionTracker::new()));
+                (Some(ModerationQueue { tracker, regions }), Some(receiver))
+            }
+            None => (None, None),
+        };
+
+        // Everything stored is logged; logs are compacted by the first
+        // snapshots
+        let documents = Self::load_documents(config.storage.store.as_ref());
+        Self::load_passwords(config.storage.store.as_ref(), &tenants);
+        let persisted = documents.iter()
+            .map(|(key, document)| {
+                (key.clone(), Persisted { saved: None, logged: document.state_vector().clone(), repairs: document.repairs() })
+            })
+            .collect();
+
         Self {
+            state: ServerState {
+                connections: Arc::new(RwLock::new(ConnectionManager::with_config(connection_config))),
+                documents: Arc::new(RwLock::new(documents)),
+                clients: Arc::new(
+                    ClientManager::new(config.validate_outbound, metrics.clone())
+                        .with_update_staleness(config.presence.update_staleness),
+                ),
+                redactor: Arc::new(redactor),
+                tenants: Arc::new(tenants),
+                document_policy: config.document_policy,
+                metrics,
+                ids: config.ids.clone(),
+                policy: config.policy.clone(),
+                operations: broadcast::channel(OPERATION_EVENT_CAPACITY).0,
+                moderation,
+                syntax_checker: config.syntax_checker.clone(),
+                commands: Arc::new(config.commands.clone()),
+                export: config.export,
+                events: Arc::new(EventLog::new()),
+                previews: Arc::new(PreviewCache::new()),
+                activity: Arc::new(ActivityFeed::new(config.activity)),
+                tails: Arc::new(TailCache::new()),
+                skew: Arc::new(SkewDetector::new(config.skew.clone())),
+                overload: Arc::new(OverloadDetector::new(config.overload.clone())),
+                jobs: Arc::new(JobQueue::default()),
+                replication: Arc::new(Replication::new(config.standby.as_ref())),
+                deprecations: Arc::new(config.deprecations.clone()),
+                passwords: config.passwords,
+                store: config.storage.store.clone(),
+                persisted: Arc::new(parking_lot::Mutex::new(persisted)),
+                health: Arc::new(parking_lot::Mutex::new(StorageHealth::new(config.storage.degraded_capacity))),
+                cursors: Arc::new(parking_lot::Mutex::new(CursorThrottle::default())),
+                cursor_staleness: config.presence.cursor_staleness,
+                tokens: config.tokens.clone(),
+            },
+            moderation_regions: parking_lot::Mutex::new(moderation_regions),
+            seeded: AtomicBool::new(false),
             config,
-            connections: Arc::new(RwLock::new(ConnectionManager::new())),
-            documents: Arc::new(RwLock::new(HashMap::new())),
-            clients: Arc::new(ClientManager::new()),
         }
     }
 
-    /// Start the WebSocket server
-    pub async fn run(&self) -> Result<()> {
-        let connections = self.connections.clone();
-        let documents = self.documents.clone();
-        let clients = self.clients.clone();
-        
-        // WebSocket route
-        let ws_route = warp::path("ws")
-            .and(warp::ws())
-            .map(move |ws: warp::ws::Ws| {
-                let connections = connections.clone();
-                let documents = documents.clone();
-                let clients = clients.clone();
-                
-                ws.on_upgrade(move |socket| {
-                    Self::handle_connection(
-                        socket,
-                        connections,
-                        documents,
-                        clients,
-                    )
-                })
-            });
+    /// Load every document of a store, skipping those that fail to load
+    fn load_documents(store: &dyn DocumentStore) -> HashMap<String, Document> {
+        let keys = store.list().unwrap_or_else(|e| {
+            log::error!("Could not list stored documents: {}", e);
+            Vec::new()
+        });
+        let mut documents = HashMap::new();
+        for key in keys {
+            let Some((_, document_id)) = key.split_once('/') else {
+                log::warn!("Skipped stored document {} without a tenant", key);
+                continue;
+            };
+            match store.load(&key).and_then(|stored| stored.map(|stored| stored.restore(document_id)).transpose()) {
+                Ok(Some(document)) => {
+                    documents.insert(key, document);
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("Could not load stored document {}: {}", key, e),
+            }
+        }
+        if !documents.is_empty() {
+            log::info!("Loaded {} stored documents", documents.len());
+        }
+        documents
+    }
 
-        // Start the server
-        let addr = std::net::SocketAddr::new(
-            self.config.host.parse()?,
-            self.config.port,
-        );
-        
-        log::info!("Starting WebSocket server on ws://{}", addr);
-        warp::serve(ws_route)
-            .run(addr)
-            .await;
+    /// Protect stored documents with their stored password hashes again
+    fn load_passwords(store: &dyn DocumentStore, tenants: &TenantRegistry) {
+        let passwords = store.load_passwords().unwrap_or_else(|e| {
+            log::error!("Could not load stored passwords: {}", e);
+            Vec::new()
+        });
+        for (key, hash) in passwords {
+            match key.split_once('/').and_then(|(tenant_id, document_id)| Some((tenants.get(tenant_id).ok()?, document_id))) {
+                Some((tenant, document_id)) => tenant.passwords().insert_document(document_id, hash),
+                None => log::warn!("Skipped stored password of document {} without a tenant", key),
+            }
+        }
+    }
 
-        Ok(())
+    /// Get the tenant registry
+    pub fn tenants(&self) -> &TenantRegistry {
+        &self.state.tenants
     }
 
-    /// Handle a new WebSocket connection
-    async fn handle_connection(
-        socket: WebSocket,
-        connections: Arc<RwLock<ConnectionManager>>,
-        documents: Arc<RwLock<HashMap<String, Document>>>,
-        clients: Arc<ClientManager>,
-    ) {
-        // Generate a unique client ID
-        let client_id = Uuid::new_v4().to_string();
-        
-        // Split the WebSocket into sender and receiver
-        let (mut ws_sender, mut ws_receiver) = socket.split();
-        
-        // Create a channel for sending messages to this client
-        let (tx, mut rx) = mpsc::channel(32);
-        
-        // Add client to client manager before registering with connection manager
-        clients.add_client(client_id.clone(), tx.clone()).await;
-        
-        // Add the client to the connection manager
-        {
-            let mut manager = connections.write().await;
-            if let Err(e) = manager.register_client(client_id.clone()).await {
-                log::error!("Failed to register client: {}", e);
-                clients.remove_client(&client_id).await;
-                return;
+    /// Get the number of connected clients across all tenants
+    pub fn client_count(&self) -> usize {
+        self.state.clients.client_count()
+    }
+
+    /// Get the IDs of a tenant's clients working on a document
+    pub async fn clients_in_document(&self, tenant_id: &str, document_id: &str) -> Result<Vec<String>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(self.state.clients.clients_in_document(tenant.id(), document_id))
+    }
+
+    /// Get the clients present in a document, in the order they joined
+    pub fn presence(&self, tenant_id: &str, document_id: &str) -> Result<Vec<Presence>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(self.state.clients.presence(tenant.id(), &tenant.aliases().resolve(document_id)))
+    }
+
+    /// Remove clients idle for longer than the presence timeout from the
+    /// documents they were in, as the background sweep does, returning
+    /// how many documents changed
+    pub async fn expire_presence(&self) -> usize {
+        self.state.clients.expire_presence(self.config.presence.idle_timeout).await
+    }
+
+    /// List the connected clients of a tenant
+    pub async fn clients(&self, tenant_id: &str) -> Result<Vec<ClientSummary>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(self.state.clients.clients(tenant.id()).await)
+    }
+
+    /// Serve a client over a transport, as if it had connected to the
+    /// tenant's WebSocket route. The connection runs in the background
+    /// until either side closes it.
+    pub fn accept<T: Transport>(&self, transport: T, tenant_id: &str, key: Option<&str>) -> Result<JoinHandle<()>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        tenant.authorize(key)?;
+        Ok(tokio::spawn(Self::handle_connection(transport, self.state.clone(), tenant, None, None)))
+    }
+
+    /// Serve a client over a transport, as if it had connected to the
+    /// tenant's WebSocket route with a session token
+    pub fn resume<T: Transport>(&self, transport: T, tenant_id: &str, token: &str) -> Result<JoinHandle<()>, TokenError> {
+        let tenant = self.state.tenants.get(tenant_id).map_err(|_| TokenError::Invalid)?;
+        let client_id = Self::open_session(&self.state, &tenant, token)?;
+        Ok(tokio::spawn(Self::handle_connection(transport, self.state.clone(), tenant, Some(client_id), None)))
+    }
+
+    /// Connect a client in process, without a socket, and return its end
+    /// of the connection. Background tasks started by `run`, such as usage
+    /// reports and moderation, don't run unless the server does.
+    pub fn connect_in_process(&self, tenant_id: &str, key: Option<&str>) -> Result<MemoryTransport, TenantError> {
+        let (client, server) = duplex();
+        self.accept(server, tenant_id, key)?;
+        Ok(client)
+    }
+
+    /// Resume a session in process with a session token, and return the
+    /// client's end of the connection
+    pub fn resume_in_process(&self, tenant_id: &str, token: &str) -> Result<MemoryTransport, TokenError> {
+        let (client, server) = duplex();
+        self.resume(server, tenant_id, token)?;
+        Ok(client)
+    }
+
+    /// Get the version and start of the text of a tenant's recently active
+    /// documents, most recent first
+    pub async fn recent_documents(&self, tenant_id: &str, limit: usize) -> Result<Vec<DocumentPreview>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(Self::recent_previews(&self.state, &tenant, limit, |_| true).await)
+    }
+
+    /// Get the documents a document, by ID or slug, links to and those
+    /// linking to it
+    pub async fn document_links(&self, tenant_id: &str, document_id: &str) -> Result<DocumentLinks, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(Self::collect_links(&self.state, &tenant, document_id).await)
+    }
+
+    /// Get the queue of long-running tasks, to run or inspect jobs
+    pub fn jobs(&self) -> &Arc<JobQueue> {
+        self.state.jobs()
+    }
+
+    /// Get whether the server is a primary or a standby, and how far a
+    /// standby has caught up
+    pub fn replication_status(&self) -> ReplicationStatus {
+        self.state.replication.status()
+    }
+
+    /// Promote a standby to primary: stop following the primary and accept
+    /// clients
+    pub fn promote(&self) -> Result<ReplicationStatus, ReplicationError> {
+        self.state.replication.promote()
+    }
+
+    /// Get the clients flagged for sending skewed timestamps, with the
+    /// operations they had quarantined
+    pub fn skewed_clients(&self) -> Vec<SkewReport> {
+        self.state.skew.flagged()
+    }
+
+    /// Clear a flagged client and apply the operations it had quarantined,
+    /// returning how many were applied
+    pub async fn release_skewed(&self, tenant_id: &str, client_id: &str) -> Result<usize, SkewError> {
+        Self::release_quarantine(&self.state, tenant_id, client_id).await
+    }
+
+    /// Clear a flagged client and discard the operations it had
+    /// quarantined, returning how many were discarded
+    pub fn discard_skewed(&self, tenant_id: &str, client_id: &str) -> Result<usize, SkewError> {
+        self.state.skew.clear(tenant_id, client_id).map(|held| held.len())
+    }
+
+    /// Clear a flagged client and apply the operations it had quarantined
+    pub(crate) async fn release_quarantine(state: &ServerState, tenant_id: &str, client_id: &str) -> Result<usize, SkewError> {
+        let held = state.skew.clear(tenant_id, client_id)?;
+        let Ok(tenant) = state.tenants.get(tenant_id) else {
+            return Ok(0);
+        };
+        let mut applied = 0;
+        for HeldOperation { document_id, operation } in held {
+            match Self::merge_remote(state, &tenant, &document_id, operation, OperationSource::User, client_id).await {
+                Ok(true) => applied += 1,
+                Ok(false) => {}
+                Err(e) => log::error!("Failed to apply quarantined operation on {}: {}", document_id, e),
             }
         }
-        
-        log::info!("Client connected: {}", client_id);
-        
-        // Send welcome message
-        let welcome_msg = Message::new(
-            MessageType::Status,
-            client_id.clone(),
-            json!({ "status": "connected", "client_id": &client_id }),
+        Ok(applied)
+    }
+
+    /// Move a document, with its settings, to another tenant
+    pub async fn transfer_document(&self, tenant_id: &str, document_id: &str, request: TransferRequest) -> Result<Transfer, TransferError> {
+        Self::move_document(&self.state, tenant_id, document_id, request).await
+    }
+
+    /// Move a document to another tenant under the documents lock, and
+    /// tell both tenants' clients
+    pub(crate) async fn move_document(state: &ServerState, tenant_id: &str, document_id: &str, request: TransferRequest) -> Result<Transfer, TransferError> {
+        let source = state.tenants.get(tenant_id)?;
+        let target = state.tenants.get(&request.to_tenant)?;
+        let from_document = source.aliases().resolve(document_id);
+        let to_document = request.document_id.clone().unwrap_or_else(|| from_document.clone());
+        let (from_key, to_key) = (source.scoped(&from_document), target.scoped(&to_document));
+        if from_key == to_key {
+            return Err(TransferError::SamePlace(from_document));
+        }
+        if source.encryption() != target.encryption() {
+            return Err(TransferError::Encryption(source.id().to_string(), target.id().to_string()));
+        }
+
+        let mut docs = state.documents.write().await;
+        let Some(document) = docs.get(&from_key) else {
+            return Err(TransferError::NotFound(from_document));
+        };
+        if state.clients.is_temporary(&from_key) {
+            return Err(TransferError::Temporary(from_document));
+        }
+        if docs.contains_key(&to_key) {
+            return Err(TransferError::Exists(target.id().to_string(), to_document));
+        }
+        if let Some(limit) = target.quota().limit().filter(|limit| document.len() > *limit) {
+            return Err(TransferError::TooLarge {
+                document_id: from_document,
+                tenant_id: target.id().to_string(),
+                length: document.len(),
+                limit,
+            });
+        }
+        let Some(document) = docs.remove(&from_key) else {
+            return Err(TransferError::NotFound(from_document));
+        };
+        let moved = document.fork(to_document.clone());
+        let version = moved.version();
+        docs.insert(to_key, moved.clone());
+        state.tails.forget(&from_key);
+
+        // Settings travel with the document while it is locked, so no
+        // operation sees it half moved
+        let mut settings = source.classroom().take_document(&from_document).unwrap_or_default();
+        if request.owner.is_some() {
+            settings.owner = request.owner.clone();
+        }
+        target.classroom().insert_document(&to_document, settings);
+        let password = source.passwords().take_document(&from_document);
+        if let Some(hash) = &password {
+            target.passwords().insert_document(&to_document, hash.clone());
+        }
+        target.undo().set_scope(&to_document, source.undo().scope(&from_document));
+        target.checklists().insert_document(&to_document, source.checklists().take_document(&from_document));
+        let slug = source.aliases().slug(&from_document);
+        source.aliases().remove_document(&from_document);
+        if let Some(slug) = slug {
+            if let Err(e) = target.aliases().set_slug(&to_document, &slug) {
+                log::warn!("Dropped slug of document {} moved to tenant {}: {}", to_document, target.id(), e);
+            }
+        }
+        source.undo().remove_document(&from_document);
+        source.links().remove_document(&from_document);
+        source.quota().forget(&from_document);
+        drop(docs);
+
+        state.previews.forget(source.id(), &from_document);
+        state.activity.forget(source.id(), &from_document);
+        Self::record_change(state, &target, &to_document, true, version);
+        Self::delete_stored(state, &from_key);
+        Self::store_document(state, &target.scoped(&to_document), &moved);
+        if let Some(hash) = &password {
+            Self::store_password(state, &target.scoped(&to_document), Some(hash));
+        }
+        state.replication.log().record(target.id(), &to_document, ReplicatedChange::Document { document: Box::new(moved) });
+
+        let transfer = Transfer {
+            from_tenant: source.id().to_string(),
+            from_document,
+            to_tenant: target.id().to_string(),
+            to_document,
+            version,
+        };
+        log::info!(
+            target: AUDIT_TARGET,
+            "Moved document {} of tenant {} to {} of tenant {} at version {}",
+            transfer.from_document, transfer.from_tenant, transfer.to_document, transfer.to_tenant, version,
         );
-        
-        if let Err(e) = tx.send(WsMessage::text(serde_json::to_string(&welcome_msg).unwrap())).await {
-            log::error!("Failed to send welcome message: {}", e);
-            clients.remove_client(&client_id).await;
+        match serde_json::to_value(&transfer) {
+            Ok(payload) => {
+                let notice = Message::new(MessageType::DocumentTransferred, "server".to_string(), payload);
+                state.clients.broadcast(source.id(), &notice, None).await;
+                state.clients.broadcast(target.id(), &notice, None).await;
+            }
+            Err(e) => log::error!("Failed to serialize transfer: {}", e),
+        }
+        Ok(transfer)
+    }
+
+    /// Append a source to a tenant's document, by ID or slug, creating the
+    /// document if needed. Markdown task lists become checklists of the
+    /// document.
+    pub async fn import_document(&self, tenant_id: &str, document_id: &str, source: &str, format: ImportFormat) -> Result<ImportReport, ImportError> {
+        Self::import_source(&self.state, tenant_id, document_id, source, format).await
+    }
+
+    /// Apply an import's operations and checklists, and relay them to the
+    /// tenant's clients
+    pub(crate) async fn import_source(
+        state: &ServerState,
+        tenant_id: &str,
+        document_id: &str,
+        source: &str,
+        format: ImportFormat,
+    ) -> Result<ImportReport, ImportError> {
+        let tenant = state.tenants.get(tenant_id)?;
+        if tenant.encryption().is_end_to_end() {
+            return Err(ImportError::EndToEnd(tenant.id().to_string()));
+        }
+        let document_id = tenant.aliases().resolve(document_id);
+        let existing = Self::document_copy(state, &tenant, &document_id).await
+            .unwrap_or_else(|| Document::new(document_id.clone()));
+        let imported = import_into(&existing, source, format);
+        let operations = imported.operations(&existing, IMPORT_CLIENT_ID);
+        if let Some(limit) = tenant.quota().limit().filter(|limit| existing.len() + operations.len() > *limit) {
+            return Err(ImportError::TooLarge { document_id, limit });
+        }
+
+        let inserted = operations.len();
+        for operation in operations {
+            Self::merge_remote(state, &tenant, &document_id, operation, OperationSource::Import, IMPORT_CLIENT_ID).await?;
+        }
+        for checklist in &imported.checklists {
+            for operation in checklist.operations(IMPORT_CLIENT_ID) {
+                if tenant.checklists().apply(&document_id, &checklist.name, operation.clone()) != Some(true) {
+                    continue;
+                }
+                let relay = ChecklistOperationMessage {
+                    document_id: document_id.clone(),
+                    checklist: checklist.name.clone(),
+                    operation,
+                };
+                match serde_json::to_value(&relay) {
+                    Ok(payload) => {
+                        let relay = Message::new(MessageType::ChecklistOperation, IMPORT_CLIENT_ID.to_string(), payload);
+                        Self::relay(state, &tenant, &document_id, &relay, None).await;
+                    }
+                    Err(e) => log::error!("Failed to serialize checklist operation: {}", e),
+                }
+            }
+        }
+
+        let version = Self::with_document(state, &tenant, &document_id, Document::version).await.unwrap_or_default();
+        log::info!("Imported {} characters of {} into document {} of tenant {}", inserted, format.name(), document_id, tenant.id());
+        Ok(ImportReport {
+            document_id,
+            version,
+            inserted,
+            checklists: imported.checklists.into_iter().map(|checklist| checklist.name).collect(),
+            unmapped: imported.unmapped,
+        })
+    }
+
+    /// Create the documents of `ServerConfig::seed` in every tenant lacking
+    /// them, once per server, and return those created. `run` seeds before
+    /// serving; applications mounting `routes` call this themselves.
+    pub async fn seed_documents(&self) -> Vec<SeededDocument> {
+        if self.config.seed.documents.is_empty() || self.state.replication.is_standby() || self.seeded.swap(true, Ordering::SeqCst) {
+            return Vec::new();
+        }
+        let mut seeded = Vec::new();
+        for tenant_id in self.state.tenants.ids() {
+            let Ok(tenant) = self.state.tenants.get(&tenant_id) else {
+                continue;
+            };
+            if tenant.encryption().is_end_to_end() {
+                continue;
+            }
+            for document in &self.config.seed.documents {
+                if Self::with_document(&self.state, &tenant, &document.document_id, |_| ()).await.is_some() {
+                    continue;
+                }
+                match Self::import_source(&self.state, tenant.id(), &document.document_id, &document.source, document.format).await {
+                    Ok(report) => seeded.push(SeededDocument {
+                        tenant_id: tenant.id().to_string(),
+                        document_id: report.document_id,
+                        version: report.version,
+                    }),
+                    Err(e) => log::warn!("Could not seed document {} of tenant {}: {}", document.document_id, tenant.id(), e),
+                }
+            }
+        }
+        seeded
+    }
+
+    /// Get up to `limit` activities of a tenant's workspace before an
+    /// activity ID, newest first
+    pub fn activity(&self, tenant_id: &str, before: Option<u64>, limit: usize) -> Result<ActivityPage, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(self.state.activity.page(tenant.id(), before, limit, Utc::now(), |_| true))
+    }
+
+    /// Get the clients and documents using the most bandwidth, at most
+    /// `limit` of each
+    pub fn bandwidth(&self, limit: usize) -> BandwidthReport {
+        self.state.metrics.bandwidth.report(limit)
+    }
+
+    /// Get the alerts raised for clients editing abusively fast, newest
+    /// first, and the clients whose writes are restricted
+    pub fn abuse_report(&self) -> AbuseReport {
+        self.state.metrics.abuse.report(std::time::Instant::now())
+    }
+
+    /// Lift the write restriction of a client, letting it edit again
+    pub fn lift_restriction(&self, tenant_id: &str, client_id: &str) -> Result<(), AbuseError> {
+        self.state.metrics.abuse.lift(tenant_id, client_id, std::time::Instant::now())
+    }
+
+    /// Break down what every document holds in memory now, listing the
+    /// `limit` heaviest documents, along with the samples taken so far
+    pub async fn memory(&self, limit: usize) -> MemoryOverview {
+        Self::memory_overview(&self.state, limit).await
+    }
+
+    /// Sample the memory of every document now, and keep the sample in
+    /// the trend
+    pub async fn sample_memory(&self) -> MemorySample {
+        Self::record_memory_sample(&self.state).await
+    }
+
+    /// Save a snapshot of every document changed since its last one now,
+    /// returning how many were saved once they, and the operations logged
+    /// before them, are durable
+    pub async fn save_snapshots(&self) -> usize {
+        Self::snapshot_documents(&self.state).await
+    }
+
+    /// Write degraded documents, those the store failed to write, now,
+    /// returning how many recovered once their writes are durable
+    pub async fn recover_storage(&self) -> usize {
+        Self::recover_documents(&self.state).await
+    }
+
+    /// Documents the store failed to write
+    pub fn degraded_documents(&self) -> Vec<DegradedDocument> {
+        self.state.degraded_documents()
+    }
+
+    async fn snapshot_documents(state: &ServerState) -> usize {
+        // Repaired documents are written as a whole, as their history no
+        // longer follows the log. Degraded documents wait on recovery.
+        let changed: Vec<(String, Document, bool)> = {
+            let docs = state.documents.read().await;
+            let health = state.health.lock();
+            let persisted = state.persisted.lock();
+            docs.iter()
+                .filter(|(key, _)| !state.clients.is_temporary(key) && !health.is_degraded(key))
+                .filter_map(|(key, document)| {
+                    let stored = persisted.get(key);
+                    let repaired = stored.map_or(0, |stored| stored.repairs) != document.repairs();
+                    let changed = stored.and_then(|stored| stored.saved) != Some(document.version())
+                        && stored.is_none_or(|stored| stored.logged.covers(document.state_vector()));
+                    (repaired || changed).then(|| (key.clone(), document.clone(), repaired))
+                })
+                .collect()
+        };
+        let queued: Vec<(String, u64)> = changed
+            .into_iter()
+            .filter(|(key, document, repaired)| Self::queue_snapshot(state, key, document, *repaired))
+            .map(|(key, document, _)| (key, document.version()))
+            .collect();
+        if queued.is_empty() {
+            return 0;
+        }
+
+        // Count the snapshots as saved once they are durable
+        if Self::flush_store(state).await.is_none() {
+            return 0;
+        }
+        let mut persisted = state.persisted.lock();
+        for (key, version) in &queued {
+            persisted.entry(key.clone()).or_default().saved = Some(*version);
+        }
+        queued.len()
+    }
+
+    /// Store a document written as a whole, such as a copy or a replicated
+    /// snapshot, rather than operation by operation
+    fn store_document(state: &ServerState, key: &str, document: &Document) {
+        if Self::queue_snapshot(state, key, document, true) {
+            state.persisted.lock().entry(key.to_string()).or_default().saved = Some(document.version());
+        }
+    }
+
+    /// Hand a snapshot of a document to the store, which may write it
+    /// later, returning whether it took it. A document written `whole`
+    /// replaces its logged operations, and the log starts over from it.
+    /// A degraded document is left to recovery, which writes it whole.
+    fn queue_snapshot(state: &ServerState, key: &str, document: &Document, whole: bool) -> bool {
+        let mut health = state.health.lock();
+        if health.is_degraded(key) {
+            if whole {
+                health.fail(key, "the document was replaced while degraded", None, Utc::now());
+            }
+            return false;
+        }
+        let queued = if whole { state.store.replace(key, document) } else { state.store.save(key, document) };
+        match queued {
+            Ok(()) => {
+                health.written(key);
+                if whole {
+                    Self::logged_whole(state, key, document);
+                }
+                true
+            }
+            Err(e) => {
+                log::error!("Could not save a snapshot of document {}: {}", key, e);
+                if health.fail(key, &e.to_string(), None, Utc::now()) {
+                    Self::announce_storage(state, key, true);
+                }
+                false
+            }
+        }
+    }
+
+    /// Record that the log of a document written whole starts over from it
+    fn logged_whole(state: &ServerState, key: &str, document: &Document) {
+        let mut persisted = state.persisted.lock();
+        let stored = persisted.entry(key.to_string()).or_default();
+        stored.logged = document.state_vector().clone();
+        stored.repairs = document.repairs();
+    }
+
+    /// Log an operation with the store, or hold it while the document is
+    /// degraded
+    fn append_operation(state: &ServerState, key: &str, operation: &Operation) {
+        let mut health = state.health.lock();
+        if health.hold(key, operation) {
             return;
         }
-        
-        // Spawn a task to handle outgoing messages
-        let send_task = tokio::spawn(async move {
-            while let Some(message) = rx.recv().await {
-                if let Err(e) = ws_sender.send(message).await {
-                    log::error!("Failed to send WebSocket message: {}", e);
-                    break;
+        let operations = std::slice::from_ref(operation);
+        match state.store.append_ops(key, operations) {
+            Ok(()) => health.written(key),
+            Err(e) => {
+                log::error!("Could not store an operation on document {}: {}", key, e);
+                if health.fail(key, &e.to_string(), Some(operations), Utc::now()) {
+                    Self::announce_storage(state, key, true);
                 }
             }
-        });
-        
-        // Spawn a task to handle incoming messages
-        let receive_task = tokio::spawn({
-            let connections = connections.clone();
-            let documents = documents.clone();
-            let clients = clients.clone();
-            let client_id = client_id.clone();
-            
-            async move {
-                while let Some(result) = ws_receiver.next().await {
-                    match result {
-                        Ok(msg) => {
-                            if let Ok(text) = msg.to_str() {
-                                if let Ok(message) = serde_json::from_str::<Message>(text) {
-                                    // Create a new task to handle the message asynchronously
-                                    let connections = connections.clone();
-                                    let documents = documents.clone();
-                                    let clients = clients.clone();
-                                    let client_id = client_id.clone();
-                                    
-                                    tokio::spawn(async move {
-                                        Self::handle_message(
-                                            message,
-                                            &client_id,
-                                            &connections,
-                                            &documents,
-                                            &*clients
-                                        ).await;
-                                    });
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("WebSocket error: {}", e);
-                            break;
-                        }
+        }
+    }
+
+    /// Wait off the runtime until the store's writes are durable. If any
+    /// failed, every document written since the last flush is degraded;
+    /// otherwise returns how many documents the flush recovered.
+    async fn flush_store(state: &ServerState) -> Option<usize> {
+        let written = state.health.lock().take_unflushed();
+        let store = state.store.clone();
+        let error = match tokio::task::spawn_blocking(move || store.flush()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(e) => Some(format!("could not wait for the store: {}", e)),
+        };
+        match error {
+            None => {
+                let recovered = state.health.lock().flushed(&written);
+                for key in &recovered {
+                    Self::announce_storage(state, key, false);
+                }
+                Some(recovered.len())
+            }
+            Some(error) => {
+                log::error!("Could not flush the store: {}", error);
+                let degraded = state.health.lock().flush_failed(written, &error, Utc::now());
+                for key in &degraded {
+                    Self::announce_storage(state, key, true);
+                }
+                None
+            }
+        }
+    }
+
+    /// Write what the store lacks of every degraded document, and confirm
+    /// recent writes, returning how many documents recovered. Documents
+    /// that failed again stay degraded until the next attempt.
+    async fn recover_documents(state: &ServerState) -> usize {
+        if state.health.lock().is_idle() {
+            return 0;
+        }
+        let keys = state.health.lock().degraded_keys();
+        let docs = state.documents.read().await;
+        for key in &keys {
+            // Under the health lock, nothing else is written to the
+            // document before what recovers it
+            let mut health = state.health.lock();
+            let Some(recovery) = health.begin_recovery(key) else {
+                continue;
+            };
+            let (written, held) = match (recovery, docs.get(key)) {
+                (_, None) => (state.store.delete(key), None),
+                (Recovery::Append(operations), Some(_)) => (state.store.append_ops(key, &operations), Some(operations)),
+                (Recovery::Whole, Some(document)) => {
+                    let written = state.store.replace(key, document);
+                    if written.is_ok() {
+                        Self::logged_whole(state, key, document);
                     }
+                    (written, None)
+                }
+            };
+            match written {
+                Ok(()) => health.written(key),
+                Err(e) => {
+                    log::warn!("Could not recover document {}: {}", key, e);
+                    health.fail(key, &e.to_string(), held.as_deref(), Utc::now());
+                }
+            }
+        }
+        drop(docs);
+        Self::flush_store(state).await.unwrap_or(0)
+    }
+
+    /// Warn the clients of a document that the store fails to write it,
+    /// so its recent edits live only in memory, or that it recovered
+    fn announce_storage(state: &ServerState, key: &str, degraded: bool) {
+        if degraded {
+            log::warn!("Document {} is degraded: its edits are kept in memory until the store recovers", key);
+        } else {
+            log::info!("Document {} recovered: the store holds its edits again", key);
+        }
+        let state = state.clone();
+        let key = key.to_string();
+        tokio::spawn(async move {
+            for tenant_id in state.tenant_ids() {
+                let Ok(tenant) = state.tenant(&tenant_id) else {
+                    continue;
+                };
+                if let Some(document_id) = tenant.unscoped(&key) {
+                    let status = Self::storage_status(document_id, degraded);
+                    state.clients.broadcast_document(tenant.id(), document_id, &status, None).await;
                 }
             }
         });
-        
-        // Wait for either task to complete
-        tokio::select! {
-            _ = send_task => {}
-            _ = receive_task => {}
+    }
+
+    /// The `status` message telling clients whether a document is degraded
+    fn storage_status(document_id: &str, degraded: bool) -> Message {
+        let payload = if degraded {
+            json!({
+                "status": "warning",
+                "code": "storage_degraded",
+                "document_id": document_id,
+                "message": "Edits are kept in memory until storage recovers",
+            })
+        } else {
+            json!({ "status": "ok", "code": "storage_recovered", "document_id": document_id })
+        };
+        Message::new(MessageType::Status, "server".to_string(), payload)
+    }
+
+    /// Store a document's password hash, or its removal, unless the
+    /// document is temporary
+    pub(crate) fn store_password(state: &ServerState, key: &str, hash: Option<&str>) {
+        if state.clients.is_temporary(key) {
+            return;
         }
-        
-        // Clean up on disconnect
-        log::info!("Client disconnected: {}", client_id);
-        clients.remove_client(&client_id).await;
-        if let Err(e) = connections.write().await.disconnect_client(&client_id).await {
-            log::error!("Failed to remove connection: {}", e);
+        if let Err(e) = state.store.save_password(key, hash) {
+            log::error!("Could not store the password of document {}: {}", key, e);
         }
     }
-    
-    /// Handle incoming WebSocket messages
-    async fn handle_message(
-        message: Message,
-        client_id: &str,
-        _connections: &Arc<RwLock<ConnectionManager>>,
-        documents: &Arc<RwLock<HashMap<String, Document>>>,
-        clients: &ClientManager,
-    ) {
-        match message.message_type() {
-            MessageType::Operation => {
-                if let Ok(op_msg) = serde_json::from_value::<OperationMessage>(message.payload().clone()) {
-                    // Handle document operation
-                    let mut docs = documents.write().await;
-                    if let Some(doc) = docs.get_mut(&op_msg.document_id) {
-                        // Apply the operation to the document
-                        if let Err(e) = doc.apply_operation(op_msg.operation.clone()) {
-                            log::error!("Failed to apply operation: {}", e);
-                        } else {
-                            log::info!("Applied operation to document {}", op_msg.document_id);
-                        }
-                    } else {
-                        log::warn!("Document not found: {}", op_msg.document_id);
-                    }
-                }
 
-                // Broadcast the operation to other clients
-                clients.broadcast(&message, Some(client_id)).await;
+    /// Remove a document from the store
+    fn delete_stored(state: &ServerState, key: &str) {
+        let mut health = state.health.lock();
+        state.persisted.lock().remove(key);
+        match state.store.delete(key) {
+            Ok(()) => health.written(key),
+            Err(e) => {
+                log::error!("Could not remove stored document {}: {}", key, e);
+                health.fail(key, &e.to_string(), None, Utc::now());
             }
-            _ => {
-                log::debug!("Unhandled message type: {:?}", message.message_type());
+        }
+    }
+
+    pub(crate) async fn memory_overview(state: &ServerState, limit: usize) -> MemoryOverview {
+        let (current, mut documents) = Self::measure_memory(state).await;
+        documents.sort_by(|a, b| {
+            b.memory.total_bytes().cmp(&a.memory.total_bytes())
+                .then_with(|| (&a.tenant_id, &a.document_id).cmp(&(&b.tenant_id, &b.document_id)))
+        });
+        documents.truncate(limit);
+        MemoryOverview { current, documents, trend: state.metrics.memory.samples() }
+    }
+
+    async fn record_memory_sample(state: &ServerState) -> MemorySample {
+        let (sample, _) = Self::measure_memory(state).await;
+        state.metrics.memory.record(sample.clone());
+        sample
+    }
+
+    /// Get the memory of every document, and of all of them together
+    async fn measure_memory(state: &ServerState) -> (MemorySample, Vec<DocumentMemory>) {
+        let documents: Vec<DocumentMemory> = state.documents.read().await.iter()
+            .filter_map(|(key, document)| {
+                let (tenant_id, document_id) = key.split_once('/')?;
+                Some(DocumentMemory {
+                    tenant_id: tenant_id.to_string(),
+                    document_id: document_id.to_string(),
+                    memory: document.memory_report(),
+                })
+            })
+            .collect();
+        let mut sample = MemorySample { at: Utc::now(), documents: documents.len(), memory: Default::default() };
+        for document in &documents {
+            sample.memory += document.memory;
+        }
+        (sample, documents)
+    }
+
+    /// Get a snapshot of the server metrics
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.state.metrics.snapshot()
+    }
+
+    /// Get whether the server is shedding work, and its operation latency
+    pub fn overload(&self) -> OverloadStatus {
+        self.state.overload.status()
+    }
+
+    /// Get a copy of a tenant's document, by ID or slug
+    pub async fn document(&self, tenant_id: &str, document_id: &str) -> Result<Option<Document>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(Self::document_copy(&self.state, &tenant, document_id).await)
+    }
+
+    pub(crate) async fn document_copy(state: &ServerState, tenant: &Tenant, document_id: &str) -> Option<Document> {
+        Self::with_document(state, tenant, document_id, Document::clone).await
+    }
+
+    /// Read a document, by ID or slug, without copying it
+    pub(crate) async fn with_document<T>(
+        state: &ServerState,
+        tenant: &Tenant,
+        document_id: &str,
+        read: impl FnOnce(&Document) -> T,
+    ) -> Option<T> {
+        let document_id = tenant.aliases().resolve(document_id);
+        state.documents.read().await.get(&tenant.scoped(&document_id)).map(read)
+    }
+
+    /// Read a document, by ID or slug, once it has reached `min_version`.
+    /// Waits up to `READ_WAIT_TIMEOUT` for writes still in flight, so a
+    /// client reading after its own acknowledged write sees that write.
+    pub(crate) async fn read_at_least<T>(
+        state: &ServerState,
+        tenant: &Tenant,
+        document_id: &str,
+        min_version: Option<u64>,
+        read: impl Fn(&Document) -> T,
+    ) -> Result<T, DocumentError> {
+        // Subscribe before the first look so no write falls in between
+        let mut operations = state.operations.subscribe();
+        let deadline = tokio::time::Instant::now() + READ_WAIT_TIMEOUT;
+        loop {
+            let outcome = Self::with_document(state, tenant, document_id, |doc| match min_version {
+                Some(required) if doc.version() < required => Err(doc.version()),
+                _ => Ok(read(doc)),
+            })
+            .await;
+            let version = match outcome {
+                None => return Err(DocumentError::NotFound(document_id.to_string())),
+                Some(Ok(value)) => return Ok(value),
+                Some(Err(version)) => version,
+            };
+            match tokio::time::timeout_at(deadline, operations.recv()).await {
+                Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+                _ => {
+                    return Err(DocumentError::Stale {
+                        document_id: document_id.to_string(),
+                        version,
+                        required: min_version.unwrap_or_default(),
+                    });
+                }
             }
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    
-    
-    
-    #[tokio::test]
-    async fn test_server_initialization() {
-        // Create a test server configuration
-        let config = ServerConfig {
-            port: 0, // Let OS assign a port
-            ..Default::default()
-        };
-        
-        // Create a new server instance
-        let server = EditorServer::new(config);
-        
-        // Test connection statistics
-        let stats = server.connections.read().await.get_statistics().await;
-        assert_eq!(stats.total_clients, 0);
-        
-        // Test documents map is empty
-        assert!(server.documents.read().await.is_empty());
+    /// Change a document, by ID or slug, in place
+    pub(crate) async fn with_document_mut<T>(
+        state: &ServerState,
+        tenant: &Tenant,
+        document_id: &str,
+        write: impl FnOnce(&mut Document) -> T,
+    ) -> Option<T> {
+        let document_id = tenant.aliases().resolve(document_id);
+        state.documents.write().await.get_mut(&tenant.scoped(&document_id)).map(write)
     }
-    
+
+    /// Get the IDs of a tenant's documents, sorted
+    pub(crate) async fn document_ids(state: &ServerState, tenant: &Tenant) -> Vec<String> {
+        let mut ids: Vec<String> = state.documents.read().await
+            .keys()
+            .filter_map(|key| tenant.unscoped(key).map(str::to_string))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Describe a document for policy evaluation
+    async fn document_info(state: &ServerState, tenant: &Tenant, document_id: &str) -> DocumentInfo {
+        let docs = state.documents.read().await;
+        let doc = docs.get(&tenant.scoped(document_id));
+        DocumentInfo {
+            id: document_id.to_string(),
+            exists: doc.is_some(),
+            length: doc.map_or(0, Document::len),
+            version: doc.map_or(0, Document::version),
+        }
+    }
+
+    /// Mirror a document of another CoEdit server into a local document,
+    /// in both directions, until the returned handle is stopped
+    pub fn sync_document(&self, link: SyncLink) -> Result<SyncHandle, TenantError> {
+        let tenant = self.state.tenants.get(&link.local_tenant)?;
+        Ok(federation::spawn(self.state.clone(), tenant, link))
+    }
+
+    /// Apply an operation received from a sync link to a local document,
+    /// creating the document if needed, and relay it to local clients.
+    /// Returns whether the operation was new.
+    pub(crate) async fn merge_remote(
+        state: &ServerState,
+        tenant: &Tenant,
+        document_id: &str,
+        operation: Operation,
+        source: OperationSource,
+        origin: &str,
+    ) -> Result<bool, DocumentError> {
+        // Remote operations are not subject to the local quota: rejecting
+        // them would leave the replicas diverged for good
+        let mut docs = state.documents.write().await;
+        let created = !docs.contains_key(&tenant.scoped(document_id));
+        let doc = Self::document_for_operation(&mut docs, tenant, document_id, DocumentPolicy::AutoCreate)?;
+        let Some(applied) = doc.merge_operation(operation.clone())? else {
+            return Ok(false);
+        };
+        state.tails.record(&tenant.scoped(document_id), applied.version, &operation);
+        drop(docs);
+        Self::record_change_from(state, tenant, document_id, created, applied.version, source);
+
+        match serde_json::to_value(OperationMessage::new(operation.clone(), document_id.to_string()).with_source(source)) {
+            Ok(payload) => {
+                let relay = Message::new(MessageType::Operation, origin.to_string(), payload);
+                Self::relay(state, tenant, document_id, &relay, None).await;
+            }
+            Err(e) => log::error!("Failed to serialize operation: {}", e),
+        }
+        Self::publish_operation(state, tenant, OperationEvent {
+            tenant_id: tenant.id().to_string(),
+            document_id: document_id.to_string(),
+            operation,
+            origin: origin.to_string(),
+            source,
+        });
+        Ok(true)
+    }
+
+    /// Count an operation applied to a local document and announce it to
+    /// sync links and standbys
+    fn publish_operation(state: &ServerState, tenant: &Tenant, event: OperationEvent) {
+        state.metrics.operations.increment(tenant.id(), &event.document_id);
+        let key = tenant.scoped(&event.document_id);
+        if !state.clients.is_temporary(&key) {
+            Self::append_operation(state, &key, &event.operation);
+            state.persisted.lock().entry(key).or_default().logged.observe(&event.operation);
+            let change = ReplicatedChange::Operation { operation: event.operation.clone(), source: event.source };
+            state.replication.log().record(&event.tenant_id, &event.document_id, change);
+        }
+        let _ = state.operations.send(event);
+    }
+
+    /// Store a document replicated from the primary. Snapshots replace the
+    /// document; copies from the log only create it.
+    pub(crate) async fn restore_document(state: &ServerState, tenant: &Tenant, document_id: &str, document: Document, replace: bool) {
+        let version = document.version();
+        let mut docs = state.documents.write().await;
+        let created = !docs.contains_key(&tenant.scoped(document_id));
+        if !created && !replace {
+            return;
+        }
+        docs.insert(tenant.scoped(document_id), document.clone());
+        state.tails.forget(&tenant.scoped(document_id));
+        drop(docs);
+        Self::record_change(state, tenant, document_id, created, version);
+        Self::store_document(state, &tenant.scoped(document_id), &document);
+        state.replication.log().record(tenant.id(), document_id, ReplicatedChange::Document { document: Box::new(document) });
+    }
+
+    /// Copy every document but temporary ones, with the position of the
+    /// replication log they hold at least
+    pub(crate) async fn replication_snapshot(state: &ServerState) -> ReplicationSnapshot {
+        // Changes are logged after they are applied, so the documents hold
+        // every change up to a position read first
+        let position = state.replication.log().head();
+        let docs = state.documents.read().await;
+        let mut documents = Vec::new();
+        for tenant_id in state.tenant_ids() {
+            let Ok(tenant) = state.tenant(&tenant_id) else {
+                continue;
+            };
+            for (key, document) in docs.iter() {
+                match tenant.unscoped(key) {
+                    Some(document_id) if !state.clients.is_temporary(key) => documents.push(SnapshotDocument {
+                        tenant_id: tenant_id.clone(),
+                        document_id: document_id.to_string(),
+                        document: document.clone(),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        ReplicationSnapshot { position, documents }
+    }
+
+    /// Record that a document was created or changed, for `GET /events`
+    fn record_change(state: &ServerState, tenant: &Tenant, document_id: &str, created: bool, version: u64) {
+        Self::record_change_from(state, tenant, document_id, created, version, OperationSource::User);
+    }
+
+    /// Record a change by a kind of actor. Imports are one change to the
+    /// activity feed, not one per character.
+    fn record_change_from(state: &ServerState, tenant: &Tenant, document_id: &str, created: bool, version: u64, source: OperationSource) {
+        // Temporary documents are never announced
+        if state.clients.is_temporary(&tenant.scoped(document_id)) {
+            return;
+        }
+        let kind = if created { DocumentEventKind::Created } else { DocumentEventKind::Changed };
+        state.events.record(tenant.id(), document_id, kind, version);
+        if created {
+            state.activity.created(tenant.id(), document_id, version, Utc::now());
+        } else if source != OperationSource::Import {
+            state.activity.changed(tenant.id(), document_id, version, Utc::now());
+        }
+        state.previews.touch(tenant.id(), document_id, version);
+        tenant.links().touch(document_id);
+    }
+
+    /// Get previews of a tenant's recently active visible documents, most
+    /// recent first, taking the previews that changes made stale
+    pub(crate) async fn recent_previews(
+        state: &ServerState,
+        tenant: &Tenant,
+        limit: usize,
+        visible: impl Fn(&str) -> bool,
+    ) -> Vec<DocumentPreview> {
+        for document_id in state.previews.stale(tenant.id(), limit, &visible) {
+            let preview = Self::with_document(state, tenant, &document_id, |doc| (doc.version(), doc.prefix(PREVIEW_LENGTH))).await;
+            if let Some((version, preview)) = preview {
+                state.previews.fill(tenant.id(), &document_id, version, preview);
+            }
+        }
+        state.previews.recent(tenant.id(), limit, visible)
+    }
+
+    /// Get a document's links and backlinks, by ID or slug, reading the
+    /// links of documents changed since the last lookup
+    pub(crate) async fn collect_links(state: &ServerState, tenant: &Tenant, document_id: &str) -> DocumentLinks {
+        for changed in tenant.links().stale() {
+            let found = Self::with_document(state, tenant, &changed, |doc| links(&doc.content())).await;
+            let targets = found
+                .unwrap_or_default()
+                .into_iter()
+                .map(|link| tenant.aliases().resolve(&link.target))
+                .collect();
+            tenant.links().update(&changed, targets);
+        }
+        tenant.links().links(&tenant.aliases().resolve(document_id))
+    }
+
+    /// Subscribe to operations applied to local documents
+    pub(crate) fn operation_events(state: &ServerState) -> broadcast::Receiver<OperationEvent> {
+        state.operations.subscribe()
+    }
+
+    /// Build the usage report the server would send now. Nothing is sent
+    /// unless telemetry is enabled.
+    pub async fn usage_report(&self) -> UsageReport {
+        Self::build_usage_report(&self.state).await
+    }
+
+    async fn build_usage_report(state: &ServerState) -> UsageReport {
+        let documents = state.documents.read().await.len();
+        UsageReport::new(documents, state.clients.peak_clients())
+    }
+
+    /// Post a usage report every interval until the server stops
+    fn spawn_usage_reports(state: ServerState, reporter: UsageReporter, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately; report after a full period
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if state.overload.is_shedding() {
+                    log::debug!("Skipping usage report while overloaded");
+                    continue;
+                }
+                let report = Self::build_usage_report(&state).await;
+                state.clients.reset_peak_clients();
+                log::debug!("Sending usage report: {:?}", report);
+                if let Err(e) = reporter.send(&report).await {
+                    log::warn!("Failed to send usage report: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Sample the memory of documents every interval until the server
+    /// stops
+    fn spawn_memory_samples(state: ServerState, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if state.overload.is_shedding() {
+                    log::debug!("Skipping memory sample while overloaded");
+                    continue;
+                }
+                let sample = Self::record_memory_sample(&state).await;
+                log::debug!("Documents hold about {} bytes", sample.memory.total_bytes());
+            }
+        })
+    }
+
+    /// Save snapshots of changed documents every interval until the
+    /// server stops
+    fn spawn_snapshots(state: ServerState, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let saved = Self::snapshot_documents(&state).await;
+                if saved > 0 {
+                    log::debug!("Saved snapshots of {} documents", saved);
+                }
+            }
+        })
+    }
+
+    /// Retry degraded documents and confirm recent writes every interval
+    /// until the server stops
+    fn spawn_storage_recovery(state: ServerState, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let recovered = Self::recover_documents(&state).await;
+                if recovered > 0 {
+                    log::info!("Recovered {} degraded documents", recovered);
+                }
+            }
+        })
+    }
+
+    /// Remove idle clients from the presence of documents until the server
+    /// stops, checking twice per idle timeout
+    fn spawn_presence_expiry(state: ServerState, idle_timeout: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(idle_timeout / 2);
+            loop {
+                ticker.tick().await;
+                state.clients.expire_presence(idle_timeout).await;
+            }
+        })
+    }
+
+    /// Check queued words in batches until the server stops
+    fn spawn_moderation(
+        state: ServerState,
+        filter: Arc<dyn ContentFilter>,
+        config: ModerationConfig,
+        mut regions: mpsc::Receiver<TextRegion>,
+    ) -> JoinHandle<()> {
+        let webhook = config.webhook_url.as_deref().and_then(|url| {
+            WebhookNotifier::new(url)
+                .map_err(|e| log::error!("Moderation webhook disabled: {}", e))
+                .ok()
+        });
+
+        tokio::spawn(async move {
+            while let Some(first) = regions.recv().await {
+                // Give the batch a moment to fill up before checking it
+                let mut batch = vec![first];
+                let deadline = tokio::time::sleep(config.batch_delay);
+                tokio::pin!(deadline);
+                while batch.len() < config.batch_size {
+                    tokio::select! {
+                        _ = &mut deadline => break,
+                        region = regions.recv() => match region {
+                            Some(region) => batch.push(region),
+                            None => break,
+                        },
+                    }
+                }
+
+                let texts: Vec<String> = batch.iter().map(TextRegion::text).collect();
+                match filter.check(&texts).await {
+                    Ok(results) => {
+                        for (region, terms) in batch.into_iter().zip(results) {
+                            if !terms.is_empty() {
+                                Self::moderate(&state, &config, webhook.as_ref(), region, terms).await;
+                            }
+                        }
+                    }
+                    Err(e) => log::warn!("Content filter failed, {} words were not checked: {}", texts.len(), e),
+                }
+            }
+        })
+    }
+
+    /// Handle a word the content filter flagged
+    async fn moderate(
+        state: &ServerState,
+        config: &ModerationConfig,
+        webhook: Option<&WebhookNotifier>,
+        region: TextRegion,
+        terms: Vec<String>,
+    ) {
+        log::info!(
+            "Content filter flagged a word of client {} in document {} ({:?})",
+            region.client_id, region.document_id, config.action,
+        );
+        if let Some(webhook) = webhook {
+            let data = json!({
+                "tenant_id": &region.tenant_id,
+                "client_id": &region.client_id,
+                "terms": &terms,
+                "action": config.action,
+            });
+            webhook.notify(WebhookEvent::new("moderation.flagged", region.document_id.clone(), data));
+        }
+
+        let replacement = match config.action {
+            ModerationAction::Flag => return,
+            ModerationAction::Redact => Some(config.replacement),
+            ModerationAction::Reject => None,
+        };
+        let Ok(tenant) = state.tenants.get(&region.tenant_id) else {
+            return;
+        };
+
+        // Replace or remove the characters of the word still in the document
+        let mut applied = Vec::new();
+        let version = {
+            let mut docs = state.documents.write().await;
+            let Some(doc) = docs.get_mut(&tenant.scoped(&region.document_id)) else {
+                return;
+            };
+            for (position, _) in &region.characters {
+                if doc.character_at(position).is_none() {
+                    continue;
+                }
+                let mut operations = vec![Operation::delete(MODERATION_CLIENT_ID.to_string(), position.clone())];
+                if let Some(replacement) = replacement {
+                    let masked = doc.position_after(position);
+                    operations.push(Operation::insert(MODERATION_CLIENT_ID.to_string(), replacement, masked));
+                }
+                for operation in operations {
+                    match doc.merge_operation(operation.clone()) {
+                        Ok(Some(done)) => {
+                            state.tails.record(&tenant.scoped(&region.document_id), done.version, &operation);
+                            applied.push(operation);
+                        }
+                        Ok(None) => {}
+                        Err(e) => log::error!("Failed to apply moderation operation: {}", e),
+                    }
+                }
+            }
+            doc.version()
+        };
+        if !applied.is_empty() {
+            Self::record_change(state, &tenant, &region.document_id, false, version);
+        }
+
+        for operation in applied {
+            let op_msg = OperationMessage::new(operation.clone(), region.document_id.clone()).with_source(OperationSource::Server);
+            match serde_json::to_value(op_msg) {
+                Ok(payload) => {
+                    let relay = Message::new(MessageType::Operation, MODERATION_CLIENT_ID.to_string(), payload);
+                    Self::relay(state, &tenant, &region.document_id, &relay, None).await;
+                }
+                Err(e) => log::error!("Failed to serialize operation: {}", e),
+            }
+            Self::publish_operation(state, &tenant, OperationEvent {
+                tenant_id: tenant.id().to_string(),
+                document_id: region.document_id.clone(),
+                operation,
+                origin: MODERATION_CLIENT_ID.to_string(),
+                source: OperationSource::Server,
+            });
+        }
+
+        if config.action == ModerationAction::Reject {
+            let error = Message::new(
+                MessageType::Error,
+                region.client_id.clone(),
+                json!(format!("Content in document {} was rejected by moderation", region.document_id)),
+            );
+            state.clients.send_to(&region.client_id, &error).await;
+        }
+    }
+
+    /// Get concurrency statistics for every document of a tenant
+    pub async fn concurrency_stats(&self, tenant_id: &str) -> Result<HashMap<String, ConcurrencyStats>, TenantError> {
+        let tenant = self.state.tenants.get(tenant_id)?;
+        Ok(self.state.documents.read().await
+            .iter()
+            .filter_map(|(key, doc)| {
+                tenant.unscoped(key).map(|id| (id.to_string(), doc.concurrency_stats().clone()))
+            })
+            .collect())
+    }
+
+    /// Start the WebSocket server
+    pub async fn run(&self) -> Result<()> {
+        // Refuse to start with a configuration that would fail once serving
+        let report = doctor::diagnose(&self.config);
+        for check in report.at(CheckLevel::Warning) {
+            log::warn!("Self-check {}: {}", check.name, check.message);
+        }
+        if report.has_errors() {
+            let errors: Vec<String> = report.at(CheckLevel::Error)
+                .map(|check| format!("{}: {}", check.name, check.message))
+                .collect();
+            anyhow::bail!("Self-check failed:\n{}", errors.join("\n"));
+        }
+
+        self.seed_documents().await;
+        let routes = self.routes();
+
+        // Start the server
+        let addr = std::net::SocketAddr::new(
+            self.config.host.parse()?,
+            self.config.port,
+        );
+        let background = self.start_background_tasks();
+
+        log::info!("Starting WebSocket server on ws://{}", addr);
+        warp::serve(routes)
+            .run(addr)
+            .await;
+
+        for task in background {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    /// Build the filter serving every route of the server: the WebSocket
+    /// routes, exports, events, the admin API and frontend assets. Mount it
+    /// in another warp application, under a prefix if needed:
+    ///
+    /// ```no_run
+    /// # use crdt_editor_backend::websocket::{EditorServer, ServerConfig};
+    /// # use warp::Filter;
+    /// # async fn serve() {
+    /// let server = EditorServer::new(ServerConfig::default());
+    /// let _tasks = server.start_background_tasks();
+    /// let app = warp::path("coedit").and(server.routes());
+    /// warp::serve(app).run(([127, 0, 0, 1], 3000)).await;
+    /// # }
+    /// ```
+    pub fn routes(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone + Send + Sync + 'static {
+        let state = self.state.clone();
+
+        // WebSocket route: `/ws` for the default tenant, `/t/:tenant/ws` for
+        // others, each optionally followed by a document to join
+        let ws_route = warp::path!("ws")
+            .map(|| (DEFAULT_TENANT.to_string(), None))
+            .or(warp::path!("ws" / String).map(|document_id| (DEFAULT_TENANT.to_string(), Some(document_id))))
+            .unify()
+            .or(warp::path!("t" / String / "ws").map(|tenant_id| (tenant_id, None)))
+            .unify()
+            .or(warp::path!("t" / String / "ws" / String).map(|tenant_id, document_id| (tenant_id, Some(document_id))))
+            .unify()
+            .and(warp::ws())
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::header::optional::<String>("authorization"))
+            .map(move |(tenant_id, document_id): (String, Option<String>), ws: warp::ws::Ws, query: HashMap<String, String>, authorization: Option<String>| {
+                // The key comes from the query, or from a bearer token for
+                // clients that can set headers
+                let key = query.get("key").map(String::as_str)
+                    .or_else(|| authorization.as_deref().and_then(|value| value.strip_prefix("Bearer ")));
+                let session = query.get("session").map(String::as_str);
+                Self::upgrade(ws, state.clone(), &tenant_id, key, session, document_id)
+            });
+        ws_route
+            .or(export::routes(self.state.clone()))
+            .or(import::routes(self.state.clone()))
+            .or(events::routes(self.state.clone()))
+            .or(previews::routes(self.state.clone()))
+            .or(activity::routes(self.state.clone()))
+            .or(backlinks::routes(self.state.clone()))
+            .or(admin::routes(self.state.clone(), &self.config.admin))
+            .or(replication::routes(self.state.clone(), &self.config.admin))
+            .or(assets::routes(&self.config.assets))
+    }
+
+    /// Start the tasks serving relies on besides the routes: memory
+    /// samples, snapshots, key rotation, and usage reports, moderation and
+    /// following the primary, when configured. `run` starts them itself; call this
+    /// once when mounting `routes` elsewhere, and abort the returned tasks
+    /// on shutdown. Moderation starts only on the first call.
+    pub fn start_background_tasks(&self) -> Vec<JoinHandle<()>> {
+        let usage_reports = UsageReporter::from_config(&self.config.telemetry).map(|reporter| {
+            log::info!("Anonymous usage reporting is enabled");
+            Self::spawn_usage_reports(self.state.clone(), reporter, self.config.telemetry.interval)
+        });
+
+        let moderation = self.config.moderation.filter.clone().zip(self.moderation_regions.lock().take()).map(
+            |(filter, regions)| Self::spawn_moderation(self.state.clone(), filter, self.config.moderation.clone(), regions),
+        );
+        let standby = self.config.standby.clone()
+            .filter(|_| self.state.replication.is_standby())
+            .map(|standby| {
+                log::info!("Following {} as a warm standby", standby.primary);
+                replication::spawn_follower(self.state.clone(), standby)
+            });
+        // A zero interval is reported by the self-check
+        let memory = (!self.config.memory.interval.is_zero())
+            .then(|| Self::spawn_memory_samples(self.state.clone(), self.config.memory.interval));
+        let snapshots = (!self.config.storage.snapshot_interval.is_zero())
+            .then(|| Self::spawn_snapshots(self.state.clone(), self.config.storage.snapshot_interval));
+        let presence = (!self.config.presence.idle_timeout.is_zero())
+            .then(|| Self::spawn_presence_expiry(self.state.clone(), self.config.presence.idle_timeout));
+        let recovery = (!self.config.storage.retry_interval.is_zero())
+            .then(|| Self::spawn_storage_recovery(self.state.clone(), self.config.storage.retry_interval));
+        let key_rotation = (!self.config.storage.key_rotation.is_zero())
+            .then(|| spawn_key_rotation(self.config.storage.store.clone(), self.config.storage.key_rotation));
+        usage_reports
+            .into_iter()
+            .chain(moderation)
+            .chain(standby)
+            .chain(memory)
+            .chain(snapshots)
+            .chain(recovery)
+            .chain(presence)
+            .chain(key_rotation)
+            .collect()
+    }
+
+    /// Resolve the tenant of an upgrade request and accept or refuse it. A
+    /// session token stands in for the access key, and resumes its session.
+    fn upgrade(
+        ws: warp::ws::Ws,
+        state: ServerState,
+        tenant_id: &str,
+        key: Option<&str>,
+        session: Option<&str>,
+        document_id: Option<String>,
+    ) -> Box<dyn warp::Reply> {
+        let tenant = match state.tenants.get(tenant_id) {
+            Ok(tenant) => tenant,
+            Err(e) => {
+                return Box::new(warp::reply::with_status(e.to_string(), StatusCode::NOT_FOUND));
+            }
+        };
+        let resume = match session {
+            Some(token) => match Self::open_session(&state, &tenant, token) {
+                Ok(client_id) => Some(client_id),
+                Err(e) => {
+                    log::warn!("Rejected session token: {}", e);
+                    return Box::new(warp::reply::with_status(e.to_string(), StatusCode::UNAUTHORIZED));
+                }
+            },
+            None => None,
+        };
+        if let (None, Err(e)) = (&resume, tenant.authorize(key)) {
+            log::warn!("Rejected connection: {}", e);
+            return Box::new(warp::reply::with_status(e.to_string(), StatusCode::FORBIDDEN));
+        }
+        if state.replication.is_standby() {
+            return Box::new(warp::reply::with_status(
+                "This server is a standby; connect to the primary".to_string(),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ));
+        }
+
+        Box::new(ws.on_upgrade(move |socket| Self::handle_connection(socket, state, tenant, resume, document_id)))
+    }
+
+    /// Open a session token presented to a tenant, returning the client ID
+    /// of the session it resumes
+    fn open_session(state: &ServerState, tenant: &Tenant, token: &str) -> Result<String, TokenError> {
+        let claims: SessionClaims = state.tokens.sealer.open(TokenKind::Session, token, Utc::now())?;
+        if claims.tenant_id != tenant.id() {
+            return Err(TokenError::Invalid);
+        }
+        Ok(claims.client_id)
+    }
+
+    /// Seal a token resuming a client's session, to send with its welcome
+    fn seal_session(state: &ServerState, tenant: &Tenant, client_id: &str) -> Option<(String, DateTime<Utc>)> {
+        let expires = Utc::now() + chrono::Duration::from_std(state.tokens.session_ttl).unwrap_or(chrono::Duration::MAX);
+        let claims = SessionClaims { tenant_id: tenant.id().to_string(), client_id: client_id.to_string() };
+        match state.tokens.sealer.seal(TokenKind::Session, &claims, expires) {
+            Ok(token) => Some((token, expires)),
+            Err(e) => {
+                log::error!("Could not seal a session token for client {}: {}", client_id, e);
+                None
+            }
+        }
+    }
+
+    /// Handle a new connection, joining the document its URL named. A
+    /// connection with a session token takes over the client ID of its
+    /// session once that session's connection is closed, and gets a new
+    /// one otherwise.
+    async fn handle_connection<T: Transport>(
+        transport: T,
+        state: ServerState,
+        tenant: Arc<Tenant>,
+        resume: Option<String>,
+        document_id: Option<String>,
+    ) {
+        // Claim the session to resume, or generate a unique client ID
+        let resumed = match resume {
+            Some(client_id) => {
+                let mut manager = state.connections.write().await;
+                let closed = manager.get_client_status(&client_id).await == Some(ConnectionStatus::Disconnected);
+                (closed && manager.recover_connection(&client_id).await.is_ok()).then_some(client_id)
+            }
+            None => None,
+        };
+        let client_id = resumed.clone().unwrap_or_else(|| state.ids.client_id());
+        
+        // Split the connection into sender and receiver
+        let (mut ws_sender, mut ws_receiver) = transport.split();
+        
+        // Create a channel for sending messages to this client
+        let (tx, mut rx) = mpsc::channel(32);
+        
+        // Shared shutdown signal: whichever pump ends first stops the other,
+        // in-flight handlers stop streaming to this client, and the client
+        // manager can close the connection by evicting the client
+        let shutdown = CancellationToken::new();
+
+        // Add client to client manager before registering with connection manager
+        state.clients.add_client(client_id.clone(), tenant.id().to_string(), tx.clone(), shutdown.clone()).await;
+        
+        // Add the client to the connection manager, unless it resumes a
+        // session the manager already knows
+        if resumed.is_none() {
+            let mut manager = state.connections.write().await;
+            if let Err(e) = manager.register_client(client_id.clone()).await {
+                log::error!("Failed to register client: {}", e);
+                state.clients.remove_client(&client_id).await;
+                return;
+            }
+        }
+        
+        log::info!(
+            "Client {}: {} (tenant {})",
+            if resumed.is_some() { "resumed" } else { "connected" },
+            client_id,
+            tenant.id(),
+        );
+        
+        // Send welcome message, with a token resuming the session later
+        let mut welcome = json!({
+            "status": "connected",
+            "client_id": &client_id,
+            "tenant_id": tenant.id(),
+            "resumed": resumed.is_some(),
+            "features": SUPPORTED_FEATURES,
+            "deprecations": state.deprecations.as_slice(),
+        });
+        if let Some((token, expires)) = Self::seal_session(&state, &tenant, &client_id) {
+            welcome["session_token"] = json!(token);
+            welcome["session_expires_at"] = json!(expires);
+        }
+        let welcome_msg = Message::new(MessageType::Status, client_id.clone(), welcome);
+        
+        let welcome_msg = state.clients.encode(&welcome_msg).unwrap_or_else(|| Frame::new(Arc::from("")));
+        if let Err(e) = tx.send(welcome_msg).await {
+            log::error!("Failed to send welcome message: {}", e);
+            state.clients.remove_client(&client_id).await;
+            return;
+        }
+        
+        // Spawn a task to handle outgoing messages
+        let send_task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            let metrics = state.metrics.clone();
+
+            async move {
+                loop {
+                    let frame = tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        frame = rx.recv() => match frame {
+                            Some(frame) => frame,
+                            None => break,
+                        },
+                    };
+                    // Cursors and presence queued behind a slow connection
+                    // for too long are no use once they arrive
+                    if frame.is_stale(std::time::Instant::now()) {
+                        metrics.stale_messages.increment();
+                        continue;
+                    }
+                    if let Err(e) = ws_sender.send(frame.text).await {
+                        log::error!("Failed to send message: {}", e);
+                        break;
+                    }
+                }
+                shutdown.cancel();
+                let _ = ws_sender.close().await;
+            }
+        });
+        
+        // Spawn a task to handle incoming messages
+        let connection_tenant = tenant.clone();
+        let receive_task = tokio::spawn({
+            let state = state.clone();
+            let client_id = client_id.clone();
+            let shutdown = shutdown.clone();
+            
+            async move {
+                if let Some(document_id) = document_id {
+                    Self::join_from_path(&document_id, &client_id, &tenant, &state, &shutdown).await;
+                }
+                loop {
+                    let result = tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        result = ws_receiver.next() => match result {
+                            Some(result) => result,
+                            None => break,
+                        },
+                    };
+                    match result {
+                        Ok(text) => {
+                            let decoded = Message::decode(&text);
+                            let document_id = decoded.as_ref().ok().and_then(document_of);
+                            state.metrics.bandwidth.record(tenant.id(), &client_id, document_id, Direction::In, text.len());
+                            // Read nothing more from a client over its cap, or
+                            // its document's, until it is back under
+                            let delay = state.metrics.bandwidth.throttle_delay(tenant.id(), &client_id, document_id);
+                            if !delay.is_zero() {
+                                log::debug!("Throttling client {} for {:?}", client_id, delay);
+                                tokio::select! {
+                                    _ = shutdown.cancelled() => break,
+                                    _ = tokio::time::sleep(delay) => {}
+                                }
+                            }
+                            match decoded {
+                                Ok(message) => {
+                                    log::debug!(
+                                        "Received message from {}: {}",
+                                        client_id,
+                                        state.redactor.redact_message(&message)
+                                    );
+
+                                    // Handle messages one at a time so a client's operations
+                                    // apply in the order it sent them; other connections
+                                    // are processed by their own tasks in parallel
+                                    let span = tracing::info_span!(
+                                        "message",
+                                        client_id = %client_id,
+                                        request_id = message.request_id().unwrap_or_default(),
+                                        message_type = ?message.message_type(),
+                                    );
+                                    
+                                    Self::handle_message(message, &client_id, &tenant, &state, &shutdown)
+                                        .instrument(span)
+                                        .await;
+                                    state.clients.touch_presence(&client_id);
+                                }
+                                Err(e) => {
+                                    log::warn!(
+                                        "Invalid message from {}: {} ({})",
+                                        client_id,
+                                        e,
+                                        state.redactor.redact_raw(&text)
+                                    );
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Connection error: {}", e);
+                            break;
+                        }
+                    }
+                }
+                shutdown.cancel();
+            }
+        });
+        
+        // Both pumps stop as soon as either one ends
+        let _ = tokio::join!(send_task, receive_task);
+        
+        // Clean up on disconnect
+        log::info!("Client disconnected: {}", client_id);
+        let documents = state.clients.documents_of(&client_id);
+        state.clients.remove_client(&client_id).await;
+        state.clients.leave_presence(&client_id).await;
+        Self::release_gc_barriers(&state, &connection_tenant, &client_id, &documents).await;
+        Self::destroy_temporary_documents(&state, &connection_tenant, &client_id).await;
+        state.skew.forget(connection_tenant.id(), &client_id);
+        state.cursors.lock().forget_client(&client_id);
+        state.metrics.abuse.forget(connection_tenant.id(), &client_id, std::time::Instant::now());
+        state.metrics.deprecations.forget(&client_id);
+        connection_tenant.passwords().forget_client(&client_id);
+        state.metrics.bandwidth.forget_client(&client_id);
+        if let Some(moderation) = &state.moderation {
+            let regions = moderation.tracker.lock().finish_client(&client_id);
+            for region in regions {
+                moderation.push(region);
+            }
+        }
+        if let Err(e) = state.connections.write().await.disconnect_client(&client_id).await {
+            log::error!("Failed to remove connection: {}", e);
+        }
+    }
+    
+    /// Join a document named in the connection URL. The client is sent its
+    /// state as if it had asked with `getDocument`, going through the same
+    /// checks, and joins it only if they pass and the document exists.
+    async fn join_from_path(
+        document_id: &str,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+        shutdown: &CancellationToken,
+    ) {
+        let request = Message::new(MessageType::GetDocument, client_id.to_string(), json!({ "document_id": document_id }));
+        Self::handle_message(request, client_id, tenant, state, shutdown).await;
+    }
+
+    /// Relay a message about a document to its subscribers, leaving out
+    /// those its password keeps out
+    async fn relay(state: &ServerState, tenant: &Tenant, document_id: &str, message: &Message, exclude_id: Option<&str>) {
+        let passwords = tenant.passwords();
+        let may_read = |client_id: &str| passwords.check_access(document_id, client_id).is_ok();
+        state.clients.broadcast_document_to(tenant.id(), document_id, message, exclude_id, may_read).await;
+    }
+
+    /// Stop a departed client from holding back garbage collection of the
+    /// documents it worked on
+    async fn release_gc_barriers(state: &ServerState, tenant: &Tenant, client_id: &str, documents: &[String]) {
+        if documents.is_empty() {
+            return;
+        }
+        let mut docs = state.documents.write().await;
+        for document_id in documents {
+            if let Some(doc) = docs.get_mut(&tenant.scoped(document_id)) {
+                doc.release_gc_barrier(client_id);
+            }
+        }
+    }
+
+    /// Remove the temporary documents a client created, with their settings
+    async fn destroy_temporary_documents(state: &ServerState, tenant: &Tenant, client_id: &str) {
+        let keys = state.clients.release_temporary(client_id);
+        if keys.is_empty() {
+            return;
+        }
+        let mut docs = state.documents.write().await;
+        for key in &keys {
+            docs.remove(key);
+            state.tails.forget(key);
+        }
+        drop(docs);
+
+        for document_id in keys.iter().filter_map(|key| tenant.unscoped(key)) {
+            tenant.aliases().remove_document(document_id);
+            tenant.classroom().remove_document(document_id);
+            tenant.passwords().remove_document(document_id);
+            tenant.checklists().remove_document(document_id);
+            tenant.links().remove_document(document_id);
+            tenant.undo().remove_document(document_id);
+            tenant.quota().forget(document_id);
+            log::info!("Destroyed temporary document {} of client {}", document_id, client_id);
+        }
+    }
+
+    /// Handle incoming WebSocket messages
+    async fn handle_message(
+        message: Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+        shutdown: &CancellationToken,
+    ) {
+        if shutdown.is_cancelled() {
+            return;
+        }
+
+        let clients = &state.clients;
+
+        // Refuse messages needing capabilities this server lacks rather
+        // than misread them
+        if let Err(e) = check_features(message.requires()) {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+
+        // Count clients still relying on what will be dropped
+        for deprecation in state.deprecations.iter().filter(|deprecation| deprecation.applies_to(&message)) {
+            if state.metrics.deprecations.record(&deprecation.feature, client_id) {
+                log::warn!(
+                    "Client {} of tenant {} uses {}, which is deprecated and removed in {}",
+                    client_id,
+                    tenant.id(),
+                    deprecation.feature,
+                    deprecation.sunset,
+                );
+            }
+        }
+
+        // Keep clients out of password-protected documents they haven't
+        // unlocked
+        if message.message_type() != &MessageType::UnlockDocument {
+            if let Some(document_id) = message.payload().get("document_id").and_then(serde_json::Value::as_str) {
+                if let Err(e) = tenant.passwords().check_access(&tenant.aliases().resolve(document_id), client_id) {
+                    clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                    return;
+                }
+            }
+        }
+
+        match message.message_type() {
+            MessageType::Operation => {
+                let mut op_msg = match serde_json::from_value::<OperationMessage>(message.payload().clone()) {
+                    Ok(op_msg) => op_msg,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid operation: {}", e));
+                        clients.send_to(client_id, &error).await;
+                        return;
+                    }
+                };
+
+                // Address documents by their canonical ID from here on, and
+                // relay the operation under it if the client used a slug
+                let canonical = tenant.aliases().resolve(&op_msg.document_id);
+                let mut rewritten = canonical != op_msg.document_id;
+                op_msg.document_id = canonical;
+
+                // Only the server itself generates server operations
+                if op_msg.source == OperationSource::Server {
+                    let error = message.error_reply(client_id.to_string(), "Clients cannot send server operations".to_string());
+                    clients.send_to(client_id, &error).await;
+                    return;
+                }
+
+                if let Err(e) = tenant.classroom().check_edit(&op_msg.document_id, client_id) {
+                    clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                    return;
+                }
+                if let Operation::Insert { character, .. } = &op_msg.operation {
+                    let mut text = [0; 4];
+                    if let Err(e) = tenant.classroom().check_text(&op_msg.document_id, character.encode_utf8(&mut text)) {
+                        clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                        return;
+                    }
+                }
+
+                // Count the operation toward the client's editing rate; clients
+                // restricted for editing abusively fast can't write
+                if let Err(e) = state.metrics.abuse.observe(tenant.id(), client_id, &op_msg.operation, std::time::Instant::now()) {
+                    clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                    return;
+                }
+
+                // Ask the policy engine before touching the document
+                let document = Self::document_info(state, tenant, &op_msg.document_id).await;
+                let created = !document.exists;
+                let input = PolicyInput::new(tenant.id(), client_id, document, &op_msg.operation).with_source(op_msg.source);
+                match state.policy.evaluate(&input).await {
+                    Decision::Allow => {}
+                    Decision::Deny { reason } => {
+                        log::info!("Policy denied operation of {} on {}: {}", client_id, op_msg.document_id, reason);
+                        let error = message.error_reply(client_id.to_string(), format!("Operation denied: {}", reason));
+                        clients.send_to(client_id, &error).await;
+                        return;
+                    }
+                    Decision::Transform { operation } => {
+                        op_msg.operation = operation;
+                        rewritten = true;
+                    }
+                }
+
+                let relay = if !rewritten {
+                    message.clone()
+                } else {
+                    match operation_payload(message.payload(), &op_msg) {
+                        Ok(payload) => Message::new(MessageType::Operation, message.client_id().to_string(), payload)
+                            .with_requires(message.requires())
+                            .with_extensions(message.extensions().clone()),
+                        Err(e) => {
+                            log::error!("Failed to serialize operation: {}", e);
+                            return;
+                        }
+                    }
+                };
+
+                // Handle document operation; its latency until broadcast
+                // counts against the overload budget
+                let started = std::time::Instant::now();
+                let quota = tenant.quota();
+                let mut docs = state.documents.write().await;
+                let doc = match Self::document_for_operation(
+                    &mut docs,
+                    tenant,
+                    &op_msg.document_id,
+                    state.document_policy,
+                ) {
+                    Ok(doc) => doc,
+                    Err(e) => {
+                        drop(docs);
+                        log::warn!("Rejected operation: {}", e);
+                        clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                        return;
+                    }
+                };
+
+                // Enforce the hard size limit before growing the document
+                if matches!(op_msg.operation, Operation::Insert { .. }) && !quota.allows_insert(doc.len()) {
+                    drop(docs);
+                    let error = message.error_reply(
+                        client_id.to_string(),
+                        format!(
+                            "Document {} has reached its size limit of {} characters",
+                            op_msg.document_id,
+                            quota.limit().unwrap_or_default(),
+                        ),
+                    );
+                    clients.send_to(client_id, &error).await;
+                    return;
+                }
+
+                // Watch for clients with skewed clocks, holding their operations
+                // once flagged when quarantining
+                if !doc.knows(&op_msg.operation) {
+                    let clock = doc.concurrency_stats().latest_clock();
+                    if let Err(e) = state.skew.observe(tenant.id(), client_id, &op_msg.document_id, clock, &op_msg.operation) {
+                        drop(docs);
+                        clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                        return;
+                    }
+                }
+
+                // Apply the operation to the document. Operations the document
+                // already has, such as a client's retry, are acknowledged
+                // without being applied or relayed again.
+                let applied = match doc.merge_operation(op_msg.operation.clone()) {
+                    Ok(Some(applied)) => {
+                        state.tails.record(&tenant.scoped(&op_msg.document_id), applied.version, &op_msg.operation);
+                        applied
+                    }
+                    Ok(None) => {
+                        let version = doc.version();
+                        drop(docs);
+                        log::debug!("Operation on document {} was already applied", op_msg.document_id);
+                        let ack = message.ack(client_id.to_string(), json!({ "document_id": &op_msg.document_id, "version": version }));
+                        clients.send_to(client_id, &ack).await;
+                        return;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to apply operation: {}", e);
+                        drop(docs);
+                        let error = message.error_reply(client_id.to_string(), e.to_string());
+                        clients.send_to(client_id, &error).await;
+                        return;
+                    }
+                };
+                log::info!("Applied operation to document {} (version {})", op_msg.document_id, applied.version);
+
+                let warning = quota.observe(&op_msg.document_id, doc.len());
+                drop(docs);
+
+                Self::record_change(state, tenant, &op_msg.document_id, created, applied.version);
+
+                // The client whose operation created the document owns it
+                if created {
+                    tenant.classroom().claim(&op_msg.document_id, client_id);
+                }
+                if op_msg.source.undoable_by_default() {
+                    tenant.undo().record(&op_msg.document_id, client_id, &op_msg.operation);
+                }
+
+                clients.join_document(client_id, &op_msg.document_id).await;
+                let ack = message.ack(client_id.to_string(), json!({ "document_id": &op_msg.document_id, "version": applied.version }));
+                clients.send_to(client_id, &ack).await;
+
+                if let Some(warning) = warning {
+                    Self::warn_quota(state, tenant, quota, client_id, &warning).await;
+                }
+
+                // Relay the operation to the document's other clients
+                Self::relay(state, tenant, &op_msg.document_id, &relay, Some(client_id)).await;
+                state.overload.record(started.elapsed());
+                if let Some(moderation) = &state.moderation {
+                    let region = moderation.tracker.lock().observe(tenant.id(), &op_msg.document_id, client_id, &op_msg.operation);
+                    if let Some(region) = region {
+                        moderation.push(region);
+                    }
+                }
+                Self::publish_operation(state, tenant, OperationEvent {
+                    tenant_id: tenant.id().to_string(),
+                    document_id: op_msg.document_id,
+                    operation: op_msg.operation,
+                    origin: client_id.to_string(),
+                    source: op_msg.source,
+                });
+            }
+            // Keep editing fast under load by refusing work nobody waits on
+            MessageType::PlaybackRequest | MessageType::CheckSyntax | MessageType::DiffRequest if state.overload.is_shedding() => {
+                state.metrics.shed_requests.increment();
+                let error = message.error_reply(client_id.to_string(), "The server is overloaded; try again later".to_string());
+                clients.send_to(client_id, &error).await;
+            }
+            // Cursors are ephemeral; the next update replaces a dropped one
+            MessageType::CursorUpdate if state.overload.is_shedding() => {
+                state.metrics.shed_requests.increment();
+            }
+            MessageType::CreateDocument => {
+                match serde_json::from_value::<CreateDocumentMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_create_document(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid document request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::GetDocument => {
+                match serde_json::from_value::<GetDocumentMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_get_document(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid document request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::PlaybackRequest => {
+                match serde_json::from_value::<PlaybackRequestMessage>(message.payload().clone()) {
+                    Ok(request) => Self::start_playback(request, &message, client_id, tenant, state, shutdown).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid playback request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::PlaybackStop => {
+                clients.stop_playback(client_id).await;
+            }
+            MessageType::DiffRequest => {
+                match serde_json::from_value::<DiffRequestMessage>(message.payload().clone()) {
+                    Ok(request) => Self::start_diff_view(request, &message, client_id, tenant, state, shutdown).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid diff request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::DiffStop => {
+                clients.stop_diff_view(client_id).await;
+            }
+            MessageType::SubscribeActivity => {
+                match serde_json::from_value::<SubscribeActivityMessage>(message.payload().clone()) {
+                    Ok(request) => Self::start_activity_feed(request, &message, client_id, tenant, state, shutdown).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid activity subscription: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::UnsubscribeActivity => {
+                clients.stop_activity_feed(client_id).await;
+            }
+            MessageType::RepairRequest => {
+                match serde_json::from_value::<RepairRequestMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_repair(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid repair request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SetSlug => {
+                match serde_json::from_value::<SetSlugMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_set_slug(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid slug request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SetFrozen => {
+                match serde_json::from_value::<SetFrozenMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_set_frozen(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid settings request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SetCharset => {
+                match serde_json::from_value::<SetCharsetMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_set_charset(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid settings request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SetPassword => {
+                match serde_json::from_value::<SetPasswordMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_set_password(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid password request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::UnlockDocument => {
+                match serde_json::from_value::<UnlockDocumentMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_unlock_document(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid unlock request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::ShareDocument => {
+                match serde_json::from_value::<ShareDocumentMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_share_document(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid share request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::CheckSyntax => {
+                match serde_json::from_value::<CheckSyntaxMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_check_syntax(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid syntax check request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::CreateBreakouts => {
+                match serde_json::from_value::<CreateBreakoutsMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_create_breakouts(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid breakout request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::DocumentUpdate => {
+                match serde_json::from_value::<DocumentUpdateMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_document_update(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid document update: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SyncRequest => {
+                match serde_json::from_value::<SyncRequestMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_sync_request(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid sync request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SetPresence => {
+                match serde_json::from_value::<SetPresenceMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_set_presence(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid presence: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::GetPresence => {
+                match serde_json::from_value::<GetPresenceMessage>(message.payload().clone()) {
+                    Ok(request) => {
+                        let document_id = tenant.aliases().resolve(&request.document_id);
+                        let snapshot = clients.presence.lock().snapshot(tenant.id(), &document_id);
+                        clients.send_presence_to(client_id, &snapshot).await;
+                    }
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid presence request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::CursorUpdate => {
+                match serde_json::from_value::<CursorUpdateMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_cursor_update(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid cursor update: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::ChecklistOperation => {
+                match serde_json::from_value::<ChecklistOperationMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_checklist_operation(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid checklist operation: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::GetChecklist => {
+                match serde_json::from_value::<GetChecklistMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_get_checklist(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid checklist request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::Undo | MessageType::Redo => {
+                match serde_json::from_value::<UndoMessage>(message.payload().clone()) {
+                    Ok(request) => {
+                        let redo = message.message_type() == &MessageType::Redo;
+                        Self::handle_undo(request, redo, &message, client_id, tenant, state).await
+                    }
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid undo request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::SetUndoScope => {
+                match serde_json::from_value::<SetUndoScopeMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_set_undo_scope(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid settings request: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            MessageType::Command => {
+                match serde_json::from_value::<CommandMessage>(message.payload().clone()) {
+                    Ok(request) => Self::handle_command(request, &message, client_id, tenant, state).await,
+                    Err(e) => {
+                        let error = message.error_reply(client_id.to_string(), format!("Invalid command: {}", e));
+                        clients.send_to(client_id, &error).await;
+                    }
+                }
+            }
+            _ => {
+                log::debug!("Unhandled message type: {:?}", message.message_type());
+            }
+        }
+    }
+
+    /// Look up the target of an operation, creating it if the policy allows
+    fn document_for_operation<'a>(
+        docs: &'a mut HashMap<String, Document>,
+        tenant: &Tenant,
+        document_id: &str,
+        policy: DocumentPolicy,
+    ) -> Result<&'a mut Document, DocumentError> {
+        let key = tenant.scoped(document_id);
+        match policy {
+            DocumentPolicy::AutoCreate => Ok(docs.entry(key).or_insert_with(|| {
+                log::info!("Created document {} in tenant {}", document_id, tenant.id());
+                Document::new(document_id.to_string())
+            })),
+            DocumentPolicy::Strict => docs
+                .get_mut(&key)
+                .ok_or_else(|| DocumentError::NotFound(document_id.to_string())),
+        }
+    }
+
+    /// Send a client a document's state, at least as new as it asked for,
+    /// and join it to the document
+    async fn handle_get_document(
+        request: GetDocumentMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let key = tenant.scoped(&document_id);
+        let ready = Self::read_at_least(state, tenant, &document_id, request.min_version, |_| ()).await;
+
+        // Opening a document joins it, to receive its operations from here
+        // on. Joining and queueing the snapshot under the document lock
+        // sends every operation the snapshot lacks after it; operations
+        // applied just before may arrive after it too, and merge as known.
+        let docs = state.documents.read().await;
+        let snapshot = ready.and_then(|()| {
+            let doc = docs.get(&key).ok_or_else(|| DocumentError::NotFound(document_id.clone()))?;
+            clients.subscribe(tenant.id(), &document_id, client_id);
+            Ok(if request.accept_tail {
+                DocumentStateMessage::from_tail(document_id.clone(), state.tails.join(&key, doc))
+            } else {
+                DocumentStateMessage::new(document_id.clone(), doc)
+            })
+        });
+        let reply = snapshot.map_err(|e| e.to_string()).and_then(|snapshot| {
+            serde_json::to_value(&snapshot).map_err(|e| format!("Failed to serialize document: {}", e))
+        });
+        let opened = reply.is_ok();
+        let reply = match reply {
+            Ok(payload) => Message::new(MessageType::DocumentState, client_id.to_string(), payload)
+                .with_request_id(message.request_id().map(str::to_string)),
+            Err(e) => message.error_reply(client_id.to_string(), e),
+        };
+        clients.send_to(client_id, &reply).await;
+        drop(docs);
+        if opened {
+            clients.join_document(client_id, &document_id).await;
+            if state.health.lock().is_degraded(&key) {
+                clients.send_to(client_id, &Self::storage_status(&document_id, true)).await;
+            }
+        }
+    }
+
+    /// Send a reconnecting client the operations it missed: those beyond its
+    /// state vector, or applied after its version. When repairs reordered
+    /// the history, a version gets the whole history, which the client
+    /// merges skipping what it has.
+    async fn handle_sync_request(
+        request: SyncRequestMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let reply = Self::with_document_mut(state, tenant, &document_id, |doc| {
+            let operations = match (request.state_vector, request.version) {
+                (Some(known), _) => {
+                    // As with updates, the state vector is what the client has seen
+                    let operations = doc.operations_since(&known);
+                    doc.set_gc_barrier(client_id, known);
+                    operations
+                }
+                (None, Some(version)) if version > doc.version() => {
+                    return Err(format!(
+                        "Version {} of document {} is ahead of the server's {}",
+                        version,
+                        document_id,
+                        doc.version(),
+                    ));
+                }
+                (None, Some(version)) => doc.operations_after(version).unwrap_or_else(|| doc.operations()).to_vec(),
+                (None, None) => doc.operations().to_vec(),
+            };
+            Ok(SyncResponseMessage {
+                document_id: document_id.clone(),
+                version: doc.version(),
+                state_vector: doc.state_vector().clone(),
+                operations,
+            })
+        })
+        .await
+        .unwrap_or_else(|| Err(DocumentError::NotFound(document_id.clone()).to_string()));
+
+        let reply = reply.and_then(|reply| {
+            log::debug!("Syncing {} missed operations of document {} to {}", reply.operations.len(), document_id, client_id);
+            serde_json::to_value(&reply).map_err(|e| format!("Failed to serialize sync response: {}", e))
+        });
+        let reply = match reply {
+            Ok(payload) => {
+                clients.join_document(client_id, &document_id).await;
+                Message::new(MessageType::SyncResponse, client_id.to_string(), payload)
+                    .with_request_id(message.request_id().map(str::to_string))
+            }
+            Err(e) => message.error_reply(client_id.to_string(), e),
+        };
+        clients.send_to(client_id, &reply).await;
+    }
+
+    /// Merge the operations an offline client made into a document, relay
+    /// the new ones, and reply with an update holding the operations the
+    /// client lacks. Updates without operations only fetch what the client
+    /// lacks; they don't create documents.
+    async fn handle_document_update(
+        mut request: DocumentUpdateMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        // Submitted operations pass the same checks as live ones
+        if !request.update.is_empty() {
+            if let Err(e) = tenant.classroom().check_edit(&document_id, client_id) {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+            let inserted: String = request.update.operations.iter()
+                .filter_map(|operation| match operation {
+                    Operation::Insert { character, .. } => Some(*character),
+                    Operation::Delete { .. } | Operation::DeleteRange { .. } => None,
+                })
+                .collect();
+            if let Err(e) = tenant.classroom().check_text(&document_id, &inserted) {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+            let document = Self::document_info(state, tenant, &document_id).await;
+            for operation in &mut request.update.operations {
+                let input = PolicyInput::new(tenant.id(), client_id, document.clone(), operation);
+                match state.policy.evaluate(&input).await {
+                    Decision::Allow => {}
+                    Decision::Deny { reason } => {
+                        log::info!("Policy denied update of {} on {}: {}", client_id, document_id, reason);
+                        let error = message.error_reply(client_id.to_string(), format!("Operation denied: {}", reason));
+                        clients.send_to(client_id, &error).await;
+                        return;
+                    }
+                    Decision::Transform { operation: transformed } => *operation = transformed,
+                }
+            }
+        }
+
+        let quota = tenant.quota();
+        let mut docs = state.documents.write().await;
+        let created = !docs.contains_key(&tenant.scoped(&document_id));
+        let doc = if request.update.is_empty() {
+            docs.get_mut(&tenant.scoped(&document_id)).ok_or_else(|| DocumentError::NotFound(document_id.clone()))
+        } else {
+            Self::document_for_operation(&mut docs, tenant, &document_id, state.document_policy)
+        };
+        let doc = match doc {
+            Ok(doc) => doc,
+            Err(e) => {
+                drop(docs);
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+
+        let inserts = request.update.operations.iter().filter(|operation| matches!(operation, Operation::Insert { .. })).count();
+        if inserts > 0 && quota.limit().is_some_and(|limit| doc.len() + inserts > limit) {
+            drop(docs);
+            let error = message.error_reply(
+                client_id.to_string(),
+                format!(
+                    "Update of document {} would exceed its size limit of {} characters",
+                    document_id,
+                    quota.limit().unwrap_or_default(),
+                ),
+            );
+            clients.send_to(client_id, &error).await;
+            return;
+        }
+
+        let applied = match request.update.apply_to(doc) {
+            Ok(applied) => {
+                let first = doc.version() + 1 - applied.len() as u64;
+                for (version, operation) in (first..).zip(&applied) {
+                    state.tails.record(&tenant.scoped(&document_id), version, operation);
+                }
+                applied
+            }
+            Err(e) => {
+                drop(docs);
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+        // The state vector the client submitted is what it has seen
+        doc.set_gc_barrier(client_id, request.update.state_vector.clone());
+        let reply = DocumentUpdateMessage {
+            update: DocumentUpdate::since(doc, &request.update.state_vector),
+            document_id: document_id.clone(),
+        };
+        let warning = if applied.is_empty() { None } else { quota.observe(&document_id, doc.len()) };
+        drop(docs);
+
+        if !applied.is_empty() {
+            log::info!("Merged {} operations of an update into document {} (version {})", applied.len(), document_id, reply.update.version);
+            Self::record_change(state, tenant, &document_id, created, reply.update.version);
+            if created {
+                tenant.classroom().claim(&document_id, client_id);
+            }
+        }
+        clients.join_document(client_id, &document_id).await;
+
+        let reply = match serde_json::to_value(&reply) {
+            Ok(payload) => Message::new(MessageType::DocumentUpdate, client_id.to_string(), payload)
+                .with_request_id(message.request_id().map(str::to_string)),
+            Err(e) => message.error_reply(client_id.to_string(), format!("Failed to serialize update: {}", e)),
+        };
+        clients.send_to(client_id, &reply).await;
+        if let Some(warning) = warning {
+            Self::warn_quota(state, tenant, quota, client_id, &warning).await;
+        }
+
+        // Relay the new operations as if they had been sent one by one
+        for operation in applied {
+            tenant.undo().record(&document_id, client_id, &operation);
+            match serde_json::to_value(OperationMessage::new(operation.clone(), document_id.clone())) {
+                Ok(payload) => {
+                    let relay = Message::new(MessageType::Operation, client_id.to_string(), payload);
+                    Self::relay(state, tenant, &document_id, &relay, Some(client_id)).await;
+                }
+                Err(e) => log::error!("Failed to serialize operation: {}", e),
+            }
+            if let Some(moderation) = &state.moderation {
+                let region = moderation.tracker.lock().observe(tenant.id(), &document_id, client_id, &operation);
+                if let Some(region) = region {
+                    moderation.push(region);
+                }
+            }
+            Self::publish_operation(state, tenant, OperationEvent {
+                tenant_id: tenant.id().to_string(),
+                document_id: document_id.clone(),
+                operation,
+                origin: client_id.to_string(),
+                source: OperationSource::User,
+            });
+        }
+    }
+
+    /// Run a slash command on a document. Operations the command returns
+    /// pass the checks of the sender's own edits, are applied as the
+    /// server's and relayed to every client, the sender included; a status
+    /// goes back to the sender in the ack.
+    async fn handle_command(
+        request: CommandMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let handler = parse_command(&request.command).and_then(|(name, args)| match state.commands.get(&name) {
+            Some(handler) => Ok((handler.clone(), name, args)),
+            None => Err(CommandError::Unknown(name)),
+        });
+        let (handler, name, args) = match handler {
+            Ok(handler) => handler,
+            Err(e) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+        let Some(document) = state.documents.read().await.get(&tenant.scoped(&document_id)).cloned() else {
+            let error = message.error_reply(client_id.to_string(), DocumentError::NotFound(document_id).to_string());
+            clients.send_to(client_id, &error).await;
+            return;
+        };
+
+        let input = CommandInput {
+            tenant_id: tenant.id().to_string(),
+            client_id: client_id.to_string(),
+            name: name.clone(),
+            args,
+            document,
+        };
+        let mut operations = match handler.run(&input).await {
+            Ok(CommandOutput::Operations(operations)) => operations,
+            Ok(CommandOutput::Status { message: status, data }) => {
+                let ack = message.ack(
+                    client_id.to_string(),
+                    json!({ "document_id": &document_id, "command": &name, "status": status, "data": data }),
+                );
+                clients.send_to(client_id, &ack).await;
+                return;
+            }
+            Err(e) => {
+                log::info!("Command /{} of {} on {} failed: {}", name, client_id, document_id, e);
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+
+        // The sender must be allowed to make the edits themselves
+        if !operations.is_empty() {
+            if let Err(e) = tenant.classroom().check_edit(&document_id, client_id) {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+            let inserted: String = operations.iter()
+                .filter_map(|operation| match operation {
+                    Operation::Insert { character, .. } => Some(*character),
+                    Operation::Delete { .. } | Operation::DeleteRange { .. } => None,
+                })
+                .collect();
+            if let Err(e) = tenant.classroom().check_text(&document_id, &inserted) {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+            let document = Self::document_info(state, tenant, &document_id).await;
+            for operation in &mut operations {
+                let input = PolicyInput::new(tenant.id(), client_id, document.clone(), operation).with_source(OperationSource::Server);
+                match state.policy.evaluate(&input).await {
+                    Decision::Allow => {}
+                    Decision::Deny { reason } => {
+                        log::info!("Policy denied command /{} of {} on {}: {}", name, client_id, document_id, reason);
+                        let error = message.error_reply(client_id.to_string(), format!("Operation denied: {}", reason));
+                        clients.send_to(client_id, &error).await;
+                        return;
+                    }
+                    Decision::Transform { operation: transformed } => *operation = transformed,
+                }
+            }
+        }
+
+        let quota = tenant.quota();
+        let mut docs = state.documents.write().await;
+        let Some(doc) = docs.get_mut(&tenant.scoped(&document_id)) else {
+            drop(docs);
+            let error = message.error_reply(client_id.to_string(), DocumentError::NotFound(document_id).to_string());
+            clients.send_to(client_id, &error).await;
+            return;
+        };
+        let inserts = operations.iter().filter(|operation| matches!(operation, Operation::Insert { .. })).count();
+        if inserts > 0 && quota.limit().is_some_and(|limit| doc.len() + inserts > limit) {
+            drop(docs);
+            let error = message.error_reply(
+                client_id.to_string(),
+                format!(
+                    "Command /{} on document {} would exceed its size limit of {} characters",
+                    name,
+                    document_id,
+                    quota.limit().unwrap_or_default(),
+                ),
+            );
+            clients.send_to(client_id, &error).await;
+            return;
+        }
+
+        // Edits made since the snapshot may have removed what an operation
+        // targets; those operations are skipped
+        let mut applied = Vec::new();
+        for operation in operations {
+            match doc.merge_operation(operation.clone()) {
+                Ok(Some(done)) => {
+                    state.tails.record(&tenant.scoped(&document_id), done.version, &operation);
+                    applied.push(operation);
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Skipped operation of command /{} on {}: {}", name, document_id, e),
+            }
+        }
+        let version = doc.version();
+        let warning = if applied.is_empty() { None } else { quota.observe(&document_id, doc.len()) };
+        drop(docs);
+
+        if !applied.is_empty() {
+            log::info!("Command /{} of {} applied {} operations to document {} (version {})", name, client_id, applied.len(), document_id, version);
+            Self::record_change(state, tenant, &document_id, false, version);
+        }
+        let count = applied.len();
+        for operation in applied {
+            let op_msg = OperationMessage::new(operation.clone(), document_id.clone()).with_source(OperationSource::Server);
+            match serde_json::to_value(op_msg) {
+                Ok(payload) => {
+                    let relay = Message::new(MessageType::Operation, COMMAND_CLIENT_ID.to_string(), payload);
+                    Self::relay(state, tenant, &document_id, &relay, None).await;
+                }
+                Err(e) => log::error!("Failed to serialize operation: {}", e),
+            }
+            Self::publish_operation(state, tenant, OperationEvent {
+                tenant_id: tenant.id().to_string(),
+                document_id: document_id.clone(),
+                operation,
+                origin: COMMAND_CLIENT_ID.to_string(),
+                source: OperationSource::Server,
+            });
+        }
+
+        let ack = message.ack(
+            client_id.to_string(),
+            json!({ "document_id": &document_id, "command": &name, "version": version, "applied": count }),
+        );
+        clients.send_to(client_id, &ack).await;
+        if let Some(warning) = warning {
+            Self::warn_quota(state, tenant, quota, client_id, &warning).await;
+        }
+    }
+
+    /// Apply an operation to a checklist of a document, and relay it to the
+    /// tenant's other clients if it changed the checklist
+    async fn handle_checklist_operation(
+        request: ChecklistOperationMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        let exists = Self::with_document(state, tenant, &document_id, |_| ()).await.is_some();
+        let checked = if request.checklist.is_empty() {
+            Err("Checklist name must not be empty".to_string())
+        } else if !exists {
+            Err(DocumentError::NotFound(document_id.clone()).to_string())
+        } else {
+            tenant.classroom().check_edit(&document_id, client_id)
+                .and_then(|()| tenant.classroom().check_text(&document_id, request.operation.text().unwrap_or_default()))
+                .map_err(|e| e.to_string())
+        };
+        if let Err(e) = checked {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e)).await;
+            return;
+        }
+
+        let Some(changed) = tenant.checklists().apply(&document_id, &request.checklist, request.operation.clone()) else {
+            let error = format!("Checklist {} of document {} has reached its limit of {} items", request.checklist, document_id, MAX_CHECKLIST_ITEMS);
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), error)).await;
+            return;
+        };
+        let ack = message.ack(
+            client_id.to_string(),
+            json!({ "document_id": &document_id, "checklist": &request.checklist, "changed": changed }),
+        );
+        clients.send_to(client_id, &ack).await;
+        if !changed {
+            return;
+        }
+
+        let relay = ChecklistOperationMessage { document_id: document_id.clone(), ..request };
+        match serde_json::to_value(&relay) {
+            Ok(payload) => {
+                let relay = Message::new(MessageType::ChecklistOperation, client_id.to_string(), payload);
+                Self::relay(state, tenant, &document_id, &relay, Some(client_id)).await;
+            }
+            Err(e) => log::error!("Failed to serialize checklist operation: {}", e),
+        }
+    }
+
+    /// Send a client a checklist of a document
+    async fn handle_get_checklist(
+        request: GetChecklistMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        if Self::with_document(state, tenant, &document_id, |_| ()).await.is_none() {
+            let error = DocumentError::NotFound(document_id).to_string();
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), error)).await;
+            return;
+        }
+        let checklist = tenant.checklists().checklist(&document_id, &request.checklist);
+        let reply = ChecklistStateMessage {
+            document_id,
+            checklist: request.checklist,
+            items: checklist.items(),
+            operations: checklist.operations(),
+        };
+        let reply = match serde_json::to_value(&reply) {
+            Ok(payload) => Message::new(MessageType::ChecklistState, client_id.to_string(), payload)
+                .with_request_id(message.request_id().map(str::to_string)),
+            Err(e) => message.error_reply(client_id.to_string(), format!("Failed to serialize checklist: {}", e)),
+        };
+        clients.send_to(client_id, &reply).await;
+    }
+
+    /// Tell the tenant's clients a document passed its soft size limit
+    async fn warn_quota(state: &ServerState, tenant: &Tenant, quota: &QuotaTracker, client_id: &str, warning: &QuotaWarning) {
+        let status = Message::new(
+            MessageType::Status,
+            client_id.to_string(),
+            json!({
+                "status": "warning",
+                "code": "quota_soft_limit",
+                "document_id": &warning.document_id,
+                "size": warning.size,
+                "limit": warning.limit,
+            }),
+        );
+        state.clients.broadcast(tenant.id(), &status, None).await;
+        quota.notify(warning);
+    }
+
+    /// Create a document owned by the client, seeded with its initial
+    /// content. Documents can be created this way whatever the document
+    /// policy; creating one that exists is an error. Temporary documents
+    /// live until the client disconnects.
+    async fn handle_create_document(
+        request: CreateDocumentMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let quota = tenant.quota();
+        let characters: Vec<char> = request.initial_content.chars().collect();
+
+        let rejection = if document_id.is_empty() {
+            Some("Document ID must not be empty".to_string())
+        } else if quota.limit().is_some_and(|limit| characters.len() > limit) {
+            Some(format!(
+                "Initial content of {} characters exceeds the size limit of {} characters",
+                characters.len(),
+                quota.limit().unwrap_or_default(),
+            ))
+        } else if state.documents.read().await.contains_key(&tenant.scoped(&document_id)) {
+            Some(DocumentError::AlreadyExists(document_id.clone()).to_string())
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), rejection)).await;
+            return;
+        }
+
+        // Seed the document before publishing it, a chunk at a time. The
+        // positions are spread evenly so later edits anywhere in the text
+        // keep short paths.
+        let mut document = Document::new(document_id.clone());
+        let mut operations = Vec::with_capacity(characters.len());
+        let positions = Position::spread(characters.len());
+        for (chunk, positions) in characters.chunks(SEED_CHUNK_SIZE).zip(positions.chunks(SEED_CHUNK_SIZE)) {
+            for (&character, position) in chunk.iter().zip(positions) {
+                let operation = Operation::insert(client_id.to_string(), character, position.clone());
+                if let Err(e) = document.apply_operation(operation.clone()) {
+                    log::error!("Failed to seed document {}: {}", document_id, e);
+                    clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                    return;
+                }
+                operations.push(operation);
+            }
+            tokio::task::yield_now().await;
+        }
+        let version = document.version();
+        let length = document.len();
+
+        {
+            // Another client may have created the document while seeding
+            let mut docs = state.documents.write().await;
+            let key = tenant.scoped(&document_id);
+            if docs.contains_key(&key) {
+                drop(docs);
+                let error = DocumentError::AlreadyExists(document_id).to_string();
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), error)).await;
+                return;
+            }
+            if request.temporary {
+                clients.hold_temporary(key.clone(), client_id);
+            }
+            docs.insert(key, document);
+        }
+        log::info!("Created document {} in tenant {} with {} characters", document_id, tenant.id(), length);
+
+        tenant.classroom().claim(&document_id, client_id);
+        clients.join_document(client_id, &document_id).await;
+        Self::record_change(state, tenant, &document_id, true, version);
+        if let Some(warning) = quota.observe(&document_id, length) {
+            Self::warn_quota(state, tenant, quota, client_id, &warning).await;
+        }
+
+        // Sync links pick the seed up like imported text; one that falls
+        // behind on a large seed reconciles
+        if !request.temporary {
+            for operation in operations {
+                Self::publish_operation(state, tenant, OperationEvent {
+                    tenant_id: tenant.id().to_string(),
+                    document_id: document_id.clone(),
+                    operation,
+                    origin: client_id.to_string(),
+                    source: OperationSource::Import,
+                });
+            }
+        }
+
+        let created = DocumentCreatedMessage { document_id, version, length };
+        match serde_json::to_value(&created) {
+            Ok(payload) => {
+                let reply = Message::new(MessageType::DocumentCreated, client_id.to_string(), payload)
+                    .with_request_id(message.request_id().map(str::to_string));
+                clients.send_to(client_id, &reply).await;
+            }
+            Err(e) => log::error!("Failed to serialize document creation: {}", e),
+        }
+    }
+
+    /// Give an existing document a slug. A slug may not shadow another
+    /// document's ID or a slug another document holds.
+    async fn handle_set_slug(
+        request: SetSlugMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        let result = {
+            let docs = state.documents.read().await;
+            match docs.get(&tenant.scoped(&document_id)) {
+                None => Err(DocumentError::NotFound(document_id.clone()).to_string()),
+                Some(_) if request.slug != document_id && docs.contains_key(&tenant.scoped(&request.slug)) => {
+                    Err(AliasError::Taken(request.slug.clone()).to_string())
+                }
+                Some(doc) => tenant.aliases().set_slug(&document_id, &request.slug).map(|()| doc.version()).map_err(|e| e.to_string()),
+            }
+        };
+
+        let reply = match result {
+            Ok(version) => {
+                log::info!("Document {} in tenant {} is now {}", document_id, tenant.id(), request.slug);
+                if !state.clients.is_temporary(&tenant.scoped(&document_id)) {
+                    state.activity.renamed(tenant.id(), &document_id, &request.slug, client_id, version, Utc::now());
+                }
+                message.ack(client_id.to_string(), json!({ "document_id": &document_id, "slug": &request.slug }))
+            }
+            Err(e) => message.error_reply(client_id.to_string(), e),
+        };
+        clients.send_to(client_id, &reply).await;
+    }
+
+    /// Freeze or unfreeze a document for everyone but its owner, and tell
+    /// the tenant's clients about the new settings
+    async fn handle_set_frozen(
+        request: SetFrozenMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        let settings = match tenant.classroom().set_frozen(&document_id, client_id, request.frozen) {
+            Ok(settings) => settings,
+            Err(e) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+        log::info!(
+            "Document {} in tenant {} is {}",
+            document_id,
+            tenant.id(),
+            if settings.frozen { "frozen" } else { "open for editing" },
+        );
+
+        let ack = message.ack(client_id.to_string(), json!({ "document_id": &document_id, "frozen": settings.frozen }));
+        clients.send_to(client_id, &ack).await;
+        Self::announce_settings(document_id, client_id, tenant, state).await;
+    }
+
+    /// Protect a document with a password on behalf of its owner, or
+    /// remove its password. Hashing runs off the async runtime.
+    async fn handle_set_password(
+        request: SetPasswordMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        if let Err(e) = tenant.classroom().check_owner(&document_id, client_id) {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+
+        let hash = match request.password {
+            Some(password) => {
+                let (memory_kib, iterations) = (state.passwords.memory_kib, state.passwords.iterations);
+                match tokio::task::spawn_blocking(move || hash_password(&password, memory_kib, iterations)).await {
+                    Ok(Ok(hash)) => Some(hash),
+                    Ok(Err(e)) => {
+                        clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                        return;
+                    }
+                    Err(e) => {
+                        log::error!("Hashing the password of document {} failed: {}", document_id, e);
+                        clients.send_to(client_id, &message.error_reply(client_id.to_string(), "Password could not be set".to_string())).await;
+                        return;
+                    }
+                }
+            }
+            None => None,
+        };
+        let protected = hash.is_some();
+        Self::store_password(state, &tenant.scoped(&document_id), hash.as_deref());
+        tenant.passwords().set(&document_id, client_id, hash);
+        // Clients that were working on the document unlock it to go on
+        if protected {
+            let passwords = tenant.passwords();
+            clients.lock_out(tenant.id(), &document_id, |client| passwords.check_access(&document_id, client).is_ok()).await;
+        }
+        log::info!(
+            "Document {} in tenant {} is {}",
+            document_id,
+            tenant.id(),
+            if protected { "protected by a password" } else { "no longer protected by a password" },
+        );
+
+        let ack = message.ack(client_id.to_string(), json!({ "document_id": &document_id, "protected": protected }));
+        clients.send_to(client_id, &ack).await;
+    }
+
+    /// Unlock a password-protected document for a client's connection,
+    /// counting wrong passwords toward the document's lockout. Share tokens
+    /// unlock it without the password.
+    async fn handle_unlock_document(
+        request: UnlockDocumentMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        if let Err(e) = request.validate() {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        if let Some(token) = request.share_token {
+            let granted = state.open_share(tenant, &document_id, &token)
+                .map_err(|e| e.to_string())
+                .and_then(|claims| tenant.passwords().grant(&document_id, client_id, &claims.password).map_err(|e| e.to_string()));
+            let reply = match granted {
+                Ok(()) => message.ack(client_id.to_string(), json!({ "document_id": &document_id, "unlocked": true })),
+                Err(e) => {
+                    log::warn!("Client {} of tenant {} sent an unusable share token for document {}: {}", client_id, tenant.id(), document_id, e);
+                    message.error_reply(client_id.to_string(), e)
+                }
+            };
+            clients.send_to(client_id, &reply).await;
+            return;
+        }
+
+        let hash = match tenant.passwords().begin_unlock(&document_id, &state.passwords, std::time::Instant::now()) {
+            Ok(hash) => hash,
+            Err(e) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+
+        let password = request.password.unwrap_or_default();
+        let matched = match tokio::task::spawn_blocking(move || verify_password(&password, &hash)).await {
+            Ok(Ok(matched)) => matched,
+            Ok(Err(e)) => {
+                log::error!("Password of document {} in tenant {} can't be checked: {}", document_id, tenant.id(), e);
+                false
+            }
+            Err(e) => {
+                log::error!("Checking the password of document {} failed: {}", document_id, e);
+                false
+            }
+        };
+        let reply = match tenant.passwords().finish_unlock(&document_id, client_id, matched, &state.passwords, std::time::Instant::now()) {
+            Ok(()) => message.ack(client_id.to_string(), json!({ "document_id": &document_id, "unlocked": true })),
+            Err(e) => {
+                if let PasswordError::Wrong(_) = e {
+                    log::warn!("Client {} of tenant {} sent a wrong password for document {}", client_id, tenant.id(), document_id);
+                }
+                message.error_reply(client_id.to_string(), e.to_string())
+            }
+        };
+        clients.send_to(client_id, &reply).await;
+    }
+
+    /// Issue a token unlocking a password-protected document without its
+    /// password, to a client that has access to it
+    async fn handle_share_document(
+        request: ShareDocumentMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let Some(fingerprint) = tenant.passwords().fingerprint(&document_id) else {
+            let error = PasswordError::NotProtected(document_id);
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), error.to_string())).await;
+            return;
+        };
+        let expires = Utc::now() + chrono::Duration::from_std(state.tokens.share_ttl).unwrap_or(chrono::Duration::MAX);
+        let claims = ShareClaims { tenant_id: tenant.id().to_string(), document_id: document_id.clone(), password: fingerprint };
+        let reply = match state.tokens.sealer.seal(TokenKind::Share, &claims, expires) {
+            Ok(token) => {
+                log::info!("Client {} of tenant {} shared document {}", client_id, tenant.id(), document_id);
+                message.ack(client_id.to_string(), json!({ "document_id": &document_id, "share_token": token, "expires_at": expires }))
+            }
+            Err(e) => {
+                log::error!("Could not seal a share token for document {} in tenant {}: {}", document_id, tenant.id(), e);
+                message.error_reply(client_id.to_string(), "Share token could not be issued".to_string())
+            }
+        };
+        clients.send_to(client_id, &reply).await;
+    }
+
+    /// Restrict the characters inserted into a document on behalf of its
+    /// owner, and tell the tenant's clients about the new settings
+    async fn handle_set_charset(
+        request: SetCharsetMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        let settings = match tenant.classroom().set_charset(&document_id, client_id, request.charset) {
+            Ok(settings) => settings,
+            Err(e) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+        };
+        log::info!("Document {} in tenant {} accepts {} characters", document_id, tenant.id(), settings.charset);
+
+        let ack = message.ack(client_id.to_string(), json!({ "document_id": &document_id, "charset": settings.charset }));
+        clients.send_to(client_id, &ack).await;
+        Self::announce_settings(document_id, client_id, tenant, state).await;
+    }
+
+    /// Tell the tenant's other clients about a document's settings
+    async fn announce_settings(document_id: String, client_id: &str, tenant: &Tenant, state: &ServerState) {
+        let settings = tenant.classroom().settings(&document_id);
+        let changed = SettingsChangedMessage {
+            undo: tenant.undo().scope(&document_id),
+            document_id,
+            owner: settings.owner,
+            frozen: settings.frozen,
+            charset: settings.charset,
+        };
+        match serde_json::to_value(&changed) {
+            Ok(payload) => {
+                let notice = Message::new(MessageType::SettingsChanged, client_id.to_string(), payload);
+                state.clients.broadcast(tenant.id(), &notice, Some(client_id)).await;
+            }
+            Err(e) => log::error!("Failed to serialize settings: {}", e),
+        }
+    }
+
+    /// Set the name and color a client is shown with, announcing them in
+    /// the documents it is present in
+    async fn handle_set_presence(
+        request: SetPresenceMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        if let Err(e) = request.validate() {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+        // Profiles aren't tied to a document, so the reply is a status
+        // rather than an ack
+        let mut status = serde_json::to_value(&request).unwrap_or_default();
+        status["status"] = json!("presence");
+        let reply = Message::new(MessageType::Status, client_id.to_string(), status)
+            .with_request_id(message.request_id().map(str::to_string));
+        clients.set_profile(tenant.id(), client_id, request.name, request.color).await;
+        clients.send_to(client_id, &reply).await;
+    }
+
+    /// Relay a client's cursor to the document's other clients, at most
+    /// once per `CURSOR_INTERVAL`; updates in between are coalesced and the
+    /// newest is flushed when the interval is up. Updates are not
+    /// acknowledged.
+    async fn handle_cursor_update(
+        request: CursorUpdateMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        if let Err(e) = request.validate() {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        if Self::with_document(state, tenant, &document_id, |_| ()).await.is_none() {
+            let error = message.error_reply(client_id.to_string(), DocumentError::NotFound(document_id).to_string());
+            clients.send_to(client_id, &error).await;
+            return;
+        }
+        clients.join_document(client_id, &document_id).await;
+
+        // Relay what the client sent, unknown fields included, addressed
+        // by document ID rather than slug. The deadline is the server's
+        // to keep, so it isn't relayed.
+        let mut payload = message.payload().clone();
+        payload["document_id"] = json!(&document_id);
+        if let Some(payload) = payload.as_object_mut() {
+            payload.remove("stale_after_ms");
+        }
+        let staleness = request.stale_after_ms.map_or(state.cursor_staleness, Duration::from_millis);
+        let relay = Message::new(MessageType::CursorUpdate, client_id.to_string(), payload)
+            .with_requires(message.requires())
+            .with_extensions(message.extensions().clone())
+            .with_stale_at(stale_at(staleness));
+
+        let throttled = state.cursors.lock().observe(tenant.id(), &document_id, client_id, relay, std::time::Instant::now());
+        match throttled {
+            Throttled::Relay(relay) => Self::relay(state, tenant, &document_id, &relay, Some(client_id)).await,
+            Throttled::Flush(delay) => {
+                let state = state.clone();
+                let tenant_id = tenant.id().to_string();
+                let client_id = client_id.to_string();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let held = state.cursors.lock().flush(&tenant_id, &document_id, &client_id, std::time::Instant::now());
+                    if let (Some(relay), Ok(tenant)) = (held, state.tenants.get(&tenant_id)) {
+                        Self::relay(&state, &tenant, &document_id, &relay, Some(&client_id)).await;
+                    }
+                });
+            }
+            Throttled::Coalesced => {}
+        }
+    }
+
+    /// Choose whose edits undo reverts in a document, on behalf of its owner
+    async fn handle_set_undo_scope(
+        request: SetUndoScopeMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        if let Err(e) = tenant.classroom().check_owner(&document_id, client_id) {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+        tenant.undo().set_scope(&document_id, request.scope);
+        log::info!("Undo in document {} of tenant {} is now {}", document_id, tenant.id(), request.scope);
+
+        let ack = message.ack(client_id.to_string(), json!({ "document_id": &document_id, "undo": request.scope }));
+        clients.send_to(client_id, &ack).await;
+        Self::announce_settings(document_id, client_id, tenant, state).await;
+    }
+
+    /// Revert the newest edit in the client's undo scope, or with `redo`
+    /// the newest undo. The inverse operations, one per character a range
+    /// delete removed, reach the document's clients, the undoing one
+    /// included, before the acknowledgement.
+    async fn handle_undo(
+        request: UndoMessage,
+        redo: bool,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        if let Err(e) = tenant.classroom().check_edit(&document_id, client_id) {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+
+        let step = if redo { UndoHistory::redo } else { UndoHistory::undo };
+        let mut docs = state.documents.write().await;
+        let result = match docs.get_mut(&tenant.scoped(&document_id)) {
+            None => Err(DocumentError::NotFound(document_id.clone()).to_string()),
+            Some(doc) => match step(tenant.undo(), &document_id, doc, client_id) {
+                Err(e) => Err(e.to_string()),
+                Ok(operations) => {
+                    let mut applied = Vec::new();
+                    let mut failure = None;
+                    for operation in operations {
+                        match doc.merge_operation(operation.clone()) {
+                            Ok(Some(change)) => {
+                                state.tails.record(&tenant.scoped(&document_id), change.version, &operation);
+                                applied.push((operation, change.version));
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                failure = Some(e.to_string());
+                                break;
+                            }
+                        }
+                    }
+                    match (failure, applied.last()) {
+                        (Some(e), None) => Err(e),
+                        (_, Some(_)) => Ok(applied),
+                        (None, None) if redo => Err(UndoError::NothingToRedo(document_id.clone()).to_string()),
+                        (None, None) => Err(UndoError::NothingToUndo(document_id.clone()).to_string()),
+                    }
+                }
+            },
+        };
+        drop(docs);
+
+        let applied = match result {
+            Ok(applied) => applied,
+            Err(e) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e)).await;
+                return;
+            }
+        };
+        let version = applied.last().map_or(0, |(_, version)| *version);
+        log::info!("{} an edit of document {} (version {})", if redo { "Redid" } else { "Undid" }, document_id, version);
+        Self::record_change(state, tenant, &document_id, false, version);
+
+        for (operation, _) in applied {
+            match serde_json::to_value(OperationMessage::new(operation.clone(), document_id.clone())) {
+                Ok(payload) => {
+                    let relay = Message::new(MessageType::Operation, client_id.to_string(), payload);
+                    Self::relay(state, tenant, &document_id, &relay, None).await;
+                }
+                Err(e) => log::error!("Failed to serialize operation: {}", e),
+            }
+            Self::publish_operation(state, tenant, OperationEvent {
+                tenant_id: tenant.id().to_string(),
+                document_id: document_id.clone(),
+                operation,
+                origin: client_id.to_string(),
+                source: OperationSource::User,
+            });
+        }
+
+        let ack = message.ack(client_id.to_string(), json!({ "document_id": &document_id, "version": version }));
+        clients.send_to(client_id, &ack).await;
+    }
+
+    /// Copy a document once per student, on behalf of its owner. Breakout
+    /// copies that already exist are kept as they are.
+    async fn handle_create_breakouts(
+        request: CreateBreakoutsMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+
+        let result = tenant.classroom().check_owner(&document_id, client_id)
+            .map_err(|e| e.to_string())
+            .and_then(|()| {
+                request.students.iter()
+                    .map(|student| breakout_id(&document_id, student))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())
+            });
+        let breakouts = match result {
+            Ok(breakouts) => breakouts,
+            Err(e) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e)).await;
+                return;
+            }
+        };
+
+        {
+            let mut docs = state.documents.write().await;
+            let Some(source) = docs.get(&tenant.scoped(&document_id)).cloned() else {
+                drop(docs);
+                let error = DocumentError::NotFound(document_id).to_string();
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), error)).await;
+                return;
+            };
+            for breakout in &breakouts {
+                docs.entry(tenant.scoped(breakout)).or_insert_with(|| {
+                    tenant.classroom().claim(breakout, client_id);
+                    let copy = source.fork(breakout.clone());
+                    Self::record_change(state, tenant, breakout, true, copy.version());
+                    Self::store_document(state, &tenant.scoped(breakout), &copy);
+                    let change = ReplicatedChange::Document { document: Box::new(copy.clone()) };
+                    state.replication.log().record(tenant.id(), breakout, change);
+                    copy
+                });
+            }
+        }
+        log::info!("Created {} breakouts of document {} in tenant {}", breakouts.len(), document_id, tenant.id());
+
+        let ack = message.ack(client_id.to_string(), json!({ "document_id": &document_id, "breakouts": &breakouts }));
+        clients.send_to(client_id, &ack).await;
+    }
+
+    /// Check the code blocks of a document with the configured checker
+    async fn handle_check_syntax(
+        request: CheckSyntaxMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        let Some(checker) = &state.syntax_checker else {
+            let error = message.error_reply(client_id.to_string(), "Syntax checking is not enabled".to_string());
+            clients.send_to(client_id, &error).await;
+            return;
+        };
+        let Some(document) = Self::document_copy(state, tenant, &request.document_id).await else {
+            let error = DocumentError::NotFound(request.document_id).to_string();
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), error)).await;
+            return;
+        };
+
+        // Check a snapshot, so edits made meanwhile don't hold the document
+        let mut diagnostics = Vec::new();
+        for (index, block) in code_blocks(&document.content()).into_iter().enumerate() {
+            for diagnostic in checker.check(block.language.as_deref(), &block.code).await {
+                diagnostics.push(BlockDiagnostic {
+                    block: index,
+                    language: block.language.clone(),
+                    line: block.first_line + diagnostic.line - 1,
+                    column: diagnostic.column,
+                    message: diagnostic.message,
+                });
+            }
+        }
+
+        let report = SyntaxReportMessage {
+            document_id: document.id().to_string(),
+            version: document.version(),
+            diagnostics,
+        };
+        match serde_json::to_value(&report) {
+            Ok(payload) => {
+                let reply = Message::new(MessageType::SyntaxReport, client_id.to_string(), payload)
+                    .with_request_id(message.request_id().map(str::to_string));
+                clients.send_to(client_id, &reply).await;
+            }
+            Err(e) => log::error!("Failed to serialize syntax report: {}", e),
+        }
+    }
+
+    /// Reply to a repair request with the operations of the regions where the
+    /// client's copy diverged
+    async fn handle_repair(
+        request: RepairRequestMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+    ) {
+        let clients = &state.clients;
+        if let Err(e) = request.validate() {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let response = match state.documents.read().await.get(&tenant.scoped(&document_id)) {
+            Some(doc) => {
+                let diverged = doc.content_hash().diverged_from(&request.regions);
+                RepairResponseMessage::new(document_id.clone(), doc, diverged)
+            }
+            None => {
+                let error = DocumentError::NotFound(request.document_id.clone());
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), error.to_string())).await;
+                return;
+            }
+        };
+        log::debug!(
+            "Repairing {} regions of {} for client {}",
+            response.regions.len(),
+            document_id,
+            client_id,
+        );
+
+        match serde_json::to_value(&response) {
+            Ok(payload) => {
+                let reply = Message::new(MessageType::RepairResponse, client_id.to_string(), payload)
+                    .with_request_id(message.request_id().map(str::to_string));
+                clients.send_to(client_id, &reply).await;
+            }
+            Err(e) => log::error!("Failed to serialize repair response: {}", e),
+        }
+    }
+
+    /// Stream a document's history to a client as playback frames
+    async fn start_playback(
+        request: PlaybackRequestMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+        shutdown: &CancellationToken,
+    ) {
+        let clients = &state.clients;
+        if let Err(e) = request.validate() {
+            clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+            return;
+        }
+
+        // Copy the history so the document lock isn't held while streaming
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        let playback = match state.documents.read().await.get(&tenant.scoped(&document_id)) {
+            Some(doc) => Playback::from_document(doc),
+            None => {
+                let error = DocumentError::NotFound(request.document_id.clone());
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), error.to_string())).await;
+                return;
+            }
+        };
+
+        let task = tokio::spawn({
+            let clients = clients.clone();
+            let client_id = client_id.to_string();
+            let shutdown = shutdown.clone();
+
+            async move {
+                let mut playback = playback;
+                playback.seek(request.from);
+
+                let mut ticker = request.ops_per_second
+                    .map(|speed| tokio::time::interval(Duration::from_secs_f64(1.0 / speed)));
+
+                for frame in playback.take(request.count.unwrap_or(usize::MAX)) {
+                    if let Some(ticker) = ticker.as_mut() {
+                        tokio::select! {
+                            _ = shutdown.cancelled() => break,
+                            _ = ticker.tick() => {}
+                        }
+                    }
+                    if shutdown.is_cancelled() {
+                        break;
+                    }
+
+                    let payload = PlaybackFrameMessage {
+                        document_id: request.document_id.clone(),
+                        frame,
+                    };
+                    let message = match serde_json::to_value(&payload) {
+                        Ok(value) => Message::new(MessageType::PlaybackFrame, client_id.clone(), value),
+                        Err(e) => {
+                            log::error!("Failed to serialize playback frame: {}", e);
+                            break;
+                        }
+                    };
+                    if !clients.send_to(&client_id, &message).await {
+                        break;
+                    }
+                }
+            }
+        });
+
+        clients.start_playback(client_id, task).await;
+    }
+
+    /// Send a client the changes of a document since a revision, and again
+    /// after edits to it, at most every `DIFF_VIEW_INTERVAL`, until the
+    /// client stops the view or the document is gone
+    async fn start_diff_view(
+        request: DiffRequestMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+        shutdown: &CancellationToken,
+    ) {
+        let clients = &state.clients;
+        let document_id = tenant.aliases().resolve(&request.document_id);
+        // Subscribe before taking the base so no edit falls in between
+        let mut operations = state.operations.subscribe();
+        let base = match Self::with_document(state, tenant, &document_id, |doc| revision(doc, request.version)).await {
+            Some(Ok(base)) => base,
+            Some(Err(e)) => {
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), e.to_string())).await;
+                return;
+            }
+            None => {
+                let error = DocumentError::NotFound(request.document_id.clone());
+                clients.send_to(client_id, &message.error_reply(client_id.to_string(), error.to_string())).await;
+                return;
+            }
+        };
+        let Ok(tenant) = state.tenants.get(tenant.id()) else {
+            return;
+        };
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            let client_id = client_id.to_string();
+            let shutdown = shutdown.clone();
+            let request_id = message.request_id().map(str::to_string);
+
+            async move {
+                loop {
+                    let annotations = Self::with_document(&state, &tenant, &document_id, |doc| DiffAnnotationsMessage {
+                        document_id: document_id.clone(),
+                        base_version: request.version,
+                        version: doc.version(),
+                        ranges: diff(&base, doc),
+                    })
+                    .await;
+                    let message = match annotations.map(|annotations| serde_json::to_value(&annotations)) {
+                        Some(Ok(payload)) => Message::new(MessageType::DiffAnnotations, client_id.clone(), payload)
+                            .with_request_id(request_id.clone()),
+                        Some(Err(e)) => {
+                            log::error!("Failed to serialize diff annotations: {}", e);
+                            return;
+                        }
+                        None => {
+                            let error = DocumentError::NotFound(document_id.clone()).to_string();
+                            let error = Message::new(MessageType::Error, client_id.clone(), json!(error)).with_request_id(request_id);
+                            state.clients.send_to(&client_id, &error).await;
+                            return;
+                        }
+                    };
+                    if !state.clients.send_to(&client_id, &message).await {
+                        return;
+                    }
+
+                    // Wait for the next edit of the document, then for the
+                    // edits following it closely
+                    loop {
+                        let event = tokio::select! {
+                            _ = shutdown.cancelled() => return,
+                            event = operations.recv() => event,
+                        };
+                        match event {
+                            Ok(event) if event.tenant_id == tenant.id() && event.document_id == document_id => break,
+                            Ok(_) => {}
+                            Err(broadcast::error::RecvError::Lagged(_)) => break,
+                            Err(broadcast::error::RecvError::Closed) => return,
+                        }
+                    }
+                    tokio::select! {
+                        _ = shutdown.cancelled() => return,
+                        _ = tokio::time::sleep(DIFF_VIEW_INTERVAL) => {}
+                    }
+                    operations = operations.resubscribe();
+                }
+            }
+        });
+
+        clients.start_diff_view(client_id, task).await;
+    }
+
+    /// Send a client the newest page of its tenant's activity feed, then
+    /// every new activity until the client unsubscribes
+    async fn start_activity_feed(
+        request: SubscribeActivityMessage,
+        message: &Message,
+        client_id: &str,
+        tenant: &Tenant,
+        state: &ServerState,
+        shutdown: &CancellationToken,
+    ) {
+        let limit = request.limit.unwrap_or(DEFAULT_ACTIVITY_LIMIT).clamp(1, MAX_ACTIVITIES_PER_PAGE);
+        let page_message = |page: &ActivityPage, request_id: Option<String>| match serde_json::to_value(page) {
+            Ok(payload) => Some(Message::new(MessageType::ActivityPage, client_id.to_string(), payload).with_request_id(request_id)),
+            Err(e) => {
+                log::error!("Failed to serialize activity page: {}", e);
+                None
+            }
+        };
+        // Subscribe before taking the page so no activity falls in between
+        let mut updates = state.activity.subscribe();
+        let visible = |client_id: &str, document_id: &str, tenant: &Tenant| tenant.passwords().check_access(document_id, client_id).is_ok();
+        let page = state.activity.page(tenant.id(), None, limit, Utc::now(), |document_id| visible(client_id, document_id, tenant));
+        let mut newest = page.activities.first().map_or(0, |activity| activity.id);
+        let Some(first) = page_message(&page, message.request_id().map(str::to_string)) else {
+            return;
+        };
+        if !state.clients.send_to(client_id, &first).await {
+            return;
+        }
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            let client_id = client_id.to_string();
+            let tenant_id = tenant.id().to_string();
+            let shutdown = shutdown.clone();
+
+            async move {
+                let Ok(tenant) = state.tenants.get(&tenant_id) else {
+                    return;
+                };
+                loop {
+                    let update = tokio::select! {
+                        _ = shutdown.cancelled() => return,
+                        update = updates.recv() => update,
+                    };
+                    let message = match update {
+                        Ok((update_tenant, activity))
+                            if update_tenant == tenant_id
+                                && activity.id > newest
+                                && visible(&client_id, &activity.document_id, &tenant) =>
+                        {
+                            newest = activity.id;
+                            match serde_json::to_value(&activity) {
+                                Ok(payload) => Message::new(MessageType::Activity, client_id.clone(), payload),
+                                Err(e) => {
+                                    log::error!("Failed to serialize activity: {}", e);
+                                    continue;
+                                }
+                            }
+                        }
+                        Ok(_) => continue,
+                        // Activities were missed; start over from a fresh page
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            let page = state.activity.page(&tenant_id, None, limit, Utc::now(), |document_id| visible(&client_id, document_id, &tenant));
+                            newest = page.activities.first().map_or(newest, |activity| activity.id);
+                            match serde_json::to_value(&page) {
+                                Ok(payload) => Message::new(MessageType::ActivityPage, client_id.clone(), payload),
+                                Err(e) => {
+                                    log::error!("Failed to serialize activity page: {}", e);
+                                    continue;
+                                }
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    };
+                    if !state.clients.send_to(&client_id, &message).await {
+                        return;
+                    }
+                }
+            }
+        });
+
+        state.clients.start_activity_feed(client_id, task).await;
+    }
+}
+
+/// Hooks into broadcasting for benchmarks, which can't open thousands of
+/// connections per run
+#[cfg(feature = "test-util")]
+impl EditorServer {
+    /// Register a client without a connection, subscribed to a document of
+    /// a tenant, returning the receiver of the frames sent to it
+    pub async fn attach_subscriber(&self, tenant_id: &str, client_id: &str, document_id: &str, capacity: usize) -> mpsc::Receiver<Frame> {
+        let (sender, frames) = mpsc::channel(capacity);
+        let clients = &self.state.clients;
+        clients.add_client(client_id.to_string(), tenant_id.to_string(), sender, CancellationToken::new()).await;
+        clients.subscriptions.write().join(tenant_id, document_id, client_id);
+        frames
+    }
+
+    /// Send a message to every subscriber of a document, as relaying an
+    /// operation does
+    pub async fn broadcast_to_subscribers(&self, tenant_id: &str, document_id: &str, message: &Message) {
+        self.state.clients.broadcast_document(tenant_id, document_id, message, None).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    
+    
+    
+    #[tokio::test]
+    async fn test_server_initialization() {
+        // Create a test server configuration
+        let config = ServerConfig {
+            port: 0, // Let OS assign a port
+            ..Default::default()
+        };
+        
+        // Create a new server instance
+        let server = EditorServer::new(config);
+        
+        // Test connection statistics
+        let stats = server.state.connections.read().await.get_statistics().await;
+        assert_eq!(stats.total_clients, 0);
+        
+        // Test documents map is empty
+        assert!(server.state.documents.read().await.is_empty());
+
+        // Only the default tenant exists
+        assert_eq!(server.tenants().ids(), vec![DEFAULT_TENANT.to_string()]);
+    }
+    
+    type TestSocket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Start a server with the given configuration on a free local port
+    fn start_test_server(config: ServerConfig) -> (Arc<EditorServer>, String) {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = Arc::new(EditorServer::new(ServerConfig {
+            port,
+            ..config
+        }));
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.run().await }
+        });
+        (server, format!("ws://127.0.0.1:{}/ws", port))
+    }
+
+    /// Connect to a test server and return the socket and assigned client ID
+    async fn connect(url: &str) -> (TestSocket, String) {
+        for _ in 0..50 {
+            if let Ok((mut socket, _)) = tokio_tungstenite::connect_async(url).await {
+                let welcome = socket.next().await.expect("welcome message").unwrap();
+                let welcome: serde_json::Value = serde_json::from_str(welcome.to_text().unwrap()).unwrap();
+                let client_id = welcome["payload"]["client_id"].as_str().unwrap().to_string();
+                return (socket, client_id);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server did not start");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_releases_client() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, _) = connect(&url).await;
+        assert_eq!(server.client_count(), 1);
+
+        socket.close(None).await.unwrap();
+        drop(socket);
+
+        let released = async {
+            while server.client_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(1), released).await.expect("client not released");
+
+        let stats = server.state.connections.read().await.get_statistics().await;
+        assert_eq!(stats.disconnected_clients, 1);
+    }
+
+    /// Build an insert operation message
+    fn insert_message(client_id: &str, document_id: &str, path: u32) -> tokio_tungstenite::tungstenite::Message {
+        let operation = Operation::Insert {
+            client_id: client_id.to_string(),
+            character: 'a',
+            position: crate::crdt::Position::new(vec![path]),
+            timestamp: crate::crdt::Timestamp::new(client_id.to_string()),
+        };
+        let payload = serde_json::to_value(OperationMessage::new(operation, document_id.to_string())).unwrap();
+        let message = Message::new(MessageType::Operation, client_id.to_string(), payload)
+            .with_request_id(Some(format!("op-{}", path)));
+        tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&message).unwrap())
+    }
+
+    /// Receive the next message, skipping presence updates, which these
+    /// tests don't look at
+    async fn receive(socket: &mut TestSocket) -> Message {
+        loop {
+            let frame = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("message")
+                .unwrap()
+                .unwrap();
+            let message: Message = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+            if message.message_type() != &MessageType::PresenceUpdate {
+                return message;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_operation_auto_creates_document() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+
+        socket.send(insert_message(&client_id, "doc1", 1)).await.unwrap();
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::Ack);
+        assert_eq!(reply.payload(), &json!({ "document_id": "doc1", "version": 1 }));
+
+        let docs = server.state.documents.read().await;
+        assert_eq!(docs.get("default/doc1").map(|doc| doc.content()), Some("a".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_client_registry_introspection() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut author, author_id) = connect(&url).await;
+        let (_reader, reader_id) = connect(&url).await;
+        assert_eq!(server.client_count(), 2);
+
+        author.send(insert_message(&author_id, "doc1", 1)).await.unwrap();
+        assert_eq!(receive(&mut author).await.message_type(), &MessageType::Ack);
+
+        assert_eq!(server.clients_in_document(DEFAULT_TENANT, "doc1").await.unwrap(), vec![author_id.clone()]);
+        assert!(server.clients_in_document(DEFAULT_TENANT, "doc2").await.unwrap().is_empty());
+
+        let listing = server.clients(DEFAULT_TENANT).await.unwrap();
+        assert_eq!(listing.len(), 2);
+        let author_summary = listing.iter().find(|c| c.client_id == author_id).unwrap();
+        assert_eq!(author_summary.documents, vec!["doc1".to_string()]);
+        let reader_summary = listing.iter().find(|c| c.client_id == reader_id).unwrap();
+        assert!(reader_summary.documents.is_empty());
+
+        assert!(matches!(server.clients("missing").await, Err(TenantError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_strict_policy_rejects_unknown_document() {
+        let (server, url) = start_test_server(ServerConfig {
+            document_policy: DocumentPolicy::Strict,
+            ..Default::default()
+        });
+        let (mut socket, client_id) = connect(&url).await;
+
+        socket.send(insert_message(&client_id, "doc1", 1)).await.unwrap();
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert_eq!(reply.request_id(), Some("op-1"));
+        assert_eq!(reply.payload(), &json!(DocumentError::NotFound("doc1".to_string()).to_string()));
+
+        assert!(server.state.documents.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_document_with_initial_content() {
+        let (server, url) = start_test_server(ServerConfig {
+            document_policy: DocumentPolicy::Strict,
+            ..Default::default()
+        });
+        let (mut socket, client_id) = connect(&url).await;
+
+        let request = Message::new(MessageType::CreateDocument, client_id.clone(), json!({ "document_id": "notes", "initial_content": "héllo" }))
+            .with_request_id(Some("create-1".to_string()));
+        socket.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&request).unwrap())).await.unwrap();
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::DocumentCreated);
+        assert_eq!(reply.request_id(), Some("create-1"));
+        assert_eq!(reply.payload(), &json!({ "document_id": "notes", "version": 5, "length": 5 }));
+        assert_eq!(server.document(DEFAULT_TENANT, "notes").await.unwrap().unwrap().content(), "héllo");
+
+        // The seed is editable like typed text, and the creator owns it
+        socket.send(insert_message(&client_id, "notes", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        assert!(server.document(DEFAULT_TENANT, "notes").await.unwrap().unwrap().content().starts_with('a'));
+        send_message(&mut socket, &client_id, MessageType::SetFrozen, json!({ "document_id": "notes", "frozen": true })).await;
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+
+        send_message(&mut socket, &client_id, MessageType::CreateDocument, json!({ "document_id": "notes" })).await;
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert_eq!(reply.payload(), &json!(DocumentError::AlreadyExists("notes".to_string()).to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_temporary_documents_end_with_session() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+
+        send_message(&mut socket, &client_id, MessageType::CreateDocument, json!({ "document_id": "preview", "initial_content": "# Draft", "temporary": true })).await;
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::DocumentCreated);
+        socket.send(insert_message(&client_id, "preview", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        assert_eq!(server.document(DEFAULT_TENANT, "preview").await.unwrap().unwrap().content(), "a# Draft");
+        assert!(server.state.events().since(DEFAULT_TENANT, None, 10, |_| true).unwrap().events.is_empty());
+
+        socket.close(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while server.document(DEFAULT_TENANT, "preview").await.unwrap().is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("temporary document outlived its session");
+    }
+
+    #[tokio::test]
+    async fn test_create_document_with_large_seed() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+
+        let content: String = (0..3 * SEED_CHUNK_SIZE + 17).map(|i| char::from(b'a' + (i % 26) as u8)).collect();
+        send_message(&mut socket, &client_id, MessageType::CreateDocument, json!({ "document_id": "big", "initial_content": &content })).await;
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::DocumentCreated);
+        assert_eq!(reply.payload()["version"], content.len());
+        let document = server.document(DEFAULT_TENANT, "big").await.unwrap().unwrap();
+        assert_eq!(document.content(), content);
+        assert_eq!(server.state.events().since(DEFAULT_TENANT, None, 10, |_| true).unwrap().events.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_messages_processed_in_order() {
+        let (_server, url) = start_test_server(ServerConfig::default());
+        let (mut author, author_id) = connect(&url).await;
+        let (mut peer, peer_id) = connect(&url).await;
+        author.send(insert_message(&author_id, "doc1", 0)).await.unwrap();
+        assert_eq!(receive(&mut author).await.message_type(), &MessageType::Ack);
+        open(&mut peer, &peer_id, "doc1").await;
+
+        // Send a burst of operations without waiting between them
+        let count = 200;
+        for i in 1..count {
+            author.send(insert_message(&author_id, "doc1", i)).await.unwrap();
+        }
+
+        // The peer must see them in the order they were sent
+        for i in 1..count {
+            let message = receive(&mut peer).await;
+            let op_msg: OperationMessage = serde_json::from_value(message.payload().clone()).unwrap();
+            match op_msg.operation {
+                Operation::Insert { position, .. } => assert_eq!(position.path(), &vec![i]),
+                other => panic!("unexpected operation {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_sender_evicted_after_repeated_failures() {
+        let metrics = Arc::new(ServerMetrics::default());
+        let clients = ClientManager::new(true, metrics.clone());
+        let (live_tx, mut live_rx) = mpsc::channel(32);
+        let (dead_tx, dead_rx) = mpsc::channel(32);
+        let dead_shutdown = CancellationToken::new();
+        clients.add_client("live".to_string(), DEFAULT_TENANT.to_string(), live_tx, CancellationToken::new()).await;
+        clients.add_client("dead".to_string(), DEFAULT_TENANT.to_string(), dead_tx, dead_shutdown.clone()).await;
+        drop(dead_rx);
+
+        let message = Message::new(MessageType::Status, "server".to_string(), json!({ "status": "ping" }));
+        for _ in 0..MAX_SEND_FAILURES - 1 {
+            clients.broadcast(DEFAULT_TENANT, &message, None).await;
+        }
+        assert!(clients.clients.read().await.contains_key("dead"));
+        assert!(!dead_shutdown.is_cancelled());
+
+        clients.broadcast(DEFAULT_TENANT, &message, None).await;
+        assert!(!clients.clients.read().await.contains_key("dead"));
+        assert!(dead_shutdown.is_cancelled());
+        assert_eq!(clients.client_count(), 1);
+        assert_eq!(metrics.snapshot().client_evictions, 1);
+        assert_eq!(metrics.snapshot().send_failures, u64::from(MAX_SEND_FAILURES));
+
+        // The live client kept receiving every broadcast
+        for _ in 0..MAX_SEND_FAILURES {
+            assert!(live_rx.recv().await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_channel_does_not_block_client_map() {
+        let clients = Arc::new(ClientManager::new(true, Arc::new(ServerMetrics::default())));
+        let (live_tx, mut live_rx) = mpsc::channel(32);
+        let (stalled_tx, _stalled_rx) = mpsc::channel(1);
+        stalled_tx.send(Frame::new(Arc::from("backlog"))).await.unwrap();
+        clients.add_client("live".to_string(), DEFAULT_TENANT.to_string(), live_tx, CancellationToken::new()).await;
+        clients.add_client("stalled".to_string(), DEFAULT_TENANT.to_string(), stalled_tx, CancellationToken::new()).await;
+
+        let message = Message::new(MessageType::Status, "server".to_string(), json!({ "status": "ping" }));
+        let broadcast = tokio::spawn({
+            let clients = clients.clone();
+            async move { clients.broadcast(DEFAULT_TENANT, &message, None).await }
+        });
+
+        // While the stalled send waits, the live client already has its copy
+        // and the client map is free for registration
+        tokio::time::timeout(SEND_TIMEOUT / 2, live_rx.recv()).await.unwrap().unwrap();
+        let (late_tx, _late_rx) = mpsc::channel(32);
+        tokio::time::timeout(
+            SEND_TIMEOUT / 2,
+            clients.add_client("late".to_string(), DEFAULT_TENANT.to_string(), late_tx, CancellationToken::new()),
+        )
+        .await
+        .unwrap();
+        assert!(!broadcast.is_finished());
+
+        // The stalled send times out and counts as a failure
+        tokio::time::timeout(SEND_TIMEOUT * 2, broadcast).await.unwrap().unwrap();
+        let failures = clients.clients.read().await["stalled"].failures.load(Ordering::Relaxed);
+        assert_eq!(failures, 1);
+        assert_eq!(clients.client_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_full_channel_drops_ephemeral_messages() {
+        let metrics = Arc::new(ServerMetrics::default());
+        let clients = ClientManager::new(true, metrics.clone());
+        let (stalled_tx, mut stalled_rx) = mpsc::channel(1);
+        stalled_tx.send(Frame::new(Arc::from("backlog"))).await.unwrap();
+        clients.add_client("stalled".to_string(), DEFAULT_TENANT.to_string(), stalled_tx, CancellationToken::new()).await;
+
+        // An ephemeral message has no use late, so it's dropped rather than
+        // waited on, and doesn't count as a failure
+        let message = Message::new(MessageType::Status, "server".to_string(), json!({ "status": "ping" }))
+            .with_stale_at(stale_at(Duration::from_secs(1)));
+        tokio::time::timeout(SEND_TIMEOUT / 2, clients.broadcast(DEFAULT_TENANT, &message, None)).await.unwrap();
+        let failures = clients.clients.read().await["stalled"].failures.load(Ordering::Relaxed);
+        assert_eq!((failures, metrics.stale_messages.get()), (0, 1));
+        assert_eq!(&*stalled_rx.recv().await.unwrap().text, "backlog");
+        assert!(stalled_rx.try_recv().is_err());
+
+        // Past its deadline a frame is stale
+        let frame = Frame { text: Arc::from("ping"), stale_at: stale_at(Duration::from_millis(1)) };
+        assert!(!frame.is_stale(std::time::Instant::now()));
+        assert!(frame.is_stale(std::time::Instant::now() + Duration::from_millis(5)));
+        assert!(!Frame::new(Arc::from("operation")).is_stale(std::time::Instant::now() + Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_shares_one_frame() {
+        let clients = ClientManager::new(true, Arc::new(ServerMetrics::default()));
+        let mut receivers = Vec::new();
+        for i in 0..3 {
+            let (tx, rx) = mpsc::channel(32);
+            clients.add_client(format!("client{}", i), DEFAULT_TENANT.to_string(), tx, CancellationToken::new()).await;
+            receivers.push(rx);
+        }
+
+        let message = Message::new(MessageType::Status, "server".to_string(), json!({ "status": "ping" }));
+        clients.broadcast(DEFAULT_TENANT, &message, None).await;
+
+        let mut frames = Vec::new();
+        for rx in &mut receivers {
+            frames.push(rx.recv().await.unwrap());
+        }
+        assert!(frames.iter().all(|frame| Arc::ptr_eq(&frame.text, &frames[0].text)));
+        assert_eq!(&*frames[0].text, serde_json::to_string(&message).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_repair_round_trip() {
+        let (_server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+
+        for path in [1, 2] {
+            socket.send(insert_message(&client_id, "doc1", path << 24)).await.unwrap();
+            assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        }
+
+        // The client only has the first character
+        let mut replica = Document::new("doc1".to_string());
+        replica.apply(Operation::insert(client_id.clone(), 'a', crate::crdt::Position::new(vec![1 << 24])));
+
+        let request = Message::new(
+            MessageType::RepairRequest,
+            client_id.clone(),
+            serde_json::to_value(RepairRequestMessage::new("doc1".to_string(), &replica)).unwrap(),
+        )
+        .with_request_id(Some("repair-1".to_string()));
+        socket.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&request).unwrap())).await.unwrap();
+
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::RepairResponse);
+        assert_eq!(reply.request_id(), Some("repair-1"));
+        let response: RepairResponseMessage = serde_json::from_value(reply.payload().clone()).unwrap();
+        assert_eq!(response.regions, vec![2]);
+        assert_eq!(response.operations.len(), 1);
+
+        replica.repair_regions(&response.regions, response.operations);
+        assert_eq!(replica.checksum(), response.checksum);
+    }
+
+    #[tokio::test]
+    async fn test_repaired_documents_are_persisted() {
+        let store = Arc::new(crate::storage::MemoryStore::new());
+        let storage = StorageConfig { store: store.clone(), snapshot_interval: Duration::from_secs(3600), ..Default::default() };
+        let (server, url) = start_test_server(ServerConfig { storage, ..Default::default() });
+        let (mut socket, client_id) = connect(&url).await;
+        for path in [1, 2] {
+            socket.send(insert_message(&client_id, "doc1", path << 24)).await.unwrap();
+            assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        }
+        assert_eq!(server.save_snapshots().await, 1);
+
+        // Region 2 is repaired from a replica with another character there
+        let tenant = server.state.tenants.get(DEFAULT_TENANT).unwrap();
+        let repair = Operation::insert("peer".to_string(), 'z', crate::crdt::Position::new(vec![2 << 24]));
+        EditorServer::with_document_mut(&server.state, &tenant, "doc1", |doc| doc.repair_regions(&[2], vec![repair])).await.unwrap();
+
+        // The version stays, but the document is written again as a whole
+        assert_eq!(server.save_snapshots().await, 1);
+        let stored = store.load("default/doc1").unwrap().unwrap();
+        assert!(stored.operations.is_empty());
+        assert_eq!(stored.restore("doc1").unwrap().content(), "az");
+
+        // Edits after the repair are logged and snapshotted as usual
+        socket.send(insert_message(&client_id, "doc1", 3 << 24)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        assert_eq!(store.load("default/doc1").unwrap().unwrap().operations.len(), 1);
+        assert_eq!(server.save_snapshots().await, 1);
+        assert_eq!(store.load("default/doc1").unwrap().unwrap().restore("doc1").unwrap().content(), "aza");
+    }
+
+    /// Policy replacing every inserted character with `*`
+    #[derive(Debug)]
+    struct MaskInserts;
+
+    impl PolicyEngine for MaskInserts {
+        fn evaluate<'a>(&'a self, input: &'a PolicyInput) -> futures::future::BoxFuture<'a, Decision> {
+            Box::pin(async move {
+                match input.operation.clone() {
+                    Operation::Insert { client_id, position, timestamp, .. } => Decision::Transform {
+                        operation: Operation::Insert { client_id, character: '*', position, timestamp },
+                    },
+                    Operation::Delete { .. } | Operation::DeleteRange { .. } => Decision::deny("Deletes are disabled"),
+                }
+            })
+        }
+    }
+
+    /// Type text into a document, one insert per character, and wait for the acks
+    async fn type_text(socket: &mut TestSocket, client_id: &str, document_id: &str, text: &str) {
+        for (i, character) in text.chars().enumerate() {
+            let operation = Operation::insert(client_id.to_string(), character, crate::crdt::Position::new(vec![i as u32 + 1]));
+            let payload = serde_json::to_value(OperationMessage::new(operation, document_id.to_string())).unwrap();
+            let message = Message::new(MessageType::Operation, client_id.to_string(), payload);
+            socket.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&message).unwrap())).await.unwrap();
+            assert_eq!(receive(socket).await.message_type(), &MessageType::Ack);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_moderation_redacts_and_rejects_words() {
+        for (action, expected) in [(ModerationAction::Redact, "oh **** "), (ModerationAction::Reject, "oh  ")] {
+            let (server, url) = start_test_server(ServerConfig {
+                moderation: ModerationConfig {
+                    filter: Some(Arc::new(crate::moderation::WordlistFilter::new(["darn"]))),
+                    action,
+                    batch_delay: Duration::from_millis(10),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            let (mut socket, client_id) = connect(&url).await;
+            type_text(&mut socket, &client_id, "doc1", "oh darn ").await;
+
+            // Each flagged character is deleted, and masked when redacting
+            let operations = if action == ModerationAction::Redact { 8 } else { 4 };
+            for _ in 0..operations {
+                let message = receive(&mut socket).await;
+                assert_eq!(message.message_type(), &MessageType::Operation);
+                assert_eq!(message.client_id(), MODERATION_CLIENT_ID);
+                assert_eq!(message.payload()["source"], "server");
+            }
+            if action == ModerationAction::Reject {
+                assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Error);
+            }
+            let doc = server.document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap();
+            assert_eq!(doc.content(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_operations_carry_their_source() {
+        let (_server, url) = start_test_server(ServerConfig::default());
+        let (mut bot, bot_id) = connect(&url).await;
+        let (mut reader, reader_id) = connect(&url).await;
+        bot.send(insert_message(&bot_id, "doc1", 3)).await.unwrap();
+        assert_eq!(receive(&mut bot).await.message_type(), &MessageType::Ack);
+        open(&mut reader, &reader_id, "doc1").await;
+
+        let operation = Operation::insert(bot_id.clone(), 'a', crate::crdt::Position::new(vec![1]));
+        let tagged = OperationMessage::new(operation, "doc1".to_string()).with_source(OperationSource::Bot);
+        send_message(&mut bot, &bot_id, MessageType::Operation, serde_json::to_value(&tagged).unwrap()).await;
+        assert_eq!(receive(&mut bot).await.message_type(), &MessageType::Ack);
+        let relay = receive(&mut reader).await;
+        assert_eq!(relay.message_type(), &MessageType::Operation);
+        assert_eq!(relay.payload()["source"], "bot");
+
+        // Server operations can't be forged by clients
+        let operation = Operation::insert(bot_id.clone(), 'b', crate::crdt::Position::new(vec![2]));
+        let forged = OperationMessage::new(operation, "doc1".to_string()).with_source(OperationSource::Server);
+        send_message(&mut bot, &bot_id, MessageType::Operation, serde_json::to_value(&forged).unwrap()).await;
+        let reply = receive(&mut bot).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert!(reply.payload().to_string().contains("server operations"));
+    }
+
+    /// Send a message with a payload from a client
+    async fn send_message(socket: &mut TestSocket, client_id: &str, message_type: MessageType, payload: serde_json::Value) {
+        let message = Message::new(message_type, client_id.to_string(), payload);
+        socket.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&message).unwrap())).await.unwrap();
+    }
+
+    /// Open a document, joining it to receive its operations
+    async fn open(socket: &mut TestSocket, client_id: &str, document_id: &str) {
+        send_message(socket, client_id, MessageType::GetDocument, json!({ "document_id": document_id })).await;
+        assert_eq!(receive(socket).await.message_type(), &MessageType::DocumentState);
+    }
+
+    #[tokio::test]
+    async fn test_owner_freezes_document_and_creates_breakouts() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut teacher, teacher_id) = connect(&url).await;
+        let (mut student, student_id) = connect(&url).await;
+
+        // The teacher creates the document and owns it
+        teacher.send(insert_message(&teacher_id, "essay", 1)).await.unwrap();
+        assert_eq!(receive(&mut teacher).await.message_type(), &MessageType::Ack);
+        open(&mut student, &student_id, "essay").await;
+
+        send_message(&mut student, &student_id, MessageType::SetFrozen, json!({ "document_id": "essay", "frozen": true })).await;
+        assert_eq!(receive(&mut student).await.message_type(), &MessageType::Error);
+
+        send_message(&mut teacher, &teacher_id, MessageType::SetFrozen, json!({ "document_id": "essay", "frozen": true })).await;
+        assert_eq!(receive(&mut teacher).await.message_type(), &MessageType::Ack);
+        let notice = receive(&mut student).await;
+        assert_eq!(notice.message_type(), &MessageType::SettingsChanged);
+        assert_eq!(notice.payload()["frozen"], true);
+        assert_eq!(notice.payload()["owner"], teacher_id.as_str());
+
+        student.send(insert_message(&student_id, "essay", 2)).await.unwrap();
+        let reply = receive(&mut student).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert!(reply.payload().to_string().contains("frozen"));
+
+        // Breakouts start from the source and are open to students
+        send_message(&mut teacher, &teacher_id, MessageType::CreateBreakouts, json!({ "document_id": "essay", "students": ["alice"] })).await;
+        let ack = receive(&mut teacher).await;
+        assert_eq!(ack.message_type(), &MessageType::Ack);
+        assert_eq!(ack.payload()["breakouts"], json!(["essay-alice"]));
+        let breakout = server.document(DEFAULT_TENANT, "essay-alice").await.unwrap().unwrap();
+        assert_eq!(breakout.content(), "a");
+
+        student.send(insert_message(&student_id, "essay-alice", 2)).await.unwrap();
+        assert_eq!(receive(&mut student).await.message_type(), &MessageType::Ack);
+        assert_eq!(server.document(DEFAULT_TENANT, "essay").await.unwrap().unwrap().content(), "a");
+    }
+
+    #[tokio::test]
+    async fn test_owner_restricts_characters() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut owner, owner_id) = connect(&url).await;
+        let (mut other, other_id) = connect(&url).await;
+
+        owner.send(insert_message(&owner_id, "log", 1)).await.unwrap();
+        assert_eq!(receive(&mut owner).await.message_type(), &MessageType::Ack);
+        open(&mut other, &other_id, "log").await;
+
+        send_message(&mut other, &other_id, MessageType::SetCharset, json!({ "document_id": "log", "charset": "ascii" })).await;
+        assert_eq!(receive(&mut other).await.message_type(), &MessageType::Error);
+
+        send_message(&mut owner, &owner_id, MessageType::SetCharset, json!({ "document_id": "log", "charset": "ascii" })).await;
+        let ack = receive(&mut owner).await;
+        assert_eq!(ack.message_type(), &MessageType::Ack);
+        assert_eq!(ack.payload()["charset"], "ascii");
+        let notice = receive(&mut other).await;
+        assert_eq!(notice.message_type(), &MessageType::SettingsChanged);
+        assert_eq!(notice.payload()["charset"], "ascii");
+
+        // The restriction applies to the owner too
+        let operation = Operation::insert(owner_id.clone(), 'é', crate::crdt::Position::new(vec![2]));
+        send_message(&mut owner, &owner_id, MessageType::Operation, json!(OperationMessage::new(operation, "log".to_string()))).await;
+        let reply = receive(&mut owner).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert!(reply.payload().to_string().contains("U+00E9"));
+
+        other.send(insert_message(&other_id, "log", 3)).await.unwrap();
+        assert_eq!(receive(&mut other).await.message_type(), &MessageType::Ack);
+        assert_eq!(server.document(DEFAULT_TENANT, "log").await.unwrap().unwrap().content(), "aa");
+    }
+
+    #[tokio::test]
+    async fn test_undo_scopes() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut alice, alice_id) = connect(&url).await;
+        let (mut bob, bob_id) = connect(&url).await;
+
+        type_text(&mut alice, &alice_id, "doc1", "ab").await;
+        bob.send(insert_message(&bob_id, "doc1", 3)).await.unwrap();
+        assert_eq!(receive(&mut bob).await.message_type(), &MessageType::Ack);
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Operation);
+
+        // Alice undoes her own newest edit, not Bob's later one
+        send_message(&mut alice, &alice_id, MessageType::Undo, json!({ "document_id": "doc1" })).await;
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Operation);
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Ack);
+        assert_eq!(receive(&mut bob).await.message_type(), &MessageType::Operation);
+        assert_eq!(server.document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap().content(), "aa");
+
+        // Only the owner chooses the scope
+        send_message(&mut bob, &bob_id, MessageType::SetUndoScope, json!({ "document_id": "doc1", "scope": "global" })).await;
+        assert_eq!(receive(&mut bob).await.message_type(), &MessageType::Error);
+        send_message(&mut alice, &alice_id, MessageType::SetUndoScope, json!({ "document_id": "doc1", "scope": "global" })).await;
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Ack);
+        let notice = receive(&mut bob).await;
+        assert_eq!(notice.message_type(), &MessageType::SettingsChanged);
+        assert_eq!(notice.payload()["undo"], "global");
+
+        // Globally, Alice undoes Bob's edit, then her own
+        for expected in ["a", ""] {
+            send_message(&mut alice, &alice_id, MessageType::Undo, json!({ "document_id": "doc1" })).await;
+            assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Operation);
+            assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Ack);
+            assert_eq!(server.document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap().content(), expected);
+        }
+        send_message(&mut bob, &bob_id, MessageType::Undo, json!({ "document_id": "doc1" })).await;
+        for _ in 0..2 {
+            assert_eq!(receive(&mut bob).await.message_type(), &MessageType::Operation);
+        }
+        let reply = receive(&mut bob).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert!(reply.payload().to_string().contains("Nothing to undo"));
+    }
+
+    #[tokio::test]
+    async fn test_redo_after_undo() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut alice, alice_id) = connect(&url).await;
+
+        type_text(&mut alice, &alice_id, "doc1", "ab").await;
+        send_message(&mut alice, &alice_id, MessageType::Undo, json!({ "document_id": "doc1" })).await;
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Operation);
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Ack);
+        assert_eq!(server.document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap().content(), "a");
+
+        send_message(&mut alice, &alice_id, MessageType::Redo, json!({ "document_id": "doc1" })).await;
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Operation);
+        assert_eq!(receive(&mut alice).await.message_type(), &MessageType::Ack);
+        assert_eq!(server.document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap().content(), "ab");
+
+        send_message(&mut alice, &alice_id, MessageType::Redo, json!({ "document_id": "doc1" })).await;
+        let reply = receive(&mut alice).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert!(reply.payload().to_string().contains("Nothing to redo"));
+    }
+
+    #[tokio::test]
+    async fn test_check_syntax_reports_document_lines() {
+        let (_server, url) = start_test_server(ServerConfig {
+            syntax_checker: Some(Arc::new(crate::blocks::DelimiterChecker)),
+            ..Default::default()
+        });
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "pad", "Q1\n```js\nf(;\n```").await;
+
+        let request = Message::new(MessageType::CheckSyntax, client_id.clone(), json!({ "document_id": "pad" }))
+            .with_request_id(Some("check-1".to_string()));
+        socket.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&request).unwrap())).await.unwrap();
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::SyntaxReport);
+        assert_eq!(reply.request_id(), Some("check-1"));
+        let report: SyntaxReportMessage = serde_json::from_value(reply.payload().clone()).unwrap();
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].language.as_deref(), Some("js"));
+        assert_eq!((report.diagnostics[0].line, report.diagnostics[0].column), (3, 2));
+    }
+
+    #[tokio::test]
+    async fn test_export_route() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "pad", "a<b").await;
+        let routes = export::routes(server.state.clone());
+
+        let response = warp::test::request().path("/documents/pad/export").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body().as_ref(), b"a<b");
+
+        let response = warp::test::request().path("/documents/pad/export?format=html").reply(&routes).await;
+        assert_eq!(response.headers()["content-type"], "text/html; charset=utf-8");
+        assert_eq!(response.body().as_ref(), b"<p>a&lt;b</p>\n");
+
+        let response = warp::test::request().path("/documents/pad/export?format=pdf").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = warp::test::request().path("/documents/missing/export").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = warp::test::request().path("/t/unknown/documents/pad/export").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_highlighted_export_route() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "pad", "```rust\nlet x = 1;\n```").await;
+        let routes = export::routes(server.state.clone());
+
+        let plain = warp::test::request().path("/documents/pad/export?format=html").reply(&routes).await;
+        let response = warp::test::request().path("/documents/pad/export?format=html&theme=InspiredGitHub").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = std::str::from_utf8(response.body()).unwrap();
+        assert!(body.starts_with("<pre class=\"highlight\" style=\"background-color:#ffffff;\"><code class=\"language-rust\"><span style="));
+        assert_ne!(response.headers()["etag"], plain.headers()["etag"]);
+
+        let response = warp::test::request().path("/documents/pad/export?format=html&theme=Nope").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = warp::test::request().path("/documents/pad/export?theme=InspiredGitHub").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reads_wait_for_min_version() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut writer, writer_id) = connect(&url).await;
+        let (mut reader, reader_id) = connect(&url).await;
+        type_text(&mut writer, &writer_id, "pad", "ab").await;
+
+        send_message(&mut reader, &reader_id, MessageType::GetDocument, json!({ "document_id": "pad", "min_version": 2 })).await;
+        let state = receive(&mut reader).await;
+        assert_eq!(state.message_type(), &MessageType::DocumentState);
+        assert_eq!((state.payload()["content"].as_str(), state.payload()["version"].as_u64()), (Some("ab"), Some(2)));
+
+        // A read ahead of the document waits for the write in flight
+        send_message(&mut reader, &reader_id, MessageType::GetDocument, json!({ "document_id": "pad", "min_version": 3 })).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        writer.send(insert_message(&writer_id, "pad", 10)).await.unwrap();
+        let state = loop {
+            let message = receive(&mut reader).await;
+            if message.message_type() != &MessageType::Operation {
+                break message;
+            }
+        };
+        assert_eq!(state.message_type(), &MessageType::DocumentState);
+        assert_eq!(state.payload()["version"], 3);
+
+        // Reads that the document never catches up with give up
+        let routes = export::routes(server.state.clone());
+        let response = warp::test::request().path("/documents/pad/export?min_version=3").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = warp::test::request().path("/documents/pad/export?min_version=9").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "1");
+        send_message(&mut reader, &reader_id, MessageType::GetDocument, json!({ "document_id": "missing" })).await;
+        assert_eq!(receive(&mut reader).await.message_type(), &MessageType::Error);
+    }
+
+    #[tokio::test]
+    async fn test_export_etags() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "pad", "ab").await;
+        let routes = export::routes(server.state.clone());
+
+        let first = warp::test::request().path("/documents/pad/export").reply(&routes).await;
+        let tag = first.headers()["etag"].to_str().unwrap().to_string();
+        assert_eq!(first.headers()["cache-control"], "no-cache");
+
+        // Unchanged documents revalidate without a body; cached renders match
+        let revalidated = warp::test::request()
+            .path("/documents/pad/export")
+            .header("if-none-match", format!("\"other\", W/{}", tag))
+            .reply(&routes)
+            .await;
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+        assert!(revalidated.body().is_empty());
+        let again = warp::test::request().path("/documents/pad/export").reply(&routes).await;
+        assert_eq!((again.headers()["etag"].to_str().unwrap(), again.body()), (tag.as_str(), first.body()));
+
+        // Formats have their own tags
+        let html = warp::test::request().path("/documents/pad/export?format=html").reply(&routes).await;
+        assert_ne!(html.headers()["etag"].to_str().unwrap(), tag);
+
+        // An edit changes the tag and the content
+        let operation = Operation::insert(client_id.clone(), 'c', crate::crdt::Position::new(vec![9]));
+        let payload = serde_json::to_value(OperationMessage::new(operation, "pad".to_string())).unwrap();
+        send_message(&mut socket, &client_id, MessageType::Operation, payload).await;
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        let changed = warp::test::request()
+            .path("/documents/pad/export")
+            .header("if-none-match", tag.as_str())
+            .reply(&routes)
+            .await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_eq!(changed.body().as_ref(), b"abc");
+        assert_ne!(changed.headers()["etag"].to_str().unwrap(), tag);
+    }
+
+    #[tokio::test]
+    async fn test_events_route() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "pad", "abc").await;
+        let routes = events::routes(server.state.clone());
+
+        let response = warp::test::request().path("/events?timeout=0").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let page: crate::websocket::EventPage = serde_json::from_slice(response.body()).unwrap();
+        let kinds: Vec<_> = page.events.iter().map(|e| (e.kind, e.version)).collect();
+        assert_eq!(kinds, vec![(DocumentEventKind::Created, 1), (DocumentEventKind::Changed, 3)]);
+
+        // A poll from the newest cursor waits for the next change
+        let poll = warp::test::request().path(&format!("/events?since={}&timeout=5", page.next)).reply(&routes);
+        let edit = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            socket.send(insert_message(&client_id, "pad", 10)).await.unwrap();
+        };
+        let (response, ()) = tokio::join!(poll, edit);
+        let next: crate::websocket::EventPage = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(next.events.len(), 1);
+        assert_eq!(next.events[0].version, 4);
+
+        let response = warp::test::request().path("/events?since=999").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::GONE);
+        let response = warp::test::request().path("/t/nope/events").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_jobs() {
+        let (server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "notes-1", "abc").await;
+        type_text(&mut socket, &client_id, "todo", "x").await;
+        let delete = OperationMessage::new(Operation::delete(client_id.clone(), crate::crdt::Position::new(vec![2])), "notes-1".to_string());
+        send_message(&mut socket, &client_id, MessageType::Operation, serde_json::to_value(&delete).unwrap()).await;
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+
+        let disabled = admin::routes(server.state.clone(), &AdminConfig::default());
+        let response = warp::test::request().method("GET").path("/admin/jobs/1").reply(&disabled).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let routes = &admin::routes(server.state.clone(), &AdminConfig { token: Some("secret".to_string()) });
+        let start = |body: serde_json::Value, token: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/admin/jobs")
+                .header("authorization", format!("Bearer {}", token))
+                .json(&body)
+                .reply(routes)
+        };
+        assert_eq!(start(json!({ "action": "compact" }), "wrong").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(start(json!({ "action": "reindex" }), "secret").await.status(), StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(start(json!({ "action": "export", "tenant": "nope" }), "secret").await.status(), StatusCode::NOT_FOUND);
+
+        let poll = |id: u64| async move {
+            loop {
+                let response = warp::test::request()
+                    .method("GET")
+                    .path(&format!("/admin/jobs/{}", id))
+                    .header("authorization", "Bearer secret")
+                    .reply(routes)
+                    .await;
+                let job: crate::jobs::Job = serde_json::from_slice(response.body()).unwrap();
+                if job.status.is_finished() {
+                    return job;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+
+        let response = start(json!({ "action": "compact", "tenant": DEFAULT_TENANT, "prefix": "notes-" }), "secret").await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let id = serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()["id"].as_u64().unwrap();
+        let job = poll(id).await;
+        assert_eq!(job.status, crate::jobs::JobStatus::Completed);
+        assert_eq!(job.progress, crate::jobs::JobProgress { done: 1, total: 1 });
+        assert_eq!(job.result, Some(json!({ "documents": 1, "tombstones_removed": 1 })));
+        let document = server.document(DEFAULT_TENANT, "notes-1").await.unwrap().unwrap();
+        assert_eq!((document.content(), document.character_count()), ("ac".to_string(), 2));
+
+        let response = start(json!({ "action": "export", "tenant": DEFAULT_TENANT }), "secret").await;
+        let id = serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()["id"].as_u64().unwrap();
+        let job = poll(id).await;
+        assert_eq!(job.result.unwrap()["documents"], json!({ "notes-1": "ac", "todo": "x" }));
+    }
+
+    #[tokio::test]
+    async fn test_export_access_checks_and_watermarks() {
+        let (server, url) = start_test_server(ServerConfig {
+            tenants: vec![
+                TenantConfig { id: "acme".to_string(), api_keys: vec!["secret".to_string()], ..Default::default() },
+                TenantConfig { id: "vault".to_string(), encryption: crate::security::EncryptionMode::EndToEnd, ..Default::default() },
+            ],
+            export: ExportConfig { watermark_authors: true, watermark_timestamp: true },
+            ..Default::default()
+        });
+        let (mut socket, client_id) = connect(&url).await;
+        type_text(&mut socket, &client_id, "pad", "ab").await;
+        let routes = export::routes(server.state.clone());
+
+        let response = warp::test::request().path("/documents/pad/export").reply(&routes).await;
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        assert!(body.starts_with(&format!("ab\n\n-- \nAuthors: {}\nExported: ", client_id)), "{}", body);
+
+        // Access keys apply to exports as to connections
+        let response = warp::test::request().path("/t/acme/documents/pad/export").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = warp::test::request().path("/t/acme/documents/pad/export?key=secret").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // End-to-end encrypted documents are never exported
+        let response = warp::test::request().path("/t/vault/documents/pad/export").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(String::from_utf8_lossy(response.body()).contains("end-to-end"));
+    }
+
+    #[tokio::test]
+    async fn test_policy_denies_and_transforms() {
+        let (_server, url) = start_test_server(ServerConfig {
+            policy: Arc::new(MaskInserts),
+            ..Default::default()
+        });
+        let (mut socket, client_id) = connect(&url).await;
+        let (mut peer, peer_id) = connect(&url).await;
+
+        socket.send(insert_message(&client_id, "doc1", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        open(&mut peer, &peer_id, "doc1").await;
+        socket.send(insert_message(&client_id, "doc1", 2)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        let relayed = receive(&mut peer).await;
+        let op: OperationMessage = serde_json::from_value(relayed.payload().clone()).unwrap();
+        assert!(matches!(op.operation, Operation::Insert { character: '*', .. }));
+
+        let delete = Operation::delete(client_id.clone(), crate::crdt::Position::new(vec![1]));
+        let payload = serde_json::to_value(OperationMessage::new(delete, "doc1".to_string())).unwrap();
+        let message = Message::new(MessageType::Operation, client_id.clone(), payload);
+        socket.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&message).unwrap())).await.unwrap();
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::Error);
+        assert!(reply.payload().to_string().contains("Deletes are disabled"));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_operation_not_relayed() {
+        let (_server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        let (mut peer, peer_id) = connect(&url).await;
+
+        socket.send(insert_message(&client_id, "doc1", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        open(&mut peer, &peer_id, "doc1").await;
+        socket.send(insert_message(&client_id, "doc1", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        socket.send(insert_message(&client_id, "doc1", 2)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+
+        // The peer doesn't see the retried insert again
+        let relayed = receive(&mut peer).await;
+        let op: OperationMessage = serde_json::from_value(relayed.payload().clone()).unwrap();
+        assert_eq!(op.operation.position().path(), &[2]);
+    }
+
+    #[tokio::test]
+    async fn test_operations_addressed_by_slug() {
+        let (_server, url) = start_test_server(ServerConfig::default());
+        let (mut socket, client_id) = connect(&url).await;
+        let (mut peer, peer_id) = connect(&url).await;
+
+        socket.send(insert_message(&client_id, "doc1", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        open(&mut peer, &peer_id, "doc1").await;
+
+        let set_slug = |slug: &str| {
+            let payload = json!({ "document_id": "doc1", "slug": slug });
+            let message = Message::new(MessageType::SetSlug, client_id.clone(), payload);
+            tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(&message).unwrap())
+        };
+        socket.send(set_slug("meeting-notes")).await.unwrap();
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply.message_type(), &MessageType::Ack);
+        assert_eq!(reply.payload()["slug"], "meeting-notes");
+
+        // A slug may not shadow another document's ID
+        socket.send(insert_message(&client_id, "doc2", 1)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        socket.send(set_slug("doc2")).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Error);
+
+        // Edits through the slug land in the document and relay under its ID
+        socket.send(insert_message(&client_id, "meeting-notes", 2)).await.unwrap();
+        assert_eq!(receive(&mut socket).await.message_type(), &MessageType::Ack);
+        let relayed = receive(&mut peer).await;
+        let op: OperationMessage = serde_json::from_value(relayed.payload().clone()).unwrap();
+        assert_eq!(op.document_id, "doc1");
+    }
+
+    #[tokio::test]
+    async fn test_usage_report_tracks_peak_clients() {
+        let server = EditorServer::new(ServerConfig::default());
+        let (tx, _rx) = mpsc::channel(1);
+        for id in ["a", "b", "c"] {
+            server.state.clients.add_client(id.to_string(), DEFAULT_TENANT.to_string(), tx.clone(), CancellationToken::new()).await;
+        }
+        server.state.clients.remove_client("a").await;
+        server.state.clients.remove_client("b").await;
+
+        let report = server.usage_report().await;
+        assert_eq!(report.documents, "0");
+        assert_eq!(report.peak_clients, "1-9");
+        assert_eq!(server.state.clients.peak_clients(), 3);
+
+        // A new period starts from the clients still connected
+        server.state.clients.reset_peak_clients();
+        assert_eq!(server.state.clients.peak_clients(), 1);
+    }
+
+    /// Generator handing out predictable IDs
+    #[derive(Debug, Default)]
+    struct SequentialIds(AtomicUsize);
+
+    impl IdGenerator for SequentialIds {
+        fn client_id(&self) -> String {
+            format!("client-{}", self.0.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn document_id(&self) -> String {
+            format!("doc-{}", self.0.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_ids_from_generator() {
+        let (_server, url) = start_test_server(ServerConfig {
+            ids: Arc::new(SequentialIds::default()),
+            ..Default::default()
+        });
+
+        let (_first, first_id) = connect(&url).await;
+        let (_second, second_id) = connect(&url).await;
+        assert_eq!(first_id, "client-1");
+        assert_eq!(second_id, "client-2");
+    }
+
     #[tokio::test]
     async fn test_websocket_connection() {
         // This would test the WebSocket connection flow
@@ -0,0 +1,224 @@
+/*
+ * File: src/websocket/skew.rs
+ * Purpose: Detect clients whose operation timestamps are skewed
+ *
+ * A correct client never turns its logical clock back and merges the
+ * clocks it sees, so its operations on a document carry clocks that don't
+ * decrease and stay close to the document clock, the highest clock the
+ * document has seen. Buggy clients show up as operations that:
+ * - regress: carry a clock below the client's previous operation on the
+ *   same document
+ * - jump ahead: carry a clock more than `max_ahead` past the document clock
+ *
+ * A single skewed operation is not a bug: clocks of clients that were
+ * offline may lag. A client is flagged once `threshold` of its latest
+ * `window` operations were skewed, and stays flagged until an operator
+ * clears it. Flagged clients are listed in the admin API:
+ *
+ *   GET    /admin/skew
+ *   POST   /admin/skew/<tenant>/<client_id>/release
+ *   DELETE /admin/skew/<tenant>/<client_id>
+ *
+ * With `quarantine` on, the operations of flagged clients are held instead
+ * of applied, up to `QUARANTINE_CAPACITY` per client, and the client gets
+ * an error. Releasing a client applies its held operations and clears the
+ * flag; deleting it discards them. Unflagged clients are forgotten when
+ * they disconnect.
+ */
+
+use std::collections::{HashMap, VecDeque};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crdt::Operation;
+
+/// Most operations held per quarantined client
+pub const QUARANTINE_CAPACITY: usize = 1000;
+
+/// Thresholds of timestamp skew detection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkewConfig {
+    /// How far past the document clock an operation's clock may be
+    pub max_ahead: u64,
+    /// Latest operations of a client considered
+    pub window: usize,
+    /// Skewed operations within the window that flag a client
+    pub threshold: usize,
+    /// Hold the operations of flagged clients for an operator to validate
+    pub quarantine: bool,
+}
+
+impl Default for SkewConfig {
+    fn default() -> Self {
+        Self {
+            max_ahead: 10_000,
+            window: 20,
+            threshold: 5,
+            quarantine: false,
+        }
+    }
+}
+
+/// How an operation's clock compares to what was seen before
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Skew {
+    /// Below the client's previous clock on the document
+    Regressed,
+    /// Too far past the document clock
+    Ahead,
+}
+
+/// Skew errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SkewError {
+    #[error("Operation quarantined: client {0} sends skewed timestamps")]
+    Quarantined(String),
+    #[error("Quarantine of client {0} is full")]
+    QuarantineFull(String),
+    #[error("Client {0} is not flagged")]
+    NotFlagged(String),
+}
+
+/// An operation held while its client is quarantined
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeldOperation {
+    pub document_id: String,
+    pub operation: Operation,
+}
+
+/// What was detected for a client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkewReport {
+    pub tenant_id: String,
+    pub client_id: String,
+    /// Operations observed
+    pub operations: u64,
+    pub regressed: u64,
+    pub ahead: u64,
+    pub flagged: bool,
+    /// Operations held for validation
+    pub held: Vec<HeldOperation>,
+}
+
+#[derive(Debug, Default)]
+struct ClientSkew {
+    /// Latest clock of the client per document
+    clocks: HashMap<String, u64>,
+    /// Whether each of the latest operations was skewed
+    recent: VecDeque<bool>,
+    operations: u64,
+    regressed: u64,
+    ahead: u64,
+    flagged: bool,
+    held: Vec<HeldOperation>,
+}
+
+/// Timestamp skew of the clients of every tenant
+#[derive(Debug)]
+pub struct SkewDetector {
+    config: SkewConfig,
+    /// Clients by tenant ID and client ID
+    clients: Mutex<HashMap<(String, String), ClientSkew>>,
+}
+
+impl SkewDetector {
+    /// Create a detector with the given thresholds
+    pub fn new(config: SkewConfig) -> Self {
+        Self {
+            config,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check an operation a client is about to apply, given the clock of
+    /// the document. Fails when the client is quarantined; the operation
+    /// is then held instead of applied.
+    pub fn observe(
+        &self,
+        tenant_id: &str,
+        client_id: &str,
+        document_id: &str,
+        document_clock: u64,
+        operation: &Operation,
+    ) -> Result<Option<Skew>, SkewError> {
+        let mut clients = self.clients.lock();
+        let client = clients.entry((tenant_id.to_string(), client_id.to_string())).or_default();
+        let clock = operation.timestamp().logical_clock();
+
+        let skew = match client.clocks.get(document_id) {
+            Some(&previous) if clock < previous => Some(Skew::Regressed),
+            _ if clock > document_clock.saturating_add(self.config.max_ahead) => Some(Skew::Ahead),
+            _ => None,
+        };
+        let latest = client.clocks.entry(document_id.to_string()).or_default();
+        *latest = (*latest).max(clock);
+        client.operations += 1;
+        match skew {
+            Some(Skew::Regressed) => client.regressed += 1,
+            Some(Skew::Ahead) => client.ahead += 1,
+            None => {}
+        }
+        client.recent.push_back(skew.is_some());
+        if client.recent.len() > self.config.window {
+            client.recent.pop_front();
+        }
+        if !client.flagged && client.recent.iter().filter(|skewed| **skewed).count() >= self.config.threshold {
+            log::warn!("Client {} of tenant {} sends skewed timestamps", client_id, tenant_id);
+            client.flagged = true;
+        }
+
+        if client.flagged && self.config.quarantine {
+            if client.held.len() >= QUARANTINE_CAPACITY {
+                return Err(SkewError::QuarantineFull(client_id.to_string()));
+            }
+            client.held.push(HeldOperation { document_id: document_id.to_string(), operation: operation.clone() });
+            return Err(SkewError::Quarantined(client_id.to_string()));
+        }
+        Ok(skew)
+    }
+
+    /// Get the reports of flagged clients, sorted by tenant and client
+    pub fn flagged(&self) -> Vec<SkewReport> {
+        let clients = self.clients.lock();
+        let mut reports: Vec<SkewReport> = clients
+            .iter()
+            .filter(|(_, client)| client.flagged)
+            .map(|((tenant_id, client_id), client)| SkewReport {
+                tenant_id: tenant_id.clone(),
+                client_id: client_id.clone(),
+                operations: client.operations,
+                regressed: client.regressed,
+                ahead: client.ahead,
+                flagged: client.flagged,
+                held: client.held.clone(),
+            })
+            .collect();
+        reports.sort_by(|a, b| (&a.tenant_id, &a.client_id).cmp(&(&b.tenant_id, &b.client_id)));
+        reports
+    }
+
+    /// Clear a flagged client, returning the operations it had held
+    pub fn clear(&self, tenant_id: &str, client_id: &str) -> Result<Vec<HeldOperation>, SkewError> {
+        let mut clients = self.clients.lock();
+        let key = (tenant_id.to_string(), client_id.to_string());
+        match clients.get_mut(&key) {
+            Some(client) if client.flagged => {
+                client.flagged = false;
+                client.recent.clear();
+                Ok(std::mem::take(&mut client.held))
+            }
+            _ => Err(SkewError::NotFlagged(client_id.to_string())),
+        }
+    }
+
+    /// Forget a disconnected client, unless it is flagged
+    pub fn forget(&self, tenant_id: &str, client_id: &str) {
+        let mut clients = self.clients.lock();
+        let key = (tenant_id.to_string(), client_id.to_string());
+        if clients.get(&key).is_some_and(|client| !client.flagged) {
+            clients.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * File: src/websocket/subscriptions.rs
+ * Purpose: Index of which clients work on which documents
+ *
+ * This module keeps two maps in step:
+ * - document → subscribed clients, so per-document fan-out and rosters
+ *   touch only that document's subscribers instead of every connection
+ * - client → documents, so a disconnect removes a client from all of its
+ *   documents without scanning the index
+ *
+ * Documents are keyed by tenant and ID, so equal document IDs in different
+ * tenants never share subscribers. Empty entries are pruned.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// A document within a tenant
+type DocumentKey = (String, String);
+
+/// Reverse index from documents to their subscribed clients
+#[derive(Debug, Default)]
+pub struct SubscriptionIndex {
+    /// Subscribed clients per document
+    subscribers: HashMap<DocumentKey, HashSet<String>>,
+    /// Subscribed documents per client
+    documents: HashMap<String, HashSet<DocumentKey>>,
+}
+
+impl SubscriptionIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe a client to a document, returning whether it was new
+    pub fn join(&mut self, tenant_id: &str, document_id: &str, client_id: &str) -> bool {
+        let key = (tenant_id.to_string(), document_id.to_string());
+        let added = self.subscribers.entry(key.clone()).or_default().insert(client_id.to_string());
+        if added {
+            self.documents.entry(client_id.to_string()).or_default().insert(key);
+        }
+        added
+    }
+
+    /// Unsubscribe a client from a document, returning whether it was subscribed
+    pub fn leave(&mut self, tenant_id: &str, document_id: &str, client_id: &str) -> bool {
+        let key = (tenant_id.to_string(), document_id.to_string());
+        let client_id = client_id.to_string();
+        let removed = remove_entry(&mut self.subscribers, &key, &client_id);
+        if removed {
+            remove_entry(&mut self.documents, &client_id, &key);
+        }
+        removed
+    }
+
+    /// Unsubscribe a client from every document
+    pub fn remove_client(&mut self, client_id: &str) {
+        let client_id = client_id.to_string();
+        for key in self.documents.remove(&client_id).unwrap_or_default() {
+            remove_entry(&mut self.subscribers, &key, &client_id);
+        }
+    }
+
+    /// Get the clients subscribed to a document
+    pub fn subscribers(&self, tenant_id: &str, document_id: &str) -> impl Iterator<Item = &str> {
+        self.subscribers
+            .get(&(tenant_id.to_string(), document_id.to_string()))
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Get the IDs of the documents a client is subscribed to
+    pub fn documents(&self, client_id: &str) -> impl Iterator<Item = &str> {
+        self.documents
+            .get(client_id)
+            .into_iter()
+            .flatten()
+            .map(|(_, document_id)| document_id.as_str())
+    }
+
+    /// Number of documents with at least one subscriber
+    pub fn document_count(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+/// Remove a value from a set in a map, dropping the set once it is empty
+fn remove_entry<K: Hash + Eq, V: Hash + Eq>(map: &mut HashMap<K, HashSet<V>>, key: &K, value: &V) -> bool {
+    let Some(set) = map.get_mut(key) else {
+        return false;
+    };
+    let removed = set.remove(value);
+    if set.is_empty() {
+        map.remove(key);
+    }
+    removed
+}
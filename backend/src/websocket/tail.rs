@@ -0,0 +1,153 @@
+/*
+ * File: src/websocket/tail.rs
+ * Purpose: Snapshots and recent operations of active documents, for late joiners
+ *
+ * Every `getDocument` renders the whole text of the document, under the
+ * documents lock. Clients joining a busy document can instead accept a
+ * tail: the reply holds the text the server last rendered for a joiner,
+ * and the operations applied since, which the client applies as if they
+ * had been relayed to it:
+ *
+ *   {"document_id": "notes", "content": "Hel", "version": 3, "tail": [<op 4>, <op 5>]}
+ *
+ * `TailCache` keeps that snapshot and a buffer of up to `TAIL_CAPACITY`
+ * operations per document a joiner asked for, for the most recent
+ * `TAIL_CACHE_DOCUMENTS` documents. Operations are recorded as they are
+ * applied, while the documents lock is held, so the buffer holds exactly
+ * the operations after the snapshot. A full buffer, or one that missed an
+ * operation, is dropped and the next joiner gets a fresh snapshot. Clients
+ * holding an older copy of the document resync through `documentUpdate`,
+ * which computes what they lack.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+use parking_lot::Mutex;
+
+use crate::crdt::{Document, Operation};
+
+/// Most operations buffered after a snapshot
+pub const TAIL_CAPACITY: usize = 512;
+
+/// Most documents with a snapshot, across tenants
+pub const TAIL_CACHE_DOCUMENTS: usize = 1000;
+
+/// A document's state as a snapshot and the operations after it
+#[derive(Debug, Clone)]
+pub struct DocumentTail {
+    pub content: Arc<str>,
+    /// Version of the snapshot
+    pub version: u64,
+    /// Checksum of the snapshot, see `Document::checksum`
+    pub checksum: u64,
+    /// Operations after the snapshot, in the order they were applied
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    content: Arc<str>,
+    version: u64,
+    checksum: u64,
+    operations: VecDeque<Operation>,
+    /// Position in the order documents were joined
+    tick: u64,
+}
+
+impl Entry {
+    fn head(&self) -> u64 {
+        self.version + self.operations.len() as u64
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    ticks: u64,
+}
+
+/// Snapshots and recent operations of documents, keyed by tenant-scoped ID
+#[derive(Debug, Default)]
+pub struct TailCache {
+    entries: Mutex<Entries>,
+}
+
+impl TailCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an operation applied to a document, with the document's
+    /// version after it. Call while holding the documents lock.
+    pub fn record(&self, key: &str, version: u64, operation: &Operation) {
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.by_key.get_mut(key) else {
+            return;
+        };
+        if version != entry.head() + 1 || entry.operations.len() >= TAIL_CAPACITY {
+            entries.by_key.remove(key);
+            return;
+        }
+        entry.operations.push_back(operation.clone());
+    }
+
+    /// Drop a document's snapshot, when it is replaced or removed
+    pub fn forget(&self, key: &str) {
+        self.entries.lock().by_key.remove(key);
+    }
+
+    /// Get a document's state for a joiner: the cached snapshot and the
+    /// operations after it, or a fresh snapshot. Call while holding the
+    /// documents lock.
+    pub fn join(&self, key: &str, document: &Document) -> DocumentTail {
+        let mut entries = self.entries.lock();
+        entries.ticks += 1;
+        let tick = entries.ticks;
+        if let Some(entry) = entries.by_key.get_mut(key) {
+            if entry.head() == document.version() {
+                entry.tick = tick;
+                return DocumentTail {
+                    content: entry.content.clone(),
+                    version: entry.version,
+                    checksum: entry.checksum,
+                    operations: entry.operations.iter().cloned().collect(),
+                };
+            }
+        }
+
+        let entry = Entry {
+            content: Arc::from(document.content()),
+            version: document.version(),
+            checksum: document.checksum(),
+            operations: VecDeque::new(),
+            tick,
+        };
+        let tail = DocumentTail {
+            content: entry.content.clone(),
+            version: entry.version,
+            checksum: entry.checksum,
+            operations: Vec::new(),
+        };
+        entries.by_key.insert(key.to_string(), entry);
+        if entries.by_key.len() > TAIL_CACHE_DOCUMENTS {
+            let oldest = entries.by_key.iter().min_by_key(|(_, entry)| entry.tick).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.by_key.remove(&oldest);
+            }
+        }
+        tail
+    }
+
+    /// Count the documents with a snapshot
+    pub fn len(&self) -> usize {
+        self.entries.lock().by_key.len()
+    }
+
+    /// Check whether no document has a snapshot
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
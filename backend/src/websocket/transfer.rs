@@ -0,0 +1,76 @@
+/*
+ * File: src/websocket/transfer.rs
+ * Purpose: Moving a document from one tenant to another
+ *
+ * Operators move a document between tenants, such as from a course's
+ * workspace to an archive, through the admin API:
+ *
+ *   POST /admin/documents/<tenant>/<document_id>/transfer
+ *        {"to_tenant": "archive", "document_id": "notes-2024", "owner": "client-7"}
+ *
+ * The document leaves the source tenant with its content and history,
+ * its classroom settings, undo scope, checklists and slug, and arrives
+ * under `document_id`, its old ID by default. Ownership is remapped to
+ * `owner` when given; client IDs belong to connections, so the old owner
+ * can't act in the other tenant anyway. Undo histories stay behind, as
+ * they are per client. The move happens under the documents lock: no
+ * operation lands on the document in between, and a failed move leaves
+ * it where it was.
+ *
+ * A move is refused when the target already has the document, when the
+ * tenants encrypt differently, or when the document exceeds the target's
+ * size limit. Temporary documents are bound to their client's session and
+ * never move. Every move is written to the audit log, under the
+ * `AUDIT_TARGET` log target, and both tenants' clients get
+ * `documentTransferred`.
+ */
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::tenant::TenantError;
+
+/// Log target of audit records
+pub const AUDIT_TARGET: &str = "coedit::audit";
+
+/// Where to move a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferRequest {
+    pub to_tenant: String,
+    /// ID of the document in the target; the same ID when None
+    #[serde(default)]
+    pub document_id: Option<String>,
+    /// Client to own the document in the target; the owner is kept when None
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+/// A completed move
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub from_tenant: String,
+    pub from_document: String,
+    pub to_tenant: String,
+    pub to_document: String,
+    /// Document version, unchanged by the move
+    pub version: u64,
+}
+
+/// Transfer errors
+#[derive(Debug, Error)]
+pub enum TransferError {
+    #[error(transparent)]
+    Tenant(#[from] TenantError),
+    #[error("Document {0} not found")]
+    NotFound(String),
+    #[error("Document {0} is temporary and can't be moved")]
+    Temporary(String),
+    #[error("Tenant {0} already has a document {1}")]
+    Exists(String, String),
+    #[error("Document {0} would move to where it already is")]
+    SamePlace(String),
+    #[error("Tenants {0} and {1} encrypt documents differently")]
+    Encryption(String, String),
+    #[error("Document {document_id} has {length} characters, over the limit of {limit} of tenant {tenant_id}")]
+    TooLarge { document_id: String, tenant_id: String, length: usize, limit: usize },
+}
@@ -0,0 +1,114 @@
+/*
+ * File: src/websocket/transport.rs
+ * Purpose: Connections carrying protocol messages, over sockets or in memory
+ *
+ * The server speaks the protocol over any `Transport`: a connection split
+ * into a sink of outgoing text frames and a stream of incoming ones.
+ * Control frames such as pings stay inside the transport. Implementations:
+ * - warp WebSockets, for clients connecting to the server
+ * - tokio-tungstenite WebSockets, for connecting to a server
+ * - `MemoryTransport`, either end of an in-memory `duplex`
+ *
+ * `EditorServer::connect_in_process` serves a client over a duplex without
+ * binding a port, so routing, rooms and sync can be tested
+ * deterministically; tests over real sockets are kept to a smoke suite.
+ */
+
+use std::{pin::Pin, sync::Arc};
+use futures::{future, Sink, SinkExt, Stream, StreamExt};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_tungstenite::{tungstenite, WebSocketStream};
+use tokio_util::sync::PollSender;
+
+/// Frames buffered in each direction of an in-memory duplex
+pub const MEMORY_CHANNEL_CAPACITY: usize = 32;
+
+/// Transport errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TransportError {
+    #[error("Connection closed")]
+    Closed,
+    #[error("Transport error: {0}")]
+    Failed(String),
+}
+
+/// Outgoing half of a connection, taking shared text frames
+pub type FrameSink = Pin<Box<dyn Sink<Arc<str>, Error = TransportError> + Send>>;
+
+/// Incoming half of a connection, yielding text frames
+pub type FrameStream = Pin<Box<dyn Stream<Item = Result<String, TransportError>> + Send>>;
+
+/// A connection carrying protocol messages as text frames
+pub trait Transport: Send + 'static {
+    /// Split the connection into its outgoing and incoming halves
+    fn split(self) -> (FrameSink, FrameStream);
+}
+
+impl Transport for warp::ws::WebSocket {
+    fn split(self) -> (FrameSink, FrameStream) {
+        let (sink, stream) = StreamExt::split(self);
+        // warp's Message owns its text, so the shared frame is copied only
+        // here, as it is written to the socket
+        let sink = sink
+            .with(|frame: Arc<str>| future::ok::<_, warp::Error>(warp::ws::Message::text(&*frame)))
+            .sink_map_err(|e| TransportError::Failed(e.to_string()));
+        let stream = stream.filter_map(|frame| {
+            future::ready(match frame {
+                Ok(frame) => frame.to_str().ok().map(|text| Ok(text.to_string())),
+                Err(e) => Some(Err(TransportError::Failed(e.to_string()))),
+            })
+        });
+        (Box::pin(sink), Box::pin(stream))
+    }
+}
+
+impl<S> Transport for WebSocketStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn split(self) -> (FrameSink, FrameStream) {
+        let (sink, stream) = StreamExt::split(self);
+        let sink = sink
+            .with(|frame: Arc<str>| future::ok::<_, tungstenite::Error>(tungstenite::Message::Text(frame.to_string())))
+            .sink_map_err(|e| TransportError::Failed(e.to_string()));
+        let stream = stream.filter_map(|frame| {
+            future::ready(match frame {
+                Ok(tungstenite::Message::Text(text)) => Some(Ok(text)),
+                Ok(_) => None,
+                Err(e) => Some(Err(TransportError::Failed(e.to_string()))),
+            })
+        });
+        (Box::pin(sink), Box::pin(stream))
+    }
+}
+
+/// One end of an in-memory connection. Closing or dropping one end ends
+/// the other end's stream.
+#[derive(Debug)]
+pub struct MemoryTransport {
+    outgoing: mpsc::Sender<Arc<str>>,
+    incoming: mpsc::Receiver<Arc<str>>,
+}
+
+/// Create the two ends of an in-memory connection
+pub fn duplex() -> (MemoryTransport, MemoryTransport) {
+    let (a_tx, a_rx) = mpsc::channel(MEMORY_CHANNEL_CAPACITY);
+    let (b_tx, b_rx) = mpsc::channel(MEMORY_CHANNEL_CAPACITY);
+    (
+        MemoryTransport { outgoing: a_tx, incoming: b_rx },
+        MemoryTransport { outgoing: b_tx, incoming: a_rx },
+    )
+}
+
+impl Transport for MemoryTransport {
+    fn split(self) -> (FrameSink, FrameStream) {
+        let sink = PollSender::new(self.outgoing).sink_map_err(|_| TransportError::Closed);
+        let stream = ReceiverStream::new(self.incoming).map(|frame| Ok(frame.to_string()));
+        (Box::pin(sink), Box::pin(stream))
+    }
+}
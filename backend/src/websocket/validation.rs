@@ -0,0 +1,108 @@
+/*
+ * File: src/websocket/validation.rs
+ * Purpose: Schema validation of outbound messages
+ *
+ * This module checks every message the server is about to send against
+ * the protocol schema:
+ * - Required envelope fields are present
+ * - Payloads match the structure expected for their message type
+ * - Frames stay within the maximum size
+ *
+ * Debug builds panic on violations so protocol regressions fail tests;
+ * release builds log them and send the message anyway.
+ */
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    presence::PresenceUpdate,
+    websocket::{
+        activity::{Activity, ActivityPage},
+        message::{
+            ChecklistOperationMessage, ChecklistStateMessage, CursorUpdateMessage, DiffAnnotationsMessage, DocumentCreatedMessage, DocumentStateMessage, DocumentUpdateMessage, Message, MessageType,
+            OperationMessage, PlaybackFrameMessage, RepairResponseMessage, SettingsChangedMessage, SyncResponseMessage, SyntaxReportMessage,
+        },
+        transfer::Transfer,
+    },
+};
+
+/// Largest frame the server is allowed to send
+pub const MAX_OUTBOUND_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// A protocol schema violation in an outbound message
+#[derive(Error, Debug, PartialEq)]
+pub enum SchemaViolation {
+    #[error("{0:?} message is missing required field `{1}`")]
+    MissingField(MessageType, &'static str),
+    #[error("{0:?} message has an invalid payload: {1}")]
+    InvalidPayload(MessageType, String),
+    #[error("Frame of {0} bytes exceeds the {1} byte limit")]
+    TooLarge(usize, usize),
+}
+
+/// Validate a message and its serialized form against the protocol schema
+pub fn validate_outbound(message: &Message, encoded: &str) -> Result<(), SchemaViolation> {
+    let message_type = message.message_type().clone();
+    if encoded.len() > MAX_OUTBOUND_FRAME_BYTES {
+        return Err(SchemaViolation::TooLarge(encoded.len(), MAX_OUTBOUND_FRAME_BYTES));
+    }
+    if message.client_id().is_empty() {
+        return Err(SchemaViolation::MissingField(message_type, "client_id"));
+    }
+
+    let payload = message.payload();
+    match message_type {
+        MessageType::Error if !payload.is_string() => {
+            return Err(SchemaViolation::InvalidPayload(message_type, "expected a string".to_string()));
+        }
+        MessageType::Status => require_string(&message_type, payload, "status")?,
+        MessageType::Ack => require_string(&message_type, payload, "document_id")?,
+        MessageType::DocumentCreated => parse::<DocumentCreatedMessage>(&message_type, payload)?,
+        MessageType::Operation => parse::<OperationMessage>(&message_type, payload)?,
+        MessageType::DocumentState => parse::<DocumentStateMessage>(&message_type, payload)?,
+        MessageType::DocumentUpdate => parse::<DocumentUpdateMessage>(&message_type, payload)?,
+        MessageType::SyncResponse => parse::<SyncResponseMessage>(&message_type, payload)?,
+        MessageType::PresenceUpdate => parse::<PresenceUpdate>(&message_type, payload)?,
+        MessageType::CursorUpdate => parse::<CursorUpdateMessage>(&message_type, payload)?,
+        MessageType::ChecklistOperation => parse::<ChecklistOperationMessage>(&message_type, payload)?,
+        MessageType::ChecklistState => parse::<ChecklistStateMessage>(&message_type, payload)?,
+        MessageType::PlaybackFrame => parse::<PlaybackFrameMessage>(&message_type, payload)?,
+        MessageType::RepairResponse => parse::<RepairResponseMessage>(&message_type, payload)?,
+        MessageType::SettingsChanged => parse::<SettingsChangedMessage>(&message_type, payload)?,
+        MessageType::SyntaxReport => parse::<SyntaxReportMessage>(&message_type, payload)?,
+        MessageType::DocumentTransferred => parse::<Transfer>(&message_type, payload)?,
+        MessageType::DiffAnnotations => parse::<DiffAnnotationsMessage>(&message_type, payload)?,
+        MessageType::ActivityPage => parse::<ActivityPage>(&message_type, payload)?,
+        MessageType::Activity => parse::<Activity>(&message_type, payload)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Report a violation: panic in debug builds, log in release builds
+pub fn report_violation(violation: &SchemaViolation) {
+    if cfg!(debug_assertions) {
+        panic!("Outbound protocol violation: {}", violation);
+    } else {
+        log::error!("Outbound protocol violation: {}", violation);
+    }
+}
+
+fn require_string(message_type: &MessageType, payload: &Value, field: &'static str) -> Result<(), SchemaViolation> {
+    match payload.get(field) {
+        Some(Value::String(_)) => Ok(()),
+        Some(_) => Err(SchemaViolation::InvalidPayload(
+            message_type.clone(),
+            format!("`{}` must be a string", field),
+        )),
+        None => Err(SchemaViolation::MissingField(message_type.clone(), field)),
+    }
+}
+
+fn parse<T: DeserializeOwned>(message_type: &MessageType, payload: &Value) -> Result<(), SchemaViolation> {
+    serde_json::from_value::<T>(payload.clone())
+        .map(|_| ())
+        .map_err(|e| SchemaViolation::InvalidPayload(message_type.clone(), e.to_string()))
+}
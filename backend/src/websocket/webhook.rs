@@ -0,0 +1,100 @@
+/*
+ * File: src/websocket/webhook.rs
+ * Purpose: Outbound webhook notifications for server events
+ *
+ * This module posts JSON event payloads to an operator-configured HTTP
+ * endpoint. Delivery is best-effort: failures are logged and never block
+ * or fail the editing path.
+ */
+
+use chrono::{DateTime, Utc};
+use hyper::{client::HttpConnector, Body, Client, Method, Request, Uri};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Webhook delivery errors
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Invalid webhook URL: {0}")]
+    InvalidUrl(String),
+    #[error("Failed to serialize webhook event: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Webhook request failed: {0}")]
+    Request(#[from] hyper::Error),
+    #[error("Webhook endpoint responded with status {0}")]
+    Status(u16),
+}
+
+/// Event envelope delivered to webhook endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// Event name, e.g. `quota.warning`
+    pub event: String,
+    /// Document the event relates to
+    pub document_id: String,
+    /// Event-specific data
+    pub data: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookEvent {
+    /// Create a new webhook event
+    pub fn new(event: &str, document_id: String, data: serde_json::Value) -> Self {
+        Self {
+            event: event.to_string(),
+            document_id,
+            data,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Posts events to a single HTTP endpoint
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    url: Uri,
+    client: Client<HttpConnector>,
+}
+
+impl WebhookNotifier {
+    /// Create a notifier for the given `http://` URL
+    pub fn new(url: &str) -> Result<Self, WebhookError> {
+        let url: Uri = url.parse().map_err(|_| WebhookError::InvalidUrl(url.to_string()))?;
+        if url.scheme_str() != Some("http") {
+            return Err(WebhookError::InvalidUrl(format!("{} (only http:// is supported)", url)));
+        }
+
+        Ok(Self {
+            url,
+            client: Client::new(),
+        })
+    }
+
+    /// Deliver an event and wait for the endpoint to accept it
+    pub async fn send(&self, event: &WebhookEvent) -> Result<(), WebhookError> {
+        let body = serde_json::to_vec(event)?;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .map_err(|e| WebhookError::InvalidUrl(e.to_string()))?;
+
+        let response = self.client.request(request).await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(WebhookError::Status(response.status().as_u16()))
+        }
+    }
+
+    /// Deliver an event in the background, logging failures
+    pub fn notify(&self, event: WebhookEvent) {
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.send(&event).await {
+                log::warn!("Failed to deliver {} webhook: {}", event.event, e);
+            }
+        });
+    }
+}
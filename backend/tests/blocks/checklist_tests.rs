@@ -0,0 +1,119 @@
+/*
+ * File: tests/blocks/checklist_tests.rs
+ * Purpose: Test suite for checklists
+ *
+ * Test Categories:
+ * - Checklist blocks in documents and their HTML export
+ * - Convergence of concurrent toggles, reorders and renames
+ * - Syncing checklists through the server
+ */
+
+use std::collections::HashMap;
+use serde_json::json;
+use crdt_editor_backend::{
+    blocks::{code_blocks, export_with_checklists, parse, Block, Checklist, ExportFormat},
+    fixtures::TestServer,
+    websocket::{
+        message::{ChecklistOperationMessage, ChecklistStateMessage},
+        MessageType,
+    },
+};
+
+fn texts(checklist: &Checklist) -> Vec<(String, bool)> {
+    checklist.items().into_iter().map(|item| (item.text, item.checked)).collect()
+}
+
+#[test]
+fn test_checklist_blocks() {
+    let content = "Groceries:\n```checklist groceries\nignored\n```\n```checklist\nnot a checklist\n```";
+    let blocks = parse(content);
+    assert_eq!(blocks[1], Block::Checklist { name: "groceries".to_string() });
+    assert!(matches!(&blocks[2], Block::Code(code) if code.language.as_deref() == Some("checklist")));
+    assert_eq!(code_blocks(content).len(), 1);
+
+    let mut checklist = Checklist::new();
+    checklist.add("alice", 0, "Milk & eggs");
+    let id = checklist.items()[0].id.clone();
+    checklist.set_checked("alice", &id, true);
+    let html = export_with_checklists(content, ExportFormat::Html, &HashMap::from([("groceries".to_string(), checklist)]));
+    assert!(html.contains("<ul class=\"checklist\" data-checklist=\"groceries\">\n<li><input type=\"checkbox\" disabled checked> Milk &amp; eggs</li>\n</ul>"));
+    assert_eq!(export_with_checklists(content, ExportFormat::Text, &HashMap::new()), content);
+}
+
+#[test]
+fn test_concurrent_toggles_and_reorders_converge() {
+    let mut seed = Checklist::new();
+    for (i, text) in ["milk", "bread", "eggs"].iter().enumerate() {
+        seed.add("seed", i, text);
+    }
+    let ids: Vec<_> = seed.items().into_iter().map(|item| item.id).collect();
+    let mut alice = seed.clone();
+    let mut bob = seed.clone();
+
+    // Both toggle "milk"; Alice moves it last while Bob renames it
+    let from_alice = [
+        alice.set_checked("alice", &ids[0], true).unwrap(),
+        alice.move_item("alice", &ids[0], 2).unwrap(),
+        alice.set_checked("alice", &ids[1], true).unwrap(),
+    ];
+    let from_bob = [
+        bob.set_checked("bob", &ids[0], false).unwrap(),
+        bob.rename("bob", &ids[0], "oat milk").unwrap(),
+        bob.remove(&ids[2]).unwrap(),
+    ];
+    for operation in &from_bob {
+        alice.apply(operation.clone());
+    }
+    for operation in from_alice.iter().rev() {
+        bob.apply(operation.clone());
+    }
+
+    assert_eq!(texts(&alice), texts(&bob));
+    // Concurrent toggles are settled by stamp, here by client ID, so Bob's
+    // uncheck wins; the move and the rename both stay
+    assert_eq!(texts(&alice), [("bread".to_string(), true), ("oat milk".to_string(), false)]);
+
+    let mut late = Checklist::new();
+    for operation in alice.operations() {
+        late.apply(operation);
+    }
+    assert_eq!(texts(&late), texts(&alice));
+}
+
+#[tokio::test]
+async fn test_checklists_sync_through_server() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+    alice.create_document("todo", "```checklist chores\n```").await;
+    bob.get_document("todo").await;
+
+    let mut local = Checklist::new();
+    let operation = local.add(alice.id(), 0, "dishes");
+    let request = ChecklistOperationMessage { document_id: "todo".to_string(), checklist: "chores".to_string(), operation };
+    alice.request(MessageType::ChecklistOperation, &request).await;
+    assert_eq!(alice.expect(MessageType::Ack).await.payload()["changed"], true);
+    let relay = bob.expect(MessageType::ChecklistOperation).await;
+    let relay: ChecklistOperationMessage = serde_json::from_value(relay.payload().clone()).unwrap();
+    assert_eq!(relay.operation, request.operation);
+
+    // Repeats change nothing and are not relayed
+    alice.request(MessageType::ChecklistOperation, &request).await;
+    assert_eq!(alice.expect(MessageType::Ack).await.payload()["changed"], false);
+
+    bob.request(MessageType::GetChecklist, json!({ "document_id": "todo", "checklist": "chores" })).await;
+    let state = bob.expect(MessageType::ChecklistState).await;
+    let state: ChecklistStateMessage = serde_json::from_value(state.payload().clone()).unwrap();
+    assert_eq!(state.items.len(), 1);
+    assert_eq!(state.items[0].text, "dishes");
+    let mut replica = Checklist::new();
+    for operation in state.operations {
+        replica.apply(operation);
+    }
+    assert_eq!(texts(&replica), texts(&local));
+
+    // Checklists belong to existing documents
+    let request = ChecklistOperationMessage { document_id: "missing".to_string(), ..request };
+    alice.request(MessageType::ChecklistOperation, &request).await;
+    alice.expect(MessageType::Error).await;
+}
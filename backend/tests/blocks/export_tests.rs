@@ -0,0 +1,59 @@
+/*
+ * File: tests/blocks/export_tests.rs
+ * Purpose: Test suite for document export
+ *
+ * Test Categories:
+ * - Format parsing
+ * - Byte-for-byte text export
+ * - HTML export of paragraphs and code
+ * - Watermarks
+ */
+
+use chrono::{TimeZone, Utc};
+use crdt_editor_backend::blocks::{export, ExportFormat, Watermark};
+
+#[test]
+fn test_export_formats() {
+    assert_eq!("text".parse::<ExportFormat>(), Ok(ExportFormat::Text));
+    assert_eq!("html".parse::<ExportFormat>(), Ok(ExportFormat::Html));
+    assert_eq!("markdown".parse::<ExportFormat>(), Ok(ExportFormat::Markdown));
+    assert!("pdf".parse::<ExportFormat>().is_err());
+    assert_eq!(ExportFormat::Html.content_type(), "text/html; charset=utf-8");
+    assert_eq!(ExportFormat::Markdown.content_type(), "text/markdown; charset=utf-8");
+}
+
+#[test]
+fn test_text_export_is_verbatim() {
+    let content = "```go\nfunc main() {\n\tfmt.Println(\"hi\")\n}\n```\n";
+    assert_eq!(export(content, ExportFormat::Text), content);
+}
+
+#[test]
+fn test_html_export_preserves_code() {
+    let content = "Intro <b>\nsecond line\n\nNext\n```js\nif (a < b) {\n\t  go();\n}\n```";
+    let html = export(content, ExportFormat::Html);
+
+    assert_eq!(
+        html,
+        "<p>Intro &lt;b&gt;<br>\nsecond line</p>\n<p>Next</p>\n\
+         <pre><code class=\"language-js\">if (a &lt; b) {\n\t  go();\n}</code></pre>\n"
+    );
+}
+
+#[test]
+fn test_watermarks() {
+    assert_eq!(Watermark::default().render(ExportFormat::Text), "");
+
+    let watermark = Watermark {
+        authors: vec!["alice".to_string(), "b<b".to_string()],
+        exported_at: Some(Utc.with_ymd_and_hms(2026, 10, 16, 9, 30, 0).unwrap()),
+    };
+    assert_eq!(
+        watermark.render(ExportFormat::Text),
+        "\n\n-- \nAuthors: alice, b<b\nExported: 2026-10-16T09:30:00Z\n",
+    );
+    assert_eq!(
+        watermark.render(ExportFormat::Html),
+        "<footer class=\"watermark\"><p>Authors: alice, b&lt;b</p><p>Exported: 2026-10-16T09:30:00Z</p></footer>\n",
+    );
+}
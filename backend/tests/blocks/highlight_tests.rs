@@ -0,0 +1,51 @@
+/*
+ * File: tests/blocks/highlight_tests.rs
+ * Purpose: Test suite for syntax highlighting of code blocks
+ *
+ * Test Categories:
+ * - Highlighting tagged code blocks without changing their text
+ * - Untagged blocks, unknown languages and unknown themes
+ */
+
+use crdt_editor_backend::blocks::{export, export_highlighted, highlight, ExportFormat, HighlightError, DEFAULT_THEME};
+
+/// Drop the tags of HTML and decode the escapes syntect and export emit
+fn text_of(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#39;", "'").replace("&amp;", "&")
+}
+
+#[test]
+fn test_highlighting_keeps_code_text() {
+    let code = "fn main() {\n\tlet s = \"<tag> & 'x'\";\n}";
+    let content = format!("Intro\n```rust\n{}\n```\n", code);
+    let html = export_highlighted(&content, DEFAULT_THEME).unwrap();
+
+    assert!(html.starts_with("<p>Intro</p>\n<pre class=\"highlight\""));
+    assert!(html.contains("<code class=\"language-rust\"><span style=\""));
+    let start = html.find("<code").unwrap();
+    let end = html.find("</code>").unwrap();
+    assert_eq!(text_of(&html[start..end]), code);
+}
+
+#[test]
+fn test_plain_blocks_and_unknown_themes() {
+    // Untagged blocks and languages syntect doesn't know stay plain
+    let content = "```\nplain\n```\n```klingon\nqapla'\n```\n";
+    assert_eq!(export_highlighted(content, DEFAULT_THEME).unwrap(), export(content, ExportFormat::Html));
+
+    // Extensions work as tags too
+    assert!(export_highlighted("```py\nx = 1\n```\n", "base16-ocean.dark").unwrap().contains("<span"));
+
+    assert!(highlight::theme_names().contains(&DEFAULT_THEME));
+    assert_eq!(export_highlighted(content, "Nope"), Err(HighlightError::UnknownTheme("Nope".to_string())));
+}
@@ -0,0 +1,116 @@
+/*
+ * File: tests/blocks/import_tests.rs
+ * Purpose: Test suite for plain text and Markdown import
+ *
+ * Test Categories:
+ * - Format parsing and verbatim text import
+ * - Mapping headings, code and task lists onto blocks
+ * - Reports of unmapped constructs
+ * - Operations appending an import to a document
+ */
+
+use crdt_editor_backend::{
+    blocks::{import, import_into, parse, Block, Checklist, Construct, ImportFormat, ImportedChecklist, Unmapped},
+    crdt::Document,
+};
+
+fn markdown(source: &str) -> crdt_editor_backend::blocks::Import {
+    import(source, ImportFormat::Markdown)
+}
+
+#[test]
+fn test_import_formats() {
+    assert_eq!("text".parse::<ImportFormat>(), Ok(ImportFormat::Text));
+    assert_eq!("markdown".parse::<ImportFormat>(), Ok(ImportFormat::Markdown));
+    assert!("docx".parse::<ImportFormat>().is_err());
+
+    let source = "- [ ] stays text\r\n<b>bold</b>";
+    let imported = import(source, ImportFormat::Text);
+    assert_eq!(imported.content, source);
+    assert!(imported.checklists.is_empty() && imported.unmapped.is_empty());
+}
+
+#[test]
+fn test_markdown_maps_onto_blocks() {
+    let source = "Title\n=====\n\nIntro\n\n    let x = 1;\n\n    x + 1\n\n~~~~python\nprint(\"~~~\")\n~~~~\n\n## Done\n- [x] write\n- [ ] review\n* plain item\n";
+    let imported = markdown(source);
+
+    assert_eq!(
+        imported.content,
+        "# Title\n\nIntro\n\n```\nlet x = 1;\n\nx + 1\n```\n\n```python\nprint(\"~~~\")\n```\n\n## Done\n```checklist tasks-1\n```\n* plain item\n",
+    );
+    assert_eq!(
+        imported.checklists,
+        [ImportedChecklist { name: "tasks-1".to_string(), items: vec![("write".to_string(), true), ("review".to_string(), false)] }],
+    );
+    assert!(imported.unmapped.is_empty());
+
+    // The content parses into the blocks the source describes
+    let blocks = parse(&imported.content);
+    let kinds: Vec<&str> = blocks.iter()
+        .map(|block| match block {
+            Block::Text { .. } => "text",
+            Block::Code(_) => "code",
+            Block::Checklist { .. } => "checklist",
+        })
+        .collect();
+    assert_eq!(kinds, ["text", "code", "text", "code", "text", "checklist", "text"]);
+}
+
+#[test]
+fn test_checklist_names_stay_unique() {
+    let source = "```checklist tasks-1\n```\n- [ ] one\n\ntext\n\n- [ ] two";
+    let imported = markdown(source);
+    let names: Vec<&str> = imported.checklists.iter().map(|checklist| checklist.name.as_str()).collect();
+    assert_eq!(names, ["tasks-2", "tasks-3"]);
+
+    let mut document = Document::new("notes".to_string());
+    for operation in markdown("- [ ] old").operations(&document, "alice") {
+        document.apply_operation(operation).unwrap();
+    }
+    let imported = import_into(&document, "- [ ] new", ImportFormat::Markdown);
+    assert_eq!(imported.checklists[0].name, "tasks-2");
+}
+
+#[test]
+fn test_unmapped_constructs_are_reported() {
+    let source = "---\ntitle: Notes\n---\n| a | b |\n|---|:-:|\n| 1 | 2 |\n\n<div>raw</div>\nSee ![chart](chart.png)\n[^1]: A footnote\n- [ ] top\n  - [x] nested";
+    let imported = markdown(source);
+
+    let unmapped: Vec<(usize, Construct)> = imported.unmapped.iter().map(|Unmapped { line, construct }| (*line, *construct)).collect();
+    assert_eq!(
+        unmapped,
+        [
+            (1, Construct::FrontMatter),
+            (4, Construct::Table),
+            (8, Construct::Html),
+            (9, Construct::Image),
+            (10, Construct::Footnote),
+            (12, Construct::NestedTask),
+        ],
+    );
+    // Everything but the task list is kept as it was
+    let (kept, _) = imported.content.split_once("```checklist").unwrap();
+    assert_eq!(kept, "---\ntitle: Notes\n---\n| a | b |\n|---|:-:|\n| 1 | 2 |\n\n<div>raw</div>\nSee ![chart](chart.png)\n[^1]: A footnote\n");
+    assert_eq!(imported.checklists[0].items, [("top".to_string(), false), ("nested".to_string(), true)]);
+}
+
+#[test]
+fn test_operations_append_to_document() {
+    let mut document = Document::new("notes".to_string());
+    for operation in markdown("Intro").operations(&document, "alice") {
+        document.apply_operation(operation).unwrap();
+    }
+    let imported = markdown("- [ ] ship\n- [x] test");
+    for operation in imported.operations(&document, "import") {
+        document.apply_operation(operation).unwrap();
+    }
+    assert_eq!(document.content(), "Intro\n```checklist tasks-1\n```");
+
+    let mut checklist = Checklist::new();
+    for operation in imported.checklists[0].operations("import") {
+        checklist.apply(operation);
+    }
+    let items: Vec<(String, bool)> = checklist.items().into_iter().map(|item| (item.text, item.checked)).collect();
+    assert_eq!(items, [("ship".to_string(), false), ("test".to_string(), true)]);
+}
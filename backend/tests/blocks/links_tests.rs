@@ -0,0 +1,87 @@
+/*
+ * File: tests/blocks/links_tests.rs
+ * Purpose: Test suite for links between documents
+ *
+ * Test Categories:
+ * - Finding links in text, outside code
+ * - Incremental updates of the backlink index
+ * - Links through the server and over HTTP
+ */
+
+use std::collections::BTreeSet;
+use serde_json::{json, Value};
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    blocks::{links, DocumentLink, LinkIndex},
+    fixtures::TestServer,
+    tenant::DEFAULT_TENANT,
+    websocket::MessageType,
+};
+
+fn targets(ids: &[&str]) -> BTreeSet<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn test_links_in_text() {
+    let content = "See [[notes]] and [[budget | the budget]].\n```\n[[in-code]]\n```\n[[ ]] [[two words]] [[open";
+    assert_eq!(
+        links(content),
+        [
+            DocumentLink { target: "notes".to_string(), label: None },
+            DocumentLink { target: "budget".to_string(), label: Some("the budget".to_string()) },
+        ]
+    );
+    assert!(links("[[a\n]]").is_empty());
+}
+
+#[test]
+fn test_backlinks_follow_updates() {
+    let index = LinkIndex::new();
+    index.touch("a");
+    assert_eq!(index.stale(), ["a"]);
+    index.update("a", targets(&["b", "c", "a"]));
+    index.update("d", targets(&["b"]));
+    assert!(index.stale().is_empty());
+    assert_eq!(index.links("a").links, ["b", "c"]);
+    assert_eq!(index.links("b").backlinks, ["a", "d"]);
+    assert!(index.links("a").backlinks.is_empty());
+
+    index.update("a", targets(&["c", "e"]));
+    assert_eq!(index.links("b").backlinks, ["d"]);
+    assert_eq!(index.links("e").backlinks, ["a"]);
+
+    index.remove_document("d");
+    assert!(index.links("b").backlinks.is_empty());
+    assert!(index.links("d").links.is_empty());
+}
+
+#[tokio::test]
+async fn test_links_through_server() {
+    let server = TestServer::in_process();
+    let mut client = server.connect().await;
+    client.create_document("home", "Start at [[guide]] or [[faq]]. ").await;
+    client.create_document("guide", "Back to [[home]]").await;
+    client.request(MessageType::SetSlug, json!({ "document_id": "guide", "slug": "handbook" })).await;
+    client.expect(MessageType::Ack).await;
+
+    let links = server.server().document_links(DEFAULT_TENANT, "handbook").await.unwrap();
+    assert_eq!(links.document_id, "guide");
+    assert_eq!(links.links, ["home"]);
+    assert_eq!(links.backlinks, ["home"]);
+
+    // Links typed later, by slug, are picked up on the next lookup
+    client.create_document("index", "").await;
+    client.type_text("index", "[[handbook]]").await;
+    client.create_document("faq", "[[guide|The guide]]").await;
+    let links = server.server().document_links(DEFAULT_TENANT, "guide").await.unwrap();
+    assert_eq!(links.backlinks, ["faq", "home", "index"]);
+
+    let routes = server.server().routes();
+    let response = warp::test::request().path("/documents/faq/links").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body, json!({ "document_id": "faq", "links": ["guide"], "backlinks": ["home"] }));
+    let response = warp::test::request().path("/t/nobody/documents/faq/links").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,23 @@
+/*
+ * File: tests/blocks/mod.rs
+ * Purpose: Test module organization for document blocks
+ *
+ * Test modules:
+ * - parse_tests: Tests for splitting documents into text and code blocks
+ * - checklist_tests: Tests for checklists and their blocks
+ * - links_tests: Tests for links between documents and backlinks
+ * - export_tests: Tests for text and HTML export
+ * - import_tests: Tests for plain text and Markdown import
+ * - roundtrip_tests: Golden round trips of documents through export and import
+ * - highlight_tests: Tests for syntax highlighting of code blocks
+ * - syntax_tests: Tests for the delimiter syntax checker
+ */
+
+mod parse_tests;
+mod checklist_tests;
+mod links_tests;
+mod export_tests;
+mod import_tests;
+mod roundtrip_tests;
+mod highlight_tests;
+mod syntax_tests;
@@ -0,0 +1,50 @@
+/*
+ * File: tests/blocks/parse_tests.rs
+ * Purpose: Test suite for document block parsing
+ *
+ * Test Categories:
+ * - Text and code blocks with language tags
+ * - Unclosed fences
+ * - Whitespace preservation
+ */
+
+use crdt_editor_backend::blocks::{code_blocks, parse, Block, CodeBlock};
+
+#[test]
+fn test_code_blocks_with_language_tags() {
+    let content = "Task: add two numbers\n```rust\nfn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n```\nGood luck!";
+    let blocks = parse(content);
+
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[0], Block::Text { text: "Task: add two numbers".to_string() });
+    assert_eq!(
+        blocks[1],
+        Block::Code(CodeBlock {
+            language: Some("rust".to_string()),
+            code: "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}".to_string(),
+            first_line: 3,
+            closed: true,
+        })
+    );
+    assert_eq!(blocks[2], Block::Text { text: "Good luck!".to_string() });
+}
+
+#[test]
+fn test_unclosed_and_untagged_fences() {
+    let blocks = code_blocks("```\nplain\n```\n\n```python\ndef f():\n\tpass");
+
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].language, None);
+    assert!(blocks[0].closed);
+    assert_eq!(blocks[1].language.as_deref(), Some("python"));
+    assert_eq!(blocks[1].code, "def f():\n\tpass");
+    assert_eq!(blocks[1].first_line, 6);
+    assert!(!blocks[1].closed);
+}
+
+#[test]
+fn test_plain_documents() {
+    assert!(parse("").is_empty());
+    assert_eq!(parse("just text\n"), vec![Block::Text { text: "just text\n".to_string() }]);
+    assert!(code_blocks("no code here").is_empty());
+}
@@ -0,0 +1,117 @@
+/*
+ * File: tests/blocks/roundtrip_tests.rs
+ * Purpose: Round trips of documents through export and import
+ *
+ * Test Categories:
+ * - Golden Markdown sources, their imported documents and exports
+ * - Import of an export giving back the content and checklists
+ *
+ * Each `tests/golden/markdown/<case>.md` source is imported into a new
+ * document, which must match `<case>.document.txt`, and exported as
+ * Markdown, which must match `<case>.export.md`. Importing the export must
+ * then give back the same document, for text and Markdown alike. Run with
+ * `COEDIT_UPDATE_GOLDEN=1` to rewrite the expected files after an
+ * intended change, and review the diff.
+ */
+
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use crdt_editor_backend::{
+    blocks::{export_with_checklists, import, Checklist, ExportFormat, ImportFormat},
+    crdt::Document,
+};
+
+/// A document with its checklists, as an import leaves it
+struct Imported {
+    document: Document,
+    checklists: HashMap<String, Checklist>,
+}
+
+impl Imported {
+    fn from_source(source: &str, format: ImportFormat) -> Self {
+        let imported = import(source, format);
+        let mut document = Document::new("golden".to_string());
+        for operation in imported.operations(&document, "import") {
+            document.apply_operation(operation).unwrap();
+        }
+        let checklists = imported.checklists.iter()
+            .map(|checklist| {
+                let mut items = Checklist::new();
+                for operation in checklist.operations("import") {
+                    items.apply(operation);
+                }
+                (checklist.name.clone(), items)
+            })
+            .collect();
+        Self { document, checklists }
+    }
+
+    fn export(&self, format: ExportFormat) -> String {
+        export_with_checklists(&self.document.content(), format, &self.checklists)
+    }
+
+    /// Names of the checklists with their items' texts and check marks
+    fn checklist_items(&self) -> Vec<(String, Vec<(String, bool)>)> {
+        let mut items: Vec<_> = self.checklists.iter()
+            .map(|(name, checklist)| {
+                (name.clone(), checklist.items().into_iter().map(|item| (item.text, item.checked)).collect())
+            })
+            .collect();
+        items.sort();
+        items
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/markdown")
+}
+
+/// Compare with an expected file, or rewrite it when updating
+fn assert_golden(path: &Path, actual: &str) {
+    if std::env::var_os("COEDIT_UPDATE_GOLDEN").is_some() {
+        fs::write(path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    assert_eq!(actual, expected, "{} is out of date", path.display());
+}
+
+fn sources() -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = fs::read_dir(golden_dir()).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name.ends_with(".md") && !name.ends_with(".export.md")
+        })
+        .collect();
+    sources.sort();
+    sources
+}
+
+#[test]
+fn test_golden_markdown_round_trips() {
+    let sources = sources();
+    assert!(sources.len() >= 4, "golden sources missing from {}", golden_dir().display());
+    for path in sources {
+        let source = fs::read_to_string(&path).unwrap();
+        let imported = Imported::from_source(&source, ImportFormat::Markdown);
+        let exported = imported.export(ExportFormat::Markdown);
+        assert_golden(&path.with_extension("document.txt"), &imported.document.content());
+        assert_golden(&path.with_extension("export.md"), &exported);
+
+        let again = Imported::from_source(&exported, ImportFormat::Markdown);
+        assert_eq!(again.document.content(), imported.document.content(), "{}", path.display());
+        assert_eq!(again.checklist_items(), imported.checklist_items(), "{}", path.display());
+        assert_eq!(again.export(ExportFormat::Markdown), exported, "{}", path.display());
+    }
+}
+
+#[test]
+fn test_text_round_trips_verbatim() {
+    for path in sources() {
+        let source = fs::read_to_string(&path).unwrap();
+        let imported = Imported::from_source(&source, ImportFormat::Text);
+        assert_eq!(imported.document.content(), source);
+        let again = Imported::from_source(&imported.export(ExportFormat::Text), ImportFormat::Text);
+        assert_eq!(again.document.content(), source, "{}", path.display());
+    }
+}
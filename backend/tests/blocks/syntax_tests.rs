@@ -0,0 +1,41 @@
+/*
+ * File: tests/blocks/syntax_tests.rs
+ * Purpose: Test suite for the delimiter syntax checker
+ *
+ * Test Categories:
+ * - Balanced code
+ * - Mismatched, unmatched and unclosed brackets
+ * - String literals
+ */
+
+use crdt_editor_backend::blocks::{DelimiterChecker, Diagnostic, SyntaxChecker};
+
+#[tokio::test]
+async fn test_balanced_code_passes() {
+    let code = "fn f<'a>(s: &'a str) -> Vec<char> {\n    vec![s.chars().next().unwrap_or('}')]\n}";
+    assert_eq!(DelimiterChecker.check(Some("rust"), code).await, Vec::new());
+    assert!(DelimiterChecker::diagnostics("print(\"(not a paren\")").is_empty());
+}
+
+#[test]
+fn test_bracket_errors() {
+    let diagnostics = DelimiterChecker::diagnostics("foo(]\n)\n{");
+
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic { line: 1, column: 5, message: "`]` does not close `(` from 1:4".to_string() },
+            Diagnostic { line: 2, column: 1, message: "Unmatched `)`".to_string() },
+            Diagnostic { line: 3, column: 1, message: "Unclosed `{`".to_string() },
+        ]
+    );
+}
+
+#[test]
+fn test_unterminated_strings() {
+    let diagnostics = DelimiterChecker::diagnostics("let s = \"open\nlet t = \"escaped \\\" quote\";");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 9));
+    assert_eq!(diagnostics[0].message, "Unterminated string");
+}
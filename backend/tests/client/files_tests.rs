@@ -0,0 +1,132 @@
+/*
+ * File: tests/client/files_tests.rs
+ * Purpose: Test suite for keeping local files in step with documents
+ *
+ * Test Categories:
+ * - Merging file edits with concurrent remote edits
+ * - Reconciling a file and a document when syncing starts
+ * - Picking up the files of a directory
+ */
+
+use std::{fs, path::PathBuf, time::Duration};
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use crdt_editor_backend::{
+    client::{sync_directory, FileSync, ReconnectConfig, SyncConfig},
+    crdt::Position,
+    fixtures::{NetworkSimulator, TestServer},
+    tenant::DEFAULT_TENANT,
+};
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn fast_reconnects() -> ReconnectConfig {
+    ReconnectConfig {
+        min_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        ..Default::default()
+    }
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("coedit-sync-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+async fn server_content(server: &TestServer, document_id: &str) -> Option<String> {
+    server.server().document(DEFAULT_TENANT, document_id).await.unwrap().map(|doc| doc.content())
+}
+
+/// Wait until the server holds a document with the given content
+async fn wait_for_content(server: &TestServer, document_id: &str, content: &str) {
+    timeout(SYNC_TIMEOUT, async {
+        while server_content(server, document_id).await.as_deref() != Some(content) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("{} never became {:?}", document_id, content));
+}
+
+#[tokio::test]
+async fn test_file_and_remote_edits_are_merged() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    let mut laptop = server.connect().await;
+    laptop.type_text("notes", "ab").await;
+
+    let path = temp_dir().join("notes.md");
+    let mut sync = FileSync::start(network.connector(), &path, "notes", "desktop", fast_reconnects()).await.unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "ab");
+
+    // The file and the document change at the same time
+    fs::write(&path, "ab!").unwrap();
+    laptop.insert("notes", 'X', Position::new(vec![1])).await;
+    timeout(SYNC_TIMEOUT, async {
+        while fs::read_to_string(&path).unwrap() != "Xab!" {
+            sync.sync().unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("remote edit written to the file");
+    wait_for_content(&server, "notes", "Xab!").await;
+
+    // Deletions in the file delete the characters they removed
+    fs::write(&path, "X!").unwrap();
+    assert!(sync.sync().unwrap());
+    wait_for_content(&server, "notes", "X!").await;
+    assert!(!sync.sync().unwrap());
+    sync.close().await;
+}
+
+#[tokio::test]
+async fn test_start_reconciles_file_and_document() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    let dir = temp_dir();
+
+    // An empty document takes the file's content
+    fs::write(dir.join("fresh.md"), "local").unwrap();
+    let sync = FileSync::start(network.connector(), dir.join("fresh.md"), "fresh", "desktop-1", fast_reconnects()).await.unwrap();
+    wait_for_content(&server, "fresh", "local").await;
+    sync.close().await;
+
+    // Otherwise the document wins, and the file is kept aside
+    let mut laptop = server.connect().await;
+    laptop.type_text("notes", "ab").await;
+    fs::write(dir.join("notes.md"), "mine").unwrap();
+    let sync = FileSync::start(network.connector(), dir.join("notes.md"), "notes", "desktop-2", fast_reconnects()).await.unwrap();
+    assert_eq!(fs::read_to_string(dir.join("notes.md")).unwrap(), "ab");
+    assert_eq!(fs::read_to_string(dir.join("notes.md.conflict")).unwrap(), "mine");
+    assert_eq!(server_content(&server, "notes").await.as_deref(), Some("ab"));
+    sync.close().await;
+}
+
+#[tokio::test]
+async fn test_directory_files_are_synced() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    let dir = temp_dir();
+    fs::write(dir.join("plan.md"), "one").unwrap();
+    fs::write(dir.join("build.rs"), "fn main() {}").unwrap();
+
+    let shutdown = CancellationToken::new();
+    let config = SyncConfig { poll_interval: Duration::from_millis(10), reconnect: fast_reconnects() };
+    let task = tokio::spawn({
+        let (connector, dir, shutdown) = (network.connector(), dir.clone(), shutdown.clone());
+        async move { sync_directory(connector, &dir, "desktop", config, shutdown).await }
+    });
+    wait_for_content(&server, "plan", "one").await;
+
+    // Files added later are picked up
+    fs::write(dir.join("todo.txt"), "two").unwrap();
+    wait_for_content(&server, "todo", "two").await;
+    fs::write(dir.join("plan.md"), "one more").unwrap();
+    wait_for_content(&server, "plan", "one more").await;
+
+    shutdown.cancel();
+    task.await.unwrap().unwrap();
+    assert_eq!(server_content(&server, "build").await, None);
+}
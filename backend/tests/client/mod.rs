@@ -0,0 +1,15 @@
+/*
+ * File: tests/client/mod.rs
+ * Purpose: Test module organization for the client SDK
+ *
+ * Test modules:
+ * - files_tests: Tests for keeping local files in step with documents
+ * - replica_tests: Tests for pending edits and reconciliation of replicas
+ * - reconnect_tests: Tests for reconnects over a simulated network
+ * - tail_tests: Tests for following the changes to a client's document
+ */
+
+mod files_tests;
+mod replica_tests;
+mod reconnect_tests;
+mod tail_tests;
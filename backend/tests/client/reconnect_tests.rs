@@ -0,0 +1,150 @@
+/*
+ * File: tests/client/reconnect_tests.rs
+ * Purpose: Test suite for clients reconnecting over a simulated network
+ *
+ * Test Categories:
+ * - Exponential backoff between attempts
+ * - Resending edits made offline and catching up on missed ones
+ * - Keeping the deprecations the server announces
+ * - Following who is present
+ */
+
+use std::time::Duration;
+use tokio::time::timeout;
+use crdt_editor_backend::{
+    client::{ClientStatus, EditorClient, ReconnectConfig, Replica},
+    crdt::{Operation, Position},
+    fixtures::{NetworkSimulator, TestServer},
+    tenant::DEFAULT_TENANT,
+    websocket::{Deprecation, ServerConfig},
+};
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn fast_reconnects() -> ReconnectConfig {
+    ReconnectConfig {
+        min_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        ..Default::default()
+    }
+}
+
+async fn server_content(server: &TestServer, document_id: &str) -> String {
+    server.server().document(DEFAULT_TENANT, document_id).await.unwrap().map(|doc| doc.content()).unwrap_or_default()
+}
+
+#[test]
+fn test_backoff_grows_to_limit() {
+    let config = ReconnectConfig {
+        min_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+        ..Default::default()
+    };
+    let delays: Vec<u128> = [1, 2, 3, 4, 5, 40].into_iter().map(|failures| config.backoff(failures).as_millis()).collect();
+    assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+}
+
+#[tokio::test]
+async fn test_failed_attempts_are_retried() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    network.fail_next(3);
+    let client = EditorClient::start(network.connector(), Replica::new("doc1", "phone"), fast_reconnects());
+    client.edit(Operation::insert("phone".to_string(), 'a', Position::spread(1)[0].clone())).unwrap();
+
+    timeout(SYNC_TIMEOUT, client.synced()).await.expect("client syncs once the server is reachable");
+    assert_eq!(network.attempts(), 4);
+    assert_eq!(server_content(&server, "doc1").await, "a");
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_offline_edits_resent_on_reconnect() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    let client = EditorClient::start(network.connector(), Replica::new("doc1", "phone"), fast_reconnects());
+    let mut laptop = server.connect().await;
+    let positions = Position::spread(4);
+
+    client.edit(Operation::insert("phone".to_string(), 'a', positions[0].clone())).unwrap();
+    timeout(SYNC_TIMEOUT, client.synced()).await.expect("first edit syncs");
+    assert_eq!(server_content(&server, "doc1").await, "a");
+
+    // Operations of other clients are relayed while connected
+    laptop.insert("doc1", 'c', positions[2].clone()).await;
+    timeout(SYNC_TIMEOUT, async {
+        while client.content() != "ac" {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("relayed operation merged");
+
+    // Both edit while the network is down
+    network.go_offline();
+    client.edit(Operation::insert("phone".to_string(), 'b', positions[1].clone())).unwrap();
+    laptop.insert("doc1", 'd', positions[3].clone()).await;
+    assert_eq!(client.pending(), 1);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(matches!(client.status(), ClientStatus::Disconnected { .. } | ClientStatus::Connecting));
+
+    // Reconnecting resends the pending edit and catches up on the missed one
+    network.go_online();
+    timeout(SYNC_TIMEOUT, client.synced()).await.expect("client resyncs after reconnecting");
+    assert_eq!(client.pending(), 0);
+    assert_eq!(client.content(), "abcd");
+    assert_eq!(server_content(&server, "doc1").await, "abcd");
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_server_deprecations_are_kept() {
+    let deprecation = Deprecation::new("disconnect", "0.2.0", Some("closing the WebSocket"));
+    let server = TestServer::in_process_with_config(ServerConfig {
+        deprecations: vec![deprecation.clone()],
+        ..Default::default()
+    });
+    let network = NetworkSimulator::new(server.server().clone());
+    let client = EditorClient::start(network.connector(), Replica::new("doc1", "phone"), fast_reconnects());
+    assert!(client.deprecations().is_empty());
+
+    timeout(SYNC_TIMEOUT, client.synced()).await.expect("client connects");
+    assert_eq!(client.deprecations(), vec![deprecation]);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_presence_followed_across_reconnects() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    let client = EditorClient::start(network.connector(), Replica::new("doc1", "phone"), fast_reconnects());
+    client.edit(Operation::insert("phone".to_string(), 'a', Position::spread(1)[0].clone())).unwrap();
+    timeout(SYNC_TIMEOUT, client.synced()).await.expect("client connects");
+
+    let wait_for = |present: usize| {
+        let client = &client;
+        async move {
+            timeout(SYNC_TIMEOUT, async {
+                while client.presence().len() != present {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .expect("presence updated")
+        }
+    };
+    let mut laptop = server.connect().await;
+    laptop.get_document("doc1").await;
+    wait_for(2).await;
+    laptop.close().await;
+    wait_for(1).await;
+
+    // A new connection starts over from the snapshot it joins with
+    network.go_offline();
+    let mut tablet = server.connect().await;
+    tablet.get_document("doc1").await;
+    network.go_online();
+    timeout(SYNC_TIMEOUT, client.synced()).await.expect("client reconnects");
+    wait_for(2).await;
+    client.close().await;
+}
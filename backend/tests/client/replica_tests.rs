@@ -0,0 +1,53 @@
+/*
+ * File: tests/client/replica_tests.rs
+ * Purpose: Test suite for client replicas
+ *
+ * Test Categories:
+ * - Pending local edits and the updates carrying them
+ * - Reconciliation with server updates and relayed operations
+ */
+
+use crdt_editor_backend::{
+    client::Replica,
+    crdt::{Document, DocumentUpdate, Operation, Position},
+};
+
+#[test]
+fn test_replica_tracks_pending_edits() {
+    let positions = Position::spread(3);
+    let mut server = Document::new("doc1".to_string());
+    let mut replica = Replica::new("doc1", "phone");
+    replica.edit(Operation::insert("phone".to_string(), 'a', positions[0].clone())).unwrap();
+    assert_eq!(replica.pending(), 1);
+
+    // The server merges the update and replies with what the replica lacks
+    server.apply(Operation::insert("laptop".to_string(), 'c', positions[2].clone()));
+    let outgoing = replica.outgoing();
+    assert_eq!(outgoing.operations.len(), 1);
+    outgoing.apply_to(&mut server).unwrap();
+    let reply = DocumentUpdate::since(&server, &outgoing.state_vector);
+    assert_eq!(replica.reconcile(&reply).unwrap().len(), 1);
+    assert_eq!(replica.pending(), 0);
+    assert_eq!(replica.document().content(), "ac");
+    assert!(replica.outgoing().is_empty());
+
+    // Relayed operations count as the server's; repeats change nothing
+    let relayed = Operation::insert("laptop".to_string(), 'b', positions[1].clone());
+    assert!(replica.receive(relayed.clone()).unwrap());
+    assert!(!replica.receive(relayed).unwrap());
+    assert_eq!(replica.server_state().get("laptop"), 2);
+    assert!(replica.outgoing().is_empty());
+    assert_eq!(replica.document().content(), "abc");
+}
+
+#[test]
+fn test_restored_replica_resends_pending_edits() {
+    let mut replica = Replica::new("doc1", "phone");
+    replica.edit(Operation::insert("phone".to_string(), 'a', Position::spread(1)[0].clone())).unwrap();
+    let kept = replica.clone();
+
+    let restored = Replica::restore(kept.document().clone(), kept.server_state().clone(), "phone");
+    assert_eq!(restored.author(), "phone");
+    assert_eq!(restored.pending(), 1);
+    assert_eq!(restored.outgoing(), replica.outgoing());
+}
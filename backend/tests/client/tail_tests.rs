@@ -0,0 +1,55 @@
+/*
+ * File: tests/client/tail_tests.rs
+ * Purpose: Test suite for following the changes to a client's document
+ *
+ * Test Categories:
+ * - Smallest replaced span between two texts
+ * - Changes of edits merged from the server
+ */
+
+use std::time::Duration;
+use tokio::time::timeout;
+use crdt_editor_backend::{
+    client::{ContentChange, ContentChanges, EditorClient, ReconnectConfig, Replica},
+    crdt::Position,
+    fixtures::{NetworkSimulator, TestServer},
+};
+
+const CHANGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn change(offset: usize, removed: &str, inserted: &str) -> Option<ContentChange> {
+    Some(ContentChange { offset, removed: removed.to_string(), inserted: inserted.to_string() })
+}
+
+#[test]
+fn test_change_between_texts() {
+    assert_eq!(ContentChange::between("", "hello"), change(0, "", "hello"));
+    assert_eq!(ContentChange::between("hello", "help"), change(3, "lo", "p"));
+    assert_eq!(ContentChange::between("hello", "hello world"), change(5, "", " world"));
+    assert_eq!(ContentChange::between("aaa", "aa"), change(2, "a", ""));
+    assert_eq!(ContentChange::between("héllo", "hallo"), change(1, "é", "a"));
+    assert_eq!(ContentChange::between("same", "same"), None);
+}
+
+#[tokio::test]
+async fn test_changes_follow_merged_edits() {
+    let server = TestServer::in_process();
+    let network = NetworkSimulator::new(server.server().clone());
+    let mut laptop = server.connect().await;
+    laptop.type_text("doc1", "ac").await;
+
+    let client = EditorClient::start(network.connector(), Replica::new("doc1", "tail"), ReconnectConfig::default());
+    let mut changes = ContentChanges::new(&client);
+    let first = timeout(CHANGE_TIMEOUT, changes.next()).await.expect("catch-up arrives");
+    assert_eq!(first, change(0, "", "ac"));
+
+    // A relayed insert between the two characters
+    let positions = Position::spread(2);
+    let between = Position::between(&positions[0], &positions[1]);
+    laptop.insert("doc1", 'b', between).await;
+    let second = timeout(CHANGE_TIMEOUT, changes.next()).await.expect("relayed edit arrives");
+    assert_eq!(second, change(1, "", "b"));
+    assert_eq!(changes.content(), "abc");
+    drop(changes);
+    client.close().await;
+}
@@ -0,0 +1,102 @@
+/*
+ * File: tests/commands/builtin_tests.rs
+ * Purpose: Test suite for the built-in commands
+ *
+ * Test Categories:
+ * - Appending templates and formatting line ends
+ * - Exporting documents and rejecting bad arguments
+ */
+
+use crdt_editor_backend::{
+    commands::{CommandError, CommandHandler, CommandInput, CommandOutput, ExportCommand, FormatCommand, InsertTemplateCommand},
+    crdt::{Document, Operation, Position},
+};
+
+fn document(text: &str) -> Document {
+    let mut doc = Document::new("notes".to_string());
+    for (character, position) in text.chars().zip(Position::spread(text.chars().count())) {
+        doc.apply(Operation::insert("alice".to_string(), character, position));
+    }
+    doc
+}
+
+fn input(name: &str, args: &str, document: Document) -> CommandInput {
+    CommandInput {
+        tenant_id: "default".to_string(),
+        client_id: "alice".to_string(),
+        name: name.to_string(),
+        args: args.to_string(),
+        document,
+    }
+}
+
+async fn apply(handler: &dyn CommandHandler, input: CommandInput) -> String {
+    let Ok(CommandOutput::Operations(operations)) = handler.run(&input).await else {
+        panic!("expected operations");
+    };
+    let mut doc = input.document;
+    for operation in operations {
+        doc.apply_operation(operation).unwrap();
+    }
+    doc.content()
+}
+
+#[tokio::test]
+async fn test_templates_and_formatting() {
+    let templates = InsertTemplateCommand::default().with_template("todo", "\n- [ ] ");
+    assert_eq!(apply(&templates, input("insert-template", "todo", document("Plan"))).await, "Plan\n- [ ] ");
+    assert_eq!(apply(&templates, input("insert-template", "todo", document(""))).await, "\n- [ ] ");
+
+    // Text after deleted characters still lands at the end
+    let mut doc = document("Plan!");
+    let last = doc.visible_characters().last().map(|(_, position)| position.clone()).unwrap();
+    doc.apply(Operation::delete("alice".to_string(), last));
+    assert_eq!(apply(&templates, input("insert-template", "todo", doc)).await, "Plan\n- [ ] ");
+
+    // Nothing sorts between the last character and a tombstone extending
+    // its position with a zero, so the text follows the tombstone
+    let mut doc = document("Plan");
+    let last = doc.visible_characters().last().map(|(_, position)| position.clone()).unwrap();
+    let crowded = Position::new([last.path().as_slice(), &[0]].concat());
+    doc.apply(Operation::insert("bob".to_string(), '?', crowded.clone()));
+    doc.apply(Operation::delete("bob".to_string(), crowded));
+    assert_eq!(apply(&templates, input("insert-template", "todo", doc)).await, "Plan\n- [ ] ");
+
+    // After characters tied at one position, followed by a tombstone
+    let mut doc = document("Plan");
+    let last = doc.visible_characters().last().map(|(_, position)| position.clone()).unwrap();
+    doc.apply(Operation::insert("bob".to_string(), 's', last.clone()));
+    let following = Position::new([last.path().as_slice(), &[1]].concat());
+    doc.apply(Operation::insert("bob".to_string(), '?', following.clone()));
+    doc.apply(Operation::delete("bob".to_string(), following));
+    let content = apply(&templates, input("insert-template", "todo", doc)).await;
+    assert!(content.ends_with("\n- [ ] ") && content.len() == "Plans\n- [ ] ".len(), "{}", content);
+
+    let formatted = apply(&FormatCommand, input("format", "", document("a \t\nb\n  c  \n\nd  "))).await;
+    assert_eq!(formatted, "a\nb\n  c\n\nd");
+    assert_eq!(apply(&FormatCommand, input("format", "", document("clean\n"))).await, "clean\n");
+}
+
+#[tokio::test]
+async fn test_export_and_bad_arguments() {
+    let Ok(CommandOutput::Status { message, data }) = ExportCommand.run(&input("export", "html", document("Hi <b>"))).await else {
+        panic!("expected a status");
+    };
+    assert_eq!(message, "Exported notes as html");
+    assert_eq!(data["content_type"], "text/html; charset=utf-8");
+    assert!(data["content"].as_str().unwrap().contains("Hi &lt;b&gt;"));
+    let Ok(CommandOutput::Status { data, .. }) = ExportCommand.run(&input("export", "", document("Hi"))).await else {
+        panic!("expected a status");
+    };
+    assert_eq!((data["format"].as_str(), data["content"].as_str()), (Some("text"), Some("Hi")));
+
+    let invalid = |result: Result<CommandOutput, CommandError>| matches!(result, Err(CommandError::InvalidArguments { .. }));
+    assert!(invalid(ExportCommand.run(&input("export", "pdf", document(""))).await));
+    assert!(invalid(FormatCommand.run(&input("format", "all", document(""))).await));
+    let templates = InsertTemplateCommand::default().with_template("todo", "- [ ] ");
+    assert!(invalid(templates.run(&input("insert-template", "", document(""))).await));
+    let Err(unknown) = templates.run(&input("insert-template", "meeting", document(""))).await else {
+        panic!("expected an error");
+    };
+    assert_eq!(unknown.to_string(), "Invalid arguments for /insert-template: unknown template meeting; available: todo");
+}
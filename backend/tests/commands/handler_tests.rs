@@ -0,0 +1,100 @@
+/*
+ * File: tests/commands/handler_tests.rs
+ * Purpose: Test suite for command handlers and their registry
+ *
+ * Test Categories:
+ * - Parsing command lines and registering handlers
+ * - Running built-in and custom commands through the server
+ */
+
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use serde_json::json;
+use crdt_editor_backend::{
+    commands::{
+        parse_command, CommandError, CommandHandler, CommandInput, CommandOutput, CommandRegistry, InsertTemplateCommand,
+        COMMAND_CLIENT_ID,
+    },
+    crdt::{Operation, OperationSource},
+    fixtures::TestServer,
+    websocket::{message::{CommandMessage, OperationMessage}, MessageType, ServerConfig},
+};
+
+/// Command replacing the document's text with its uppercase
+#[derive(Debug)]
+struct Shout;
+
+impl CommandHandler for Shout {
+    fn run<'a>(&'a self, input: &'a CommandInput) -> BoxFuture<'a, Result<CommandOutput, CommandError>> {
+        Box::pin(async move {
+            let mut operations = Vec::new();
+            for (character, position) in input.document.visible_characters().filter(|(c, _)| c.is_lowercase()) {
+                operations.push(Operation::delete(COMMAND_CLIENT_ID.to_string(), position.clone()));
+                let upper = character.to_ascii_uppercase();
+                operations.push(Operation::insert(COMMAND_CLIENT_ID.to_string(), upper, input.document.position_after(position)));
+            }
+            Ok(CommandOutput::Operations(operations))
+        })
+    }
+}
+
+fn command(document_id: &str, line: &str) -> CommandMessage {
+    CommandMessage { document_id: document_id.to_string(), command: line.to_string() }
+}
+
+#[test]
+fn test_parse_and_register() {
+    assert_eq!(parse_command("/insert-template  meeting notes ").unwrap(), ("insert-template".to_string(), "meeting notes".to_string()));
+    assert_eq!(parse_command(" /format").unwrap(), ("format".to_string(), String::new()));
+    assert!(matches!(parse_command("format"), Err(CommandError::NotACommand(_))));
+    assert!(matches!(parse_command("/Format"), Err(CommandError::InvalidName(_))));
+    assert!(matches!(parse_command("/"), Err(CommandError::InvalidName(_))));
+
+    let mut registry = CommandRegistry::default();
+    assert_eq!(registry.names().collect::<Vec<_>>(), ["export", "format", "insert-template"]);
+    registry.register("shout", Arc::new(Shout)).unwrap();
+    assert!(registry.get("shout").is_some());
+    assert!(matches!(registry.register("-shout", Arc::new(Shout)), Err(CommandError::InvalidName(_))));
+    assert_eq!(CommandRegistry::empty().names().count(), 0);
+}
+
+#[tokio::test]
+async fn test_commands_through_the_server() {
+    let mut commands = CommandRegistry::default();
+    commands.register("shout", Arc::new(Shout)).unwrap();
+    let templates = InsertTemplateCommand::default().with_template("sign", "!");
+    commands.register("insert-template", Arc::new(templates)).unwrap();
+    let server = TestServer::in_process_with_config(ServerConfig { commands, ..Default::default() });
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+    alice.create_document("notes", "hi").await;
+    bob.get_document("notes").await;
+
+    // Every client of the document, the sender included, gets the server's
+    // operations
+    alice.request(MessageType::Command, command("notes", "/shout")).await;
+    for client in [&mut alice, &mut bob] {
+        let relay = client.expect(MessageType::Operation).await;
+        let operation: OperationMessage = serde_json::from_value(relay.payload().clone()).unwrap();
+        assert_eq!((relay.client_id(), operation.source), (COMMAND_CLIENT_ID, OperationSource::Server));
+    }
+    let ack = alice.expect(MessageType::Ack).await;
+    assert_eq!((&ack.payload()["command"], &ack.payload()["applied"]), (&json!("shout"), &json!(4)));
+    alice.request(MessageType::Command, command("notes", "/insert-template sign")).await;
+    alice.expect(MessageType::Ack).await;
+    assert_eq!(server.server().document("default", "notes").await.unwrap().unwrap().content(), "HI!");
+
+    alice.request(MessageType::Command, command("notes", "/export")).await;
+    let ack = alice.expect(MessageType::Ack).await;
+    assert_eq!(ack.payload()["data"]["content"], "HI!");
+
+    // Failures reach the sender alone
+    for (document_id, line, error) in [
+        ("notes", "/missing", "Unknown command /missing"),
+        ("other", "/format", "Document not found: other"),
+        ("notes", "/insert-template nope", "Invalid arguments for /insert-template: unknown template nope; available: sign"),
+    ] {
+        alice.request(MessageType::Command, command(document_id, line)).await;
+        assert_eq!(alice.expect(MessageType::Error).await.payload(), error);
+    }
+}
@@ -0,0 +1,12 @@
+/*
+ * File: tests/commands/mod.rs
+ * Purpose: Test module organization for server-side commands
+ *
+ * Test modules:
+ * - builtin_tests: Tests for the built-in commands
+ * - handler_tests: Tests for command parsing, the registry and running
+ *   commands through the server
+ */
+
+mod builtin_tests;
+mod handler_tests;
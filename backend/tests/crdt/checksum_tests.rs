@@ -0,0 +1,162 @@
+/*
+ * File: tests/crdt/checksum_tests.rs
+ * Purpose: Test suite for the incremental content checksum
+ *
+ * Test Categories:
+ * - Checksum agreement between replicas
+ * - Updates on insert and delete
+ * - Stability across garbage collection
+ * - Localizing diverged regions
+ * - Repairing diverged regions
+ */
+
+use crdt_editor_backend::crdt::{ContentHash, Document, Operation, Position, Timestamp};
+
+/// Build an insert with an explicit logical clock
+fn insert_at(client_id: &str, clock: u64, character: char, path: Vec<u32>) -> Operation {
+    let mut timestamp = Timestamp::new(client_id.to_string());
+    for _ in 0..clock {
+        timestamp.increment();
+    }
+    Operation::Insert {
+        client_id: client_id.to_string(),
+        character,
+        position: Position::new(path),
+        timestamp,
+    }
+}
+
+#[test]
+fn test_empty_documents_match() {
+    let a = Document::new("doc_a".to_string());
+    let b = Document::new("doc_b".to_string());
+    assert_eq!(a.checksum(), b.checksum());
+}
+
+#[test]
+fn test_replicas_agree_regardless_of_order() {
+    let ops = vec![
+        insert_at("client1", 1, 'a', vec![1]),
+        insert_at("client2", 1, 'b', vec![2]),
+        insert_at("client1", 2, 'c', vec![3]),
+    ];
+
+    let mut forward = Document::new("test_doc".to_string());
+    let mut reverse = Document::new("test_doc".to_string());
+    for op in &ops {
+        forward.apply(op.clone());
+    }
+    for op in ops.iter().rev() {
+        reverse.apply(op.clone());
+    }
+
+    assert_eq!(forward.content(), reverse.content());
+    assert_eq!(forward.checksum(), reverse.checksum());
+}
+
+#[test]
+fn test_checksum_tracks_edits() {
+    let mut doc = Document::new("test_doc".to_string());
+    let empty = doc.checksum();
+
+    doc.apply(insert_at("client1", 1, 'a', vec![1]));
+    let one = doc.checksum();
+    assert_ne!(one, empty);
+
+    doc.apply(insert_at("client1", 2, 'b', vec![2]));
+    assert_ne!(doc.checksum(), one);
+
+    // Deleting 'b' restores the checksum of "a"
+    doc.apply(Operation::delete("client1".to_string(), Position::new(vec![2])));
+    assert_eq!(doc.checksum(), one);
+
+    // Deleting an already deleted character changes nothing
+    doc.apply(Operation::delete("client1".to_string(), Position::new(vec![2])));
+    assert_eq!(doc.checksum(), one);
+}
+
+#[test]
+fn test_same_text_different_history_differs() {
+    let mut a = Document::new("test_doc".to_string());
+    let mut b = Document::new("test_doc".to_string());
+    a.apply(insert_at("client1", 1, 'x', vec![1]));
+    b.apply(insert_at("client1", 1, 'x', vec![2]));
+
+    // Equal text at different positions is not the same CRDT state
+    assert_eq!(a.content(), b.content());
+    assert_ne!(a.checksum(), b.checksum());
+}
+
+#[test]
+fn test_garbage_collection_keeps_checksum() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.apply(insert_at("client1", 1, 'a', vec![1]));
+    doc.apply(insert_at("client1", 2, 'b', vec![2]));
+    doc.apply(Operation::delete("client1".to_string(), Position::new(vec![1])));
+
+    let before = doc.checksum();
+    doc.collect_garbage();
+    assert_eq!(doc.checksum(), before);
+}
+
+#[test]
+fn test_diverged_regions_localized() {
+    let early = vec![1 << 24];
+    let late = vec![200 << 24];
+
+    let mut a = Document::new("test_doc".to_string());
+    let mut b = Document::new("test_doc".to_string());
+    for doc in [&mut a, &mut b] {
+        doc.apply(insert_at("client1", 1, 'a', early.clone()));
+        doc.apply(insert_at("client1", 2, 'b', late.clone()));
+    }
+    assert!(a.content_hash().diverged_regions(b.content_hash()).is_empty());
+
+    // Only b sees an edit in the late region
+    b.apply(insert_at("client2", 3, 'c', vec![200 << 24, 1]));
+
+    assert_ne!(a.checksum(), b.checksum());
+    let diverged = a.content_hash().diverged_regions(b.content_hash());
+    assert_eq!(diverged, vec![ContentHash::region_of(&Position::new(late))]);
+    assert_eq!(diverged, vec![200]);
+}
+
+#[test]
+fn test_region_operations_filtered() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.apply(insert_at("client1", 1, 'a', vec![1 << 24]));
+    doc.apply(insert_at("client1", 2, 'b', vec![200 << 24]));
+    doc.apply(Operation::delete("client1".to_string(), Position::new(vec![200 << 24])));
+
+    let ops = doc.region_operations(&[200]);
+    assert_eq!(ops.len(), 2);
+    assert!(ops.iter().all(|op| ContentHash::region_of(op.position()) == 200));
+    assert!(doc.region_operations(&[5]).is_empty());
+}
+
+#[test]
+fn test_repair_regions_converges() {
+    let mut server = Document::new("test_doc".to_string());
+    let mut client = Document::new("test_doc".to_string());
+    let shared = insert_at("client1", 1, 'a', vec![1 << 24]);
+    for doc in [&mut server, &mut client] {
+        doc.apply(shared.clone());
+    }
+
+    // The client missed two edits and made one the server never saw
+    server.apply(insert_at("client2", 2, 'b', vec![200 << 24]));
+    server.apply(insert_at("client2", 3, 'c', vec![200 << 24, 1]));
+    server.apply(Operation::delete("client2".to_string(), Position::new(vec![200 << 24])));
+    client.apply(insert_at("client1", 2, 'x', vec![200 << 24, 5]));
+
+    let diverged = server.content_hash().diverged_from(client.content_hash().regions());
+    assert_eq!(diverged, vec![200]);
+
+    client.repair_regions(&diverged, server.region_operations(&diverged));
+
+    assert_eq!(client.content(), "ac");
+    assert_eq!(client.checksum(), server.checksum());
+    assert!(client.content_hash().diverged_regions(server.content_hash()).is_empty());
+    // Untouched regions keep their history
+    assert_eq!(client.region_operations(&[1]).len(), 1);
+}
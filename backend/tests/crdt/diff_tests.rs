@@ -0,0 +1,66 @@
+/*
+ * File: tests/crdt/diff_tests.rs
+ * Purpose: Test suite for changes since an earlier revision
+ *
+ * Test Categories:
+ * - Inserted and removed ranges in content order
+ * - Removed text whose tombstones were collected
+ * - Revisions outside the history
+ */
+
+use crdt_editor_backend::crdt::{diff_since, revision, DiffKind, DiffRange, Document, DocumentError, Operation, Position};
+
+fn range(kind: DiffKind, offset: usize, text: &str) -> DiffRange {
+    DiffRange { kind, offset, text: text.to_string() }
+}
+
+/// "Hello world" edited into "Jello orld!!", with the version of the
+/// original text
+fn edited() -> (Document, u64) {
+    let mut doc = Document::new("notes".to_string());
+    let positions = Position::spread(11);
+    for (character, position) in "Hello world".chars().zip(&positions) {
+        doc.apply(Operation::insert("alice".to_string(), character, position.clone()));
+    }
+    let base = doc.version();
+
+    doc.apply(Operation::delete("bob".to_string(), positions[0].clone()));
+    doc.apply(Operation::insert("bob".to_string(), 'J', doc.position_after(&positions[0])));
+    doc.apply(Operation::delete("bob".to_string(), positions[6].clone()));
+    for (character, position) in "!!?".chars().zip(doc.positions_after(&positions[10], 3)) {
+        doc.apply(Operation::insert("bob".to_string(), character, position));
+    }
+    // Typed and removed again since the base: no change
+    let question = doc.visible_characters().last().map(|(_, position)| position.clone()).unwrap();
+    doc.apply(Operation::delete("bob".to_string(), question));
+    (doc, base)
+}
+
+#[test]
+fn test_ranges_since_revision() {
+    let (doc, base) = edited();
+    assert_eq!(doc.content(), "Jello orld!!");
+    assert_eq!(revision(&doc, base).unwrap().content(), "Hello world");
+
+    let expected = vec![
+        range(DiffKind::Removed, 0, "H"),
+        range(DiffKind::Inserted, 0, "J"),
+        range(DiffKind::Removed, 6, "w"),
+        range(DiffKind::Inserted, 10, "!!"),
+    ];
+    assert_eq!(diff_since(&doc, base).unwrap(), expected);
+    assert_eq!(diff_since(&doc, 0).unwrap(), vec![range(DiffKind::Inserted, 0, "Jello orld!!")]);
+    assert!(diff_since(&doc, doc.version()).unwrap().is_empty());
+}
+
+#[test]
+fn test_collected_tombstones_and_unknown_revisions() {
+    let (mut doc, base) = edited();
+    let before = diff_since(&doc, base).unwrap();
+    doc.collect_garbage();
+    assert_eq!(doc.gc_report().tombstones, 0);
+    assert_eq!(diff_since(&doc, base).unwrap(), before);
+
+    let unknown = diff_since(&doc, 19).unwrap_err();
+    assert!(matches!(unknown, DocumentError::UnknownRevision { version: 19, length: 18, .. }));
+}
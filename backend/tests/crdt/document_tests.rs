@@ -6,12 +6,17 @@
  * - Document creation and initialization
  * - Character insertion at various positions
  * - Character deletion
+ * - Range deletion and concurrent inserts into the range
  * - Concurrent operations and conflict resolution
+ * - Deleting one of the characters tied at a position
  * - Document state consistency
  * - Garbage collection
+ * - Memory breakdown
+ * - Version tracking
+ * - Large documents spanning many chunks
  */
 
-use crdt_editor_backend::crdt::{Document, Operation, Position};
+use crdt_editor_backend::crdt::{AppliedOp, Document, DocumentError, Operation, Position, StateVector};
 
 #[test]
 fn test_document_creation() {
@@ -228,6 +233,77 @@ fn test_automatic_garbage_collection() {
     assert_eq!(doc.content(), "lo");
 }
 
+/// Build a document holding `text` at positions [1], [2], ...
+fn document_with(text: &str) -> (Document, Vec<Position>) {
+    let mut doc = Document::new("test_doc".to_string());
+    let positions: Vec<Position> = (1..=text.len() as u32).map(|i| Position::new(vec![i])).collect();
+    for (c, pos) in text.chars().zip(&positions) {
+        doc.apply(Operation::insert("client1".to_string(), c, pos.clone()));
+    }
+    (doc, positions)
+}
+
+#[test]
+fn test_insert_anchored_on_collected_tombstone() {
+    let (mut collected, positions) = document_with("abc");
+    let (mut retained, _) = document_with("abc");
+
+    // Both replicas delete 'b', but only one collects the tombstone
+    for doc in [&mut collected, &mut retained] {
+        doc.apply(Operation::delete("client1".to_string(), positions[1].clone()));
+    }
+    collected.collect_garbage();
+
+    // A remote client anchored its insert on 'b' before seeing the delete
+    let anchored = Position::between(&positions[1], &positions[2]);
+    assert_eq!(&anchored.path()[..1], positions[1].path().as_slice());
+    for doc in [&mut collected, &mut retained] {
+        let applied = doc
+            .apply_operation(Operation::insert("client2".to_string(), 'x', anchored.clone()))
+            .unwrap();
+        assert_eq!(applied.index, Some(1));
+    }
+
+    assert_eq!(collected.content(), "axc");
+    assert_eq!(retained.content(), "axc");
+    assert_eq!(collected.concurrency_stats().unknown_anchors, 1);
+    assert_eq!(retained.concurrency_stats().unknown_anchors, 0);
+}
+
+#[test]
+fn test_inserts_into_collected_region_converge() {
+    let (mut collected, positions) = document_with("abcde");
+    let (mut retained, _) = document_with("abcde");
+
+    // Delete "bcd", leaving a region of tombstones between 'a' and 'e'
+    for doc in [&mut collected, &mut retained] {
+        for pos in &positions[1..4] {
+            doc.apply(Operation::delete("client1".to_string(), pos.clone()));
+        }
+    }
+    collected.collect_garbage();
+
+    // Inserts anchored on each collected tombstone, applied in different orders
+    let inserts: Vec<Operation> = ['x', 'y', 'z']
+        .into_iter()
+        .zip(1..4)
+        .map(|(c, i)| {
+            let pos = Position::between(&positions[i], &positions[i + 1]);
+            Operation::insert("client2".to_string(), c, pos)
+        })
+        .collect();
+    for op in inserts.iter().rev() {
+        collected.apply(op.clone());
+    }
+    for op in &inserts {
+        retained.apply(op.clone());
+    }
+
+    assert_eq!(collected.content(), "axyze");
+    assert_eq!(retained.content(), collected.content());
+    assert_eq!(collected.concurrency_stats().unknown_anchors, 3);
+}
+
 #[test]
 fn test_apply_insert_operation() {
     let mut doc = Document::new("test_doc".to_string());
@@ -258,3 +334,360 @@ fn test_apply_delete_operation() {
     assert_eq!(doc.content(), "");
     assert_eq!(doc.operations().len(), 2);
 }
+
+/// Build a document by typing text as one client
+fn typed(text: &str) -> (Document, Vec<Position>) {
+    let mut doc = Document::new("test_doc".to_string());
+    let positions = Position::spread(text.chars().count());
+    for (c, pos) in text.chars().zip(&positions) {
+        doc.apply(Operation::insert("writer".to_string(), c, pos.clone()));
+    }
+    (doc, positions)
+}
+
+#[test]
+fn test_range_delete_is_one_operation() {
+    let (mut doc, positions) = typed("hello world");
+    doc.apply(Operation::delete("writer".to_string(), positions[0].clone()));
+
+    let op = Operation::delete_range("editor".to_string(), &doc, positions[0].clone(), positions[5].clone());
+    assert!(!doc.knows(&op));
+    let applied = doc.apply_operation(op.clone()).unwrap();
+    assert_eq!(doc.content(), "world");
+    assert_eq!(applied, AppliedOp { version: 13, index: Some(0) });
+    assert_eq!(doc.operations().len(), 13);
+    assert!(op.is_delete());
+
+    // A retry is known; a range ending before it starts is refused
+    assert!(doc.knows(&op));
+    assert_eq!(doc.merge_operation(op), Ok(None));
+    let backwards = Operation::delete_range("editor".to_string(), &doc, positions[9].clone(), positions[6].clone());
+    assert!(matches!(doc.apply_operation(backwards), Err(DocumentError::InvalidPosition(_))));
+
+    // The whole document, from sentinel to sentinel
+    let all = Operation::delete_range("editor".to_string(), &doc, Position::start(), Position::end());
+    assert_eq!(doc.apply_operation(all).unwrap().index, Some(0));
+    assert!(doc.is_empty());
+}
+
+#[test]
+fn test_range_delete_keeps_concurrent_inserts() {
+    let (mut deleter, positions) = typed("abcd");
+    let mut typist = deleter.clone();
+    let snapshot = serde_json::to_string(&deleter).unwrap();
+
+    // One replica types into the range while the other deletes it
+    let insert = Operation::insert("typist".to_string(), 'X', Position::between(&positions[1], &positions[2]));
+    let range = Operation::delete_range("deleter".to_string(), &deleter, positions[0].clone(), positions[3].clone());
+    typist.apply(insert.clone());
+    let before_range = StateVector::of(&typist);
+    typist.apply(range.clone());
+    deleter.apply(range.clone());
+    deleter.apply(insert.clone());
+    assert_eq!(deleter.content(), "X");
+    assert_eq!(typist.content(), "X");
+    assert_eq!(deleter.checksum(), typist.checksum());
+
+    // A replica loaded from a snapshot finds the range's characters too
+    let mut restored: Document = serde_json::from_str(&snapshot).unwrap();
+    restored.apply(range.clone());
+    restored.apply(insert);
+    assert_eq!(restored.content(), "X");
+    assert!(restored.knows(&range));
+
+    // Tombstones of a range delete wait for barriers to cover it
+    deleter.set_gc_barrier("laptop", before_range);
+    let report = deleter.gc_report();
+    assert_eq!((report.tombstones, report.collectible), (4, 0));
+    deleter.set_gc_barrier("laptop", StateVector::of(&deleter));
+    assert_eq!(deleter.gc_report().collectible, 4);
+    deleter.collect_garbage();
+    assert_eq!((deleter.character_count(), deleter.content().as_str()), (1, "X"));
+}
+
+#[test]
+fn test_gc_barriers_hold_back_tombstones() {
+    let mut doc = Document::new("test_doc".to_string());
+    let positions = Position::spread(3);
+    for (c, pos) in "abc".chars().zip(&positions) {
+        doc.apply(Operation::insert("writer".to_string(), c, pos.clone()));
+    }
+    let inserts_seen = StateVector::of(&doc);
+    doc.apply(Operation::delete("editor".to_string(), positions[1].clone()));
+
+    // One client acknowledged the delete, the other only the inserts
+    doc.set_gc_barrier("phone", StateVector::of(&doc));
+    doc.set_gc_barrier("laptop", inserts_seen);
+    let report = doc.gc_report();
+    assert_eq!((report.tombstones, report.collectible, report.estimated_savings), (1, 0, 0));
+    assert_eq!(report.blocking_clients, ["laptop"]);
+    doc.collect_garbage();
+    assert_eq!(doc.character_count(), 3);
+    assert_eq!(doc.content(), "ac");
+
+    // Reporting changes nothing; releasing the barrier lets the tombstone go
+    assert!(doc.release_gc_barrier("laptop"));
+    assert!(!doc.release_gc_barrier("laptop"));
+    let report = doc.gc_report();
+    assert_eq!(report.collectible, 1);
+    assert!(report.estimated_savings > 0);
+    assert!(report.blocking_clients.is_empty());
+    assert_eq!(doc.character_count(), 3);
+    doc.collect_garbage();
+    assert_eq!(doc.character_count(), 2);
+    assert_eq!(doc.gc_report().tombstones, 0);
+}
+
+#[test]
+fn test_memory_report_by_part() {
+    let mut doc = Document::new("test_doc".to_string());
+    assert_eq!(doc.memory_report().total_bytes(), doc.memory_report().indexes.bytes);
+
+    let positions = Position::spread(3);
+    for (c, pos) in "abc".chars().zip(&positions) {
+        doc.apply(Operation::insert("writer".to_string(), c, pos.clone()));
+    }
+    doc.apply(Operation::delete("writer".to_string(), positions[1].clone()));
+    let report = doc.memory_report();
+    assert_eq!((report.live.count, report.tombstones.count, report.operations.count, report.indexes.count), (2, 1, 4, 1));
+    assert_eq!(report.live.bytes, 2 * report.tombstones.bytes);
+    assert!(report.operations.bytes > 0 && report.indexes.bytes > 0);
+
+    // Collecting drops the tombstone; the history stays
+    doc.collect_garbage();
+    let collected = doc.memory_report();
+    assert_eq!((collected.tombstones.count, collected.tombstones.bytes), (0, 0));
+    assert_eq!(collected.operations, report.operations);
+    assert!(collected.total_bytes() < report.total_bytes());
+}
+
+#[test]
+fn test_visible_length() {
+    let mut doc = Document::new("test_doc".to_string());
+    assert!(doc.is_empty());
+
+    let pos = Position::between(&Position::start(), &Position::new(vec![u32::MAX]));
+    doc.apply(Operation::insert("client1".to_string(), 'A', pos.clone()));
+    assert_eq!(doc.len(), 1);
+
+    // Deleting the same character twice only counts once
+    doc.apply(Operation::delete("client1".to_string(), pos.clone()));
+    doc.apply(Operation::delete("client1".to_string(), pos));
+    assert_eq!(doc.len(), 0);
+    assert_eq!(doc.character_count(), 1);
+}
+
+#[test]
+fn test_version_bumps_on_apply() {
+    let mut doc = Document::new("test_doc".to_string());
+    assert_eq!(doc.version(), 0);
+
+    let pos = Position::between(&Position::start(), &Position::new(vec![u32::MAX]));
+    doc.apply_operation(Operation::insert("client1".to_string(), 'A', pos.clone())).unwrap();
+    assert_eq!(doc.version(), 1);
+
+    // Every applied operation bumps the version, even one with no visible effect
+    doc.apply(Operation::delete("client1".to_string(), pos.clone()));
+    doc.apply(Operation::delete("client1".to_string(), pos));
+    assert_eq!(doc.version(), 3);
+
+    // Garbage collection doesn't change the version
+    doc.collect_garbage();
+    assert_eq!(doc.version(), 3);
+}
+
+#[test]
+fn test_apply_operation_reports_changes() {
+    let mut doc = Document::new("test_doc".to_string());
+    let first = Position::new(vec![10]);
+    let second = Position::new(vec![20]);
+
+    let applied = doc.apply_operation(Operation::insert("client1".to_string(), 'B', second.clone())).unwrap();
+    assert_eq!(applied, AppliedOp { version: 1, index: Some(0) });
+    let applied = doc.apply_operation(Operation::insert("client1".to_string(), 'A', first.clone())).unwrap();
+    assert_eq!(applied, AppliedOp { version: 2, index: Some(0) });
+
+    // Deleting reports the visible index; deleting again changes nothing
+    let applied = doc.apply_operation(Operation::delete("client1".to_string(), second.clone())).unwrap();
+    assert_eq!(applied, AppliedOp { version: 3, index: Some(1) });
+    let applied = doc.apply_operation(Operation::delete("client1".to_string(), second)).unwrap();
+    assert_eq!(applied, AppliedOp { version: 4, index: None });
+
+    assert_eq!(doc.content(), "A");
+}
+
+#[test]
+fn test_insert_at_end_sentinel_rejected() {
+    let mut doc = Document::new("test_doc".to_string());
+    let result = doc.apply_operation(Operation::insert("client1".to_string(), 'A', Position::end()));
+    assert!(matches!(result, Err(DocumentError::InvalidPosition(_))));
+
+    // The infallible wrapper skips the operation
+    doc.apply(Operation::insert("client1".to_string(), 'A', Position::end()));
+    assert!(doc.is_empty());
+    assert_eq!(doc.version(), 0);
+}
+
+#[test]
+fn test_deleting_tied_inserts_by_author() {
+    let mut doc = Document::new("test_doc".to_string());
+    let position = Position::new(vec![10]);
+    doc.apply_operation(Operation::insert("client1".to_string(), 'A', position.clone())).unwrap();
+    doc.apply_operation(Operation::insert("client2".to_string(), 'B', position.clone())).unwrap();
+    assert_eq!(doc.content(), "AB");
+
+    // The second character at the position can be deleted
+    let delete = Operation::delete_of("client1".to_string(), position.clone(), "client2".to_string());
+    assert!(doc.merge_operation(delete.clone()).unwrap().is_some());
+    assert_eq!(doc.merge_operation(delete).unwrap(), None);
+    assert_eq!(doc.content(), "A");
+    assert_eq!(doc.character_by(&position, "client1"), Some('A'));
+    assert_eq!(doc.character_by(&position, "client2"), None);
+
+    // Replicas applying the deletes in either order converge
+    let first = Operation::delete_of("client2".to_string(), position.clone(), "client1".to_string());
+    let mut other = Document::new("test_doc".to_string());
+    let (inserts, deletes) = doc.operations().split_at(2);
+    for operation in inserts.iter().chain([&first]).chain(deletes) {
+        other.merge_operation(operation.clone()).unwrap();
+    }
+    doc.apply_operation(first).unwrap();
+    assert_eq!(doc.content(), "");
+    assert_eq!(other.content(), doc.content());
+
+    // Deletes without an author still take the first character
+    let untargeted = Operation::delete("client1".to_string(), position);
+    assert_eq!(doc.merge_operation(untargeted).unwrap(), None);
+}
+
+#[test]
+fn test_merge_skips_known_operations() {
+    let mut doc = Document::new("test_doc".to_string());
+    let insert = Operation::insert("client1".to_string(), 'A', Position::new(vec![10]));
+    let delete = Operation::delete("client1".to_string(), Position::new(vec![10]));
+
+    assert!(doc.merge_operation(insert.clone()).unwrap().is_some());
+    assert_eq!(doc.merge_operation(insert.clone()).unwrap(), None);
+    assert_eq!(doc.content(), "A");
+
+    // A concurrent insert at the same position by another client is new
+    let concurrent = Operation::insert("client2".to_string(), 'B', Position::new(vec![10]));
+    assert!(doc.merge_operation(concurrent).unwrap().is_some());
+    assert_eq!(doc.content().len(), 2);
+
+    assert!(doc.merge_operation(delete.clone()).unwrap().is_some());
+    assert_eq!(doc.merge_operation(delete).unwrap(), None);
+    // The tombstone still identifies the insert
+    assert_eq!(doc.merge_operation(insert).unwrap(), None);
+    assert_eq!(doc.version(), 3);
+}
+
+#[test]
+fn test_replacing_a_character_in_place() {
+    let mut doc = Document::new("test_doc".to_string());
+    for (path, c) in [(10, 'a'), (11, 'b'), (12, 'c')] {
+        doc.apply_operation(Operation::insert("client1".to_string(), c, Position::new(vec![path]))).unwrap();
+    }
+    let b = Position::new(vec![11]);
+    assert_eq!(doc.character_at(&b), Some('b'));
+
+    doc.apply_operation(Operation::delete("client1".to_string(), b.clone())).unwrap();
+    assert_eq!(doc.character_at(&b), None);
+    let replacement = doc.position_after(&b);
+    doc.apply_operation(Operation::insert("client1".to_string(), '*', replacement)).unwrap();
+    assert_eq!(doc.content(), "a*c");
+
+    // After the last character there is nothing to stay before
+    let last = doc.position_after(&Position::new(vec![12]));
+    doc.apply_operation(Operation::insert("client1".to_string(), '!', last)).unwrap();
+    assert_eq!(doc.content(), "a*c!");
+}
+
+#[test]
+fn test_fork_copies_history() {
+    let mut doc = Document::new("source".to_string());
+    doc.apply_operation(Operation::insert("client1".to_string(), 'A', Position::new(vec![1]))).unwrap();
+
+    let mut copy = doc.fork("copy".to_string());
+    assert_eq!(copy.id(), "copy");
+    assert_eq!((copy.content(), copy.version(), copy.checksum()), (doc.content(), doc.version(), doc.checksum()));
+
+    // The copies evolve separately
+    copy.apply_operation(Operation::insert("client2".to_string(), 'B', Position::new(vec![2]))).unwrap();
+    assert_eq!(copy.content(), "AB");
+    assert_eq!(doc.content(), "A");
+}
+
+#[test]
+fn test_authors_of_visible_text() {
+    let mut doc = Document::new("doc1".to_string());
+    doc.apply_operation(Operation::insert("bob".to_string(), 'b', Position::new(vec![2]))).unwrap();
+    doc.apply_operation(Operation::insert("alice".to_string(), 'a', Position::new(vec![1]))).unwrap();
+    doc.apply_operation(Operation::insert("carol".to_string(), 'c', Position::new(vec![3]))).unwrap();
+    assert_eq!(doc.authors(), vec!["alice", "bob", "carol"]);
+
+    doc.apply_operation(Operation::delete("alice".to_string(), Position::new(vec![3]))).unwrap();
+    assert_eq!(doc.authors(), vec!["alice", "bob"]);
+}
+
+#[test]
+fn test_positions_for_a_run_of_text() {
+    let mut doc = Document::new("test_doc".to_string());
+    let positions = Position::spread(2);
+    doc.apply(Operation::insert("client1".to_string(), 'a', positions[0].clone()));
+    doc.apply(Operation::insert("client1".to_string(), 'd', positions[1].clone()));
+
+    // Between two characters, and past the last one at the same depth
+    for (character, position) in "bc".chars().zip(doc.positions_after(&positions[0], 2)) {
+        doc.apply(Operation::insert("client1".to_string(), character, position));
+    }
+    let tail = doc.positions_after(&positions[1], 2);
+    assert!(tail.iter().all(|position| position.path().len() == 1));
+    for (character, position) in "ef".chars().zip(tail) {
+        doc.apply(Operation::insert("client1".to_string(), character, position));
+    }
+    assert_eq!(doc.content(), "abcdef");
+    let visible: String = doc.visible_characters().map(|(character, _)| character).collect();
+    assert_eq!(visible, "abcdef");
+
+    // No room left at the end of the range
+    let last = Position::new(vec![u32::MAX - 1]);
+    let deeper = doc.positions_after(&last, 3);
+    assert!(deeper.windows(2).all(|pair| pair[0] < pair[1]) && last < deeper[0]);
+}
+
+#[test]
+fn test_large_document_inserted_out_of_order() {
+    // Enough characters for many chunks, inserted in a shuffled order so
+    // every insert lands mid-document
+    let count = 5_000;
+    let positions = Position::spread(count);
+    let letter = |i: usize| (b'a' + (i % 26) as u8) as char;
+    let mut order: Vec<usize> = (0..count).collect();
+    let mut seed = 7u64;
+    for i in (1..count).rev() {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        order.swap(i, (seed >> 33) as usize % (i + 1));
+    }
+    let mut doc = Document::new("big".to_string());
+    for &i in &order {
+        doc.apply_operation(Operation::insert("client1".to_string(), letter(i), positions[i].clone())).unwrap();
+    }
+    let mut expected: Vec<char> = (0..count).map(letter).collect();
+    assert_eq!(doc.content(), expected.iter().collect::<String>());
+
+    // Deletes report the visible index of their character
+    for i in (0..count).step_by(3).rev() {
+        let applied = doc.apply_operation(Operation::delete("client1".to_string(), positions[i].clone())).unwrap();
+        assert_eq!(applied.index, Some(i));
+        expected.remove(i);
+    }
+    assert_eq!(doc.content(), expected.iter().collect::<String>());
+    assert_eq!(doc.character_at(&positions[1]), Some(letter(1)));
+    let known = doc.merge_operation(Operation::insert("client1".to_string(), 'b', positions[1].clone()));
+    assert_eq!(known, Ok(None));
+
+    let copy: Document = serde_json::from_str(&serde_json::to_string(&doc).unwrap()).unwrap();
+    assert_eq!(copy.content(), doc.content());
+    assert_eq!(copy.len(), doc.len());
+}
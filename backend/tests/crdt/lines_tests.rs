@@ -0,0 +1,103 @@
+/*
+ * File: tests/crdt/lines_tests.rs
+ * Purpose: Test suite for the index of visible characters and lines
+ *
+ * Test Categories:
+ * - Agreement with a plain scan across chunk splits and deletions
+ * - Line and column mapping of documents with a very long line
+ */
+
+use crdt_editor_backend::crdt::{Document, LineIndex, Operation, Position, CHUNK_CAPACITY};
+
+/// Slots as (visible, newline), the way the index sees them
+struct Model(Vec<(bool, bool)>);
+
+impl Model {
+    fn visible_before(&self, slot: usize) -> usize {
+        self.0[..slot].iter().filter(|(visible, _)| *visible).count()
+    }
+
+    fn text(&self) -> Vec<bool> {
+        self.0.iter().filter(|(visible, _)| *visible).map(|(_, newline)| *newline).collect()
+    }
+
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let text = self.text();
+        let line = text[..offset].iter().filter(|newline| **newline).count();
+        let start = text[..offset].iter().rposition(|newline| *newline).map_or(0, |i| i + 1);
+        (line, offset - start)
+    }
+}
+
+#[test]
+fn test_index_matches_plain_scan() {
+    let mut index = LineIndex::new();
+    let mut model = Model(Vec::new());
+    let mut seed: u64 = 42;
+    let mut next = |bound: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % bound
+    };
+
+    for _ in 0..CHUNK_CAPACITY * 8 {
+        let slot = next(model.0.len() + 1);
+        let newline = next(40) == 0;
+        index.insert(slot, true, newline);
+        model.0.insert(slot, (true, newline));
+        if next(4) == 0 {
+            let slot = next(model.0.len());
+            index.hide(slot);
+            model.0[slot].0 = false;
+        }
+    }
+    assert_eq!(index.len(), model.0.len());
+    assert_eq!(index.visible_len(), model.text().len());
+    assert_eq!(index.line_count(), model.text().iter().filter(|newline| **newline).count() + 1);
+
+    for slot in (0..=model.0.len()).step_by(37) {
+        assert_eq!(index.visible_before(slot), model.visible_before(slot), "slot {}", slot);
+    }
+    for offset in (0..=model.text().len()).step_by(29) {
+        let (line, column) = model.line_column(offset);
+        assert_eq!(index.line_column(offset), Some((line, column)), "offset {}", offset);
+        assert_eq!(index.offset_of(line, column), Some(offset));
+    }
+    assert_eq!(index.line_column(model.text().len() + 1), None);
+    assert_eq!(index.line_start(index.line_count()), None);
+
+    // Building from scratch gives the same answers
+    let rebuilt = LineIndex::build(model.0.iter().copied());
+    assert_eq!(rebuilt.visible_before(model.0.len() / 2), index.visible_before(model.0.len() / 2));
+    assert_eq!(rebuilt.line_column(model.text().len() / 2), index.line_column(model.text().len() / 2));
+}
+
+#[test]
+fn test_document_maps_long_line() {
+    let text = format!("{}\nend", "x".repeat(5000));
+    let positions = Position::spread(text.chars().count());
+    let mut doc = Document::new("minified".to_string());
+    doc.set_garbage_collection_threshold(1000);
+    for (character, position) in text.chars().zip(&positions) {
+        doc.apply(Operation::insert("alice".to_string(), character, position.clone()));
+    }
+    assert_eq!(doc.line_count(), 2);
+    assert_eq!(doc.line_column(4321), Some((0, 4321)));
+    assert_eq!(doc.line_column(5001), Some((1, 0)));
+    assert_eq!(doc.offset_of(1, 3), Some(5004));
+    assert_eq!(doc.offset_of(1, 4), None);
+    assert_eq!(doc.offset_of(0, 5000), Some(5000));
+
+    // Deletes on the long line shift what follows, through garbage collection
+    for position in &positions[..1200] {
+        let applied = doc.apply_operation(Operation::delete("alice".to_string(), position.clone())).unwrap();
+        assert_eq!(applied.index, Some(0));
+    }
+    assert_eq!(doc.line_column(3801), Some((1, 0)));
+    let inserted = doc.apply_operation(Operation::insert("bob".to_string(), '\n', Position::between(&positions[2000], &positions[2001]))).unwrap();
+    assert_eq!(inserted.index, Some(801));
+    assert_eq!((doc.line_count(), doc.line_column(3803)), (3, Some((2, 1))));
+
+    // The index is rebuilt after a round trip
+    let copy: Document = serde_json::from_str(&serde_json::to_string(&doc).unwrap()).unwrap();
+    assert_eq!((copy.line_count(), copy.line_column(3803), copy.offset_of(1, 0)), (3, Some((2, 1)), Some(802)));
+}
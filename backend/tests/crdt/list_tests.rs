@@ -0,0 +1,96 @@
+/*
+ * File: tests/crdt/list_tests.rs
+ * Purpose: Test suite for the conflict-free ordered list
+ *
+ * Test Categories:
+ * - Local inserts, moves, updates and removals
+ * - Convergence of concurrent inserts and reorders
+ * - Operations delivered out of order and more than once
+ */
+
+use crdt_editor_backend::crdt::{ListOperation, OrderedList};
+
+fn values(list: &OrderedList<String>) -> Vec<String> {
+    list.items().into_iter().map(|(_, value)| value.clone()).collect()
+}
+
+/// Build a list of values and return it with the operations that built it
+fn seeded(values: &[&str]) -> (OrderedList<String>, Vec<ListOperation<String>>) {
+    let mut list = OrderedList::new();
+    let operations = values.iter().enumerate().map(|(i, value)| list.insert("seed", i, value.to_string())).collect();
+    (list, operations)
+}
+
+#[test]
+fn test_local_edits() {
+    let (mut list, _) = seeded(&["a", "b", "c"]);
+    assert_eq!(values(&list), ["a", "b", "c"]);
+
+    let ids: Vec<_> = list.items().into_iter().map(|(id, _)| id.clone()).collect();
+    list.insert("alice", 0, "first".to_string());
+    list.move_to("alice", &ids[2], 1).unwrap();
+    list.update("alice", &ids[1], "B".to_string()).unwrap();
+    assert_eq!(values(&list), ["first", "c", "a", "B"]);
+
+    list.remove(&ids[0]).unwrap();
+    assert_eq!(values(&list), ["first", "c", "B"]);
+    assert!(list.move_to("alice", &ids[0], 0).is_none());
+    assert!(list.update("alice", &ids[0], "gone".to_string()).is_none());
+    assert_eq!(list.len(), 3);
+    assert_eq!(list.footprint(), 4);
+}
+
+#[test]
+fn test_concurrent_reorders_converge() {
+    let (seed, operations) = seeded(&["a", "b", "c", "d"]);
+    let ids: Vec<_> = seed.items().into_iter().map(|(id, _)| id.clone()).collect();
+    let mut alice = seed.clone();
+    let mut bob = seed.clone();
+
+    // Both move "a", to different places, and each inserts at the front
+    let from_alice = [alice.move_to("alice", &ids[0], 4).unwrap(), alice.insert("alice", 0, "x".to_string())];
+    let from_bob = [bob.move_to("bob", &ids[0], 2).unwrap(), bob.insert("bob", 0, "y".to_string())];
+    for operation in &from_bob {
+        alice.apply(operation.clone());
+    }
+    for operation in &from_alice {
+        bob.apply(operation.clone());
+    }
+
+    assert_eq!(values(&alice), values(&bob));
+    assert_eq!(values(&alice).iter().filter(|value| *value == "a").count(), 1);
+    assert_eq!(alice.len(), 6);
+
+    // A replica built from scratch, with every operation twice and in
+    // reverse order, ends up the same
+    let mut late = OrderedList::new();
+    for operation in operations.iter().chain(&from_alice).chain(&from_bob).rev().chain(&from_alice) {
+        late.apply(operation.clone());
+    }
+    assert_eq!(values(&late), values(&alice));
+}
+
+#[test]
+fn test_removal_wins_over_concurrent_move_and_update() {
+    let (seed, _) = seeded(&["a", "b"]);
+    let id = seed.items()[0].0.clone();
+    let mut alice = seed.clone();
+    let mut bob = seed.clone();
+
+    let removal = alice.remove(&id).unwrap();
+    let moved = bob.move_to("bob", &id, 1).unwrap();
+    let updated = bob.update("bob", &id, "A".to_string()).unwrap();
+    alice.apply(moved);
+    alice.apply(updated);
+    bob.apply(removal);
+    assert_eq!(values(&alice), ["b"]);
+    assert_eq!(values(&bob), ["b"]);
+
+    // Late joiners rebuild the same state from a replica's operations
+    let mut late = OrderedList::new();
+    for operation in bob.operations() {
+        late.apply(operation);
+    }
+    assert_eq!(values(&late), ["b"]);
+    assert!(!late.apply(bob.operations()[0].clone()));
+}
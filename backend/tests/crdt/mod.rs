@@ -3,11 +3,29 @@
  * Purpose: Test module organization for CRDT implementation
  * 
  * Test modules:
+ * - checksum_tests: Tests for the incremental content checksum
+ * - diff_tests: Tests for changes since an earlier revision
  * - document_tests: Tests for Document and Operation
+ * - lines_tests: Tests for the index of visible characters and lines
+ * - list_tests: Tests for the conflict-free ordered list
+ * - playback_tests: Tests for history playback
  * - position_tests: Tests for Position identifiers
+ * - source_tests: Tests for operation source tags
+ * - stats_tests: Tests for concurrency statistics
+ * - tiebreak_tests: Tests for ordering inserts at equal positions
  * - timestamp_tests: Tests for Lamport timestamps
+ * - update_tests: Tests for document updates and state vectors
  */
 
+mod checksum_tests;
+mod diff_tests;
 mod document_tests;
+mod lines_tests;
+mod list_tests;
+mod playback_tests;
 mod position_tests;
+mod source_tests;
+mod stats_tests;
+mod tiebreak_tests;
 mod timestamp_tests;
+mod update_tests;
@@ -0,0 +1,78 @@
+/*
+ * File: tests/crdt/playback_tests.rs
+ * Purpose: Test suite for document history playback
+ * 
+ * Test Categories:
+ * - Frame-by-frame reconstruction
+ * - Seeking forwards and backwards
+ * - Empty histories
+ */
+
+use crdt_editor_backend::crdt::{Document, Operation, Playback, Position};
+
+fn hello_document() -> (Document, Vec<Position>) {
+    let mut doc = Document::new("test_doc".to_string());
+    let mut last_pos = Position::start();
+    let mut positions = Vec::new();
+
+    for c in "Hello".chars() {
+        let pos = Position::between(&last_pos, &Position::new(vec![u32::MAX]));
+        positions.push(pos.clone());
+        doc.apply(Operation::insert("client1".to_string(), c, pos.clone()));
+        last_pos = pos;
+    }
+    (doc, positions)
+}
+
+#[test]
+fn test_playback_frames() {
+    let (mut doc, positions) = hello_document();
+    doc.apply(Operation::delete("client1".to_string(), positions[4].clone()));
+
+    let contents: Vec<String> = Playback::from_document(&doc)
+        .map(|frame| frame.content)
+        .collect();
+
+    assert_eq!(contents, vec!["H", "He", "Hel", "Hell", "Hello", "Hell"]);
+}
+
+#[test]
+fn test_playback_frame_metadata() {
+    let (doc, _) = hello_document();
+    let mut playback = Playback::from_document(&doc);
+
+    let frame = playback.step().unwrap();
+    assert_eq!(frame.index, 0);
+    assert_eq!(frame.total, 5);
+    assert_eq!(frame.operation.client_id(), "client1");
+    assert_eq!(playback.position(), 1);
+}
+
+#[test]
+fn test_playback_seek() {
+    let (doc, _) = hello_document();
+    let mut playback = Playback::from_document(&doc);
+
+    playback.seek(3);
+    assert_eq!(playback.content(), "Hel");
+    assert_eq!(playback.step().unwrap().content, "Hell");
+
+    // Seeking backwards rebuilds from the start
+    playback.seek(1);
+    assert_eq!(playback.content(), "H");
+
+    // Seeking past the end stops at the final state
+    playback.seek(100);
+    assert!(playback.is_finished());
+    assert_eq!(playback.content(), doc.content());
+}
+
+#[test]
+fn test_playback_empty_history() {
+    let doc = Document::new("empty".to_string());
+    let mut playback = Playback::from_document(&doc);
+
+    assert!(playback.is_empty());
+    assert!(playback.is_finished());
+    assert!(playback.step().is_none());
+}
@@ -55,6 +55,37 @@ fn test_position_between() {
     assert!(between < pos2);
 }
 
+#[test]
+fn test_position_between_without_room() {
+    // Siblings with no room between them descend below the left one
+    let left = Position::new(vec![5]);
+    let right = Position::new(vec![5, 1]);
+    let between = Position::between(&left, &right);
+    assert_eq!(between, Position::new(vec![5, 0, 1]));
+    assert!(left < between && between < right);
+    let deeper = Position::new(vec![5, 0, 7]);
+    let between = Position::between(&left, &deeper);
+    assert!(left < between && between < deeper);
+    assert_eq!(Position::between(&deeper, &left), between);
+
+    // Inserting right after one position over and over stays between
+    let mut next = Position::new(vec![6]);
+    for _ in 0..100 {
+        let between = Position::between(&left, &next);
+        assert!(left < between && between < next, "{:?} {:?}", between, next);
+        assert_ne!(between.path().last(), Some(&0));
+        next = between;
+    }
+
+    // Nothing sorts between equal positions, or before a zero extension
+    assert!(!Position::has_room(&left, &left));
+    assert_eq!(Position::between(&left, &left), left);
+    let zeros = Position::new(vec![5, 0, 0]);
+    assert!(!Position::has_room(&left, &zeros));
+    assert_eq!(Position::between(&left, &zeros), zeros);
+    assert!(Position::has_room(&left, &deeper));
+}
+
 #[test]
 fn test_position_bounds() {
     // Test start position
@@ -95,6 +126,22 @@ fn test_position_dense_sequence() {
     }
 }
 
+#[test]
+fn test_position_spread() {
+    assert!(Position::spread(0).is_empty());
+
+    let positions = Position::spread(1000);
+    assert_eq!(positions.len(), 1000);
+    assert!(Position::start() < positions[0]);
+    for pair in positions.windows(2) {
+        assert!(pair[0] < pair[1]);
+        // Room is left between neighbours for later inserts
+        assert_eq!(pair[0].path().len(), 1);
+        assert!(pair[1].path()[0] - pair[0].path()[0] > 1);
+    }
+    assert!(positions[999] < Position::end());
+}
+
 #[test]
 fn test_position_serialization() {
     let pos = Position::new(vec![10, 20, 30]);
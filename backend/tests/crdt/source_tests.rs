@@ -0,0 +1,44 @@
+/*
+ * File: tests/crdt/source_tests.rs
+ * Purpose: Test suite for operation source tags
+ *
+ * Test Categories:
+ * - Wire names and parsing
+ * - Default source of operation messages
+ * - Undo eligibility
+ */
+
+use crdt_editor_backend::{
+    crdt::{Operation, OperationSource, Position},
+    websocket::message::OperationMessage,
+};
+
+#[test]
+fn test_source_names_round_trip() {
+    for source in [OperationSource::User, OperationSource::Server, OperationSource::Bot, OperationSource::Import] {
+        assert_eq!(source.name().parse::<OperationSource>(), Ok(source));
+        assert_eq!(serde_json::to_value(source).unwrap(), source.name());
+    }
+    assert!("robot".parse::<OperationSource>().is_err());
+}
+
+#[test]
+fn test_operation_messages_default_to_user() {
+    let operation = Operation::insert("alice".to_string(), 'a', Position::new(vec![1]));
+    let message = OperationMessage::new(operation.clone(), "doc1".to_string());
+    let value = serde_json::to_value(&message).unwrap();
+    assert!(value.get("source").is_none());
+    assert_eq!(serde_json::from_value::<OperationMessage>(value).unwrap().source, OperationSource::User);
+
+    let tagged = serde_json::to_value(message.with_source(OperationSource::Bot)).unwrap();
+    assert_eq!(tagged["source"], "bot");
+    assert_eq!(serde_json::from_value::<OperationMessage>(tagged).unwrap().source, OperationSource::Bot);
+}
+
+#[test]
+fn test_only_user_operations_undoable_by_default() {
+    assert!(OperationSource::User.undoable_by_default());
+    for source in [OperationSource::Server, OperationSource::Bot, OperationSource::Import] {
+        assert!(!source.undoable_by_default());
+    }
+}
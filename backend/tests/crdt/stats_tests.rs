@@ -0,0 +1,71 @@
+/*
+ * File: tests/crdt/stats_tests.rs
+ * Purpose: Test suite for CRDT concurrency statistics
+ * 
+ * Test Categories:
+ * - Detection of causally ordered vs. concurrent operations
+ * - Tie-breaks on identical positions
+ * - Interleaving of concurrent inserts
+ */
+
+use crdt_editor_backend::crdt::{Document, Operation, Position, Timestamp};
+
+/// Build an insert with an explicit logical clock
+fn insert_at(client_id: &str, clock: u64, character: char, path: Vec<u32>) -> Operation {
+    let mut timestamp = Timestamp::new(client_id.to_string());
+    for _ in 0..clock {
+        timestamp.increment();
+    }
+    Operation::Insert {
+        client_id: client_id.to_string(),
+        character,
+        position: Position::new(path),
+        timestamp,
+    }
+}
+
+#[test]
+fn test_causal_operations_not_concurrent() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.apply(insert_at("client1", 1, 'a', vec![1]));
+    doc.apply(insert_at("client2", 2, 'b', vec![2]));
+    doc.apply(insert_at("client1", 3, 'c', vec![3]));
+
+    let stats = doc.concurrency_stats();
+    assert_eq!(stats.total_operations, 3);
+    assert_eq!(stats.concurrent_operations, 0);
+    assert_eq!(stats.concurrency_ratio(), 0.0);
+}
+
+#[test]
+fn test_concurrent_operations_detected() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.apply(insert_at("client1", 1, 'a', vec![1]));
+    doc.apply(insert_at("client2", 1, 'b', vec![2]));
+
+    let stats = doc.concurrency_stats();
+    assert_eq!(stats.concurrent_operations, 1);
+    assert_eq!(stats.concurrency_ratio(), 0.5);
+}
+
+#[test]
+fn test_tie_break_counted() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.apply_operation(insert_at("client1", 1, 'a', vec![5])).unwrap();
+    doc.apply_operation(insert_at("client2", 1, 'b', vec![5])).unwrap();
+
+    assert_eq!(doc.concurrency_stats().tie_breaks, 1);
+}
+
+#[test]
+fn test_interleaved_insert_counted() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.apply(insert_at("client1", 1, 'a', vec![1]));
+    doc.apply(insert_at("client1", 2, 'b', vec![3]));
+
+    // client2 has not seen client1's run and splits it
+    doc.apply(insert_at("client2", 1, 'x', vec![2]));
+
+    assert_eq!(doc.content(), "axb");
+    assert_eq!(doc.concurrency_stats().interleaved_inserts, 1);
+}
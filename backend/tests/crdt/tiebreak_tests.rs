@@ -0,0 +1,133 @@
+/*
+ * File: tests/crdt/tiebreak_tests.rs
+ * Purpose: Test suite for ordering inserts at equal positions
+ *
+ * Test Categories:
+ * - Timestamp-then-client ordering
+ * - Convergence under every arrival order
+ * - Arrival-order strategy for comparison
+ * - Strategy carried into playback
+ */
+
+use crdt_editor_backend::crdt::{Document, Operation, Playback, Position, TieBreak, Timestamp};
+
+/// Build an insert with an explicit logical clock
+fn insert_at(client_id: &str, clock: u64, character: char, path: Vec<u32>) -> Operation {
+    let mut timestamp = Timestamp::new(client_id.to_string());
+    for _ in 0..clock {
+        timestamp.increment();
+    }
+    Operation::Insert {
+        client_id: client_id.to_string(),
+        character,
+        position: Position::new(path),
+        timestamp,
+    }
+}
+
+/// Every ordering of the given operations
+fn permutations(ops: &[Operation]) -> Vec<Vec<Operation>> {
+    if ops.len() <= 1 {
+        return vec![ops.to_vec()];
+    }
+    let mut result = Vec::new();
+    for i in 0..ops.len() {
+        let mut rest = ops.to_vec();
+        let first = rest.remove(i);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, first.clone());
+            result.push(tail);
+        }
+    }
+    result
+}
+
+/// Apply operations in order to a fresh document using a strategy
+fn replay(ops: &[Operation], tie_break: TieBreak) -> String {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.set_tie_break(tie_break);
+    for op in ops {
+        doc.apply_operation(op.clone()).unwrap();
+    }
+    doc.content()
+}
+
+#[test]
+fn test_default_strategy() {
+    let doc = Document::new("test_doc".to_string());
+    assert_eq!(doc.tie_break(), TieBreak::TimestampThenClient);
+}
+
+#[test]
+fn test_timestamp_then_client_order() {
+    let ops = vec![
+        insert_at("client2", 2, 'c', vec![5]),
+        insert_at("client2", 1, 'b', vec![5]),
+        insert_at("client1", 1, 'a', vec![5]),
+    ];
+    assert_eq!(replay(&ops, TieBreak::TimestampThenClient), "abc");
+}
+
+#[test]
+fn test_same_position_inserts_converge_in_any_order() {
+    let ops = vec![
+        insert_at("client1", 1, 'x', vec![1]),
+        insert_at("client1", 2, 'a', vec![5]),
+        insert_at("client2", 2, 'b', vec![5]),
+        insert_at("client3", 1, 'c', vec![5]),
+        insert_at("client2", 1, 'y', vec![9]),
+    ];
+
+    let orders = permutations(&ops);
+    assert_eq!(orders.len(), 120);
+    for order in &orders {
+        assert_eq!(replay(order, TieBreak::TimestampThenClient), "xcaby");
+    }
+}
+
+#[test]
+fn test_deletes_converge_after_tie_break() {
+    let ops = vec![
+        insert_at("client1", 1, 'a', vec![5]),
+        insert_at("client2", 1, 'b', vec![5]),
+        insert_at("client3", 1, 'c', vec![5]),
+    ];
+
+    for order in permutations(&ops) {
+        let mut doc = Document::new("test_doc".to_string());
+        for op in order {
+            doc.apply(op);
+        }
+        // Deletes by position remove the first character at that position
+        doc.apply(Operation::delete("client1".to_string(), Position::new(vec![5])));
+        assert_eq!(doc.content(), "bc");
+    }
+}
+
+#[test]
+fn test_arrival_order_depends_on_arrival() {
+    let first = insert_at("client1", 1, 'a', vec![5]);
+    let second = insert_at("client2", 1, 'b', vec![5]);
+
+    let forward = replay(&[first.clone(), second.clone()], TieBreak::ArrivalOrder);
+    let reverse = replay(&[second, first], TieBreak::ArrivalOrder);
+    assert_eq!(forward, "ab");
+    assert_eq!(reverse, "ba");
+}
+
+#[test]
+fn test_playback_keeps_tie_break() {
+    let mut doc = Document::new("test_doc".to_string());
+    doc.set_tie_break(TieBreak::ArrivalOrder);
+    doc.apply(insert_at("client2", 1, 'b', vec![5]));
+    doc.apply(insert_at("client1", 1, 'a', vec![5]));
+    assert_eq!(doc.content(), "ba");
+
+    let mut playback = Playback::from_document(&doc);
+    playback.seek(playback.len());
+    assert_eq!(playback.content(), doc.content());
+
+    playback.seek(0);
+    playback.seek(playback.len());
+    assert_eq!(playback.content(), doc.content());
+}
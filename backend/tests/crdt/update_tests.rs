@@ -0,0 +1,132 @@
+/*
+ * File: tests/crdt/update_tests.rs
+ * Purpose: Test suite for document updates and state vectors
+ *
+ * Test Categories:
+ * - Collecting the operations a replica lacks
+ * - Convergence of replicas exchanging updates after editing offline
+ * - Idempotent and all-or-nothing merging
+ * - Encoding for storage
+ * - State vectors tracked by documents
+ * - Operations applied after a version
+ */
+
+use crdt_editor_backend::crdt::{Document, DocumentError, DocumentUpdate, Operation, Position, StateVector, VersionVector};
+
+/// Type text into a document at evenly spread positions
+fn type_text(document: &mut Document, client_id: &str, text: &str) {
+    for (character, position) in text.chars().zip(Position::spread(text.chars().count())) {
+        document.apply(Operation::insert(client_id.to_string(), character, position));
+    }
+}
+
+#[test]
+fn test_update_holds_what_replica_lacks() {
+    let mut server = Document::new("doc1".to_string());
+    type_text(&mut server, "alice", "abc");
+
+    let full = DocumentUpdate::since(&server, &StateVector::new());
+    assert_eq!(full.operations.len(), 3);
+    assert_eq!(full.version, 3);
+    assert_eq!(full.state_vector, StateVector::of(&server));
+    assert_eq!(full.state_vector.get("alice"), 3);
+
+    let mut client = Document::new("doc1".to_string());
+    full.apply_to(&mut client).unwrap();
+    assert_eq!(client.content(), "abc");
+
+    // Only the operations after the client's state vector are sent
+    let known = StateVector::of(&client);
+    let position = server.position_after(&Position::spread(3)[2]);
+    server.apply(Operation::insert("bob".to_string(), 'd', position));
+    let delta = DocumentUpdate::since(&server, &known);
+    assert_eq!(delta.operations.len(), 1);
+    assert_eq!(delta.operations[0].client_id(), "bob");
+    assert!(delta.state_vector.covers(&known));
+    assert!(!known.covers(&delta.state_vector));
+    assert!(DocumentUpdate::since(&server, &delta.state_vector).is_empty());
+}
+
+#[test]
+fn test_offline_replicas_converge() {
+    let mut server = Document::new("doc1".to_string());
+    type_text(&mut server, "alice", "hello");
+    let mut offline = Document::new("doc1".to_string());
+    DocumentUpdate::since(&server, &StateVector::new()).apply_to(&mut offline).unwrap();
+    let last_seen = StateVector::of(&server);
+
+    // Both sides edit while the client is offline
+    let positions = Position::spread(5);
+    offline.apply(Operation::insert("phone".to_string(), '!', offline.position_after(&positions[4])));
+    offline.apply(Operation::delete("phone".to_string(), positions[0].clone()));
+    server.apply(Operation::insert("bob".to_string(), '?', server.position_after(&positions[4])));
+    server.apply(Operation::delete("bob".to_string(), positions[1].clone()));
+
+    // The client submits its pending operations, persisted as a blob, and
+    // merges what the server replies with
+    let pending = DocumentUpdate::decode(&DocumentUpdate::since(&offline, &last_seen).encode()).unwrap();
+    assert_eq!(pending.operations.len(), 2);
+    assert_eq!(pending.apply_to(&mut server).unwrap().len(), 2);
+    let reply = DocumentUpdate::since(&server, &pending.state_vector);
+    assert_eq!(reply.operations.len(), 2);
+    reply.apply_to(&mut offline).unwrap();
+
+    assert_eq!(offline.content(), server.content());
+    assert_eq!(offline.checksum(), server.checksum());
+    assert_eq!(StateVector::of(&offline), StateVector::of(&server));
+}
+
+#[test]
+fn test_merging_is_idempotent_and_all_or_nothing() {
+    let mut source = Document::new("doc1".to_string());
+    type_text(&mut source, "alice", "ab");
+    let update = DocumentUpdate::since(&source, &StateVector::new());
+
+    let mut replica = Document::new("doc1".to_string());
+    assert_eq!(update.apply_to(&mut replica).unwrap().len(), 2);
+    assert!(update.apply_to(&mut replica).unwrap().is_empty());
+    assert_eq!(replica.content(), "ab");
+    assert_eq!(replica.version(), 2);
+
+    let mut invalid = DocumentUpdate::since(&source, &StateVector::new());
+    invalid.operations.push(Operation::insert("alice".to_string(), 'x', Position::end()));
+    let mut empty = Document::new("doc1".to_string());
+    assert!(matches!(invalid.apply_to(&mut empty), Err(DocumentError::InvalidPosition(_))));
+    assert_eq!(empty.version(), 0);
+    assert!(DocumentUpdate::decode("not an update").is_err());
+}
+
+#[test]
+fn test_document_tracks_its_state_vector() {
+    let mut doc = Document::new("doc1".to_string());
+    type_text(&mut doc, "alice", "abc");
+    let known = doc.state_vector().clone();
+    doc.apply(Operation::delete("bob".to_string(), Position::spread(3)[0].clone()));
+    doc.collect_garbage();
+    assert_eq!(doc.state_vector().get("alice"), 3);
+    assert_eq!(doc.state_vector().get("bob"), 1);
+
+    let missing = doc.operations_since(&known);
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].client_id(), "bob");
+    assert!(doc.operations_since(doc.state_vector()).is_empty());
+    assert_eq!(doc.operations_since(&StateVector::new()).len(), 4);
+    // A version vector is a state vector by another name
+    let since: VersionVector = known;
+    assert_eq!(doc.operations_since(&since), missing);
+
+    // Stored documents count their history again when loaded
+    let stored: Document = serde_json::from_str(&serde_json::to_string(&doc).unwrap()).unwrap();
+    assert_eq!(stored.state_vector(), doc.state_vector());
+}
+
+#[test]
+fn test_operations_after_version() {
+    let mut doc = Document::new("doc1".to_string());
+    type_text(&mut doc, "alice", "abc");
+    let after: Vec<&str> = doc.operations_after(1).unwrap().iter().map(|op| op.client_id()).collect();
+    assert_eq!(after, ["alice", "alice"]);
+    assert!(doc.operations_after(3).unwrap().is_empty());
+    assert_eq!(doc.operations_after(0).unwrap().len(), 3);
+    assert!(doc.operations_after(4).is_none());
+}
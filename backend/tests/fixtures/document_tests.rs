@@ -0,0 +1,36 @@
+/*
+ * File: tests/fixtures/document_tests.rs
+ * Purpose: Test suite for the document builder
+ *
+ * Test Categories:
+ * - Built documents hold the text
+ * - Authorship is shared between clients
+ */
+
+use crdt_editor_backend::{fixtures::DocumentBuilder, Operation, Position};
+
+#[test]
+fn test_builds_document_with_text() {
+    let document = DocumentBuilder::with_text("Hello").id("notes").build();
+    assert_eq!(document.content(), "Hello");
+    assert_eq!(document.version(), 5);
+
+    let empty = DocumentBuilder::new("empty").build();
+    assert_eq!(empty.content(), "");
+}
+
+#[test]
+fn test_clients_take_turns() {
+    let builder = DocumentBuilder::with_text("Hello").with_clients(3);
+    assert_eq!(builder.client_ids(), vec!["client1", "client2", "client3"]);
+
+    let authors: Vec<String> = builder.operations().iter().map(|op| op.client_id().to_string()).collect();
+    assert_eq!(authors, vec!["client1", "client2", "client3", "client1", "client2"]);
+
+    // Room is left for later inserts between the characters
+    let mut document = builder.build();
+    let positions = Position::spread(5);
+    let between = Position::new(vec![positions[0].path()[0] + 1]);
+    document.apply(Operation::insert("client4".to_string(), '!', between));
+    assert_eq!(document.content(), "H!ello");
+}
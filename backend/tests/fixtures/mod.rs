@@ -0,0 +1,11 @@
+/*
+ * File: tests/fixtures/mod.rs
+ * Purpose: Test module organization for test fixtures
+ *
+ * Test modules:
+ * - document_tests: Tests for the document builder
+ *
+ * The server fixtures are exercised by `websocket::server_tests`.
+ */
+
+mod document_tests;
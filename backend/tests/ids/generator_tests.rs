@@ -0,0 +1,50 @@
+/*
+ * File: tests/ids/generator_tests.rs
+ * Purpose: Test suite for client and document ID generation
+ *
+ * Test Categories:
+ * - UUID versions of generated IDs
+ * - Time ordering of UUIDv7 IDs
+ * - Document slug format
+ */
+
+use std::collections::HashSet;
+use crdt_editor_backend::ids::{IdGenerator, SlugIds, UuidV4Ids, UuidV7Ids};
+use uuid::Uuid;
+
+#[test]
+fn test_uuid_versions() {
+    let v7 = Uuid::parse_str(&UuidV7Ids.client_id()).unwrap();
+    assert_eq!(v7.get_version_num(), 7);
+    let v4 = Uuid::parse_str(&UuidV4Ids.document_id()).unwrap();
+    assert_eq!(v4.get_version_num(), 4);
+}
+
+#[test]
+fn test_uuid_v7_ids_sort_by_creation() {
+    let ids: Vec<String> = (0..100)
+        .map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            UuidV7Ids.document_id()
+        })
+        .collect();
+
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+}
+
+#[test]
+fn test_document_slugs() {
+    let slugs: HashSet<String> = (0..1000).map(|_| SlugIds.document_id()).collect();
+    assert_eq!(slugs.len(), 1000);
+
+    for slug in &slugs {
+        let (left, right) = slug.split_once('-').unwrap();
+        assert_eq!((left.len(), right.len()), (4, 4));
+        assert!(slug.chars().all(|c| c == '-' || "23456789abcdefghjkmnpqrstuvwxyz".contains(c)));
+    }
+
+    // Client IDs stay UUIDs
+    assert!(Uuid::parse_str(&SlugIds.client_id()).is_ok());
+}
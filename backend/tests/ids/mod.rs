@@ -0,0 +1,9 @@
+/*
+ * File: tests/ids/mod.rs
+ * Purpose: Test module organization for identifier generation
+ *
+ * Test modules:
+ * - generator_tests: Tests for the ID generators
+ */
+
+mod generator_tests;
@@ -0,0 +1,9 @@
+/*
+ * File: tests/jobs/mod.rs
+ * Purpose: Test module organization for the job queue
+ *
+ * Test modules:
+ * - queue_tests: Tests for queuing, progress and cancellation of jobs
+ */
+
+mod queue_tests;
@@ -0,0 +1,95 @@
+/*
+ * File: tests/jobs/queue_tests.rs
+ * Purpose: Test suite for the job queue
+ *
+ * Test Categories:
+ * - Completion: results, failures and progress of finished jobs
+ * - Queuing: jobs beyond the running limit wait for a slot
+ * - Cancellation: queued and running jobs can be cancelled, finished ones can't
+ */
+
+use std::{sync::Arc, time::Duration};
+use serde_json::json;
+use tokio::sync::oneshot;
+use crdt_editor_backend::jobs::{Job, JobError, JobProgress, JobQueue, JobStatus};
+
+/// Wait until a job has finished
+async fn finished(queue: &JobQueue, id: u64) -> Job {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let job = queue.get(id).unwrap();
+            if job.status.is_finished() {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("job did not finish")
+}
+
+#[tokio::test]
+async fn test_jobs_complete_with_progress() {
+    let queue = Arc::new(JobQueue::default());
+    let id = queue.enqueue("count", |job| async move {
+        job.set_total(3);
+        for _ in 0..3 {
+            job.advance();
+        }
+        Ok(json!({ "counted": 3 }))
+    });
+    let job = finished(&queue, id).await;
+    assert_eq!(job.kind, "count");
+    assert_eq!(job.status, JobStatus::Completed);
+    assert_eq!(job.progress, JobProgress { done: 3, total: 3 });
+    assert_eq!(job.result, Some(json!({ "counted": 3 })));
+    assert!(job.finished_at.is_some());
+
+    let id = queue.enqueue("broken", |_| async { Err(JobError::Failed("disk full".to_string())) });
+    let job = finished(&queue, id).await;
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.error.as_deref(), Some("disk full"));
+    assert_eq!(queue.list().len(), 2);
+}
+
+#[tokio::test]
+async fn test_jobs_wait_for_a_slot() {
+    let queue = Arc::new(JobQueue::new(1));
+    let (release, released) = oneshot::channel::<()>();
+    let first = queue.enqueue("first", |_| async move {
+        let _ = released.await;
+        Ok(json!(null))
+    });
+    let second = queue.enqueue("second", |_| async { Ok(json!(null)) });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(queue.get(first).unwrap().status, JobStatus::Running);
+    assert_eq!(queue.get(second).unwrap().status, JobStatus::Queued);
+
+    release.send(()).unwrap();
+    assert_eq!(finished(&queue, second).await.status, JobStatus::Completed);
+}
+
+#[tokio::test]
+async fn test_cancel_jobs() {
+    let queue = Arc::new(JobQueue::new(1));
+    let running = queue.enqueue("loop", |job| async move {
+        loop {
+            job.check_cancelled()?;
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    });
+    let queued = queue.enqueue("never", |_| async { Ok(json!("ran")) });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    queue.cancel(queued).unwrap();
+    assert_eq!(queue.get(queued).unwrap().status, JobStatus::Cancelled);
+    queue.cancel(running).unwrap();
+    assert_eq!(finished(&queue, running).await.status, JobStatus::Cancelled);
+
+    // The cancelled job never ran, even once the slot was free
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(queue.get(queued).unwrap().result, None);
+    assert_eq!(queue.cancel(running), Err(JobError::Finished(running)));
+    assert_eq!(queue.cancel(999), Err(JobError::NotFound(999)));
+}
@@ -0,0 +1,158 @@
+/*
+ * File: tests/metrics/abuse_tests.rs
+ * Purpose: Test suite for abusive editing rate detection
+ *
+ * Test Categories:
+ * - Alerting once per burst on operation rates and delete storms
+ * - Restricting writes, lifting restrictions and expiring them
+ * - Refusing operations of restricted clients on the server and the admin API
+ */
+
+use std::time::{Duration, Instant};
+use crdt_editor_backend::{
+    crdt::{Operation, Position},
+    fixtures::TestServer,
+    metrics::{AbuseConfig, AbuseDetector, AbuseError, AbuseKind},
+    tenant::DEFAULT_TENANT,
+    websocket::{message::OperationMessage, AdminConfig, MessageType, ServerConfig},
+};
+
+fn insert() -> Operation {
+    Operation::insert("alice".to_string(), 'a', Position::start())
+}
+
+fn delete() -> Operation {
+    Operation::delete("alice".to_string(), Position::start())
+}
+
+#[test]
+fn test_alerts_once_per_burst() {
+    let detector = AbuseDetector::new(AbuseConfig {
+        max_operations_per_second: Some(10),
+        max_deletes_per_second: Some(2),
+        window: Duration::from_secs(1),
+        restrict_for: None,
+    });
+    let start = Instant::now();
+
+    // Ten inserts a second are allowed; the eleventh goes over
+    for _ in 0..10 {
+        assert!(detector.observe(DEFAULT_TENANT, "alice", &insert(), start).unwrap().is_empty());
+    }
+    let alerts = detector.observe(DEFAULT_TENANT, "alice", &insert(), start).unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].kind, AbuseKind::OperationRate);
+    assert_eq!(alerts[0].limit, 10);
+    assert_eq!(alerts[0].restricted_until, None);
+
+    // The burst goes on without alerting again, except for the deletes
+    let later = start + Duration::from_millis(200);
+    let kinds: Vec<AbuseKind> = (0..3)
+        .flat_map(|_| detector.observe(DEFAULT_TENANT, "alice", &delete(), later).unwrap())
+        .map(|alert| alert.kind)
+        .collect();
+    assert_eq!(kinds, vec![AbuseKind::DeleteStorm]);
+
+    // Once the rate falls back under the limit, a new burst alerts again
+    let quiet = start + Duration::from_secs(2);
+    assert!(detector.observe(DEFAULT_TENANT, "alice", &insert(), quiet).unwrap().is_empty());
+    let alerts: usize = (0..11).map(|_| detector.observe(DEFAULT_TENANT, "alice", &insert(), quiet).unwrap().len()).sum();
+    assert_eq!(alerts, 1);
+
+    // Other clients are counted on their own
+    assert!(detector.observe(DEFAULT_TENANT, "bob", &insert(), quiet).unwrap().is_empty());
+    assert_eq!(detector.alerts_raised(), 3);
+    let report = detector.report(quiet);
+    assert_eq!(report.alerts.len(), 3);
+    assert_eq!(report.alerts[1].kind, AbuseKind::DeleteStorm);
+    assert!(report.restricted.is_empty());
+}
+
+#[test]
+fn test_restrictions_refuse_writes_until_lifted_or_over() {
+    let detector = AbuseDetector::new(AbuseConfig {
+        max_operations_per_second: None,
+        max_deletes_per_second: Some(1),
+        window: Duration::from_secs(1),
+        restrict_for: Some(Duration::from_secs(60)),
+    });
+    let start = Instant::now();
+    detector.observe(DEFAULT_TENANT, "alice", &delete(), start).unwrap();
+    let alerts = detector.observe(DEFAULT_TENANT, "alice", &delete(), start).unwrap();
+    assert!(alerts[0].restricted_until.is_some());
+
+    // The restriction holds past a disconnect, until lifted
+    assert!(matches!(detector.observe(DEFAULT_TENANT, "alice", &insert(), start), Err(AbuseError::Restricted { .. })));
+    detector.forget(DEFAULT_TENANT, "alice", start);
+    let report = detector.report(start);
+    assert_eq!(report.restricted.len(), 1);
+    assert_eq!(report.restricted[0].client_id, "alice");
+    detector.lift(DEFAULT_TENANT, "alice", start).unwrap();
+    assert!(matches!(detector.lift(DEFAULT_TENANT, "alice", start), Err(AbuseError::NotRestricted(_))));
+    assert!(detector.observe(DEFAULT_TENANT, "alice", &insert(), start).is_ok());
+
+    // Restrictions also end on their own
+    let quiet = start + Duration::from_secs(5);
+    detector.observe(DEFAULT_TENANT, "bob", &delete(), quiet).unwrap();
+    detector.observe(DEFAULT_TENANT, "bob", &delete(), quiet).unwrap();
+    assert!(detector.observe(DEFAULT_TENANT, "bob", &insert(), quiet + Duration::from_secs(59)).is_err());
+    assert!(detector.observe(DEFAULT_TENANT, "bob", &insert(), quiet + Duration::from_secs(61)).is_ok());
+    assert!(detector.report(quiet + Duration::from_secs(61)).restricted.is_empty());
+}
+
+#[test]
+fn test_detection_is_off_by_default() {
+    let detector = AbuseDetector::new(AbuseConfig::default());
+    let now = Instant::now();
+    for _ in 0..10_000 {
+        assert!(detector.observe(DEFAULT_TENANT, "alice", &delete(), now).unwrap().is_empty());
+    }
+    assert_eq!(detector.alerts_raised(), 0);
+}
+
+#[tokio::test]
+async fn test_server_restricts_abusive_clients() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        abuse: AbuseConfig {
+            max_operations_per_second: Some(2),
+            window: Duration::from_secs(1),
+            restrict_for: Some(Duration::from_secs(60)),
+            ..Default::default()
+        },
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        ..Default::default()
+    });
+    let mut client = server.connect().await;
+    client.create_document("notes", "").await;
+
+    // The operation raising the alert is applied; the next ones are refused
+    let positions = Position::spread(4);
+    client.type_text("notes", "abc").await;
+    let operation = Operation::insert(client.id().to_string(), 'd', positions[3].clone());
+    client.request(MessageType::Operation, OperationMessage::new(operation, "notes".to_string())).await;
+    let error = client.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains("restricted"), "{}", error.payload());
+    assert_eq!(server.server().metrics().abuse_alerts, 1);
+
+    let routes = server.server().routes();
+    let reply = warp::test::request()
+        .path("/admin/abuse")
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(reply.status(), 200);
+    let report: serde_json::Value = serde_json::from_slice(reply.body()).unwrap();
+    assert_eq!(report["alerts"][0]["kind"], "operation_rate");
+    assert_eq!(report["restricted"][0]["client_id"], client.id());
+
+    // Lifting the restriction lets the client edit again
+    let reply = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/admin/abuse/{}/{}", DEFAULT_TENANT, client.id()))
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(reply.status(), 204);
+    assert!(server.server().abuse_report().restricted.is_empty());
+    client.insert("notes", 'd', positions[3].clone()).await;
+}
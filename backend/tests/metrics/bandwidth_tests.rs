@@ -0,0 +1,84 @@
+/*
+ * File: tests/metrics/bandwidth_tests.rs
+ * Purpose: Test suite for bandwidth accounting and caps
+ *
+ * Test Categories:
+ * - Counting bytes per client and document, and reporting the heaviest
+ * - Throttling clients over their cap
+ */
+
+use std::time::{Duration, Instant};
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    metrics::{BandwidthConfig, BandwidthMeter, BandwidthUsage, Direction},
+    tenant::DEFAULT_TENANT,
+    websocket::ServerConfig,
+};
+
+#[test]
+fn test_bytes_counted_per_client_and_document() {
+    let meter = BandwidthMeter::new(BandwidthConfig::default());
+    meter.record(DEFAULT_TENANT, "alice", Some("notes"), Direction::In, 100);
+    meter.record(DEFAULT_TENANT, "alice", Some("notes"), Direction::Out, 40);
+    meter.record(DEFAULT_TENANT, "bob", Some("notes"), Direction::Out, 40);
+    meter.record(DEFAULT_TENANT, "bob", Some("todo"), Direction::In, 500);
+    meter.record(DEFAULT_TENANT, "bob", None, Direction::In, 7);
+
+    assert_eq!(meter.client("alice"), Some(BandwidthUsage { bytes_in: 100, bytes_out: 40 }));
+    assert_eq!(meter.document(DEFAULT_TENANT, "notes"), Some(BandwidthUsage { bytes_in: 100, bytes_out: 80 }));
+    assert_eq!(meter.totals(), BandwidthUsage { bytes_in: 607, bytes_out: 80 });
+
+    let report = meter.report(1);
+    assert_eq!(report.clients.len(), 1);
+    assert_eq!(report.clients[0].client_id, "bob");
+    assert_eq!(report.documents[0].document_id, "todo");
+
+    // Without caps nothing is throttled; sessions end with their client
+    assert_eq!(meter.throttle_delay(DEFAULT_TENANT, "bob", Some("todo")), Duration::ZERO);
+    meter.forget_client("bob");
+    assert_eq!(meter.client("bob"), None);
+    assert!(meter.document(DEFAULT_TENANT, "todo").is_some());
+}
+
+#[test]
+fn test_throttle_delay_follows_caps() {
+    let meter = BandwidthMeter::new(BandwidthConfig {
+        client_bytes_per_second: Some(1000),
+        document_bytes_per_second: Some(100),
+    });
+
+    // A second's worth of the cap is allowed at once
+    meter.record(DEFAULT_TENANT, "alice", None, Direction::In, 1000);
+    assert_eq!(meter.throttle_delay(DEFAULT_TENANT, "alice", None), Duration::ZERO);
+    meter.record(DEFAULT_TENANT, "alice", None, Direction::Out, 500);
+    let delay = meter.throttle_delay(DEFAULT_TENANT, "alice", None);
+    assert!(delay > Duration::from_millis(400) && delay <= Duration::from_millis(500), "{:?}", delay);
+
+    // The stricter cap of the document holds the client back longer
+    meter.record(DEFAULT_TENANT, "bob", Some("notes"), Direction::In, 300);
+    let delay = meter.throttle_delay(DEFAULT_TENANT, "bob", Some("notes"));
+    assert!(delay > Duration::from_millis(1900) && delay <= Duration::from_secs(2), "{:?}", delay);
+    assert_eq!(meter.throttled(), 2);
+}
+
+#[tokio::test]
+async fn test_server_counts_and_throttles_clients() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        bandwidth: BandwidthConfig { client_bytes_per_second: Some(2000), document_bytes_per_second: None },
+        ..Default::default()
+    });
+    let mut client = server.connect().await;
+    client.create_document("notes", "").await;
+
+    let start = Instant::now();
+    client.type_text("notes", "hello world").await;
+    let metrics = server.server().metrics();
+    assert!(metrics.bytes_in > 2000 && metrics.bytes_out > 0, "{:?}", metrics);
+    assert!(metrics.throttled_messages > 0);
+    assert!(start.elapsed() > Duration::from_millis(500));
+
+    let report = server.server().bandwidth(10);
+    assert_eq!(report.clients[0].client_id, client.id());
+    assert_eq!(report.documents[0].document_id, "notes");
+    assert!(report.documents[0].usage.bytes_in > 2000);
+}
@@ -0,0 +1,76 @@
+/*
+ * File: tests/metrics/labels_tests.rs
+ * Purpose: Test suite for counters with bounded label sets
+ *
+ * Test Categories:
+ * - Keeping the busiest tenants and documents, summing the rest
+ * - Counting operations through the server
+ */
+
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    metrics::{LabelConfig, LabelLimit, LabeledCounter, LabeledValue, OTHER_LABEL},
+    tenant::DEFAULT_TENANT,
+    websocket::ServerConfig,
+};
+
+fn series(tenant: &str, document: &str, value: u64) -> LabeledValue {
+    LabeledValue { tenant: tenant.to_string(), document: document.to_string(), value }
+}
+
+fn count(counter: &LabeledCounter, tenant: &str, document: &str, times: u64) {
+    for _ in 0..times {
+        counter.increment(tenant, document);
+    }
+}
+
+#[test]
+fn test_busiest_labels_kept() {
+    let counter = LabeledCounter::default();
+    count(&counter, "acme", "plan", 5);
+    count(&counter, "acme", "notes", 3);
+    count(&counter, "acme", "draft", 1);
+    count(&counter, "globex", "a", 2);
+    count(&counter, "initech", "b", 1);
+    count(&counter, "initech", "c", 1);
+
+    let unlimited = LabelConfig { tenants: LabelLimit::Unlimited, documents: LabelLimit::Unlimited };
+    assert_eq!(counter.export(unlimited).len(), 6);
+
+    let limited = LabelConfig { tenants: LabelLimit::Top(2), documents: LabelLimit::Top(2) };
+    assert_eq!(
+        counter.export(limited),
+        [
+            series("acme", "notes", 3),
+            series("acme", OTHER_LABEL, 1),
+            series("acme", "plan", 5),
+            series("globex", "a", 2),
+            series(OTHER_LABEL, OTHER_LABEL, 2),
+        ]
+    );
+    // Nothing is lost by bucketing
+    let total: u64 = counter.export(limited).iter().map(|series| series.value).sum();
+    assert_eq!(total, 13);
+    assert_eq!(counter.get("acme", "draft"), 1);
+
+    let totals_only = LabelConfig { tenants: LabelLimit::Top(0), documents: LabelLimit::Top(0) };
+    assert_eq!(counter.export(totals_only), [series(OTHER_LABEL, OTHER_LABEL, 13)]);
+}
+
+#[tokio::test]
+async fn test_operations_counted_per_document() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        metric_labels: LabelConfig { tenants: LabelLimit::Unlimited, documents: LabelLimit::Top(1) },
+        ..Default::default()
+    });
+    let mut client = server.connect().await;
+    client.create_document("busy", "").await;
+    client.type_text("busy", "abc").await;
+    client.create_document("quiet", "").await;
+    client.type_text("quiet", "x").await;
+
+    assert_eq!(
+        server.server().metrics().operations,
+        [series(DEFAULT_TENANT, "busy", 3), series(DEFAULT_TENANT, OTHER_LABEL, 1)]
+    );
+}
@@ -0,0 +1,97 @@
+/*
+ * File: tests/metrics/memory_tests.rs
+ * Purpose: Test suite for memory breakdowns and their trend
+ *
+ * Test Categories:
+ * - Keeping the latest samples
+ * - Breaking down the memory of a server's documents
+ * - Reporting it over the admin API
+ */
+
+use chrono::Utc;
+use serde_json::Value;
+use crdt_editor_backend::{
+    crdt::MemoryReport,
+    fixtures::TestServer,
+    metrics::{MemoryConfig, MemorySample, MemoryTrend},
+    tenant::DEFAULT_TENANT,
+    websocket::{AdminConfig, ServerConfig},
+};
+
+fn sample(documents: usize) -> MemorySample {
+    MemorySample { at: Utc::now(), documents, memory: MemoryReport::default() }
+}
+
+#[test]
+fn test_trend_keeps_latest_samples() {
+    let trend = MemoryTrend::new(2);
+    assert_eq!(trend.latest(), None);
+    for documents in 1..=3 {
+        trend.record(sample(documents));
+    }
+    let kept: Vec<usize> = trend.samples().iter().map(|sample| sample.documents).collect();
+    assert_eq!(kept, [2, 3]);
+    assert_eq!(trend.latest().unwrap().documents, 3);
+
+    let none = MemoryTrend::new(0);
+    none.record(sample(1));
+    assert!(none.samples().is_empty());
+}
+
+#[tokio::test]
+async fn test_server_samples_document_memory() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        memory: MemoryConfig { samples: 10, ..Default::default() },
+        ..Default::default()
+    });
+    let mut alice = server.connect().await;
+    alice.create_document("small", "hi").await;
+    alice.create_document("large", "a longer document").await;
+
+    let before = server.server().sample_memory().await;
+    assert_eq!(before.documents, 2);
+    assert_eq!(before.memory.live.count, "hi".len() + "a longer document".len());
+    assert_eq!(server.server().metrics().memory, Some(before.clone()));
+
+    alice.type_text("small", "!").await;
+    server.server().sample_memory().await;
+    let overview = server.server().memory(1).await;
+    assert_eq!(overview.current.documents, 2);
+    assert_eq!(overview.documents.len(), 1);
+    assert_eq!((overview.documents[0].tenant_id.as_str(), overview.documents[0].document_id.as_str()), (DEFAULT_TENANT, "large"));
+    assert_eq!(overview.trend.len(), 2);
+    assert!(overview.trend[1].memory.total_bytes() > before.memory.total_bytes());
+}
+
+#[tokio::test]
+async fn test_memory_over_admin_api() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        ..Default::default()
+    });
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "hello").await;
+
+    let routes = server.server().routes();
+    let reply = warp::test::request()
+        .path("/admin/memory?limit=5")
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(reply.status(), 200);
+    let body: Value = serde_json::from_slice(reply.body()).unwrap();
+    assert_eq!(body["current"]["live"]["count"], 5);
+    assert_eq!(body["documents"][0]["document_id"], "notes");
+    for part in ["tombstones", "operations", "indexes"] {
+        assert!(body["documents"][0][part]["bytes"].is_u64(), "{}", part);
+    }
+
+    let reply = warp::test::request()
+        .path("/admin/memory?limit=many")
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(reply.status(), 400);
+    let reply = warp::test::request().path("/admin/memory").reply(&routes).await;
+    assert_eq!(reply.status(), 401);
+}
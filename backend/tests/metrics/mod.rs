@@ -0,0 +1,17 @@
+/*
+ * File: tests/metrics/mod.rs
+ * Purpose: Test module organization for server metrics
+ *
+ * Test modules:
+ * - abuse_tests: Tests for abusive editing rate detection
+ * - bandwidth_tests: Tests for bandwidth accounting and caps
+ * - labels_tests: Tests for counters with bounded label sets
+ * - memory_tests: Tests for memory breakdowns and their trend
+ * - overload_tests: Tests for overload detection and shedding
+ */
+
+mod abuse_tests;
+mod bandwidth_tests;
+mod labels_tests;
+mod memory_tests;
+mod overload_tests;
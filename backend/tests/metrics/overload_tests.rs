@@ -0,0 +1,86 @@
+/*
+ * File: tests/metrics/overload_tests.rs
+ * Purpose: Test suite for overload detection and shedding
+ *
+ * Test Categories:
+ * - Shedding starts above the budget and stops below the recovery threshold
+ * - Shedding stops when operations stop
+ * - The server refuses non-essential requests while shedding
+ */
+
+use std::time::Duration;
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    metrics::{overload::EVALUATE_EVERY, OverloadChange, OverloadConfig, OverloadDetector},
+    websocket::{message::PlaybackRequestMessage, MessageType, ServerConfig},
+};
+
+fn detector(quiet_period: Duration) -> OverloadDetector {
+    OverloadDetector::new(OverloadConfig {
+        budget: Some(Duration::from_millis(10)),
+        recovery_ratio: 0.5,
+        quiet_period,
+    })
+}
+
+/// Record a batch of equal latencies, returning the last change
+fn record(detector: &OverloadDetector, latency: Duration, count: usize) -> Option<OverloadChange> {
+    (0..count).filter_map(|_| detector.record(latency)).last()
+}
+
+#[test]
+fn test_shedding_follows_latency() {
+    let off = OverloadDetector::new(OverloadConfig::default());
+    assert_eq!(record(&off, Duration::from_secs(1), EVALUATE_EVERY), None);
+    assert!(!off.is_shedding());
+
+    let detector = detector(Duration::from_secs(60));
+    assert_eq!(record(&detector, Duration::from_millis(1), EVALUATE_EVERY), None);
+    let change = record(&detector, Duration::from_millis(50), 1024);
+    assert_eq!(change, Some(OverloadChange::Started { p99: Duration::from_millis(50) }));
+    assert!(detector.is_shedding());
+    assert!(detector.status().since.is_some());
+
+    // Below the budget but above the recovery threshold keeps shedding
+    assert_eq!(record(&detector, Duration::from_millis(8), 1024), None);
+    assert!(detector.is_shedding());
+    assert_eq!(
+        record(&detector, Duration::from_millis(2), 1024),
+        Some(OverloadChange::Ended { p99: Duration::from_millis(2) })
+    );
+    assert!(!detector.is_shedding());
+    assert_eq!(detector.status().p99_ms, Some(2.0));
+}
+
+#[test]
+fn test_shedding_stops_when_quiet() {
+    let detector = detector(Duration::from_millis(20));
+    record(&detector, Duration::from_millis(50), EVALUATE_EVERY);
+    assert!(detector.is_shedding());
+
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(!detector.is_shedding());
+    assert!(detector.status().since.is_none());
+}
+
+#[tokio::test]
+async fn test_server_sheds_non_essential_requests() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        overload: OverloadConfig {
+            budget: Some(Duration::from_nanos(1)),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let mut client = server.connect().await;
+    let text = "a".repeat(EVALUATE_EVERY);
+    client.type_text("doc1", &text).await;
+    assert!(server.server().overload().shedding);
+
+    // Edits still go through; history playback doesn't
+    client.type_text("doc2", "b").await;
+    client.request(MessageType::PlaybackRequest, PlaybackRequestMessage::new("doc1".to_string(), None)).await;
+    let error = client.expect(MessageType::Error).await;
+    assert!(error.payload().to_string().contains("overloaded"));
+    assert_eq!(server.server().metrics().shed_requests, 1);
+}
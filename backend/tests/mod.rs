@@ -3,9 +3,39 @@
  * Purpose: Test module organization
  * 
  * Test modules:
+ * - blocks: Tests for code blocks, checklists, links, export, and syntax checks
+ * - client: Tests for the client SDK
+ * - commands: Tests for server-side commands
  * - crdt: Tests for CRDT implementation
+ * - fixtures: Tests for test fixtures
+ * - ids: Tests for identifier generation
+ * - jobs: Tests for the job queue
+ * - metrics: Tests for server metrics
+ * - moderation: Tests for content moderation
+ * - policy: Tests for operation policies
+ * - presence: Tests for presence of clients in documents
+ * - security: Tests for security features
+ * - storage: Tests for document persistence
+ * - telemetry: Tests for usage statistics
+ * - tenant: Tests for multi-tenancy
+ * - undo: Tests for server-side undo
  * - websocket: Tests for WebSocket server
  */
 
+mod blocks;
+mod client;
+mod commands;
 mod crdt;
+mod fixtures;
+mod ids;
+mod jobs;
+mod metrics;
+mod moderation;
+mod policy;
+mod presence;
+mod security;
+mod storage;
+mod telemetry;
+mod tenant;
+mod undo;
 mod websocket;
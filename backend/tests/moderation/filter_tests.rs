@@ -0,0 +1,78 @@
+/*
+ * File: tests/moderation/filter_tests.rs
+ * Purpose: Test suite for content filters
+ *
+ * Test Categories:
+ * - Whole-word, case-insensitive wordlist matching
+ * - Wordlist parsing
+ * - External HTTP filter results and failures
+ */
+
+use warp::Filter;
+use serde_json::{json, Value};
+use crdt_editor_backend::moderation::{ContentFilter, HttpFilter, WordlistFilter};
+
+fn texts(texts: &[&str]) -> Vec<String> {
+    texts.iter().map(|text| text.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_wordlist_matches_whole_words() {
+    let filter = WordlistFilter::new(["darn", "Heck"]);
+
+    assert_eq!(filter.matches("DARN it"), vec!["darn"]);
+    assert_eq!(filter.matches("heck, darn!"), vec!["heck", "darn"]);
+    assert!(filter.matches("darned checkers").is_empty());
+
+    let results = filter.check(&texts(&["fine", "heck", ""])).await.unwrap();
+    assert_eq!(results, vec![Vec::<String>::new(), vec!["heck".to_string()], Vec::new()]);
+}
+
+#[test]
+fn test_wordlist_parsing() {
+    let filter: WordlistFilter = "# Classroom list\ndarn\n\n  heck  \n".parse().unwrap();
+    assert_eq!(filter.matches("darn heck classroom"), vec!["darn", "heck"]);
+}
+
+#[tokio::test]
+async fn test_http_filter_results() {
+    let route = warp::post()
+        .and(warp::path("moderate"))
+        .and(warp::body::json())
+        .map(|request: Value| {
+            let results: Vec<Vec<String>> = request["texts"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|text| match text.as_str() {
+                    Some("darn") => vec!["darn".to_string()],
+                    _ => Vec::new(),
+                })
+                .collect();
+            warp::reply::json(&json!({ "results": results }))
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let filter = HttpFilter::new(&format!("http://{}/moderate", addr)).unwrap();
+    let results = filter.check(&texts(&["hello", "darn"])).await.unwrap();
+    assert_eq!(results, vec![Vec::<String>::new(), vec!["darn".to_string()]]);
+}
+
+#[tokio::test]
+async fn test_http_filter_failures() {
+    assert!(HttpFilter::new("not a url").is_err());
+    assert!(HttpFilter::new("https://moderation.example").is_err());
+
+    // Too few results for the batch
+    let route = warp::post().map(|| warp::reply::json(&json!({ "results": [] })));
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let filter = HttpFilter::new(&format!("http://{}/", addr)).unwrap();
+    assert!(filter.check(&texts(&["hello"])).await.is_err());
+
+    // Nothing listens on the port
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let filter = HttpFilter::new(&format!("http://127.0.0.1:{}/", port)).unwrap();
+    assert!(filter.check(&texts(&["hello"])).await.is_err());
+}
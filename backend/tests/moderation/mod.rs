@@ -0,0 +1,11 @@
+/*
+ * File: tests/moderation/mod.rs
+ * Purpose: Test module organization for content moderation
+ *
+ * Test modules:
+ * - filter_tests: Tests for the wordlist and HTTP content filters
+ * - regions_tests: Tests for grouping inserted characters into words
+ */
+
+mod filter_tests;
+mod regions_tests;
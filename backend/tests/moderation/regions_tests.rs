@@ -0,0 +1,76 @@
+/*
+ * File: tests/moderation/regions_tests.rs
+ * Purpose: Test suite for grouping inserted characters into words
+ *
+ * Test Categories:
+ * - Words ending at non-alphanumeric characters
+ * - Separate words per client and document
+ * - Deletes and disconnects
+ */
+
+use crdt_editor_backend::{
+    crdt::{Operation, Position},
+    moderation::{regions::MAX_REGION_CHARS, RegionTracker},
+};
+
+fn insert(client: &str, character: char, path: u32) -> Operation {
+    Operation::insert(client.to_string(), character, Position::new(vec![path]))
+}
+
+/// Type a string starting at `path`, returning the words it completes
+fn type_text(tracker: &mut RegionTracker, document: &str, client: &str, text: &str, path: u32) -> Vec<String> {
+    text.chars()
+        .enumerate()
+        .filter_map(|(i, c)| tracker.observe("default", document, client, &insert(client, c, path + i as u32)))
+        .map(|region| region.text())
+        .collect()
+}
+
+#[test]
+fn test_words_end_at_boundaries() {
+    let mut tracker = RegionTracker::new();
+
+    assert_eq!(type_text(&mut tracker, "doc", "alice", "hi there, you", 1), vec!["hi", "there"]);
+    assert_eq!(tracker.pending_count(), 1);
+
+    // Long runs are checked without waiting for a boundary
+    let long = "a".repeat(MAX_REGION_CHARS);
+    assert_eq!(type_text(&mut tracker, "doc", "bob", &long, 100), vec![long]);
+}
+
+#[test]
+fn test_words_tracked_per_client_and_document() {
+    let mut tracker = RegionTracker::new();
+
+    type_text(&mut tracker, "doc", "alice", "he", 1);
+    type_text(&mut tracker, "doc", "bob", "xy", 10);
+    type_text(&mut tracker, "other", "alice", "zz", 20);
+
+    let region = tracker.observe("default", "doc", "alice", &insert("alice", ' ', 3)).unwrap();
+    assert_eq!(region.text(), "he");
+    assert_eq!((region.document_id.as_str(), region.client_id.as_str()), ("doc", "alice"));
+    assert_eq!(region.characters[0].0, Position::new(vec![1]));
+    assert_eq!(tracker.pending_count(), 2);
+}
+
+#[test]
+fn test_deletes_and_disconnects() {
+    let mut tracker = RegionTracker::new();
+
+    type_text(&mut tracker, "doc", "alice", "darn", 1);
+    tracker.observe("default", "doc", "alice", &Operation::delete("alice".to_string(), Position::new(vec![4])));
+    type_text(&mut tracker, "other", "alice", "ok", 10);
+    type_text(&mut tracker, "doc", "bob", "hi", 20);
+
+    let mut finished: Vec<String> = tracker.finish_client("alice").iter().map(|region| region.text()).collect();
+    finished.sort();
+    assert_eq!(finished, vec!["dar", "ok"]);
+    assert_eq!(tracker.pending_count(), 1);
+
+    // Deleting every character of a word drops it
+    for path in 20..22 {
+        tracker.observe("default", "doc", "bob", &Operation::delete("bob".to_string(), Position::new(vec![path])));
+    }
+    assert_eq!(tracker.pending_count(), 0);
+    assert!(tracker.observe("default", "doc", "bob", &insert("bob", ' ', 22)).is_none());
+}
@@ -0,0 +1,125 @@
+/*
+ * File: tests/policy/engine_tests.rs
+ * Purpose: Test suite for policy engines
+ *
+ * Test Categories:
+ * - Decision caching per identity and document
+ * - External HTTP policy decisions
+ * - Failing closed and open
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use futures::future::BoxFuture;
+use warp::Filter;
+use crdt_editor_backend::{
+    crdt::{Operation, Position},
+    policy::{CachedPolicy, Decision, DocumentInfo, HttpPolicy, PolicyEngine, PolicyInput},
+};
+
+fn input(client: &str, document: &str) -> PolicyInput {
+    let info = DocumentInfo {
+        id: document.to_string(),
+        exists: true,
+        length: 0,
+        version: 0,
+    };
+    let operation = Operation::insert(client.to_string(), 'a', Position::new(vec![1]));
+    PolicyInput::new("default", client, info, &operation)
+}
+
+/// Engine counting its evaluations
+#[derive(Debug)]
+struct Counting {
+    calls: AtomicUsize,
+    decision: Decision,
+}
+
+impl PolicyEngine for Counting {
+    fn evaluate<'a>(&'a self, _input: &'a PolicyInput) -> BoxFuture<'a, Decision> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { self.decision.clone() })
+    }
+}
+
+fn counting(decision: Decision) -> Arc<Counting> {
+    Arc::new(Counting {
+        calls: AtomicUsize::new(0),
+        decision,
+    })
+}
+
+#[tokio::test]
+async fn test_decisions_cached_per_identity_and_document() {
+    let inner = counting(Decision::deny("no"));
+    let cached = CachedPolicy::new(inner.clone(), Duration::from_secs(60));
+
+    assert_eq!(cached.evaluate(&input("alice", "notes")).await, Decision::deny("no"));
+    assert_eq!(cached.evaluate(&input("alice", "notes")).await, Decision::deny("no"));
+    assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+    cached.evaluate(&input("bob", "notes")).await;
+    cached.evaluate(&input("alice", "todo")).await;
+    assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+
+    cached.clear();
+    cached.evaluate(&input("alice", "notes")).await;
+    assert_eq!(inner.calls.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_expired_and_transform_decisions_not_reused() {
+    let inner = counting(Decision::Allow);
+    let expired = CachedPolicy::new(inner.clone(), Duration::ZERO);
+    expired.evaluate(&input("alice", "notes")).await;
+    expired.evaluate(&input("alice", "notes")).await;
+    assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+    let operation = Operation::insert("alice".to_string(), '*', Position::new(vec![1]));
+    let inner = counting(Decision::Transform { operation });
+    let cached = CachedPolicy::new(inner.clone(), Duration::from_secs(60));
+    cached.evaluate(&input("alice", "notes")).await;
+    cached.evaluate(&input("alice", "notes")).await;
+    assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_http_policy_decisions() {
+    let route = warp::post()
+        .and(warp::path("policy"))
+        .and(warp::body::json())
+        .map(|input: PolicyInput| {
+            let decision = if input.client_id == "guest" {
+                Decision::deny("Guests can't edit")
+            } else {
+                Decision::Allow
+            };
+            warp::reply::json(&decision)
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let policy = HttpPolicy::new(&format!("http://{}/policy", addr)).unwrap();
+    assert_eq!(policy.evaluate(&input("alice", "notes")).await, Decision::Allow);
+    assert_eq!(policy.evaluate(&input("guest", "notes")).await, Decision::deny("Guests can't edit"));
+}
+
+#[tokio::test]
+async fn test_http_policy_failures() {
+    assert!(HttpPolicy::new("not a url").is_err());
+    assert!(HttpPolicy::new("https://policy.example").is_err());
+
+    // Nothing listens on the port
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let url = format!("http://127.0.0.1:{}/policy", port);
+
+    let closed = HttpPolicy::new(&url).unwrap();
+    assert!(matches!(closed.evaluate(&input("alice", "notes")).await, Decision::Deny { .. }));
+    let open = HttpPolicy::new(&url).unwrap().fail_open(true);
+    assert_eq!(open.evaluate(&input("alice", "notes")).await, Decision::Allow);
+}
@@ -0,0 +1,11 @@
+/*
+ * File: tests/policy/mod.rs
+ * Purpose: Test module organization for operation policies
+ *
+ * Test modules:
+ * - engine_tests: Tests for decision caching and the HTTP policy
+ * - rules_tests: Tests for the embedded rule language
+ */
+
+mod engine_tests;
+mod rules_tests;
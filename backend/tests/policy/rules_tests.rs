@@ -0,0 +1,100 @@
+/*
+ * File: tests/policy/rules_tests.rs
+ * Purpose: Test suite for the embedded policy rule language
+ *
+ * Test Categories:
+ * - First matching rule decides
+ * - Text and number conditions
+ * - Operation kinds
+ * - Operation sources
+ * - Parse errors
+ */
+
+use crdt_editor_backend::{
+    crdt::{Operation, OperationSource, Position},
+    policy::{Decision, DocumentInfo, PolicyError, PolicyInput, RulePolicy},
+};
+
+fn input(client: &str, document: &str, length: usize, operation: Operation) -> PolicyInput {
+    let info = DocumentInfo {
+        id: document.to_string(),
+        exists: length > 0,
+        length,
+        version: length as u64,
+    };
+    PolicyInput::new("default", client, info, &operation)
+}
+
+fn insert(client: &str, document: &str, length: usize) -> PolicyInput {
+    input(client, document, length, Operation::insert(client.to_string(), 'a', Position::new(vec![1])))
+}
+
+fn delete(client: &str, document: &str, length: usize) -> PolicyInput {
+    input(client, document, length, Operation::delete(client.to_string(), Position::new(vec![1])))
+}
+
+#[test]
+fn test_first_matching_rule_decides() {
+    let policy = RulePolicy::parse(r#"
+        # Announcements are read-only except for the bot
+        allow * when document ^= "announce-" and client == "bot"
+        deny * when document ^= "announce-" reason "Announcements are read-only"
+    "#).unwrap();
+
+    assert_eq!(policy.decide(&insert("bot", "announce-1", 0)), Decision::Allow);
+    assert_eq!(policy.decide(&insert("alice", "announce-1", 0)), Decision::deny("Announcements are read-only"));
+    // No rule matches: allowed
+    assert_eq!(policy.decide(&insert("alice", "notes", 0)), Decision::Allow);
+}
+
+#[test]
+fn test_number_conditions_and_kinds() {
+    let policy = RulePolicy::parse(r#"
+        deny insert when length >= 100 reason "Document is full"
+        deny delete when exists == "false"
+    "#).unwrap();
+
+    assert_eq!(policy.decide(&insert("alice", "notes", 99)), Decision::Allow);
+    assert_eq!(policy.decide(&insert("alice", "notes", 100)), Decision::deny("Document is full"));
+    // Deletes may shrink a full document
+    assert_eq!(policy.decide(&delete("alice", "notes", 100)), Decision::Allow);
+    assert_eq!(policy.decide(&delete("alice", "notes", 0)), Decision::deny("Denied by policy"));
+}
+
+#[test]
+fn test_catch_all_rules() {
+    let policy = RulePolicy::parse("allow * when client == \"teacher\"\ndeny").unwrap();
+    assert_eq!(policy.decide(&insert("teacher", "notes", 0)), Decision::Allow);
+    assert!(matches!(policy.decide(&delete("student", "notes", 0)), Decision::Deny { .. }));
+
+    // An empty rule set allows everything
+    assert_eq!(RulePolicy::parse("").unwrap().decide(&insert("alice", "notes", 0)), Decision::Allow);
+}
+
+#[test]
+fn test_source_conditions() {
+    let policy = RulePolicy::parse("deny * when document ^= \"wiki-\" and source == \"bot\" reason \"No bots\"").unwrap();
+    assert_eq!(policy.decide(&insert("alice", "wiki-home", 3)), Decision::Allow);
+    let bot = insert("helper", "wiki-home", 3).with_source(OperationSource::Bot);
+    assert_eq!(policy.decide(&bot), Decision::Deny { reason: "No bots".to_string() });
+}
+
+#[test]
+fn test_invalid_rules_rejected() {
+    for (source, line) in [
+        ("block *", 1),
+        ("allow\ndeny update", 2),
+        ("deny * when size > 3", 1),
+        ("deny * when length ^= 3", 1),
+        ("deny * when client > \"a\"", 1),
+        ("deny * when client == alice", 1),
+        ("deny * when length >= lots", 1),
+        ("deny * reason \"unterminated", 1),
+        ("deny * when client == \"a\" extra", 1),
+    ] {
+        match RulePolicy::parse(source) {
+            Err(PolicyError::InvalidRule { line: reported, .. }) => assert_eq!(reported, line, "{}", source),
+            other => panic!("Expected an invalid rule for {:?}, got {:?}", source, other),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+/*
+ * File: tests/presence/cursors_tests.rs
+ * Purpose: Test suite for throttling relayed cursor updates
+ *
+ * Test Categories:
+ * - First updates relayed at once
+ * - Coalescing within the interval
+ * - Per-client and per-document slots
+ */
+
+use std::time::{Duration, Instant};
+use crdt_editor_backend::presence::{CursorThrottle, Throttled, CURSOR_INTERVAL};
+
+#[test]
+fn test_updates_within_interval_are_coalesced() {
+    let mut throttle = CursorThrottle::new(CURSOR_INTERVAL);
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 1, at(0)), Throttled::Relay(1));
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 2, at(10)), Throttled::Flush(Duration::from_millis(40)));
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 3, at(20)), Throttled::Coalesced);
+
+    // Only the newest held update is flushed
+    assert_eq!(throttle.flush("t1", "doc1", "alice", at(50)), Some(3));
+    assert_eq!(throttle.flush("t1", "doc1", "alice", at(50)), None);
+
+    // The flush counts as a relay
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 4, at(60)), Throttled::Flush(Duration::from_millis(40)));
+    assert_eq!(throttle.flush("t1", "doc1", "alice", at(100)), Some(4));
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 5, at(200)), Throttled::Relay(5));
+}
+
+#[test]
+fn test_slots_are_per_client_and_document() {
+    let mut throttle = CursorThrottle::new(CURSOR_INTERVAL);
+    let now = Instant::now();
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 1, now), Throttled::Relay(1));
+    assert_eq!(throttle.observe("t1", "doc2", "alice", 2, now), Throttled::Relay(2));
+    assert_eq!(throttle.observe("t1", "doc1", "bob", 3, now), Throttled::Relay(3));
+    assert_eq!(throttle.observe("t2", "doc1", "alice", 4, now), Throttled::Relay(4));
+
+    // A forgotten client starts over, and its held updates are dropped
+    assert!(matches!(throttle.observe("t1", "doc1", "alice", 5, now), Throttled::Flush(_)));
+    throttle.forget_client("alice");
+    assert_eq!(throttle.flush("t1", "doc1", "alice", now), None);
+    assert_eq!(throttle.observe("t1", "doc1", "alice", 6, now), Throttled::Relay(6));
+}
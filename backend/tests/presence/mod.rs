@@ -0,0 +1,15 @@
+/*
+ * File: tests/presence/mod.rs
+ * Purpose: Test module organization for presence
+ *
+ * Test modules:
+ * - tracker_tests: Tests for tracking who is present in each document
+ * - cursors_tests: Tests for throttling relayed cursor updates
+ * - server_tests: Tests for presence and cursor updates sent to clients
+ * - view_tests: Tests for a client's copy of a document's presence
+ */
+
+mod cursors_tests;
+mod server_tests;
+mod tracker_tests;
+mod view_tests;
@@ -0,0 +1,198 @@
+/*
+ * File: tests/presence/server_tests.rs
+ * Purpose: Test suite for presence and cursor updates sent to clients
+ *
+ * Test Categories:
+ * - Snapshots on join and diffs to the other clients
+ * - Profile changes and their validation
+ * - Departures on disconnect and idle timeout
+ * - Cursors relayed to other clients, coalesced within the interval
+ * - Stale cursors dropped from queues that operations stay in
+ */
+
+use std::time::Duration;
+use serde_json::json;
+use crdt_editor_backend::{
+    crdt::Position,
+    fixtures::{TestClient, TestServer},
+    presence::{DepartureReason, PresenceConfig, PresenceUpdate},
+    tenant::DEFAULT_TENANT,
+    websocket::{message::CursorUpdateMessage, MessageType, ServerConfig},
+};
+
+async fn expect_presence(client: &mut TestClient) -> PresenceUpdate {
+    let message = client.expect(MessageType::PresenceUpdate).await;
+    serde_json::from_value(message.payload().clone()).unwrap()
+}
+
+#[tokio::test]
+async fn test_joining_clients_get_snapshots_and_others_diffs() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("doc1", "").await;
+    alice.request(MessageType::GetPresence, json!({ "document_id": "doc1" })).await;
+    let own = expect_presence(&mut alice).await;
+    assert!(own.full);
+    assert_eq!((own.seq, own.present.len()), (1, 1));
+
+    let mut bob = server.connect().await;
+    bob.request(MessageType::SyncRequest, json!({ "document_id": "doc1" })).await;
+    let snapshot = expect_presence(&mut bob).await;
+    assert!(snapshot.full);
+    assert_eq!(snapshot.seq, 2);
+    let ids: Vec<&str> = snapshot.present.iter().map(|p| p.client_id.as_str()).collect();
+    assert_eq!(ids, [alice.id(), bob.id()]);
+
+    let joined = expect_presence(&mut alice).await;
+    assert!(!joined.full);
+    assert_eq!(joined.seq, 2);
+    assert_eq!(joined.present[0].client_id, bob.id());
+
+    // A fresh snapshot is there whenever a client missed a diff
+    alice.request(MessageType::GetPresence, json!({ "document_id": "doc1" })).await;
+    assert_eq!(expect_presence(&mut alice).await, snapshot);
+}
+
+#[tokio::test]
+async fn test_profile_changes_are_announced() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("doc1", "").await;
+    let mut bob = server.connect().await;
+    bob.request(MessageType::SyncRequest, json!({ "document_id": "doc1" })).await;
+    expect_presence(&mut alice).await;
+
+    bob.request(MessageType::SetPresence, json!({ "name": "Bob", "color": "#00aa00" })).await;
+    assert_eq!(bob.expect(MessageType::Status).await.payload()["status"], "presence");
+    let changed = expect_presence(&mut alice).await;
+    assert_eq!(changed.seq, 3);
+    assert_eq!((changed.present[0].name.as_str(), changed.present[0].color.as_str()), ("Bob", "#00aa00"));
+
+    bob.request(MessageType::SetPresence, json!({ "color": "green" })).await;
+    bob.expect(MessageType::Error).await;
+    bob.request(MessageType::SetPresence, json!({ "name": " " })).await;
+    bob.expect(MessageType::Error).await;
+    assert_eq!(server.server().presence(DEFAULT_TENANT, "doc1").unwrap()[1].name, "Bob");
+}
+
+#[tokio::test]
+async fn test_departures_on_disconnect_and_timeout() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        presence: PresenceConfig { idle_timeout: Duration::from_millis(50), ..Default::default() },
+        ..Default::default()
+    });
+    let mut alice = server.connect().await;
+    alice.create_document("doc1", "").await;
+    let mut bob = server.connect().await;
+    bob.request(MessageType::SyncRequest, json!({ "document_id": "doc1" })).await;
+    let mut carol = server.connect().await;
+    carol.request(MessageType::SyncRequest, json!({ "document_id": "doc1" })).await;
+    expect_presence(&mut alice).await;
+    expect_presence(&mut alice).await;
+
+    let bob_id = bob.id().to_string();
+    bob.close().await;
+    let left = expect_presence(&mut alice).await;
+    assert_eq!(left.departed[0].client_id, bob_id);
+    assert_eq!(left.departed[0].reason, DepartureReason::Left);
+
+    // Carol keeps sending; alice goes quiet and times out
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    carol.type_text("doc1", "x").await;
+    assert_eq!(server.server().expire_presence().await, 1);
+    let timed_out = expect_presence(&mut carol).await;
+    assert_eq!(timed_out.departed[0].client_id, alice.id());
+    assert_eq!(timed_out.departed[0].reason, DepartureReason::Timeout);
+    let present: Vec<String> = server.server().presence(DEFAULT_TENANT, "doc1").unwrap().into_iter().map(|p| p.client_id).collect();
+    assert_eq!(present, [carol.id()]);
+}
+
+#[tokio::test]
+async fn test_cursors_relayed_and_coalesced() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("doc1", "abc").await;
+    let mut bob = server.connect().await;
+    bob.request(MessageType::SyncRequest, json!({ "document_id": "doc1" })).await;
+    bob.expect(MessageType::SyncResponse).await;
+
+    // A burst of moves: the first is relayed at once, the last after the
+    // interval, and the ones between are dropped
+    for caret in 1..=3 {
+        alice.request(MessageType::CursorUpdate, json!({
+            "document_id": "doc1",
+            "caret": Position::new(vec![caret]),
+            "selection": { "start": Position::start(), "end": Position::new(vec![caret]) },
+        }))
+        .await;
+    }
+    let mut carets = Vec::new();
+    for _ in 0..2 {
+        let relayed = bob.expect(MessageType::CursorUpdate).await;
+        assert_eq!(relayed.client_id(), alice.id());
+        let cursor: CursorUpdateMessage = serde_json::from_value(relayed.payload().clone()).unwrap();
+        assert_eq!(cursor.selection.unwrap().end, cursor.caret);
+        carets.push(cursor.caret);
+    }
+    assert_eq!(carets, [Position::new(vec![1]), Position::new(vec![3])]);
+    while let Some(message) = bob.recv_within(Duration::from_millis(100)).await {
+        assert_ne!(message.message_type(), &MessageType::CursorUpdate);
+    }
+    while let Some(message) = alice.recv_within(Duration::from_millis(20)).await {
+        assert_ne!(message.message_type(), &MessageType::CursorUpdate);
+    }
+
+    // Backwards selections and unknown documents are refused
+    let backwards = json!({
+        "document_id": "doc1",
+        "caret": Position::new(vec![1]),
+        "selection": { "start": Position::new(vec![2]), "end": Position::new(vec![1]) },
+    });
+    alice.request(MessageType::CursorUpdate, backwards).await;
+    alice.expect(MessageType::Error).await;
+    alice.request(MessageType::CursorUpdate, json!({ "document_id": "missing", "caret": Position::start() })).await;
+    alice.expect(MessageType::Error).await;
+}
+
+#[tokio::test]
+async fn test_stale_cursors_dropped_behind_operations() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("doc1", "").await;
+    let mut bob = server.connect().await;
+    bob.request(MessageType::SyncRequest, json!({ "document_id": "doc1" })).await;
+    bob.expect(MessageType::SyncResponse).await;
+
+    // Bob stops reading, so what alice sends queues up behind the
+    // operations, and her cursor outlives its deadline there
+    alice.type_text("doc1", &"a".repeat(40)).await;
+    alice.request(MessageType::CursorUpdate, json!({
+        "document_id": "doc1",
+        "caret": Position::new(vec![1]),
+        "stale_after_ms": 20,
+    }))
+    .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Every operation arrives, the cursor doesn't
+    let mut operations = 0;
+    while let Some(message) = bob.recv_within(Duration::from_millis(100)).await {
+        assert_ne!(message.message_type(), &MessageType::CursorUpdate);
+        if message.message_type() == &MessageType::Operation {
+            operations += 1;
+        }
+    }
+    assert_eq!(operations, 40);
+    assert_eq!(server.server().metrics().stale_messages, 1);
+
+    // A fresh cursor is relayed, without the deadline
+    alice.request(MessageType::CursorUpdate, json!({
+        "document_id": "doc1",
+        "caret": Position::new(vec![2]),
+        "stale_after_ms": 60_000,
+    }))
+    .await;
+    let relayed = bob.expect(MessageType::CursorUpdate).await;
+    assert_eq!(relayed.payload()["caret"], json!(Position::new(vec![2])));
+    assert!(relayed.payload().get("stale_after_ms").is_none());
+}
@@ -0,0 +1,113 @@
+/*
+ * File: tests/presence/tracker_tests.rs
+ * Purpose: Test suite for tracking who is present in each document
+ *
+ * Test Categories:
+ * - Joins, with default and chosen profiles
+ * - Departures on disconnect and idle timeout
+ * - Sequence numbers and snapshots
+ */
+
+use std::time::Duration;
+use chrono::{DateTime, TimeZone, Utc};
+use crdt_editor_backend::presence::{default_color, is_valid_color, DepartureReason, PresenceTracker};
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+}
+
+#[test]
+fn test_join_announces_new_clients_once() {
+    let mut tracker = PresenceTracker::new();
+    let update = tracker.join("t1", "doc1", "alice", at(0)).unwrap();
+    assert_eq!(update.seq, 1);
+    assert!(!update.full);
+    assert_eq!(update.present[0].name, "alice");
+    assert_eq!(update.present[0].color, default_color("alice"));
+    assert!(is_valid_color(&update.present[0].color));
+    assert_eq!(update.present[0].joined_at, at(0));
+
+    // Joining again only marks the client active
+    assert_eq!(tracker.join("t1", "doc1", "alice", at(5)), None);
+    // Tenants don't share documents
+    assert_eq!(tracker.join("t2", "doc1", "bob", at(5)).unwrap().seq, 1);
+    assert_eq!(tracker.present("t1", "doc1").len(), 1);
+}
+
+#[test]
+fn test_profiles_apply_to_present_and_later_documents() {
+    let mut tracker = PresenceTracker::new();
+    tracker.join("t1", "doc1", "alice", at(0));
+
+    let updates = tracker.set_profile("alice", Some("Alice".to_string()), Some("#112233".to_string()));
+    assert_eq!(updates.len(), 1);
+    let (tenant_id, update) = &updates[0];
+    assert_eq!(tenant_id, "t1");
+    assert_eq!(update.seq, 2);
+    assert_eq!(update.present[0].name, "Alice");
+    assert_eq!(update.present[0].joined_at, at(0));
+
+    // A partial change keeps the other field
+    tracker.set_profile("alice", None, Some("#445566".to_string()));
+    let joined = tracker.join("t1", "doc2", "alice", at(10)).unwrap();
+    assert_eq!((joined.present[0].name.as_str(), joined.present[0].color.as_str()), ("Alice", "#445566"));
+}
+
+#[test]
+fn test_disconnect_departs_every_document() {
+    let mut tracker = PresenceTracker::new();
+    tracker.join("t1", "doc1", "alice", at(0));
+    tracker.join("t1", "doc2", "alice", at(0));
+    tracker.join("t1", "doc1", "bob", at(1));
+
+    let mut updates = tracker.remove_client("alice");
+    updates.sort_by(|a, b| a.1.document_id.cmp(&b.1.document_id));
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[0].1.seq, 3);
+    assert_eq!(updates[0].1.departed[0].client_id, "alice");
+    assert_eq!(updates[0].1.departed[0].reason, DepartureReason::Left);
+
+    let present: Vec<String> = tracker.present("t1", "doc1").into_iter().map(|p| p.client_id).collect();
+    assert_eq!(present, ["bob"]);
+    assert!(tracker.present("t1", "doc2").is_empty());
+    assert!(tracker.remove_client("alice").is_empty());
+}
+
+#[test]
+fn test_idle_clients_time_out_and_come_back() {
+    let mut tracker = PresenceTracker::new();
+    let timeout = Duration::from_secs(60);
+    tracker.join("t1", "doc1", "alice", at(0));
+    tracker.join("t1", "doc1", "bob", at(0));
+    tracker.join("t1", "doc1", "carol", at(0));
+    tracker.touch("bob", at(50));
+
+    assert!(tracker.expire(timeout, at(60)).is_empty());
+    let updates = tracker.expire(timeout, at(100));
+    assert_eq!(updates.len(), 1);
+    let departed: Vec<(&str, DepartureReason)> =
+        updates[0].1.departed.iter().map(|d| (d.client_id.as_str(), d.reason)).collect();
+    assert_eq!(departed, [("alice", DepartureReason::Timeout), ("carol", DepartureReason::Timeout)]);
+    assert_eq!(updates[0].1.seq, 4);
+
+    // The next message of a timed out client brings it back
+    let back = tracker.join("t1", "doc1", "alice", at(120)).unwrap();
+    assert_eq!(back.present[0].joined_at, at(120));
+    // A zero timeout keeps everyone
+    assert!(tracker.expire(Duration::ZERO, at(10_000)).is_empty());
+}
+
+#[test]
+fn test_snapshot_lists_everyone_in_join_order() {
+    let mut tracker = PresenceTracker::new();
+    assert_eq!(tracker.snapshot("t1", "doc1").seq, 0);
+    tracker.join("t1", "doc1", "bob", at(2));
+    tracker.join("t1", "doc1", "alice", at(1));
+    tracker.join("t1", "doc1", "carol", at(2));
+
+    let snapshot = tracker.snapshot("t1", "doc1");
+    assert!(snapshot.full);
+    assert_eq!(snapshot.seq, 3);
+    let order: Vec<&str> = snapshot.present.iter().map(|p| p.client_id.as_str()).collect();
+    assert_eq!(order, ["alice", "bob", "carol"]);
+}
@@ -0,0 +1,77 @@
+/*
+ * File: tests/presence/view_tests.rs
+ * Purpose: Test suite for a client's copy of a document's presence
+ *
+ * Test Categories:
+ * - Snapshots and the diffs after them
+ * - Gaps in the sequence, and refreshing from a snapshot
+ */
+
+use chrono::{DateTime, TimeZone, Utc};
+use crdt_editor_backend::presence::{Departure, DepartureReason, Presence, PresenceUpdate, PresenceView};
+
+fn presence(client_id: &str, joined: i64) -> Presence {
+    Presence {
+        client_id: client_id.to_string(),
+        name: client_id.to_string(),
+        color: "#4363d8".to_string(),
+        joined_at: at(joined),
+    }
+}
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+}
+
+fn update(seq: u64, full: bool, present: Vec<Presence>, departed: &[&str]) -> PresenceUpdate {
+    PresenceUpdate {
+        document_id: "notes".to_string(),
+        seq,
+        full,
+        present,
+        departed: departed.iter()
+            .map(|client_id| Departure { client_id: client_id.to_string(), reason: DepartureReason::Left })
+            .collect(),
+    }
+}
+
+fn names(view: &PresenceView) -> Vec<String> {
+    view.present().into_iter().map(|presence| presence.client_id).collect()
+}
+
+#[test]
+fn test_snapshots_then_diffs() {
+    let mut view = PresenceView::new();
+    assert!(view.is_stale());
+    assert!(view.apply(&update(2, true, vec![presence("bob", 5), presence("alice", 0)], &[])));
+    assert_eq!(names(&view), ["alice", "bob"]);
+
+    let mut renamed = presence("bob", 5);
+    renamed.name = "Bob".to_string();
+    assert!(view.apply(&update(3, false, vec![renamed, presence("carol", 9)], &[])));
+    assert!(view.apply(&update(4, false, Vec::new(), &["alice"])));
+    assert_eq!(names(&view), ["bob", "carol"]);
+    assert_eq!(view.present()[0].name, "Bob");
+
+    // Diffs the view already holds are skipped
+    assert!(view.apply(&update(3, false, vec![presence("alice", 0)], &[])));
+    assert_eq!(names(&view), ["bob", "carol"]);
+}
+
+#[test]
+fn test_gaps_wait_on_a_snapshot() {
+    let mut view = PresenceView::new();
+    // A diff before any snapshot can't be applied
+    assert!(!view.apply(&update(1, false, vec![presence("alice", 0)], &[])));
+    view.apply(&update(1, true, vec![presence("alice", 0)], &[]));
+
+    // Seq 2 went missing
+    assert!(!view.apply(&update(3, false, vec![presence("carol", 9)], &[])));
+    assert!(view.is_stale());
+    assert!(!view.apply(&update(4, false, Vec::new(), &["alice"])));
+    assert_eq!(names(&view), ["alice"]);
+
+    assert!(view.apply(&update(4, true, vec![presence("bob", 5), presence("carol", 9)], &[])));
+    assert!(!view.is_stale());
+    assert_eq!(names(&view), ["bob", "carol"]);
+}
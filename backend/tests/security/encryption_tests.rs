@@ -0,0 +1,103 @@
+/*
+ * File: tests/security/encryption_tests.rs
+ * Purpose: Test suite for per-document encryption at rest
+ *
+ * Test Categories:
+ * - Encrypt/decrypt round trips
+ * - Document binding of ciphertexts
+ * - Data key and master key rotation
+ * - Master key parsing
+ */
+
+use std::sync::Arc;
+use crdt_editor_backend::security::{
+    DocumentEncryption,
+    EncryptionError,
+    MasterKey,
+    StaticKeyProvider,
+};
+
+fn setup() -> (Arc<StaticKeyProvider>, DocumentEncryption) {
+    let provider = Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string())));
+    let encryption = DocumentEncryption::new(provider.clone());
+    (provider, encryption)
+}
+
+#[test]
+fn test_encrypt_decrypt_round_trip() {
+    let (_, encryption) = setup();
+
+    let record = encryption.encrypt("doc1", b"Hello").unwrap();
+    assert_ne!(serde_json::to_string(&record).unwrap().as_bytes(), b"Hello");
+    assert_eq!(encryption.decrypt("doc1", &record).unwrap(), b"Hello");
+}
+
+#[test]
+fn test_record_bound_to_document() {
+    let (_, encryption) = setup();
+
+    let record = encryption.encrypt("doc1", b"secret").unwrap();
+    encryption.encrypt("doc2", b"other").unwrap();
+
+    assert!(matches!(
+        encryption.decrypt("doc2", &record),
+        Err(EncryptionError::DecryptionFailed)
+    ));
+}
+
+#[test]
+fn test_document_key_rotation() {
+    let (_, encryption) = setup();
+
+    let old_record = encryption.encrypt("doc1", b"v1").unwrap();
+    let version = encryption.rotate_document_key("doc1").unwrap();
+    let new_record = encryption.encrypt("doc1", b"v2").unwrap();
+
+    assert_eq!(version, 2);
+    assert_eq!(old_record.key_version, 1);
+    assert_eq!(new_record.key_version, 2);
+    assert_eq!(encryption.decrypt("doc1", &old_record).unwrap(), b"v1");
+    assert_eq!(encryption.decrypt("doc1", &new_record).unwrap(), b"v2");
+}
+
+#[test]
+fn test_master_key_rotation_rewraps_data_keys() {
+    let (provider, encryption) = setup();
+
+    let record = encryption.encrypt("doc1", b"payload").unwrap();
+    provider.rotate(MasterKey::generate("k2".to_string()));
+
+    assert_eq!(encryption.rewrap_all().unwrap(), 1);
+    assert_eq!(encryption.wrapped_keys("doc1")[0].master_key_id, "k2");
+    assert_eq!(encryption.rewrap_all().unwrap(), 0);
+
+    // Data keys are unchanged, so existing records still decrypt
+    assert_eq!(encryption.decrypt("doc1", &record).unwrap(), b"payload");
+}
+
+#[test]
+fn test_wrapped_keys_restore() {
+    let (provider, encryption) = setup();
+    let record = encryption.encrypt("doc1", b"persisted").unwrap();
+
+    let restored = DocumentEncryption::new(provider);
+    restored.load_wrapped_keys("doc1", encryption.wrapped_keys("doc1"));
+    assert_eq!(restored.decrypt("doc1", &record).unwrap(), b"persisted");
+
+    restored.forget_document("doc1");
+    assert!(matches!(
+        restored.decrypt("doc1", &record),
+        Err(EncryptionError::DataKeyNotFound(_, 1))
+    ));
+}
+
+#[test]
+fn test_master_key_parsing() {
+    let key = "00".repeat(32);
+    let provider = StaticKeyProvider::parse(&format!("new:{},old:{}", key, key));
+    assert!(provider.is_ok());
+
+    assert!(StaticKeyProvider::parse("").is_err());
+    assert!(StaticKeyProvider::parse("k1:abcd").is_err());
+    assert!(StaticKeyProvider::parse("no-separator").is_err());
+}
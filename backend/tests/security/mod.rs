@@ -0,0 +1,15 @@
+/*
+ * File: tests/security/mod.rs
+ * Purpose: Test module organization for security features
+ * 
+ * Test modules:
+ * - encryption_tests: Tests for per-document encryption at rest
+ * - passwords_tests: Tests for document password hashes
+ * - redaction_tests: Tests for log redaction
+ * - tokens_tests: Tests for sealed session and share tokens
+ */
+
+mod encryption_tests;
+mod passwords_tests;
+mod redaction_tests;
+mod tokens_tests;
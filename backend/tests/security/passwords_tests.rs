@@ -0,0 +1,50 @@
+/*
+ * File: tests/security/passwords_tests.rs
+ * Purpose: Test suite for document password hashes
+ *
+ * Test Categories:
+ * - Argon2id hashes that carry their costs
+ * - Salted hashes that verify only the right password
+ * - Constant-time comparison of secrets
+ */
+
+use crdt_editor_backend::security::{check_cost, hash_password, secrets_match, verify_password, PasswordHashError};
+
+#[test]
+fn test_hashes_carry_their_costs() {
+    let hash = hash_password("correct horse", 64, 1).unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$m=64,t=1,p=1$"), "{}", hash);
+    // Hashes made with other costs keep verifying
+    let stronger = hash_password("correct horse", 128, 2).unwrap();
+    assert!(stronger.starts_with("$argon2id$v=19$m=128,t=2,p=1$"), "{}", stronger);
+    assert_eq!(verify_password("correct horse", &stronger), Ok(true));
+
+    assert!(check_cost(64, 1).is_ok());
+    assert!(matches!(check_cost(64, 0), Err(PasswordHashError::Cost(_))));
+    assert!(matches!(check_cost(1, 1), Err(PasswordHashError::Cost(_))));
+    assert!(matches!(hash_password("secret", 64, 0), Err(PasswordHashError::Cost(_))));
+}
+
+#[test]
+fn test_hashes_are_salted_and_verify() {
+    let hash = hash_password("correct horse", 64, 1).unwrap();
+    assert_ne!(hash, hash_password("correct horse", 64, 1).unwrap());
+    assert_eq!(verify_password("correct horse", &hash), Ok(true));
+    assert_eq!(verify_password("correct horse!", &hash), Ok(false));
+
+    assert_eq!(hash_password("", 64, 1), Err(PasswordHashError::Empty));
+    assert_eq!(
+        verify_password("secret", "$pbkdf2-sha256$i=1000$00$00"),
+        Err(PasswordHashError::Unsupported("pbkdf2-sha256".to_string())),
+    );
+    assert!(verify_password("secret", "not a hash").is_err());
+}
+
+#[test]
+fn test_secrets_match_only_themselves() {
+    assert!(secrets_match("secret", "secret"));
+    assert!(secrets_match("", ""));
+    assert!(!secrets_match("secret", "Secret"));
+    assert!(!secrets_match("secret", "secret2"));
+    assert!(!secrets_match("", "secret"));
+}
@@ -0,0 +1,90 @@
+/*
+ * File: tests/security/redaction_tests.rs
+ * Purpose: Test suite for log redaction
+ *
+ * Test Categories:
+ * - Default redaction of document text
+ * - Configurable secret patterns
+ * - Raw (unparseable) input handling
+ */
+
+use serde_json::json;
+use crdt_editor_backend::security::{RedactionConfig, Redactor};
+use crdt_editor_backend::websocket::message::{Message, MessageType, OperationMessage};
+use crdt_editor_backend::crdt::{Operation, Position};
+
+#[test]
+fn test_default_redacts_document_text() {
+    let redactor = Redactor::default();
+    let message = Message::new(
+        MessageType::Operation,
+        "client1".to_string(),
+        serde_json::to_value(OperationMessage::new(
+            Operation::insert("client1".to_string(), 'S', Position::start()),
+            "doc1".to_string(),
+        )).unwrap(),
+    );
+
+    let redacted = redactor.redact_message(&message).to_string();
+    assert!(!redacted.contains("\"S\""));
+    assert!(redacted.contains("[REDACTED]"));
+    assert!(redacted.contains("doc1"));
+
+    let unlock = Message::new(
+        MessageType::UnlockDocument,
+        "client1".to_string(),
+        serde_json::json!({ "document_id": "doc1", "password": "hunter2" }),
+    );
+    assert!(!redactor.redact_message(&unlock).to_string().contains("hunter2"));
+}
+
+#[test]
+fn test_nested_content_fields() {
+    let redactor = Redactor::default();
+    let value = json!({
+        "documents": [{ "document_id": "doc1", "content": "private notes" }]
+    });
+
+    let redacted = redactor.redact_value(&value);
+    assert_eq!(redacted["documents"][0]["content"], "[REDACTED]");
+    assert_eq!(redacted["documents"][0]["document_id"], "doc1");
+}
+
+#[test]
+fn test_custom_patterns() {
+    let config = RedactionConfig {
+        fields: Vec::new(),
+        patterns: vec![r"token-[a-z0-9]+".to_string()],
+        replacement: "***".to_string(),
+    };
+    let redactor = Redactor::new(&config).unwrap();
+
+    assert_eq!(redactor.redact_str("auth token-abc123 ok"), "auth *** ok");
+    assert_eq!(
+        redactor.redact_value(&json!({ "content": "token-x" })),
+        json!({ "content": "***" })
+    );
+}
+
+#[test]
+fn test_invalid_pattern_rejected() {
+    let config = RedactionConfig {
+        patterns: vec!["(".to_string()],
+        ..Default::default()
+    };
+    assert!(Redactor::new(&config).is_err());
+}
+
+#[test]
+fn test_redact_raw_input() {
+    let redactor = Redactor::default();
+
+    let redacted = redactor.redact_raw("not json: my secret text");
+    assert!(!redacted.contains("secret"));
+
+    let redacted = redactor.redact_raw(r#"{"content":"secret","type":"x"}"#);
+    assert!(!redacted.contains("secret"));
+    assert!(redacted.contains("\"type\":\"x\""));
+
+    assert_eq!(Redactor::disabled().redact_raw("plain"), "plain");
+}
@@ -0,0 +1,144 @@
+/*
+ * File: tests/security/tokens_tests.rs
+ * Purpose: Test suite for sealed session and share tokens
+ *
+ * Test Categories:
+ * - Round trips, expiry and binding to the token kind
+ * - Tampered tokens and master key rotation
+ * - Session tokens resuming sessions
+ * - Share tokens unlocking protected documents, until the password changes
+ */
+
+use std::sync::Arc;
+use chrono::{Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crdt_editor_backend::{
+    fixtures::{TestClient, TestServer},
+    security::{MasterKey, StaticKeyProvider, TokenError, TokenKind, TokenSealer},
+    tenant::{PasswordConfig, DEFAULT_TENANT},
+    websocket::{message::SetFrozenMessage, MessageType, ServerConfig},
+};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Share {
+    tenant_id: String,
+    document_id: String,
+}
+
+fn share() -> Share {
+    Share { tenant_id: "acme".to_string(), document_id: "notes".to_string() }
+}
+
+#[test]
+fn test_round_trip_expiry_and_kind() {
+    let sealer = TokenSealer::new(Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string()))));
+    let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+    let token = sealer.seal(TokenKind::Share, &share(), now + Duration::hours(1)).unwrap();
+    assert!(token.starts_with("v1.k1."));
+    assert!(!token.contains("notes"), "claims are encrypted");
+
+    assert_eq!(sealer.open::<Share>(TokenKind::Share, &token, now).unwrap(), share());
+    let expired = sealer.open::<Share>(TokenKind::Share, &token, now + Duration::hours(1));
+    assert!(matches!(expired, Err(TokenError::Expired(at)) if at == now + Duration::hours(1)));
+    assert!(matches!(sealer.open::<Share>(TokenKind::Session, &token, now), Err(TokenError::Invalid)));
+}
+
+#[test]
+fn test_tampering_and_rotation() {
+    let provider = Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string())));
+    let sealer = TokenSealer::new(provider.clone());
+    let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+    let expires = now + Duration::minutes(5);
+    let old = sealer.seal(TokenKind::Session, &share(), expires).unwrap();
+
+    // Flipping a byte of the ciphertext or swapping the key ID fails
+    let mut tampered = old.clone().into_bytes();
+    let last = tampered.len() - 1;
+    tampered[last] = if tampered[last] == b'0' { b'1' } else { b'0' };
+    let tampered = String::from_utf8(tampered).unwrap();
+    assert!(matches!(sealer.open::<Share>(TokenKind::Session, &tampered, now), Err(TokenError::Invalid)));
+    for malformed in ["", "v1.k1", "v2.k1.00", "v1.k1.zz", "v1.k1.0011"] {
+        assert!(matches!(sealer.open::<Share>(TokenKind::Session, malformed, now), Err(TokenError::Malformed)), "{}", malformed);
+    }
+
+    // After a rotation new tokens use the new key and old ones still open
+    provider.rotate(MasterKey::generate("k2".to_string()));
+    let new = sealer.seal(TokenKind::Session, &share(), expires).unwrap();
+    assert!(new.starts_with("v1.k2."));
+    assert_eq!(sealer.open::<Share>(TokenKind::Session, &old, now).unwrap(), share());
+    let swapped = old.replacen("v1.k1.", "v1.k2.", 1);
+    assert!(matches!(sealer.open::<Share>(TokenKind::Session, &swapped, now), Err(TokenError::Invalid)));
+
+    // Leaving the retired key out revokes its tokens
+    let retired = TokenSealer::new(Arc::new(StaticKeyProvider::new(MasterKey::generate("k3".to_string()))));
+    assert!(matches!(retired.open::<Share>(TokenKind::Session, &old, now), Err(TokenError::UnknownKey(id)) if id == "k1"));
+}
+
+/// Receive an error, and check it says why
+async fn expect_error(client: &mut TestClient, reason: &str) {
+    let error = client.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains(reason), "{}", error.payload());
+}
+
+#[tokio::test]
+async fn test_session_tokens_resume_sessions() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "").await;
+    let token = alice.session_token().expect("session token in welcome").to_string();
+
+    // A session still connected isn't taken over
+    let second = server.resume(&token).await;
+    assert_ne!(second.id(), alice.id());
+    second.close().await;
+
+    // Once closed, the session resumes, keeping ownership of its documents
+    let alice_id = alice.id().to_string();
+    alice.close().await;
+    let mut resumed = loop {
+        let client = server.resume(&token).await;
+        if client.id() == alice_id {
+            break client;
+        }
+        client.close().await;
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    };
+    resumed.request(MessageType::SetFrozen, SetFrozenMessage { document_id: "notes".to_string(), frozen: true }).await;
+    assert_eq!(resumed.expect(MessageType::Ack).await.payload()["frozen"], true);
+
+    for token in ["v1.ephemeral.00", "not a token", &token.replacen("v1.", "v1.x", 1)] {
+        assert!(server.server().resume_in_process(DEFAULT_TENANT, token).is_err(), "{}", token);
+    }
+}
+
+#[tokio::test]
+async fn test_share_tokens_unlock_documents() {
+    let passwords = PasswordConfig { memory_kib: 64, iterations: 1, ..Default::default() };
+    let server = TestServer::in_process_with_config(ServerConfig { passwords, ..Default::default() });
+    let mut owner = server.connect().await;
+    owner.create_document("notes", "secret plans").await;
+    owner.create_document("open", "").await;
+    owner.request(MessageType::ShareDocument, json!({ "document_id": "open" })).await;
+    expect_error(&mut owner, "has no password").await;
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    owner.expect(MessageType::Ack).await;
+    owner.request(MessageType::ShareDocument, json!({ "document_id": "notes" })).await;
+    let token = owner.expect(MessageType::Ack).await.payload()["share_token"].as_str().unwrap().to_string();
+
+    let mut guest = server.connect().await;
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "open", "share_token": &token })).await;
+    expect_error(&mut guest, "Invalid token").await;
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "password": "hunter2", "share_token": &token })).await;
+    expect_error(&mut guest, "either a password or a share token").await;
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "share_token": &token })).await;
+    guest.expect(MessageType::Ack).await;
+    assert_eq!(guest.get_document("notes").await.content, "secret plans");
+
+    // A new password revokes the tokens of the old one
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter3" })).await;
+    owner.expect(MessageType::Ack).await;
+    let mut late = server.connect().await;
+    late.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "share_token": &token })).await;
+    expect_error(&mut late, "revoked").await;
+}
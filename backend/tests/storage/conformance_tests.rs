@@ -0,0 +1,42 @@
+/*
+ * File: tests/storage/conformance_tests.rs
+ * Purpose: Conformance suite run against every DocumentStore
+ *
+ * Each backend, in each of its configurations, gets one test running
+ * `fixtures::store_conformance` on a fresh store.
+ *
+ * Test Categories:
+ * - The in-memory store
+ * - The SQLite store, in memory, on file, encrypted and committing writes
+ *   one at a time
+ */
+
+use std::{sync::Arc, time::Duration};
+use crdt_editor_backend::{
+    fixtures::store_conformance,
+    security::{MasterKey, StaticKeyProvider},
+    storage::{GroupCommit, MemoryStore, SqliteStore},
+};
+
+fn database() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("coedit-conformance-{}.db", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn test_memory_store_conforms() {
+    store_conformance(&MemoryStore::new());
+}
+
+#[test]
+fn test_sqlite_store_conforms() {
+    store_conformance(&SqliteStore::in_memory().unwrap());
+    store_conformance(&SqliteStore::open(database()).unwrap());
+}
+
+#[test]
+fn test_configured_sqlite_stores_conform() {
+    let provider = Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string())));
+    store_conformance(&SqliteStore::open_encrypted(database(), provider).unwrap());
+    let unbatched = GroupCommit { max_batch: 1, window: Duration::ZERO, queue_capacity: 1 };
+    store_conformance(&SqliteStore::open_with(database(), unbatched, None).unwrap());
+}
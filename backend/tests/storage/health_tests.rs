@@ -0,0 +1,234 @@
+/*
+ * File: tests/storage/health_tests.rs
+ * Purpose: Test suite for degraded documents, those the store failed to write
+ *
+ * Test Categories:
+ * - Held operations, and overflowing the bound on them
+ * - Clients are warned, and told of recovery
+ * - Failures found by flushes write documents whole
+ * - Degraded documents over the admin API
+ */
+
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use chrono::Utc;
+use serde_json::Value;
+use crdt_editor_backend::{
+    crdt::{Document, Operation, Position},
+    fixtures::{TestClient, TestServer},
+    storage::{health::Recovery, DocumentStore, MemoryStore, StorageConfig, StorageError, StorageHealth, StoredDocument},
+    websocket::{AdminConfig, MessageType, ServerConfig},
+};
+
+/// A store that refuses writes, or takes and loses them until a flush
+/// tells, while told to
+#[derive(Debug, Default)]
+struct FlakyStore {
+    inner: MemoryStore,
+    refusing: AtomicBool,
+    losing: AtomicBool,
+}
+
+impl FlakyStore {
+    fn write(&self, write: impl FnOnce(&MemoryStore) -> Result<(), StorageError>) -> Result<(), StorageError> {
+        if self.refusing.load(Ordering::SeqCst) {
+            return Err(StorageError::Backend("disk full".to_string()));
+        }
+        if self.losing.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        write(&self.inner)
+    }
+}
+
+impl DocumentStore for FlakyStore {
+    fn load(&self, key: &str) -> Result<Option<StoredDocument>, StorageError> {
+        self.inner.load(key)
+    }
+
+    fn save(&self, key: &str, document: &Document) -> Result<(), StorageError> {
+        self.write(|inner| inner.save(key, document))
+    }
+
+    fn replace(&self, key: &str, document: &Document) -> Result<(), StorageError> {
+        self.write(|inner| inner.replace(key, document))
+    }
+
+    fn list(&self) -> Result<Vec<String>, StorageError> {
+        self.inner.list()
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.write(|inner| inner.delete(key))
+    }
+
+    fn append_ops(&self, key: &str, operations: &[Operation]) -> Result<(), StorageError> {
+        self.write(|inner| inner.append_ops(key, operations))
+    }
+
+    fn save_password(&self, key: &str, hash: Option<&str>) -> Result<(), StorageError> {
+        self.write(|inner| inner.save_password(key, hash))
+    }
+
+    fn load_passwords(&self) -> Result<Vec<(String, String)>, StorageError> {
+        self.inner.load_passwords()
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        if self.losing.load(Ordering::SeqCst) {
+            return Err(StorageError::Backend("lost writes".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn config(store: &Arc<FlakyStore>, degraded_capacity: usize) -> ServerConfig {
+    ServerConfig {
+        storage: StorageConfig { store: store.clone(), degraded_capacity, ..Default::default() },
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        ..Default::default()
+    }
+}
+
+fn operation(clock: usize) -> Operation {
+    Operation::insert("alice".to_string(), 'a', Position::spread(clock + 1)[clock].clone())
+}
+
+/// Receive messages until a storage status arrives, and return its code
+async fn storage_status(client: &mut TestClient) -> String {
+    loop {
+        let message = client.expect(MessageType::Status).await;
+        if let Some(code) = message.payload()["code"].as_str().filter(|code| code.starts_with("storage_")) {
+            return code.to_string();
+        }
+    }
+}
+
+/// Restart a server on the store, and read a document from it
+async fn stored_content(store: &Arc<FlakyStore>, document_id: &str) -> String {
+    let server = TestServer::in_process_with_config(config(store, 1024));
+    let mut reader = server.connect().await;
+    reader.get_document(document_id).await.content
+}
+
+#[test]
+fn test_held_operations_overflow_into_whole_writes() {
+    let mut health = StorageHealth::new(2);
+    let degraded_at = Utc::now() - chrono::Duration::minutes(1);
+    assert!(!health.hold("default/notes", &operation(0)));
+    assert!(health.fail("default/notes", "disk full", Some([operation(0)].as_slice()), degraded_at));
+    assert!(health.hold("default/notes", &operation(1)));
+    // Failing again isn't news
+    assert!(!health.fail("default/notes", "disk full", Some([].as_slice()), Utc::now()));
+    assert_eq!(health.report()[0].held, 2);
+    assert_eq!(health.begin_recovery("default/notes"), Some(Recovery::Append(vec![operation(0), operation(1)])));
+    assert!(!health.is_degraded("default/notes"));
+
+    // A failed recovery keeps counting from when the document degraded
+    health.fail("default/notes", "disk full", Some([operation(0), operation(1)].as_slice()), Utc::now());
+    health.hold("default/notes", &operation(2));
+    let report = health.report();
+    assert_eq!((report[0].since, report[0].held, report[0].whole, report[0].retries), (degraded_at, 0, true, 1));
+    assert_eq!(health.begin_recovery("default/notes"), Some(Recovery::Whole));
+}
+
+#[test]
+fn test_flushes_confirm_what_they_wrote() {
+    let mut health = StorageHealth::new(8);
+    health.written("default/a");
+    let written = health.take_unflushed();
+    health.written("default/b");
+    assert_eq!(health.flush_failed(written, "lost writes", Utc::now()), ["default/a", "default/b"]);
+    assert!(health.report().iter().all(|document| document.whole));
+
+    health.begin_recovery("default/a");
+    health.begin_recovery("default/b");
+    health.written("default/a");
+    let written = health.take_unflushed();
+    health.written("default/b");
+    // Only what the flush covered recovered
+    assert_eq!(health.flushed(&written), ["default/a"]);
+    assert!(!health.is_idle());
+    let written = health.take_unflushed();
+    assert_eq!(health.flushed(&written), ["default/b"]);
+    assert!(health.is_idle());
+}
+
+#[tokio::test]
+async fn test_refused_writes_are_held_and_recovered() {
+    let store = Arc::new(FlakyStore::default());
+    let server = TestServer::in_process_with_config(config(&store, 1024));
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "").await;
+    let mut bob = server.connect().await;
+    bob.get_document("notes").await;
+
+    store.refusing.store(true, Ordering::SeqCst);
+    alice.type_text("notes", "Hi").await;
+    assert_eq!(storage_status(&mut bob).await, "storage_degraded");
+    let degraded = server.server().degraded_documents();
+    assert_eq!((degraded[0].key.as_str(), degraded[0].held, degraded[0].whole), ("default/notes", 2, false));
+    // Snapshots wait on recovery, and clients opening it are warned
+    assert_eq!(server.server().save_snapshots().await, 0);
+    let mut carol = server.connect().await;
+    assert_eq!(carol.get_document("notes").await.content, "Hi");
+    assert_eq!(storage_status(&mut carol).await, "storage_degraded");
+
+    // Recovery fails while the store does
+    assert_eq!(server.server().recover_storage().await, 0);
+    assert_eq!(server.server().degraded_documents()[0].retries, 1);
+
+    store.refusing.store(false, Ordering::SeqCst);
+    assert_eq!(server.server().recover_storage().await, 1);
+    assert!(server.server().degraded_documents().is_empty());
+    assert_eq!(storage_status(&mut bob).await, "storage_recovered");
+    assert_eq!(stored_content(&store, "notes").await, "Hi");
+}
+
+#[tokio::test]
+async fn test_lost_writes_are_written_whole() {
+    let store = Arc::new(FlakyStore::default());
+    let server = TestServer::in_process_with_config(config(&store, 1));
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "").await;
+    alice.type_text("notes", "Hi").await;
+    assert_eq!(server.server().recover_storage().await, 0);
+
+    // The store takes writes it loses, which only the flush tells
+    store.losing.store(true, Ordering::SeqCst);
+    alice.create_document("draft", "").await;
+    alice.type_text("draft", "Hey").await;
+    assert_eq!(server.server().recover_storage().await, 0);
+    let degraded = server.server().degraded_documents();
+    assert_eq!(degraded.len(), 1);
+    assert_eq!((degraded[0].key.as_str(), degraded[0].whole), ("default/draft", true));
+
+    store.losing.store(false, Ordering::SeqCst);
+    assert_eq!(server.server().recover_storage().await, 1);
+    assert_eq!(stored_content(&store, "draft").await, "Hey");
+    assert_eq!(stored_content(&store, "notes").await, "Hi");
+}
+
+#[tokio::test]
+async fn test_degraded_documents_over_admin_api() {
+    let store = Arc::new(FlakyStore::default());
+    let server = TestServer::in_process_with_config(config(&store, 1));
+    let mut alice = server.connect().await;
+    store.refusing.store(true, Ordering::SeqCst);
+    alice.create_document("notes", "").await;
+    alice.type_text("notes", "Hi").await;
+
+    let routes = server.server().routes();
+    let reply = warp::test::request()
+        .path("/admin/storage")
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(reply.status(), 200);
+    let body: Value = serde_json::from_slice(reply.body()).unwrap();
+    assert_eq!(body["degraded"][0]["key"], "default/notes");
+    assert_eq!(body["degraded"][0]["error"], "Storage failed: disk full");
+    // Two operations overflow a bound of one
+    assert_eq!(body["degraded"][0]["whole"], true);
+    let reply = warp::test::request().path("/admin/storage").reply(&routes).await;
+    assert_eq!(reply.status(), 401);
+}
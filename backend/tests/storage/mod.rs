@@ -0,0 +1,15 @@
+/*
+ * File: tests/storage/mod.rs
+ * Purpose: Test module organization for document persistence
+ *
+ * Test modules:
+ * - conformance_tests: Conformance suite run against every DocumentStore
+ * - health_tests: Tests for degraded documents, those the store failed to write
+ * - server_tests: Tests for servers persisting and restoring documents
+ * - sqlite_tests: Tests for the SQLite store's database file
+ */
+
+mod conformance_tests;
+mod health_tests;
+mod server_tests;
+mod sqlite_tests;
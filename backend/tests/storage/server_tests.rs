@@ -0,0 +1,99 @@
+/*
+ * File: tests/storage/server_tests.rs
+ * Purpose: Test suite for servers persisting and restoring documents
+ *
+ * Test Categories:
+ * - Operations are logged as they are applied
+ * - Snapshots compact the log
+ * - Documents outlive a restart
+ * - Document passwords outlive a restart
+ */
+
+use std::{sync::Arc, time::Duration};
+use serde_json::json;
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    storage::{DocumentStore, MemoryStore, StorageConfig},
+    tenant::{PasswordConfig, DEFAULT_TENANT},
+    websocket::{MessageType, ServerConfig},
+};
+
+fn config(store: &Arc<MemoryStore>) -> ServerConfig {
+    ServerConfig {
+        storage: StorageConfig { store: store.clone(), snapshot_interval: Duration::from_secs(3600), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_operations_logged_and_snapshots_compact() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::in_process_with_config(config(&store));
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "Hi").await;
+    alice.create_document("empty", "").await;
+    alice.type_text("empty", "!!").await;
+
+    // Seeds are logged like any other edit
+    let stored = store.load("default/notes").unwrap().unwrap();
+    assert!(stored.snapshot.is_none());
+    assert_eq!(stored.operations.len(), 2);
+
+    assert_eq!(server.server().save_snapshots().await, 2);
+    let stored = store.load("default/empty").unwrap().unwrap();
+    assert_eq!(stored.snapshot.as_ref().unwrap().content(), "!!");
+    assert!(stored.operations.is_empty());
+    // Unchanged documents aren't saved again
+    assert_eq!(server.server().save_snapshots().await, 0);
+}
+
+#[tokio::test]
+async fn test_documents_outlive_restart() {
+    let store = Arc::new(MemoryStore::new());
+    let first = TestServer::in_process_with_config(config(&store));
+    let mut alice = first.connect().await;
+    alice.create_document("saved", "Hello").await;
+    first.server().save_snapshots().await;
+    alice.create_document("logged", "").await;
+    alice.type_text("logged", "world").await;
+    first.shutdown().await;
+
+    let second = TestServer::in_process_with_config(config(&store));
+    let saved = second.server().document(DEFAULT_TENANT, "saved").await.unwrap().unwrap();
+    assert_eq!(saved.content(), "Hello");
+    let mut bob = second.connect().await;
+    assert_eq!(bob.get_document("logged").await.content, "world");
+    // The first snapshots compact what was logged
+    assert_eq!(second.server().save_snapshots().await, 2);
+    assert!(store.load("default/logged").unwrap().unwrap().operations.is_empty());
+}
+
+#[tokio::test]
+async fn test_passwords_outlive_restart() {
+    let store = Arc::new(MemoryStore::new());
+    let config = || ServerConfig {
+        passwords: PasswordConfig { memory_kib: 64, iterations: 1, ..Default::default() },
+        ..config(&store)
+    };
+    let first = TestServer::in_process_with_config(config());
+    let mut owner = first.connect().await;
+    owner.create_document("notes", "secret").await;
+    owner.create_document("open", "public").await;
+    for (document_id, password) in [("notes", json!("hunter2")), ("open", json!("hunter2")), ("open", json!(null))] {
+        owner.request(MessageType::SetPassword, json!({ "document_id": document_id, "password": password })).await;
+        owner.expect(MessageType::Ack).await;
+    }
+    first.shutdown().await;
+    assert_eq!(store.load_passwords().unwrap().len(), 1);
+
+    // The restarted server keeps the document protected
+    let second = TestServer::in_process_with_config(config());
+    let mut guest = second.connect().await;
+    guest.request(MessageType::GetDocument, json!({ "document_id": "notes" })).await;
+    let error = guest.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains("protected by a password"), "{}", error.payload());
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    guest.expect(MessageType::Ack).await;
+    assert_eq!(guest.get_document("notes").await.content, "secret");
+    assert_eq!(guest.get_document("open").await.content, "public");
+}
@@ -0,0 +1,193 @@
+/*
+ * File: tests/storage/sqlite_tests.rs
+ * Purpose: Test suite for the SQLite store's database file
+ *
+ * Test Categories:
+ * - Schema migrations
+ * - Documents outlive reopening the file
+ * - Queued writes, durable once flushed
+ * - Group commit, and a bounded queue
+ * - Servers recover every document at startup
+ * - Encryption of stored documents, and rewrapping their keys
+ */
+
+use std::{path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant}};
+use crdt_editor_backend::{
+    fixtures::{DocumentBuilder, TestServer},
+    security::{MasterKey, StaticKeyProvider},
+    storage::{DocumentStore, GroupCommit, SqliteStore, StorageConfig},
+    tenant::DEFAULT_TENANT,
+    websocket::ServerConfig,
+};
+
+fn database() -> PathBuf {
+    std::env::temp_dir().join(format!("coedit-store-{}.db", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn test_migrations_applied_once() {
+    let path = database();
+    let store = SqliteStore::open(&path).unwrap();
+    let version = store.schema_version().unwrap();
+    assert!(version >= 1);
+    drop(store);
+
+    assert_eq!(SqliteStore::open(&path).unwrap().schema_version().unwrap(), version);
+
+    // A database from a newer server is refused
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    connection.pragma_update(None, "user_version", version as i64 + 1).unwrap();
+    drop(connection);
+    assert!(SqliteStore::open(&path).is_err());
+}
+
+#[test]
+fn test_documents_outlive_reopening() {
+    let path = database();
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    let store = SqliteStore::open(&path).unwrap();
+    store.save("default/notes", &DocumentBuilder::with_text("Hi").id("notes").build()).unwrap();
+    store.append_ops("default/logged", &operations).unwrap();
+    drop(store);
+
+    let store = SqliteStore::open(&path).unwrap();
+    assert_eq!(store.list().unwrap(), ["default/logged", "default/notes"]);
+    let notes = store.load("default/notes").unwrap().unwrap().restore("notes").unwrap();
+    assert_eq!(notes.content(), "Hi");
+    assert_eq!(notes.version(), 2);
+    let logged = store.load("default/logged").unwrap().unwrap();
+    assert_eq!(logged.operations, operations);
+}
+
+#[test]
+fn test_flushed_writes_are_durable() {
+    let path = database();
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    let store = SqliteStore::open(&path).unwrap();
+    store.append_ops("default/notes", &operations).unwrap();
+    store.save("default/saved", &DocumentBuilder::with_text("Hi").id("saved").build()).unwrap();
+    store.delete("default/saved").unwrap();
+    store.flush().unwrap();
+
+    // Another connection sees them while the store is still open
+    let reader = SqliteStore::open(&path).unwrap();
+    assert_eq!(reader.list().unwrap(), ["default/notes"]);
+    assert_eq!(reader.load("default/notes").unwrap().unwrap().operations, operations);
+}
+
+#[test]
+fn test_group_commit_as_configured() {
+    let operations = DocumentBuilder::with_text("Hello, world").operations();
+
+    // One write queued at a time, each committed on its own; writers wait
+    // for room rather than failing
+    let path = database();
+    let unbatched = GroupCommit { max_batch: 1, window: Duration::ZERO, queue_capacity: 1 };
+    let store = SqliteStore::open_with(&path, unbatched, None).unwrap();
+    for operation in &operations {
+        store.append_ops("default/notes", std::slice::from_ref(operation)).unwrap();
+    }
+    store.flush().unwrap();
+    assert_eq!(store.load("default/notes").unwrap().unwrap().operations, operations);
+
+    // A flush doesn't wait out the window
+    let path = database();
+    let windowed = GroupCommit { window: Duration::from_secs(30), ..Default::default() };
+    let store = SqliteStore::open_with(&path, windowed, None).unwrap();
+    let started = Instant::now();
+    store.append_ops("default/notes", &operations).unwrap();
+    store.flush().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(store.load("default/notes").unwrap().unwrap().operations, operations);
+}
+
+#[tokio::test]
+async fn test_server_recovers_documents() {
+    let path = database();
+    let config = |store: Arc<SqliteStore>| ServerConfig {
+        storage: StorageConfig { store, ..Default::default() },
+        ..Default::default()
+    };
+
+    let store = Arc::new(SqliteStore::open(&path).unwrap());
+    let first = TestServer::in_process_with_config(config(store.clone()));
+    let mut alice = first.connect().await;
+    alice.create_document("saved", "Hello").await;
+    first.server().save_snapshots().await;
+    alice.create_document("logged", "").await;
+    alice.type_text("logged", "world").await;
+    first.shutdown().await;
+    // Acknowledged operations are durable once the store is flushed
+    store.flush().unwrap();
+
+    let second = TestServer::in_process_with_config(config(Arc::new(SqliteStore::open(&path).unwrap())));
+    let saved = second.server().document(DEFAULT_TENANT, "saved").await.unwrap().unwrap();
+    assert_eq!(saved.content(), "Hello");
+    let mut bob = second.connect().await;
+    assert_eq!(bob.get_document("logged").await.content, "world");
+}
+
+/// Read a closed database file and its write-ahead log, if one is left
+fn stored_bytes(path: &Path) -> Vec<u8> {
+    let mut bytes = std::fs::read(path).unwrap();
+    bytes.extend(std::fs::read(path.with_extension("db-wal")).unwrap_or_default());
+    bytes
+}
+
+fn contains(bytes: &[u8], text: &str) -> bool {
+    bytes.windows(text.len()).any(|window| window == text.as_bytes())
+}
+
+#[test]
+fn test_encrypted_databases_hold_no_plaintext() {
+    let path = database();
+    let provider = Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string())));
+    let operations = DocumentBuilder::with_text("Hello").operations();
+    let store = SqliteStore::open_encrypted(&path, provider.clone()).unwrap();
+    store.save("default/notes", &DocumentBuilder::with_text("Hi").id("notes").build()).unwrap();
+    store.append_ops("default/logged", &operations).unwrap();
+    store.save_password("default/notes", Some("$pbkdf2$secret-hash")).unwrap();
+    drop(store);
+
+    let bytes = stored_bytes(&path);
+    for plaintext in ["characters", "Insert", "secret-hash"] {
+        assert!(!contains(&bytes, plaintext), "{} is stored as plaintext", plaintext);
+    }
+
+    // The same master keys read everything back
+    let store = SqliteStore::open_encrypted(&path, provider).unwrap();
+    assert_eq!(store.load("default/notes").unwrap().unwrap().restore("notes").unwrap().content(), "Hi");
+    assert_eq!(store.load("default/logged").unwrap().unwrap().operations, operations);
+    assert_eq!(store.load_passwords().unwrap(), [("default/notes".to_string(), "$pbkdf2$secret-hash".to_string())]);
+    drop(store);
+
+    // Without them, nothing is readable
+    let store = SqliteStore::open(&path).unwrap();
+    assert!(store.load("default/notes").is_err());
+    assert!(store.load_passwords().is_err());
+    let other = Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string())));
+    assert!(SqliteStore::open_encrypted(&path, other).unwrap().load("default/logged").is_err());
+}
+
+#[test]
+fn test_rotated_keys_are_stored() {
+    let path = database();
+    let store = SqliteStore::open(&path).unwrap();
+    store.save("default/plain", &DocumentBuilder::with_text("Old").id("plain").build()).unwrap();
+    drop(store);
+
+    let provider = Arc::new(StaticKeyProvider::new(MasterKey::generate("k1".to_string())));
+    let store = SqliteStore::open_encrypted(&path, provider.clone()).unwrap();
+    store.save("default/notes", &DocumentBuilder::with_text("Hi").id("notes").build()).unwrap();
+    let active = MasterKey::generate("k2".to_string());
+    provider.rotate(active.clone());
+    assert_eq!(store.rotate_keys().unwrap(), 1);
+    assert_eq!(store.rotate_keys().unwrap(), 0);
+    drop(store);
+
+    // The retired master key isn't needed any more, and rows stored before
+    // encryption was turned on are still read
+    let store = SqliteStore::open_encrypted(&path, Arc::new(StaticKeyProvider::new(active))).unwrap();
+    assert_eq!(store.load("default/notes").unwrap().unwrap().restore("notes").unwrap().content(), "Hi");
+    assert_eq!(store.load("default/plain").unwrap().unwrap().restore("plain").unwrap().content(), "Old");
+}
@@ -0,0 +1,9 @@
+/*
+ * File: tests/telemetry/mod.rs
+ * Purpose: Test module organization for usage statistics
+ *
+ * Test modules:
+ * - usage_tests: Tests for usage reports and their delivery
+ */
+
+mod usage_tests;
@@ -0,0 +1,85 @@
+/*
+ * File: tests/telemetry/usage_tests.rs
+ * Purpose: Test suite for anonymous usage statistics
+ *
+ * Test Categories:
+ * - Off-by-default configuration
+ * - Count bucketing
+ * - Report contents
+ * - Report delivery
+ */
+
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+use crdt_editor_backend::telemetry::{bucket, TelemetryConfig, UsageReport, UsageReporter};
+
+#[test]
+fn test_disabled_by_default() {
+    let config = TelemetryConfig::default();
+    assert!(!config.enabled);
+    assert!(config.endpoint.is_none());
+    assert!(UsageReporter::from_config(&config).is_none());
+
+    // Enabling without an endpoint still sends nothing
+    let config = TelemetryConfig { enabled: true, ..Default::default() };
+    assert!(UsageReporter::from_config(&config).is_none());
+}
+
+#[test]
+fn test_counts_bucketed() {
+    assert_eq!(bucket(0), "0");
+    assert_eq!(bucket(1), "1-9");
+    assert_eq!(bucket(9), "1-9");
+    assert_eq!(bucket(10), "10-99");
+    assert_eq!(bucket(999), "100-999");
+    assert_eq!(bucket(5_000), "1000-9999");
+    assert_eq!(bucket(10_000), "10000+");
+    assert_eq!(bucket(u64::MAX), "10000+");
+}
+
+#[test]
+fn test_report_contains_only_coarse_counters() {
+    let report = UsageReport::new(42, 7);
+    let value = serde_json::to_value(&report).unwrap();
+
+    let mut fields: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+    fields.sort();
+    assert_eq!(fields, ["documents", "peak_clients", "version"]);
+    assert_eq!(value["documents"], "10-99");
+    assert_eq!(value["peak_clients"], "1-9");
+    assert_eq!(value["version"], env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn test_reporter_rejects_unsupported_endpoint() {
+    assert!(UsageReporter::new("not a url").is_err());
+    assert!(UsageReporter::new("https://example.com/usage").is_err());
+}
+
+#[tokio::test]
+async fn test_report_delivery() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let route = warp::post()
+        .and(warp::path("usage"))
+        .and(warp::body::json())
+        .map({
+            let received = received.clone();
+            move |report: UsageReport| {
+                received.lock().unwrap().push(report);
+                warp::reply()
+            }
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let config = TelemetryConfig {
+        enabled: true,
+        endpoint: Some(format!("http://{}/usage", addr)),
+        ..Default::default()
+    };
+    let reporter = UsageReporter::from_config(&config).unwrap();
+    let report = UsageReport::new(3, 250);
+    reporter.send(&report).await.unwrap();
+
+    assert_eq!(*received.lock().unwrap(), vec![report]);
+}
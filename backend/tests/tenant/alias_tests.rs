@@ -0,0 +1,84 @@
+/*
+ * File: tests/tenant/alias_tests.rs
+ * Purpose: Test suite for document slugs
+ *
+ * Test Categories:
+ * - Slug resolution
+ * - Rename redirects
+ * - Collision handling
+ * - Slug validation
+ * - Tenant isolation
+ */
+
+use crdt_editor_backend::tenant::{AliasError, AliasTable, TenantConfig, TenantRegistry};
+use crdt_editor_backend::websocket::QuotaConfig;
+
+#[test]
+fn test_slug_resolves_to_document() {
+    let aliases = AliasTable::new();
+    aliases.set_slug("doc1", "meeting-notes").unwrap();
+
+    assert_eq!(aliases.resolve("meeting-notes"), "doc1");
+    assert_eq!(aliases.slug("doc1").as_deref(), Some("meeting-notes"));
+    // Anything else is taken to be a document ID
+    assert_eq!(aliases.resolve("doc2"), "doc2");
+}
+
+#[test]
+fn test_renamed_slug_redirects() {
+    let aliases = AliasTable::new();
+    aliases.set_slug("doc1", "draft").unwrap();
+    aliases.set_slug("doc1", "final-report").unwrap();
+
+    assert_eq!(aliases.slug("doc1").as_deref(), Some("final-report"));
+    assert_eq!(aliases.resolve("draft"), "doc1");
+    assert_eq!(aliases.resolve("final-report"), "doc1");
+
+    // Renaming back to a retired slug is allowed
+    aliases.set_slug("doc1", "draft").unwrap();
+    assert_eq!(aliases.slug("doc1").as_deref(), Some("draft"));
+}
+
+#[test]
+fn test_slug_collisions_rejected() {
+    let aliases = AliasTable::new();
+    aliases.set_slug("doc1", "draft").unwrap();
+    aliases.set_slug("doc1", "final").unwrap();
+
+    assert_eq!(aliases.set_slug("doc2", "final"), Err(AliasError::Taken("final".to_string())));
+    // Retired slugs stay reserved for their document
+    assert_eq!(aliases.set_slug("doc2", "draft"), Err(AliasError::Taken("draft".to_string())));
+    assert_eq!(aliases.resolve("draft"), "doc1");
+
+    // Removing the document frees its slugs
+    aliases.remove_document("doc1");
+    assert!(!aliases.contains("draft"));
+    aliases.set_slug("doc2", "draft").unwrap();
+    assert_eq!(aliases.resolve("draft"), "doc2");
+}
+
+#[test]
+fn test_invalid_slugs_rejected() {
+    let aliases = AliasTable::new();
+    for slug in ["ab", "Notes", "has space", "-leading", "trailing-", "under_score", &"a".repeat(65)] {
+        assert_eq!(aliases.set_slug("doc1", slug), Err(AliasError::InvalidSlug(slug.to_string())));
+    }
+    assert!(aliases.slug("doc1").is_none());
+}
+
+#[test]
+fn test_slugs_scoped_to_tenant() {
+    let configs = ["acme", "globex"]
+        .iter()
+        .map(|id| TenantConfig { id: id.to_string(), ..Default::default() })
+        .collect();
+    let registry = TenantRegistry::new(QuotaConfig::default(), configs).unwrap();
+    let acme = registry.get("acme").unwrap();
+    let globex = registry.get("globex").unwrap();
+
+    acme.aliases().set_slug("doc1", "roadmap").unwrap();
+    globex.aliases().set_slug("doc9", "roadmap").unwrap();
+
+    assert_eq!(acme.aliases().resolve("roadmap"), "doc1");
+    assert_eq!(globex.aliases().resolve("roadmap"), "doc9");
+}
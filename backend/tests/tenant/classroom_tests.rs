@@ -0,0 +1,91 @@
+/*
+ * File: tests/tenant/classroom_tests.rs
+ * Purpose: Test suite for classroom settings
+ *
+ * Test Categories:
+ * - Document ownership
+ * - Freezing and unfreezing
+ * - Breakout naming
+ * - Character restrictions
+ */
+
+use crdt_editor_backend::tenant::{
+    classroom::breakout_id, CharacterSet, ClassroomError, ClassroomTable, DocumentSettings,
+};
+
+#[test]
+fn test_first_claim_owns_document() {
+    let classroom = ClassroomTable::new();
+
+    assert!(classroom.claim("doc1", "teacher"));
+    assert!(!classroom.claim("doc1", "student"));
+    assert_eq!(classroom.settings("doc1").owner.as_deref(), Some("teacher"));
+    assert_eq!(classroom.settings("unknown"), DocumentSettings::default());
+    assert!(classroom.check_owner("doc1", "teacher").is_ok());
+    assert_eq!(classroom.check_owner("doc1", "student"), Err(ClassroomError::NotOwner("doc1".to_string())));
+}
+
+#[test]
+fn test_frozen_documents_limited_to_owner() {
+    let classroom = ClassroomTable::new();
+    classroom.claim("doc1", "teacher");
+
+    assert_eq!(classroom.set_frozen("doc1", "student", true), Err(ClassroomError::NotOwner("doc1".to_string())));
+    assert!(classroom.check_edit("doc1", "student").is_ok());
+
+    let settings = classroom.set_frozen("doc1", "teacher", true).unwrap();
+    assert!(settings.frozen);
+    assert_eq!(classroom.check_edit("doc1", "student"), Err(ClassroomError::Frozen("doc1".to_string())));
+    assert!(classroom.check_edit("doc1", "teacher").is_ok());
+
+    classroom.set_frozen("doc1", "teacher", false).unwrap();
+    assert!(classroom.check_edit("doc1", "student").is_ok());
+
+    // Documents without an owner can't be frozen
+    assert!(classroom.set_frozen("doc2", "teacher", true).is_err());
+    classroom.remove_document("doc1");
+    assert_eq!(classroom.settings("doc1"), DocumentSettings::default());
+}
+
+#[test]
+fn test_breakout_names() {
+    assert_eq!(breakout_id("essay", "alice").unwrap(), "essay-alice");
+    assert_eq!(breakout_id("essay", "group-2").unwrap(), "essay-group-2");
+    assert_eq!(breakout_id("essay", "Bob"), Err(ClassroomError::InvalidBreakout("Bob".to_string())));
+    assert!(breakout_id("essay", "x").is_err());
+}
+
+#[test]
+fn test_character_restrictions() {
+    let classroom = ClassroomTable::new();
+    classroom.claim("log", "owner");
+    assert!(classroom.check_text("log", "héllo 👋").is_ok());
+
+    assert_eq!(
+        classroom.set_charset("log", "student", CharacterSet::Ascii),
+        Err(ClassroomError::NotOwner("log".to_string())),
+    );
+    let settings = classroom.set_charset("log", "owner", CharacterSet::Ascii).unwrap();
+    assert_eq!(settings.charset, CharacterSet::Ascii);
+    assert!(classroom.check_text("log", "ok: 42\n\tdone~").is_ok());
+    let error = classroom.check_text("log", "héllo").unwrap_err();
+    assert_eq!(
+        error,
+        ClassroomError::DisallowedCharacter {
+            document_id: "log".to_string(),
+            character: 'é',
+            charset: CharacterSet::Ascii,
+        },
+    );
+    assert!(error.to_string().contains("U+00E9"));
+    assert!(classroom.check_text("log", "\u{7}").is_err());
+
+    classroom.set_charset("log", "owner", CharacterSet::NoEmoji).unwrap();
+    assert!(classroom.check_text("log", "héllo, 世界 → ok").is_ok());
+    for emoji in ["👋", "🇳🇱", "☕", "⌛", "❤\u{FE0F}"] {
+        assert!(classroom.check_text("log", emoji).is_err(), "{} is an emoji", emoji);
+    }
+
+    // Documents nobody claimed accept everything
+    assert!(classroom.check_text("other", "👋").is_ok());
+}
@@ -0,0 +1,17 @@
+/*
+ * File: tests/tenant/mod.rs
+ * Purpose: Test module organization for multi-tenancy
+ * 
+ * Test modules:
+ * - registry_tests: Tests for the tenant registry and namespacing
+ * - alias_tests: Tests for document slugs and alias resolution
+ * - classroom_tests: Tests for document owners, freezing, and breakouts
+ * - passwords_tests: Tests for pre-shared document passwords
+ * - protected_routes_tests: Tests for protected documents on HTTP routes and feeds
+ */
+
+mod registry_tests;
+mod alias_tests;
+mod classroom_tests;
+mod passwords_tests;
+mod protected_routes_tests;
@@ -0,0 +1,175 @@
+/*
+ * File: tests/tenant/passwords_tests.rs
+ * Purpose: Test suite for pre-shared document passwords
+ *
+ * Test Categories:
+ * - Unlocking protected documents per connection
+ * - Relays withheld from clients that haven't unlocked
+ * - Lockout after wrong passwords, and its expiry
+ * - Concurrent attempts reserved against the lockout
+ * - Owner-only passwords and the admin reset
+ */
+
+use std::time::{Duration, Instant};
+use serde_json::json;
+use crdt_editor_backend::{
+    crdt::Position,
+    fixtures::TestServer,
+    tenant::{PasswordConfig, PasswordError, PasswordTable, DEFAULT_TENANT},
+    websocket::{AdminConfig, Message, MessageType, ServerConfig},
+};
+
+fn config() -> PasswordConfig {
+    PasswordConfig { memory_kib: 64, iterations: 1, max_failures: 2, lockout: Duration::from_secs(60) }
+}
+
+#[test]
+fn test_wrong_passwords_lock_out_until_expiry() {
+    let passwords = PasswordTable::new();
+    let now = Instant::now();
+    passwords.set("notes", "owner", Some("hash".to_string()));
+    assert!(passwords.is_protected("notes"));
+    assert_eq!(passwords.check_access("notes", "owner"), Ok(()));
+    assert_eq!(passwords.check_access("notes", "guest"), Err(PasswordError::Locked("notes".to_string())));
+    assert_eq!(passwords.check_access("open", "guest"), Ok(()));
+
+    for _ in 0..2 {
+        assert_eq!(passwords.begin_unlock("notes", &config(), now), Ok("hash".to_string()));
+        assert_eq!(passwords.finish_unlock("notes", "guest", false, &config(), now), Err(PasswordError::Wrong("notes".to_string())));
+    }
+    let locked_out = PasswordError::LockedOut { document_id: "notes".to_string(), seconds: 60 };
+    assert_eq!(passwords.begin_unlock("notes", &config(), now), Err(locked_out));
+
+    let later = now + Duration::from_secs(61);
+    assert!(passwords.begin_unlock("notes", &config(), later).is_ok());
+    assert_eq!(passwords.finish_unlock("notes", "guest", true, &config(), later), Ok(()));
+    assert_eq!(passwords.check_access("notes", "guest"), Ok(()));
+
+    // Unlocks last for the connection
+    passwords.forget_client("guest");
+    assert!(passwords.check_access("notes", "guest").is_err());
+    assert_eq!(passwords.begin_unlock("open", &config(), now), Err(PasswordError::NotProtected("open".to_string())));
+}
+
+#[test]
+fn test_concurrent_attempts_share_the_lockout() {
+    let passwords = PasswordTable::new();
+    let now = Instant::now();
+    passwords.set("notes", "owner", Some("hash".to_string()));
+
+    // Attempts checked at once are reserved as they begin, so no more than
+    // `max_failures` of them get a hash to guess against
+    assert!(passwords.begin_unlock("notes", &config(), now).is_ok());
+    assert!(passwords.begin_unlock("notes", &config(), now).is_ok());
+    assert_eq!(passwords.begin_unlock("notes", &config(), now), Err(PasswordError::Busy("notes".to_string())));
+    assert!(passwords.finish_unlock("notes", "guest", false, &config(), now).is_err());
+    assert!(passwords.finish_unlock("notes", "mallory", false, &config(), now).is_err());
+    let locked_out = PasswordError::LockedOut { document_id: "notes".to_string(), seconds: 60 };
+    assert_eq!(passwords.begin_unlock("notes", &config(), now), Err(locked_out));
+
+    // A right password frees its reservation along with the failures
+    let later = now + Duration::from_secs(61);
+    assert!(passwords.begin_unlock("notes", &config(), later).is_ok());
+    assert_eq!(passwords.finish_unlock("notes", "guest", true, &config(), later), Ok(()));
+    assert!(passwords.begin_unlock("notes", &config(), later).is_ok());
+    assert!(passwords.begin_unlock("notes", &config(), later).is_ok());
+}
+
+#[tokio::test]
+async fn test_protected_document_needs_unlocking() {
+    let server = TestServer::in_process_with_config(ServerConfig { passwords: config(), ..Default::default() });
+    let mut owner = server.connect().await;
+    let mut guest = server.connect().await;
+    owner.create_document("notes", "secret plans").await;
+
+    // Only the owner sets a password
+    guest.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    guest.expect(MessageType::Error).await;
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    let ack = owner.expect(MessageType::Ack).await;
+    assert_eq!(ack.payload()["protected"], true);
+    assert_eq!(owner.get_document("notes").await.content, "secret plans");
+
+    // Anything addressing the document needs the password first
+    guest.request(MessageType::GetDocument, json!({ "document_id": "notes" })).await;
+    let error = guest.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains("protected by a password"), "{}", error.payload());
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "password": "hunter3" })).await;
+    let error = guest.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains("Wrong password"), "{}", error.payload());
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    guest.expect(MessageType::Ack).await;
+    assert_eq!(guest.get_document("notes").await.content, "secret plans");
+
+    // Removing the password opens the document again
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": null })).await;
+    assert_eq!(owner.expect(MessageType::Ack).await.payload()["protected"], false);
+    let mut late = server.connect().await;
+    assert_eq!(late.get_document("notes").await.content, "secret plans");
+}
+
+#[tokio::test]
+async fn test_locked_clients_receive_nothing() {
+    let server = TestServer::in_process_with_config(ServerConfig { passwords: config(), ..Default::default() });
+    let mut owner = server.connect().await;
+    let mut reader = server.connect().await;
+    owner.create_document("notes", "").await;
+    reader.get_document("notes").await;
+
+    // A new password drops the readers that haven't unlocked it
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    owner.expect(MessageType::Ack).await;
+    owner.insert("notes", 's', Position::new(vec![1])).await;
+    owner.request(MessageType::CursorUpdate, json!({ "document_id": "notes", "caret": Position::new(vec![1]) })).await;
+    while let Some(message) = reader.recv_within(Duration::from_millis(100)).await {
+        assert!(!matches!(message.message_type(), MessageType::Operation | MessageType::CursorUpdate), "{:?}", message);
+    }
+
+    // Naming the document in the connection URL doesn't join it either
+    let mut by_path = warp::test::ws().path("/ws/notes").handshake(server.server().routes()).await.unwrap();
+    for expected in [MessageType::Status, MessageType::Error] {
+        let message: Message = serde_json::from_str(by_path.recv().await.unwrap().to_str().unwrap()).unwrap();
+        assert_eq!(message.message_type(), &expected);
+    }
+    let joined = server.server().clients_in_document(DEFAULT_TENANT, "notes").await.unwrap();
+    assert_eq!(joined, [owner.id()]);
+
+    // Once unlocked and opened again, operations reach the reader
+    reader.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    reader.expect(MessageType::Ack).await;
+    assert_eq!(reader.get_document("notes").await.content, "s");
+    owner.insert("notes", 't', Position::new(vec![2])).await;
+    reader.expect(MessageType::Operation).await;
+}
+
+#[tokio::test]
+async fn test_admin_reset_lifts_lockout() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        passwords: config(),
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        ..Default::default()
+    });
+    let mut owner = server.connect().await;
+    let mut guest = server.connect().await;
+    owner.create_document("notes", "").await;
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    owner.expect(MessageType::Ack).await;
+
+    for password in ["guess1", "guess2", "hunter2"] {
+        guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "password": password })).await;
+        guest.expect(MessageType::Error).await;
+    }
+
+    let routes = server.server().routes();
+    let path = format!("/admin/passwords/{}/notes", DEFAULT_TENANT);
+    for expected in [204, 404] {
+        let reply = warp::test::request()
+            .method("DELETE")
+            .path(&path)
+            .header("authorization", "Bearer secret")
+            .reply(&routes)
+            .await;
+        assert_eq!(reply.status(), expected);
+    }
+    assert_eq!(guest.get_document("notes").await.content, "");
+}
@@ -0,0 +1,154 @@
+/*
+ * File: tests/tenant/protected_routes_tests.rs
+ * Purpose: Test suite for password-protected documents on HTTP routes and feeds
+ *
+ * Test Categories:
+ * - Exports, imports and links that need a share token over HTTP
+ * - Listings, events and activity that leave protected documents out
+ * - Tails of protected documents held back until unlocking
+ */
+
+use serde_json::{json, Value};
+use crdt_editor_backend::{
+    fixtures::{TestClient, TestServer},
+    tenant::PasswordConfig,
+    websocket::{message::DocumentStateMessage, ActivityPage, MessageType, ServerConfig},
+};
+
+/// Start a server with an open document `wiki` and a document `notes`
+/// linking to it, protected by a password. Returns the owner and a share
+/// token for `notes`.
+async fn protected() -> (TestServer, TestClient, String) {
+    let passwords = PasswordConfig { memory_kib: 64, iterations: 1, ..Default::default() };
+    let server = TestServer::in_process_with_config(ServerConfig { passwords, ..Default::default() });
+    let mut owner = server.connect().await;
+    owner.create_document("wiki", "Start here").await;
+    owner.create_document("notes", "secret plans, see [[wiki]]").await;
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter2" })).await;
+    owner.expect(MessageType::Ack).await;
+    owner.request(MessageType::ShareDocument, json!({ "document_id": "notes" })).await;
+    let token = owner.expect(MessageType::Ack).await.payload()["share_token"].as_str().unwrap().to_string();
+    (server, owner, token)
+}
+
+/// Send a request to the server's routes, returning the status and body
+async fn request(server: &TestServer, method: &str, path: &str) -> (u16, String) {
+    let reply = warp::test::request().method(method).path(path).body("more").reply(&server.server().routes()).await;
+    (reply.status().as_u16(), String::from_utf8_lossy(reply.body()).to_string())
+}
+
+fn document_ids(items: &Value) -> Vec<&str> {
+    items.as_array().unwrap().iter().map(|item| item["document_id"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn test_exports_and_imports_need_a_share_token() {
+    let (server, mut owner, token) = protected().await;
+    let (status, body) = request(&server, "GET", "/documents/notes/export").await;
+    assert_eq!(status, 403);
+    assert!(body.contains("protected by a password"), "{}", body);
+    assert_eq!(request(&server, "GET", "/documents/notes/export?share=bogus").await.0, 403);
+    let (status, body) = request(&server, "GET", &format!("/documents/notes/export?share={}", token)).await;
+    assert_eq!(status, 200);
+    assert!(body.contains("secret plans"), "{}", body);
+    assert_eq!(request(&server, "GET", "/documents/wiki/export").await.0, 200);
+
+    // Imports write to the document, so they need the token too
+    assert_eq!(request(&server, "POST", "/documents/notes/import").await.0, 403);
+    assert_eq!(request(&server, "POST", &format!("/documents/notes/import?share={}", token)).await.0, 200);
+
+    // A new password revokes the token here as well
+    owner.request(MessageType::SetPassword, json!({ "document_id": "notes", "password": "hunter3" })).await;
+    owner.expect(MessageType::Ack).await;
+    let (status, body) = request(&server, "GET", &format!("/documents/notes/export?share={}", token)).await;
+    assert_eq!(status, 403);
+    assert!(body.contains("revoked"), "{}", body);
+}
+
+#[tokio::test]
+async fn test_links_need_a_share_token_and_hide_protected_backlinks() {
+    let (server, mut owner, token) = protected().await;
+    owner.create_document("readme", "See [[wiki]]").await;
+    assert_eq!(request(&server, "GET", "/documents/notes/links").await.0, 403);
+    let (status, body) = request(&server, "GET", &format!("/documents/notes/links?share={}", token)).await;
+    assert_eq!(status, 200);
+    let links: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(links["links"], json!(["wiki"]));
+
+    // Linking to the wiki is part of what the protected document says
+    let (status, body) = request(&server, "GET", "/documents/wiki/links").await;
+    assert_eq!(status, 200);
+    let links: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(links["backlinks"], json!(["readme"]));
+}
+
+#[tokio::test]
+async fn test_listings_leave_out_protected_documents() {
+    let (server, _owner, _token) = protected().await;
+    let (status, body) = request(&server, "GET", "/documents").await;
+    assert_eq!(status, 200);
+    let listing: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(document_ids(&listing["documents"]), ["wiki"]);
+    assert!(!body.contains("secret plans"), "{}", body);
+}
+
+#[tokio::test]
+async fn test_events_leave_out_protected_documents() {
+    let (server, mut owner, _token) = protected().await;
+    let (status, body) = request(&server, "GET", "/events?timeout=0").await;
+    assert_eq!(status, 200);
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(document_ids(&page["events"]), ["wiki"]);
+
+    // Followers move past the hidden events
+    owner.type_text("notes", "x").await;
+    owner.type_text("wiki", "y").await;
+    let (_, body) = request(&server, "GET", &format!("/events?since={}&timeout=0", page["next"])).await;
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(document_ids(&page["events"]), ["wiki"]);
+}
+
+#[tokio::test]
+async fn test_activity_leaves_out_protected_documents() {
+    let (server, mut owner, token) = protected().await;
+    let (status, body) = request(&server, "GET", "/activity").await;
+    assert_eq!(status, 200);
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(document_ids(&page["activities"]), ["wiki"]);
+
+    // Subscribers see the document's activity once they unlock it
+    let mut locked = server.connect().await;
+    let mut unlocked = server.connect().await;
+    unlocked.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "share_token": &token })).await;
+    unlocked.expect(MessageType::Ack).await;
+    let mut pages = Vec::new();
+    for client in [&mut locked, &mut unlocked] {
+        client.request(MessageType::SubscribeActivity, json!({})).await;
+        let page: ActivityPage = serde_json::from_value(client.expect(MessageType::ActivityPage).await.payload().clone()).unwrap();
+        pages.push(page.activities.into_iter().map(|activity| activity.document_id).collect::<Vec<_>>());
+    }
+    assert_eq!(pages, [vec!["wiki"], vec!["notes", "wiki"]]);
+
+    owner.request(MessageType::SetSlug, json!({ "document_id": "notes", "slug": "plans" })).await;
+    owner.expect(MessageType::Ack).await;
+    owner.create_document("later", "").await;
+    assert_eq!(unlocked.expect(MessageType::Activity).await.payload()["document_id"], "notes");
+    assert_eq!(unlocked.expect(MessageType::Activity).await.payload()["document_id"], "later");
+    assert_eq!(locked.expect(MessageType::Activity).await.payload()["document_id"], "later");
+}
+
+#[tokio::test]
+async fn test_tails_need_unlocking() {
+    let (server, mut owner, token) = protected().await;
+    owner.type_text("notes", "x").await;
+    let mut guest = server.connect().await;
+    guest.request(MessageType::GetDocument, json!({ "document_id": "notes", "accept_tail": true })).await;
+    let error = guest.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains("protected by a password"), "{}", error.payload());
+
+    guest.request(MessageType::UnlockDocument, json!({ "document_id": "notes", "share_token": &token })).await;
+    guest.expect(MessageType::Ack).await;
+    guest.request(MessageType::GetDocument, json!({ "document_id": "notes", "accept_tail": true })).await;
+    let state: DocumentStateMessage = serde_json::from_value(guest.expect(MessageType::DocumentState).await.payload().clone()).unwrap();
+    assert!(state.content.contains("secret plans"), "{}", state.content);
+}
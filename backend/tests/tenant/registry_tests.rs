@@ -0,0 +1,96 @@
+/*
+ * File: tests/tenant/registry_tests.rs
+ * Purpose: Test suite for the tenant registry
+ * 
+ * Test Categories:
+ * - Default tenant and registration
+ * - Tenant ID validation
+ * - Access key authorization
+ * - Namespaced document keys
+ * - Per-tenant quotas
+ */
+
+use crdt_editor_backend::security::EncryptionMode;
+use crdt_editor_backend::tenant::{TenantConfig, TenantError, TenantRegistry, DEFAULT_TENANT};
+use crdt_editor_backend::websocket::QuotaConfig;
+
+fn tenant(id: &str) -> TenantConfig {
+    TenantConfig {
+        id: id.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_default_tenant_always_present() {
+    let registry = TenantRegistry::new(QuotaConfig::default(), vec![tenant("acme")]).unwrap();
+    assert_eq!(registry.ids(), vec!["acme".to_string(), DEFAULT_TENANT.to_string()]);
+    assert!(registry.get(DEFAULT_TENANT).is_ok());
+}
+
+#[test]
+fn test_register_and_remove() {
+    let registry = TenantRegistry::new(QuotaConfig::default(), Vec::new()).unwrap();
+    registry.register(tenant("acme")).unwrap();
+
+    assert!(matches!(registry.register(tenant("acme")), Err(TenantError::Exists(_))));
+    registry.remove("acme").unwrap();
+    assert!(matches!(registry.get("acme"), Err(TenantError::NotFound(_))));
+}
+
+#[test]
+fn test_invalid_tenant_ids() {
+    let registry = TenantRegistry::new(QuotaConfig::default(), Vec::new()).unwrap();
+    for id in ["", "a/b", "with space", "ünïcode"] {
+        assert!(matches!(registry.register(tenant(id)), Err(TenantError::InvalidId(_))));
+    }
+}
+
+#[test]
+fn test_access_keys() {
+    let registry = TenantRegistry::new(QuotaConfig::default(), Vec::new()).unwrap();
+    let open = registry.get(DEFAULT_TENANT).unwrap();
+    assert!(open.authorize(None).is_ok());
+
+    let locked = registry.register(TenantConfig {
+        id: "acme".to_string(),
+        api_keys: vec!["secret".to_string()],
+        quota: None,
+        encryption: EncryptionMode::AtRest,
+    }).unwrap();
+    assert!(locked.authorize(Some("secret")).is_ok());
+    assert!(matches!(locked.authorize(Some("wrong")), Err(TenantError::Unauthorized(_))));
+    assert!(matches!(locked.authorize(None), Err(TenantError::Unauthorized(_))));
+}
+
+#[test]
+fn test_document_namespacing() {
+    let registry = TenantRegistry::new(QuotaConfig::default(), vec![tenant("a"), tenant("ab")]).unwrap();
+    let a = registry.get("a").unwrap();
+    let ab = registry.get("ab").unwrap();
+
+    assert_eq!(a.scoped("doc1"), "a/doc1");
+    assert_eq!(a.unscoped("a/doc1"), Some("doc1"));
+    assert_eq!(a.unscoped("a/nested/doc"), Some("nested/doc"));
+
+    // Keys of other tenants are never visible, even with a shared prefix
+    assert_eq!(a.unscoped("ab/doc1"), None);
+    assert_eq!(ab.unscoped("a/doc1"), None);
+}
+
+#[test]
+fn test_per_tenant_quota() {
+    let default_quota = QuotaConfig {
+        max_document_characters: Some(10),
+        ..Default::default()
+    };
+    let registry = TenantRegistry::new(default_quota, vec![TenantConfig {
+        id: "big".to_string(),
+        api_keys: Vec::new(),
+        quota: Some(QuotaConfig::default()),
+        encryption: EncryptionMode::AtRest,
+    }]).unwrap();
+
+    assert!(!registry.get(DEFAULT_TENANT).unwrap().quota().allows_insert(10));
+    assert!(registry.get("big").unwrap().quota().allows_insert(10));
+}
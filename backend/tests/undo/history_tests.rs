@@ -0,0 +1,259 @@
+/*
+ * File: tests/undo/history_tests.rs
+ * Purpose: Test suite for undo histories and scopes
+ *
+ * Test Categories:
+ * - Own-edits scope with interleaved edits of other clients
+ * - Undoing one of the inserts tied at a position
+ * - Global linear scope
+ * - Undoing deletes
+ * - Edits already reverted by other clients
+ * - Undoing range deletes in one step
+ * - Redo, and new edits clearing it
+ * - Undoing deletes whose tombstones were collected
+ * - Undoing range deletes whose tombstones were collected, in order
+ */
+
+use crdt_editor_backend::{
+    crdt::{Document, Operation, Position},
+    undo::{UndoError, UndoHistory, UndoScope},
+};
+
+/// Apply an insert and record it for its client
+fn type_char(doc: &mut Document, history: &UndoHistory, client: &str, character: char, path: u32) {
+    let operation = Operation::insert(client.to_string(), character, Position::new(vec![path]));
+    doc.apply_operation(operation.clone()).unwrap();
+    history.record(doc.id(), client, &operation);
+}
+
+/// Undo for a client and apply the inverse
+fn undo(doc: &mut Document, history: &UndoHistory, client: &str) -> Result<(), UndoError> {
+    for operation in history.undo(doc.id(), doc, client)? {
+        doc.apply_operation(operation).unwrap();
+    }
+    Ok(())
+}
+
+/// Redo for a client and apply the inverse
+fn redo(doc: &mut Document, history: &UndoHistory, client: &str) -> Result<(), UndoError> {
+    for operation in history.redo(doc.id(), doc, client)? {
+        doc.apply_operation(operation).unwrap();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_own_scope_keeps_other_clients_edits() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    assert_eq!(history.scope("doc1"), UndoScope::Own);
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "bob", 'b', 2);
+    type_char(&mut doc, &history, "alice", 'c', 3);
+
+    undo(&mut doc, &history, "alice").unwrap();
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "b");
+    assert_eq!(undo(&mut doc, &history, "alice"), Err(UndoError::NothingToUndo("doc1".to_string())));
+
+    undo(&mut doc, &history, "bob").unwrap();
+    assert_eq!(doc.content(), "");
+}
+
+#[test]
+fn test_undo_of_a_tied_insert_removes_only_it() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "bob", 'b', 1);
+    assert_eq!(doc.content(), "ab");
+
+    undo(&mut doc, &history, "bob").unwrap();
+    assert_eq!(doc.content(), "a");
+
+    // Redoing brings back Bob's character, not the newest one there
+    type_char(&mut doc, &history, "carol", 'c', 1);
+    redo(&mut doc, &history, "bob").unwrap();
+    assert_eq!(doc.content().chars().filter(|c| *c == 'b').count(), 1);
+    assert_eq!(doc.content().len(), 3);
+}
+
+#[test]
+fn test_global_scope_is_linear() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    history.set_scope("doc1", UndoScope::Global);
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "bob", 'b', 2);
+
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "a");
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "");
+    assert!(undo(&mut doc, &history, "bob").is_err());
+}
+
+#[test]
+fn test_undoing_a_delete_restores_the_character() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "alice", 'b', 2);
+    let delete = Operation::delete("alice".to_string(), Position::new(vec![1]));
+    doc.apply_operation(delete.clone()).unwrap();
+    history.record("doc1", "alice", &delete);
+    assert_eq!(doc.content(), "b");
+
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+
+    // The restored character is the one the older insert refers to now
+    undo(&mut doc, &history, "alice").unwrap();
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "");
+}
+
+#[test]
+fn test_edits_reverted_by_others_are_skipped() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "alice", 'b', 2);
+    doc.apply_operation(Operation::delete("bob".to_string(), Position::new(vec![2]))).unwrap();
+
+    // Alice's newest edit is gone already, so undo reaches the one before
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "");
+    assert!(undo(&mut doc, &history, "alice").is_err());
+}
+
+#[test]
+fn test_undoing_a_range_delete_restores_it() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "alice", 'b', 2);
+    type_char(&mut doc, &history, "alice", 'c', 3);
+    let range = Operation::delete_range("alice".to_string(), &doc, Position::new(vec![2]), Position::end());
+    doc.apply_operation(range.clone()).unwrap();
+    history.record("doc1", "alice", &range);
+    assert_eq!(doc.content(), "a");
+
+    // One undo brings back the whole range
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "abc");
+    redo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "a");
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "abc");
+
+    // The inserts before it follow the restored characters
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+}
+
+#[test]
+fn test_redo_reverts_undo() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "alice", 'b', 2);
+    let delete = Operation::delete("alice".to_string(), Position::new(vec![1]));
+    doc.apply_operation(delete.clone()).unwrap();
+    history.record("doc1", "alice", &delete);
+
+    undo(&mut doc, &history, "alice").unwrap();
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "a");
+    assert_eq!(redo(&mut doc, &history, "bob"), Err(UndoError::NothingToRedo("doc1".to_string())));
+
+    redo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+    redo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "b");
+    assert!(redo(&mut doc, &history, "alice").is_err());
+
+    // Redone edits can be undone again
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+}
+
+#[test]
+fn test_new_edits_clear_redo() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "bob", 'b', 2);
+    undo(&mut doc, &history, "alice").unwrap();
+    undo(&mut doc, &history, "bob").unwrap();
+
+    // Bob's edit clears his redo, not Alice's
+    type_char(&mut doc, &history, "bob", 'c', 3);
+    assert!(redo(&mut doc, &history, "bob").is_err());
+    redo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ac");
+
+    // Globally, any edit clears every redo
+    history.set_scope("doc1", UndoScope::Global);
+    undo(&mut doc, &history, "alice").unwrap();
+    type_char(&mut doc, &history, "bob", 'd', 4);
+    assert!(redo(&mut doc, &history, "alice").is_err());
+    assert_eq!(doc.content(), "cd");
+}
+
+#[test]
+fn test_undo_after_garbage_collection() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "alice", 'b', 2);
+    let delete = Operation::delete("alice".to_string(), Position::new(vec![1]));
+    doc.apply_operation(delete.clone()).unwrap();
+    history.record("doc1", "alice", &delete);
+    doc.collect_garbage();
+    assert_eq!(doc.character_count(), 1);
+
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+
+    // Redoing deletes the restored character, and undoing that again
+    // restores it once more after its tombstone is collected
+    redo(&mut doc, &history, "alice").unwrap();
+    doc.collect_garbage();
+    assert_eq!(doc.content(), "b");
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "a");
+}
+
+#[test]
+fn test_undo_range_after_garbage_collection_keeps_order() {
+    let mut doc = Document::new("doc1".to_string());
+    let history = UndoHistory::new();
+    type_char(&mut doc, &history, "alice", 'a', 1);
+    type_char(&mut doc, &history, "alice", 'b', 2);
+    type_char(&mut doc, &history, "alice", 'c', 3);
+    type_char(&mut doc, &history, "alice", 'z', 9);
+    let range = Operation::delete_range("alice".to_string(), &doc, Position::new(vec![1]), Position::new(vec![3]));
+    doc.apply_operation(range.clone()).unwrap();
+    history.record("doc1", "alice", &range);
+    doc.collect_garbage();
+    assert_eq!(doc.content(), "z");
+
+    // The run is placed at once, so its characters neither collide nor
+    // reorder around the gap the collected tombstones left
+    let restored = history.undo("doc1", &doc, "alice").unwrap();
+    assert_eq!(restored.len(), 3);
+    assert!(restored.windows(2).all(|pair| pair[0].position() < pair[1].position()));
+    for operation in restored {
+        doc.apply_operation(operation).unwrap();
+    }
+    assert_eq!(doc.content(), "abcz");
+
+    // Older inserts follow the restored characters
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "abc");
+    undo(&mut doc, &history, "alice").unwrap();
+    assert_eq!(doc.content(), "ab");
+}
@@ -0,0 +1,9 @@
+/*
+ * File: tests/undo/mod.rs
+ * Purpose: Test module organization for server-side undo
+ *
+ * Test modules:
+ * - history_tests: Tests for undo histories and scopes
+ */
+
+mod history_tests;
@@ -0,0 +1,162 @@
+/*
+ * File: tests/websocket/activity_tests.rs
+ * Purpose: Test suite for workspace activity feeds
+ *
+ * Test Categories:
+ * - Paging newest first, tenant separation
+ * - Heavy edits aggregated once per window
+ * - Bounded retention by count and age
+ * - Subscriptions and the HTTP feed
+ */
+
+use std::time::Duration;
+use chrono::Utc;
+use serde_json::{json, Value};
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    tenant::DEFAULT_TENANT,
+    websocket::{
+        message::SetSlugMessage, ActivityConfig, ActivityFeed, ActivityKind, ActivityPage, MessageType, ServerConfig,
+    },
+};
+
+fn ids(page: &ActivityPage) -> Vec<u64> {
+    page.activities.iter().map(|activity| activity.id).collect()
+}
+
+#[test]
+fn test_pages_newest_first() {
+    let feed = ActivityFeed::default();
+    let now = Utc::now();
+    feed.created("acme", "notes", 0, now);
+    feed.created("other", "todo", 0, now);
+    feed.renamed("acme", "notes", "class-notes", "alice", 4, now);
+    feed.created("acme", "draft", 2, now);
+
+    let first = feed.page("acme", None, 2, now, |_| true);
+    assert_eq!(ids(&first), vec![4, 3]);
+    assert_eq!(first.next, Some(3));
+    assert_eq!(first.activities[1].kind, ActivityKind::Renamed { slug: "class-notes".to_string(), by: "alice".to_string() });
+    let second = feed.page("acme", first.next, 2, now, |_| true);
+    assert_eq!(ids(&second), vec![1]);
+    assert_eq!(second.next, None);
+
+    assert_eq!(ids(&feed.page("other", None, 10, now, |_| true)), vec![2]);
+    assert!(feed.page("nobody", None, 10, now, |_| true).activities.is_empty());
+}
+
+#[test]
+fn test_heavy_edits_recorded_once_per_window() {
+    let feed = ActivityFeed::new(ActivityConfig {
+        heavy_edit_operations: 3,
+        heavy_edit_window: Duration::from_secs(60),
+        ..Default::default()
+    });
+    let start = Utc::now();
+    for version in 1..=10 {
+        feed.changed("acme", "notes", version, start);
+    }
+    let page = feed.page("acme", None, 10, start, |_| true);
+    assert_eq!(page.activities.len(), 1);
+    assert_eq!(page.activities[0].kind, ActivityKind::HeavilyEdited { operations: 3 });
+    assert_eq!(page.activities[0].version, 3);
+
+    // A new window starts counting again
+    let later = start + chrono::Duration::seconds(61);
+    for version in 11..=13 {
+        feed.changed("acme", "notes", version, later);
+    }
+    feed.changed("acme", "draft", 1, later);
+    assert_eq!(feed.page("acme", None, 10, later, |_| true).activities.len(), 2);
+}
+
+#[test]
+fn test_retention_bounds_the_feed() {
+    let feed = ActivityFeed::new(ActivityConfig {
+        capacity: 3,
+        retention: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
+    });
+    let start = Utc::now();
+    for i in 0..5 {
+        feed.created("acme", &format!("doc{}", i), 0, start);
+    }
+    assert_eq!(ids(&feed.page("acme", None, 10, start, |_| true)), vec![5, 4, 3]);
+
+    let tomorrow = start + chrono::Duration::hours(25);
+    feed.created("acme", "fresh", 0, tomorrow);
+    let page = feed.page("acme", None, 10, tomorrow, |_| true);
+    assert_eq!(ids(&page), vec![6]);
+    assert!(feed.page("acme", None, 10, tomorrow + chrono::Duration::hours(25), |_| true).activities.is_empty());
+}
+
+#[tokio::test]
+async fn test_subscribers_follow_activity() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        activity: ActivityConfig { heavy_edit_operations: 3, ..Default::default() },
+        ..Default::default()
+    });
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+    alice.create_document("notes", "").await;
+
+    alice.request(MessageType::SubscribeActivity, json!({})).await;
+    let page: ActivityPage = serde_json::from_value(alice.expect(MessageType::ActivityPage).await.payload().clone()).unwrap();
+    assert_eq!(page.activities.len(), 1);
+    assert_eq!((page.activities[0].kind.clone(), page.activities[0].document_id.as_str()), (ActivityKind::Created, "notes"));
+
+    bob.create_document("todo", "").await;
+    let activity = alice.expect(MessageType::Activity).await;
+    assert_eq!(activity.payload()["kind"], "created");
+    assert_eq!(activity.payload()["document_id"], "todo");
+
+    bob.request(MessageType::SetSlug, SetSlugMessage { document_id: "todo".to_string(), slug: "tasks".to_string() }).await;
+    bob.expect(MessageType::Ack).await;
+    let activity = alice.expect(MessageType::Activity).await;
+    assert_eq!(activity.payload()["kind"], "renamed");
+    assert_eq!(activity.payload()["slug"], "tasks");
+    assert_eq!(activity.payload()["by"], bob.id());
+
+    bob.type_text("notes", "abc").await;
+    let activity = alice.expect(MessageType::Activity).await;
+    assert_eq!(activity.payload()["kind"], "heavily_edited");
+    assert_eq!(activity.payload()["operations"], 3);
+
+    // Temporary documents stay out of the feed, and unsubscribing stops it
+    bob.request(MessageType::CreateDocument, json!({ "document_id": "scratch", "temporary": true })).await;
+    bob.expect(MessageType::DocumentCreated).await;
+    alice.request(MessageType::UnsubscribeActivity, json!({})).await;
+    bob.create_document("later", "").await;
+    while let Some(message) = alice.recv_within(Duration::from_millis(200)).await {
+        assert_ne!(message.message_type(), &MessageType::Activity, "{:?}", message.payload());
+    }
+    let page = server.server().activity(DEFAULT_TENANT, None, 10).unwrap();
+    let documents: Vec<&str> = page.activities.iter().map(|activity| activity.document_id.as_str()).collect();
+    assert_eq!(documents, vec!["later", "notes", "todo", "todo", "notes"]);
+}
+
+#[tokio::test]
+async fn test_http_feed() {
+    let server = TestServer::in_process();
+    let mut client = server.connect().await;
+    client.create_document("notes", "").await;
+    client.create_document("draft", "").await;
+    let routes = server.server().routes();
+
+    let response = warp::test::request().path("/activity?limit=1").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["activities"][0]["document_id"], "draft");
+    let next = body["next"].as_u64().unwrap();
+
+    let response = warp::test::request().path(&format!("/activity?before={}", next)).reply(&routes).await;
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["activities"][0]["document_id"], "notes");
+    assert_eq!(body["next"], Value::Null);
+
+    let response = warp::test::request().path("/activity?before=latest").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = warp::test::request().path("/t/nobody/activity").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
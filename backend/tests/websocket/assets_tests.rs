@@ -0,0 +1,114 @@
+/*
+ * File: tests/websocket/assets_tests.rs
+ * Purpose: Test suite for static asset serving
+ *
+ * Test Categories:
+ * - Serving files with content type and caching headers
+ * - SPA fallback to index.html
+ * - Missing assets and disabled serving
+ * - Path traversal
+ */
+
+use std::path::PathBuf;
+use warp::http::StatusCode;
+use crdt_editor_backend::websocket::{assets, StaticConfig};
+
+/// Create a directory holding a small frontend build
+fn frontend() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("coedit-assets-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("assets")).unwrap();
+    std::fs::write(root.join("index.html"), "<html>editor</html>").unwrap();
+    std::fs::write(root.join("assets/app.js"), "console.log('editor')").unwrap();
+    root
+}
+
+async fn get(config: &StaticConfig, path: &str) -> warp::http::Response<warp::hyper::body::Bytes> {
+    warp::test::request().path(path).reply(&assets::routes(config)).await
+}
+
+#[tokio::test]
+async fn test_serves_files_with_headers() {
+    let config = StaticConfig::directory(frontend());
+
+    let response = get(&config, "/assets/app.js").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().as_ref(), b"console.log('editor')");
+    assert_eq!(response.headers()["content-type"], "text/javascript");
+    assert_eq!(response.headers()["cache-control"], "public, max-age=3600");
+
+    // The root serves index.html, which is always revalidated
+    let response = get(&config, "/").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/html");
+    assert_eq!(response.headers()["cache-control"], "no-cache");
+}
+
+#[tokio::test]
+async fn test_spa_fallback() {
+    let config = StaticConfig::directory(frontend());
+
+    let response = get(&config, "/docs/meeting-notes").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().as_ref(), b"<html>editor</html>");
+    assert_eq!(response.headers()["cache-control"], "no-cache");
+
+    // Missing files are not papered over with the index
+    assert_eq!(get(&config, "/assets/missing.js").await.status(), StatusCode::NOT_FOUND);
+
+    let config = StaticConfig {
+        spa_fallback: false,
+        ..StaticConfig::directory(frontend())
+    };
+    assert_eq!(get(&config, "/docs/meeting-notes").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_custom_cache_control() {
+    let config = StaticConfig {
+        cache_control: "public, max-age=31536000, immutable".to_string(),
+        ..StaticConfig::directory(frontend())
+    };
+    let response = get(&config, "/assets/app.js").await;
+    assert_eq!(response.headers()["cache-control"], "public, max-age=31536000, immutable");
+}
+
+#[tokio::test]
+async fn test_disabled_by_default() {
+    let response = get(&StaticConfig::default(), "/").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_path_traversal_refused() {
+    let root = frontend();
+    std::fs::write(root.parent().unwrap().join("secret.txt"), "secret").unwrap();
+    let config = StaticConfig::directory(root.join("assets"));
+
+    for path in ["/../index.html", "/..%2Findex.html", "/assets/../../secret.txt"] {
+        let response = get(&config, path).await;
+        assert_ne!(response.body().as_ref(), b"<html>editor</html>", "{}", path);
+        assert_ne!(response.body().as_ref(), b"secret", "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_absolute_and_encoded_paths_refused() {
+    let root = frontend();
+    let secret = root.parent().unwrap().join(format!("secret-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&secret, "secret").unwrap();
+    let config = StaticConfig {
+        spa_fallback: false,
+        ..StaticConfig::directory(root.join("assets"))
+    };
+
+    let absolute = format!("/{}", secret.display());
+    let name = secret.file_name().unwrap().to_str().unwrap();
+    let encoded = format!("/%2e%2e/{}", name);
+    let encoded_slash = format!("/%2e%2e%2f{}", name);
+    for path in [absolute.as_str(), encoded.as_str(), encoded_slash.as_str(), "/./app.js", "//app.js"] {
+        let response = get(&config, path).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+    }
+    // Encoded names of files inside the directory are still served
+    assert_eq!(get(&config, "/app%2Ejs").await.status(), StatusCode::OK);
+}
@@ -0,0 +1,128 @@
+/*
+ * File: tests/websocket/compat_tests.rs
+ * Purpose: Test suite for forward compatibility with newer clients
+ *
+ * Test Categories:
+ * - Preserving unknown fields through decoding and rewritten payloads
+ * - Relaying a newer client's unknown fields to other clients
+ * - Rejecting messages that require unsupported features
+ * - Counting clients that use deprecated message types and features
+ */
+
+use serde_json::json;
+use crdt_editor_backend::{
+    crdt::{Operation, Position},
+    fixtures::TestServer,
+    metrics::DeprecatedUsage,
+    websocket::{
+        compat::{operation_payload, FEATURE_CURSORS, FEATURE_DELTA_SYNC, FEATURE_LATE_JOIN_TAIL, FEATURE_PRESENCE, FEATURE_RANGE_DELETE, FEATURE_REDO, FEATURE_UNDO},
+        message::OperationMessage,
+        check_features, CompatError, Deprecation, Message, MessageType, ServerConfig, SUPPORTED_FEATURES,
+    },
+};
+
+/// An operation as a newer client sends it, with fields this server
+/// doesn't know at every level
+fn newer_operation(client_id: &str, document_id: &str) -> Message {
+    let operation = Operation::insert(client_id.to_string(), 'x', Position::new(vec![1]));
+    let mut payload = serde_json::to_value(OperationMessage::new(operation, document_id.to_string())).unwrap();
+    payload["cursor_hint"] = json!(7);
+    payload["operation"]["Insert"]["style"] = json!({ "bold": true });
+    Message::decode(&json!({
+        "type": "operation",
+        "client_id": client_id,
+        "payload": payload,
+        "trace": "abc",
+    }).to_string()).unwrap()
+}
+
+#[test]
+fn test_unknown_fields_survive_decoding_and_rewrites() {
+    let message = newer_operation("alice", "notes");
+    assert_eq!(message.extensions().get("trace"), Some(&json!("abc")));
+    let encoded: serde_json::Value = serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+    assert_eq!(encoded["trace"], json!("abc"));
+    assert_eq!(encoded["payload"]["operation"]["Insert"]["style"], json!({ "bold": true }));
+
+    // The operation still parses, and a changed payload keeps the unknowns
+    let mut op_msg: OperationMessage = serde_json::from_value(message.payload().clone()).unwrap();
+    op_msg.document_id = "canonical".to_string();
+    let payload = operation_payload(message.payload(), &op_msg).unwrap();
+    assert_eq!(payload["document_id"], json!("canonical"));
+    assert_eq!(payload["cursor_hint"], json!(7));
+    assert_eq!(payload["operation"]["Insert"]["style"], json!({ "bold": true }));
+
+    // Fields of an operation that changed kind are dropped
+    op_msg.operation = Operation::delete("alice".to_string(), Position::new(vec![1]));
+    let payload = operation_payload(message.payload(), &op_msg).unwrap();
+    assert_eq!(payload["operation"].as_object().unwrap().len(), 1);
+    assert!(payload["operation"]["Delete"].get("style").is_none());
+    assert_eq!(payload["cursor_hint"], json!(7));
+}
+
+#[tokio::test]
+async fn test_newer_client_fields_are_relayed() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+    alice.create_document("notes", "").await;
+    bob.get_document("notes").await;
+
+    alice.send(&newer_operation(alice.id(), "notes")).await;
+    alice.expect(MessageType::Ack).await;
+    let relayed = bob.expect(MessageType::Operation).await;
+    assert_eq!(relayed.extensions().get("trace"), Some(&json!("abc")));
+    assert_eq!(relayed.payload()["cursor_hint"], json!(7));
+    assert_eq!(relayed.payload()["operation"]["Insert"]["style"], json!({ "bold": true }));
+    assert_eq!(bob.get_document("notes").await.content, "x");
+}
+
+#[tokio::test]
+async fn test_unsupported_features_are_rejected() {
+    assert_eq!(check_features(FEATURE_LATE_JOIN_TAIL | FEATURE_UNDO | FEATURE_RANGE_DELETE | FEATURE_REDO | FEATURE_DELTA_SYNC | FEATURE_PRESENCE | FEATURE_CURSORS), Ok(()));
+    let unknown = 1 << 40;
+    assert_eq!(check_features(SUPPORTED_FEATURES | unknown), Err(CompatError::UnsupportedFeatures(unknown)));
+
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "").await;
+
+    let message = newer_operation(alice.id(), "notes").with_requires(unknown | FEATURE_UNDO);
+    alice.send(&message).await;
+    let error = alice.expect(MessageType::Error).await;
+    assert!(error.payload().as_str().unwrap().contains("0x10000000000"));
+    assert_eq!(alice.get_document("notes").await.content, "");
+
+    alice.send(&newer_operation(alice.id(), "notes").with_requires(FEATURE_UNDO)).await;
+    alice.expect(MessageType::Ack).await;
+}
+
+#[tokio::test]
+async fn test_deprecated_features_are_counted() {
+    let connect = Deprecation::new("connect", "0.2.0", None);
+    let undo = Deprecation::new("0x10", "0.3.0", Some("history snapshots"));
+    assert!(connect.applies_to(&Message::new(MessageType::Connect, "alice".to_string(), json!({}))));
+    assert!(!connect.applies_to(&Message::new(MessageType::Connected, "alice".to_string(), json!({}))));
+    assert!(undo.applies_to(&newer_operation("alice", "notes").with_requires(FEATURE_UNDO | FEATURE_LATE_JOIN_TAIL)));
+    assert!(!undo.applies_to(&newer_operation("alice", "notes").with_requires(FEATURE_LATE_JOIN_TAIL)));
+
+    let server = TestServer::in_process_with_config(ServerConfig {
+        deprecations: vec![connect, undo],
+        ..Default::default()
+    });
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+    alice.create_document("notes", "").await;
+    alice.request(MessageType::Connect, json!({})).await;
+    alice.request(MessageType::Connect, json!({})).await;
+    bob.request(MessageType::Connect, json!({})).await;
+    alice.send(&newer_operation(alice.id(), "notes").with_requires(FEATURE_UNDO)).await;
+    alice.expect(MessageType::Ack).await;
+    bob.get_document("notes").await;
+
+    let usage = server.server().metrics().deprecated_usage;
+    assert_eq!(usage, vec![
+        DeprecatedUsage { feature: "0x10".to_string(), messages: 1, clients: 1 },
+        DeprecatedUsage { feature: "connect".to_string(), messages: 3, clients: 2 },
+    ]);
+}
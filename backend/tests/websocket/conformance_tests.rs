@@ -0,0 +1,60 @@
+/*
+ * File: tests/websocket/conformance_tests.rs
+ * Purpose: Run the protocol conformance suite against this server
+ * 
+ * Test Categories:
+ * - Required protocol checks over real WebSockets
+ * - Command line handling of the suite
+ */
+
+use std::{net::TcpListener, process::Command, time::Duration};
+use crdt_editor_backend::websocket::server::{EditorServer, ServerConfig};
+
+const CONFORMANCE: &str = env!("CARGO_BIN_EXE_conformance");
+
+/// Start a server on a free local port and wait until it accepts connections
+async fn start_server() -> u16 {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = EditorServer::new(ServerConfig {
+        port,
+        ..Default::default()
+    });
+    tokio::spawn(async move { server.run().await });
+
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return port;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on port {}", port);
+}
+
+#[tokio::test]
+async fn test_required_checks_pass() {
+    let port = start_server().await;
+    let url = format!("ws://127.0.0.1:{}/ws", port);
+
+    let output = tokio::task::spawn_blocking(move || {
+        Command::new(CONFORMANCE).args(["--json", "--timeout-ms", "1000", &url]).output().unwrap()
+    })
+    .await
+    .unwrap();
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let failed: Vec<&serde_json::Value> = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|check| check["level"] == "required" && check["passed"] != true)
+        .collect();
+    assert!(failed.is_empty(), "Required checks failed: {:?}", failed);
+    assert_eq!(report["passed"], true);
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_rejects_unknown_arguments() {
+    let output = Command::new(CONFORMANCE).arg("--bogus").output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -10,12 +10,17 @@
  * - Connection recovery
  */
 
-use std::time::Duration;
-use crdt_editor_backend::websocket::connection::{
-    ConnectionManager,
-    ConnectionStatus,
-    ClientInfo,
-    ConnectionError,
+use std::{sync::Arc, time::Duration};
+use crdt_editor_backend::websocket::{
+    clock::{Clock, ManualClock},
+    connection::{
+        ConnectionConfig,
+        ConnectionManager,
+        ConnectionStatus,
+        ClientInfo,
+        ConnectionError,
+        Session,
+    },
 };
 
 #[tokio::test]
@@ -62,19 +67,63 @@ async fn test_connection_closure() {
 
 #[tokio::test]
 async fn test_connection_timeout() {
-    let mut manager = ConnectionManager::new();
+    let clock = Arc::new(ManualClock::default());
+    let mut manager = ConnectionManager::with_config(ConnectionConfig {
+        connection_timeout: Duration::from_secs(3),
+    })
+    .with_clock(clock.clone());
     let client_id = "client1".to_string();
     
     manager.register_client(client_id.clone()).await.unwrap();
     
-    // Simulate no activity for longer than heartbeat interval
-    tokio::time::sleep(Duration::from_secs(5)).await;
+    // Simulate no activity for longer than the connection timeout
+    clock.advance(Duration::from_secs(5));
     
     // Check if the connection has timed out
     let status = manager.get_client_status(&client_id).await;
     assert_eq!(status, Some(ConnectionStatus::TimedOut));
 }
 
+#[tokio::test]
+async fn test_check_connection_timeout_with_manual_clock() {
+    let clock = Arc::new(ManualClock::default());
+    let mut manager = ConnectionManager::with_config(ConnectionConfig {
+        connection_timeout: Duration::from_secs(30),
+    })
+    .with_clock(clock.clone());
+    manager.register_client("client1".to_string()).await.unwrap();
+
+    clock.advance(Duration::from_secs(30));
+    assert!(!manager.check_connection_timeout("client1").await.unwrap());
+
+    // Activity resets the timeout
+    manager.update_heartbeat("client1").await.unwrap();
+    clock.advance(Duration::from_secs(31));
+    assert!(manager.check_connection_timeout("client1").await.unwrap());
+    assert_eq!(
+        manager.get_client_info("client1").await.unwrap().last_activity,
+        Some(clock.now() - chrono::Duration::seconds(31))
+    );
+}
+
+#[tokio::test]
+async fn test_default_timeout_policy() {
+    let clock = Arc::new(ManualClock::default());
+    let manager = ConnectionManager::new().with_clock(clock.clone());
+    let mut registrar = manager.clone();
+    registrar.register_client("client1".to_string()).await.unwrap();
+    registrar.register_client("client2".to_string()).await.unwrap();
+    registrar.disconnect_client("client2").await.unwrap();
+
+    clock.advance(ConnectionConfig::default().connection_timeout);
+    assert_eq!(manager.get_client_status("client1").await, Some(ConnectionStatus::Connected));
+
+    // Only connected clients time out; disconnected ones keep their status
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(manager.get_client_status("client1").await, Some(ConnectionStatus::TimedOut));
+    assert_eq!(manager.get_client_status("client2").await, Some(ConnectionStatus::Disconnected));
+}
+
 #[tokio::test]
 async fn test_connection_recovery() {
     let mut manager = ConnectionManager::new();
@@ -147,6 +196,65 @@ async fn test_connection_error_handling() {
     ));
 }
 
+#[test]
+fn test_session_transitions() {
+    let mut session = Session::new(ClientInfo {
+        id: "client1".to_string(),
+        ip: "127.0.0.1".to_string(),
+        connected_at: chrono::Utc::now(),
+        last_activity: None,
+    });
+    assert_eq!(session.status, ConnectionStatus::Connected);
+    assert!(matches!(session.recover(), Err(ConnectionError::InvalidState(_))));
+
+    session.time_out().unwrap();
+    assert!(matches!(session.time_out(), Err(ConnectionError::InvalidState(_))));
+    session.disconnect().unwrap();
+    assert!(matches!(session.disconnect(), Err(ConnectionError::InvalidState(_))));
+    assert!(matches!(session.time_out(), Err(ConnectionError::InvalidState(_))));
+
+    session.recover().unwrap();
+    assert_eq!(session.status, ConnectionStatus::Connected);
+}
+
+#[tokio::test]
+async fn test_duplicate_registration() {
+    let mut manager = ConnectionManager::new();
+    manager.register_client("client1".to_string()).await.unwrap();
+
+    let result = manager.register_client("client1".to_string()).await;
+    assert!(matches!(result, Err(ConnectionError::ClientExists(_))));
+}
+
+#[tokio::test]
+async fn test_concurrent_register_and_disconnect_stay_consistent() {
+    let manager = ConnectionManager::new();
+    let tasks: Vec<_> = (0..50)
+        .map(|i| {
+            let mut manager = manager.clone();
+            tokio::spawn(async move {
+                let client_id = format!("client{}", i);
+                manager.register_client(client_id.clone()).await.unwrap();
+                if i % 2 == 0 {
+                    manager.disconnect_client(&client_id).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let stats = manager.get_statistics().await;
+    assert_eq!(stats.total_clients, 50);
+    assert_eq!(stats.connected_clients, 25);
+    for i in 0..50 {
+        let session = manager.get_session(&format!("client{}", i)).await.unwrap();
+        let expected = if i % 2 == 0 { ConnectionStatus::Disconnected } else { ConnectionStatus::Connected };
+        assert_eq!(session.status, expected);
+    }
+}
+
 #[tokio::test]
 async fn test_connection_heartbeat() {
     let mut manager = ConnectionManager::new();
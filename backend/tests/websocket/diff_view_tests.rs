@@ -0,0 +1,67 @@
+/*
+ * File: tests/websocket/diff_view_tests.rs
+ * Purpose: Test suite for diff overlays streamed to clients
+ *
+ * Test Categories:
+ * - Annotations sent on request and after edits, until the view stops
+ * - Refusing revisions outside the history
+ */
+
+use std::time::Duration;
+use crdt_editor_backend::{
+    crdt::{DiffKind, Position},
+    fixtures::{TestClient, TestServer},
+    websocket::{message::{DiffAnnotationsMessage, DiffRequestMessage}, MessageType},
+};
+
+async fn annotations(client: &mut TestClient) -> DiffAnnotationsMessage {
+    let message = client.expect(MessageType::DiffAnnotations).await;
+    serde_json::from_value(message.payload().clone()).unwrap()
+}
+
+async fn end_of(server: &TestServer, document_id: &str) -> Position {
+    let doc = server.server().document("default", document_id).await.unwrap().unwrap();
+    let last = doc.visible_characters().last().map(|(_, position)| position.clone()).unwrap();
+    doc.positions_after(&last, 1).remove(0)
+}
+
+#[tokio::test]
+async fn test_annotations_follow_edits_until_stopped() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+    let created = alice.create_document("notes", "Hi").await;
+
+    bob.request(MessageType::DiffRequest, DiffRequestMessage { document_id: "notes".to_string(), version: created.version }).await;
+    let initial = annotations(&mut bob).await;
+    assert_eq!((initial.base_version, initial.version), (created.version, created.version));
+    assert!(initial.ranges.is_empty());
+
+    // Each edit brings a fresh overlay against the same revision
+    alice.insert("notes", '!', end_of(&server, "notes").await).await;
+    let update = annotations(&mut bob).await;
+    assert_eq!(update.version, created.version + 1);
+    assert_eq!(update.ranges.len(), 1);
+    assert_eq!((update.ranges[0].kind, update.ranges[0].offset, update.ranges[0].text.as_str()), (DiffKind::Inserted, 2, "!"));
+
+    bob.request(MessageType::DiffStop, serde_json::json!({})).await;
+    // Wait out the pacing of the view, so the stop has been handled
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    alice.insert("notes", '?', end_of(&server, "notes").await).await;
+    while let Some(message) = bob.recv_within(Duration::from_millis(500)).await {
+        assert_ne!(message.message_type(), &MessageType::DiffAnnotations);
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_revisions_refused() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "Hi").await;
+
+    alice.request(MessageType::DiffRequest, DiffRequestMessage { document_id: "notes".to_string(), version: 9 }).await;
+    let error = alice.expect(MessageType::Error).await;
+    assert_eq!(error.payload(), "Document notes has no revision 9; its history has 2 operations");
+    alice.request(MessageType::DiffRequest, DiffRequestMessage { document_id: "missing".to_string(), version: 0 }).await;
+    assert_eq!(alice.expect(MessageType::Error).await.payload(), "Document not found: missing");
+}
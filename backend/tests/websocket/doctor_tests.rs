@@ -0,0 +1,119 @@
+/*
+ * File: tests/websocket/doctor_tests.rs
+ * Purpose: Test suite for the configuration self-check
+ *
+ * Test Categories:
+ * - Sound configurations pass
+ * - Broken settings are reported with how to fix them
+ * - The server refuses to start when a check fails
+ */
+
+use std::{net::TcpListener, time::Duration};
+use crdt_editor_backend::{
+    metrics::{AbuseConfig, MemoryConfig, OverloadConfig},
+    storage::StorageConfig,
+    tenant::{PasswordConfig, TenantConfig},
+    websocket::{diagnose, AdminConfig, AssetSource, CheckLevel, EditorServer, ServerConfig, StandbyConfig, StaticConfig},
+};
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        port: 0,
+        ..Default::default()
+    }
+}
+
+fn level(config: &ServerConfig, name: &str) -> CheckLevel {
+    diagnose(config).checks.iter().find(|check| check.name == name).unwrap().level
+}
+
+#[test]
+fn test_default_config_passes() {
+    let report = diagnose(&config());
+    assert!(!report.has_errors(), "{}", report);
+    assert_eq!(report.at(CheckLevel::Warning).count(), 0);
+}
+
+#[test]
+fn test_reports_broken_settings() {
+    let slow_heartbeat = ServerConfig {
+        heartbeat_interval: Duration::from_secs(60),
+        connection_timeout: Duration::from_secs(30),
+        ..config()
+    };
+    assert_eq!(level(&slow_heartbeat, "timeouts"), CheckLevel::Error);
+    assert!(diagnose(&slow_heartbeat).to_string().contains("must be shorter than the connection timeout"));
+
+    let hostname = ServerConfig { host: "localhost".to_string(), ..config() };
+    assert_eq!(level(&hostname, "address"), CheckLevel::Error);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let busy = ServerConfig { port: listener.local_addr().unwrap().port(), ..config() };
+    assert_eq!(level(&busy, "address"), CheckLevel::Error);
+
+    let tenants = ServerConfig {
+        tenants: vec![TenantConfig { id: "bad id".to_string(), ..Default::default() }],
+        ..config()
+    };
+    assert_eq!(level(&tenants, "tenants"), CheckLevel::Error);
+
+    let admin = ServerConfig { admin: AdminConfig { token: Some(" ".to_string()) }, ..config() };
+    assert_eq!(level(&admin, "admin"), CheckLevel::Error);
+
+    let assets = ServerConfig {
+        assets: StaticConfig {
+            source: Some(AssetSource::Directory("/nonexistent/coedit".into())),
+            ..Default::default()
+        },
+        ..config()
+    };
+    assert_eq!(level(&assets, "assets"), CheckLevel::Warning);
+    assert!(!diagnose(&assets).has_errors());
+
+    let overload = ServerConfig {
+        overload: OverloadConfig { budget: Some(Duration::ZERO), ..Default::default() },
+        ..config()
+    };
+    assert_eq!(level(&overload, "overload"), CheckLevel::Error);
+
+    let abuse = ServerConfig {
+        abuse: AbuseConfig { max_operations_per_second: Some(100), window: Duration::ZERO, ..Default::default() },
+        ..config()
+    };
+    assert_eq!(level(&abuse, "abuse"), CheckLevel::Error);
+    let abuse = ServerConfig {
+        abuse: AbuseConfig { restrict_for: Some(Duration::from_secs(60)), ..Default::default() },
+        ..config()
+    };
+    assert_eq!(level(&abuse, "abuse"), CheckLevel::Warning);
+
+    let passwords = ServerConfig { passwords: PasswordConfig { iterations: 0, ..Default::default() }, ..config() };
+    assert_eq!(level(&passwords, "passwords"), CheckLevel::Error);
+    let passwords = ServerConfig { passwords: PasswordConfig { max_failures: 1, ..Default::default() }, ..config() };
+    assert_eq!(level(&passwords, "passwords"), CheckLevel::Error);
+    let passwords = ServerConfig { passwords: PasswordConfig { memory_kib: 1024, ..Default::default() }, ..config() };
+    assert_eq!(level(&passwords, "passwords"), CheckLevel::Warning);
+
+    let memory = ServerConfig { memory: MemoryConfig { interval: Duration::ZERO, ..Default::default() }, ..config() };
+    assert_eq!(level(&memory, "memory"), CheckLevel::Error);
+    let memory = ServerConfig { memory: MemoryConfig { samples: 0, ..Default::default() }, ..config() };
+    assert_eq!(level(&memory, "memory"), CheckLevel::Warning);
+    let storage = ServerConfig { storage: StorageConfig { snapshot_interval: Duration::ZERO, ..Default::default() }, ..config() };
+    assert_eq!(level(&storage, "storage"), CheckLevel::Warning);
+
+    let standby = ServerConfig { standby: Some(StandbyConfig::new("primary:8080", "secret")), ..config() };
+    assert_eq!(level(&standby, "standby"), CheckLevel::Error);
+    let standby = ServerConfig { standby: Some(StandbyConfig::new("http://primary:8080", "secret")), ..config() };
+    assert_eq!(level(&standby, "standby"), CheckLevel::Warning);
+}
+
+#[tokio::test]
+async fn test_server_refuses_to_start_when_a_check_fails() {
+    let server = EditorServer::new(ServerConfig {
+        heartbeat_interval: Duration::from_secs(60),
+        connection_timeout: Duration::from_secs(30),
+        ..config()
+    });
+    let error = tokio::time::timeout(Duration::from_secs(5), server.run()).await.unwrap().unwrap_err();
+    assert!(error.to_string().contains("timeouts"));
+}
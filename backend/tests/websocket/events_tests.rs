@@ -0,0 +1,90 @@
+/*
+ * File: tests/websocket/events_tests.rs
+ * Purpose: Test suite for the document lifecycle event log
+ *
+ * Test Categories:
+ * - Coalescing of consecutive changes
+ * - Tenant filtering and paging with cursors
+ * - Expired cursors
+ * - Long polling
+ */
+
+use std::{sync::Arc, time::Duration};
+use crdt_editor_backend::websocket::{
+    events::{EventError, EVENT_LOG_CAPACITY},
+    DocumentEventKind, EventLog,
+};
+
+#[test]
+fn test_consecutive_changes_coalesced() {
+    let log = EventLog::new();
+    log.record("default", "doc1", DocumentEventKind::Created, 1);
+    log.record("default", "doc1", DocumentEventKind::Changed, 2);
+    log.record("default", "doc1", DocumentEventKind::Changed, 3);
+
+    let page = log.since("default", None, 10, |_| true).unwrap();
+    let summary: Vec<_> = page.events.iter().map(|e| (e.kind, e.version, e.cursor)).collect();
+    assert_eq!(summary, vec![(DocumentEventKind::Created, 1, 1), (DocumentEventKind::Changed, 3, 3)]);
+    assert_eq!(page.next, 3);
+
+    // A follower that saw the change at cursor 2 sees the newer version again
+    let page = log.since("default", Some(2), 10, |_| true).unwrap();
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].version, 3);
+
+    log.record("default", "doc2", DocumentEventKind::Changed, 1);
+    log.record("default", "doc1", DocumentEventKind::Changed, 4);
+    assert_eq!(log.since("default", Some(3), 10, |_| true).unwrap().events.len(), 2);
+}
+
+#[test]
+fn test_tenant_filtering_and_paging() {
+    let log = EventLog::new();
+    log.record("default", "a", DocumentEventKind::Created, 1);
+    log.record("acme", "b", DocumentEventKind::Created, 1);
+    log.record("default", "c", DocumentEventKind::Created, 1);
+    log.record("acme", "d", DocumentEventKind::Created, 1);
+
+    let first = log.since("default", None, 1, |_| true).unwrap();
+    assert_eq!(first.events[0].document_id, "a");
+    assert_eq!(first.next, 1);
+    let second = log.since("default", Some(first.next), 1, |_| true).unwrap();
+    assert_eq!(second.events[0].document_id, "c");
+
+    // A short page has seen everything up to the newest event
+    let rest = log.since("default", Some(second.next), 1, |_| true).unwrap();
+    assert!(rest.events.is_empty());
+    assert_eq!(rest.next, log.cursor());
+}
+
+#[test]
+fn test_expired_cursors_rejected() {
+    let log = EventLog::new();
+    assert_eq!(log.since("default", Some(5), 10, |_| true), Err(EventError::Expired(5)));
+
+    for i in 0..=EVENT_LOG_CAPACITY {
+        log.record("default", &format!("doc{}", i), DocumentEventKind::Created, 1);
+    }
+    assert_eq!(log.since("default", Some(0), 10, |_| true), Err(EventError::Expired(0)));
+    assert_eq!(log.since("default", Some(1), 10, |_| true).unwrap().events[0].cursor, 2);
+    assert_eq!(log.since("default", None, 10, |_| true).unwrap().events[0].cursor, 2);
+}
+
+#[tokio::test]
+async fn test_polls_wait_for_events() {
+    let log = Arc::new(EventLog::new());
+    let page = log.wait_since("default", Some(0), 10, Duration::from_millis(20), |_| true).await.unwrap();
+    assert!(page.events.is_empty());
+
+    let poll = tokio::spawn({
+        let log = log.clone();
+        async move { log.wait_since("default", Some(0), 10, Duration::from_secs(5), |_| true).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    log.record("acme", "other", DocumentEventKind::Created, 1);
+    log.record("default", "doc1", DocumentEventKind::Created, 1);
+
+    let page = tokio::time::timeout(Duration::from_secs(1), poll).await.unwrap().unwrap().unwrap();
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].document_id, "doc1");
+}
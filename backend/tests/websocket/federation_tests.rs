@@ -0,0 +1,159 @@
+/*
+ * File: tests/websocket/federation_tests.rs
+ * Purpose: Test suite for mirroring documents between servers
+ *
+ * Test Categories:
+ * - Reconciling diverged copies on connect
+ * - Live mirroring in both directions
+ * - Stopping a sync link
+ */
+
+use std::{net::TcpListener, sync::Arc, time::Duration};
+use futures::{SinkExt, StreamExt};
+use tokio_tungstenite::{connect_async, tungstenite::Message as Frame};
+use crdt_editor_backend::{
+    crdt::{Operation, Position, Timestamp},
+    websocket::{
+        message::{Message, MessageType, OperationMessage},
+        EditorServer, ServerConfig, SyncLink,
+    },
+};
+
+type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Start a server on a free local port and wait until it accepts connections
+async fn start_server() -> (Arc<EditorServer>, String) {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = Arc::new(EditorServer::new(ServerConfig {
+        port,
+        ..Default::default()
+    }));
+    tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    (server, format!("ws://127.0.0.1:{}/ws", port))
+}
+
+/// Connect an editor and return its socket and client ID
+async fn connect(url: &str) -> (Socket, String) {
+    let (mut socket, _) = connect_async(url).await.unwrap();
+    let welcome = socket.next().await.unwrap().unwrap();
+    let welcome: serde_json::Value = serde_json::from_str(welcome.to_text().unwrap()).unwrap();
+    let client_id = welcome["payload"]["client_id"].as_str().unwrap().to_string();
+    (socket, client_id)
+}
+
+/// Send an insert and wait for its ack
+async fn insert(socket: &mut Socket, client_id: &str, document_id: &str, character: char, path: u32) {
+    let operation = Operation::Insert {
+        client_id: client_id.to_string(),
+        character,
+        position: Position::new(vec![path]),
+        timestamp: Timestamp::new(client_id.to_string()),
+    };
+    let payload = serde_json::to_value(OperationMessage::new(operation, document_id.to_string())).unwrap();
+    let message = Message::new(MessageType::Operation, client_id.to_string(), payload);
+    socket.send(Frame::Text(serde_json::to_string(&message).unwrap())).await.unwrap();
+
+    loop {
+        let frame = socket.next().await.unwrap().unwrap();
+        let reply: Message = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+        if reply.message_type() == &MessageType::Ack {
+            return;
+        }
+    }
+}
+
+/// Wait until a server's copy of a document has the expected content
+async fn wait_for_content(server: &EditorServer, document_id: &str, expected: &str) {
+    for _ in 0..100 {
+        let doc = server.document("default", document_id).await.unwrap();
+        if doc.as_ref().map(|doc| doc.content()).as_deref() == Some(expected) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let doc = server.document("default", document_id).await.unwrap();
+    panic!("Expected {:?}, got {:?}", expected, doc.map(|doc| doc.content()));
+}
+
+#[tokio::test]
+async fn test_diverged_copies_reconciled_on_connect() {
+    let (home, home_url) = start_server().await;
+    let (team, team_url) = start_server().await;
+
+    let (mut home_editor, home_id) = connect(&home_url).await;
+    let (mut team_editor, team_id) = connect(&team_url).await;
+    insert(&mut home_editor, &home_id, "notes", 'a', 1 << 24).await;
+    insert(&mut team_editor, &team_id, "notes", 'b', 2 << 24).await;
+    insert(&mut team_editor, &team_id, "notes", 'c', 3 << 24).await;
+
+    let _link = home.sync_document(SyncLink::new(team_url.as_str(), "notes")).unwrap();
+
+    wait_for_content(&home, "notes", "abc").await;
+    wait_for_content(&team, "notes", "abc").await;
+    let home_doc = home.document("default", "notes").await.unwrap().unwrap();
+    let team_doc = team.document("default", "notes").await.unwrap().unwrap();
+    assert_eq!(home_doc.checksum(), team_doc.checksum());
+}
+
+#[tokio::test]
+async fn test_live_edits_mirrored_both_ways() {
+    let (home, home_url) = start_server().await;
+    let (team, team_url) = start_server().await;
+    let _link = home.sync_document(SyncLink::new(team_url.as_str(), "notes")).unwrap();
+    // Wait for the link before editing, so the edits are live ones
+    wait_for_link(&team).await;
+
+    let (mut home_editor, home_id) = connect(&home_url).await;
+    let (mut team_editor, team_id) = connect(&team_url).await;
+
+    insert(&mut home_editor, &home_id, "notes", 'x', 1 << 24).await;
+    wait_for_content(&team, "notes", "x").await;
+
+    insert(&mut team_editor, &team_id, "notes", 'y', 2 << 24).await;
+    wait_for_content(&home, "notes", "xy").await;
+    wait_for_content(&team, "notes", "xy").await;
+}
+
+#[tokio::test]
+async fn test_stopped_link_stops_mirroring() {
+    let (home, home_url) = start_server().await;
+    let (team, team_url) = start_server().await;
+    let link = home.sync_document(SyncLink::new(team_url.as_str(), "notes")).unwrap();
+    wait_for_link(&team).await;
+
+    link.stop();
+    for _ in 0..100 {
+        if link.is_finished() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(link.is_finished());
+
+    let (mut home_editor, home_id) = connect(&home_url).await;
+    insert(&mut home_editor, &home_id, "notes", 'x', 1 << 24).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(team.document("default", "notes").await.unwrap().is_none());
+}
+
+/// Wait until a sync link is the only client of a server and has reconciled
+async fn wait_for_link(server: &EditorServer) {
+    for _ in 0..100 {
+        if server.client_count() == 1 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Sync link did not connect");
+}
@@ -0,0 +1,96 @@
+/*
+ * File: tests/websocket/import_tests.rs
+ * Purpose: Test suite for importing text and Markdown over HTTP
+ *
+ * Test Categories:
+ * - Appending a Markdown source with its checklists, relayed to clients
+ * - Refusing bad formats, sources and oversized imports
+ */
+
+use std::time::Duration;
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    blocks::Construct,
+    fixtures::TestServer,
+    tenant::TenantConfig,
+    websocket::{ImportReport, MessageType, QuotaConfig, ServerConfig, IMPORT_CLIENT_ID},
+};
+
+fn server() -> TestServer {
+    let tenant = |id: &str, quota: Option<usize>| TenantConfig {
+        id: id.to_string(),
+        quota: quota.map(|limit| QuotaConfig { max_document_characters: Some(limit), ..Default::default() }),
+        ..Default::default()
+    };
+    TestServer::in_process_with_config(ServerConfig {
+        tenants: vec![tenant("acme", None), tenant("tiny", Some(10))],
+        ..Default::default()
+    })
+}
+
+#[tokio::test]
+async fn test_markdown_import_reaches_clients() {
+    let server = server();
+    let mut alice = server.connect_to("acme", None).await;
+    alice.create_document("notes", "Intro").await;
+
+    let routes = server.server().routes();
+    let response = warp::test::request()
+        .method("POST")
+        .path("/t/acme/documents/notes/import?format=markdown")
+        .body("- [x] ship\n\n| a |\n|---|")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: ImportReport = serde_json::from_slice(response.body()).unwrap();
+    let content = "Intro\n```checklist tasks-1\n```\n\n| a |\n|---|";
+    assert_eq!((report.document_id.as_str(), report.inserted), ("notes", content.chars().count() - "Intro".len()));
+    assert_eq!(report.checklists, ["tasks-1"]);
+    assert_eq!(report.unmapped.iter().map(|unmapped| (unmapped.line, unmapped.construct)).collect::<Vec<_>>(), [(3, Construct::Table)]);
+
+    let document = server.server().document("acme", "notes").await.unwrap().unwrap();
+    assert_eq!(document.content(), content);
+    assert_eq!(report.version, document.version());
+
+    // Connected clients get the text and the checklist like other edits
+    let mut relayed = String::new();
+    let mut checklist_operations = 0;
+    while let Some(message) = alice.recv_within(Duration::from_millis(200)).await {
+        match message.message_type() {
+            MessageType::Operation => {
+                assert_eq!(message.payload()["source"], "import");
+                relayed.extend(message.payload()["operation"]["Insert"]["character"].as_str());
+            }
+            MessageType::ChecklistOperation => {
+                assert_eq!(message.client_id(), IMPORT_CLIENT_ID);
+                checklist_operations += 1;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(relayed.len(), report.inserted);
+    assert_eq!(checklist_operations, 2);
+}
+
+#[tokio::test]
+async fn test_rejected_imports() {
+    let server = server();
+    let routes = server.server().routes();
+    let post = |path: &str, body: &'static [u8]| warp::test::request().method("POST").path(path).body(body);
+
+    let response = post("/t/acme/documents/notes/import?format=docx", b"text").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = post("/t/acme/documents/notes/import", b"\xff\xfe").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = post("/t/nowhere/documents/notes/import", b"text").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = post("/t/tiny/documents/notes/import", b"more than ten characters").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(server.server().document("tiny", "notes").await.unwrap().is_none());
+
+    // Plain text goes in verbatim
+    let response = post("/t/tiny/documents/notes/import?format=text", b"- [ ] a").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let document = server.server().document("tiny", "notes").await.unwrap().unwrap();
+    assert_eq!(document.content(), "- [ ] a");
+}
@@ -8,10 +8,18 @@
  * - Operation message handling
  * - Connection status messages
  * - Error message handling
+ * - Playback request validation
+ * - Request ID propagation
+ * - Document state versions
+ * - Repair request validation
+ * - Malformed client frames
  */
 
-use crdt_editor_backend::websocket::message::{Message, MessageType, OperationMessage, StatusMessage};
-use crdt_editor_backend::crdt::{Operation, Position};
+use crdt_editor_backend::websocket::message::{
+    DocumentStateMessage, Message, MessageType, OperationMessage, PlaybackRequestMessage, RepairRequestMessage,
+    StatusMessage,
+};
+use crdt_editor_backend::crdt::{Document, Operation, OperationSource, Position};
 
 #[test]
 fn test_message_creation() {
@@ -26,6 +34,7 @@ fn test_message_creation() {
                 Position::start(),
             ),
             document_id: "doc1".to_string(),
+            source: OperationSource::User,
         }).unwrap(),
     );
     
@@ -44,6 +53,7 @@ fn test_operation_message_serialization() {
     let msg = OperationMessage {
         operation,
         document_id: "doc1".to_string(),
+        source: OperationSource::User,
     };
     
     let serialized = serde_json::to_string(&msg).unwrap();
@@ -95,7 +105,136 @@ fn test_message_validation() {
     let msg = OperationMessage {
         operation,
         document_id: "".to_string(), // Invalid empty document ID
+        source: OperationSource::User,
     };
     
     assert!(msg.validate().is_err());
 }
+
+#[test]
+fn test_playback_request_validation() {
+    let request = PlaybackRequestMessage::new("doc1".to_string(), Some(10.0));
+    assert!(request.validate().is_ok());
+    assert!(PlaybackRequestMessage::step("doc1".to_string(), 3).validate().is_ok());
+
+    let too_fast = PlaybackRequestMessage::new("doc1".to_string(), Some(1e9));
+    assert!(too_fast.validate().is_err());
+
+    let stopped = PlaybackRequestMessage::new("doc1".to_string(), Some(0.0));
+    assert!(stopped.validate().is_err());
+
+    // Optional fields default when omitted by the client
+    let parsed: PlaybackRequestMessage = serde_json::from_str(r#"{"document_id":"doc1"}"#).unwrap();
+    assert_eq!(parsed.from, 0);
+    assert!(parsed.count.is_none());
+    assert!(parsed.ops_per_second.is_none());
+}
+
+#[test]
+fn test_request_id_round_trip() {
+    let message = Message::new(
+        MessageType::Operation,
+        "client1".to_string(),
+        serde_json::json!({}),
+    ).with_request_id(Some("req-42".to_string()));
+
+    let serialized = serde_json::to_string(&message).unwrap();
+    let deserialized: Message = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized.request_id(), Some("req-42"));
+
+    // Messages without a request ID keep their original wire format
+    let plain = Message::error("client1".to_string(), "oops".to_string());
+    assert!(!serde_json::to_string(&plain).unwrap().contains("request_id"));
+    let parsed: Message = serde_json::from_str(r#"{"type":"status","client_id":"c","payload":null}"#).unwrap();
+    assert_eq!(parsed.request_id(), None);
+}
+
+#[test]
+fn test_replies_echo_request_id() {
+    let request = Message::new(
+        MessageType::Operation,
+        "client1".to_string(),
+        serde_json::json!({}),
+    ).with_request_id(Some("req-7".to_string()));
+
+    let error = request.error_reply("client1".to_string(), "Invalid operation".to_string());
+    assert_eq!(error.message_type(), &MessageType::Error);
+    assert_eq!(error.request_id(), Some("req-7"));
+
+    let ack = request.ack("client1".to_string(), serde_json::json!({ "document_id": "doc1" }));
+    assert_eq!(ack.message_type(), &MessageType::Ack);
+    assert_eq!(ack.request_id(), Some("req-7"));
+}
+
+#[test]
+fn test_document_state_carries_version() {
+    let mut doc = Document::new("doc1".to_string());
+    doc.apply(Operation::insert("client1".to_string(), 'A', Position::new(vec![1])));
+
+    let state = DocumentStateMessage::new("doc1".to_string(), &doc);
+    assert_eq!(state.version, 1);
+
+    let serialized = serde_json::to_value(&state).unwrap();
+    assert_eq!(serialized["version"], 1);
+    assert_eq!(serialized["content"], "A");
+}
+
+#[test]
+fn test_document_state_carries_checksum() {
+    let mut doc = Document::new("doc1".to_string());
+    doc.apply(Operation::insert("client1".to_string(), 'A', Position::new(vec![1])));
+
+    let state = DocumentStateMessage::new("doc1".to_string(), &doc);
+    assert_eq!(state.checksum, doc.checksum());
+
+    let serialized = serde_json::to_value(&state).unwrap();
+    assert_eq!(serialized["checksum"], doc.checksum());
+}
+
+#[test]
+fn test_repair_request_validation() {
+    let doc = Document::new("doc1".to_string());
+    let request = RepairRequestMessage::new("doc1".to_string(), &doc);
+    assert!(request.validate().is_ok());
+
+    let missing_regions = RepairRequestMessage {
+        regions: vec![0; 3],
+        ..request.clone()
+    };
+    assert!(missing_regions.validate().is_err());
+
+    let no_document = RepairRequestMessage {
+        document_id: String::new(),
+        ..request
+    };
+    assert!(no_document.validate().is_err());
+}
+
+#[test]
+fn test_malformed_frames_rejected() {
+    let deep = format!(r#"{{"type":"operation","client_id":"c","payload":{}{}}}"#, "[".repeat(10_000), "]".repeat(10_000));
+    for frame in [
+        "",
+        "{",
+        "null",
+        r#"{"type":"operation"}"#,
+        r#"{"type":"noSuchType","client_id":"c","payload":{}}"#,
+        r#"{"type":"ack","client_id":7,"payload":{}}"#,
+        deep.as_str(),
+    ] {
+        assert!(Message::decode(frame).is_err(), "{:?} decoded", frame);
+    }
+
+    // Well-formed envelopes whose payloads don't fit their type
+    for frame in [
+        r#"{"type":"operation","client_id":"c","payload":{"operation":{"Insert":{}}}}"#,
+        r#"{"type":"playbackRequest","client_id":"c","payload":{"document_id":"d","ops_per_second":1e308}}"#,
+        r#"{"type":"repairRequest","client_id":"c","payload":{"document_id":"d","regions":[]}}"#,
+        r#"{"type":"setSlug","client_id":"c","payload":[1,2,3]}"#,
+    ] {
+        let message = Message::decode(frame).unwrap();
+        assert!(message.check_request().is_err(), "{:?} passed", frame);
+    }
+    let status = Message::decode(r#"{"type":"status","client_id":"c","payload":{"anything":[]}}"#).unwrap();
+    assert_eq!(status.check_request(), Ok(()));
+}
@@ -3,11 +3,53 @@
  * Purpose: Test module organization for WebSocket implementation
  * 
  * Test modules:
+ * - activity_tests: Tests for workspace activity feeds
+ * - assets_tests: Tests for static asset serving
+ * - compat_tests: Tests for forward compatibility with newer clients
+ * - conformance_tests: Protocol conformance suite run against this server
  * - connection_tests: Tests for WebSocket connection handling
+ * - diff_view_tests: Tests for diff overlays streamed to clients
+ * - doctor_tests: Tests for the configuration self-check
+ * - events_tests: Tests for the document lifecycle event log
+ * - federation_tests: Tests for mirroring documents between servers
+ * - import_tests: Tests for importing text and Markdown over HTTP
  * - message_tests: Tests for WebSocket message serialization
+ * - previews_tests: Tests for previews of recently active documents
+ * - quota_tests: Tests for document quotas and webhooks
+ * - replication_tests: Tests for warm standby replication
+ * - routes_tests: Tests for mounting the server's routes in another application
+ * - seed_tests: Tests for system documents seeded on first boot
+ * - serve_tests: Tests for the self-contained coedit binary
  * - server_tests: Tests for WebSocket server functionality
+ * - skew_tests: Tests for timestamp skew detection
+ * - subscription_tests: Tests for the per-document subscriber index
+ * - tail_tests: Tests for the late-join fast path
+ * - transfer_tests: Tests for moving documents between tenants
+ * - transport_tests: Tests for transports and in-process connections
+ * - validation_tests: Tests for outbound message schema validation
  */
 
+mod activity_tests;
+mod assets_tests;
+mod compat_tests;
+mod conformance_tests;
 mod connection_tests;
+mod diff_view_tests;
+mod doctor_tests;
+mod events_tests;
+mod federation_tests;
+mod import_tests;
 mod message_tests;
+mod previews_tests;
+mod quota_tests;
+mod replication_tests;
+mod routes_tests;
+mod seed_tests;
+mod serve_tests;
 mod server_tests;
+mod skew_tests;
+mod subscription_tests;
+mod tail_tests;
+mod transfer_tests;
+mod transport_tests;
+mod validation_tests;
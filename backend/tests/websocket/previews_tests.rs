@@ -0,0 +1,88 @@
+/*
+ * File: tests/websocket/previews_tests.rs
+ * Purpose: Test suite for previews of recently active documents
+ *
+ * Test Categories:
+ * - Recency order, tenant separation and eviction
+ * - Previews refreshed after changes, temporary documents left out
+ * - HTTP listing
+ */
+
+use serde_json::{json, Value};
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    tenant::DEFAULT_TENANT,
+    websocket::{previews::PREVIEW_CACHE_CAPACITY, MessageType, PreviewCache},
+};
+
+#[test]
+fn test_recent_documents_first() {
+    let cache = PreviewCache::new();
+    cache.touch("acme", "a", 1);
+    cache.touch("acme", "b", 1);
+    cache.touch("other", "c", 1);
+    cache.touch("acme", "a", 2);
+
+    let recent: Vec<(String, u64)> = cache.recent("acme", 10, |_| true).into_iter().map(|p| (p.document_id, p.version)).collect();
+    assert_eq!(recent, vec![("a".to_string(), 2), ("b".to_string(), 1)]);
+    assert_eq!(cache.recent("acme", 1, |_| true).len(), 1);
+
+    // Previews are stale until filled at the latest version
+    assert_eq!(cache.stale("acme", 10, |_| true), vec!["a", "b"]);
+    cache.fill("acme", "a", 2, "Hello".to_string());
+    assert_eq!(cache.stale("acme", 10, |_| true), vec!["b"]);
+    assert_eq!(cache.recent("acme", 1, |_| true)[0].preview, "Hello");
+    cache.touch("acme", "a", 3);
+    assert_eq!(cache.stale("acme", 10, |_| true), vec!["a", "b"]);
+    assert_eq!(cache.recent("acme", 1, |_| true)[0].preview, "Hello");
+}
+
+#[test]
+fn test_oldest_documents_evicted() {
+    let cache = PreviewCache::new();
+    for i in 0..=PREVIEW_CACHE_CAPACITY {
+        cache.touch("acme", &format!("doc{}", i), 1);
+    }
+    let recent = cache.recent("acme", PREVIEW_CACHE_CAPACITY + 1, |_| true);
+    assert_eq!(recent.len(), PREVIEW_CACHE_CAPACITY);
+    assert!(recent.iter().all(|preview| preview.document_id != "doc0"));
+}
+
+#[tokio::test]
+async fn test_previews_follow_changes() {
+    let server = TestServer::in_process();
+    let mut client = server.connect().await;
+    client.create_document("notes", "Hello").await;
+    client.create_document("draft", "").await;
+    client.request(MessageType::CreateDocument, json!({ "document_id": "scratch", "temporary": true })).await;
+    client.expect(MessageType::DocumentCreated).await;
+
+    let recent = server.server().recent_documents(DEFAULT_TENANT, 10).await.unwrap();
+    let ids: Vec<&str> = recent.iter().map(|p| p.document_id.as_str()).collect();
+    assert_eq!(ids, vec!["draft", "notes"]);
+    assert_eq!(recent[1].preview, "Hello");
+    assert_eq!(recent[1].version, 5);
+
+    let version = client.type_text("draft", "Hi").await;
+    let recent = server.server().recent_documents(DEFAULT_TENANT, 1).await.unwrap();
+    assert_eq!((recent[0].document_id.as_str(), recent[0].preview.as_str(), recent[0].version), ("draft", "Hi", version));
+}
+
+#[tokio::test]
+async fn test_http_listing() {
+    let server = TestServer::in_process();
+    server.connect().await.create_document("notes", "Hello").await;
+    let routes = server.server().routes();
+
+    let response = warp::test::request().path("/documents?limit=5").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["documents"][0]["document_id"], "notes");
+    assert_eq!(body["documents"][0]["preview"], "Hello");
+
+    let response = warp::test::request().path("/documents?limit=0").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = warp::test::request().path("/t/nobody/documents").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
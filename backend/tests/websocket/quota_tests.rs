@@ -0,0 +1,100 @@
+/*
+ * File: tests/websocket/quota_tests.rs
+ * Purpose: Test suite for document quotas and soft-limit warnings
+ * 
+ * Test Categories:
+ * - Hard limit enforcement
+ * - Warning threshold crossing
+ * - Hysteresis between warnings
+ * - Webhook delivery
+ */
+
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+use crdt_editor_backend::websocket::{
+    quota::{QuotaConfig, QuotaTracker},
+    webhook::{WebhookEvent, WebhookNotifier},
+};
+
+fn tracker(limit: usize) -> QuotaTracker {
+    QuotaTracker::new(QuotaConfig {
+        max_document_characters: Some(limit),
+        warning_ratio: 0.8,
+        hysteresis_ratio: 0.1,
+        webhook_url: None,
+    })
+}
+
+#[test]
+fn test_unlimited_by_default() {
+    let quota = QuotaTracker::new(QuotaConfig::default());
+    assert!(quota.allows_insert(usize::MAX - 1));
+    assert!(quota.observe("doc1", 1_000_000).is_none());
+}
+
+#[test]
+fn test_hard_limit() {
+    let quota = tracker(100);
+    assert!(quota.allows_insert(99));
+    assert!(!quota.allows_insert(100));
+}
+
+#[test]
+fn test_warning_on_threshold_crossing() {
+    let quota = tracker(100);
+    assert!(quota.observe("doc1", 79).is_none());
+
+    let warning = quota.observe("doc1", 80).unwrap();
+    assert_eq!(warning.document_id, "doc1");
+    assert_eq!(warning.warning_threshold, 80);
+    assert_eq!(warning.limit, 100);
+
+    // Documents are tracked independently
+    assert!(quota.observe("doc2", 90).is_some());
+}
+
+#[test]
+fn test_warning_hysteresis() {
+    let quota = tracker(100);
+    assert!(quota.observe("doc1", 80).is_some());
+
+    // Oscillating around the threshold doesn't re-warn
+    assert!(quota.observe("doc1", 79).is_none());
+    assert!(quota.observe("doc1", 81).is_none());
+
+    // Dropping below the hysteresis margin re-arms the warning
+    assert!(quota.observe("doc1", 69).is_none());
+    assert!(quota.observe("doc1", 85).is_some());
+}
+
+#[test]
+fn test_webhook_rejects_unsupported_url() {
+    assert!(WebhookNotifier::new("not a url").is_err());
+    assert!(WebhookNotifier::new("ftp://example.com/hook").is_err());
+}
+
+#[tokio::test]
+async fn test_webhook_delivery() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let route = warp::post()
+        .and(warp::path("hook"))
+        .and(warp::body::json())
+        .map({
+            let received = received.clone();
+            move |event: WebhookEvent| {
+                received.lock().unwrap().push(event);
+                warp::reply()
+            }
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let notifier = WebhookNotifier::new(&format!("http://{}/hook", addr)).unwrap();
+    let event = WebhookEvent::new("quota.warning", "doc1".to_string(), serde_json::json!({ "size": 80 }));
+    notifier.send(&event).await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].event, "quota.warning");
+    assert_eq!(received[0].data["size"], 80);
+}
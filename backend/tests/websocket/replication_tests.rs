@@ -0,0 +1,148 @@
+/*
+ * File: tests/websocket/replication_tests.rs
+ * Purpose: Test suite for warm standby replication
+ *
+ * Test Categories:
+ * - Paging through the replication log and expired positions
+ * - A standby catching up with a primary and following its changes
+ * - Promoting a standby
+ */
+
+use std::time::Duration;
+use serde_json::Value;
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    crdt::{Operation, OperationSource, Position, Timestamp},
+    fixtures::TestServer,
+    tenant::DEFAULT_TENANT,
+    websocket::{
+        replication::ReplicatedChange,
+        AdminConfig, EditorServer, ReplicationError, ReplicationLog, ReplicationRole, ReplicationStatus, ServerConfig,
+        StandbyConfig,
+    },
+};
+
+fn change(character: char) -> ReplicatedChange {
+    let operation = Operation::Insert {
+        client_id: "alice".to_string(),
+        character,
+        position: Position::new(vec![1]),
+        timestamp: Timestamp::new("alice".to_string()),
+    };
+    ReplicatedChange::Operation { operation, source: OperationSource::User }
+}
+
+/// Wait until a standby's progress satisfies a condition
+async fn wait_for_status(server: &EditorServer, done: impl Fn(&ReplicationStatus) -> bool) -> ReplicationStatus {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    loop {
+        let status = server.replication_status();
+        if done(&status) {
+            return status;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "standby did not catch up: {:?}", status);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+async fn content(server: &EditorServer, document_id: &str) -> Option<String> {
+    server.document(DEFAULT_TENANT, document_id).await.unwrap().map(|doc| doc.content())
+}
+
+#[test]
+fn test_log_pages_and_expired_positions() {
+    let log = ReplicationLog::new();
+    for character in ['a', 'b', 'c'] {
+        log.record(DEFAULT_TENANT, "doc1", change(character));
+    }
+
+    let page = log.since(0, 2).unwrap();
+    let positions: Vec<u64> = page.entries.iter().map(|entry| entry.position).collect();
+    assert_eq!(positions, [1, 2]);
+    assert_eq!((page.next, page.head), (2, 3));
+
+    let page = log.since(2, 10).unwrap();
+    assert_eq!(page.entries.len(), 1);
+    assert_eq!(page.next, 3);
+    assert!(log.since(3, 10).unwrap().entries.is_empty());
+
+    // A position the log never reached comes from before a restart
+    assert_eq!(log.since(7, 10).unwrap_err(), ReplicationError::Expired(7));
+}
+
+#[tokio::test]
+async fn test_standby_follows_primary_until_promoted() {
+    let primary = TestServer::with_config(ServerConfig {
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        ..Default::default()
+    })
+    .await;
+    let mut editor = primary.connect().await;
+    editor.create_document("notes", "Hello").await;
+
+    let primary_url = format!("http://127.0.0.1:{}", primary.port().unwrap());
+    let standby = TestServer::in_process_with_config(ServerConfig {
+        admin: AdminConfig { token: Some("standby-secret".to_string()) },
+        standby: Some(StandbyConfig { poll_timeout: Duration::from_secs(1), ..StandbyConfig::new(primary_url, "secret") }),
+        ..Default::default()
+    });
+    let _tasks = standby.server().start_background_tasks();
+
+    // Catch up from a snapshot, then follow the log
+    let status = wait_for_status(standby.server(), |status| status.snapshots == 1 && status.connected).await;
+    assert_eq!(status.role, ReplicationRole::Standby);
+    assert_eq!(content(standby.server(), "notes").await.as_deref(), Some("Hello"));
+
+    editor.create_document("todo", "").await;
+    editor.type_text("todo", "milk").await;
+    let head = primary.server().replication_status().head;
+    let status = wait_for_status(standby.server(), |status| status.applied == head).await;
+    assert_eq!(status.lag, 0);
+    assert_eq!(content(standby.server(), "todo").await.as_deref(), Some("milk"));
+
+    // Clients are turned away until the standby is promoted
+    let routes = standby.server().routes();
+    assert!(warp::test::ws().path("/ws").handshake(routes.clone()).await.is_err());
+    let response = warp::test::request()
+        .method("POST")
+        .path("/admin/replication/promote")
+        .header("authorization", "Bearer standby-secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let status: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(status["role"], "primary");
+    assert!(warp::test::ws().path("/ws").handshake(routes.clone()).await.is_ok());
+    assert_eq!(standby.server().promote().unwrap_err(), ReplicationError::NotStandby);
+
+    // A promoted standby no longer follows
+    editor.type_text("todo", "!").await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(content(standby.server(), "todo").await.as_deref(), Some("milk"));
+}
+
+#[tokio::test]
+async fn test_replication_requires_admin_token() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        ..Default::default()
+    });
+    let routes = server.server().routes();
+    let response = warp::test::request().path("/replication/log?since=0&timeout=0").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = warp::test::request()
+        .path("/replication/log?since=5&timeout=0")
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::GONE);
+
+    let response = warp::test::request()
+        .path("/admin/replication")
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    let status: ReplicationStatus = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(status.role, ReplicationRole::Primary);
+}
@@ -0,0 +1,87 @@
+/*
+ * File: tests/websocket/routes_tests.rs
+ * Purpose: Test suite for mounting the server's routes in another application
+ *
+ * Test Categories:
+ * - WebSocket routes under a path prefix
+ * - HTTP routes under a path prefix
+ * - Joining a document through the URL path
+ */
+
+use warp::{http::StatusCode, Filter};
+use crdt_editor_backend::{
+    fixtures::TestClient,
+    tenant::TenantConfig,
+    websocket::{message::DocumentStateMessage, EditorServer, Message, MessageType, ServerConfig},
+};
+
+async fn receive(client: &mut warp::test::WsClient) -> Message {
+    serde_json::from_str(client.recv().await.unwrap().to_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_websocket_under_prefix() {
+    let server = EditorServer::new(ServerConfig::default());
+    let app = warp::path("coedit").and(server.routes());
+
+    let mut client = warp::test::ws().path("/coedit/ws").handshake(app.clone()).await.unwrap();
+    let welcome = receive(&mut client).await;
+    assert_eq!(welcome.message_type(), &MessageType::Status);
+    assert_eq!(server.client_count(), 1);
+
+    // Outside the prefix nothing is served
+    assert!(warp::test::ws().path("/ws").handshake(app).await.is_err());
+}
+
+#[tokio::test]
+async fn test_http_routes_under_prefix() {
+    let server = EditorServer::new(ServerConfig::default());
+    let app = warp::path("coedit").and(server.routes());
+
+    let events = warp::test::request().path("/coedit/events?timeout=0").reply(&app).await;
+    assert_eq!(events.status(), StatusCode::OK);
+
+    let admin = warp::test::request().method("POST").path("/coedit/admin/jobs").reply(&app).await;
+    assert_eq!(admin.status(), StatusCode::NOT_FOUND);
+    assert_eq!(admin.body(), "The admin API is disabled");
+
+    let outside = warp::test::request().path("/events?timeout=0").reply(&app).await;
+    assert_eq!(outside.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_join_document_from_path() {
+    let server = EditorServer::new(ServerConfig {
+        tenants: vec![TenantConfig {
+            id: "acme".to_string(),
+            api_keys: vec!["secret".to_string()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+    let mut author = TestClient::over(server.connect_in_process("acme", Some("secret")).unwrap()).await;
+    author.create_document("notes", "Hello").await;
+
+    // The key may come as a bearer token instead of in the query
+    let mut client = warp::test::ws()
+        .path("/t/acme/ws/notes")
+        .header("authorization", "Bearer secret")
+        .handshake(server.routes())
+        .await
+        .unwrap();
+    let client_id = receive(&mut client).await.payload()["client_id"].as_str().unwrap().to_string();
+    let state = receive(&mut client).await;
+    assert_eq!(state.message_type(), &MessageType::DocumentState);
+    let state: DocumentStateMessage = serde_json::from_value(state.payload().clone()).unwrap();
+    assert_eq!(state.content, "Hello");
+    assert!(server.clients_in_document("acme", "notes").await.unwrap().contains(&client_id));
+
+    // Joining goes through the tenant's access check
+    assert!(warp::test::ws().path("/t/acme/ws/notes").handshake(server.routes()).await.is_err());
+
+    // A missing document is reported like any getDocument
+    let mut client = warp::test::ws().path("/t/acme/ws/missing?key=secret").handshake(server.routes()).await.unwrap();
+    receive(&mut client).await;
+    assert_eq!(receive(&mut client).await.message_type(), &MessageType::Error);
+    assert!(server.clients_in_document("acme", "missing").await.unwrap().is_empty());
+}
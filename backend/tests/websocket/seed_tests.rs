@@ -0,0 +1,61 @@
+/*
+ * File: tests/websocket/seed_tests.rs
+ * Purpose: Test suite for system documents seeded on first boot
+ *
+ * Test Categories:
+ * - Seeding every workspace through the import pipeline, once
+ * - Leaving existing documents and encrypted tenants alone
+ */
+
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    tenant::{TenantConfig, DEFAULT_TENANT},
+    websocket::{seed::WELCOME_TEMPLATE, ActivityKind, SeedConfig, SeedDocument, ServerConfig},
+};
+
+#[tokio::test]
+async fn test_seeds_every_workspace_once() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        tenants: vec![TenantConfig { id: "acme".to_string(), ..Default::default() }],
+        seed: SeedConfig::system(),
+        ..Default::default()
+    });
+
+    let seeded = server.server().seed_documents().await;
+    let mut names: Vec<(&str, &str)> = seeded.iter().map(|doc| (doc.tenant_id.as_str(), doc.document_id.as_str())).collect();
+    names.sort();
+    assert_eq!(names, [("acme", "keyboard-shortcuts"), ("acme", "welcome"), (DEFAULT_TENANT, "keyboard-shortcuts"), (DEFAULT_TENANT, "welcome")]);
+    assert!(server.server().seed_documents().await.is_empty());
+
+    // Seeded documents are ordinary documents: task lists became checklists
+    let welcome = server.server().document("acme", "welcome").await.unwrap().unwrap();
+    assert!(welcome.content().starts_with("# Welcome to CoEdit\n"));
+    assert!(welcome.content().contains("```checklist tasks-1\n```"));
+    let tenant = server.server().tenants().get("acme").unwrap();
+    assert_eq!(tenant.checklists().checklist("welcome", "tasks-1").items().len(), 3);
+    let activity = server.server().activity("acme", None, 10).unwrap();
+    assert!(activity.activities.iter().all(|activity| activity.kind == ActivityKind::Created));
+    assert_eq!(activity.activities.len(), 2);
+
+    let mut client = server.connect_to("acme", None).await;
+    let version = client.type_text("welcome", "!").await;
+    assert_eq!(version, welcome.version() + 1);
+}
+
+#[tokio::test]
+async fn test_existing_documents_kept() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        seed: SeedConfig {
+            documents: vec![SeedDocument::markdown("welcome", WELCOME_TEMPLATE), SeedDocument::markdown("guide", "# Guide")],
+        },
+        ..Default::default()
+    });
+    let mut client = server.connect().await;
+    client.create_document("welcome", "Ours").await;
+
+    let seeded = server.server().seed_documents().await;
+    assert_eq!(seeded.len(), 1);
+    assert_eq!(seeded[0].document_id, "guide");
+    let welcome = server.server().document(DEFAULT_TENANT, "welcome").await.unwrap().unwrap();
+    assert_eq!(welcome.content(), "Ours");
+}
@@ -0,0 +1,137 @@
+/*
+ * File: tests/websocket/serve_tests.rs
+ * Purpose: Test suite for the self-contained `coedit serve` binary
+ *
+ * Test Categories:
+ * - Frontend and WebSocket API on one port
+ * - Command line handling
+ * - `coedit doctor` self-check
+ * - `coedit tail` following a document
+ */
+
+use std::{
+    io::{BufRead, BufReader},
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+use tokio_tungstenite::connect_async;
+use crdt_editor_backend::{
+    client::{websocket_connector, EditorClient, ReconnectConfig, Replica},
+    crdt::{Operation, Position},
+};
+
+const COEDIT: &str = env!("CARGO_BIN_EXE_coedit");
+
+/// How long `coedit tail` may take to print a change
+const LINE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Kills the server process when the test ends
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Wait until a server accepts connections on a port
+async fn wait_for_port(port: u16) {
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_serves_frontend_and_websocket_on_one_port() {
+    let assets = std::env::temp_dir().join(format!("coedit-serve-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&assets).unwrap();
+    std::fs::write(assets.join("index.html"), "<html>editor</html>").unwrap();
+
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let _server = Server(
+        Command::new(COEDIT)
+            .args(["serve", "--port", &port.to_string(), "--assets"])
+            .arg(&assets)
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_port(port).await;
+
+    let client = hyper::Client::new();
+    let response = client
+        .get(format!("http://127.0.0.1:{}/docs/meeting-notes", port).parse().unwrap())
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(body.as_ref(), b"<html>editor</html>");
+
+    let (_socket, _) = connect_async(format!("ws://127.0.0.1:{}/ws", port)).await.unwrap();
+}
+
+#[test]
+fn test_doctor_reports_busy_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+
+    let output = Command::new(COEDIT).args(["doctor", "--port", &port]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("[error] address"), "{}", report);
+    assert!(report.contains("[ok] timeouts"), "{}", report);
+
+    drop(listener);
+    let output = Command::new(COEDIT).args(["doctor", "--port", &port]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_rejects_unknown_commands() {
+    for args in [&[][..], &["run"][..], &["serve", "--bogus"][..], &["serve", "--port", "http"][..], &["tail"][..], &["serve", "--url", "ws://x"][..], &["tail", "notes", "--welcome"][..], &["sync"][..], &["sync", "notes", "--welcome"][..]] {
+        let output = Command::new(COEDIT).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
+
+#[tokio::test]
+async fn test_tail_prints_changes() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let _server = Server(Command::new(COEDIT).args(["serve", "--port", &port.to_string()]).spawn().unwrap());
+    wait_for_port(port).await;
+
+    let url = format!("ws://127.0.0.1:{}/ws", port);
+    let writer = EditorClient::start(websocket_connector(url.clone()), Replica::new("notes", "laptop"), ReconnectConfig::default());
+    let positions = Position::spread(3);
+    for (character, position) in "hi".chars().zip(&positions) {
+        writer.edit(Operation::insert("laptop".to_string(), character, position.clone())).unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(5), writer.synced()).await.expect("writer syncs");
+
+    let mut tail = Server(
+        Command::new(COEDIT)
+            .args(["tail", "notes", "--url", &url])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let stdout = tail.0.stdout.take().unwrap();
+    let (lines, mut received) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if lines.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let line = tokio::time::timeout(LINE_TIMEOUT, received.recv()).await.expect("tail prints the content");
+    assert_eq!(line.as_deref(), Some(r#"{"offset":0,"removed":"","inserted":"hi"}"#));
+    writer.edit(Operation::insert("laptop".to_string(), '!', positions[2].clone())).unwrap();
+    let line = tokio::time::timeout(LINE_TIMEOUT, received.recv()).await.expect("tail prints the edit");
+    assert_eq!(line.as_deref(), Some(r#"{"offset":2,"removed":"","inserted":"!"}"#));
+    writer.close().await;
+}
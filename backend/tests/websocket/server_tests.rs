@@ -1,273 +1,357 @@
 /*
  * File: tests/websocket/server_tests.rs
  * Purpose: Test suite for WebSocket server functionality
- * 
+ *
  * Test Categories:
  * - Server initialization and shutdown
  * - Request handling and routing
- * - Document state management
+ * - Document state management, and catching up after reconnects
  * - Client message broadcasting
  * - Error handling and recovery
+ *
+ * Startup, connections and shutdown run over real sockets as a smoke
+ * suite; the other tests connect in process.
  */
 
-// TODO: Re-enable after implementing server module
-/*
 use std::time::Duration;
-use tokio::time::timeout;
-use warp::test::WsClient;
+use serde_json::{json, Value};
 use crdt_editor_backend::{
-    websocket::server::{EditorServer, ServerConfig},
-    websocket::message::{Message, MessageType, OperationMessage},
-    crdt::{Document, Operation, Position},
+    crdt::{Document, DocumentUpdate, Operation, Position, StateVector},
+    fixtures::{TestClient, TestServer},
+    jobs::Job,
+    tenant::DEFAULT_TENANT,
+    websocket::{
+        message::{DocumentUpdateMessage, OperationMessage, SyncRequestMessage, SyncResponseMessage},
+        AdminConfig, MessageType, ServerConfig,
+    },
 };
 
-async fn setup_test_server() -> (EditorServer, ServerConfig) {
-    let config = ServerConfig {
-        port: 0, // Random available port
-        host: "127.0.0.1".to_string(),
-        heartbeat_interval: Duration::from_secs(5),
-        connection_timeout: Duration::from_secs(10),
-    };
-    
-    let server = EditorServer::new(config.clone()).await.unwrap();
-    (server, config)
-}
-
 #[tokio::test]
 async fn test_server_initialization() {
-    let (server, config) = setup_test_server().await;
-    assert!(server.is_running().await);
-    assert!(server.port() > 0);
-    assert_eq!(server.host(), &config.host);
+    let server = TestServer::spawn().await;
+    assert!(server.port().is_some_and(|port| port > 0));
+    assert_eq!(server.server().client_count(), 0);
+
+    let _client = server.connect().await;
+    assert_eq!(server.server().client_count(), 1);
 }
 
 #[tokio::test]
 async fn test_client_connection() {
-    let (server, _) = setup_test_server().await;
-    let client = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect");
-    
-    // Send connection message
-    let msg = Message::new(
-        MessageType::Connect,
-        "client1".to_string(),
-        serde_json::json!({"client_id": "client1"}),
-    );
-    client.send_text(serde_json::to_string(&msg).unwrap()).await;
-    
-    // Expect connection acknowledgment
-    let response = client.recv().await.unwrap();
-    let response_msg: Message = serde_json::from_str(response.to_str().unwrap()).unwrap();
-    assert_eq!(response_msg.message_type(), &MessageType::Connected);
+    let server = TestServer::spawn().await;
+    let first = server.connect().await;
+    let second = server.connect().await;
+
+    assert!(!first.id().is_empty());
+    assert_ne!(first.id(), second.id());
 }
 
 #[tokio::test]
 async fn test_document_creation() {
-    let (server, _) = setup_test_server().await;
-    let client = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect");
-    
-    // Create new document
-    let msg = Message::new(
-        MessageType::CreateDocument,
-        "client1".to_string(),
-        serde_json::json!({
-            "document_id": "doc1",
-            "initial_content": ""
-        }),
-    );
-    client.send_text(serde_json::to_string(&msg).unwrap()).await;
-    
-    // Expect document creation confirmation
-    let response = client.recv().await.unwrap();
-    let response_msg: Message = serde_json::from_str(response.to_str().unwrap()).unwrap();
-    assert_eq!(response_msg.message_type(), &MessageType::DocumentCreated);
+    let server = TestServer::in_process();
+    let mut client = server.connect().await;
+
+    let created = client.create_document("doc1", "Hello").await;
+    assert_eq!(created.document_id, "doc1");
+    assert_eq!(created.length, 5);
+    assert_eq!(client.get_document("doc1").await.content, "Hello");
 }
 
 #[tokio::test]
 async fn test_operation_broadcast() {
-    let (server, _) = setup_test_server().await;
-    
-    // Connect two clients
-    let client1 = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect client1");
-    
-    let client2 = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect client2");
-    
-    // Create document
-    let create_msg = Message::new(
-        MessageType::CreateDocument,
-        "client1".to_string(),
-        serde_json::json!({
-            "document_id": "doc1",
-            "initial_content": ""
-        }),
-    );
-    client1.send_text(serde_json::to_string(&create_msg).unwrap()).await;
-    client1.recv().await.unwrap(); // Wait for creation confirmation
-    
-    // Send operation from client1
-    let operation = Operation::insert(
-        "client1".to_string(),
-        'A',
-        Position::start(),
-    );
-    
-    let op_msg = Message::new(
-        MessageType::Operation,
-        "client1".to_string(),
-        serde_json::to_value(OperationMessage {
-            operation,
-            document_id: "doc1".to_string(),
-        }).unwrap(),
-    );
-    client1.send_text(serde_json::to_string(&op_msg).unwrap()).await;
-    
-    // Verify client2 receives the operation
-    let broadcast = client2.recv().await.unwrap();
-    let broadcast_msg: Message = serde_json::from_str(broadcast.to_str().unwrap()).unwrap();
-    assert_eq!(broadcast_msg.message_type(), &MessageType::Operation);
+    let server = TestServer::in_process();
+    let mut clients = server.connect_many(2).await;
+
+    clients[0].create_document("doc1", "").await;
+    clients[1].get_document("doc1").await;
+    clients[0].insert("doc1", 'A', Position::new(vec![1 << 24])).await;
+
+    let relay = clients[1].expect(MessageType::Operation).await;
+    let relay: OperationMessage = serde_json::from_value(relay.payload().clone()).unwrap();
+    assert_eq!(relay.document_id, "doc1");
+    assert_eq!(relay.operation.client_id(), clients[0].id());
+}
+
+#[tokio::test]
+async fn test_range_delete_broadcast() {
+    let server = TestServer::in_process();
+    let mut writer = server.connect().await;
+    let mut reader = server.connect().await;
+    writer.type_text("doc1", "hello world").await;
+    assert_eq!(reader.get_document("doc1").await.content, "hello world");
+
+    // One operation deletes the whole selection
+    let replica = server.server().document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap();
+    let (_, first) = replica.visible_characters().next().unwrap();
+    let (_, last) = replica.visible_characters().nth(5).unwrap();
+    let operation = Operation::delete_range(writer.id().to_string(), &replica, first.clone(), last.clone());
+    writer.request(MessageType::Operation, OperationMessage::new(operation, "doc1".to_string())).await;
+    let ack = writer.expect(MessageType::Ack).await;
+    assert_eq!(ack.payload()["version"], 12);
+
+    let relay = reader.expect(MessageType::Operation).await;
+    assert!(relay.payload()["operation"]["DeleteRange"].is_object());
+    assert_eq!(reader.get_document("doc1").await.content, "world");
 }
 
 #[tokio::test]
 async fn test_document_state_sync() {
-    let (server, _) = setup_test_server().await;
-    let client = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect");
-    
-    // Request document state
-    let msg = Message::new(
-        MessageType::GetDocument,
-        "client1".to_string(),
-        serde_json::json!({"document_id": "doc1"}),
-    );
-    client.send_text(serde_json::to_string(&msg).unwrap()).await;
-    
-    // Expect document state response
-    let response = client.recv().await.unwrap();
-    let response_msg: Message = serde_json::from_str(response.to_str().unwrap()).unwrap();
-    assert_eq!(response_msg.message_type(), &MessageType::DocumentState);
+    let server = TestServer::in_process();
+    let mut writer = server.connect().await;
+    let mut reader = server.connect().await;
+
+    let version = writer.type_text("doc1", "Hi").await;
+    let state = reader.get_document("doc1").await;
+    assert_eq!(state.content, "Hi");
+    assert_eq!(state.version, version);
+}
+
+#[tokio::test]
+async fn test_opening_a_document_misses_no_operations() {
+    const COUNT: usize = 200;
+    let server = TestServer::in_process();
+    let mut writer = server.connect().await;
+    let mut reader = server.connect().await;
+    writer.insert("doc1", 'a', Position::new(vec![1])).await;
+
+    // The reader opens the document while the writer keeps appending
+    let writing = tokio::spawn(async move {
+        for i in 1..COUNT {
+            writer.insert("doc1", 'a', Position::new(vec![i as u32 + 1])).await;
+        }
+        writer
+    });
+    tokio::task::yield_now().await;
+    let state = reader.get_document("doc1").await;
+
+    // Every operation the snapshot lacks is relayed after it
+    let mut received = vec![false; COUNT];
+    received[..state.content.len()].fill(true);
+    while let Some(message) = reader.recv_within(Duration::from_millis(500)).await {
+        if message.message_type() == &MessageType::Operation {
+            let relayed: OperationMessage = serde_json::from_value(message.payload().clone()).unwrap();
+            if let Operation::Insert { position, .. } = relayed.operation {
+                received[position.path()[0] as usize - 1] = true;
+            }
+        }
+    }
+    writing.await.unwrap();
+    assert!(received.iter().all(|&received| received), "{:?}", received);
 }
 
 #[tokio::test]
 async fn test_error_handling() {
-    let (server, _) = setup_test_server().await;
-    let client = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect");
-    
-    // Send invalid operation
-    let msg = Message::new(
-        MessageType::Operation,
-        "client1".to_string(),
-        serde_json::json!({
-            "invalid": "operation"
-        }),
-    );
-    client.send_text(serde_json::to_string(&msg).unwrap()).await;
-    
-    // Expect error response
-    let response = client.recv().await.unwrap();
-    let response_msg: Message = serde_json::from_str(response.to_str().unwrap()).unwrap();
-    assert_eq!(response_msg.message_type(), &MessageType::Error);
+    let server = TestServer::in_process();
+    let mut client = server.connect().await;
+
+    let request_id = client.request(MessageType::Operation, json!({ "invalid": "operation" })).await;
+    let error = client.expect(MessageType::Error).await;
+    assert_eq!(error.request_id(), Some(request_id.as_str()));
+
+    // The connection survives the bad request
+    client.type_text("doc1", "ok").await;
 }
 
 #[tokio::test]
 async fn test_server_shutdown() {
-    let (server, _) = setup_test_server().await;
-    let client = warp::test::ws()
-        .path("/ws")
-        .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-        .await
-        .expect("Failed to connect");
-    
-    // Initiate server shutdown
+    let server = TestServer::spawn().await;
+    let url = server.url();
+    server.connect().await.close().await;
+
     server.shutdown().await;
-    
-    // Verify client disconnection
-    let result = timeout(Duration::from_secs(1), client.recv()).await;
-    assert!(result.unwrap().is_none());
+    assert!(tokio_tungstenite::connect_async(url).await.is_err());
 }
 
 #[tokio::test]
 async fn test_concurrent_operations() {
-    let (server, _) = setup_test_server().await;
-    
-    // Connect three clients
-    let clients: Vec<WsClient> = vec![
-        warp::test::ws()
-            .path("/ws")
-            .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-            .await
-            .expect("Failed to connect client1"),
-        warp::test::ws()
-            .path("/ws")
-            .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-            .await
-            .expect("Failed to connect client2"),
-        warp::test::ws()
-            .path("/ws")
-            .handshake(format!("ws://{}:{}/ws", server.host(), server.port()))
-            .await
-            .expect("Failed to connect client3"),
-    ];
-    
-    // Create document
-    let create_msg = Message::new(
-        MessageType::CreateDocument,
-        "client1".to_string(),
-        serde_json::json!({
-            "document_id": "doc1",
-            "initial_content": ""
-        }),
-    );
-    clients[0].send_text(serde_json::to_string(&create_msg).unwrap()).await;
-    clients[0].recv().await.unwrap(); // Wait for creation confirmation
-    
-    // Send concurrent operations
-    for (i, client) in clients.iter().enumerate() {
-        let operation = Operation::insert(
-            format!("client{}", i + 1),
-            char::from(b'A' + i as u8),
-            Position::start(),
-        );
-        
-        let op_msg = Message::new(
-            MessageType::Operation,
-            format!("client{}", i + 1),
-            serde_json::to_value(OperationMessage {
-                operation,
-                document_id: "doc1".to_string(),
-            }).unwrap(),
-        );
-        client.send_text(serde_json::to_string(&op_msg).unwrap()).await;
+    let server = TestServer::in_process();
+    let mut clients = server.connect_many(3).await;
+    clients[0].create_document("doc1", "").await;
+    for client in &mut clients[1..] {
+        client.get_document("doc1").await;
     }
-    
-    // Verify all clients receive all operations
-    for client in clients.iter() {
-        for _ in 0..3 {
-            let msg = client.recv().await.unwrap();
-            let msg: Message = serde_json::from_str(msg.to_str().unwrap()).unwrap();
-            assert_eq!(msg.message_type(), &MessageType::Operation);
+
+    // Every client inserts at the same position before hearing from the others
+    for (i, client) in clients.iter_mut().enumerate() {
+        let operation = Operation::insert(client.id().to_string(), char::from(b'A' + i as u8), Position::new(vec![1 << 24]));
+        client.request(MessageType::Operation, OperationMessage::new(operation, "doc1".to_string())).await;
+    }
+
+    // Each gets its ack and the other two operations, in any order, between
+    // presence updates of the others joining
+    for client in &mut clients {
+        let mut types = Vec::new();
+        while types.len() < 3 {
+            let message_type = client.recv().await.message_type().clone();
+            if message_type != MessageType::PresenceUpdate {
+                types.push(message_type);
+            }
+        }
+        assert_eq!(types.iter().filter(|t| **t == MessageType::Ack).count(), 1);
+        assert_eq!(types.iter().filter(|t| **t == MessageType::Operation).count(), 2);
+        while let Some(message) = client.recv_within(Duration::from_millis(50)).await {
+            assert_eq!(message.message_type(), &MessageType::PresenceUpdate);
+        }
+    }
+
+    let contents: Vec<String> = futures::future::join_all(clients.iter_mut().map(|client: &mut TestClient| async move {
+        client.get_document("doc1").await.content
+    }))
+    .await;
+    assert_eq!(contents[0].len(), 3);
+    assert!(contents.iter().all(|content| *content == contents[0]));
+}
+
+#[tokio::test]
+async fn test_offline_client_submits_update() {
+    let server = TestServer::in_process();
+    let mut online = server.connect().await;
+    let mut offline = server.connect().await;
+    online.type_text("doc1", "ab").await;
+
+    // The offline client starts from the whole history
+    offline.request(MessageType::DocumentUpdate, json!({ "document_id": "doc1", "version": 0, "state_vector": {} })).await;
+    let reply = offline.expect(MessageType::DocumentUpdate).await;
+    let reply: DocumentUpdateMessage = serde_json::from_value(reply.payload().clone()).unwrap();
+    assert_eq!(reply.update.version, 2);
+    let mut replica = Document::new("doc1".to_string());
+    reply.update.apply_to(&mut replica).unwrap();
+    assert_eq!(replica.content(), "ab");
+    let last_seen = reply.update.state_vector;
+
+    // Both edit; the offline client's edit reaches the server as an update
+    let end = Position::spread(2)[1].clone();
+    replica.apply(Operation::insert("phone".to_string(), 'c', replica.position_after(&end)));
+    online.insert("doc1", 'X', Position::new(vec![1])).await;
+    let pending = DocumentUpdate::since(&replica, &last_seen);
+    offline
+        .request(MessageType::DocumentUpdate, DocumentUpdateMessage { document_id: "doc1".to_string(), update: pending })
+        .await;
+    let reply = offline.expect(MessageType::DocumentUpdate).await;
+    let reply: DocumentUpdateMessage = serde_json::from_value(reply.payload().clone()).unwrap();
+    assert_eq!(reply.update.operations.len(), 1);
+    assert_eq!(reply.update.version, 4);
+    reply.update.apply_to(&mut replica).unwrap();
+
+    // The online client hears the merged operation like any other
+    let relay = online.expect(MessageType::Operation).await;
+    assert_eq!(relay.payload()["operation"]["Insert"]["character"], "c");
+    assert_eq!(online.get_document("doc1").await.content, replica.content());
+    assert_eq!(StateVector::of(&replica), reply.update.state_vector);
+
+    // Updates without operations don't create documents
+    offline.request(MessageType::DocumentUpdate, json!({ "document_id": "missing", "version": 0, "state_vector": {} })).await;
+    offline.expect(MessageType::Error).await;
+}
+
+#[tokio::test]
+async fn test_reconnecting_client_syncs_missed_operations() {
+    let server = TestServer::in_process();
+    let mut online = server.connect().await;
+    online.type_text("doc1", "ab").await;
+    let seen = server.server().document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap();
+
+    // Two more edits land while the client is away
+    online.insert("doc1", 'X', Position::new(vec![1])).await;
+    online.insert("doc1", 'Y', Position::new(vec![2])).await;
+
+    let mut back = server.connect().await;
+    back.request(MessageType::SyncRequest, json!({ "document_id": "doc1", "version": 2 })).await;
+    let reply = back.expect(MessageType::SyncResponse).await;
+    let reply: SyncResponseMessage = serde_json::from_value(reply.payload().clone()).unwrap();
+    assert_eq!(reply.version, 4);
+    let characters: Vec<char> = reply.operations.iter().filter_map(|operation| match operation {
+        Operation::Insert { character, .. } => Some(*character),
+        _ => None,
+    }).collect();
+    assert_eq!(characters, ['X', 'Y']);
+
+    // A state vector gets exactly the operations beyond it
+    back.request(MessageType::SyncRequest, SyncRequestMessage {
+        document_id: "doc1".to_string(),
+        version: None,
+        state_vector: Some(seen.state_vector().clone()),
+    })
+    .await;
+    let reply = back.expect(MessageType::SyncResponse).await;
+    let reply: SyncResponseMessage = serde_json::from_value(reply.payload().clone()).unwrap();
+    assert_eq!(reply.operations.len(), 2);
+    let mut synced = seen.clone();
+    for operation in reply.operations {
+        synced.merge_operation(operation).unwrap();
+    }
+    assert_eq!(synced.content(), online.get_document("doc1").await.content);
+    assert_eq!(synced.state_vector(), &reply.state_vector);
+
+    // Up-to-date clients get nothing; versions ahead and unknown documents fail
+    back.request(MessageType::SyncRequest, json!({ "document_id": "doc1", "version": 4 })).await;
+    let reply = back.expect(MessageType::SyncResponse).await;
+    assert_eq!(reply.payload()["operations"], json!([]));
+    back.request(MessageType::SyncRequest, json!({ "document_id": "doc1", "version": 5 })).await;
+    back.expect(MessageType::Error).await;
+    back.request(MessageType::SyncRequest, json!({ "document_id": "missing", "version": 0 })).await;
+    back.expect(MessageType::Error).await;
+}
+
+/// Run an admin job to completion and get its result
+async fn run_admin_job(server: &TestServer, body: Value) -> Value {
+    let routes = server.server().routes();
+    let response = warp::test::request()
+        .method("POST")
+        .path("/admin/jobs")
+        .header("authorization", "Bearer secret")
+        .json(&body)
+        .reply(&routes)
+        .await;
+    let id = serde_json::from_slice::<Value>(response.body()).unwrap()["id"].as_u64().unwrap();
+    loop {
+        let response = warp::test::request()
+            .path(&format!("/admin/jobs/{}", id))
+            .header("authorization", "Bearer secret")
+            .reply(&routes)
+            .await;
+        let job: Job = serde_json::from_slice(response.body()).unwrap();
+        if job.status.is_finished() {
+            return job.result.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_gc_dry_run_names_blocking_clients() {
+    let config = ServerConfig { admin: AdminConfig { token: Some("secret".to_string()) }, ..Default::default() };
+    let server = TestServer::in_process_with_config(config);
+    let mut online = server.connect().await;
+    let mut offline = server.connect().await;
+    online.type_text("doc1", "abc").await;
+
+    // The offline client acknowledges the document before the delete
+    offline.request(MessageType::DocumentUpdate, json!({ "document_id": "doc1", "version": 0, "state_vector": {} })).await;
+    offline.expect(MessageType::DocumentUpdate).await;
+    let delete = OperationMessage::new(Operation::delete(online.id().to_string(), Position::spread(3)[1].clone()), "doc1".to_string());
+    online.request(MessageType::Operation, delete).await;
+    online.expect(MessageType::Ack).await;
+
+    let result = run_admin_job(&server, json!({ "action": "compact", "dry_run": true })).await;
+    let report = &result["reports"]["default/doc1"];
+    assert_eq!(report["tombstones"], 1);
+    assert_eq!(report["collectible"], 0);
+    assert_eq!(report["blocking_clients"], json!([offline.id()]));
+    let result = run_admin_job(&server, json!({ "action": "compact" })).await;
+    assert_eq!(result["tombstones_removed"], 0);
+
+    // Leaving releases the barrier
+    offline.close().await;
+    for _ in 0..100 {
+        let doc = server.server().document(DEFAULT_TENANT, "doc1").await.unwrap().unwrap();
+        if doc.gc_barriers().is_empty() {
+            break;
         }
+        tokio::time::sleep(Duration::from_millis(10)).await;
     }
+    let result = run_admin_job(&server, json!({ "action": "compact", "dry_run": true })).await;
+    assert_eq!(result["reports"]["default/doc1"]["collectible"], 1);
+    let result = run_admin_job(&server, json!({ "action": "compact" })).await;
+    assert_eq!(result["tombstones_removed"], 1);
 }
-*/
@@ -0,0 +1,111 @@
+/*
+ * File: tests/websocket/skew_tests.rs
+ * Purpose: Test suite for timestamp skew detection
+ *
+ * Test Categories:
+ * - Flagging clients whose clocks regress or jump ahead
+ * - Quarantining a flagged client's operations and releasing them
+ */
+
+use serde_json::Value;
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    crdt::{Operation, Position, Timestamp},
+    fixtures::TestServer,
+    tenant::DEFAULT_TENANT,
+    websocket::{
+        message::OperationMessage,
+        AdminConfig, MessageType, ServerConfig, Skew, SkewConfig, SkewDetector, SkewError,
+    },
+};
+
+fn insert_at_clock(client_id: &str, clock: u64, index: u32) -> Operation {
+    let mut timestamp = Timestamp::new(client_id.to_string());
+    for _ in 0..clock {
+        timestamp.increment();
+    }
+    Operation::Insert {
+        client_id: client_id.to_string(),
+        character: 'x',
+        position: Position::new(vec![index]),
+        timestamp,
+    }
+}
+
+#[test]
+fn test_flags_regressing_and_runaway_clocks() {
+    let detector = SkewDetector::new(SkewConfig { max_ahead: 100, window: 4, threshold: 2, quarantine: false });
+    let observe = |clock, document_clock| {
+        detector.observe(DEFAULT_TENANT, "alice", "doc1", document_clock, &insert_at_clock("alice", clock, 1))
+    };
+
+    assert_eq!(observe(5, 5), Ok(None));
+    assert_eq!(observe(5, 5), Ok(None));
+    assert_eq!(observe(3, 5), Ok(Some(Skew::Regressed)));
+    assert!(detector.flagged().is_empty());
+
+    // Skewed operations that fall out of the window no longer count
+    assert_eq!(observe(6, 6), Ok(None));
+    assert_eq!(observe(7, 7), Ok(None));
+    assert_eq!(observe(8, 8), Ok(None));
+    assert_eq!(observe(500, 8), Ok(Some(Skew::Ahead)));
+    assert!(detector.flagged().is_empty());
+
+    assert_eq!(observe(400, 500), Ok(Some(Skew::Regressed)));
+    let reports = detector.flagged();
+    assert_eq!(reports.len(), 1);
+    assert_eq!((reports[0].regressed, reports[0].ahead, reports[0].operations), (2, 1, 8));
+
+    // Unflagged clients are forgotten on disconnect, flagged ones kept
+    detector.forget(DEFAULT_TENANT, "alice");
+    assert_eq!(detector.flagged().len(), 1);
+    assert_eq!(detector.clear(DEFAULT_TENANT, "alice"), Ok(Vec::new()));
+    assert_eq!(detector.clear(DEFAULT_TENANT, "alice"), Err(SkewError::NotFlagged("alice".to_string())));
+    detector.forget(DEFAULT_TENANT, "alice");
+    assert_eq!(observe(1, 500), Ok(None));
+}
+
+#[tokio::test]
+async fn test_quarantined_operations_are_held_until_released() {
+    let server = TestServer::in_process_with_config(ServerConfig {
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        skew: SkewConfig { window: 5, threshold: 2, quarantine: true, ..Default::default() },
+        ..Default::default()
+    });
+    let mut client = server.connect().await;
+    client.create_document("notes", "").await;
+    let client_id = client.id().to_string();
+
+    let mut send = async |clock, index| {
+        let operation = insert_at_clock(&client_id, clock, index);
+        client.request(MessageType::Operation, OperationMessage::new(operation, "notes".to_string())).await;
+        client.recv().await
+    };
+    assert_eq!(send(10, 100).await.message_type(), &MessageType::Ack);
+    assert_eq!(send(4, 200).await.message_type(), &MessageType::Ack);
+    let reply = send(3, 300).await;
+    assert_eq!(reply.message_type(), &MessageType::Error);
+    assert!(reply.payload().to_string().contains("quarantined"));
+
+    let routes = server.server().routes();
+    let response = warp::test::request().path("/admin/skew").header("authorization", "Bearer secret").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let flagged: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(flagged[0]["client_id"], client_id.as_str());
+    assert_eq!(flagged[0]["regressed"], 2);
+    assert_eq!(flagged[0]["held"].as_array().unwrap().len(), 1);
+    assert_eq!(server.server().document(DEFAULT_TENANT, "notes").await.unwrap().unwrap().content(), "xx");
+
+    // Releasing applies the held operation and lets the client edit again
+    let response = warp::test::request()
+        .method("POST")
+        .path(&format!("/admin/skew/{}/{}/release", DEFAULT_TENANT, client_id))
+        .header("authorization", "Bearer secret")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(serde_json::from_slice::<Value>(response.body()).unwrap()["applied"], 1);
+    assert_eq!(server.server().document(DEFAULT_TENANT, "notes").await.unwrap().unwrap().content(), "xxx");
+    assert!(server.server().skewed_clients().is_empty());
+    assert_eq!(server.server().discard_skewed(DEFAULT_TENANT, &client_id), Err(SkewError::NotFlagged(client_id.clone())));
+}
@@ -0,0 +1,65 @@
+/*
+ * File: tests/websocket/subscription_tests.rs
+ * Purpose: Test suite for the per-document subscriber index
+ *
+ * Test Categories:
+ * - Joining and leaving documents
+ * - Cleanup when a client disconnects
+ * - Tenant isolation of document IDs
+ */
+
+use crdt_editor_backend::websocket::SubscriptionIndex;
+
+/// Collect a document's subscribers, sorted
+fn subscribers(index: &SubscriptionIndex, tenant_id: &str, document_id: &str) -> Vec<String> {
+    let mut ids: Vec<String> = index.subscribers(tenant_id, document_id).map(str::to_string).collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn test_join_and_leave() {
+    let mut index = SubscriptionIndex::new();
+    assert!(index.join("default", "doc1", "client1"));
+    assert!(index.join("default", "doc1", "client2"));
+    assert!(!index.join("default", "doc1", "client1"));
+    assert_eq!(subscribers(&index, "default", "doc1"), vec!["client1", "client2"]);
+
+    assert!(index.leave("default", "doc1", "client1"));
+    assert!(!index.leave("default", "doc1", "client1"));
+    assert_eq!(subscribers(&index, "default", "doc1"), vec!["client2"]);
+    assert_eq!(index.documents("client1").count(), 0);
+
+    // The last subscriber leaving drops the document
+    index.leave("default", "doc1", "client2");
+    assert_eq!(index.document_count(), 0);
+}
+
+#[test]
+fn test_remove_client_leaves_every_document() {
+    let mut index = SubscriptionIndex::new();
+    index.join("default", "doc1", "client1");
+    index.join("default", "doc2", "client1");
+    index.join("default", "doc2", "client2");
+
+    let mut documents: Vec<&str> = index.documents("client1").collect();
+    documents.sort();
+    assert_eq!(documents, vec!["doc1", "doc2"]);
+
+    index.remove_client("client1");
+    assert_eq!(index.documents("client1").count(), 0);
+    assert!(subscribers(&index, "default", "doc1").is_empty());
+    assert_eq!(subscribers(&index, "default", "doc2"), vec!["client2"]);
+    assert_eq!(index.document_count(), 1);
+}
+
+#[test]
+fn test_tenants_do_not_share_subscribers() {
+    let mut index = SubscriptionIndex::new();
+    index.join("acme", "doc1", "client1");
+    index.join("globex", "doc1", "client2");
+
+    assert_eq!(subscribers(&index, "acme", "doc1"), vec!["client1"]);
+    assert_eq!(subscribers(&index, "globex", "doc1"), vec!["client2"]);
+    assert_eq!(index.document_count(), 2);
+}
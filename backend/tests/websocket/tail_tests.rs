@@ -0,0 +1,96 @@
+/*
+ * File: tests/websocket/tail_tests.rs
+ * Purpose: Test suite for the late-join fast path
+ *
+ * Test Categories:
+ * - Buffering operations after a snapshot, and dropping gapped or full buffers
+ * - Joining a document through a snapshot and its tail
+ */
+
+use serde_json::json;
+use crdt_editor_backend::{
+    crdt::{Document, Operation, Position},
+    fixtures::TestServer,
+    websocket::{message::DocumentStateMessage, tail::TAIL_CAPACITY, MessageType, TailCache},
+};
+
+fn insert(character: char, index: usize) -> Operation {
+    Operation::insert("alice".to_string(), character, Position::new(vec![index as u32 + 1]))
+}
+
+#[test]
+fn test_tail_follows_snapshot_until_gap() {
+    let cache = TailCache::new();
+    let mut doc = Document::new("doc1".to_string());
+    doc.apply_operation(insert('a', 0)).unwrap();
+
+    // Operations of documents nobody joined are not kept
+    cache.record("default/doc1", 1, &insert('a', 0));
+    assert!(cache.is_empty());
+
+    let tail = cache.join("default/doc1", &doc);
+    assert_eq!((tail.content.as_ref(), tail.version), ("a", 1));
+    assert!(tail.operations.is_empty());
+
+    for (index, character) in ['b', 'c'].into_iter().enumerate() {
+        let operation = insert(character, index + 1);
+        let applied = doc.apply_operation(operation.clone()).unwrap();
+        cache.record("default/doc1", applied.version, &operation);
+    }
+    let tail = cache.join("default/doc1", &doc);
+    assert_eq!((tail.content.as_ref(), tail.version, tail.operations.len()), ("a", 1, 2));
+
+    // A missed operation drops the buffer, and the next joiner gets a snapshot
+    doc.apply_operation(insert('d', 3)).unwrap();
+    let operation = insert('e', 4);
+    let applied = doc.apply_operation(operation.clone()).unwrap();
+    cache.record("default/doc1", applied.version, &operation);
+    assert!(cache.is_empty());
+    let tail = cache.join("default/doc1", &doc);
+    assert_eq!((tail.content.as_ref(), tail.version), ("abcde", 5));
+    assert!(tail.operations.is_empty());
+}
+
+#[test]
+fn test_full_tail_is_dropped() {
+    let cache = TailCache::new();
+    let mut doc = Document::new("doc1".to_string());
+    cache.join("default/doc1", &doc);
+    for index in 0..=TAIL_CAPACITY {
+        let operation = insert('x', index);
+        let applied = doc.apply_operation(operation.clone()).unwrap();
+        cache.record("default/doc1", applied.version, &operation);
+    }
+    assert!(cache.is_empty());
+    let tail = cache.join("default/doc1", &doc);
+    assert_eq!(tail.version, doc.version());
+    assert_eq!(tail.content.len(), TAIL_CAPACITY + 1);
+}
+
+#[tokio::test]
+async fn test_late_joiner_gets_snapshot_and_tail() {
+    let server = TestServer::in_process();
+    let mut alice = server.connect().await;
+    alice.create_document("notes", "").await;
+    alice.type_text("notes", "Hi").await;
+
+    let mut bob = server.connect().await;
+    bob.request(MessageType::GetDocument, json!({ "document_id": "notes", "accept_tail": true })).await;
+    let first: DocumentStateMessage = serde_json::from_value(bob.expect(MessageType::DocumentState).await.payload().clone()).unwrap();
+    assert_eq!((first.content.as_str(), first.version), ("Hi", 2));
+    assert!(first.tail.is_empty());
+
+    // Operations since the snapshot reach the next joiner as its tail
+    let version = alice.insert("notes", '!', Position::new(vec![u32::MAX - 1])).await;
+    let mut carol = server.connect().await;
+    carol.request(MessageType::GetDocument, json!({ "document_id": "notes", "accept_tail": true })).await;
+    let state: DocumentStateMessage = serde_json::from_value(carol.expect(MessageType::DocumentState).await.payload().clone()).unwrap();
+    assert_eq!((state.content.as_str(), state.version, state.checksum), ("Hi", 2, first.checksum));
+    assert_eq!(state.version + state.tail.len() as u64, version);
+    assert!(matches!(state.tail[0], Operation::Insert { character: '!', .. }));
+
+    // Clients not accepting a tail get the whole text as before
+    let state = carol.get_document("notes").await;
+    assert_eq!((state.content.as_str(), state.version), ("Hi!", version));
+    assert!(state.tail.is_empty());
+}
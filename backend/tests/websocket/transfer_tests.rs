@@ -0,0 +1,116 @@
+/*
+ * File: tests/websocket/transfer_tests.rs
+ * Purpose: Test suite for moving documents between tenants
+ *
+ * Test Categories:
+ * - Moving a document with its slug and remapped owner, and notifying both tenants
+ * - Refusing moves that would conflict or exceed the target's limits
+ */
+
+use serde_json::{json, Value};
+use warp::http::StatusCode;
+use crdt_editor_backend::{
+    fixtures::TestServer,
+    tenant::TenantConfig,
+    websocket::{
+        message::{SetFrozenMessage, SetSlugMessage},
+        AdminConfig, MessageType, QuotaConfig, ServerConfig, Transfer, TransferError, TransferRequest,
+    },
+};
+
+fn server() -> TestServer {
+    let tenant = |id: &str, quota: Option<usize>| TenantConfig {
+        id: id.to_string(),
+        quota: quota.map(|limit| QuotaConfig { max_document_characters: Some(limit), ..Default::default() }),
+        ..Default::default()
+    };
+    TestServer::in_process_with_config(ServerConfig {
+        admin: AdminConfig { token: Some("secret".to_string()) },
+        tenants: vec![tenant("acme", None), tenant("archive", None), tenant("tiny", Some(1))],
+        ..Default::default()
+    })
+}
+
+fn request(to_tenant: &str, document_id: Option<&str>) -> TransferRequest {
+    TransferRequest {
+        to_tenant: to_tenant.to_string(),
+        document_id: document_id.map(str::to_string),
+        owner: None,
+    }
+}
+
+#[tokio::test]
+async fn test_transfer_moves_document_with_settings() {
+    let server = server();
+    let mut alice = server.connect_to("acme", None).await;
+    let mut bob = server.connect_to("archive", None).await;
+    alice.create_document("notes", "Hi").await;
+    alice.request(MessageType::SetSlug, SetSlugMessage { document_id: "notes".to_string(), slug: "class-notes".to_string() }).await;
+    alice.expect(MessageType::Ack).await;
+
+    let routes = server.server().routes();
+    let response = warp::test::request()
+        .method("POST")
+        .path("/admin/documents/acme/class-notes/transfer")
+        .header("authorization", "Bearer secret")
+        .body(json!({ "to_tenant": "archive", "document_id": "notes-2024", "owner": bob.id() }).to_string())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let transfer: Transfer = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!((transfer.from_document.as_str(), transfer.to_document.as_str(), transfer.version), ("notes", "notes-2024", 2));
+
+    // Both tenants hear about it, and the document lives on in the target only
+    for client in [&mut alice, &mut bob] {
+        let notice = client.expect(MessageType::DocumentTransferred).await;
+        assert_eq!(notice.payload()["to_tenant"], "archive");
+    }
+    assert!(server.server().document("acme", "notes").await.unwrap().is_none());
+    let moved = server.server().document("archive", "class-notes").await.unwrap().unwrap();
+    assert_eq!((moved.id(), moved.content().as_str(), moved.version()), ("notes-2024", "Hi", 2));
+
+    // Ownership was remapped to the target's client
+    bob.request(MessageType::SetFrozen, SetFrozenMessage { document_id: "notes-2024".to_string(), frozen: true }).await;
+    bob.expect(MessageType::Ack).await;
+    alice.create_document("notes", "New").await;
+}
+
+#[tokio::test]
+async fn test_transfer_refuses_conflicts() {
+    let server = server();
+    let mut alice = server.connect_to("acme", None).await;
+    alice.create_document("notes", "Hi").await;
+    let mut bob = server.connect_to("archive", None).await;
+    bob.create_document("notes", "Taken").await;
+
+    let refused = |result: Result<Transfer, TransferError>| result.unwrap_err();
+    let transfer = |to: &'static str, id: Option<&'static str>| server.server().transfer_document("acme", "notes", request(to, id));
+    assert!(matches!(refused(transfer("archive", None).await), TransferError::Exists(..)));
+    assert!(matches!(refused(transfer("acme", None).await), TransferError::SamePlace(_)));
+    assert!(matches!(refused(transfer("tiny", None).await), TransferError::TooLarge { length: 2, limit: 1, .. }));
+    assert!(matches!(refused(transfer("nowhere", None).await), TransferError::Tenant(_)));
+    let missing = server.server().transfer_document("acme", "missing", request("archive", None)).await;
+    assert!(matches!(refused(missing), TransferError::NotFound(_)));
+    assert_eq!(server.server().document("acme", "notes").await.unwrap().unwrap().content(), "Hi");
+
+    let routes = server.server().routes();
+    let post = |path: &str, token: &str| {
+        warp::test::request()
+            .method("POST")
+            .path(path)
+            .header("authorization", format!("Bearer {}", token))
+            .body(json!({ "to_tenant": "archive" }).to_string())
+    };
+    let response = post("/admin/documents/acme/notes/transfer", "wrong").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = post("/admin/documents/acme/notes/transfer", "secret").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = post("/admin/documents/acme/missing/transfer", "secret").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // A renamed copy moves fine
+    let moved = server.server().transfer_document("acme", "notes", request("archive", Some("notes-acme"))).await.unwrap();
+    assert_eq!(moved.to_document, "notes-acme");
+    let listed: Value = serde_json::to_value(&moved).unwrap();
+    assert_eq!(listed["from_tenant"], "acme");
+}
@@ -0,0 +1,76 @@
+/*
+ * File: tests/websocket/transport_tests.rs
+ * Purpose: Test suite for transports and in-process connections
+ *
+ * Test Categories:
+ * - In-memory duplex delivery and closing
+ * - Tenant routing and access keys of in-process clients
+ * - Rooms and disconnect cleanup without sockets
+ */
+
+use std::{sync::Arc, time::Duration};
+use futures::{SinkExt, StreamExt};
+use crdt_editor_backend::{
+    fixtures::{TestClient, TestServer},
+    tenant::{TenantConfig, TenantError, DEFAULT_TENANT},
+    websocket::{duplex, EditorServer, ServerConfig, Transport},
+};
+
+#[tokio::test]
+async fn test_duplex_carries_frames_both_ways() {
+    let (left, right) = duplex();
+    let (mut left_sink, mut left_stream) = left.split();
+    let (mut right_sink, mut right_stream) = right.split();
+
+    left_sink.send(Arc::from("ping")).await.unwrap();
+    assert_eq!(right_stream.next().await.unwrap().unwrap(), "ping");
+    right_sink.send(Arc::from("pong")).await.unwrap();
+    assert_eq!(left_stream.next().await.unwrap().unwrap(), "pong");
+
+    // Closing one end ends the other end's stream
+    left_sink.close().await.unwrap();
+    assert!(right_stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_in_process_tenants_and_keys() {
+    let server = EditorServer::new(ServerConfig {
+        tenants: vec![TenantConfig {
+            id: "acme".to_string(),
+            api_keys: vec!["secret".to_string()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+
+    assert!(matches!(server.connect_in_process("nobody", None), Err(TenantError::NotFound(_))));
+    assert!(matches!(server.connect_in_process("acme", Some("wrong")), Err(TenantError::Unauthorized(_))));
+
+    let mut client = TestClient::over(server.connect_in_process("acme", Some("secret")).unwrap()).await;
+    client.type_text("doc1", "hi").await;
+    assert_eq!(server.document("acme", "doc1").await.unwrap().unwrap().content(), "hi");
+    assert!(server.document(DEFAULT_TENANT, "doc1").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_in_process_rooms_and_disconnect() {
+    let server = TestServer::in_process();
+    let mut writer = server.connect().await;
+    let reader = server.connect().await;
+    assert_eq!(server.server().client_count(), 2);
+
+    writer.type_text("doc1", "a").await;
+    let writer_id = writer.id().to_string();
+    assert_eq!(server.server().clients_in_document(DEFAULT_TENANT, "doc1").await.unwrap(), vec![writer_id]);
+
+    writer.close().await;
+    reader.close().await;
+    for _ in 0..100 {
+        if server.server().client_count() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(server.server().client_count(), 0);
+    assert!(server.server().clients_in_document(DEFAULT_TENANT, "doc1").await.unwrap().is_empty());
+}
@@ -0,0 +1,76 @@
+/*
+ * File: tests/websocket/validation_tests.rs
+ * Purpose: Test suite for outbound message schema validation
+ * 
+ * Test Categories:
+ * - Valid messages
+ * - Envelope violations
+ * - Payload violations
+ * - Violation reporting
+ */
+
+use serde_json::json;
+use crdt_editor_backend::websocket::{
+    message::{Message, MessageType},
+    validation::{report_violation, validate_outbound, SchemaViolation, MAX_OUTBOUND_FRAME_BYTES},
+};
+
+fn check(message: &Message) -> Result<(), SchemaViolation> {
+    let encoded = serde_json::to_string(message).unwrap();
+    validate_outbound(message, &encoded)
+}
+
+#[test]
+fn test_valid_messages_pass() {
+    let messages = [
+        Message::error("client1".to_string(), "Document not found".to_string()),
+        Message::new(MessageType::Status, "server".to_string(), json!({"status": "connected"})),
+        Message::new(MessageType::Ack, "server".to_string(), json!({"document_id": "doc1"})),
+        Message::new(MessageType::Connected, "server".to_string(), json!(null)),
+    ];
+
+    for message in &messages {
+        assert_eq!(check(message), Ok(()));
+    }
+}
+
+#[test]
+fn test_missing_client_id() {
+    let message = Message::new(MessageType::Status, String::new(), json!({"status": "connected"}));
+    assert_eq!(check(&message), Err(SchemaViolation::MissingField(MessageType::Status, "client_id")));
+}
+
+#[test]
+fn test_missing_required_field() {
+    let status = Message::new(MessageType::Status, "server".to_string(), json!({"code": "x"}));
+    assert_eq!(check(&status), Err(SchemaViolation::MissingField(MessageType::Status, "status")));
+
+    let ack = Message::new(MessageType::Ack, "server".to_string(), json!({}));
+    assert_eq!(check(&ack), Err(SchemaViolation::MissingField(MessageType::Ack, "document_id")));
+}
+
+#[test]
+fn test_invalid_payload() {
+    let error = Message::new(MessageType::Error, "server".to_string(), json!({"error": "x"}));
+    assert!(matches!(check(&error), Err(SchemaViolation::InvalidPayload(MessageType::Error, _))));
+
+    let operation = Message::new(MessageType::Operation, "server".to_string(), json!({"document_id": "doc1"}));
+    assert!(matches!(check(&operation), Err(SchemaViolation::InvalidPayload(MessageType::Operation, _))));
+}
+
+#[test]
+fn test_oversized_frame() {
+    let message = Message::error("server".to_string(), "x".to_string());
+    let encoded = "x".repeat(MAX_OUTBOUND_FRAME_BYTES + 1);
+    assert!(matches!(
+        validate_outbound(&message, &encoded),
+        Err(SchemaViolation::TooLarge(_, MAX_OUTBOUND_FRAME_BYTES))
+    ));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Outbound protocol violation")]
+fn test_violation_panics_in_debug() {
+    report_violation(&SchemaViolation::MissingField(MessageType::Ack, "document_id"));
+}